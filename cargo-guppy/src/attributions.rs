@@ -0,0 +1,327 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::core::{BaseFilterOptions, Kind};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::{
+    cargo::{CargoOptions, CargoSet},
+    DependencyDirection, PackageMetadata,
+};
+use guppy_cmdlib::{
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+};
+use log::warn;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+};
+
+#[derive(Debug, Parser)]
+pub struct AttributionsOptions {
+    #[clap(flatten)]
+    pf: PackagesAndFeatures,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(flatten)]
+    base_filter_opts: BaseFilterOptions,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(long)]
+    /// Also include packages built on the host platform, such as build dependencies and proc macros
+    include_host: bool,
+
+    #[clap(long)]
+    /// Directory with vendored sources, as created by `cargo vendor`
+    ///
+    /// Non-local packages are looked up in this directory first.
+    vendor_dir: Option<Utf8PathBuf>,
+
+    #[clap(long)]
+    /// Directory with templates to customize the output
+    ///
+    /// Recognized templates are `header.txt`, `license.txt`, `package.txt`, `file.txt` and
+    /// `footer.txt`. Missing templates use the default format.
+    template_dir: Option<Utf8PathBuf>,
+
+    #[clap(long, short)]
+    /// Write attributions to this file (default: stdout)
+    output: Option<Utf8PathBuf>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl AttributionsOptions {
+    pub fn exec(&self) -> Result<()> {
+        let target_platform = string_to_platform_spec(self.target_platform.as_deref())?;
+        let host_platform = string_to_platform_spec(self.host_platform.as_deref())?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev()?)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(target_platform)
+            .set_host_platform(host_platform)
+            .add_omitted_packages(self.base_filter_opts.omitted_package_ids(&pkg_graph));
+
+        let (initials, features_only) = self.pf.make_feature_sets(&pkg_graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+
+        let mut package_set = cargo_set.target_features().to_package_set();
+        if self.include_host {
+            package_set = package_set.union(&cargo_set.host_features().to_package_set());
+        }
+
+        let direct_deps = cargo_set
+            .target_direct_deps()
+            .union(cargo_set.host_direct_deps());
+        let templates = Templates::new(self.template_dir.as_deref())?;
+
+        // Group packages by license, sorted by license and then by name and version.
+        let mut by_license: BTreeMap<&str, Vec<PackageAttribution>> = BTreeMap::new();
+        for package in package_set.packages(DependencyDirection::Forward) {
+            let show_package = match self.base_filter_opts.kind {
+                Kind::All => true,
+                Kind::Workspace => package.in_workspace(),
+                Kind::DirectThirdParty => {
+                    !package.in_workspace()
+                        && direct_deps
+                            .contains(package.id())
+                            .expect("valid package ID")
+                }
+                Kind::ThirdParty => !package.in_workspace(),
+            };
+            if !show_package || !self.base_filter_opts.filter_expr_opts.matches(package) {
+                continue;
+            }
+
+            let attribution = PackageAttribution::new(package, self.vendor_dir.as_deref())?;
+            if attribution.files.is_empty() {
+                warn!(
+                    "no license or notice files found for {} {}",
+                    package.name(),
+                    package.version()
+                );
+            }
+            by_license
+                .entry(package.license().unwrap_or("(unknown)"))
+                .or_default()
+                .push(attribution);
+        }
+
+        let mut out = String::new();
+        out.push_str(&templates.header);
+        for (license, attributions) in &mut by_license {
+            attributions.sort_by(|a, b| {
+                (a.package.name(), a.package.version())
+                    .cmp(&(b.package.name(), b.package.version()))
+            });
+            out.push_str(&templates.render_license(license, attributions.len()));
+            for attribution in attributions.iter() {
+                out.push_str(&templates.render_package(attribution.package));
+                for (path, contents) in &attribution.files {
+                    out.push_str(&templates.render_file(path, contents));
+                }
+            }
+        }
+        out.push_str(&templates.footer);
+
+        match &self.output {
+            Some(output) => fs::write(output, out)
+                .wrap_err_with(|| format!("writing attributions to {} failed", output))?,
+            None => io::stdout().write_all(out.as_bytes())?,
+        }
+
+        Ok(())
+    }
+}
+
+/// License and notice files collected for a single package.
+struct PackageAttribution<'g> {
+    package: PackageMetadata<'g>,
+    // File name to contents.
+    files: BTreeMap<String, String>,
+}
+
+impl<'g> PackageAttribution<'g> {
+    /// File name prefixes (uppercased) that are collected as license or notice files.
+    const FILE_PREFIXES: &'static [&'static str] = &[
+        "LICENSE",
+        "LICENCE",
+        "UNLICENSE",
+        "COPYING",
+        "COPYRIGHT",
+        "NOTICE",
+    ];
+
+    fn new(package: PackageMetadata<'g>, vendor_dir: Option<&Utf8Path>) -> Result<Self> {
+        let source_dir = source_dir(package, vendor_dir);
+
+        let mut paths = BTreeSet::new();
+        if let Ok(entries) = source_dir.read_dir_utf8() {
+            for entry in entries {
+                let entry =
+                    entry.wrap_err_with(|| format!("reading directory {} failed", source_dir))?;
+                let upper = entry.file_name().to_ascii_uppercase();
+                if Self::FILE_PREFIXES
+                    .iter()
+                    .any(|prefix| upper.starts_with(prefix))
+                    && entry.path().is_file()
+                {
+                    paths.insert(entry.path().to_path_buf());
+                }
+            }
+        }
+        // The license-file field is relative to the package directory.
+        if let Some(license_file) = package.license_file() {
+            let path = source_dir.join(license_file);
+            if path.is_file() {
+                paths.insert(path);
+            }
+        }
+
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let contents =
+                    fs::read(&path).wrap_err_with(|| format!("reading {} failed", path))?;
+                let name = path
+                    .strip_prefix(&source_dir)
+                    .unwrap_or(&path)
+                    .as_str()
+                    .to_owned();
+                Ok((name, String::from_utf8_lossy(&contents).into_owned()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { package, files })
+    }
+}
+
+/// Returns the directory with the sources for this package.
+///
+/// Local packages are read from their own directories. Other packages are read from the vendor
+/// directory if one is provided and it contains the package, and from the directory Cargo
+/// downloaded them to otherwise.
+pub(crate) fn source_dir(
+    package: PackageMetadata<'_>,
+    vendor_dir: Option<&Utf8Path>,
+) -> Utf8PathBuf {
+    let manifest_dir = package
+        .manifest_path()
+        .parent()
+        .expect("manifest path always has a parent");
+    if package.source().is_local() {
+        return manifest_dir.to_path_buf();
+    }
+
+    if let Some(vendor_dir) = vendor_dir {
+        // cargo vendor uses "name-version" for all but one version of duplicated packages.
+        let candidates = [
+            vendor_dir.join(format!("{}-{}", package.name(), package.version())),
+            vendor_dir.join(package.name()),
+        ];
+        for candidate in candidates {
+            if candidate.join("Cargo.toml").is_file() {
+                return candidate;
+            }
+        }
+    }
+
+    manifest_dir.to_path_buf()
+}
+
+/// Templates used to render the attributions document.
+///
+/// Templates contain placeholders of the form `{{name}}` that are replaced while rendering.
+struct Templates {
+    header: String,
+    // Placeholders: {{license}}, {{count}}
+    license: String,
+    // Placeholders: {{name}}, {{version}}, {{license}}, {{repository}}
+    package: String,
+    // Placeholders: {{path}}, {{contents}}
+    file: String,
+    footer: String,
+}
+
+impl Templates {
+    const DEFAULT_HEADER: &'static str = "# Third-party attributions\n\n";
+    const DEFAULT_LICENSE: &'static str = "## {{license}} ({{count}} packages)\n\n";
+    const DEFAULT_PACKAGE: &'static str = "### {{name}} {{version}}\n\n";
+    const DEFAULT_FILE: &'static str = "#### {{path}}\n\n```\n{{contents}}\n```\n\n";
+    const DEFAULT_FOOTER: &'static str = "";
+
+    fn new(template_dir: Option<&Utf8Path>) -> Result<Self> {
+        let read = |name: &str, default: &str| -> Result<String> {
+            match template_dir {
+                Some(dir) => {
+                    let path = dir.join(name);
+                    if path.exists() {
+                        fs::read_to_string(&path)
+                            .wrap_err_with(|| format!("reading template {} failed", path))
+                    } else {
+                        Ok(default.to_owned())
+                    }
+                }
+                None => Ok(default.to_owned()),
+            }
+        };
+
+        Ok(Self {
+            header: read("header.txt", Self::DEFAULT_HEADER)?,
+            license: read("license.txt", Self::DEFAULT_LICENSE)?,
+            package: read("package.txt", Self::DEFAULT_PACKAGE)?,
+            file: read("file.txt", Self::DEFAULT_FILE)?,
+            footer: read("footer.txt", Self::DEFAULT_FOOTER)?,
+        })
+    }
+
+    fn render_license(&self, license: &str, count: usize) -> String {
+        render(
+            &self.license,
+            &[("license", license), ("count", &count.to_string())],
+        )
+    }
+
+    fn render_package(&self, package: PackageMetadata<'_>) -> String {
+        render(
+            &self.package,
+            &[
+                ("name", package.name()),
+                ("version", &package.version().to_string()),
+                ("license", package.license().unwrap_or("(unknown)")),
+                ("repository", package.repository().unwrap_or("")),
+            ],
+        )
+    }
+
+    fn render_file(&self, path: &str, contents: &str) -> String {
+        render(
+            &self.file,
+            &[("path", path), ("contents", contents.trim_end())],
+        )
+    }
+}
+
+fn render(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_owned(), |acc, (placeholder, value)| {
+            acc.replace(&format!("{{{{{}}}}}", placeholder), value)
+        })
+}
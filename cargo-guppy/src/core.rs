@@ -3,15 +3,24 @@
 
 //! Implementations for options shared by commands.
 
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::{ArgEnum, Parser};
 use color_eyre::eyre::{ensure, eyre, Result, WrapErr};
+use determinator::{rules::PathMatch, Determinator, Utf8Paths0};
 use guppy::{
-    graph::{DependencyDirection, DependencyReq, PackageGraph, PackageLink, PackageQuery},
-    platform::EnabledTernary,
+    graph::{
+        DependencyDirection, DependencyReq, PackageGraph, PackageIdSpec, PackageLink, PackageQuery,
+        PackageSet,
+    },
+    platform::{EnabledTernary, Platform, PlatformSpec, TargetFeatures, TargetSpec, Triple},
     PackageId,
 };
-use guppy_cmdlib::string_to_platform_spec;
-use std::collections::HashSet;
+use guppy_cmdlib::{string_to_platform_spec, EdgeKind, EdgeKindOptions, FilterExprOptions};
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read},
+};
 
 #[derive(ArgEnum, Copy, Clone, Debug)]
 pub enum Kind {
@@ -41,28 +50,120 @@ pub struct QueryOptions {
     direction: DependencyDirection,
 
     #[clap(rename_all = "screaming_snake_case")]
-    /// The root packages to start the query from
+    /// The root packages to start the query from, as names or Cargo package ID specifications
+    /// (e.g. serde@1.0.130 or https://github.com/rust-lang/crates.io-index#serde@1)
     roots: Vec<String>,
+
+    #[clap(long, value_name = "PATH", conflicts_with = "ROOTS")]
+    /// Start the query from the workspace packages that the files listed in PATH belong to, as
+    /// determined by the determinator ("-" for standard input; paths are NUL or newline
+    /// separated, and relative to the workspace root)
+    affected_by_paths: Option<Utf8PathBuf>,
 }
 
 impl QueryOptions {
     /// Constructs a `PackageQuery` based on these options.
     pub fn apply<'g>(&self, pkg_graph: &'g PackageGraph) -> Result<PackageQuery<'g>> {
-        if !self.roots.is_empty() {
-            // NOTE: The root set packages are specified by name. The tool currently
-            // does not handle multiple version of the same package as the current use
-            // cases are passing workspace members as the root set, which won't be
-            // duplicated.
-            let root_set = self.roots.iter().map(|s| s.as_str()).collect();
-            Ok(pkg_graph.query_directed(names_to_ids(pkg_graph, root_set), self.direction)?)
+        if self.has_roots() {
+            // A name without a version matches every version of the package.
+            let root_set = self.root_set(pkg_graph)?;
+            Ok(pkg_graph.query_directed(
+                root_set.package_ids(DependencyDirection::Forward),
+                self.direction,
+            )?)
         } else {
             ensure!(
                 self.direction == DependencyDirection::Forward,
-                eyre!("--query-reverse requires roots to be specified")
+                eyre!("--query-reverse requires roots or --affected-by-paths to be specified")
             );
             Ok(pkg_graph.query_workspace())
         }
     }
+
+    /// Returns the packages that a simulated Cargo build should start from.
+    ///
+    /// Cargo builds always follow dependencies forward, so reverse queries aren't supported.
+    pub fn initials<'g>(&self, pkg_graph: &'g PackageGraph) -> Result<PackageSet<'g>> {
+        ensure!(
+            self.direction == DependencyDirection::Forward,
+            eyre!("--query-reverse can't be used to simulate a Cargo build")
+        );
+        if self.has_roots() {
+            self.root_set(pkg_graph)
+        } else {
+            Ok(pkg_graph.resolve_workspace())
+        }
+    }
+
+    fn has_roots(&self) -> bool {
+        !self.roots.is_empty() || self.affected_by_paths.is_some()
+    }
+
+    fn root_set<'g>(&self, pkg_graph: &'g PackageGraph) -> Result<PackageSet<'g>> {
+        if let Some(paths_file) = &self.affected_by_paths {
+            return affected_by_paths(pkg_graph, paths_file);
+        }
+        let specs = self
+            .roots
+            .iter()
+            .map(PackageIdSpec::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(pkg_graph.resolve_pkgid_specs(&specs)?)
+    }
+}
+
+/// Returns the workspace packages that the paths listed in `paths_file` belong to.
+///
+/// Paths are attributed to packages the same way the determinator does, including any rules in
+/// the workspace's determinator configuration. If a path matches a rule that marks every package
+/// changed, or doesn't belong to any package, the entire workspace is returned.
+///
+/// Unlike the determinator, no builds are simulated, so changes to dependencies through
+/// `Cargo.lock` aren't picked up.
+fn affected_by_paths<'g>(
+    pkg_graph: &'g PackageGraph,
+    paths_file: &Utf8Path,
+) -> Result<PackageSet<'g>> {
+    let contents = if paths_file == "-" {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .wrap_err("reading paths from standard input failed")?;
+        contents
+    } else {
+        fs::read_to_string(paths_file)
+            .wrap_err_with(|| format!("reading paths from {} failed", paths_file))?
+    };
+    let paths: Vec<Utf8PathBuf> = if contents.contains('\0') {
+        Utf8Paths0::new(contents)
+            .iter()
+            .map(|path| path.to_owned())
+            .collect()
+    } else {
+        contents
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(Utf8PathBuf::from)
+            .collect()
+    };
+
+    let determinator = Determinator::new(pkg_graph, pkg_graph);
+    let mut package_ids = HashSet::new();
+    for path in &paths {
+        let path_match = determinator.match_path(path, |package_id| {
+            package_ids.insert(package_id);
+        });
+        match path_match {
+            PathMatch::RuleMatchedAll | PathMatch::NoMatches => {
+                return Ok(pkg_graph.resolve_workspace())
+            }
+            PathMatch::RuleMatched(_)
+            | PathMatch::AncestorMatched
+            | PathMatch::PathDependencyMatched
+            | PathMatch::WorkspaceExcluded => {}
+        }
+    }
+    Ok(pkg_graph.resolve_ids(package_ids)?)
 }
 
 #[derive(Debug, Parser)]
@@ -75,6 +176,9 @@ pub struct BaseFilterOptions {
     #[clap(long, short, arg_enum, default_value = "all")]
     /// Kind of crates to select
     pub kind: Kind,
+
+    #[clap(flatten)]
+    pub filter_expr_opts: FilterExprOptions,
 }
 
 impl BaseFilterOptions {
@@ -93,13 +197,8 @@ pub struct FilterOptions {
     #[clap(flatten)]
     pub base_opts: BaseFilterOptions,
 
-    #[clap(long, rename_all = "kebab-case")]
-    /// Include dev dependencies
-    pub include_dev: bool,
-
-    #[clap(long, rename_all = "kebab-case")]
-    /// Include build dependencies
-    pub include_build: bool,
+    #[clap(flatten)]
+    pub edge_kind_opts: EdgeKindOptions,
 
     #[clap(long)]
     /// Target to filter, "current", "any" or "always" [default: any]
@@ -134,19 +233,111 @@ impl FilterOptions {
         Ok(ret)
     }
 
-    /// Select normal, dev, or build dependencies as requested (--include-build, --include-dev), and
-    /// apply `pred_fn` to whatever's selected.
+    /// Construct a link filter based on the attribute options, or `None` if no attributes were
+    /// specified.
+    ///
+    /// A link passes the filter if, for the dependency kinds selected by these filter options, it
+    /// has all of the requested attributes.
+    pub fn make_attribute_filter<'g>(
+        &'g self,
+        attribute_opts: &'g LinkAttributeOptions,
+    ) -> Result<Option<impl Fn(PackageLink<'g>) -> bool + 'g>> {
+        if !attribute_opts.is_active() {
+            return Ok(None);
+        }
+
+        let platform_spec = string_to_platform_spec(self.target.as_deref())
+            .wrap_err_with(|| "target platform isn't known")?;
+        let target_cfg = match &attribute_opts.target_cfg {
+            Some(target_cfg) => Some(target_cfg_platforms(target_cfg)?),
+            None => None,
+        };
+
+        let ret = move |link| {
+            // --only-optional: the dependency isn't required for any selected kind.
+            let optional_ok = !attribute_opts.only_optional
+                || !self.eval(link, |req| {
+                    req.status().required_on(&platform_spec) != EnabledTernary::Disabled
+                });
+
+            // --only-no-default-features: default features are off for every selected kind.
+            let no_default_ok = !attribute_opts.only_no_default_features
+                || !self.eval(link, |req| {
+                    req.default_features().enabled_on(&platform_spec) != EnabledTernary::Disabled
+                });
+
+            // --target-cfg: the dependency is never unconditional, and is enabled on a platform
+            // matching the given target for at least one selected kind.
+            let target_ok = match &target_cfg {
+                Some(target_cfg) => {
+                    !self.eval(link, |req| {
+                        let status = req.status();
+                        status.required_status().is_always() || status.optional_status().is_always()
+                    }) && self.eval(link, |req| {
+                        req.status().enabled_on(target_cfg) != EnabledTernary::Disabled
+                    })
+                }
+                None => true,
+            };
+
+            optional_ok && no_default_ok && target_ok
+        };
+        Ok(Some(ret))
+    }
+
+    /// Select normal, dev, or build dependencies as requested (--edge-kind), and apply `pred_fn`
+    /// to whatever's selected.
     fn eval(
         &self,
         link: PackageLink<'_>,
         mut pred_fn: impl FnMut(DependencyReq<'_>) -> bool,
     ) -> bool {
-        pred_fn(link.normal())
-            || self.include_dev && pred_fn(link.dev())
-            || self.include_build && pred_fn(link.build())
+        let opts = &self.edge_kind_opts;
+        opts.includes(EdgeKind::Normal) && pred_fn(link.normal())
+            || opts.includes(EdgeKind::Build) && pred_fn(link.build())
+            || opts.includes(EdgeKind::Dev) && pred_fn(link.dev())
     }
 }
 
+#[derive(Debug, Parser)]
+pub struct LinkAttributeOptions {
+    #[clap(long, rename_all = "kebab-case")]
+    /// Only select packages pulled in through optional dependencies
+    pub only_optional: bool,
+
+    #[clap(long, rename_all = "kebab-case")]
+    /// Only select packages pulled in through dependencies with default features turned off
+    pub only_no_default_features: bool,
+
+    #[clap(long, rename_all = "kebab-case", name = "CFG")]
+    /// Only select packages pulled in through platform-specific dependencies that are enabled on
+    /// a platform matching this target, e.g. 'cfg(windows)'
+    pub target_cfg: Option<String>,
+}
+
+impl LinkAttributeOptions {
+    /// Returns true if any link attributes were specified.
+    pub fn is_active(&self) -> bool {
+        self.only_optional || self.only_no_default_features || self.target_cfg.is_some()
+    }
+}
+
+/// Returns the union of the platforms known to `target-spec` that the given target matches.
+fn target_cfg_platforms(target_cfg: &str) -> Result<PlatformSpec> {
+    let spec = TargetSpec::new(target_cfg.to_owned())
+        .wrap_err_with(|| format!("invalid target cfg `{}`", target_cfg))?;
+    let platforms: Vec<_> = Triple::builtins()
+        .map(|triple| Platform::from_triple(triple, TargetFeatures::Unknown))
+        .filter(|platform| spec.eval(platform) == Some(true))
+        .collect();
+    ensure!(
+        !platforms.is_empty(),
+        "target cfg `{}` doesn't match any known platform",
+        target_cfg
+    );
+    Ok(PlatformSpec::multi(platforms))
+}
+
 pub(crate) fn parse_direction(reverse: bool) -> DependencyDirection {
     if reverse {
         DependencyDirection::Reverse
@@ -0,0 +1,201 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy::{
+    graph::{PackageGraph, PackageLink, PackageMetadata},
+    PackageId,
+};
+use guppy_cmdlib::CargoMetadataOptions;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+};
+
+#[derive(Debug, Parser)]
+pub struct CyclesOptions {
+    #[clap(long)]
+    /// Only print cycles that don't go through a dev-dependency
+    normal_only: bool,
+
+    #[clap(long)]
+    /// Output the list of cycles as JSON
+    json: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl CyclesOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let cycles: Vec<_> = pkg_graph
+            .cycles()
+            .all_cycles()
+            .map(|package_ids| Cycle::new(&pkg_graph, &package_ids))
+            .filter(|cycle| !self.normal_only || !cycle.dev_only)
+            .collect();
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        if self.json {
+            serde_json::to_writer_pretty(&mut stdout, &cycles)?;
+            writeln!(stdout)?;
+        } else {
+            for (idx, cycle) in cycles.iter().enumerate() {
+                writeln!(
+                    stdout,
+                    "cycle {} ({}, {} packages):",
+                    idx + 1,
+                    if cycle.dev_only { "dev-only" } else { "normal" },
+                    cycle.packages.len()
+                )?;
+                for edge in &cycle.edges {
+                    writeln!(
+                        stdout,
+                        "  {} -> {} ({})",
+                        edge.from,
+                        edge.to,
+                        edge.kinds.join(", ")
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A strongly connected component with more than one package.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct Cycle<'g> {
+    // True if every cycle within this component goes through a dev-dependency.
+    pub(crate) dev_only: bool,
+    // In non-dev order.
+    pub(crate) packages: Vec<PackageRef<'g>>,
+    // The links between packages in this component, sorted by their endpoints.
+    edges: Vec<CycleEdge<'g>>,
+}
+
+impl<'g> Cycle<'g> {
+    pub(crate) fn new(pkg_graph: &'g PackageGraph, package_ids: &[&'g PackageId]) -> Self {
+        let packages: Vec<_> = package_ids
+            .iter()
+            .map(|package_id| pkg_graph.metadata(package_id).expect("valid package ID"))
+            .collect();
+        let members: HashSet<_> = package_ids.iter().copied().collect();
+
+        let mut links: Vec<_> = packages
+            .iter()
+            .flat_map(|package| package.direct_links())
+            .filter(|link| members.contains(link.to().id()))
+            .collect();
+        links.sort_by_key(|link| {
+            (
+                link.from().name(),
+                link.from().version(),
+                link.to().name(),
+                link.to().version(),
+            )
+        });
+
+        let dev_only = !has_cycle(package_ids, links.iter().filter(|link| !link.dev_only()));
+
+        Self {
+            dev_only,
+            packages: packages.into_iter().map(PackageRef::new).collect(),
+            edges: links.into_iter().map(CycleEdge::new).collect(),
+        }
+    }
+}
+
+/// Returns true if the given links contain a cycle.
+fn has_cycle<'a, 'g: 'a>(
+    package_ids: &[&'g PackageId],
+    links: impl Iterator<Item = &'a PackageLink<'g>>,
+) -> bool {
+    // Use Kahn's algorithm: if a topological sort can't consume every package, there's a cycle.
+    let mut in_degrees: HashMap<_, usize> = package_ids.iter().map(|id| (*id, 0)).collect();
+    let mut dependencies: HashMap<_, Vec<_>> = HashMap::new();
+    for link in links {
+        *in_degrees
+            .get_mut(link.to().id())
+            .expect("link within cycle") += 1;
+        dependencies
+            .entry(link.from().id())
+            .or_default()
+            .push(link.to().id());
+    }
+
+    let mut queue: Vec<_> = in_degrees
+        .iter()
+        .filter(|(_, in_degree)| **in_degree == 0)
+        .map(|(id, _)| *id)
+        .collect();
+    let mut visited = 0;
+    while let Some(id) = queue.pop() {
+        visited += 1;
+        for to in dependencies.get(id).into_iter().flatten() {
+            let in_degree = in_degrees.get_mut(to).expect("link within cycle");
+            *in_degree -= 1;
+            if *in_degree == 0 {
+                queue.push(to);
+            }
+        }
+    }
+
+    visited < package_ids.len()
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PackageRef<'g> {
+    name: &'g str,
+    version: String,
+}
+
+impl<'g> PackageRef<'g> {
+    fn new(package: PackageMetadata<'g>) -> Self {
+        Self {
+            name: package.name(),
+            version: package.version().to_string(),
+        }
+    }
+}
+
+impl<'g> std::fmt::Display for PackageRef<'g> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CycleEdge<'g> {
+    from: PackageRef<'g>,
+    to: PackageRef<'g>,
+    // Some of "normal", "build" and "dev".
+    kinds: Vec<&'static str>,
+}
+
+impl<'g> CycleEdge<'g> {
+    fn new(link: PackageLink<'g>) -> Self {
+        let kinds = [
+            ("normal", link.normal()),
+            ("build", link.build()),
+            ("dev", link.dev()),
+        ]
+        .iter()
+        .filter(|(_, req)| req.is_present())
+        .map(|(kind, _)| *kind)
+        .collect();
+
+        Self {
+            from: PackageRef::new(link.from()),
+            to: PackageRef::new(link.to()),
+            kinds,
+        }
+    }
+}
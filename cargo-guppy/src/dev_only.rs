@@ -0,0 +1,143 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy::graph::{DependencyDirection, DevOnlyPackages, PackageMetadata};
+use guppy_cmdlib::{CargoMetadataOptions, FilterExprOptions};
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Debug, Parser)]
+pub struct DevOnlyDepsOptions {
+    #[clap(long)]
+    /// Only print workspace packages
+    workspace_only: bool,
+
+    #[clap(long)]
+    /// Output the list of packages as JSON
+    json: bool,
+
+    #[clap(flatten)]
+    filter_expr_opts: FilterExprOptions,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl DevOnlyDepsOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let dev_only_packages = pkg_graph.dev_only_packages();
+
+        let mut packages: Vec<_> = dev_only_packages
+            .dev_only_set()
+            .packages(DependencyDirection::Forward)
+            .filter(|package| !self.workspace_only || package.in_workspace())
+            .filter(|package| self.filter_expr_opts.matches(*package))
+            .collect();
+        packages.sort_by_key(|package| (package.name(), package.version()));
+        let packages: Vec<_> = packages
+            .into_iter()
+            .map(|package| DevOnlyPackage::new(package, &dev_only_packages))
+            .collect();
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        if self.json {
+            serde_json::to_writer_pretty(&mut stdout, &packages)?;
+            writeln!(stdout)?;
+        } else {
+            for package in &packages {
+                writeln!(
+                    stdout,
+                    "{} {} ({})",
+                    package.name,
+                    package.version,
+                    package.reason.as_str()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A package that is only required by tests, benchmarks and examples.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct DevOnlyPackage<'g> {
+    name: &'g str,
+    version: String,
+    source: String,
+    in_workspace: bool,
+    reason: DevOnlyReason,
+    // Direct dependents, sorted by name and version.
+    dependents: Vec<Dependent<'g>>,
+}
+
+impl<'g> DevOnlyPackage<'g> {
+    fn new(package: PackageMetadata<'g>, dev_only_packages: &DevOnlyPackages<'g>) -> Self {
+        let mut links: Vec<_> = package.reverse_direct_links().collect();
+        links.sort_by_key(|link| (link.from().name(), link.from().version()));
+        let dependents: Vec<_> = links
+            .into_iter()
+            .map(|link| Dependent {
+                name: link.from().name(),
+                version: link.from().version().to_string(),
+                dev_only: link.dev_only(),
+            })
+            .collect();
+
+        let reason = if dev_only_packages
+            .test_only_workspace_set()
+            .contains(package.id())
+            .expect("valid package ID")
+        {
+            DevOnlyReason::TestOnlyPackage
+        } else if dependents.iter().any(|dependent| dependent.dev_only) {
+            DevOnlyReason::DevDependency
+        } else {
+            DevOnlyReason::DevOnlyDependents
+        };
+
+        Self {
+            name: package.name(),
+            version: package.version().to_string(),
+            source: package.source().to_string(),
+            in_workspace: package.in_workspace(),
+            reason,
+            dependents,
+        }
+    }
+}
+
+/// The reason a package is dev-only.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum DevOnlyReason {
+    /// This is a workspace package that only has test, benchmark or example targets.
+    TestOnlyPackage,
+    /// At least one package depends on this package through `dev-dependencies`.
+    DevDependency,
+    /// This package is only depended on by other dev-only packages.
+    DevOnlyDependents,
+}
+
+impl DevOnlyReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            DevOnlyReason::TestOnlyPackage => "test-only package",
+            DevOnlyReason::DevDependency => "dev-dependency",
+            DevOnlyReason::DevOnlyDependents => "dev-only dependents",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Dependent<'g> {
+    name: &'g str,
+    version: String,
+    dev_only: bool,
+}
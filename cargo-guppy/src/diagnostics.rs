@@ -0,0 +1,186 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use clap::ArgEnum;
+use color_eyre::eyre::Report;
+use guppy::{Error as GuppyError, PackageId};
+use serde::Serialize;
+use std::{error, fmt, io};
+
+/// The exit code for a command that failed with an error.
+pub const EXIT_ERROR: i32 = 1;
+
+/// The exit code for a command that ran to completion, but found a non-empty diff or a violation
+/// of a check.
+///
+/// clap also uses 2 for invalid command-line arguments by default, so those must be reported with
+/// [`EXIT_ERROR`] instead.
+pub const EXIT_VIOLATION: i32 = 2;
+
+/// Returns the exit code for a command that failed with this error.
+///
+/// This is [`EXIT_VIOLATION`] if the error is a [`Violation`], and [`EXIT_ERROR`] otherwise.
+pub fn exit_code(report: &Report) -> i32 {
+    if report.downcast_ref::<Violation>().is_some() {
+        EXIT_VIOLATION
+    } else {
+        EXIT_ERROR
+    }
+}
+
+/// An error returned by commands that ran to completion, but found a non-empty diff or a violation
+/// of a check, such as a denied lint.
+///
+/// These are distinguished from other errors so that scripts can tell the two apart through the
+/// exit code.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    message: String,
+}
+
+impl Violation {
+    /// Creates a new `Violation` with the given message.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for Violation {}
+
+/// The format in which errors are reported.
+#[derive(ArgEnum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// Human-readable output, with backtraces where available
+    Human,
+    /// A single JSON object on stderr
+    Json,
+}
+
+/// A machine-readable description of an error, for consumption by wrapper tools.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct JsonDiagnostic {
+    /// A short, stable identifier for the kind of error.
+    pub kind: &'static str,
+    /// The top-level error message.
+    pub message: String,
+    /// The chain of underlying causes, outermost first.
+    pub causes: Vec<String>,
+    /// Package IDs related to this error.
+    pub package_ids: Vec<String>,
+    /// A suggestion for how to fix this error, if one is available.
+    pub hint: Option<&'static str>,
+}
+
+impl JsonDiagnostic {
+    /// Creates a new diagnostic from an error report.
+    ///
+    /// The kind, related package IDs and hint are obtained from the first error in the chain that
+    /// is recognized.
+    pub fn new(report: &Report) -> Self {
+        let mut chain = report.chain();
+        let message = chain.next().map_or_else(String::new, |err| err.to_string());
+        let causes = chain.map(|err| err.to_string()).collect();
+
+        let mut kind = "other";
+        let mut package_ids = vec![];
+        let mut hint = None;
+        for err in report.chain() {
+            if err.downcast_ref::<Violation>().is_some() {
+                kind = "violation";
+                break;
+            }
+            if let Some(err) = err.downcast_ref::<GuppyError>() {
+                let (guppy_kind, guppy_hint) = guppy_error_kind(err);
+                kind = guppy_kind;
+                hint = guppy_hint;
+                package_ids = guppy_error_package_ids(err)
+                    .map(|package_id| package_id.repr().to_owned())
+                    .collect();
+                break;
+            }
+            if err.downcast_ref::<io::Error>().is_some() {
+                kind = "io";
+                break;
+            }
+        }
+
+        Self {
+            kind,
+            message,
+            causes,
+            package_ids,
+            hint,
+        }
+    }
+
+    /// Serializes this diagnostic as a single line of JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serializing a diagnostic never fails")
+    }
+}
+
+fn guppy_error_kind(err: &GuppyError) -> (&'static str, Option<&'static str>) {
+    match err {
+        GuppyError::CommandError(_) => (
+            "cargo-metadata-failed",
+            Some("check that `cargo metadata` succeeds in this workspace"),
+        ),
+        GuppyError::MetadataParseError(_) => (
+            "metadata-parse",
+            Some("check that the input is the JSON output of `cargo metadata`"),
+        ),
+        GuppyError::MetadataSerializeError(_) => ("metadata-serialize", None),
+        GuppyError::PackageGraphConstructError(_) => ("package-graph-construct", None),
+        GuppyError::UnknownPackageId(_) => ("unknown-package-id", None),
+        GuppyError::UnknownFeatureId(_, _) => (
+            "unknown-feature-id",
+            Some("check that the feature is declared in the package's Cargo.toml"),
+        ),
+        GuppyError::UnknownWorkspacePath(_) => (
+            "unknown-workspace-path",
+            Some("paths must be relative to the workspace root"),
+        ),
+        GuppyError::UnknownWorkspaceName(_) => (
+            "unknown-workspace-name",
+            Some("check that the package is a member of this workspace"),
+        ),
+        GuppyError::TargetSpecError(_, _) => (
+            "target-spec",
+            Some("check that the platform is a valid target triple"),
+        ),
+        GuppyError::UnknownBuildTarget(_, _) => ("unknown-build-target", None),
+        GuppyError::RequiredFeaturesMissing { .. } => (
+            "required-features-missing",
+            Some("enable the required features, or select a different build target"),
+        ),
+        GuppyError::MetadataSectionError { .. } => (
+            "metadata-section",
+            Some("check that the section has the expected format"),
+        ),
+        GuppyError::UnknownSummaryId(_)
+        | GuppyError::UnknownPackageSetSummary { .. }
+        | GuppyError::UnknownRegistryName { .. } => ("unknown-summary", None),
+        _ => ("guppy", None),
+    }
+}
+
+fn guppy_error_package_ids(err: &GuppyError) -> impl Iterator<Item = &PackageId> {
+    let package_id = match err {
+        GuppyError::UnknownPackageId(package_id)
+        | GuppyError::UnknownFeatureId(package_id, _)
+        | GuppyError::UnknownBuildTarget(package_id, _)
+        | GuppyError::RequiredFeaturesMissing { package_id, .. }
+        | GuppyError::MetadataSectionError { package_id, .. } => Some(package_id),
+        _ => None,
+    };
+    package_id.into_iter()
+}
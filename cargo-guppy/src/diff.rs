@@ -1,9 +1,19 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use guppy::{graph::PackageMetadata, PackageId};
+use crate::output::Styles;
+use camino::Utf8Path;
+use guppy::{
+    graph::{BuildTargetId, BuildTargetKind, PackageMetadata, PackageSource},
+    PackageId, Version,
+};
+use owo_colors::OwoColorize;
 use serde::{ser::SerializeStruct, Serialize, Serializer};
-use std::{collections::HashMap, ops::Deref};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    ops::Deref,
+};
 
 #[derive(Debug, Default)]
 pub struct DiffOptions;
@@ -104,14 +114,177 @@ impl DiffOptions {
         let mut added = added.into_iter().map(|x| x.1).collect::<Vec<_>>();
         added.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
 
+        // Compare features for packages that are present in both, as well as updated packages.
+        let old: HashMap<&PackageId, &PackageMetadata> =
+            old_packages.iter().map(|p| (p.id(), p)).collect();
+        let mut features_changed = new_packages
+            .iter()
+            .filter_map(|new_package| {
+                old.get(new_package.id())
+                    .and_then(|old_package| FeatureChange::new(old_package, new_package))
+            })
+            .chain(updated.iter().filter_map(|(old_package, new_package)| {
+                FeatureChange::new(old_package, new_package)
+            }))
+            .collect::<Vec<_>>();
+        features_changed.sort_by(|a, b| {
+            (a.name, &a.new_version, &a.old_version).cmp(&(b.name, &b.new_version, &b.old_version))
+        });
+
+        // Local packages have different package IDs if the workspace is checked out in different
+        // directories, e.g. while diffing two Git revisions. Don't report them as updated if
+        // nothing about them other than their absolute paths changed.
+        updated.retain(|(old_package, new_package)| {
+            !(old_package.source().is_local() && LocalContents::same(old_package, new_package))
+        });
+
+        // Dependency edges are compared by name, so version changes are reported as updates above
+        // rather than as removed and added edges.
+        let old_edges = Edge::all(old_packages);
+        let new_edges = Edge::all(new_packages);
+        let edges_added = new_edges.difference(&old_edges).copied().collect();
+        let edges_removed = old_edges.difference(&new_edges).copied().collect();
+
         Diff {
             updated,
             removed,
             added,
+            features_changed,
+            edges_added,
+            edges_removed,
         }
     }
 }
 
+/// Changes to the features declared by a package.
+#[derive(Clone, Debug, Serialize)]
+struct FeatureChange<'a> {
+    name: &'a str,
+    old_version: String,
+    new_version: String,
+    added: Vec<&'a str>,
+    removed: Vec<&'a str>,
+}
+
+impl<'a> FeatureChange<'a> {
+    fn new(old_package: &PackageMetadata<'a>, new_package: &PackageMetadata<'a>) -> Option<Self> {
+        let old_features: BTreeSet<_> = old_package.named_features().collect();
+        let new_features: BTreeSet<_> = new_package.named_features().collect();
+        if old_features == new_features {
+            return None;
+        }
+
+        Some(Self {
+            name: new_package.name(),
+            old_version: old_package.version().to_string(),
+            new_version: new_package.version().to_string(),
+            added: new_features.difference(&old_features).copied().collect(),
+            removed: old_features.difference(&new_features).copied().collect(),
+        })
+    }
+}
+
+/// The parts of a local package's metadata that don't depend on where the workspace is checked
+/// out.
+#[derive(Debug, Eq, PartialEq)]
+struct LocalContents<'a> {
+    source: PackageSource<'a>,
+    version: &'a Version,
+    edition: &'a str,
+    links: Option<&'a str>,
+    rust_version: Option<&'a Version>,
+    features: BTreeSet<&'a str>,
+    // Dependency name, package name, version requirement, and whether it's a normal, build or
+    // dev dependency.
+    deps: BTreeSet<(&'a str, &'a str, String, [bool; 3])>,
+    // Build targets, with source paths relative to the package directory.
+    targets: BTreeSet<(BuildTargetId<'a>, BuildTargetKind<'a>, Option<&'a Utf8Path>)>,
+}
+
+impl<'a> LocalContents<'a> {
+    fn new(package: &PackageMetadata<'a>) -> Self {
+        let package_dir = package.manifest_path().parent();
+        Self {
+            source: package.source(),
+            version: package.version(),
+            edition: package.edition(),
+            links: package.links(),
+            rust_version: package.minimum_rust_version(),
+            features: package.named_features().collect(),
+            deps: package
+                .direct_links()
+                .map(|link| {
+                    (
+                        link.dep_name(),
+                        link.to().name(),
+                        link.version_req().to_string(),
+                        [
+                            link.normal().is_present(),
+                            link.build().is_present(),
+                            link.dev().is_present(),
+                        ],
+                    )
+                })
+                .collect(),
+            targets: package
+                .build_targets()
+                .map(|target| {
+                    let path = package_dir.and_then(|dir| target.path().strip_prefix(dir).ok());
+                    (target.id(), target.kind(), path)
+                })
+                .collect(),
+        }
+    }
+
+    fn same(old_package: &PackageMetadata<'a>, new_package: &PackageMetadata<'a>) -> bool {
+        Self::new(old_package) == Self::new(new_package)
+    }
+}
+
+/// A dependency edge between two packages, identified by name and version.
+///
+/// Versions are included so that edges to different versions of the same package are kept apart.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+struct Edge<'a> {
+    from: EdgeEnd<'a>,
+    to: EdgeEnd<'a>,
+}
+
+impl<'a> Edge<'a> {
+    fn all(packages: &'a [PackageMetadata<'a>]) -> BTreeSet<Self> {
+        packages
+            .iter()
+            .flat_map(|package| package.direct_links())
+            .map(|link| Edge {
+                from: EdgeEnd::new(link.from()),
+                to: EdgeEnd::new(link.to()),
+            })
+            .collect()
+    }
+}
+
+/// One end of an [`Edge`].
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+struct EdgeEnd<'a> {
+    name: &'a str,
+    version: &'a Version,
+}
+
+impl<'a> EdgeEnd<'a> {
+    fn new(package: PackageMetadata<'a>) -> Self {
+        Self {
+            name: package.name(),
+            version: package.version(),
+        }
+    }
+}
+
+impl<'a> fmt::Display for EdgeEnd<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Package<'a>(pub &'a PackageMetadata<'a>);
 
@@ -141,18 +314,44 @@ pub struct Diff<'a> {
     updated: Vec<(Package<'a>, Package<'a>)>,
     removed: Vec<(Package<'a>, Option<Vec<Package<'a>>>)>,
     added: Vec<(Package<'a>, Option<Vec<Package<'a>>>)>,
+    features_changed: Vec<FeatureChange<'a>>,
+    edges_added: Vec<Edge<'a>>,
+    edges_removed: Vec<Edge<'a>>,
+}
+
+impl<'a> Diff<'a> {
+    /// Returns true if there are any changes in this diff.
+    pub fn is_changed(&self) -> bool {
+        !(self.updated.is_empty()
+            && self.removed.is_empty()
+            && self.added.is_empty()
+            && self.features_changed.is_empty()
+            && self.edges_added.is_empty()
+            && self.edges_removed.is_empty())
+    }
+
+    /// Returns a value that displays this diff using the given styles.
+    pub fn display<'b>(&'b self, styles: &'b Styles) -> DiffDisplay<'a, 'b> {
+        DiffDisplay { diff: self, styles }
+    }
 }
 
-impl<'a> ::std::fmt::Display for Diff<'a> {
-    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+pub struct DiffDisplay<'a, 'b> {
+    diff: &'b Diff<'a>,
+    styles: &'b Styles,
+}
+
+impl<'a, 'b> fmt::Display for DiffDisplay<'a, 'b> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fn write_dups(
-            f: &mut ::std::fmt::Formatter<'_>,
+            f: &mut fmt::Formatter<'_>,
             dups: &Option<Vec<Package>>,
-        ) -> ::std::fmt::Result {
+            styles: &Styles,
+        ) -> fmt::Result {
             if let Some(dups) = dups {
-                write!(f, " ({}", dups[0].version())?;
+                write!(f, " ({}", dups[0].version().style(styles.package_version))?;
                 for p in &dups[1..] {
-                    write!(f, ", {}", p.version())?;
+                    write!(f, ", {}", p.version().style(styles.package_version))?;
                 }
                 write!(f, ")")?;
             }
@@ -160,37 +359,111 @@ impl<'a> ::std::fmt::Display for Diff<'a> {
             Ok(())
         }
 
-        if !self.added.is_empty() {
-            writeln!(f, "Added Packages (Duplicate versions in '()'):")?;
-            for (added, dups) in &self.added {
-                write!(f, "\t{} {}", added.name(), added.version(),)?;
+        let Self { diff, styles } = self;
 
-                write_dups(f, dups)?;
+        if !diff.added.is_empty() {
+            writeln!(
+                f,
+                "{}",
+                "Added Packages (Duplicate versions in '()'):".style(styles.heading)
+            )?;
+            for (added, dups) in &diff.added {
+                write!(
+                    f,
+                    "\t{} {}",
+                    added.name().style(styles.added),
+                    added.version().style(styles.package_version),
+                )?;
+
+                write_dups(f, dups, styles)?;
                 writeln!(f)?;
             }
             writeln!(f)?;
         }
 
-        if !self.removed.is_empty() {
-            writeln!(f, "Removed Packages (Remaining versions in '()'):")?;
-            for (removed, dups) in &self.removed {
-                write!(f, "\t{} {}", removed.name(), removed.version(),)?;
+        if !diff.removed.is_empty() {
+            writeln!(
+                f,
+                "{}",
+                "Removed Packages (Remaining versions in '()'):".style(styles.heading)
+            )?;
+            for (removed, dups) in &diff.removed {
+                write!(
+                    f,
+                    "\t{} {}",
+                    removed.name().style(styles.removed),
+                    removed.version().style(styles.package_version),
+                )?;
 
-                write_dups(f, dups)?;
+                write_dups(f, dups, styles)?;
                 writeln!(f)?;
             }
             writeln!(f)?;
         }
 
-        if !self.updated.is_empty() {
-            writeln!(f, "Updated Packages:")?;
-            for (removed, added) in &self.updated {
+        if !diff.updated.is_empty() {
+            writeln!(f, "{}", "Updated Packages:".style(styles.heading))?;
+            for (removed, added) in &diff.updated {
                 writeln!(
                     f,
                     "\t{}: {} -> {}",
-                    removed.name(),
-                    removed.version(),
-                    added.version(),
+                    removed.name().style(styles.package_name),
+                    removed.version().style(styles.removed),
+                    added.version().style(styles.added),
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        if !diff.features_changed.is_empty() {
+            writeln!(f, "{}", "Changed Features:".style(styles.heading))?;
+            for change in &diff.features_changed {
+                if change.old_version == change.new_version {
+                    write!(
+                        f,
+                        "\t{} {}:",
+                        change.name.style(styles.package_name),
+                        change.new_version.style(styles.package_version)
+                    )?;
+                } else {
+                    write!(
+                        f,
+                        "\t{} {} -> {}:",
+                        change.name.style(styles.package_name),
+                        change.old_version.style(styles.removed),
+                        change.new_version.style(styles.added)
+                    )?;
+                }
+                for feature in &change.added {
+                    write!(f, " {}", format_args!("+{}", feature).style(styles.added))?;
+                }
+                for feature in &change.removed {
+                    write!(f, " {}", format_args!("-{}", feature).style(styles.removed))?;
+                }
+                writeln!(f)?;
+            }
+            writeln!(f)?;
+        }
+
+        if !diff.edges_added.is_empty() {
+            writeln!(f, "{}", "Added Dependency Edges:".style(styles.heading))?;
+            for edge in &diff.edges_added {
+                writeln!(
+                    f,
+                    "\t{}",
+                    format_args!("{} -> {}", edge.from, edge.to).style(styles.added)
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        if !diff.edges_removed.is_empty() {
+            writeln!(f, "{}", "Removed Dependency Edges:".style(styles.heading))?;
+            for edge in &diff.edges_removed {
+                writeln!(
+                    f,
+                    "\t{}",
+                    format_args!("{} -> {}", edge.from, edge.to).style(styles.removed)
                 )?;
             }
             writeln!(f)?;
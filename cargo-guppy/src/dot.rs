@@ -0,0 +1,172 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Customizing `.dot` output for selected packages.
+
+use clap::Parser;
+use guppy::graph::{
+    DotAttrs, DotWrite, PackageDotVisitor, PackageLink, PackageMetadata, PackageSet,
+};
+use std::fmt;
+
+/// Options for the `.dot` output of `select` and `resolve-cargo`.
+#[derive(Debug, Parser)]
+pub struct DotOptions {
+    #[clap(long)]
+    /// Include package versions in .dot node labels
+    dot_versions: bool,
+
+    #[clap(long)]
+    /// Color workspace and third-party packages differently in .dot output
+    dot_color: bool,
+
+    #[clap(long)]
+    /// Label .dot edges with the kinds of dependency (normal, build, dev)
+    dot_edge_kinds: bool,
+
+    #[clap(long)]
+    /// Cluster workspace packages by the directory they're in within the workspace
+    dot_cluster: bool,
+}
+
+impl DotOptions {
+    /// Returns a visitor that formats packages and links according to these options.
+    pub fn visitor(&self) -> DotOptionsVisitor<'_> {
+        DotOptionsVisitor { options: self }
+    }
+
+    /// Returns a visitor that formats packages and links according to these options, and also
+    /// draws packages differently based on the platforms they're built on.
+    pub fn cargo_set_visitor<'g>(
+        &self,
+        target_packages: PackageSet<'g>,
+        host_packages: PackageSet<'g>,
+    ) -> CargoSetDotVisitor<'_, 'g> {
+        CargoSetDotVisitor {
+            inner: self.visitor(),
+            target_packages,
+            host_packages,
+        }
+    }
+}
+
+/// A `.dot` visitor configured through `DotOptions`.
+#[derive(Clone, Copy, Debug)]
+pub struct DotOptionsVisitor<'a> {
+    options: &'a DotOptions,
+}
+
+impl<'a> PackageDotVisitor for DotOptionsVisitor<'a> {
+    fn visit_package(&self, package: PackageMetadata<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        if self.options.dot_versions {
+            write!(f, "{} {}", package.name(), package.version())
+        } else {
+            write!(f, "{}", package.name())
+        }
+    }
+
+    fn visit_link(&self, link: PackageLink<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        if !self.options.dot_edge_kinds {
+            return Ok(());
+        }
+        let kinds: Vec<_> = [
+            ("normal", link.normal()),
+            ("build", link.build()),
+            ("dev", link.dev()),
+        ]
+        .iter()
+        .filter(|(_, req)| req.is_present())
+        .map(|(kind, _)| *kind)
+        .collect();
+        write!(f, "{}", kinds.join(", "))
+    }
+
+    fn visit_package_attrs(
+        &self,
+        package: PackageMetadata<'_>,
+        attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        if !self.options.dot_color {
+            return Ok(());
+        }
+        let fill_color = if package.in_workspace() {
+            "lightblue"
+        } else {
+            "lightgray"
+        };
+        attrs.attr("style", "filled")?;
+        attrs.attr("fillcolor", fill_color)
+    }
+
+    fn visit_link_attrs(&self, link: PackageLink<'_>, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        // Links that only matter for tests, benchmarks and examples are drawn dashed.
+        if self.options.dot_edge_kinds && link.dev_only() {
+            attrs.attr("style", "dashed")?;
+        }
+        Ok(())
+    }
+
+    fn package_cluster(&self, package: PackageMetadata<'_>) -> Option<String> {
+        if !self.options.dot_cluster {
+            return None;
+        }
+        let parent = package.source().workspace_path()?.parent()?;
+        if parent.as_str().is_empty() {
+            // The package is at the root of the workspace.
+            Some(".".to_owned())
+        } else {
+            Some(parent.to_string())
+        }
+    }
+}
+
+/// A `.dot` visitor for packages resolved by Cargo.
+///
+/// Packages built on the target platform are drawn as ellipses, packages built on the host platform
+/// are drawn as boxes, and packages built on both are drawn as octagons.
+#[derive(Clone, Debug)]
+pub struct CargoSetDotVisitor<'a, 'g> {
+    inner: DotOptionsVisitor<'a>,
+    target_packages: PackageSet<'g>,
+    host_packages: PackageSet<'g>,
+}
+
+impl<'a, 'g> PackageDotVisitor for CargoSetDotVisitor<'a, 'g> {
+    fn visit_package(&self, package: PackageMetadata<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        self.inner.visit_package(package, f)
+    }
+
+    fn visit_link(&self, link: PackageLink<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        self.inner.visit_link(link, f)
+    }
+
+    fn visit_package_attrs(
+        &self,
+        package: PackageMetadata<'_>,
+        attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        self.inner.visit_package_attrs(package, attrs)?;
+        let on_target = self
+            .target_packages
+            .contains(package.id())
+            .expect("valid package ID");
+        let on_host = self
+            .host_packages
+            .contains(package.id())
+            .expect("valid package ID");
+        let shape = match (on_target, on_host) {
+            (true, true) => "octagon",
+            (false, true) => "box",
+            (true, false) | (false, false) => "ellipse",
+        };
+        attrs.attr("shape", shape)
+    }
+
+    fn visit_link_attrs(&self, link: PackageLink<'_>, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        self.inner.visit_link_attrs(link, attrs)
+    }
+
+    fn package_cluster(&self, package: PackageMetadata<'_>) -> Option<String> {
+        self.inner.package_cluster(package)
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::output::OutputContext;
+use clap::Parser;
+use color_eyre::eyre::{bail, Result};
+use guppy::graph::{
+    feature::{ConditionalLink, FeatureDotVisitor, FeatureId, FeatureLabel, FeatureMetadata},
+    DependencyDirection, DotAttrs, DotWrite, PackageIdSpec,
+};
+use guppy_cmdlib::CargoMetadataOptions;
+use itertools::Itertools;
+use owo_colors::OwoColorize;
+use std::fmt;
+
+#[derive(Debug, Parser)]
+pub struct FeatureClosureOptions {
+    /// Feature to start from, as `package/feature` or `package/dep:name`
+    ///
+    /// The package may be a package ID specification such as `serde@1.0.130`.
+    #[clap(value_name = "FEATURE")]
+    feature: String,
+
+    #[clap(long)]
+    /// Output the features and the links between them in .dot format
+    output_dot: bool,
+
+    #[clap(long)]
+    /// Include package versions in .dot node labels
+    dot_versions: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl FeatureClosureOptions {
+    pub fn exec(&self, output: &OutputContext) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let (spec, feature) = match self.feature.rsplit_once('/') {
+            Some((spec, feature)) if !spec.is_empty() && !feature.is_empty() => (spec, feature),
+            _ => bail!(
+                "invalid feature `{}`: expected `package/feature` or `package/dep:name`",
+                self.feature
+            ),
+        };
+        let label = match feature.strip_prefix("dep:") {
+            Some(dep_name) => FeatureLabel::OptionalDependency(dep_name),
+            None => FeatureLabel::Named(feature),
+        };
+
+        let spec = PackageIdSpec::parse(spec)?;
+        let packages: Vec<_> = pkg_graph
+            .resolve_pkgid_specs([&spec])?
+            .packages(DependencyDirection::Forward)
+            .collect();
+        let package = match packages.as_slice() {
+            [package] => *package,
+            _ => bail!(
+                "`{}` matches multiple packages, specify a version with `{}@<version>`: {}",
+                spec,
+                spec.name(),
+                packages
+                    .iter()
+                    .map(|package| format!("{} {}", package.name(), package.version()))
+                    .join(", ")
+            ),
+        };
+
+        let feature_graph = pkg_graph.feature_graph();
+        let feature_id = FeatureId::new(package.id(), label);
+        if !feature_graph.contains(feature_id) {
+            bail!(
+                "{} {} has no feature `{}`",
+                package.name(),
+                package.version(),
+                feature
+            );
+        }
+
+        if self.output_dot {
+            let visitor = ClosureDotVisitor {
+                versions: self.dot_versions,
+            };
+            print!(
+                "{}",
+                feature_graph.feature_closure_dot(feature_id, visitor)?
+            );
+            return Ok(());
+        }
+
+        let styles = &output.styles;
+        let closure = feature_graph.feature_closure(feature_id)?;
+        for feature_list in closure.packages_with_features(DependencyDirection::Forward) {
+            let package = feature_list.package();
+            println!(
+                "{} {}: {}",
+                package.name().style(styles.package_name),
+                package.version().style(styles.package_version),
+                feature_list.display_features(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A `.dot` visitor for the features in a closure.
+///
+/// Base features are drawn as boxes, and links between packages are labeled with the name of the
+/// dependency.
+#[derive(Clone, Copy, Debug)]
+struct ClosureDotVisitor {
+    versions: bool,
+}
+
+impl FeatureDotVisitor for ClosureDotVisitor {
+    fn visit_feature(&self, feature: FeatureMetadata<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        let package = feature.package();
+        if self.versions {
+            write!(f, "{} {}", package.name(), package.version())?;
+        } else {
+            write!(f, "{}", package.name())?;
+        }
+        match feature.label() {
+            FeatureLabel::Base => Ok(()),
+            label => write!(f, "/{}", label),
+        }
+    }
+
+    fn visit_link(
+        &self,
+        _from: FeatureMetadata<'_>,
+        _to: FeatureMetadata<'_>,
+        conditional_link: Option<ConditionalLink<'_>>,
+        f: &mut DotWrite<'_, '_>,
+    ) -> fmt::Result {
+        match conditional_link {
+            Some(link) => write!(f, "{}", link.package_link().dep_name()),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_feature_attrs(
+        &self,
+        feature: FeatureMetadata<'_>,
+        attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        if feature.label() == FeatureLabel::Base {
+            attrs.attr("shape", "box")?;
+        }
+        Ok(())
+    }
+}
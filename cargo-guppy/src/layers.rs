@@ -0,0 +1,83 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{diagnostics::Violation, output::OutputContext};
+use camino::Utf8PathBuf;
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::LayersConfig;
+use guppy_cmdlib::CargoMetadataOptions;
+use owo_colors::OwoColorize;
+use std::fs;
+
+#[derive(Debug, Parser)]
+pub struct LayersOptions {
+    #[clap(subcommand)]
+    cmd: LayersCommand,
+}
+
+#[derive(Debug, Parser)]
+enum LayersCommand {
+    #[clap(name = "check")]
+    /// Check that dependencies between workspace members follow the rules in a layers file
+    ///
+    /// Exits with a non-zero code if any dependency isn't allowed.
+    Check(LayersCheckOptions),
+}
+
+#[derive(Debug, Parser)]
+struct LayersCheckOptions {
+    /// Path to a TOML file with layers and the layers each of them may depend on
+    #[clap(long = "config", value_name = "PATH")]
+    config: Utf8PathBuf,
+
+    /// Also check dev-dependencies, overriding `include-dev` in the config
+    #[clap(long)]
+    include_dev: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl LayersOptions {
+    pub fn exec(&self, output: &OutputContext) -> Result<()> {
+        match &self.cmd {
+            LayersCommand::Check(options) => options.exec(output),
+        }
+    }
+}
+
+impl LayersCheckOptions {
+    fn exec(&self, output: &OutputContext) -> Result<()> {
+        let contents = fs::read_to_string(&self.config)
+            .wrap_err_with(|| format!("reading layers file {} failed", self.config))?;
+        let mut config: LayersConfig = toml::from_str(&contents)
+            .wrap_err_with(|| format!("parsing layers file {} failed", self.config))?;
+        config.include_dev |= self.include_dev;
+
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let layers = pkg_graph.layers(&config)?;
+        let violations = layers.violations();
+
+        let styles = &output.styles;
+        for violation in &violations {
+            let (from, to) = violation.link().endpoints();
+            println!(
+                "{} ({}) depends on {} ({})",
+                from.name().style(styles.package_name),
+                violation.from_layer().style(styles.heading),
+                to.name().style(styles.package_name),
+                violation.to_layer().style(styles.removed),
+            );
+        }
+
+        if !violations.is_empty() {
+            return Err(Violation::new(format!(
+                "{} dependencies between layers aren't allowed",
+                violations.len()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
@@ -20,72 +20,216 @@
 //!
 //! The list of commands is not currently stable and is subject to change.
 //!
+//! All commands accept `--error-format json`, which causes failures to be reported on stderr as a
+//! single line of JSON with the fields `kind`, `message`, `causes`, `package-ids` and `hint`.
+//!
+//! Commands exit with one of these codes:
+//! * 0: the command succeeded.
+//! * 1: the command failed with an error, including if the command-line arguments were invalid.
+//! * 2: the command ran to completion, but found a non-empty diff or a violation of a check, such
+//!   as a denied lint or a policy violation. With `--error-format json`, the `kind` of these
+//!   failures is `violation`. `diff` only exits with this code if `--exit-code` is passed in.
+//!
+//! All commands also accept `--color auto|always|never`, `--quiet` to only log errors, and
+//! `--verbose` to log debug messages such as the number of packages processed. Logs are written to
+//! stderr and can be further filtered with the `RUST_LOG` environment variable.
+//!
 //! ## Query commands
 //!
 //! * `select`: query packages and their transitive dependencies
 //! * `resolve-cargo`: query packages and features as would be built by cargo
-//! * `subtree-size`: print dependencies along with their unique subtree size
-//! * `dups`: print duplicate packages
+//! * `subtree-size`: print dependencies along with their unique subtree size, optionally weighted
+//!   by crate size, lines of code or a TOML file of weights to find build cost hotspots
+//! * `dups`: print duplicate packages, optionally failing with `--fail-on N` if a package has `N`
+//!   or more versions and skipping packages accepted by an `--allowlist` file
+//! * `dev-only-deps`: print packages that are only required by tests, benchmarks and examples
+//! * `cycles`: print dependency cycles and the links that form them
+//! * `metadata-table`: print a `[package.metadata]` section for every workspace member, as JSON
+//! * `unused-features`: print features of workspace members that aren't enabled by workspace builds
+//! * `feature-closure`: print the features transitively enabled by a feature, such as
+//!   `serde/derive`, optionally as a `.dot` graph with `--output-dot`
+//!
+//! Commands that print lists of packages, along with `attributions` and `sbom`, accept
+//! `--filter-expr` to only include packages matching an expression, e.g.
+//! `--filter-expr 'workspace & !proc_macro & licensed("MIT")'`.
+//!
+//! ## Workspace checks
+//!
+//! * `lint`: check the workspace for duplicate dependency versions, mismatched path dependency
+//!   versions, missing licenses, dependency cycles, unpublishable dependencies and dependencies
+//!   declared several times with conflicting requirements. Pass in lint names to only run those,
+//!   e.g. `cargo guppy lint requirement-conflicts`
+//! * `outdated-paths`: find path dependencies of workspace members whose version requirements
+//!   don't match the current version of the dependency, optionally updating them with `--fix`
+//! * `msrv`: find third-party dependencies that declare a `rust-version` newer than the
+//!   workspace's minimum supported Rust version, e.g. `--max 1.70`
+//! * `layers check`: check that workspace members only depend on members of the layers they're
+//!   allowed to, as declared in a TOML file passed in with `--config`
 //!
 //! ## Diff commands
 //!
-//! * `diff`: perform a diff of two `cargo metadata` JSON outputs
-//! * `diff-summaries`: perform a diff of two [summaries](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy-summaries)
+//! * `diff`: perform a diff of two `cargo metadata` JSON outputs, or of the workspace at two Git
+//!   revisions with `--from-rev` and `--to-rev`
+//! * `diff-summaries`: perform a diff of two [summaries](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy-summaries), optionally checked against a policy of acceptable changes
+//!
+//! ## Release artifacts
+//!
+//! * `attributions`: collect license and notice files for packages that would be built
+//! * `sbom`: generate a software bill of materials in the CycloneDX or SPDX format
 //!
 //! ## Workspace manipulations
 //!
 //! * `mv`: move crates to a new location in a workspace, updating paths along the way
 
+mod attributions;
 mod core;
+mod cycles;
+mod dev_only;
+mod diagnostics;
 mod diff;
+mod dot;
+mod feature_closure;
+mod layers;
+mod lint;
+mod metadata_table;
+mod msrv;
 mod mv;
+mod outdated_paths;
+mod output;
+mod sbom;
+mod tree;
+mod unused_features;
+mod weights;
+mod worktree;
+
+pub use crate::{
+    attributions::*, core::*, cycles::*, dev_only::*, diagnostics::*, dot::*, feature_closure::*,
+    layers::*, lint::*, metadata_table::*, msrv::*, mv::*, outdated_paths::*, output::*, sbom::*,
+    tree::*, unused_features::*, weights::*,
+};
 
-pub use crate::{core::*, mv::*};
-
+use crate::weights::Weights;
 use camino::Utf8PathBuf;
 use clap::{ArgEnum, Parser};
 use color_eyre::eyre::{bail, Result, WrapErr};
 use guppy::{
     graph::{
         cargo::{CargoOptions, CargoSet},
-        feature::{FeatureSet, StandardFeatures},
-        summaries::Summary,
-        DependencyDirection, DotWrite, PackageDotVisitor, PackageGraph, PackageLink,
-        PackageMetadata,
+        feature::{FeatureId, FeatureLabel, FeatureList, FeatureTraceStep, StandardFeatures},
+        summaries::{policy::DiffPolicy, Summary, SummaryVersion},
+        DependencyDirection, PackageGraph, PackageMetadata, PackageSet,
     },
+    platform::PlatformSpec,
     PackageId,
 };
 use guppy_cmdlib::{
-    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, CargoResolverVersionCmd,
+    InitialsPlatformCmd, PackagesAndFeatures,
 };
+use log::debug;
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize, Serializer};
 use std::{
     borrow::Cow,
     cmp,
     collections::{HashMap, HashSet},
-    fmt, fs,
+    fs,
     io::Write,
     iter,
     path::PathBuf,
 };
 
-pub fn cmd_diff(json: bool, old: &str, new: &str) -> Result<()> {
-    let old_json = fs::read_to_string(old)?;
-    let new_json = fs::read_to_string(new)?;
+#[derive(Debug, Parser)]
+pub struct CmdDiffOptions {
+    /// Output the diff as JSON
+    #[clap(long)]
+    json: bool,
+
+    /// Exit with code 2 if the diff is non-empty, like `git diff --exit-code`
+    #[clap(long)]
+    exit_code: bool,
+
+    /// The old `cargo metadata` JSON file
+    #[clap(
+        name = "OLD",
+        required_unless_present = "from-rev",
+        conflicts_with = "from-rev"
+    )]
+    old: Option<Utf8PathBuf>,
+
+    /// The new `cargo metadata` JSON file
+    #[clap(
+        name = "NEW",
+        required_unless_present = "from-rev",
+        conflicts_with = "from-rev"
+    )]
+    new: Option<Utf8PathBuf>,
+
+    /// Generate the old metadata from the workspace at this Git revision
+    #[clap(long, value_name = "REV", requires = "to-rev")]
+    from_rev: Option<String>,
+
+    /// Generate the new metadata from the workspace at this Git revision
+    #[clap(long, value_name = "REV", requires = "from-rev")]
+    to_rev: Option<String>,
+
+    /// Path to Cargo.toml, with --from-rev and --to-rev
+    #[clap(long, requires = "from-rev")]
+    manifest_path: Option<Utf8PathBuf>,
+}
+
+impl CmdDiffOptions {
+    /// Returns the old and new package graphs to diff.
+    fn graphs(&self) -> Result<(PackageGraph, PackageGraph)> {
+        match (&self.from_rev, &self.to_rev) {
+            (Some(from_rev), Some(to_rev)) => {
+                let manifest_path = self
+                    .manifest_path
+                    .as_deref()
+                    .unwrap_or_else(|| "Cargo.toml".as_ref());
+                let manifest_path = manifest_path
+                    .canonicalize_utf8()
+                    .wrap_err_with(|| format!("error canonicalizing {}", manifest_path))?;
+                Ok((
+                    worktree::graph_at_rev(&manifest_path, from_rev)?,
+                    worktree::graph_at_rev(&manifest_path, to_rev)?,
+                ))
+            }
+            _ => {
+                let read_graph = |path: &Option<Utf8PathBuf>| -> Result<PackageGraph> {
+                    let path = path.as_ref().expect("clap ensures paths are present");
+                    let json = fs::read_to_string(path)
+                        .wrap_err_with(|| format!("reading {} failed", path))?;
+                    Ok(PackageGraph::from_json(&json)?)
+                };
+                Ok((read_graph(&self.old)?, read_graph(&self.new)?))
+            }
+        }
+    }
+}
 
-    let old_graph = PackageGraph::from_json(&old_json)?;
-    let new_graph = PackageGraph::from_json(&new_json)?;
+pub fn cmd_diff(options: &CmdDiffOptions, output: &OutputContext) -> Result<()> {
+    let (old_graph, new_graph) = options.graphs()?;
 
     let old_packages: Vec<_> = old_graph.packages().collect();
     let new_packages: Vec<_> = new_graph.packages().collect();
 
+    debug!(
+        "diffing {} old packages against {} new packages",
+        old_packages.len(),
+        new_packages.len()
+    );
     let diff = diff::DiffOptions::default().diff(&old_packages, &new_packages);
 
-    if json {
+    if options.json {
         println!("{}", serde_json::to_string_pretty(&diff).unwrap());
     } else {
-        print!("{}", diff);
+        print!("{}", diff.display(&output.styles));
     }
 
+    if options.exit_code && diff.is_changed() {
+        return Err(Violation::new("non-empty diff").into());
+    }
     Ok(())
 }
 
@@ -98,6 +242,10 @@ pub struct DiffSummariesOptions {
     /// The new summary
     #[clap(name = "NEW")]
     pub new: Utf8PathBuf,
+
+    /// Only fail on changes not allowed by this policy file (TOML)
+    #[clap(long)]
+    pub policy: Option<Utf8PathBuf>,
 }
 
 impl DiffSummariesOptions {
@@ -116,9 +264,22 @@ impl DiffSummariesOptions {
 
         println!("{}", diff.report());
 
-        // TODO: different error codes for non-empty diff and failure, similar to git/hg
+        if let Some(policy_path) = &self.policy {
+            let policy = fs::read_to_string(policy_path)
+                .wrap_err_with(|| format!("reading policy {} failed", policy_path))?;
+            let policy = DiffPolicy::parse(&policy)
+                .wrap_err_with(|| format!("parsing policy {} failed", policy_path))?;
+
+            let violations = diff.apply_policy(&policy);
+            if violations.is_changed() {
+                println!("changes not allowed by policy:\n\n{}", violations.report());
+                return Err(Violation::new("diff violates policy").into());
+            }
+            return Ok(());
+        }
+
         if diff.is_changed() {
-            bail!("non-empty diff");
+            return Err(Violation::new("non-empty diff").into());
         }
         Ok(())
     }
@@ -126,6 +287,16 @@ impl DiffSummariesOptions {
 
 #[derive(Debug, Parser)]
 pub struct DupsOptions {
+    /// Fail if any package not in the allowlist has at least this many versions
+    #[clap(long, value_name = "N")]
+    fail_on: Option<usize>,
+
+    /// Duplicates to accept (TOML)
+    ///
+    /// Packages listed in this file are not printed and don't count towards `--fail-on`.
+    #[clap(long, value_name = "PATH")]
+    allowlist: Option<Utf8PathBuf>,
+
     #[clap(flatten)]
     filter_opts: FilterOptions,
 
@@ -133,9 +304,18 @@ pub struct DupsOptions {
     metadata_opts: CargoMetadataOptions,
 }
 
-pub fn cmd_dups(opts: &DupsOptions) -> Result<()> {
-    let command = opts.metadata_opts.make_command();
-    let pkg_graph = command.build_graph()?;
+pub fn cmd_dups(opts: &DupsOptions, output: &OutputContext) -> Result<()> {
+    let allowlist = match &opts.allowlist {
+        Some(allowlist_path) => {
+            let allowlist = fs::read_to_string(allowlist_path)
+                .wrap_err_with(|| format!("reading allowlist {} failed", allowlist_path))?;
+            DupsAllowlist::parse(&allowlist)
+                .wrap_err_with(|| format!("parsing allowlist {} failed", allowlist_path))?
+        }
+        None => DupsAllowlist::default(),
+    };
+
+    let pkg_graph = opts.metadata_opts.build_graph()?;
 
     let resolver = opts.filter_opts.make_resolver(&pkg_graph)?;
     let selection = pkg_graph.query_workspace();
@@ -144,23 +324,107 @@ pub fn cmd_dups(opts: &DupsOptions) -> Result<()> {
     for package in selection
         .resolve_with_fn(resolver)
         .packages(DependencyDirection::Forward)
+        .filter(|package| {
+            opts.filter_opts
+                .base_opts
+                .filter_expr_opts
+                .matches(*package)
+        })
     {
         dupe_map.entry(package.name()).or_default().push(package);
     }
 
+    let styles = &output.styles;
+    let mut dup_count = 0;
+    let mut fail_count = 0;
     for (name, dupes) in dupe_map {
         if dupes.len() <= 1 {
             continue;
         }
+        if allowlist.is_allowed(name, &dupes) {
+            debug!("{} is in the allowlist, skipping", name);
+            continue;
+        }
+        dup_count += 1;
+        if matches!(opts.fail_on, Some(fail_on) if dupes.len() >= fail_on) {
+            fail_count += 1;
+        }
 
-        let output = itertools::join(dupes.iter().map(|p| p.version()), ", ");
+        let versions = itertools::join(
+            dupes
+                .iter()
+                .map(|p| p.version().style(styles.package_version)),
+            ", ",
+        );
 
-        println!("{} ({})", name, output);
+        println!("{} ({})", name.style(styles.package_name), versions);
+    }
+    debug!("found {} packages with duplicate versions", dup_count);
+
+    if let Some(fail_on) = opts.fail_on {
+        if fail_count > 0 {
+            return Err(Violation::new(format!(
+                "{} package(s) have {} or more versions",
+                fail_count, fail_on
+            ))
+            .into());
+        }
     }
 
     Ok(())
 }
 
+/// Duplicates accepted by `cargo guppy dups`, read from TOML.
+///
+/// An example:
+///
+/// ```toml
+/// # Packages, by name, that may have any number of versions.
+/// allow = ["syn"]
+///
+/// # Packages that may have these versions, and only these versions.
+/// [[allow-versions]]
+/// name = "rand"
+/// versions = ["0.7.3", "0.8.5"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DupsAllowlist {
+    #[serde(default)]
+    allow: HashSet<String>,
+    #[serde(default)]
+    allow_versions: Vec<DupsAllowVersions>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DupsAllowVersions {
+    name: String,
+    versions: HashSet<String>,
+}
+
+impl DupsAllowlist {
+    fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Returns true if every version of this package is accepted.
+    ///
+    /// A package listed in `allow-versions` stops being accepted as soon as a version not in the
+    /// list shows up.
+    fn is_allowed(&self, name: &str, dupes: &[PackageMetadata<'_>]) -> bool {
+        if self.allow.contains(name) {
+            return true;
+        }
+        self.allow_versions.iter().any(|allow| {
+            allow.name == name
+                && dupes
+                    .iter()
+                    .all(|package| allow.versions.contains(&package.version().to_string()))
+        })
+    }
+}
+
 #[derive(ArgEnum, Copy, Clone, Debug)]
 pub enum BuildKind {
     All,
@@ -197,27 +461,69 @@ pub struct ResolveCargoOptions {
     /// Write summary file
     summary: Option<PathBuf>,
 
+    #[clap(long, requires = "summary")]
+    /// Record dependency edges in the summary file (uses summary version 2)
+    summary_edges: bool,
+
+    #[clap(long)]
+    /// Annotate each feature with whether it's enabled by default, explicitly requested, or
+    /// enabled by another package
+    show_provenance: bool,
+
+    #[clap(long = "explain-feature", name = "PACKAGE/FEATURE")]
+    /// Explain why a feature is enabled, e.g. `serde/std` or `serde/dep:serde_derive`
+    explain_features: Vec<String>,
+
+    #[clap(long, rename_all = "kebab-case")]
+    /// Save resolved packages in .dot format
+    ///
+    /// Packages built on the target platform are drawn as ellipses, packages built on the host
+    /// platform as boxes, and packages built on both as octagons.
+    output_dot: Option<String>,
+
+    #[clap(flatten)]
+    dot_opts: DotOptions,
+
+    #[clap(long, rename_all = "kebab-case")]
+    /// Save resolved packages in JSON format, with one entry per package and build platform
+    output_json: Option<String>,
+
     #[clap(flatten)]
     metadata_opts: CargoMetadataOptions,
 }
 
-pub fn cmd_resolve_cargo(opts: &ResolveCargoOptions) -> Result<()> {
+/// A package in the JSON output of `resolve-cargo`.
+#[derive(Debug, Serialize)]
+struct ResolvedPackage<'g> {
+    name: &'g str,
+    version: String,
+    id: &'g str,
+    source: String,
+    build_platform: &'static str,
+    features: Vec<&'g str>,
+    optional_deps: Vec<&'g str>,
+}
+
+pub fn cmd_resolve_cargo(opts: &ResolveCargoOptions, output: &OutputContext) -> Result<()> {
     let target_platform = string_to_platform_spec(opts.target_platform.as_deref())?;
     let host_platform = string_to_platform_spec(opts.host_platform.as_deref())?;
-    let command = opts.metadata_opts.make_command();
-    let pkg_graph = command.build_graph()?;
+    let pkg_graph = opts.metadata_opts.build_graph()?;
 
-    let mut cargo_opts = CargoOptions::new();
-    cargo_opts
-        .set_include_dev(opts.resolver_opts.include_dev)
-        .set_resolver(opts.resolver_opts.resolver_version.to_guppy())
-        .set_initials_platform(opts.resolver_opts.initials_platform.to_guppy())
-        .set_target_platform(target_platform)
-        .set_host_platform(host_platform)
-        .add_omitted_packages(opts.base_filter_opts.omitted_package_ids(&pkg_graph));
+    let cargo_opts = make_cargo_options(
+        &pkg_graph,
+        &opts.resolver_opts,
+        &opts.base_filter_opts,
+        target_platform,
+        host_platform,
+    )?;
 
     let (initials, features_only) = opts.pf.make_feature_sets(&pkg_graph)?;
     let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+    debug!(
+        "resolved {} target and {} host packages",
+        cargo_set.target_features().to_package_set().len(),
+        cargo_set.host_features().to_package_set().len()
+    );
 
     // Note that for the target+proc macro case, we unify direct deps here. This means that
     // direct deps of workspace proc macros (e.g. quote) will be included. This feels like it's
@@ -232,27 +538,17 @@ pub fn cmd_resolve_cargo(opts: &ResolveCargoOptions) -> Result<()> {
         BuildKind::Host | BuildKind::ProcMacro => Cow::Borrowed(cargo_set.host_direct_deps()),
     };
 
-    let print_packages = |feature_set: &FeatureSet| {
-        for feature_list in feature_set.packages_with_features(DependencyDirection::Forward) {
-            let package = feature_list.package();
-            let show_package = match opts.base_filter_opts.kind {
-                Kind::All => true,
-                Kind::Workspace => package.in_workspace(),
-                Kind::DirectThirdParty => {
-                    !package.in_workspace()
-                        && direct_deps.contains(package.id()).expect("valid package")
-                }
-                Kind::ThirdParty => !package.in_workspace(),
-            };
-            if show_package {
-                println!(
-                    "{} {}: {}",
-                    package.name(),
-                    package.version(),
-                    feature_list.display_features()
-                );
+    let show_package = |package: PackageMetadata<'_>| {
+        let show_kind = match opts.base_filter_opts.kind {
+            Kind::All => true,
+            Kind::Workspace => package.in_workspace(),
+            Kind::DirectThirdParty => {
+                !package.in_workspace()
+                    && direct_deps.contains(package.id()).expect("valid package")
             }
-        }
+            Kind::ThirdParty => !package.in_workspace(),
+        };
+        show_kind && opts.base_filter_opts.filter_expr_opts.matches(package)
     };
 
     let proc_macro_features = || {
@@ -261,20 +557,92 @@ pub fn cmd_resolve_cargo(opts: &ResolveCargoOptions) -> Result<()> {
         let feature_set = package_set.to_feature_set(StandardFeatures::All);
         cargo_set.host_features().intersection(&feature_set)
     };
-    match opts.build_kind {
-        BuildKind::All => {
-            print_packages(&cargo_set.target_features().union(cargo_set.host_features()))
-        }
-        BuildKind::Target => print_packages(cargo_set.target_features()),
-        BuildKind::ProcMacro => print_packages(&proc_macro_features()),
+    let none = || pkg_graph.feature_graph().resolve_none();
+    let (target_features, host_features) = match opts.build_kind {
+        BuildKind::All => (
+            cargo_set.target_features().clone(),
+            cargo_set.host_features().clone(),
+        ),
+        BuildKind::Target => (cargo_set.target_features().clone(), none()),
+        BuildKind::ProcMacro => (none(), proc_macro_features()),
         BuildKind::TargetAndProcMacro => {
-            print_packages(&cargo_set.target_features().union(&proc_macro_features()))
+            (cargo_set.target_features().clone(), proc_macro_features())
+        }
+        BuildKind::Host => (none(), cargo_set.host_features().clone()),
+    };
+
+    // Features requested through features-only packages count as explicitly requested.
+    let requested = cargo_set.initials().union(cargo_set.features_only());
+    let resolved = target_features.union(&host_features);
+    let feature_lists: Box<dyn Iterator<Item = FeatureList>> = if opts.show_provenance {
+        Box::new(resolved.packages_with_provenance(&requested, DependencyDirection::Forward))
+    } else {
+        Box::new(resolved.packages_with_features(DependencyDirection::Forward))
+    };
+    for feature_list in feature_lists {
+        let package = feature_list.package();
+        if show_package(*package) {
+            println!(
+                "{} {}: {}",
+                package.name().style(output.styles.package_name),
+                package.version().style(output.styles.package_version),
+                feature_list.display_features_with_provenance()
+            );
         }
-        BuildKind::Host => print_packages(cargo_set.host_features()),
+    }
+
+    if let Some(output_file) = &opts.output_dot {
+        let target_packages = target_features
+            .to_package_set()
+            .filter(DependencyDirection::Forward, show_package);
+        let host_packages = host_features
+            .to_package_set()
+            .filter(DependencyDirection::Forward, show_package);
+        let package_set = target_packages.union(&host_packages);
+        let dot = package_set.display_dot(
+            opts.dot_opts
+                .cargo_set_visitor(target_packages, host_packages),
+        );
+        let mut f = fs::File::create(output_file)?;
+        write!(f, "{}", dot)?;
+    }
+
+    if let Some(output_file) = &opts.output_json {
+        let mut resolved = vec![];
+        for (build_platform, feature_set) in
+            [("target", &target_features), ("host", &host_features)]
+        {
+            for feature_list in feature_set.packages_with_features(DependencyDirection::Forward) {
+                let package = feature_list.package();
+                if show_package(*package) {
+                    resolved.push(ResolvedPackage {
+                        name: package.name(),
+                        version: package.version().to_string(),
+                        id: package.id().repr(),
+                        source: package.source().to_string(),
+                        build_platform,
+                        features: feature_list.named_features().collect(),
+                        optional_deps: feature_list.optional_deps().collect(),
+                    });
+                }
+            }
+        }
+        let mut f = fs::File::create(output_file)?;
+        serde_json::to_writer_pretty(&mut f, &resolved)?;
+        writeln!(f)?;
+    }
+
+    for spec in &opts.explain_features {
+        explain_feature(&pkg_graph, &cargo_set, spec)?;
     }
 
     if let Some(summary_path) = &opts.summary {
-        let summary = cargo_set.to_summary(&cargo_opts)?;
+        let version = if opts.summary_edges {
+            SummaryVersion::V2
+        } else {
+            SummaryVersion::V1
+        };
+        let summary = cargo_set.to_summary_with_version(&cargo_opts, version)?;
         let mut out = "# This summary file was @generated by cargo-guppy.\n\n".to_string();
         summary.write_to_string(&mut out)?;
 
@@ -284,23 +652,32 @@ pub fn cmd_resolve_cargo(opts: &ResolveCargoOptions) -> Result<()> {
     Ok(())
 }
 
-struct NameVisitor;
-
-impl PackageDotVisitor for NameVisitor {
-    fn visit_package(&self, package: PackageMetadata<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
-        write!(f, "{}", package.name())
-    }
-
-    fn visit_link(&self, _link: PackageLink<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
-        write!(f, "")
-    }
-}
-
 #[derive(Debug, Parser)]
 pub struct CmdSelectOptions {
     #[clap(flatten)]
     filter_opts: FilterOptions,
 
+    #[clap(flatten)]
+    attribute_opts: LinkAttributeOptions,
+
+    #[clap(long)]
+    /// Select the packages Cargo would build, honoring platforms and optional dependencies
+    /// (default features are enabled on the roots, and build dependencies are always included)
+    cargo_resolve: bool,
+
+    #[clap(long, requires = "cargo-resolve")]
+    /// Only select packages that are built solely to run on the host: build dependencies,
+    /// proc macros and their dependencies (requires --cargo-resolve)
+    host_code_only: bool,
+
+    #[clap(long, arg_enum, default_value_t = CargoResolverVersionCmd::V1)]
+    /// Cargo resolver version to use with --cargo-resolve
+    resolver_version: CargoResolverVersionCmd,
+
+    #[clap(long)]
+    /// Only select packages at most this many links away from the roots
+    max_depth: Option<usize>,
+
     #[clap(long = "output-reverse", parse(from_flag = parse_direction))]
     /// Output results in reverse topological order (default: forward)
     output_direction: DependencyDirection,
@@ -309,6 +686,12 @@ pub struct CmdSelectOptions {
     /// Save selection graph in .dot format
     output_dot: Option<String>,
 
+    #[clap(flatten)]
+    dot_opts: DotOptions,
+
+    #[clap(flatten)]
+    tree_opts: TreeOptions,
+
     #[clap(flatten)]
     query_opts: QueryOptions,
 
@@ -316,15 +699,64 @@ pub struct CmdSelectOptions {
     metadata_opts: CargoMetadataOptions,
 }
 
-pub fn cmd_select(options: &CmdSelectOptions) -> Result<()> {
-    let mut command = options.metadata_opts.make_command();
-    command.other_options(["--no-deps"]);
-    let pkg_graph = command.build_graph()?;
+pub fn cmd_select(options: &CmdSelectOptions, output: &OutputContext) -> Result<()> {
+    let pkg_graph = options.metadata_opts.build_graph()?;
+
+    let (package_set, tree) = if options.cargo_resolve {
+        let cargo_set = make_select_cargo_set(&pkg_graph, options)?;
+        let package_set = select_cargo(&pkg_graph, &cargo_set, options)?;
+        let tree = options.tree_opts.output_tree.then(|| {
+            let links = cargo_set.target_links().chain(cargo_set.host_links());
+            let mut tree = PackageTree::new(
+                cargo_set
+                    .initials()
+                    .to_package_set()
+                    .packages(DependencyDirection::Forward),
+                links.filter(|link| package_set.contains(link.to().id()).unwrap_or(false)),
+                DependencyDirection::Forward,
+            );
+            tree.set_features(cargo_set.target_features().union(cargo_set.host_features()));
+            tree
+        });
+        (package_set, tree)
+    } else {
+        let package_set = select_graph(&pkg_graph, options)?;
+        let tree = if options.tree_opts.output_tree {
+            Some(select_graph_tree(&pkg_graph, &package_set, options)?)
+        } else {
+            None
+        };
+        (package_set, tree)
+    };
 
-    let query = options.query_opts.apply(&pkg_graph)?;
-    let resolver = options.filter_opts.make_resolver(&pkg_graph)?;
-    let package_set = query.resolve_with_fn(resolver);
+    debug!("selected {} packages", package_set.len());
+
+    if let Some(tree) = tree {
+        print!("{}", tree.display(&options.tree_opts));
+    } else {
+        print_package_ids(&pkg_graph, &package_set, options, output);
+    }
+
+    if let Some(ref output_file) = options.output_dot {
+        let package_set = options
+            .filter_opts
+            .base_opts
+            .filter_expr_opts
+            .apply(&package_set);
+        let dot = package_set.display_dot(options.dot_opts.visitor());
+        let mut f = fs::File::create(output_file)?;
+        write!(f, "{}", dot)?;
+    }
 
+    Ok(())
+}
+
+fn print_package_ids(
+    pkg_graph: &PackageGraph,
+    package_set: &PackageSet<'_>,
+    options: &CmdSelectOptions,
+    output: &OutputContext,
+) {
     for package_id in package_set.package_ids(options.output_direction) {
         let package = pkg_graph.metadata(package_id).unwrap();
         let in_workspace = package.in_workspace();
@@ -337,15 +769,251 @@ pub fn cmd_select(options: &CmdSelectOptions) -> Result<()> {
             Kind::DirectThirdParty => direct_dep,
             Kind::ThirdParty => !in_workspace,
         };
-        if show_package {
-            println!("{}", package_id);
+        if show_package
+            && options
+                .filter_opts
+                .base_opts
+                .filter_expr_opts
+                .matches(package)
+        {
+            println!("{}", package_id.style(output.styles.package_name));
         }
     }
+}
 
-    if let Some(ref output_file) = options.output_dot {
-        let dot = package_set.display_dot(NameVisitor);
-        let mut f = fs::File::create(output_file)?;
-        write!(f, "{}", dot)?;
+/// Selects packages by following links in the package graph.
+fn select_graph<'g>(
+    pkg_graph: &'g PackageGraph,
+    options: &'g CmdSelectOptions,
+) -> Result<PackageSet<'g>> {
+    let mut query = options.query_opts.apply(pkg_graph)?;
+    if let Some(max_depth) = options.max_depth {
+        let initial_ids: Vec<_> = query.initials().map(|package| package.id()).collect();
+        query = pkg_graph.query_directed_bounded(initial_ids, query.direction(), max_depth)?;
+    }
+    let resolver = options.filter_opts.make_resolver(pkg_graph)?;
+    let attribute_filter = options
+        .filter_opts
+        .make_attribute_filter(&options.attribute_opts)?;
+    let package_set = match attribute_filter {
+        Some(attribute_filter) => {
+            // Packages pulled in through matching links are the ones that are no longer selected
+            // once those links are omitted.
+            let all = query.clone().resolve_with_fn(&resolver);
+            let without_matching = query
+                .resolve_with_fn(|query, link| resolver(query, link) && !attribute_filter(link));
+            all.difference(&without_matching)
+        }
+        None => query.resolve_with_fn(resolver),
+    };
+    Ok(package_set)
+}
+
+/// Builds a tree of the packages selected by following links in the package graph.
+///
+/// Features are annotated as if default features were enabled on the roots, and features were
+/// only unified across the selected packages.
+fn select_graph_tree<'g>(
+    pkg_graph: &'g PackageGraph,
+    package_set: &PackageSet<'g>,
+    options: &CmdSelectOptions,
+) -> Result<PackageTree<'g>> {
+    if options.attribute_opts.is_active() {
+        bail!("--output-tree can't be combined with link attribute filters");
+    }
+
+    let query = options.query_opts.apply(pkg_graph)?;
+    let direction = query.direction();
+    let roots: Vec<_> = query.initials().collect();
+    let mut tree = PackageTree::new(
+        roots.iter().copied(),
+        package_set.links(direction),
+        direction,
+    );
+
+    if options.tree_opts.tree_features {
+        let in_set = |package_id| package_set.contains(package_id).unwrap_or(false);
+        let features = pkg_graph
+            .resolve_ids(roots.iter().map(|package| package.id()))?
+            .to_feature_set(StandardFeatures::Default)
+            .to_feature_query(DependencyDirection::Forward)
+            .resolve_with_fn(|_, link| {
+                in_set(link.from().package_id()) && in_set(link.to().package_id())
+            });
+        tree.set_features(features);
+    }
+    Ok(tree)
+}
+
+/// Simulates the Cargo build used by `select --cargo-resolve`.
+fn make_select_cargo_set<'g>(
+    pkg_graph: &'g PackageGraph,
+    options: &CmdSelectOptions,
+) -> Result<CargoSet<'g>> {
+    if options.attribute_opts.is_active() {
+        bail!("--cargo-resolve can't be combined with link attribute filters");
+    }
+    if options.max_depth.is_some() {
+        bail!("--cargo-resolve can't be combined with --max-depth");
+    }
+
+    // --target applies to both the target and host platforms, as it does for graph traversal.
+    let platform_spec = string_to_platform_spec(options.filter_opts.target.as_deref())
+        .wrap_err_with(|| "target platform isn't known")?;
+    let resolver_opts = CargoResolverOpts {
+        edge_kind_opts: options.filter_opts.edge_kind_opts.clone(),
+        initials_platform: InitialsPlatformCmd::Standard,
+        resolver_version: options.resolver_version,
+    };
+    let cargo_opts = make_cargo_options(
+        pkg_graph,
+        &resolver_opts,
+        &options.filter_opts.base_opts,
+        platform_spec.clone(),
+        platform_spec,
+    )?;
+
+    let initials = options
+        .query_opts
+        .initials(pkg_graph)?
+        .to_feature_set(StandardFeatures::Default);
+    let features_only = pkg_graph
+        .resolve_none()
+        .to_feature_set(StandardFeatures::Default);
+    Ok(CargoSet::new(initials, features_only, &cargo_opts)?)
+}
+
+/// Selects the packages that Cargo would build, on either the target or the host platform.
+///
+/// As with graph traversal, only links that `--kind` allows are followed from the roots.
+fn select_cargo<'g>(
+    pkg_graph: &'g PackageGraph,
+    cargo_set: &CargoSet<'g>,
+    options: &CmdSelectOptions,
+) -> Result<PackageSet<'g>> {
+    let package_set = if options.host_code_only {
+        cargo_set
+            .build_script_closure()
+            .union(&cargo_set.proc_macro_closure())
+    } else {
+        cargo_set
+            .target_features()
+            .union(cargo_set.host_features())
+            .to_package_set()
+    };
+
+    let kind = options.filter_opts.base_opts.kind;
+    let cargo_links: HashSet<_> = cargo_set
+        .target_links()
+        .chain(cargo_set.host_links())
+        .map(|link| (link.from().id(), link.to().id()))
+        .collect();
+    let reachable = pkg_graph
+        .query_forward(
+            cargo_set
+                .initials()
+                .to_package_set()
+                .package_ids(DependencyDirection::Forward),
+        )?
+        .resolve_with_fn(|_, link| {
+            kind.should_traverse(&link) && cargo_links.contains(&(link.from().id(), link.to().id()))
+        });
+    Ok(package_set.intersection(&reachable))
+}
+
+/// Constructs the options for a simulated Cargo build.
+fn make_cargo_options<'g>(
+    pkg_graph: &'g PackageGraph,
+    resolver_opts: &CargoResolverOpts,
+    base_filter_opts: &BaseFilterOptions,
+    target_platform: PlatformSpec,
+    host_platform: PlatformSpec,
+) -> Result<CargoOptions<'g>> {
+    let mut cargo_opts = CargoOptions::new();
+    cargo_opts
+        .set_include_dev(resolver_opts.include_dev()?)
+        .set_resolver(resolver_opts.resolver_version.to_guppy())
+        .set_initials_platform(resolver_opts.initials_platform.to_guppy())
+        .set_target_platform(target_platform)
+        .set_host_platform(host_platform)
+        .add_omitted_packages(base_filter_opts.omitted_package_ids(pkg_graph));
+    Ok(cargo_opts)
+}
+
+fn explain_feature(pkg_graph: &PackageGraph, cargo_set: &CargoSet<'_>, spec: &str) -> Result<()> {
+    let (name, feature) = match spec.split_once('/') {
+        Some((name, feature)) if !name.is_empty() && !feature.is_empty() => (name, feature),
+        _ => bail!(
+            "invalid feature `{}`: expected `package/feature` or `package/dep:name`",
+            spec
+        ),
+    };
+    let label = match feature.strip_prefix("dep:") {
+        Some(dep_name) => FeatureLabel::OptionalDependency(dep_name),
+        None => FeatureLabel::Named(feature),
+    };
+
+    let mut packages: Vec<_> = pkg_graph
+        .resolve_package_name(name)
+        .packages(DependencyDirection::Forward)
+        .collect();
+    if packages.is_empty() {
+        bail!("unknown package `{}` (in `{}`)", name, spec);
+    }
+    packages.sort_by_key(|package| package.version());
+
+    let feature_graph = pkg_graph.feature_graph();
+    let display_feature = |feature_id: FeatureId<'_>| {
+        let package = pkg_graph
+            .metadata(feature_id.package_id())
+            .expect("valid package ID");
+        format!(
+            "{} {}/{}",
+            package.name(),
+            package.version(),
+            feature_id.label()
+        )
+    };
+
+    for package in packages {
+        let feature_id = FeatureId::new(package.id(), label);
+        if !feature_graph.contains(feature_id) {
+            println!("{}: no such feature", display_feature(feature_id));
+            continue;
+        }
+
+        let mut enabled = false;
+        for (build_kind, feature_set) in [
+            ("target", cargo_set.target_features()),
+            ("host", cargo_set.host_features()),
+        ] {
+            let trace = match feature_set.trace(feature_id)? {
+                Some(trace) => trace,
+                None => continue,
+            };
+            enabled = true;
+            println!("{} ({}):", display_feature(feature_id), build_kind);
+            println!("  initial: {}", display_feature(trace.initial()));
+            for step in trace.steps() {
+                let kind = match step {
+                    FeatureTraceStep::Base { .. } => "base",
+                    FeatureTraceStep::NamedFeature { .. } => "feature",
+                    FeatureTraceStep::OptionalDependency(_) => "optional dependency",
+                    FeatureTraceStep::Dependency(link) if link.dev_only() => "dev-dependency",
+                    FeatureTraceStep::Dependency(_) => "dependency",
+                    _ => "other",
+                };
+                println!(
+                    "  {} -> {} ({})",
+                    display_feature(step.from()),
+                    display_feature(step.to()),
+                    kind
+                );
+            }
+        }
+        if !enabled {
+            println!("{}: not enabled", display_feature(feature_id));
+        }
     }
 
     Ok(())
@@ -361,13 +1029,86 @@ pub struct SubtreeSizeOptions {
     /// The root packages to start the selection from
     root: Option<String>,
 
+    #[clap(long, arg_enum, default_value = "unique")]
+    /// Sort packages by unique subtree size, total subtree size, or name
+    sort_by: SubtreeSortBy,
+
+    #[clap(long, arg_enum, default_value = "count")]
+    /// Weight each dependency by this measure when adding up subtree sizes
+    ///
+    /// With a weight other than `count`, sizes are sums of weights rather than numbers of
+    /// packages, so that large dependencies stand out as build cost hotspots.
+    weight: WeightKind,
+
+    #[clap(long, value_name = "PATH")]
+    /// TOML file with weights for packages, taking precedence over --weight
+    ///
+    /// Weights are listed by package name in a `[packages]` table, e.g.
+    /// `librocksdb-sys = 500`. Packages that aren't listed are weighted by --weight.
+    weights_file: Option<Utf8PathBuf>,
+
+    #[clap(long, arg_enum, default_value = "text")]
+    /// Output format
+    output_format: OutputFormat,
+
     #[clap(flatten)]
     metadata_opts: CargoMetadataOptions,
 }
 
-pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
-    let command = options.metadata_opts.make_command();
-    let pkg_graph = command.build_graph()?;
+#[derive(ArgEnum, Copy, Clone, Debug)]
+pub enum SubtreeSortBy {
+    /// Sort by the size of dependencies unique to a package, largest first
+    Unique,
+    /// Sort by the total size of dependencies of a package, largest first
+    Total,
+    /// Sort by package name and version
+    Name,
+}
+
+#[derive(ArgEnum, Copy, Clone, Debug)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// JSON
+    Json,
+}
+
+/// The subtree sizes of a single package, as reported by `subtree-size`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SubtreeSize<'g> {
+    #[serde(serialize_with = "serialize_package_id")]
+    package_id: &'g PackageId,
+    name: &'g str,
+    version: String,
+    // The number of dependencies that are only reachable through this package.
+    unique_size: usize,
+    // The total number of transitive dependencies of this package, excluding the package itself.
+    total_size: usize,
+    // The sum of the weights of unique_deps.
+    unique_weight: u64,
+    // The sum of the weights of all transitive dependencies, excluding the package itself.
+    total_weight: u64,
+    #[serde(serialize_with = "serialize_package_ids")]
+    unique_deps: Vec<&'g PackageId>,
+}
+
+fn serialize_package_id<S: Serializer>(
+    package_id: &&PackageId,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(package_id.repr())
+}
+
+fn serialize_package_ids<S: Serializer>(
+    package_ids: &[&PackageId],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(package_ids.iter().map(|package_id| package_id.repr()))
+}
+
+pub fn cmd_subtree_size(options: &SubtreeSizeOptions, output: &OutputContext) -> Result<()> {
+    let pkg_graph = options.metadata_opts.build_graph()?;
 
     let resolver = options.filter_opts.make_resolver(&pkg_graph)?;
 
@@ -388,7 +1129,10 @@ pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
         pkg_graph.query_workspace()
     };
 
+    let mut weights = Weights::new(options.weight, options.weights_file.as_deref())?;
+
     let mut unique_deps: HashMap<&PackageId, HashSet<&PackageId>> = HashMap::new();
+    let mut total_sizes: HashMap<&PackageId, (usize, u64)> = HashMap::new();
     for package_id in selection
         .resolve_with_fn(&resolver)
         .package_ids(DependencyDirection::Forward)
@@ -398,6 +1142,14 @@ pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
             .resolve_with_fn(&resolver)
             .package_ids(DependencyDirection::Forward)
             .collect();
+        let mut total_weight = 0;
+        for dep_package_id in &subtree_package_set {
+            if *dep_package_id != package_id {
+                total_weight += weights.weight(pkg_graph.metadata(dep_package_id)?)?;
+            }
+        }
+        // don't count ourself
+        total_sizes.insert(package_id, (subtree_package_set.len() - 1, total_weight));
         let mut nonunique_deps_set: HashSet<&PackageId> = HashSet::new();
         for dep_package_id in &subtree_package_set {
             // don't count ourself
@@ -435,15 +1187,86 @@ pub fn cmd_subtree_size(options: &SubtreeSizeOptions) -> Result<()> {
         }
     }
 
-    let mut sorted_unique_deps = unique_deps.into_iter().collect::<Vec<_>>();
-    sorted_unique_deps.sort_by_key(|a| cmp::Reverse(a.1.len()));
+    let filter_expr_opts = &options.filter_opts.base_opts.filter_expr_opts;
+    let mut subtree_sizes = vec![];
+    for (package_id, (total_size, total_weight)) in total_sizes {
+        let package = pkg_graph.metadata(package_id).unwrap();
+        if !filter_expr_opts.matches(package) {
+            continue;
+        }
+        let mut unique_deps: Vec<_> = unique_deps
+            .remove(package_id)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        unique_deps.sort_unstable();
+        let mut unique_weight = 0;
+        for dep_package_id in &unique_deps {
+            unique_weight += weights.weight(pkg_graph.metadata(dep_package_id)?)?;
+        }
+        subtree_sizes.push(SubtreeSize {
+            package_id,
+            name: package.name(),
+            version: package.version().to_string(),
+            unique_size: unique_deps.len(),
+            total_size,
+            unique_weight,
+            total_weight,
+            unique_deps,
+        });
+    }
+    // Sort by package ID first so that ties are broken deterministically.
+    subtree_sizes.sort_by_key(|size| size.package_id);
+    match options.sort_by {
+        SubtreeSortBy::Unique => subtree_sizes.sort_by_key(|size| cmp::Reverse(size.unique_weight)),
+        SubtreeSortBy::Total => subtree_sizes.sort_by_key(|size| cmp::Reverse(size.total_weight)),
+        SubtreeSortBy::Name => subtree_sizes.sort_by_key(|size| {
+            let package = pkg_graph.metadata(size.package_id).unwrap();
+            (package.name(), package.version())
+        }),
+    }
 
-    for (package_id, deps) in sorted_unique_deps.iter() {
-        if !deps.is_empty() {
-            println!("{} {}", deps.len(), package_id);
+    debug!(
+        "computed subtree sizes for {} packages",
+        subtree_sizes.len()
+    );
+
+    match options.output_format {
+        OutputFormat::Text if weights.is_count() => {
+            for size in &subtree_sizes {
+                if !size.unique_deps.is_empty() {
+                    println!(
+                        "{} {} (total {})",
+                        size.unique_size,
+                        size.package_id.style(output.styles.package_name),
+                        size.total_size
+                    );
+                }
+                for dep in &size.unique_deps {
+                    println!("    {}", dep);
+                }
+            }
+        }
+        OutputFormat::Text => {
+            for size in &subtree_sizes {
+                if !size.unique_deps.is_empty() {
+                    println!(
+                        "{} {} (total {}, {} unique of {} dependencies)",
+                        size.unique_weight,
+                        size.package_id.style(output.styles.package_name),
+                        size.total_weight,
+                        size.unique_size,
+                        size.total_size,
+                    );
+                }
+                for dep in &size.unique_deps {
+                    let weight = weights.weight(pkg_graph.metadata(dep)?)?;
+                    println!("    {} ({})", dep, weight);
+                }
+            }
         }
-        for dep in deps {
-            println!("    {}", dep);
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&subtree_sizes)?);
         }
     }
 
@@ -0,0 +1,772 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{cycles::Cycle, diagnostics::Violation, outdated_paths::outdated_links};
+use camino::Utf8PathBuf;
+use clap::Parser;
+use color_eyre::eyre::{bail, Result, WrapErr};
+use guppy::{
+    graph::{DependencyDirection, ExternalSource, PackageGraph, PackageLink},
+    DependencyKind,
+};
+use guppy_cmdlib::CargoMetadataOptions;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    io::{self, Write},
+};
+
+#[derive(Debug, Parser)]
+pub struct LintOptions {
+    /// Lints to run, e.g. `requirement-conflicts` [default: all lints]
+    ///
+    /// Lints set to `allow` in the configuration file are skipped even if listed here.
+    #[clap(value_name = "LINT")]
+    lints: Vec<String>,
+
+    #[clap(long)]
+    /// Configuration file for lints (TOML)
+    ///
+    /// Each lint is configured in a table named after it, e.g. `[duplicate-versions]`. Lints that
+    /// aren't mentioned use their default settings.
+    config: Option<Utf8PathBuf>,
+
+    #[clap(long)]
+    /// Output the list of findings as JSON
+    json: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl LintOptions {
+    pub fn exec(&self) -> Result<()> {
+        let config = match &self.config {
+            Some(config_path) => {
+                let config = fs::read_to_string(config_path)
+                    .wrap_err_with(|| format!("reading lint config {} failed", config_path))?;
+                LintConfig::parse(&config)
+                    .wrap_err_with(|| format!("parsing lint config {} failed", config_path))?
+            }
+            None => LintConfig::default(),
+        };
+
+        for lint in &self.lints {
+            if !LintConfig::NAMES.contains(&lint.as_str()) {
+                bail!(
+                    "unknown lint `{}` (known lints: {})",
+                    lint,
+                    LintConfig::NAMES.join(", ")
+                );
+            }
+        }
+
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let findings = config.run(&pkg_graph, &self.lints);
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        if self.json {
+            serde_json::to_writer_pretty(&mut stdout, &findings)?;
+            writeln!(stdout)?;
+        } else {
+            for finding in &findings {
+                writeln!(stdout, "{}", finding)?;
+            }
+        }
+
+        let denied = findings
+            .iter()
+            .filter(|finding| finding.level == LintLevel::Deny)
+            .count();
+        if denied > 0 {
+            return Err(Violation::new(format!("{} lint failure(s)", denied)).into());
+        }
+        Ok(())
+    }
+}
+
+/// Configuration for `cargo guppy lint`, read from TOML.
+///
+/// An example:
+///
+/// ```toml
+/// [duplicate-versions]
+/// level = "deny"
+/// # Fail if a package has more than two versions in the build graph.
+/// max-versions = 2
+/// # Packages, by name, that are exempt from this lint.
+/// allow = ["syn"]
+///
+/// [missing-license]
+/// # Also check workspace members that have `publish = false`.
+/// include-unpublished = true
+///
+/// [dev-only-cycles]
+/// level = "allow"
+///
+/// [requirement-conflicts]
+/// # Also compare declarations in `[dev-dependencies]`.
+/// include-dev = true
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct LintConfig {
+    #[serde(default)]
+    duplicate_versions: DuplicateVersionsConfig,
+    #[serde(default)]
+    path_version_mismatch: PathVersionMismatchConfig,
+    #[serde(default)]
+    missing_license: MissingLicenseConfig,
+    #[serde(default)]
+    dev_only_cycles: DevOnlyCyclesConfig,
+    #[serde(default)]
+    unpublishable_deps: UnpublishableDepsConfig,
+    #[serde(default)]
+    requirement_conflicts: RequirementConflictsConfig,
+}
+
+impl LintConfig {
+    /// The names of all lints, in the order they're run.
+    const NAMES: &'static [&'static str] = &[
+        DuplicateVersionsConfig::NAME,
+        PathVersionMismatchConfig::NAME,
+        MissingLicenseConfig::NAME,
+        DevOnlyCyclesConfig::NAME,
+        UnpublishableDepsConfig::NAME,
+        RequirementConflictsConfig::NAME,
+    ];
+
+    fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Runs every lint that isn't allowed, returning findings sorted by lint and message.
+    ///
+    /// If `only` is non-empty, only the lints named in it are run.
+    fn run(&self, pkg_graph: &PackageGraph, only: &[String]) -> Vec<Finding> {
+        let enabled = |name: &str, level: LintLevel| {
+            level != LintLevel::Allow && (only.is_empty() || only.iter().any(|lint| lint == name))
+        };
+
+        let mut findings = vec![];
+        if enabled(DuplicateVersionsConfig::NAME, self.duplicate_versions.level) {
+            self.duplicate_versions.run(pkg_graph, &mut findings);
+        }
+        if enabled(
+            PathVersionMismatchConfig::NAME,
+            self.path_version_mismatch.level,
+        ) {
+            self.path_version_mismatch.run(pkg_graph, &mut findings);
+        }
+        if enabled(MissingLicenseConfig::NAME, self.missing_license.level) {
+            self.missing_license.run(pkg_graph, &mut findings);
+        }
+        if enabled(DevOnlyCyclesConfig::NAME, self.dev_only_cycles.level) {
+            self.dev_only_cycles.run(pkg_graph, &mut findings);
+        }
+        if enabled(UnpublishableDepsConfig::NAME, self.unpublishable_deps.level) {
+            self.unpublishable_deps.run(pkg_graph, &mut findings);
+        }
+        if enabled(
+            RequirementConflictsConfig::NAME,
+            self.requirement_conflicts.level,
+        ) {
+            self.requirement_conflicts.run(pkg_graph, &mut findings);
+        }
+        findings.sort_by(|a, b| (a.lint, &a.message).cmp(&(b.lint, &b.message)));
+        findings
+    }
+}
+
+/// What to do when a lint finds a problem.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum LintLevel {
+    /// Don't run the lint.
+    Allow,
+    /// Report problems, but don't fail.
+    Warn,
+    /// Report problems and fail.
+    Deny,
+}
+
+impl LintLevel {
+    fn warn() -> Self {
+        LintLevel::Warn
+    }
+
+    fn deny() -> Self {
+        LintLevel::Deny
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warning",
+            LintLevel::Deny => "error",
+        }
+    }
+}
+
+/// A problem found by a lint.
+#[derive(Debug, Serialize)]
+struct Finding {
+    lint: &'static str,
+    level: LintLevel,
+    message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}",
+            self.level.as_str(),
+            self.lint,
+            self.message
+        )
+    }
+}
+
+/// Packages with more versions in the build graph than a threshold.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DuplicateVersionsConfig {
+    #[serde(default = "LintLevel::warn")]
+    level: LintLevel,
+    /// The maximum number of versions a package may have.
+    #[serde(default = "default_max_versions")]
+    max_versions: usize,
+    /// Packages, by name, that are exempt from this lint.
+    #[serde(default)]
+    allow: BTreeSet<String>,
+}
+
+impl Default for DuplicateVersionsConfig {
+    fn default() -> Self {
+        Self {
+            level: LintLevel::Warn,
+            max_versions: default_max_versions(),
+            allow: BTreeSet::new(),
+        }
+    }
+}
+
+fn default_max_versions() -> usize {
+    1
+}
+
+impl DuplicateVersionsConfig {
+    const NAME: &'static str = "duplicate-versions";
+
+    fn run(&self, pkg_graph: &PackageGraph, findings: &mut Vec<Finding>) {
+        let mut versions: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for package in pkg_graph
+            .query_workspace()
+            .resolve()
+            .packages(DependencyDirection::Forward)
+        {
+            versions
+                .entry(package.name())
+                .or_default()
+                .insert(package.version());
+        }
+
+        for (name, versions) in versions {
+            if versions.len() <= self.max_versions || self.allow.contains(name) {
+                continue;
+            }
+            findings.push(Finding {
+                lint: Self::NAME,
+                level: self.level,
+                message: format!(
+                    "{} has {} versions (maximum {}): {}",
+                    name,
+                    versions.len(),
+                    self.max_versions,
+                    itertools::join(versions, ", "),
+                ),
+            });
+        }
+    }
+}
+
+/// Path dependencies whose version requirement doesn't match the version of the package.
+///
+/// This is the same check as `cargo guppy outdated-paths`, which can also fix these.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct PathVersionMismatchConfig {
+    #[serde(default = "LintLevel::deny")]
+    level: LintLevel,
+}
+
+impl Default for PathVersionMismatchConfig {
+    fn default() -> Self {
+        Self {
+            level: LintLevel::Deny,
+        }
+    }
+}
+
+impl PathVersionMismatchConfig {
+    const NAME: &'static str = "path-version-mismatch";
+
+    fn run(&self, pkg_graph: &PackageGraph, findings: &mut Vec<Finding>) {
+        for link in outdated_links(pkg_graph) {
+            findings.push(Finding {
+                lint: Self::NAME,
+                level: self.level,
+                message: format!(
+                    "{} depends on {} with requirement {}, but its version is {}",
+                    link.from().name(),
+                    link.to().name(),
+                    link.version_req(),
+                    link.to().version(),
+                ),
+            });
+        }
+    }
+}
+
+/// Workspace members without a `license` or `license-file`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct MissingLicenseConfig {
+    #[serde(default = "LintLevel::deny")]
+    level: LintLevel,
+    /// Also check members that have `publish = false`.
+    #[serde(default)]
+    include_unpublished: bool,
+}
+
+impl Default for MissingLicenseConfig {
+    fn default() -> Self {
+        Self {
+            level: LintLevel::Deny,
+            include_unpublished: false,
+        }
+    }
+}
+
+impl MissingLicenseConfig {
+    const NAME: &'static str = "missing-license";
+
+    fn run(&self, pkg_graph: &PackageGraph, findings: &mut Vec<Finding>) {
+        for package in pkg_graph.workspace().iter() {
+            if !self.include_unpublished && package.publish().is_never() {
+                continue;
+            }
+            if package.license().is_none() && package.license_file().is_none() {
+                findings.push(Finding {
+                    lint: Self::NAME,
+                    level: self.level,
+                    message: format!(
+                        "{} doesn't specify `license` or `license-file`",
+                        package.name()
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Dependency cycles, which Cargo only permits through dev-dependencies.
+///
+/// These are common in workspaces with test helper crates, so this only warns by default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct DevOnlyCyclesConfig {
+    #[serde(default = "LintLevel::warn")]
+    level: LintLevel,
+}
+
+impl Default for DevOnlyCyclesConfig {
+    fn default() -> Self {
+        Self {
+            level: LintLevel::Warn,
+        }
+    }
+}
+
+impl DevOnlyCyclesConfig {
+    const NAME: &'static str = "dev-only-cycles";
+
+    fn run(&self, pkg_graph: &PackageGraph, findings: &mut Vec<Finding>) {
+        for package_ids in pkg_graph.cycles().all_cycles() {
+            let cycle = Cycle::new(pkg_graph, &package_ids);
+            findings.push(Finding {
+                lint: Self::NAME,
+                level: self.level,
+                message: format!(
+                    "{} cycle between {}",
+                    if cycle.dev_only { "dev-only" } else { "normal" },
+                    itertools::join(&cycle.packages, ", "),
+                ),
+            });
+        }
+    }
+}
+
+/// Dependencies of publishable workspace members that can't be published.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct UnpublishableDepsConfig {
+    #[serde(default = "LintLevel::deny")]
+    level: LintLevel,
+}
+
+impl Default for UnpublishableDepsConfig {
+    fn default() -> Self {
+        Self {
+            level: LintLevel::Deny,
+        }
+    }
+}
+
+impl UnpublishableDepsConfig {
+    const NAME: &'static str = "unpublishable-deps";
+
+    fn run(&self, pkg_graph: &PackageGraph, findings: &mut Vec<Finding>) {
+        for package in pkg_graph.workspace().iter() {
+            if package.publish().is_never() {
+                continue;
+            }
+            // Cargo strips dev-dependencies without a version while publishing, so only check
+            // links that are present in the published package.
+            for link in package.direct_links().filter(|link| !link.dev_only()) {
+                if let Some(reason) = unpublishable_reason(link) {
+                    findings.push(Finding {
+                        lint: Self::NAME,
+                        level: self.level,
+                        message: format!(
+                            "{} is publishable, but depends on {}, which {}",
+                            package.name(),
+                            link.to().name(),
+                            reason,
+                        ),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Dependencies declared several times by a workspace member with different version requirements,
+/// default features or features.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct RequirementConflictsConfig {
+    #[serde(default = "LintLevel::warn")]
+    level: LintLevel,
+    /// Also compare declarations in `[dev-dependencies]`, which often enable extra features for
+    /// tests.
+    #[serde(default)]
+    include_dev: bool,
+    /// Dependencies, by name, that are exempt from this lint.
+    #[serde(default)]
+    allow: BTreeSet<String>,
+}
+
+impl Default for RequirementConflictsConfig {
+    fn default() -> Self {
+        Self {
+            level: LintLevel::Warn,
+            include_dev: false,
+            allow: BTreeSet::new(),
+        }
+    }
+}
+
+impl RequirementConflictsConfig {
+    const NAME: &'static str = "requirement-conflicts";
+
+    fn run(&self, pkg_graph: &PackageGraph, findings: &mut Vec<Finding>) {
+        for package in pkg_graph.workspace().iter() {
+            for link in package.direct_links() {
+                if self.allow.contains(link.to().name()) {
+                    continue;
+                }
+                let conflicts = link.requirement_conflicts_with(|decl| {
+                    self.include_dev || decl.kind() != DependencyKind::Development
+                });
+                for conflict in conflicts {
+                    findings.push(Finding {
+                        lint: Self::NAME,
+                        level: self.level,
+                        message: conflict.to_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Returns the reason a dependency can't be published, if any.
+fn unpublishable_reason(link: PackageLink<'_>) -> Option<&'static str> {
+    let to = link.to();
+    let source = to.source();
+    let is_git = matches!(source.parse_external(), Some(ExternalSource::Git { .. }));
+    if source.is_local() && to.publish().is_never() {
+        Some("has `publish = false`")
+    } else if (source.is_local() || is_git) && link.version_req().comparators.is_empty() {
+        // Path and Git dependencies are replaced with registry dependencies while publishing,
+        // which requires a version.
+        Some("is specified without a version")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    /// Runs `lint` against `fixture` with the given configuration, returning findings as strings.
+    fn run_lint(config: &str, fixture: &JsonFixture, lint: &str) -> Vec<String> {
+        let config = LintConfig::parse(config).expect("config parsed");
+        config
+            .run(fixture.graph(), &[lint.to_owned()])
+            .iter()
+            .map(|finding| finding.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = LintConfig::parse("").expect("empty config parsed");
+        let levels = [
+            config.duplicate_versions.level,
+            config.path_version_mismatch.level,
+            config.missing_license.level,
+            config.dev_only_cycles.level,
+            config.unpublishable_deps.level,
+            config.requirement_conflicts.level,
+        ];
+        assert_eq!(
+            levels,
+            [
+                LintLevel::Warn,
+                LintLevel::Deny,
+                LintLevel::Deny,
+                LintLevel::Warn,
+                LintLevel::Deny,
+                LintLevel::Warn,
+            ],
+            "default levels"
+        );
+        assert_eq!(config.duplicate_versions.max_versions, 1);
+
+        static CONFIG: &str = r#"
+        [duplicate-versions]
+        level = "deny"
+        max-versions = 2
+        allow = ["syn"]
+
+        [missing-license]
+        include-unpublished = true
+
+        [dev-only-cycles]
+        level = "allow"
+
+        [requirement-conflicts]
+        include-dev = true
+        allow = ["serde"]
+        "#;
+        let config = LintConfig::parse(CONFIG).expect("config parsed");
+        assert_eq!(config.duplicate_versions.level, LintLevel::Deny);
+        assert_eq!(config.duplicate_versions.max_versions, 2);
+        assert!(config.duplicate_versions.allow.contains("syn"));
+        assert!(config.missing_license.include_unpublished);
+        assert_eq!(config.missing_license.level, LintLevel::Deny);
+        assert_eq!(config.dev_only_cycles.level, LintLevel::Allow);
+        assert!(config.requirement_conflicts.include_dev);
+        assert!(config.requirement_conflicts.allow.contains("serde"));
+
+        for (input, error) in [
+            ("[no-such-lint]", "unknown field `no-such-lint`"),
+            (
+                "[missing-license]\nmax-versions = 2",
+                "unknown field `max-versions`",
+            ),
+            (
+                "[dev-only-cycles]\nlevel = \"forbid\"",
+                "unknown variant `forbid`",
+            ),
+        ] {
+            let err = LintConfig::parse(input).expect_err("invalid config rejected");
+            assert!(
+                err.to_string().contains(error),
+                "for input {:?}, error {} contains {:?}",
+                input,
+                err,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn run_selected_lints() {
+        let graph = JsonFixture::metadata_cycle2().graph();
+        let config = LintConfig::default();
+
+        let findings = config.run(graph, &[]);
+        let lints: BTreeSet<_> = findings.iter().map(|finding| finding.lint).collect();
+        assert_eq!(
+            lints.into_iter().collect::<Vec<_>>(),
+            [
+                DevOnlyCyclesConfig::NAME,
+                MissingLicenseConfig::NAME,
+                UnpublishableDepsConfig::NAME
+            ],
+            "lints with findings"
+        );
+        let mut sorted: Vec<_> = findings
+            .iter()
+            .map(|finding| (finding.lint, &finding.message))
+            .collect();
+        sorted.sort();
+        assert!(
+            findings
+                .iter()
+                .map(|finding| (finding.lint, &finding.message))
+                .eq(sorted),
+            "findings are sorted by lint and message"
+        );
+
+        let findings = config.run(graph, &[MissingLicenseConfig::NAME.to_owned()]);
+        assert!(
+            findings
+                .iter()
+                .all(|finding| finding.lint == MissingLicenseConfig::NAME),
+            "only the selected lint is run"
+        );
+        assert_eq!(findings.len(), 4, "one finding per workspace member");
+
+        let config =
+            LintConfig::parse("[missing-license]\nlevel = \"allow\"").expect("config parsed");
+        assert!(
+            config
+                .run(graph, &[MissingLicenseConfig::NAME.to_owned()])
+                .is_empty(),
+            "allowed lints are skipped even if selected"
+        );
+    }
+
+    #[test]
+    fn duplicate_versions() {
+        let fixture = JsonFixture::metadata_dups();
+        let lint = DuplicateVersionsConfig::NAME;
+        assert_eq!(
+            run_lint("", fixture, lint),
+            [
+                "warning[duplicate-versions]: bytes has 2 versions (maximum 1): 0.3.0, 0.5.4",
+                "warning[duplicate-versions]: lazy_static has 2 versions (maximum 1): 0.2.11, \
+                 1.4.0",
+            ]
+        );
+        assert_eq!(
+            run_lint(
+                "[duplicate-versions]\nlevel = \"deny\"\nallow = [\"bytes\"]",
+                fixture,
+                lint
+            ),
+            ["error[duplicate-versions]: lazy_static has 2 versions (maximum 1): 0.2.11, 1.4.0"]
+        );
+        assert!(run_lint("[duplicate-versions]\nmax-versions = 2", fixture, lint).is_empty());
+    }
+
+    #[test]
+    fn path_version_mismatch() {
+        // This workspace has many path dependencies with version requirements, all of which
+        // match. In particular, requirements like "0.1" for 0.1.x versions aren't flagged.
+        let fixture = JsonFixture::metadata_guppy_78cb7e8();
+        assert!(run_lint("", fixture, PathVersionMismatchConfig::NAME).is_empty());
+    }
+
+    #[test]
+    fn missing_license() {
+        let fixture = JsonFixture::metadata_guppy_78cb7e8();
+        let lint = MissingLicenseConfig::NAME;
+        assert_eq!(
+            run_lint("", fixture, lint),
+            [
+                "error[missing-license]: cargo-compare doesn't specify `license` or \
+                 `license-file`",
+                "error[missing-license]: fixture-manager doesn't specify `license` or \
+                 `license-file`",
+                "error[missing-license]: guppy-cmdlib doesn't specify `license` or \
+                 `license-file`",
+            ]
+        );
+        let unpublished = run_lint(
+            "[missing-license]\ninclude-unpublished = true",
+            fixture,
+            lint,
+        );
+        assert!(
+            unpublished.len() > 3,
+            "unpublished members are also checked: {:?}",
+            unpublished
+        );
+    }
+
+    #[test]
+    fn dev_only_cycles() {
+        let fixture = JsonFixture::metadata_cycle2();
+        let lint = DevOnlyCyclesConfig::NAME;
+        assert_eq!(
+            run_lint("", fixture, lint),
+            [
+                "warning[dev-only-cycles]: dev-only cycle between lower-a 0.1.0, lower-b 0.1.0",
+                "warning[dev-only-cycles]: dev-only cycle between upper-a 0.1.0, upper-b 0.1.0",
+            ]
+        );
+        assert!(run_lint("", JsonFixture::metadata1(), lint).is_empty());
+    }
+
+    #[test]
+    fn unpublishable_deps() {
+        let lint = UnpublishableDepsConfig::NAME;
+        assert_eq!(
+            run_lint("", JsonFixture::metadata_builddep(), lint),
+            [
+                "error[unpublishable-deps]: main is publishable, but depends on builddep, which \
+                 is specified without a version"
+            ]
+        );
+        let findings = run_lint("", JsonFixture::metadata_libra(), lint);
+        assert!(
+            findings.contains(
+                &"error[unpublishable-deps]: transaction-builder is publishable, but depends \
+                  on vm, which has `publish = false`"
+                    .to_owned()
+            ),
+            "dependencies with publish = false found: {:?}",
+            findings
+        );
+    }
+
+    #[test]
+    fn requirement_conflicts() {
+        let fixture = JsonFixture::metadata1();
+        let lint = RequirementConflictsConfig::NAME;
+        assert_eq!(
+            run_lint("", fixture, lint),
+            [
+                "warning[requirement-conflicts]: testcrate declares datatest with different \
+                 version requirements: [dependencies] \"^0.4.2\", [build-dependencies] \
+                 \"^0.4.1\""
+            ]
+        );
+        assert!(run_lint(
+            "[requirement-conflicts]\nallow = [\"datatest\"]",
+            fixture,
+            lint
+        )
+        .is_empty());
+    }
+}
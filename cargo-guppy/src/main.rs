@@ -2,8 +2,11 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use cargo_guppy::{
-    CmdSelectOptions, DiffSummariesOptions, DupsOptions, MvOptions, ResolveCargoOptions,
-    SubtreeSizeOptions,
+    AttributionsOptions, CmdDiffOptions, CmdSelectOptions, CyclesOptions, DevOnlyDepsOptions,
+    DiffSummariesOptions, DupsOptions, ErrorFormat, FeatureClosureOptions, JsonDiagnostic,
+    LayersOptions, LintOptions, MetadataTableOptions, MsrvOptions, MvOptions, OutdatedPathsOptions,
+    OutputContext, OutputOpts, ResolveCargoOptions, SbomOptions, SubtreeSizeOptions,
+    UnusedFeaturesOptions,
 };
 use clap::Parser;
 use color_eyre::Result;
@@ -11,6 +14,13 @@ use color_eyre::Result;
 #[derive(Debug, Parser)]
 #[structopt(about = "Cargo.lock file analysis")]
 struct Args {
+    #[clap(long, global = true, arg_enum, default_value = "human")]
+    /// Format for reporting errors
+    error_format: ErrorFormat,
+
+    #[clap(flatten)]
+    output: OutputOpts,
+
     #[clap(subcommand)]
     cmd: Command,
 }
@@ -20,18 +30,50 @@ struct Args {
 enum Command {
     #[structopt(name = "diff")]
     /// Perform a diff of two cargo metadata JSON files
-    Diff {
-        #[structopt(long)]
-        json: bool,
-        old: String,
-        new: String,
-    },
+    ///
+    /// Reports added, removed and updated packages, changes to the features declared by packages,
+    /// and added and removed dependency edges.
+    ///
+    /// With `--from-rev` and `--to-rev`, the workspace is checked out at both Git revisions in
+    /// temporary worktrees, and metadata is generated for each instead.
+    Diff(CmdDiffOptions),
     #[structopt(name = "diff-summaries")]
     /// Diff two guppy summaries
     DiffSummaries(DiffSummariesOptions),
     #[structopt(name = "dups")]
     /// Print the number of duplicate packages
     Duplicates(DupsOptions),
+    #[structopt(name = "dev-only-deps")]
+    /// Print packages that are only required by tests, benchmarks and examples
+    ///
+    /// A package is dev-only if it is only reachable from the workspace through dev-dependencies,
+    /// or through workspace packages that only have test, benchmark or example targets.
+    DevOnlyDeps(DevOnlyDepsOptions),
+    #[structopt(name = "cycles")]
+    /// Print dependency cycles, along with the links that form them
+    ///
+    /// Cargo only permits cycles that go through at least one dev-dependency. Cycles that don't
+    /// are reported as normal cycles.
+    Cycles(CyclesOptions),
+    #[structopt(name = "metadata-table")]
+    /// Print a section of the `[package.metadata]` table for every workspace member
+    ///
+    /// The output is a JSON object mapping the names of workspace members that have the section to
+    /// its contents.
+    MetadataTable(MetadataTableOptions),
+    #[structopt(name = "unused-features")]
+    /// Print named features of workspace members that aren't enabled by workspace builds
+    ///
+    /// Simulates `cargo build --workspace` and `cargo test --workspace` with default features.
+    /// Features that aren't enabled by either build, e.g. ones that are only enabled by
+    /// `--all-features`, are candidates for deletion.
+    UnusedFeatures(UnusedFeaturesOptions),
+    #[structopt(name = "feature-closure")]
+    /// Print the features transitively enabled by a feature, across all packages
+    ///
+    /// The feature is specified as `package/feature`. This is useful for auditing which packages
+    /// and features a heavy named feature pulls in. Dev-dependencies aren't followed.
+    FeatureClosure(FeatureClosureOptions),
     #[structopt(name = "resolve-cargo")]
     /// Return packages and features that would be built by Cargo
     ResolveCargo(ResolveCargoOptions),
@@ -40,13 +82,56 @@ enum Command {
     Select(CmdSelectOptions),
     #[structopt(name = "subtree-size")]
     /// Print a list of dependencies along with their unique subtree size
+    ///
+    /// Dependencies are counted as one package each by default. With --weight or --weights-file,
+    /// they're weighted by crate size, lines of code or user-supplied weights instead.
     SubtreeSize(SubtreeSizeOptions),
+    #[structopt(name = "lint")]
+    /// Check the workspace for common problems
+    ///
+    /// Checks for duplicate dependency versions, path dependencies with mismatched versions,
+    /// members without a license, dependency cycles, unpublishable dependencies of publishable
+    /// members, and dependencies declared several times with different version requirements,
+    /// default features or features. Each lint can be configured through a TOML file. Exits with a
+    /// non-zero code if a denied lint fails.
+    Lint(LintOptions),
+    #[structopt(name = "outdated-paths")]
+    /// Print path dependencies of workspace members with outdated version requirements
+    ///
+    /// A requirement is outdated if it doesn't match the current version of the path dependency.
+    /// Exits with a non-zero code if any are found, unless `--fix` is passed to update the
+    /// requirements in place.
+    OutdatedPaths(OutdatedPathsOptions),
+    #[structopt(name = "msrv")]
+    /// Print third-party dependencies that require a newer Rust version than the workspace
+    ///
+    /// Dependencies are checked against the `rust-version` field in their manifests. Exits with a
+    /// non-zero code if any of them require a Rust version newer than `--max`.
+    Msrv(MsrvOptions),
+    #[structopt(name = "layers")]
+    /// Check dependencies between layers of workspace members
+    ///
+    /// Layers group workspace members, and list the other layers their members may depend on. This
+    /// can be used to enforce an architecture, e.g. that low-level crates don't depend on
+    /// higher-level ones.
+    Layers(LayersOptions),
     #[structopt(name = "mv")]
     /// Move packages to another location, fixing up workspace paths
     ///
     /// The source directories must be crates, and the destination must be within the same
     /// workspace.
     Mv(MvOptions),
+    #[structopt(name = "attributions")]
+    /// Collect license and notice files for packages that would be built by Cargo
+    ///
+    /// Files are grouped by license. The output format can be customized with templates.
+    Attributions(AttributionsOptions),
+    #[structopt(name = "sbom")]
+    /// Generate a software bill of materials for packages that would be built by Cargo
+    ///
+    /// Packages are listed with their sources, licenses and checksums from Cargo.lock, in the
+    /// CycloneDX or SPDX JSON format.
+    Sbom(SbomOptions),
 }
 
 // On Unix-like operating systems, the executable name of the Cargo subcommand usually doesn't have
@@ -78,15 +163,51 @@ fn args() -> impl Iterator<Item = String> {
 fn main() -> Result<()> {
     color_eyre::install()?;
 
-    let args = Args::parse_from(args());
+    let args = match Args::try_parse_from(args()) {
+        Ok(args) => args,
+        // clap exits with 2 for invalid arguments, which is reserved for violations.
+        Err(err) if err.use_stderr() => {
+            // Printing to stderr may itself fail, but there's nothing to be done about that.
+            let _ = err.print();
+            std::process::exit(cargo_guppy::EXIT_ERROR);
+        }
+        // --help and --version.
+        Err(err) => err.exit(),
+    };
+    let error_format = args.error_format;
+
+    let output = args.output.init();
+
+    let report = match exec(args.cmd, output) {
+        Ok(()) => return Ok(()),
+        Err(report) => report,
+    };
+    match error_format {
+        ErrorFormat::Human => eprintln!("Error: {:?}", report),
+        ErrorFormat::Json => eprintln!("{}", JsonDiagnostic::new(&report).to_json()),
+    }
+    std::process::exit(cargo_guppy::exit_code(&report));
+}
 
-    match args.cmd {
-        Command::Diff { json, old, new } => cargo_guppy::cmd_diff(json, &old, &new),
+fn exec(cmd: Command, output: OutputContext) -> Result<()> {
+    match cmd {
+        Command::Diff(ref options) => cargo_guppy::cmd_diff(options, &output),
         Command::DiffSummaries(options) => options.exec(),
-        Command::Duplicates(ref options) => cargo_guppy::cmd_dups(options),
-        Command::ResolveCargo(ref options) => cargo_guppy::cmd_resolve_cargo(options),
-        Command::Select(ref options) => cargo_guppy::cmd_select(options),
-        Command::SubtreeSize(ref options) => cargo_guppy::cmd_subtree_size(options),
-        Command::Mv(ref options) => options.exec(),
+        Command::Duplicates(ref options) => cargo_guppy::cmd_dups(options, &output),
+        Command::DevOnlyDeps(ref options) => options.exec(),
+        Command::Cycles(ref options) => options.exec(),
+        Command::MetadataTable(ref options) => options.exec(),
+        Command::UnusedFeatures(ref options) => options.exec(),
+        Command::FeatureClosure(ref options) => options.exec(&output),
+        Command::ResolveCargo(ref options) => cargo_guppy::cmd_resolve_cargo(options, &output),
+        Command::Select(ref options) => cargo_guppy::cmd_select(options, &output),
+        Command::SubtreeSize(ref options) => cargo_guppy::cmd_subtree_size(options, &output),
+        Command::Lint(ref options) => options.exec(),
+        Command::OutdatedPaths(ref options) => options.exec(&output),
+        Command::Msrv(ref options) => options.exec(&output),
+        Command::Layers(ref options) => options.exec(&output),
+        Command::Mv(ref options) => options.exec(&output),
+        Command::Attributions(ref options) => options.exec(),
+        Command::Sbom(ref options) => options.exec(),
     }
 }
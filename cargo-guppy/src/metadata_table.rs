@@ -0,0 +1,45 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy_cmdlib::{CargoMetadataOptions, FilterExprOptions};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+#[derive(Debug, Parser)]
+pub struct MetadataTableOptions {
+    #[clap(long)]
+    /// The key of the section to print, e.g. "foo" for `[package.metadata.foo]`
+    key: String,
+
+    #[clap(flatten)]
+    filter_expr_opts: FilterExprOptions,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl MetadataTableOptions {
+    pub fn exec(&self) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        // Workspace member names are unique, so key the output by name.
+        let sections = pkg_graph
+            .workspace()
+            .metadata_section::<Value>(&self.key)
+            .filter(|(package, _)| self.filter_expr_opts.matches(*package))
+            .map(|(package, section)| Ok((package.name(), section?)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        serde_json::to_writer_pretty(&mut stdout, &sections)?;
+        writeln!(stdout)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{diagnostics::Violation, output::OutputContext};
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy::graph::MsrvViolation;
+use guppy_cmdlib::CargoMetadataOptions;
+use owo_colors::OwoColorize;
+use semver::Version;
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Debug, Parser)]
+pub struct MsrvOptions {
+    /// Minimum supported Rust version of the workspace, e.g. 1.70
+    #[clap(long = "max", value_name = "VERSION", parse(try_from_str = parse_rust_version))]
+    max: Version,
+
+    /// Output the list of dependencies as JSON
+    #[clap(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl MsrvOptions {
+    pub fn exec(&self, output: &OutputContext) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let mut violations: Vec<_> = pkg_graph.msrv_violations(&self.max).collect();
+        violations.sort_by_key(|violation| {
+            let package = violation.package();
+            (package.name(), package.version())
+        });
+
+        if self.json {
+            let violations: Vec<_> = violations.iter().map(MsrvDependency::new).collect();
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            serde_json::to_writer_pretty(&mut stdout, &violations)?;
+            writeln!(stdout)?;
+        } else {
+            let styles = &output.styles;
+            for violation in &violations {
+                let package = violation.package();
+                println!(
+                    "{} {} requires Rust {}",
+                    package.name().style(styles.package_name),
+                    package.version().style(styles.package_version),
+                    violation.rust_version().style(styles.removed),
+                );
+            }
+        }
+
+        if !violations.is_empty() {
+            return Err(Violation::new(format!(
+                "{} dependencies require a Rust version newer than {}",
+                violations.len(),
+                self.max
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Parses a Rust version, which may leave out the minor and patch components like `rust-version`
+/// in `Cargo.toml`.
+fn parse_rust_version(s: &str) -> Result<Version, semver::Error> {
+    match s.split('.').count() {
+        1 => Version::parse(&format!("{}.0.0", s)),
+        2 => Version::parse(&format!("{}.0", s)),
+        _ => Version::parse(s),
+    }
+}
+
+/// A dependency that requires a newer Rust version than the workspace supports.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MsrvDependency<'g> {
+    name: &'g str,
+    version: String,
+    rust_version: String,
+    source: String,
+}
+
+impl<'g> MsrvDependency<'g> {
+    fn new(violation: &MsrvViolation<'g>) -> Self {
+        let package = violation.package();
+        Self {
+            name: package.name(),
+            version: package.version().to_string(),
+            rust_version: violation.rust_version().to_string(),
+            source: package.source().to_string(),
+        }
+    }
+}
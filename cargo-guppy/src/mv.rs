@@ -1,21 +1,25 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use crate::output::{OutputContext, Styles};
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::Parser;
 use color_eyre::eyre::{bail, eyre, Result, WrapErr};
 use dialoguer::Confirm;
 use guppy::graph::{PackageGraph, PackageLink, PackageMetadata};
 use guppy_cmdlib::CargoMetadataOptions;
+use guppy_edit::ManifestEditor;
+use log::debug;
+use owo_colors::OwoColorize;
 use pathdiff::diff_utf8_paths;
+use serde::Serialize;
 use std::{
     collections::{btree_map::Entry, BTreeMap, HashSet},
     fmt, fs,
     io::{self, Write},
-    mem,
     path::{Path, MAIN_SEPARATOR},
 };
-use toml_edit::{Document, Item, Table, Value};
+use toml_edit::Document;
 
 #[derive(Debug, Parser)]
 pub struct MvOptions {
@@ -27,19 +31,22 @@ pub struct MvOptions {
     #[clap(name = "DEST")]
     dest_dir: Utf8PathBuf,
 
-    /// Print out operations instead of performing them
+    /// Print out operations and a diff of manifest changes instead of performing them
     #[clap(long)]
     dry_run: bool,
 
+    /// Output the list of operations as JSON instead of performing them
+    #[clap(long)]
+    json: bool,
+
     #[clap(flatten)]
     metadata_opts: CargoMetadataOptions,
 }
 
 impl MvOptions {
-    pub fn exec(&self) -> Result<()> {
+    pub fn exec(&self, output: &OutputContext) -> Result<()> {
         // Construct a package graph.
-        let command = self.metadata_opts.make_command();
-        let pkg_graph = command.build_graph()?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
         let workspace_root = pkg_graph.workspace().root();
 
         let dest_dir = DestDir::new(&pkg_graph, &self.dest_dir)?;
@@ -113,25 +120,84 @@ impl MvOptions {
             }
         }
 
-        println!("Will perform edits:");
+        if self.json {
+            let plan = MovePlan::new(workspace_root, &manifest_edits, &src_moves);
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            serde_json::to_writer_pretty(&mut stdout, &plan)?;
+            writeln!(stdout)?;
+            return Ok(());
+        }
+
+        let styles = &output.styles;
+        if !output.quiet {
+            println!("{}", "Will perform edits:".style(styles.heading));
+            for (manifest_path, edits) in &manifest_edits {
+                println!(
+                    "manifest: {}",
+                    diff_utf8_paths(manifest_path, workspace_root)
+                        .unwrap()
+                        .style(styles.path)
+                );
+                for edit in edits {
+                    println!("  * {}", edit);
+                }
+            }
+
+            println!("\n{}", "Moves:".style(styles.heading));
+            for (src_dir, package_move) in &src_moves {
+                println!(
+                    "  * move {} to {}",
+                    src_dir.style(styles.path),
+                    package_move.new_path.style(styles.path),
+                );
+            }
+
+            println!();
+        }
+        debug!(
+            "{} manifests to edit, {} directories to move",
+            manifest_edits.len(),
+            src_moves.len()
+        );
+
+        // Compute the new contents of every manifest. The edits are performed first so that
+        // manifest paths are still valid.
+        let mut manifest_contents = BTreeMap::new();
         for (manifest_path, edits) in &manifest_edits {
-            println!(
-                "manifest: {}",
-                diff_utf8_paths(manifest_path, workspace_root).unwrap()
-            );
+            let mut editor = ManifestEditor::read(*manifest_path)?;
             for edit in edits {
-                println!("  * {}", edit);
+                editor
+                    .set_dependency_path(edit.link.dep_name(), &edit.edit_path)
+                    .wrap_err_with(|| eyre!("error while applying edits to {}", manifest_path))?;
             }
+            manifest_contents.insert(
+                manifest_path.to_path_buf(),
+                (editor.original().to_owned(), editor.to_string()),
+            );
         }
 
-        println!("\nMoves:");
-        for (src_dir, package_move) in &src_moves {
-            println!("  * move {} to {}", src_dir, package_move.new_path,);
-        }
-
-        println!();
+        // Next, update the root manifest, which may have been edited above. Do this before moving
+        // directories because this relies on the old directories existing.
+        let root_manifest_path = workspace_root.join("Cargo.toml");
+        let (original, current) = match manifest_contents.remove(&root_manifest_path) {
+            Some(contents) => contents,
+            None => {
+                let original = read_manifest(&root_manifest_path)?;
+                (original.clone(), original)
+            }
+        };
+        let mut editor = ManifestEditor::parse(&root_manifest_path, current)?;
+        update_root_toml(workspace_root, editor.document_mut(), &src_moves)
+            .wrap_err_with(|| eyre!("error while updating root toml at {}", workspace_root))?;
+        manifest_contents.insert(root_manifest_path, (original, editor.to_string()));
+        manifest_contents.retain(|_, (original, new)| original != new);
 
         if self.dry_run {
+            for (manifest_path, (original, new)) in &manifest_contents {
+                let rel_path = diff_utf8_paths(manifest_path, workspace_root).unwrap();
+                print_diff(&rel_path, original, new, styles);
+            }
             return Ok(());
         }
 
@@ -141,16 +207,11 @@ impl MvOptions {
             .interact()?;
 
         if perform {
-            // First perform the edits so that manifest paths are still valid.
-            for (manifest_path, edits) in &manifest_edits {
-                apply_edits(manifest_path, edits)?;
+            for (manifest_path, (_, new)) in &manifest_contents {
+                fs::write(manifest_path, new)
+                    .wrap_err_with(|| eyre!("error while writing manifest {}", manifest_path))?;
             }
 
-            // Next, update the root manifest. Do this before moving directories because this relies
-            // on the old directories existing.
-            update_root_toml(workspace_root, &src_moves)
-                .wrap_err_with(|| eyre!("error while updating root toml at {}", workspace_root))?;
-
             // Finally, move directories into their new spots.
             // Rely on the fact that BTreeMap is sorted so that "foo" always shows up before
             // "foo/bar".
@@ -356,105 +417,96 @@ impl<'g> fmt::Display for ManifestEdit<'g> {
     }
 }
 
-fn apply_edits(manifest_path: &Utf8Path, edits: &[ManifestEdit<'_>]) -> Result<()> {
-    let mut document = read_toml(manifest_path)?;
-    let table = document.as_table_mut();
-
-    // Grab the list of target specs.
-    let all_targets = match table.get("target") {
-        Some(target_section) => match target_section.as_table() {
-            Some(table) => table.iter().map(|(target, _)| target.to_string()).collect(),
-            None => {
-                bail!("in {}, [target] is not a table", manifest_path);
-            }
-        },
-        None => {
-            // There's no 'target' section in the manifest.
-            Vec::new()
-        }
-    };
-
-    // Search through:
-    // * dependencies, build-dependencies, dev-dependencies
-    // * [target.'foo'.dependencies], .build-dependencies and .dev-dependencies
-    for edit in edits {
-        apply_edit(table, edit)
-            .wrap_err_with(|| eyre!("error while applying edits to {}", manifest_path))?;
-        for target in &all_targets {
-            let target_table = match &mut table["target"][target] {
-                Item::Table(target_table) => target_table,
-                _ => {
-                    // Not a table, skip it.
-                    continue;
-                }
-            };
-            apply_edit(target_table, edit).wrap_err_with(|| {
-                eyre!(
-                    "error while applying edits to {}, section [target.'{}']",
-                    manifest_path,
-                    target
-                )
-            })?;
-        }
-    }
-
-    fs::write(manifest_path, document.to_string())
-        .wrap_err_with(|| eyre!("error while writing manifest {}", manifest_path))?;
-
-    Ok(())
+/// A machine-readable list of the operations performed by `cargo guppy mv`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MovePlan<'a> {
+    manifest_edits: Vec<ManifestEditPlan<'a>>,
+    moves: Vec<MoveEntry<'a>>,
 }
 
-fn apply_edit(table: &mut Table, edit: &ManifestEdit<'_>) -> Result<()> {
-    static SECTION_NAMES: &[&str] = &["dependencies", "build-dependencies", "dev-dependencies"];
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct ManifestEditPlan<'a> {
+    manifest: Utf8PathBuf,
+    edits: Vec<EditEntry<'a>>,
+}
 
-    let dep_name = edit.link.dep_name();
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct EditEntry<'a> {
+    dep_name: &'a str,
+    path: &'a Utf8Path,
+}
 
-    for &section_name in SECTION_NAMES {
-        let section = &mut table[section_name];
-        let section_table = match section {
-            Item::None => {
-                // This section is empty -- skip it.
-                continue;
-            }
-            Item::Table(table) => table,
-            Item::Value(_) | Item::ArrayOfTables(_) => {
-                bail!("section [{}] is not a table", section_name);
-            }
-        };
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct MoveEntry<'a> {
+    from: &'a Utf8Path,
+    to: &'a Utf8Path,
+}
 
-        match section_table.get_mut(dep_name) {
-            Some(item) => {
-                let dep_table = item.as_table_like_mut().ok_or_else(|| {
-                    eyre!("in section [{}], {} is not a table", section_name, dep_name)
-                })?;
-                // The dep table should have a path entry.
-                match dep_table.get_mut("path") {
-                    Some(Item::Value(value)) => {
-                        replace_decorated(value, edit.edit_path.as_str());
-                    }
-                    _ => {
-                        bail!(
-                            "in section [{}], {}.path is not a string",
-                            section_name,
-                            dep_name
-                        );
-                    }
-                }
-            }
-            None => continue,
+impl<'a> MovePlan<'a> {
+    fn new(
+        workspace_root: &Utf8Path,
+        manifest_edits: &'a BTreeMap<&Utf8Path, Vec<ManifestEdit<'_>>>,
+        src_moves: &'a BTreeMap<&Utf8Path, PackageMove<'_>>,
+    ) -> Self {
+        let manifest_edits = manifest_edits
+            .iter()
+            .map(|(manifest_path, edits)| ManifestEditPlan {
+                manifest: diff_utf8_paths(manifest_path, workspace_root).unwrap(),
+                edits: edits
+                    .iter()
+                    .map(|edit| EditEntry {
+                        dep_name: edit.link.dep_name(),
+                        path: &edit.edit_path,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let moves = src_moves
+            .iter()
+            .map(|(src_dir, package_move)| MoveEntry {
+                from: src_dir,
+                to: &package_move.new_path,
+            })
+            .collect();
+        Self {
+            manifest_edits,
+            moves,
         }
     }
+}
 
-    Ok(())
+/// Prints a unified diff between the old and new contents of a manifest.
+pub(crate) fn print_diff(rel_path: &Utf8Path, original: &str, new: &str, styles: &Styles) {
+    let patch = diffy::create_patch(original, new).to_string();
+    // diffy doesn't support custom file names, so replace its header with one that tools like
+    // `git apply` understand.
+    let hunks = patch
+        .strip_prefix("--- original\n+++ modified\n")
+        .unwrap_or(&patch);
+    println!(
+        "{}",
+        format_args!("--- a/{}", rel_path).style(styles.removed)
+    );
+    println!("{}", format_args!("+++ b/{}", rel_path).style(styles.added));
+    for line in hunks.lines() {
+        match line.as_bytes().first() {
+            Some(b'+') => println!("{}", line.style(styles.added)),
+            Some(b'-') => println!("{}", line.style(styles.removed)),
+            Some(b'@') => println!("{}", line.style(styles.package_version)),
+            _ => println!("{}", line),
+        }
+    }
 }
 
 fn update_root_toml(
     workspace_root: &Utf8Path,
+    document: &mut Document,
     src_moves: &BTreeMap<&Utf8Path, PackageMove<'_>>,
 ) -> Result<()> {
-    let root_manifest_path = workspace_root.join("Cargo.toml");
-    let mut document = read_toml(&root_manifest_path)?;
-
     // Fix up paths in workspace.members or workspace.default-members.
     let workspace_table = match document.as_table_mut().get_mut("workspace") {
         Some(item) => item
@@ -505,32 +557,10 @@ fn update_root_toml(
         }
     }
 
-    let mut out = fs::File::create(&root_manifest_path)
-        .wrap_err_with(|| eyre!("Error while opening {}", root_manifest_path))?;
-    write!(out, "{}", document)?;
-
     Ok(())
 }
 
-fn read_toml(manifest_path: &Utf8Path) -> Result<Document> {
-    let toml = fs::read_to_string(manifest_path)
-        .wrap_err_with(|| eyre!("error while reading manifest {}", manifest_path))?;
-    toml.parse::<Document>()
-        .wrap_err_with(|| eyre!("error while parsing manifest {}", manifest_path))
-}
-
-/// Replace the value while retaining the decor.
-fn replace_decorated(dest: &mut Value, new_value: impl Into<Value>) -> Value {
-    let decor = dest.decor();
-    let mut new_value = new_value.into();
-    // Copy over the decor from dest into new_value.
-    let new_decor = new_value.decor_mut();
-    if let Some(prefix) = decor.prefix() {
-        new_decor.set_prefix(prefix);
-    }
-    if let Some(suffix) = decor.suffix() {
-        new_decor.set_suffix(suffix);
-    }
-
-    mem::replace(dest, new_value)
+fn read_manifest(manifest_path: &Utf8Path) -> Result<String> {
+    fs::read_to_string(manifest_path)
+        .wrap_err_with(|| eyre!("error while reading manifest {}", manifest_path))
 }
@@ -0,0 +1,334 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{diagnostics::Violation, mv::print_diff, output::OutputContext};
+use camino::Utf8Path;
+use clap::Parser;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use guppy::graph::{PackageGraph, PackageLink};
+use guppy_cmdlib::CargoMetadataOptions;
+use guppy_edit::{toml_edit::Item, ManifestEditor};
+use owo_colors::OwoColorize;
+use pathdiff::diff_utf8_paths;
+use semver::{Version, VersionReq};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+#[derive(Debug, Parser)]
+pub struct OutdatedPathsOptions {
+    /// Update outdated version requirements in manifests
+    #[clap(long)]
+    fix: bool,
+
+    /// With --fix, print a diff of manifest changes instead of writing them out
+    #[clap(long, requires = "fix")]
+    dry_run: bool,
+
+    /// Output the list of outdated requirements as JSON
+    #[clap(long, conflicts_with = "fix")]
+    json: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl OutdatedPathsOptions {
+    pub fn exec(&self, output: &OutputContext) -> Result<()> {
+        let pkg_graph = self.metadata_opts.build_graph()?;
+        let outdated = outdated_links(&pkg_graph);
+
+        if self.fix {
+            return self.fix(&pkg_graph, &outdated, output);
+        }
+
+        if self.json {
+            let outdated: Vec<_> = outdated.iter().map(OutdatedPath::new).collect();
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            serde_json::to_writer_pretty(&mut stdout, &outdated)?;
+            writeln!(stdout)?;
+        } else {
+            let styles = &output.styles;
+            for link in &outdated {
+                println!(
+                    "{} depends on {} with requirement {}, but its version is {}",
+                    link.from().name().style(styles.package_name),
+                    link.to().name().style(styles.package_name),
+                    link.version_req().style(styles.removed),
+                    link.to().version().style(styles.package_version),
+                );
+            }
+        }
+
+        if !outdated.is_empty() {
+            return Err(Violation::new(format!(
+                "{} path dependencies have outdated version requirements (update them with --fix)",
+                outdated.len()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    fn fix(
+        &self,
+        pkg_graph: &PackageGraph,
+        outdated: &[PackageLink<'_>],
+        output: &OutputContext,
+    ) -> Result<()> {
+        let workspace_root = pkg_graph.workspace().root();
+
+        let mut manifest_links: BTreeMap<&Utf8Path, Vec<&PackageLink<'_>>> = BTreeMap::new();
+        for link in outdated {
+            manifest_links
+                .entry(link.from().manifest_path())
+                .or_default()
+                .push(link);
+        }
+
+        let mut fix_count = 0;
+        let mut editors = Vec::with_capacity(manifest_links.len());
+        for (manifest_path, links) in manifest_links {
+            let mut editor = ManifestEditor::read(manifest_path)?;
+            for link in links {
+                fix_count += fix_dependency(&mut editor, link.dep_name(), link.to().version())
+                    .wrap_err_with(|| eyre!("error while applying edits to {}", manifest_path))?;
+            }
+            editors.push(editor);
+        }
+
+        for editor in &editors {
+            if !editor.is_changed() {
+                continue;
+            }
+            if self.dry_run {
+                let rel_path = diff_utf8_paths(editor.manifest_path(), workspace_root)
+                    .expect("manifest paths are absolute");
+                print_diff(
+                    &rel_path,
+                    editor.original(),
+                    &editor.to_string(),
+                    &output.styles,
+                );
+            } else {
+                editor.write()?;
+            }
+        }
+
+        if !self.dry_run && !output.quiet {
+            println!(
+                "updated {} version requirements in {} manifests",
+                fix_count,
+                editors.iter().filter(|editor| editor.is_changed()).count(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns links from workspace members to path dependencies whose version requirements don't
+/// match the version of the dependency, sorted by package name.
+///
+/// Shared with the `path-version-mismatch` lint.
+pub(crate) fn outdated_links(pkg_graph: &PackageGraph) -> Vec<PackageLink<'_>> {
+    let mut outdated: Vec<_> = pkg_graph
+        .workspace()
+        .iter()
+        .flat_map(|package| package.direct_links())
+        .filter(|link| {
+            let to = link.to();
+            to.source().is_local() && is_outdated(link.version_req(), to.version())
+        })
+        .collect();
+    outdated.sort_by_key(|link| (link.from().name(), link.to().name()));
+    outdated
+}
+
+/// Updates every outdated requirement for `dep_name` in the manifest to match `version`. Returns
+/// the number of requirements updated.
+///
+/// A link only records one of the requirements if a dependency is listed in several sections, so
+/// each section is checked separately.
+fn fix_dependency(editor: &mut ManifestEditor, dep_name: &str, version: &Version) -> Result<usize> {
+    let mut count = 0;
+    for section in editor.sections()? {
+        let old_req = match editor.get_dependency(&section, dep_name)? {
+            Some(item) => match version_req_str(item) {
+                Some(old_req) => old_req.to_owned(),
+                None => continue,
+            },
+            None => continue,
+        };
+        let outdated = VersionReq::parse(&old_req)
+            .map(|req| is_outdated(&req, version))
+            .unwrap_or(true);
+        if outdated {
+            editor.set_dependency_version(
+                &section,
+                dep_name,
+                &fixed_version_req(&old_req, version),
+            )?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Returns true if the requirement doesn't match `version`.
+///
+/// Requirements only match pre-release versions if they name a pre-release of the same
+/// `major.minor.patch`, so a pre-release version is also checked against its release version: a
+/// path dependency on `1.1.0-alpha.1` with the requirement `"1.0"` isn't outdated.
+///
+/// A dependency without a requirement isn't outdated. (It can't be published at all.)
+fn is_outdated(req: &VersionReq, version: &Version) -> bool {
+    if req.comparators.is_empty() || req.matches(version) {
+        return false;
+    }
+    if version.pre.is_empty() {
+        return true;
+    }
+    !req.matches(&Version::new(version.major, version.minor, version.patch))
+}
+
+/// Returns the version requirement in a dependency entry, if it has one.
+fn version_req_str(item: &Item) -> Option<&str> {
+    match item.as_str() {
+        Some(req) => Some(req),
+        None => item.as_table_like()?.get("version")?.as_str(),
+    }
+}
+
+/// Returns a requirement that matches `version`. Exact requirements are kept exact.
+fn fixed_version_req(old_req: &str, version: &Version) -> String {
+    if old_req.trim_start().starts_with('=') {
+        format!("={}", version)
+    } else {
+        version.to_string()
+    }
+}
+
+/// A path dependency with an outdated version requirement.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct OutdatedPath<'g> {
+    from: &'g str,
+    to: &'g str,
+    dep_name: &'g str,
+    version_req: String,
+    version: String,
+    manifest_path: &'g Utf8Path,
+}
+
+impl<'g> OutdatedPath<'g> {
+    fn new(link: &PackageLink<'g>) -> Self {
+        Self {
+            from: link.from().name(),
+            to: link.to().name(),
+            dep_name: link.dep_name(),
+            version_req: link.version_req().to_string(),
+            version: link.to().version().to_string(),
+            manifest_path: link.from().manifest_path(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outdated_requirements() {
+        let cases = [
+            // (requirement, version, outdated)
+            ("0.1", "0.1.0", false),
+            ("0.1.0", "0.1.3", false),
+            ("=0.1.0", "0.1.0", false),
+            ("1.0.2", "1.1.0", false),
+            (">=0.5, <2", "1.0.0", false),
+            ("0.0.1", "0.1.0", true),
+            ("=0.1.0", "0.1.1", true),
+            ("2", "1.0.0", true),
+            // Pre-release versions.
+            ("1.0.0-alpha", "1.0.0-alpha", false),
+            ("1.0.0-alpha", "1.0.0-alpha.2", false),
+            ("1.0", "1.1.0-alpha.1", false),
+            ("2", "1.1.0-alpha.1", true),
+            // No requirement.
+            ("*", "0.1.0-alpha", false),
+        ];
+        for (req, version, outdated) in cases {
+            let version_req = if req == "*" {
+                VersionReq::STAR
+            } else {
+                VersionReq::parse(req).expect("valid requirement")
+            };
+            let version = Version::parse(version).expect("valid version");
+            assert_eq!(
+                is_outdated(&version_req, &version),
+                outdated,
+                "requirement {} for version {}",
+                req,
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn fix_outdated_requirements() {
+        let manifest = r#"[package]
+name = "side"
+version = "0.1.0"
+
+[dependencies]
+internal = { path = "../internal", version = "0.1" }
+other = "0.1"
+
+[dev-dependencies]
+internal = { path = "../internal", version = "=0.1.0", features = ["test"] } # exact
+
+[target.'cfg(unix)'.dependencies]
+internal = { path = "../internal", version = "0.2" }
+
+[build-dependencies]
+internal = { path = "../internal" }
+"#;
+        let mut editor =
+            ManifestEditor::parse("side/Cargo.toml", manifest).expect("manifest parsed");
+        let version = Version::new(0, 2, 0);
+        assert_eq!(
+            fix_dependency(&mut editor, "internal", &version).expect("fix succeeded"),
+            2,
+            "outdated requirements in two sections fixed"
+        );
+        assert_eq!(
+            editor.to_string(),
+            r#"[package]
+name = "side"
+version = "0.1.0"
+
+[dependencies]
+internal = { path = "../internal", version = "0.2.0" }
+other = "0.1"
+
+[dev-dependencies]
+internal = { path = "../internal", version = "=0.2.0", features = ["test"] } # exact
+
+[target.'cfg(unix)'.dependencies]
+internal = { path = "../internal", version = "0.2" }
+
+[build-dependencies]
+internal = { path = "../internal" }
+"#
+        );
+
+        assert_eq!(
+            fix_dependency(&mut editor, "internal", &version).expect("fix succeeded"),
+            0,
+            "fixing is idempotent"
+        );
+    }
+}
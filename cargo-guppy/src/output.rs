@@ -0,0 +1,214 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Output handling shared across commands.
+
+use clap::Parser;
+use env_logger::fmt::Formatter;
+use log::{Level, LevelFilter, Record};
+use owo_colors::{OwoColorize, Stream, Style};
+use std::{io::Write, sync::Arc};
+
+/// Options controlling color, verbosity and logging for all commands.
+#[derive(Debug, Parser)]
+#[must_use]
+pub struct OutputOpts {
+    /// Suppress informational output
+    #[clap(
+        name = "outputquiet",
+        global = true,
+        long = "quiet",
+        short = 'q',
+        conflicts_with = "outputverbose"
+    )]
+    pub quiet: bool,
+
+    /// Produce extra output
+    #[clap(
+        name = "outputverbose",
+        global = true,
+        long = "verbose",
+        short = 'v',
+        conflicts_with = "outputquiet"
+    )]
+    pub verbose: bool,
+
+    /// Produce color output
+    #[clap(
+        long,
+        global = true,
+        default_value = "auto",
+        possible_values = &["auto", "always", "never"],
+    )]
+    pub color: Color,
+}
+
+impl OutputOpts {
+    /// Initializes color support and logging, and returns a context for commands to use.
+    ///
+    /// Logs go to standard error. `--quiet` and `--verbose` set the default level, which can be
+    /// overridden with the `RUST_LOG` environment variable.
+    pub fn init(self) -> OutputContext {
+        let OutputOpts {
+            quiet,
+            verbose,
+            color,
+        } = self;
+        let level = if quiet {
+            LevelFilter::Error
+        } else if verbose {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        };
+
+        color.init_colored();
+
+        let mut styles = Styles::default();
+        if color.is_enabled(Stream::Stdout) {
+            styles.colorize();
+        }
+
+        env_logger::Builder::new()
+            .filter_level(level)
+            .parse_default_env()
+            .format(format_fn)
+            .init();
+
+        OutputContext {
+            quiet,
+            verbose,
+            color,
+            styles: Arc::new(styles),
+        }
+    }
+}
+
+/// Output settings passed into commands.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct OutputContext {
+    /// True if informational output should be suppressed.
+    pub quiet: bool,
+    /// True if extra output should be produced.
+    pub verbose: bool,
+    /// Whether to produce color output.
+    pub color: Color,
+    /// Styles for output to standard output.
+    pub styles: Arc<Styles>,
+}
+
+fn format_fn(f: &mut Formatter, record: &Record<'_>) -> std::io::Result<()> {
+    match record.level() {
+        Level::Error => writeln!(
+            f,
+            "{} {}",
+            "error:".if_supports_color(Stream::Stderr, |s| s.style(Style::new().bold().red())),
+            record.args()
+        ),
+        Level::Warn => writeln!(
+            f,
+            "{} {}",
+            "warning:".if_supports_color(Stream::Stderr, |s| s.style(Style::new().bold().yellow())),
+            record.args()
+        ),
+        Level::Info => writeln!(
+            f,
+            "{} {}",
+            "info:".if_supports_color(Stream::Stderr, |s| s.bold()),
+            record.args()
+        ),
+        Level::Debug => writeln!(
+            f,
+            "{} {}",
+            "debug:".if_supports_color(Stream::Stderr, |s| s.bold()),
+            record.args()
+        ),
+        Level::Trace => writeln!(
+            f,
+            "{} {}",
+            "trace:".if_supports_color(Stream::Stderr, |s| s.dimmed()),
+            record.args()
+        ),
+    }
+}
+
+/// Whether to produce color output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[must_use]
+pub enum Color {
+    /// Produce color output if the stream is a terminal that supports it.
+    Auto,
+    /// Always produce color output.
+    Always,
+    /// Never produce color output.
+    Never,
+}
+
+impl Color {
+    fn init_colored(self) {
+        match self {
+            Color::Auto => owo_colors::unset_override(),
+            Color::Always => owo_colors::set_override(true),
+            Color::Never => owo_colors::set_override(false),
+        }
+    }
+
+    /// Returns true if color output is enabled for the given stream.
+    pub fn is_enabled(self, stream: Stream) -> bool {
+        match self {
+            Color::Auto => match supports_color::on_cached(stream) {
+                Some(level) => level.has_basic,
+                None => false,
+            },
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            s => Err(format!(
+                "{} is not a valid option, expected `auto`, `always` or `never`",
+                s
+            )),
+        }
+    }
+}
+
+/// Styles used for output to standard output.
+///
+/// All styles are plain unless color output is enabled.
+#[derive(Clone, Debug, Default)]
+pub struct Styles {
+    /// Section headings.
+    pub heading: Style,
+    /// Package names and IDs.
+    pub package_name: Style,
+    /// Package versions.
+    pub package_version: Style,
+    /// Paths within the workspace.
+    pub path: Style,
+    /// Added items.
+    pub added: Style,
+    /// Removed items.
+    pub removed: Style,
+}
+
+impl Styles {
+    fn colorize(&mut self) {
+        self.heading = Style::new().bold().underline();
+        self.package_name = Style::new().bold();
+        self.package_version = Style::new().cyan();
+        self.path = Style::new().blue().bold();
+        self.added = Style::new().green();
+        self.removed = Style::new().red();
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::core::{BaseFilterOptions, Kind};
+use camino::Utf8PathBuf;
+use clap::{ArgEnum, Parser};
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::graph::{
+    cargo::{CargoOptions, CargoSet},
+    sbom::{SbomBuilder, SbomFormat},
+    DependencyDirection,
+};
+use guppy_cmdlib::{
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures,
+};
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+pub enum SbomFormatCmd {
+    Cyclonedx,
+    Spdx,
+}
+
+impl SbomFormatCmd {
+    fn to_guppy(self) -> SbomFormat {
+        match self {
+            SbomFormatCmd::Cyclonedx => SbomFormat::CycloneDx,
+            SbomFormatCmd::Spdx => SbomFormat::Spdx,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+pub struct SbomOptions {
+    #[clap(long, arg_enum, default_value_t = SbomFormatCmd::Cyclonedx)]
+    /// Format of the software bill of materials
+    format: SbomFormatCmd,
+
+    #[clap(flatten)]
+    pf: PackagesAndFeatures,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(flatten)]
+    base_filter_opts: BaseFilterOptions,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(long)]
+    /// Name of the software the SBOM describes (default: name of the workspace directory)
+    name: Option<String>,
+
+    #[clap(long)]
+    /// Creation time to record in the SBOM, as an RFC 3339 timestamp (default: current time)
+    timestamp: Option<String>,
+
+    #[clap(long, short)]
+    /// Write the SBOM to this file (default: stdout)
+    output: Option<Utf8PathBuf>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl SbomOptions {
+    pub fn exec(&self) -> Result<()> {
+        let target_platform = string_to_platform_spec(self.target_platform.as_deref())?;
+        let host_platform = string_to_platform_spec(self.host_platform.as_deref())?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev()?)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(target_platform)
+            .set_host_platform(host_platform)
+            .add_omitted_packages(self.base_filter_opts.omitted_package_ids(&pkg_graph));
+
+        let (initials, features_only) = self.pf.make_feature_sets(&pkg_graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+
+        let direct_deps = cargo_set
+            .target_direct_deps()
+            .union(cargo_set.host_direct_deps());
+        let package_set = cargo_set
+            .target_features()
+            .to_package_set()
+            .union(&cargo_set.host_features().to_package_set())
+            .filter(DependencyDirection::Forward, |package| {
+                let show_kind = match self.base_filter_opts.kind {
+                    Kind::All => true,
+                    Kind::Workspace => package.in_workspace(),
+                    Kind::DirectThirdParty => {
+                        !package.in_workspace()
+                            && direct_deps
+                                .contains(package.id())
+                                .expect("valid package ID")
+                    }
+                    Kind::ThirdParty => !package.in_workspace(),
+                };
+                show_kind && self.base_filter_opts.filter_expr_opts.matches(package)
+            });
+
+        let mut builder = SbomBuilder::new(&pkg_graph, package_set);
+        if let Some(name) = &self.name {
+            builder.set_name(name);
+        }
+        if let Some(timestamp) = &self.timestamp {
+            builder.set_timestamp(timestamp);
+        }
+        // Checksums are optional, so only read them if Cargo.lock exists.
+        if pkg_graph.workspace().root().join("Cargo.lock").exists() {
+            builder.read_lockfile_checksums()?;
+        }
+
+        let format = self.format.to_guppy();
+        match &self.output {
+            Some(output) => {
+                let mut f = fs::File::create(output)
+                    .wrap_err_with(|| format!("creating {} failed", output))?;
+                builder.write_json(format, &mut f)?;
+                writeln!(f)?;
+            }
+            None => {
+                let stdout = io::stdout();
+                let mut stdout = stdout.lock();
+                builder.write_json(format, &mut stdout)?;
+                writeln!(stdout)?;
+            }
+        }
+
+        Ok(())
+    }
+}
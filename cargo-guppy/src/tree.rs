@@ -0,0 +1,174 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tree output for selected packages.
+
+use clap::Parser;
+use guppy::{
+    graph::{
+        feature::FeatureSet, DependencyDirection, PackageLink, PackageMetadata, PackageSource,
+    },
+    PackageId,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+/// Options for the tree output of `select`.
+#[derive(Debug, Parser)]
+pub struct TreeOptions {
+    #[clap(long)]
+    /// Print selected packages as an indented dependency tree from the query roots
+    ///
+    /// `--kind` and `--filter-expr` don't apply to the tree, since omitting packages from it would
+    /// hide the paths to their dependencies.
+    pub output_tree: bool,
+
+    #[clap(long, requires = "output-tree")]
+    /// Only print the tree up to this many levels below the roots
+    tree_depth: Option<usize>,
+
+    #[clap(long, requires = "output-tree")]
+    /// Annotate each package in the tree with the named features enabled for it
+    pub tree_features: bool,
+}
+
+/// A tree of packages, rooted at the packages a query started from.
+#[derive(Clone, Debug)]
+pub struct PackageTree<'g> {
+    roots: Vec<PackageMetadata<'g>>,
+    children: HashMap<&'g PackageId, Vec<PackageMetadata<'g>>>,
+    features: Option<FeatureSet<'g>>,
+}
+
+impl<'g> PackageTree<'g> {
+    /// Creates a new tree from the given roots and the links followed from them.
+    ///
+    /// For forward queries, the children of a package are its dependencies. For reverse queries,
+    /// they're the packages that depend on it.
+    pub fn new(
+        roots: impl IntoIterator<Item = PackageMetadata<'g>>,
+        links: impl IntoIterator<Item = PackageLink<'g>>,
+        direction: DependencyDirection,
+    ) -> Self {
+        let mut roots: Vec<_> = roots.into_iter().collect();
+        roots.sort_by(compare_packages);
+
+        let mut children: HashMap<&'g PackageId, Vec<PackageMetadata<'g>>> = HashMap::new();
+        for link in links {
+            let (parent, child) = match direction {
+                DependencyDirection::Forward => (link.from(), link.to()),
+                DependencyDirection::Reverse => (link.to(), link.from()),
+            };
+            children.entry(parent.id()).or_default().push(child);
+        }
+        for packages in children.values_mut() {
+            // A package may depend on another one through several links, e.g. as both a normal and
+            // a build dependency.
+            packages.sort_by(compare_packages);
+            packages.dedup_by_key(|package| package.id());
+        }
+
+        Self {
+            roots,
+            children,
+            features: None,
+        }
+    }
+
+    /// Annotates packages in the tree with their enabled features.
+    pub fn set_features(&mut self, features: FeatureSet<'g>) -> &mut Self {
+        self.features = Some(features);
+        self
+    }
+
+    /// Returns a value that displays this tree according to the given options.
+    pub fn display<'a>(&'a self, options: &'a TreeOptions) -> impl fmt::Display + 'a {
+        TreeDisplay {
+            tree: self,
+            options,
+        }
+    }
+}
+
+struct TreeDisplay<'g, 'a> {
+    tree: &'a PackageTree<'g>,
+    options: &'a TreeOptions,
+}
+
+impl<'g, 'a> TreeDisplay<'g, 'a> {
+    fn fmt_package(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        package: PackageMetadata<'g>,
+        prefix: &mut String,
+        depth: usize,
+        expanded: &mut HashSet<&'g PackageId>,
+    ) -> fmt::Result {
+        write!(f, "{} v{}", package.name(), package.version())?;
+        match package.source() {
+            PackageSource::Workspace(path) | PackageSource::Path(path) => write!(f, " ({})", path)?,
+            source if source.is_crates_io() => {}
+            source => write!(f, " ({})", source)?,
+        }
+        if self.options.tree_features {
+            self.fmt_features(f, package.id())?;
+        }
+
+        let children = match self.tree.children.get(package.id()) {
+            Some(children) if self.options.tree_depth.map_or(true, |max| depth < max) => children,
+            _ => return writeln!(f),
+        };
+        if !expanded.insert(package.id()) {
+            // This package's children were already printed out.
+            return writeln!(f, " (*)");
+        }
+        writeln!(f)?;
+
+        for (idx, child) in children.iter().enumerate() {
+            let last = idx + 1 == children.len();
+            write!(f, "{}{}", prefix, if last { "└── " } else { "├── " })?;
+            let prefix_len = prefix.len();
+            prefix.push_str(if last { "    " } else { "│   " });
+            self.fmt_package(f, *child, prefix, depth + 1, expanded)?;
+            prefix.truncate(prefix_len);
+        }
+        Ok(())
+    }
+
+    fn fmt_features(&self, f: &mut fmt::Formatter<'_>, package_id: &PackageId) -> fmt::Result {
+        let feature_list = match &self.tree.features {
+            Some(features) => features
+                .features_for(package_id)
+                .expect("package ID is in the graph"),
+            None => None,
+        };
+        let named: Vec<_> = feature_list
+            .iter()
+            .flat_map(|list| list.named_features())
+            .collect();
+        if !named.is_empty() {
+            write!(f, " [{}]", named.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'g, 'a> fmt::Display for TreeDisplay<'g, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Packages whose children have been printed out, shared across roots.
+        let mut expanded = HashSet::new();
+        for (idx, root) in self.tree.roots.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            self.fmt_package(f, *root, &mut String::new(), 0, &mut expanded)?;
+        }
+        Ok(())
+    }
+}
+
+fn compare_packages(a: &PackageMetadata<'_>, b: &PackageMetadata<'_>) -> std::cmp::Ordering {
+    (a.name(), a.version(), a.id()).cmp(&(b.name(), b.version(), b.id()))
+}
@@ -0,0 +1,102 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use clap::Parser;
+use color_eyre::eyre::Result;
+use guppy::graph::{
+    cargo::{CargoOptions, UnusedFeatures},
+    feature::{FeatureLabel, StandardFeatures},
+    DependencyDirection,
+};
+use guppy_cmdlib::{
+    string_to_platform_spec, CargoMetadataOptions, CargoResolverVersionCmd, InitialsPlatformCmd,
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+#[derive(Debug, Parser)]
+pub struct UnusedFeaturesOptions {
+    #[clap(long = "resolver-version")]
+    #[clap(arg_enum, default_value_t = CargoResolverVersionCmd::V1)]
+    /// Cargo resolver version to use
+    resolver_version: CargoResolverVersionCmd,
+
+    #[clap(long = "initials-platform")]
+    #[clap(arg_enum, default_value_t = InitialsPlatformCmd::Standard)]
+    /// Include initial proc-macros on target platform (default: false)
+    initials_platform: InitialsPlatformCmd,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(long)]
+    /// Output unused features as JSON, keyed by package name
+    json: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl UnusedFeaturesOptions {
+    pub fn exec(&self) -> Result<()> {
+        let target_platform = string_to_platform_spec(self.target_platform.as_deref())?;
+        let host_platform = string_to_platform_spec(self.host_platform.as_deref())?;
+        let pkg_graph = self.metadata_opts.build_graph()?;
+
+        // Simulate `cargo build --workspace` and `cargo test --workspace`. Features enabled by
+        // either of these builds are in use.
+        let mut cargo_sets = vec![];
+        for include_dev in [false, true] {
+            let mut cargo_opts = CargoOptions::new();
+            cargo_opts
+                .set_include_dev(include_dev)
+                .set_resolver(self.resolver_version.to_guppy())
+                .set_initials_platform(self.initials_platform.to_guppy())
+                .set_target_platform(target_platform.clone())
+                .set_host_platform(host_platform.clone());
+            let cargo_set = pkg_graph
+                .resolve_workspace()
+                .to_feature_set(StandardFeatures::Default)
+                .into_cargo_set(&cargo_opts)?;
+            cargo_sets.push(cargo_set);
+        }
+        let unused_features = UnusedFeatures::new(&pkg_graph, &cargo_sets);
+
+        // Group features by package, sorted by package name and feature name.
+        let mut by_package: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for feature in unused_features
+            .unused_set()
+            .features(DependencyDirection::Forward)
+        {
+            if let FeatureLabel::Named(name) = feature.label() {
+                by_package
+                    .entry(feature.package().name())
+                    .or_default()
+                    .push(name);
+            }
+        }
+        for features in by_package.values_mut() {
+            features.sort_unstable();
+        }
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        if self.json {
+            serde_json::to_writer_pretty(&mut stdout, &by_package)?;
+            writeln!(stdout)?;
+        } else {
+            for (package, features) in &by_package {
+                writeln!(stdout, "{}: {}", package, features.join(", "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Weights for packages, used to estimate build costs in `subtree-size`.
+
+use crate::attributions::source_dir;
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::ArgEnum;
+use color_eyre::eyre::{Result, WrapErr};
+use guppy::{
+    graph::{ExternalSource, PackageMetadata},
+    PackageId,
+};
+use log::warn;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+#[derive(ArgEnum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum WeightKind {
+    /// Every package has a weight of 1
+    Count,
+    /// The size in bytes of the downloaded `.crate` file, or of the source files for packages
+    /// without one
+    CrateSize,
+    /// The number of lines in Rust source files
+    Lines,
+}
+
+/// A weights file, read from TOML.
+///
+/// An example:
+///
+/// ```toml
+/// [packages]
+/// # Weights are keyed by package name, and apply to every version of a package.
+/// librocksdb-sys = 500
+/// syn = 20
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct WeightsFile {
+    #[serde(default)]
+    packages: BTreeMap<String, u64>,
+}
+
+/// Computes and caches the weight of each package.
+pub(crate) struct Weights<'g> {
+    kind: WeightKind,
+    overrides: WeightsFile,
+    cargo_home: Option<Utf8PathBuf>,
+    cache: HashMap<&'g PackageId, u64>,
+}
+
+impl<'g> Weights<'g> {
+    /// Creates a new set of weights, with weights in the file at `overrides_path` taking precedence
+    /// over ones computed from `kind`.
+    pub(crate) fn new(kind: WeightKind, overrides_path: Option<&Utf8Path>) -> Result<Self> {
+        let overrides = match overrides_path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .wrap_err_with(|| format!("reading weights file {} failed", path))?;
+                toml::from_str(&contents)
+                    .wrap_err_with(|| format!("parsing weights file {} failed", path))?
+            }
+            None => WeightsFile::default(),
+        };
+        let cargo_home = match kind {
+            WeightKind::CrateSize => home::cargo_home()
+                .ok()
+                .and_then(|path| Utf8PathBuf::from_path_buf(path).ok()),
+            WeightKind::Count | WeightKind::Lines => None,
+        };
+        Ok(Self {
+            kind,
+            overrides,
+            cargo_home,
+            cache: HashMap::new(),
+        })
+    }
+
+    /// Returns true if every package has a weight of 1.
+    pub(crate) fn is_count(&self) -> bool {
+        self.kind == WeightKind::Count && self.overrides.packages.is_empty()
+    }
+
+    /// Returns the weight of this package.
+    pub(crate) fn weight(&mut self, package: PackageMetadata<'g>) -> Result<u64> {
+        if let Some(weight) = self.cache.get(package.id()) {
+            return Ok(*weight);
+        }
+        let weight = match self.overrides.packages.get(package.name()) {
+            Some(weight) => *weight,
+            None => match self.kind {
+                WeightKind::Count => 1,
+                WeightKind::CrateSize => match self.crate_file(package) {
+                    Some(crate_file) => crate_file
+                        .metadata()
+                        .wrap_err_with(|| format!("reading metadata for {} failed", crate_file))?
+                        .len(),
+                    None => package_source_weight(package, |path| Ok(path.metadata()?.len()))?,
+                },
+                WeightKind::Lines => package_source_weight(package, |path| {
+                    if path.extension() == Some("rs") {
+                        let contents = fs::read(path)?;
+                        Ok(contents.iter().filter(|b| **b == b'\n').count() as u64)
+                    } else {
+                        Ok(0)
+                    }
+                })?,
+            },
+        };
+        self.cache.insert(package.id(), weight);
+        Ok(weight)
+    }
+
+    /// Returns the path to the `.crate` file Cargo downloaded for this package, if any.
+    fn crate_file(&self, package: PackageMetadata<'_>) -> Option<Utf8PathBuf> {
+        if !matches!(
+            package.source().parse_external(),
+            Some(ExternalSource::Registry(_))
+        ) {
+            return None;
+        }
+        // Each registry has its own directory within the cache.
+        let cache_dir = self.cargo_home.as_ref()?.join("registry").join("cache");
+        let file_name = format!("{}-{}.crate", package.name(), package.version());
+        cache_dir
+            .read_dir_utf8()
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path().join(&file_name))
+            .find(|path| path.is_file())
+    }
+}
+
+/// Sums `file_weight` over the source files of a package.
+///
+/// If the sources haven't been downloaded, a warning is printed and the weight is 0.
+fn package_source_weight(
+    package: PackageMetadata<'_>,
+    file_weight: impl Fn(&Utf8Path) -> std::io::Result<u64> + Copy,
+) -> Result<u64> {
+    let dir = source_dir(package, None);
+    if !dir.is_dir() {
+        warn!(
+            "sources for {} {} not found at {}, using a weight of 0 \
+             (run `cargo fetch` to download them)",
+            package.name(),
+            package.version(),
+            dir
+        );
+        return Ok(0);
+    }
+    source_weight(&dir, file_weight)
+}
+
+/// Sums `file_weight` over the files within a package directory.
+///
+/// Hidden directories, `target` directories and directories with their own `Cargo.toml`, which
+/// belong to other packages, are skipped.
+fn source_weight(
+    dir: &Utf8Path,
+    file_weight: impl Fn(&Utf8Path) -> std::io::Result<u64> + Copy,
+) -> Result<u64> {
+    let mut total = 0;
+    let entries = dir
+        .read_dir_utf8()
+        .wrap_err_with(|| format!("reading directory {} failed", dir))?;
+    for entry in entries {
+        let entry = entry.wrap_err_with(|| format!("reading directory {} failed", dir))?;
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .wrap_err_with(|| format!("reading file type of {} failed", path))?;
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            if name.starts_with('.') || name == "target" || path.join("Cargo.toml").is_file() {
+                continue;
+            }
+            total += source_weight(path, file_weight)?;
+        } else if file_type.is_file() {
+            total += file_weight(path).wrap_err_with(|| format!("reading {} failed", path))?;
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::{json::JsonFixture, temp_workspace::TempWorkspace};
+    use guppy::MetadataCommand;
+
+    fn weights_file(dir: &Utf8Path, contents: &str) -> Utf8PathBuf {
+        let path = dir.join("weights.toml");
+        fs::write(&path, contents).expect("weights file written");
+        path
+    }
+
+    #[test]
+    fn parse_weights_file() {
+        let workspace = TempWorkspace::inside_outside();
+        let path = weights_file(
+            workspace.root(),
+            "[packages]\nlibrocksdb-sys = 500\nsyn = 20\n",
+        );
+        let weights = Weights::new(WeightKind::Count, Some(&path)).expect("weights file parsed");
+        assert_eq!(
+            weights.overrides.packages,
+            vec![("librocksdb-sys".to_owned(), 500), ("syn".to_owned(), 20)]
+                .into_iter()
+                .collect(),
+        );
+        assert!(!weights.is_count(), "overrides aren't all 1");
+
+        let weights = Weights::new(WeightKind::Count, None).expect("no weights file");
+        assert!(weights.is_count(), "no overrides");
+        let path = weights_file(workspace.root(), "");
+        let weights = Weights::new(WeightKind::Lines, Some(&path)).expect("empty file parsed");
+        assert!(!weights.is_count(), "weights aren't counts");
+
+        for invalid in &[
+            "[packages]\nsyn = \"big\"\n",
+            "[packages]\nsyn = -1\n",
+            "[package]\nsyn = 20\n",
+            "packages = 1\n",
+        ] {
+            let path = weights_file(workspace.root(), invalid);
+            let err = Weights::new(WeightKind::Count, Some(&path))
+                .err()
+                .unwrap_or_else(|| panic!("{:?} is invalid", invalid));
+            assert!(
+                format!("{}", err).starts_with("parsing weights file"),
+                "{:?}: unexpected error {}",
+                invalid,
+                err
+            );
+        }
+
+        let err = Weights::new(
+            WeightKind::Count,
+            Some(&workspace.root().join("does-not-exist.toml")),
+        )
+        .err()
+        .expect("missing file is an error");
+        assert!(format!("{}", err).starts_with("reading weights file"));
+    }
+
+    #[test]
+    fn weights_with_overrides() {
+        let workspace = TempWorkspace::inside_outside();
+        let path = weights_file(workspace.root(), "[packages]\nlazy_static = 7\n");
+        let graph = JsonFixture::metadata1().graph();
+        let mut weights = Weights::new(WeightKind::Count, Some(&path)).expect("weights parsed");
+        for package in graph.packages() {
+            let expected = if package.name() == "lazy_static" {
+                7
+            } else {
+                1
+            };
+            assert_eq!(
+                weights.weight(package).expect("weight computed"),
+                expected,
+                "weight for {}",
+                package.id()
+            );
+        }
+
+        // The fixture's sources don't exist, so they're weighted 0 (with a warning).
+        let mut weights = Weights::new(WeightKind::Lines, Some(&path)).expect("weights parsed");
+        for package in graph.packages() {
+            let expected = if package.name() == "lazy_static" {
+                7
+            } else {
+                0
+            };
+            assert_eq!(weights.weight(package).expect("weight computed"), expected);
+        }
+    }
+
+    #[test]
+    fn source_weights() {
+        let workspace = TempWorkspace::inside_outside();
+        workspace.write("side/src/lib.rs", "pub fn side() {}\n\n// Three lines.\n");
+        workspace.write("side/README.md", "Not Rust.\n");
+        // Hidden directories, target directories and nested packages are skipped.
+        for dir in &["side/.hidden", "side/target", "side/nested"] {
+            fs::create_dir_all(workspace.root().join(dir)).expect("directory created");
+            workspace.write(format!("{}/lib.rs", dir), "skipped\n");
+        }
+        workspace.write("side/nested/Cargo.toml", "");
+
+        let mut command = MetadataCommand::new();
+        command.current_dir(workspace.root());
+        let graph = command.build_graph().expect("package graph built");
+        let side = graph
+            .workspace()
+            .member_by_path("side")
+            .expect("side is a workspace member");
+
+        let mut weights = Weights::new(WeightKind::Lines, None).expect("weights created");
+        assert_eq!(weights.weight(side).expect("weight computed"), 3);
+
+        let mut weights = Weights::new(WeightKind::CrateSize, None).expect("weights created");
+        let expected = ["side/Cargo.toml", "side/src/lib.rs", "side/README.md"]
+            .iter()
+            .map(|path| {
+                fs::metadata(workspace.root().join(path))
+                    .expect("metadata read")
+                    .len()
+            })
+            .sum::<u64>();
+        assert_eq!(weights.weight(side).expect("weight computed"), expected);
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Temporary Git worktrees, used to generate metadata for other revisions of a workspace.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use guppy::{graph::PackageGraph, MetadataCommand};
+use log::{debug, warn};
+use std::{convert::TryInto, process::Command};
+use tempfile::TempDir;
+
+/// Builds a package graph for the workspace at `manifest_path`, as of the Git revision `rev`.
+///
+/// The revision is checked out into a temporary worktree, which is removed afterwards.
+pub(crate) fn graph_at_rev(manifest_path: &Utf8Path, rev: &str) -> Result<PackageGraph> {
+    let manifest_dir = manifest_path
+        .parent()
+        .ok_or_else(|| eyre!("manifest path {} has no parent", manifest_path))?;
+    let repo_root = Utf8PathBuf::from(run_git(manifest_dir, &["rev-parse", "--show-toplevel"])?);
+    let repo_root = repo_root
+        .canonicalize_utf8()
+        .wrap_err_with(|| format!("error canonicalizing {}", repo_root))?;
+    let rel_manifest_path = manifest_path.strip_prefix(&repo_root).wrap_err_with(|| {
+        format!(
+            "manifest path {} is outside Git repository {}",
+            manifest_path, repo_root
+        )
+    })?;
+
+    let worktree = Worktree::new(&repo_root, rev)?;
+    let worktree_manifest_path = worktree.path().join(rel_manifest_path);
+    debug!(
+        "running cargo metadata for {} at {}",
+        worktree_manifest_path, rev
+    );
+    let mut command = MetadataCommand::new();
+    command.manifest_path(&worktree_manifest_path);
+    command
+        .build_graph()
+        .wrap_err_with(|| format!("error generating metadata at revision {}", rev))
+}
+
+/// A Git worktree checked out at a revision in a temporary directory, removed on drop.
+struct Worktree {
+    repo_root: Utf8PathBuf,
+    path: Utf8PathBuf,
+    // Dropped after the worktree is removed.
+    _dir: TempDir,
+}
+
+impl Worktree {
+    fn new(repo_root: &Utf8Path, rev: &str) -> Result<Self> {
+        // Resolve the revision first for a better error message if it's invalid.
+        let commit = run_git(
+            repo_root,
+            &["rev-parse", "--verify", &format!("{}^{{commit}}", rev)],
+        )
+        .wrap_err_with(|| format!("revision {} not found", rev))?;
+
+        let dir = tempfile::Builder::new()
+            .prefix("cargo-guppy-")
+            .tempdir()
+            .wrap_err("error creating temporary directory")?;
+        let path: Utf8PathBuf = dir
+            .path()
+            .to_path_buf()
+            .try_into()
+            .wrap_err("temporary directory is invalid UTF-8")?;
+
+        debug!("checking out {} ({}) into {}", rev, commit, path);
+        run_git(
+            repo_root,
+            &["worktree", "add", "--detach", path.as_str(), &commit],
+        )?;
+        Ok(Self {
+            repo_root: repo_root.to_owned(),
+            path,
+            _dir: dir,
+        })
+    }
+
+    fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        if let Err(err) = run_git(
+            &self.repo_root,
+            &["worktree", "remove", "--force", self.path.as_str()],
+        ) {
+            warn!("error removing worktree at {}: {:?}", self.path, err);
+        }
+    }
+}
+
+fn run_git(dir: &Utf8Path, args: &[&str]) -> Result<String> {
+    let command_str = format!("git {}", args.join(" "));
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .wrap_err_with(|| format!("error running {}", command_str))?;
+    if !output.status.success() {
+        bail!(
+            "{} in {} failed: {}",
+            command_str,
+            dir,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .wrap_err_with(|| format!("{} produced invalid UTF-8 output", command_str))?;
+    Ok(stdout.trim_end().to_owned())
+}
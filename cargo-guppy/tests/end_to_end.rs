@@ -0,0 +1,676 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! End-to-end tests that run the `cargo guppy` binary against copies of fixture workspaces.
+
+use fixtures::temp_workspace::TempWorkspace;
+use std::{env, fs, path::Path, process::Command};
+
+fn guppy<'a>(workspace: &TempWorkspace, args: impl IntoIterator<Item = &'a str>) -> Command {
+    // Pass in "guppy" as the first argument, the same way Cargo does for subcommands.
+    workspace.command(
+        env!("CARGO_BIN_EXE_cargo-guppy"),
+        std::iter::once("guppy").chain(args),
+    )
+}
+
+/// Runs Git in the directory containing the workspace, so that path dependencies outside the
+/// workspace are included in the repository.
+fn git<'a>(workspace: &TempWorkspace, args: impl IntoIterator<Item = &'a str>) {
+    let mut command = workspace.command(
+        "git",
+        [
+            "-C",
+            "..",
+            "-c",
+            "user.name=guppy-test",
+            "-c",
+            "user.email=guppy-test@example.com",
+            "-c",
+            "commit.gpgsign=false",
+        ],
+    );
+    command.args(args);
+    workspace.run(&mut command, 0);
+}
+
+/// Compares `actual` against the snapshot stored in `tests/snapshots/<name>.txt`.
+///
+/// Set `GUPPY_UPDATE_SNAPSHOTS=1` to write out the actual output instead.
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{}.txt", name));
+    if env::var_os("GUPPY_UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).expect("snapshot written");
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "failed to read snapshot {} (set GUPPY_UPDATE_SNAPSHOTS=1 to create it): {}",
+            path.display(),
+            err
+        )
+    });
+    assert_eq!(
+        actual, expected,
+        "output matches snapshot {} (set GUPPY_UPDATE_SNAPSHOTS=1 to update it)",
+        name
+    );
+}
+
+/// The exit codes are documented, so they're pinned to literal values here.
+#[test]
+fn exit_codes() {
+    let workspace = TempWorkspace::inside_outside();
+    let fixture = |name: &str| format!("{}/../fixtures/small/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let metadata1 = fixture("metadata1.json");
+    let metadata2 = fixture("metadata2.json");
+
+    workspace.run(
+        &mut guppy(&workspace, ["diff", "--exit-code", &metadata1, &metadata1]),
+        0,
+    );
+    // Invalid arguments are errors, not violations.
+    workspace.run(&mut guppy(&workspace, ["diff", "--no-such-flag"]), 1);
+    workspace.run(&mut guppy(&workspace, ["--help"]), 0);
+    workspace.run(
+        &mut guppy(&workspace, ["diff", &metadata1, "does-not-exist.json"]),
+        1,
+    );
+    workspace.run(
+        &mut guppy(&workspace, ["diff", "--exit-code", &metadata1, &metadata2]),
+        2,
+    );
+}
+
+/// Runs `cargo guppy select` with the given arguments, and returns the sorted names of the
+/// selected packages.
+fn select_names<'a>(
+    workspace: &TempWorkspace,
+    args: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let mut command = guppy(workspace, ["select", "--output-dot", "select.dot"]);
+    command.args(args);
+    workspace.run(&mut command, 0);
+    let mut labels: Vec<_> = workspace
+        .read("select.dot")
+        .lines()
+        .filter(|line| !line.contains("->"))
+        .filter_map(|line| line.split_once("[label=\""))
+        .map(|(_, label)| label.trim_end_matches("\"]").to_owned())
+        .collect();
+    labels.sort();
+    labels
+}
+
+#[test]
+fn select_cargo_resolve_kind() {
+    let workspace = TempWorkspace::inside_outside();
+    let dot_labels =
+        |kind: &str| select_names(&workspace, ["--cargo-resolve", "--kind", kind, "main"]);
+
+    // --kind limits the links followed from the roots, as it does without --cargo-resolve.
+    assert_eq!(
+        dot_labels("workspace"),
+        ["internal", "internal-macro", "main"],
+        "only workspace links are followed"
+    );
+    assert_eq!(
+        dot_labels("direct-third-party"),
+        [
+            "external",
+            "inactive",
+            "internal",
+            "internal-macro",
+            "lazy_static",
+            "main"
+        ],
+        "links out of third-party packages aren't followed"
+    );
+    assert!(
+        dot_labels("all").contains(&"transitive".to_owned()),
+        "all links are followed"
+    );
+}
+
+#[test]
+fn select_link_attributes() {
+    let workspace = TempWorkspace::inside_outside();
+
+    // Target specs are evaluated rather than compared as strings: internal's dependencies on
+    // x86-active and aarch64-active are declared under cfg(target_arch = ...).
+    assert_eq!(
+        select_names(&workspace, ["--target-cfg", "cfg(unix)", "main"]),
+        ["aarch64-active", "x86-active"],
+        "cfg(unix) matches dependencies enabled on Unix platforms"
+    );
+    assert_eq!(
+        select_names(
+            &workspace,
+            ["--target-cfg", "cfg(target_arch=\"x86\")", "main"]
+        ),
+        ["x86-active"],
+        "whitespace doesn't matter"
+    );
+    assert_eq!(
+        select_names(
+            &workspace,
+            ["--target-cfg", "i686-unknown-linux-gnu", "main"]
+        ),
+        ["x86-active"],
+        "triples are matched against cfg expressions"
+    );
+    assert_eq!(
+        select_names(&workspace, ["--target-cfg", "cfg(windows)", "main"]),
+        ["aarch64-active", "x86-active"],
+        "dependencies enabled on some Windows platform match"
+    );
+    // main's dependency on inactive is declared under cfg(all(unix, not(unix))), which is never
+    // enabled.
+    assert!(
+        !select_names(
+            &workspace,
+            ["--target-cfg", "cfg(any(unix, windows))", "main"]
+        )
+        .contains(&"inactive".to_owned()),
+        "dependencies that are never enabled don't match"
+    );
+
+    for target_cfg in ["cfg(all(unix, not(unix)))", "cfg(not(unix)"] {
+        workspace.run(
+            &mut guppy(&workspace, ["select", "--target-cfg", target_cfg, "main"]),
+            1,
+        );
+    }
+
+    assert_eq!(
+        select_names(&workspace, ["--only-optional", "main"]),
+        ["aarch64-active", "bytes", "spin", "x86-active"],
+    );
+}
+
+#[test]
+fn select_output_tree() {
+    let workspace = TempWorkspace::inside_outside();
+    let tree = |args: &[&str]| {
+        let mut command = guppy(&workspace, ["select", "--output-tree"]);
+        command.args(args);
+        workspace.run(&mut command, 0)
+    };
+
+    // Packages reachable through several paths are only expanded the first time, and marked with
+    // (*) after that.
+    assert_snapshot("select-tree", &tree(&["main"]));
+    assert_snapshot(
+        "select-tree-reverse",
+        &tree(&["--query-reverse", "transitive"]),
+    );
+    assert_snapshot(
+        "select-tree-depth-features",
+        &tree(&["--tree-depth", "1", "--tree-features", "main", "side"]),
+    );
+    assert_snapshot(
+        "select-tree-cargo-resolve",
+        &tree(&["--cargo-resolve", "--tree-features", "main"]),
+    );
+
+    // The tree can't show which links were filtered out.
+    workspace.run(
+        &mut guppy(
+            &workspace,
+            ["select", "--output-tree", "--only-optional", "main"],
+        ),
+        1,
+    );
+    // Tree options require --output-tree.
+    workspace.run(&mut guppy(&workspace, ["select", "--tree-depth", "1"]), 1);
+}
+
+#[test]
+fn resolve_cargo_edge_kind() {
+    let workspace = TempWorkspace::inside_outside();
+    let resolve_cargo = |args: &[&str]| {
+        // With the V1 resolver, features enabled by dev-dependencies are always unified in, so use
+        // V2 to be able to tell whether dev-dependencies are followed.
+        let mut command = guppy(
+            &workspace,
+            ["resolve-cargo", "-p", "main", "--resolver-version", "v2"],
+        );
+        command.args(args);
+        command
+    };
+
+    // Cargo always follows normal and build dependencies, so edge kinds that leave either of them
+    // out are rejected.
+    for edge_kinds in ["normal", "build", "dev", "normal,dev"] {
+        workspace.run(&mut resolve_cargo(&["--edge-kind", edge_kinds]), 1);
+    }
+    workspace.run(&mut resolve_cargo(&["--edge-kind", "normal,build"]), 0);
+
+    // Dev-dependencies of the roots are followed if dev is included.
+    let with_dev = workspace.run(&mut resolve_cargo(&["--edge-kind", "normal,build,dev"]), 0);
+    assert_eq!(
+        with_dev,
+        workspace.run(&mut resolve_cargo(&["--include-dev"]), 0),
+        "--edge-kind dev and --include-dev are the same"
+    );
+    assert_ne!(
+        with_dev,
+        workspace.run(&mut resolve_cargo(&[]), 0),
+        "dev-dependencies are followed"
+    );
+}
+
+#[test]
+fn dups_fail_on_allowlist() {
+    let workspace = TempWorkspace::inside_outside();
+    let metadata = format!(
+        "{}/../fixtures/guppy/metadata_guppy_78cb7e8.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let dups = |args: &[&str], code: i32| -> Vec<String> {
+        let mut command = guppy(&workspace, ["dups", "--metadata-path", &metadata]);
+        command.args(args);
+        let mut lines: Vec<_> = workspace
+            .run(&mut command, code)
+            .lines()
+            .map(|line| line.to_owned())
+            .collect();
+        lines.sort();
+        lines
+    };
+
+    let all = dups(&[], 0);
+    assert_eq!(
+        all,
+        [
+            "hex (0.3.2, 0.4.2)",
+            "humantime (1.3.0, 2.0.1)",
+            "itertools (0.8.2, 0.9.0)",
+            "proc-macro2 (0.4.30, 1.0.24)",
+            "quote (0.6.13, 1.0.7)",
+            "syn (0.15.44, 1.0.42)",
+            "unicode-xid (0.1.0, 0.2.1)",
+            "wasi (0.9.0+wasi-snapshot-preview1, 0.10.0+wasi-snapshot-preview1)",
+        ]
+    );
+    // Every duplicate has two versions.
+    assert_eq!(dups(&["--fail-on", "2"], 2), all);
+    assert_eq!(dups(&["--fail-on", "3"], 0), all);
+
+    workspace.write(
+        "allowlist.toml",
+        r#"
+allow = ["hex", "humantime", "itertools", "proc-macro2", "quote", "unicode-xid", "wasi"]
+
+[[allow-versions]]
+name = "syn"
+versions = ["0.15.44", "1.0.42"]
+"#,
+    );
+    assert_eq!(
+        dups(&["--allowlist", "allowlist.toml", "--fail-on", "2"], 0),
+        Vec::<String>::new(),
+        "allowed duplicates aren't printed and don't count towards --fail-on"
+    );
+
+    workspace.write(
+        "allowlist.toml",
+        r#"
+allow = ["hex", "humantime", "itertools", "proc-macro2", "quote", "unicode-xid", "wasi"]
+
+[[allow-versions]]
+name = "syn"
+versions = ["1.0.42"]
+"#,
+    );
+    assert_eq!(
+        dups(&["--allowlist", "allowlist.toml", "--fail-on", "2"], 2),
+        ["syn (0.15.44, 1.0.42)"],
+        "versions missing from allow-versions aren't accepted"
+    );
+
+    workspace.write("allowlist.toml", "deny = [\"syn\"]\n");
+    dups(&["--allowlist", "allowlist.toml"], 1);
+    dups(&["--allowlist", "does-not-exist.toml"], 1);
+}
+
+#[test]
+fn outdated_paths() {
+    let workspace = TempWorkspace::inside_outside();
+    workspace.run(&mut guppy(&workspace, ["outdated-paths"]), 0);
+
+    // Requirements that match the current version aren't outdated.
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "[dependencies]\n",
+            "[dependencies]\ninternal = { path = \"../internal\", version = \"0.1\" }\n",
+        ),
+    );
+    let side_manifest = workspace.read("side/Cargo.toml");
+    let stdout = workspace.run(&mut guppy(&workspace, ["outdated-paths", "--json"]), 0);
+    assert_eq!(stdout, "[]\n");
+
+    let stdout = workspace.run(&mut guppy(&workspace, ["outdated-paths", "--fix"]), 0);
+    assert_eq!(stdout, "updated 0 version requirements in 0 manifests\n");
+    assert_eq!(
+        workspace.read("side/Cargo.toml"),
+        side_manifest,
+        "--fix leaves requirements that match alone"
+    );
+}
+
+#[test]
+fn diff_revs() {
+    let workspace = TempWorkspace::inside_outside();
+    git(&workspace, ["init", "-q"]);
+    git(&workspace, ["add", "-A"]);
+    git(&workspace, ["commit", "-q", "-m", "initial"]);
+    fn diff_revs<'a>(from: &'a str, to: &'a str, extra: &[&'a str]) -> Vec<&'a str> {
+        let mut args = vec!["diff", "--from-rev", from, "--to-rev", to];
+        args.extend(extra);
+        args
+    }
+
+    // Local packages have different IDs in the two worktrees, but they're unchanged.
+    let stdout = workspace.run(
+        &mut guppy(&workspace, diff_revs("HEAD", "HEAD", &["--exit-code"])),
+        0,
+    );
+    assert_eq!(stdout, "");
+
+    // Add a dependency to a local package without changing its version.
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "[dependencies]\n",
+            "[dependencies]\ninternal = { path = \"../internal\" }\n",
+        ),
+    );
+    git(
+        &workspace,
+        ["commit", "-q", "-a", "-m", "side depends on internal"],
+    );
+
+    let stdout = workspace.run(
+        &mut guppy(&workspace, diff_revs("HEAD~", "HEAD", &["--json"])),
+        0,
+    );
+    let diff: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
+    let updated: Vec<_> = diff["updated"]
+        .as_array()
+        .expect("updated is an array")
+        .iter()
+        .map(|pair| {
+            (
+                pair[0]["name"].as_str().expect("name is a string"),
+                pair[0]["version"].as_str().expect("version is a string"),
+                pair[1]["version"].as_str().expect("version is a string"),
+            )
+        })
+        .collect();
+    assert_eq!(
+        updated,
+        vec![("side", "0.1.0", "0.1.0")],
+        "local package with changed contents reported as updated"
+    );
+    assert_eq!(
+        diff["edges_added"],
+        serde_json::json!([{
+            "from": { "name": "side", "version": "0.1.0" },
+            "to": { "name": "internal", "version": "0.1.0" },
+        }]),
+    );
+
+    workspace.run(
+        &mut guppy(&workspace, diff_revs("HEAD~", "HEAD", &["--exit-code"])),
+        2,
+    );
+    workspace.run(
+        &mut guppy(&workspace, diff_revs("no-such-rev", "HEAD", &[])),
+        1,
+    );
+    // Both revisions must be specified.
+    workspace.run(&mut guppy(&workspace, ["diff", "--from-rev", "HEAD"]), 1);
+}
+
+#[test]
+fn diff_features_and_edges() {
+    let workspace = TempWorkspace::inside_outside();
+    git(&workspace, ["init", "-q"]);
+    git(&workspace, ["add", "-A"]);
+    git(&workspace, ["commit", "-q", "-m", "initial"]);
+
+    // Add and remove features without changing the version, and drop a dependency.
+    let internal_manifest = workspace.read("internal/Cargo.toml");
+    workspace.write(
+        "internal/Cargo.toml",
+        internal_manifest
+            .replace(
+                "extra2 = [\"aarch64-active\", \"aarch64-active/extra\"]\n",
+                "",
+            )
+            .replace("default = []\n", "default = []\nnew-feature = []\n"),
+    );
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "external = { path = \"../../external\", features = [\"side-feature\"] }\n",
+            "",
+        ),
+    );
+    git(
+        &workspace,
+        [
+            "commit",
+            "-q",
+            "-a",
+            "-m",
+            "change features and dependencies",
+        ],
+    );
+
+    let stdout = workspace.run(
+        &mut guppy(
+            &workspace,
+            ["diff", "--from-rev", "HEAD~", "--to-rev", "HEAD", "--json"],
+        ),
+        0,
+    );
+    let diff: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
+    assert_eq!(
+        diff["features_changed"],
+        serde_json::json!([{
+            "name": "internal",
+            "old_version": "0.1.0",
+            "new_version": "0.1.0",
+            "added": ["new-feature"],
+            "removed": ["extra2"],
+        }]),
+    );
+    assert_eq!(diff["edges_added"], serde_json::json!([]));
+    // side-feature, which is no longer enabled, is what pulls in external's optional dependency on
+    // bytes.
+    assert_eq!(
+        diff["edges_removed"],
+        serde_json::json!([
+            {
+                "from": { "name": "external", "version": "0.1.0" },
+                "to": { "name": "bytes", "version": "0.5.4" },
+            },
+            {
+                "from": { "name": "side", "version": "0.1.0" },
+                "to": { "name": "external", "version": "0.1.0" },
+            },
+        ]),
+    );
+
+    // Output isn't colored when it isn't a terminal.
+    let stdout = workspace.run(
+        &mut guppy(
+            &workspace,
+            ["diff", "--from-rev", "HEAD~", "--to-rev", "HEAD"],
+        ),
+        0,
+    );
+    assert!(
+        stdout.contains("Changed Features:\n\tinternal 0.1.0: +new-feature -extra2\n"),
+        "features changed in text output:\n{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(
+            "Removed Dependency Edges:\n\
+             \texternal 0.1.0 -> bytes 0.5.4\n\
+             \tside 0.1.0 -> external 0.1.0\n"
+        ),
+        "edges removed in text output:\n{}",
+        stdout
+    );
+    assert!(
+        !stdout.contains("Added Dependency Edges:"),
+        "no edges added in text output:\n{}",
+        stdout
+    );
+}
+
+#[test]
+fn diff_duplicate_versions() {
+    let workspace = TempWorkspace::inside_outside();
+    for (dir, version) in [("dup-v1", "0.1.0"), ("dup-v2", "0.2.0")] {
+        fs::create_dir_all(workspace.root().join("..").join(dir).join("src"))
+            .expect("package directory created");
+        workspace.write(
+            format!("../{}/Cargo.toml", dir),
+            format!(
+                "[package]\nname = \"dup\"\nversion = \"{}\"\nedition = \"2018\"\n",
+                version
+            ),
+        );
+        workspace.write(format!("../{}/src/lib.rs", dir), "");
+    }
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "[dependencies]\n",
+            "[dependencies]\n\
+             dup1 = { path = \"../../dup-v1\", package = \"dup\" }\n\
+             dup2 = { path = \"../../dup-v2\", package = \"dup\" }\n",
+        ),
+    );
+    git(&workspace, ["init", "-q"]);
+    git(&workspace, ["add", "-A"]);
+    git(&workspace, ["commit", "-q", "-m", "initial"]);
+
+    // Drop one of the two versions of dup.
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "dup1 = { path = \"../../dup-v1\", package = \"dup\" }\n",
+            "",
+        ),
+    );
+    git(&workspace, ["commit", "-q", "-a", "-m", "drop dup 0.1.0"]);
+
+    let stdout = workspace.run(
+        &mut guppy(
+            &workspace,
+            ["diff", "--from-rev", "HEAD~", "--to-rev", "HEAD", "--json"],
+        ),
+        0,
+    );
+    let diff: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
+    assert_eq!(diff["edges_added"], serde_json::json!([]));
+    assert_eq!(
+        diff["edges_removed"],
+        serde_json::json!([{
+            "from": { "name": "side", "version": "0.1.0" },
+            "to": { "name": "dup", "version": "0.1.0" },
+        }]),
+        "edge to the dropped version is removed, even though side still depends on dup"
+    );
+}
+
+#[test]
+fn attributions() {
+    let workspace = TempWorkspace::inside_outside();
+    let set_license = |package: &str, license: &str| {
+        let manifest_path = format!("{}/Cargo.toml", package);
+        let manifest = workspace.read(&manifest_path);
+        workspace.write(
+            &manifest_path,
+            manifest.replacen(
+                "edition = ",
+                &format!("license = \"{}\"\nedition = ", license),
+                1,
+            ),
+        );
+    };
+    set_license("main", "MIT");
+    set_license("side", "MIT");
+    set_license("internal", "Apache-2.0");
+    workspace.write("main/LICENSE", "main license\n");
+    workspace.write("side/NOTICE", "side notice\n");
+    workspace.write("internal/LICENSE-APACHE", "internal license\n");
+
+    // Non-local packages are read from the vendor directory rather than from Cargo's cache. cargo
+    // vendor uses both "name-version" and "name" as directory names.
+    for (dir, file) in [
+        ("bytes-0.5.4", "LICENSE"),
+        ("lazy_static", "LICENSE-MIT"),
+        ("spin-0.5.2", "LICENSE"),
+    ] {
+        fs::create_dir_all(workspace.root().join("vendor").join(dir))
+            .expect("vendor directory created");
+        workspace.write(format!("vendor/{}/Cargo.toml", dir), "[package]\n");
+        workspace.write(
+            format!("vendor/{}/{}", dir, file),
+            format!("{} license\n", dir),
+        );
+    }
+
+    fs::create_dir_all(workspace.root().join("templates")).expect("template directory created");
+    workspace.write("templates/header.txt", "attributions:\n");
+    workspace.write("templates/license.txt", "[{{license}}: {{count}}]\n");
+    workspace.write("templates/package.txt", "{{name}} {{version}}\n");
+    workspace.write("templates/file.txt", "  {{path}}: {{contents}}\n");
+
+    let stdout = workspace.run(
+        &mut guppy(
+            &workspace,
+            [
+                "attributions",
+                "--vendor-dir",
+                "vendor",
+                "--template-dir",
+                "templates",
+            ],
+        ),
+        0,
+    );
+    // Packages are grouped by license, and sorted by license and then by name.
+    assert_eq!(
+        stdout,
+        "attributions:\n\
+         [(unknown): 2]\n\
+         external 0.1.0\n\
+         transitive 0.1.0\n\
+         [Apache-2.0: 1]\n\
+         internal 0.1.0\n  LICENSE-APACHE: internal license\n\
+         [MIT: 4]\n\
+         bytes 0.5.4\n  LICENSE: bytes-0.5.4 license\n\
+         main 0.1.0\n  LICENSE: main license\n\
+         side 0.1.0\n  NOTICE: side notice\n\
+         spin 0.5.2\n  LICENSE: spin-0.5.2 license\n\
+         [MIT/Apache-2.0: 1]\n\
+         lazy_static 1.4.0\n  LICENSE-MIT: lazy_static license\n",
+    );
+}
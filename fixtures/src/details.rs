@@ -315,6 +315,49 @@ impl FixtureDetails {
                 }
             }
         }
+
+        // Every package is in exactly one SCC, and SCCs are returned in condensation order.
+        let sccs: Vec<_> = graph
+            .strongly_connected_components(DependencyDirection::Forward)
+            .collect();
+        let mut scc_idxs = HashMap::new();
+        for (idx, scc) in sccs.iter().enumerate() {
+            for id in scc.package_ids(DependencyDirection::Forward) {
+                assert!(
+                    scc_idxs.insert(id, idx).is_none(),
+                    "{}: package {} is in exactly one SCC",
+                    msg,
+                    id
+                );
+            }
+        }
+        assert_eq!(
+            scc_idxs.len(),
+            graph.package_count(),
+            "{}: every package is in an SCC",
+            msg
+        );
+        for link in graph.resolve_all().links(DependencyDirection::Forward) {
+            assert!(
+                scc_idxs[link.from().id()] <= scc_idxs[link.to().id()],
+                "{}: SCC for {} is returned before SCC for its dependency {}",
+                msg,
+                link.from().id(),
+                link.to().id()
+            );
+        }
+
+        let reverse_sizes: Vec<_> = graph
+            .strongly_connected_components(DependencyDirection::Reverse)
+            .map(|scc| scc.len())
+            .collect();
+        let mut forward_sizes: Vec<_> = sccs.iter().map(|scc| scc.len()).collect();
+        forward_sizes.reverse();
+        assert_eq!(
+            forward_sizes, reverse_sizes,
+            "{}: reverse SCCs are forward SCCs reversed",
+            msg
+        );
     }
 }
 
@@ -587,6 +630,50 @@ impl LinkDetails {
             }
         }
 
+        // Evaluating against all the platforms for a dependency kind at once should produce the
+        // union of the individual results.
+        for dep_kind in [
+            DependencyKind::Normal,
+            DependencyKind::Build,
+            DependencyKind::Development,
+        ] {
+            let mut platforms = vec![];
+            let mut expected = None;
+            let or = |(a, b): (EnabledTernary, EnabledTernary), (c, d)| (a | c, b | d);
+            for (_, platform, results) in self
+                .platform_results
+                .iter()
+                .filter(|(kind, _, _)| *kind == dep_kind)
+            {
+                platforms.push(platform.clone());
+                expected = Some(match expected {
+                    Some((status, default_features)) => (
+                        or(status, results.status),
+                        or(default_features, results.default_features),
+                    ),
+                    None => (results.status, results.default_features),
+                });
+            }
+            if let Some((status, default_features)) = expected {
+                let platform_spec = PlatformSpec::multi(platforms);
+                let req = link.req_for_kind(dep_kind);
+                assert_eq!(
+                    required_enabled(req.status(), &platform_spec),
+                    status,
+                    "{}: for all platforms, kind {}, status is correct",
+                    msg,
+                    dep_kind,
+                );
+                assert_eq!(
+                    required_enabled(req.default_features(), &platform_spec),
+                    default_features,
+                    "{}: for all platforms, kind {}, default features is correct",
+                    msg,
+                    dep_kind,
+                );
+            }
+        }
+
         for (dep_kind, features) in &self.features {
             let metadata = link.req_for_kind(*dep_kind);
             let mut actual_features: Vec<_> = metadata.features().collect();
@@ -101,6 +101,8 @@ pub static METADATA_PROC_MACRO1_DEV_USER: &str =
 
 pub static METADATA_ALTERNATE_REGISTRIES_PATH: &str = "../small/alternate-registries.json";
 pub static METADATA_ALTERNATE_REGISTRY_URL: &str = "https://github.com/fakeorg/crates.io-index";
+pub static METADATA_ALTERNATE_REGISTRIES_DEBUG_IGNORE: &str =
+    "debug-ignore 1.0.1 (path+file:///home/fakeuser/dev/debug-ignore)";
 
 pub static METADATA_WEAK_NAMESPACED_FEATURES_PATH: &str = "../small/weak-namespaced-features.json";
 pub static METADATA_WEAK_NAMESPACED_ID: &str =
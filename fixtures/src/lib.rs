@@ -6,6 +6,7 @@
 pub mod dep_helpers;
 pub mod details;
 pub mod json;
+pub mod temp_workspace;
 
 use guppy::PackageId;
 
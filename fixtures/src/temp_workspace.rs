@@ -0,0 +1,110 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Temporary copies of fixture workspaces, for end-to-end tests that run binaries against them.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{ffi::OsStr, fs, process::Command};
+use tempfile::TempDir;
+
+/// The directory for the inside-outside fixture, relative to the `fixtures` directory. The
+/// workspace is in `workspace` within it, and the other directories contain path dependencies.
+pub static INSIDE_OUTSIDE_DIR: &str = "workspace/inside-outside";
+
+/// A copy of a fixture workspace in a temporary directory, deleted when this is dropped.
+#[derive(Debug)]
+pub struct TempWorkspace {
+    // Held on to to keep the temp dir around for the duration of the test.
+    _temp_dir: TempDir,
+    root: Utf8PathBuf,
+}
+
+impl TempWorkspace {
+    /// Copies the inside-outside fixture into a temporary directory.
+    pub fn inside_outside() -> Self {
+        Self::new(INSIDE_OUTSIDE_DIR, "workspace")
+    }
+
+    /// Copies the fixture at `copy_dir` (relative to the `fixtures` directory) into a temporary
+    /// directory. The workspace root is at `workspace_dir` within it.
+    pub fn new(copy_dir: &str, workspace_dir: &str) -> Self {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("guppy-fixture")
+            .tempdir()
+            .expect("tempdir created");
+        let copy_from = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join(copy_dir);
+        let copy_opts = fs_extra::dir::CopyOptions {
+            content_only: true,
+            ..fs_extra::dir::CopyOptions::new()
+        };
+        fs_extra::dir::copy(&copy_from, temp_dir.path(), &copy_opts)
+            .unwrap_or_else(|err| panic!("copying {} failed: {}", copy_from, err));
+
+        // Canonicalize the path to avoid symlinks in temp dirs (e.g. on macOS) showing up
+        // differently in Cargo's output.
+        let root = temp_dir
+            .path()
+            .join(workspace_dir)
+            .canonicalize()
+            .expect("workspace dir canonicalized");
+        let root = Utf8PathBuf::from_path_buf(root).expect("workspace dir is valid UTF-8");
+        Self {
+            _temp_dir: temp_dir,
+            root,
+        }
+    }
+
+    /// Returns the root of the workspace.
+    pub fn root(&self) -> &Utf8Path {
+        &self.root
+    }
+
+    /// Returns a command that runs `program` with `args` in the workspace root.
+    pub fn command(
+        &self,
+        program: impl AsRef<OsStr>,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> Command {
+        let mut command = Command::new(program);
+        command.args(args).current_dir(&self.root);
+        command
+    }
+
+    /// Runs `command`, panicking with its output if it doesn't exit with `expected_code`.
+    ///
+    /// Returns standard output.
+    pub fn run(&self, command: &mut Command, expected_code: i32) -> String {
+        let output = command
+            .output()
+            .unwrap_or_else(|err| panic!("running {:?} failed: {}", command, err));
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        if output.status.code() != Some(expected_code) {
+            panic!(
+                "{:?} exited with {}, expected code {}\n\n--- stdout ---\n{}\n--- stderr ---\n{}",
+                command,
+                output.status,
+                expected_code,
+                stdout,
+                String::from_utf8_lossy(&output.stderr),
+            );
+        }
+        stdout
+    }
+
+    /// Returns true if the file at `rel_path` (relative to the workspace root) exists.
+    pub fn exists(&self, rel_path: impl AsRef<Utf8Path>) -> bool {
+        self.root.join(rel_path).exists()
+    }
+
+    /// Reads the file at `rel_path` (relative to the workspace root).
+    pub fn read(&self, rel_path: impl AsRef<Utf8Path>) -> String {
+        let path = self.root.join(rel_path);
+        fs::read_to_string(&path).unwrap_or_else(|err| panic!("reading {} failed: {}", path, err))
+    }
+
+    /// Writes `contents` to the file at `rel_path` (relative to the workspace root).
+    pub fn write(&self, rel_path: impl AsRef<Utf8Path>, contents: impl AsRef<[u8]>) {
+        let path = self.root.join(rel_path);
+        fs::write(&path, contents).unwrap_or_else(|err| panic!("writing {} failed: {}", path, err))
+    }
+}
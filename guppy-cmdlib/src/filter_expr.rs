@@ -0,0 +1,401 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Filter expressions for packages.
+
+use clap::Parser;
+use glob::Pattern;
+use guppy::graph::{DependencyDirection, PackageMetadata, PackageSet};
+use std::{error, fmt, str::FromStr};
+
+/// Support for filtering packages through a filter expression.
+#[derive(Debug, Parser)]
+pub struct FilterExprOptions {
+    /// Only include packages matching this filter expression
+    ///
+    /// Expressions are made up of the predicates `all`, `none`, `workspace`, `proc_macro`,
+    /// `build_script`, `crates_io`, `name("glob")` and `licensed("license")`, combined with `&`,
+    /// `|`, `!` and parentheses. For example: `workspace & !proc_macro & licensed("MIT")`.
+    ///
+    /// This filters the packages that are printed out, after dependencies are resolved: packages
+    /// that don't match are still traversed, so their dependencies may be printed.
+    #[clap(long = "filter-expr", value_name = "EXPR")]
+    pub filter_expr: Option<FilterExpr>,
+}
+
+impl FilterExprOptions {
+    /// Returns true if the given package matches the filter expression, or if no expression was
+    /// specified.
+    pub fn matches(&self, package: PackageMetadata<'_>) -> bool {
+        self.filter_expr
+            .as_ref()
+            .map_or(true, |expr| expr.matches(package))
+    }
+
+    /// Returns the packages in the given set that match the filter expression.
+    pub fn apply<'g>(&self, package_set: &PackageSet<'g>) -> PackageSet<'g> {
+        match &self.filter_expr {
+            Some(expr) => expr.apply(package_set),
+            None => package_set.clone(),
+        }
+    }
+}
+
+/// A parsed filter expression for packages.
+///
+/// Expressions are parsed from strings like `workspace & !proc_macro & licensed("MIT")`. `!` binds
+/// most tightly, followed by `&`, then `|`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FilterExpr {
+    /// Matches all packages.
+    All,
+    /// Matches no packages.
+    None,
+    /// Matches packages in the workspace.
+    Workspace,
+    /// Matches procedural macro packages.
+    ProcMacro,
+    /// Matches packages with a build script.
+    BuildScript,
+    /// Matches packages from crates.io.
+    CratesIo,
+    /// Matches packages whose name matches this glob pattern.
+    Name(Pattern),
+    /// Matches packages whose license expression mentions this license.
+    Licensed(String),
+    /// Matches packages that don't match the inner expression.
+    Not(Box<FilterExpr>),
+    /// Matches packages that match both expressions.
+    And(Box<FilterExpr>, Box<FilterExpr>),
+    /// Matches packages that match either expression.
+    Or(Box<FilterExpr>, Box<FilterExpr>),
+}
+
+impl FilterExpr {
+    /// Returns true if the given package matches this expression.
+    pub fn matches(&self, package: PackageMetadata<'_>) -> bool {
+        match self {
+            FilterExpr::All => true,
+            FilterExpr::None => false,
+            FilterExpr::Workspace => package.in_workspace(),
+            FilterExpr::ProcMacro => package.is_proc_macro(),
+            FilterExpr::BuildScript => package.has_build_script(),
+            FilterExpr::CratesIo => package.source().is_crates_io(),
+            FilterExpr::Name(pattern) => pattern.matches(package.name()),
+            FilterExpr::Licensed(license) => package.license().map_or(false, |expr| {
+                // Match whole licenses, so that "MIT" doesn't match "MIT-0".
+                expr.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '/')
+                    .any(|part| part == license)
+            }),
+            FilterExpr::Not(inner) => !inner.matches(package),
+            FilterExpr::And(a, b) => a.matches(package) && b.matches(package),
+            FilterExpr::Or(a, b) => a.matches(package) || b.matches(package),
+        }
+    }
+
+    /// Returns the packages in the given set that match this expression.
+    pub fn apply<'g>(&self, package_set: &PackageSet<'g>) -> PackageSet<'g> {
+        package_set.filter(DependencyDirection::Forward, |package| {
+            self.matches(package)
+        })
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = FilterExprParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = ExprParser::new(s);
+        let expr = parser.parse_or()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(parser.error(format!("unexpected '{}'", c))),
+        }
+    }
+}
+
+/// An error that occurred while parsing a [`FilterExpr`].
+#[derive(Clone, Debug)]
+pub struct FilterExprParseError {
+    /// The byte offset into the input at which the error occurred.
+    pub position: usize,
+    message: String,
+}
+
+impl fmt::Display for FilterExprParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl error::Error for FilterExprParseError {}
+
+/// A recursive descent parser for filter expressions.
+struct ExprParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> ExprParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn parse_or(&mut self) -> Result<FilterExpr, FilterExprParseError> {
+        let mut expr = self.parse_and()?;
+        while self.eat('|') {
+            expr = FilterExpr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<FilterExpr, FilterExprParseError> {
+        let mut expr = self.parse_unary()?;
+        while self.eat('&') {
+            expr = FilterExpr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<FilterExpr, FilterExprParseError> {
+        if self.eat('!') {
+            return Ok(FilterExpr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat('(') {
+            let expr = self.parse_or()?;
+            self.expect(')')?;
+            return Ok(expr);
+        }
+
+        self.skip_whitespace();
+        let start = self.position;
+        let ident = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let expr = match ident {
+            "all" => FilterExpr::All,
+            "none" => FilterExpr::None,
+            "workspace" => FilterExpr::Workspace,
+            "proc_macro" => FilterExpr::ProcMacro,
+            "build_script" => FilterExpr::BuildScript,
+            "crates_io" => FilterExpr::CratesIo,
+            "name" => {
+                let arg = self.parse_string_arg()?;
+                let pattern = Pattern::new(&arg).map_err(|err| {
+                    self.position = start;
+                    self.error(format!("invalid glob pattern '{}': {}", arg, err.msg))
+                })?;
+                FilterExpr::Name(pattern)
+            }
+            "licensed" => FilterExpr::Licensed(self.parse_string_arg()?),
+            "" => {
+                return Err(match self.peek() {
+                    Some(c) => self.error(format!("expected predicate, found '{}'", c)),
+                    None => self.error("expected predicate, found end of input"),
+                })
+            }
+            other => {
+                self.position = start;
+                return Err(self.error(format!("unknown predicate '{}'", other)));
+            }
+        };
+        Ok(expr)
+    }
+
+    fn parse_string_arg(&mut self) -> Result<String, FilterExprParseError> {
+        self.expect('(')?;
+        self.expect('"')?;
+        let arg = self.take_while(|c| c != '"').to_string();
+        if self.peek().is_none() {
+            return Err(self.error("unterminated string"));
+        }
+        self.position += 1;
+        self.expect(')')?;
+        Ok(arg)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = &self.input[self.position..];
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.position += len;
+        &rest[..len]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    /// Consumes the given character, skipping any whitespace before it, if it's next.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), FilterExprParseError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", expected)))
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> FilterExprParseError {
+        FilterExprParseError {
+            position: self.position,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    fn parse(input: &str) -> FilterExpr {
+        input
+            .parse()
+            .unwrap_or_else(|err| panic!("{:?} parsed: {}", input, err))
+    }
+
+    fn not(expr: FilterExpr) -> FilterExpr {
+        FilterExpr::Not(Box::new(expr))
+    }
+
+    fn and(a: FilterExpr, b: FilterExpr) -> FilterExpr {
+        FilterExpr::And(Box::new(a), Box::new(b))
+    }
+
+    fn or(a: FilterExpr, b: FilterExpr) -> FilterExpr {
+        FilterExpr::Or(Box::new(a), Box::new(b))
+    }
+
+    #[test]
+    fn parse_atoms() {
+        let pattern = |s| FilterExpr::Name(Pattern::new(s).expect("valid pattern"));
+        for (input, expected) in [
+            ("all", FilterExpr::All),
+            ("none", FilterExpr::None),
+            ("workspace", FilterExpr::Workspace),
+            ("proc_macro", FilterExpr::ProcMacro),
+            ("build_script", FilterExpr::BuildScript),
+            ("crates_io", FilterExpr::CratesIo),
+            ("name(\"guppy-*\")", pattern("guppy-*")),
+            ("name( \"a b\" )", pattern("a b")),
+            (
+                "licensed(\"Apache-2.0\")",
+                FilterExpr::Licensed("Apache-2.0".to_owned()),
+            ),
+            ("  workspace  ", FilterExpr::Workspace),
+        ] {
+            assert_eq!(parse(input), expected, "for input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn parse_precedence() {
+        use FilterExpr::*;
+
+        for (input, expected) in [
+            // ! binds most tightly, then &, then |.
+            (
+                "workspace | proc_macro & !crates_io",
+                or(Workspace, and(ProcMacro, not(CratesIo))),
+            ),
+            (
+                "!workspace & proc_macro | crates_io",
+                or(and(not(Workspace), ProcMacro), CratesIo),
+            ),
+            // Binary operators are left-associative.
+            ("all & none & workspace", and(and(All, None), Workspace)),
+            ("all | none | workspace", or(or(All, None), Workspace)),
+            // Parentheses override precedence.
+            (
+                "(workspace | proc_macro) & crates_io",
+                and(or(Workspace, ProcMacro), CratesIo),
+            ),
+            ("!(all&none)", not(and(All, None))),
+            ("!!workspace", not(not(Workspace))),
+            ("((workspace))", Workspace),
+        ] {
+            assert_eq!(parse(input), expected, "for input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (input, message) in [
+            ("", "expected predicate, found end of input at position 0"),
+            (
+                "workspace &",
+                "expected predicate, found end of input at position 11",
+            ),
+            (
+                "workspace & )",
+                "expected predicate, found ')' at position 12",
+            ),
+            ("foo & workspace", "unknown predicate 'foo' at position 0"),
+            (
+                "workspace | Workspace",
+                "unknown predicate 'Workspace' at position 12",
+            ),
+            ("workspace workspace", "unexpected 'w' at position 10"),
+            ("workspace)", "unexpected ')' at position 9"),
+            ("(workspace", "expected ')' at position 10"),
+            ("name", "expected '(' at position 4"),
+            ("name(guppy)", "expected '\"' at position 5"),
+            ("name(\"guppy", "unterminated string at position 11"),
+            ("name(\"guppy\"", "expected ')' at position 12"),
+            (
+                "name(\"[\")",
+                "invalid glob pattern '[': invalid range pattern at position 0",
+            ),
+        ] {
+            let err = input.parse::<FilterExpr>().expect_err("input is invalid");
+            assert_eq!(err.to_string(), message, "for input {:?}", input);
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let names = |input: &str| {
+            let expr = parse(input);
+            let mut names: Vec<_> = graph
+                .packages()
+                .filter(|package| expr.matches(*package))
+                .map(|package| package.name())
+                .collect();
+            names.sort_unstable();
+            names.dedup();
+            names
+        };
+
+        assert_eq!(
+            names("workspace & name(\"guppy*\")"),
+            [
+                "guppy",
+                "guppy-benchmarks",
+                "guppy-cmdlib",
+                "guppy-summaries"
+            ]
+        );
+        assert_eq!(names("workspace & proc_macro"), Vec::<&str>::new());
+        assert!(names("proc_macro").contains(&"serde_derive"));
+        assert!(names("!workspace").iter().all(|name| *name != "guppy"));
+
+        // Licenses are matched as whole words in the license expression.
+        let mit = names("licensed(\"MIT\")");
+        assert!(mit.contains(&"serde"), "MIT OR Apache-2.0 matches");
+        let apache = names("licensed(\"Apache\")");
+        assert!(apache.is_empty(), "Apache doesn't match Apache-2.0");
+    }
+}
@@ -4,22 +4,41 @@
 //! Support for CLI operations with guppy, with structopt integration.
 //!
 //! This library allows translating command-line arguments into guppy's data structures.
+//!
+//! # Optional features
+//!
+//! * `proptest1`: Support for property-based testing of command-line options.
+//! * `zstd`: Support for reading `cargo metadata` output from files compressed with zstd.
 
 #[cfg(feature = "proptest1")]
 pub mod proptest;
 
+mod filter_expr;
+
+pub use filter_expr::*;
+
 use clap::{ArgEnum, Parser};
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use flate2::read::GzDecoder;
 use guppy::{
     graph::{
         cargo::{CargoResolverVersion, InitialsPlatform},
-        feature::{named_feature_filter, FeatureSet, StandardFeatures},
-        PackageGraph,
+        feature::{
+            feature_id_filter, named_feature_filter, FeatureId, FeatureLabel, FeatureSet,
+            StandardFeatures,
+        },
+        DependencyDirection, NamePattern, PackageGraph, PackageMetadata, PackageSet,
     },
     platform::{Platform, PlatformSpec, TargetFeatures},
-    MetadataCommand,
+    CargoMetadata, MetadataCommand,
+};
+use log::debug;
+use std::{
+    env, fmt,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
 };
-use std::{env, path::PathBuf};
 
 /// Support for packages and features.
 ///
@@ -27,15 +46,26 @@ use std::{env, path::PathBuf};
 #[derive(Debug, Parser)]
 pub struct PackagesAndFeatures {
     #[clap(long = "package", short = 'p')]
-    /// Packages to start the query from (default: entire workspace)
+    /// Packages to start the query from, with glob and /regex/ patterns supported (default: entire
+    /// workspace)
     pub packages: Vec<String>,
 
+    #[clap(long = "workspace", alias = "all")]
+    /// Start the query from all packages in the workspace, ignoring --package
+    pub workspace: bool,
+
+    #[clap(long = "exclude", requires = "workspace")]
+    /// Packages to exclude from --workspace, with glob and /regex/ patterns supported
+    pub exclude: Vec<String>,
+
     #[clap(long = "features-only")]
     /// Packages that take part in feature unification but aren't in the result set (default: none)
     pub features_only: Vec<String>,
 
-    // TODO: support --workspace and --exclude
-    /// List of features to activate across all packages
+    /// List of features to activate, separated by commas or spaces
+    ///
+    /// Features of dependencies may be enabled with `dep-name/feature`, or with `dep-name?/feature`
+    /// to enable the feature only if the dependency is enabled through other means.
     #[clap(long = "features", use_value_delimiter = true)]
     pub features: Vec<String>,
 
@@ -51,15 +81,24 @@ pub struct PackagesAndFeatures {
 impl PackagesAndFeatures {
     /// Evaluates this struct against the given graph, and converts it into the initials and
     /// features-only `FeatureSet`s.
+    ///
+    /// Features are interpreted the same way Cargo does:
+    /// * `feature` enables a feature in the selected packages. At least one of the selected or
+    ///   features-only packages must have this feature.
+    /// * `package/feature`, where `package` is a selected package, enables a feature in that
+    ///   package.
+    /// * `dep-name/feature` enables a feature in the dependency called `dep-name` of any selected
+    ///   package, enabling the dependency if it is optional.
+    /// * `dep-name?/feature` enables a feature in the dependency called `dep-name` of any selected
+    ///   package, but only if the dependency is enabled through other means.
+    ///
+    /// Features of dependencies are added to the features-only set, so that they take part in
+    /// feature unification without changing the set of initial packages.
     pub fn make_feature_sets<'g>(
         &self,
         graph: &'g PackageGraph,
     ) -> Result<(FeatureSet<'g>, FeatureSet<'g>)> {
-        let package_set = if self.packages.is_empty() {
-            graph.resolve_workspace()
-        } else {
-            graph.resolve_workspace_names(self.packages.iter())?
-        };
+        let package_set = self.make_package_set(graph)?;
         let features_only_set = if self.features_only.is_empty() {
             graph.resolve_none()
         } else {
@@ -71,15 +110,275 @@ impl PackagesAndFeatures {
             (false, false) => StandardFeatures::Default,
             (false, true) => StandardFeatures::None,
         };
-        // TODO: support package/feature format
-        // TODO: support feature name validation similar to cargo
-        let mut feature_filter =
-            named_feature_filter(base_filter, self.features.iter().map(|s| s.as_str()));
 
-        Ok((
-            package_set.to_feature_set(&mut feature_filter),
-            features_only_set.to_feature_set(&mut feature_filter),
-        ))
+        let mut named_features = Vec::new();
+        // Features in selected packages, including optional dependencies enabled through
+        // dep-name/feature.
+        let mut initial_ids = Vec::new();
+        // Features in dependencies, along with the optional dependency feature that must be
+        // enabled for weak features to apply.
+        let mut dep_ids = Vec::new();
+        for spec in self.feature_specs() {
+            match spec {
+                FeatureSpec::Feature(feature) => {
+                    let lookups: Vec<_> = package_set
+                        .union(&features_only_set)
+                        .packages(DependencyDirection::Forward)
+                        .map(|package| lookup_feature(graph, package, feature))
+                        .collect();
+                    if !lookups.iter().any(|lookup| lookup.is_ok()) {
+                        let hint = if lookups.contains(&Err(FeatureLookupError::DepOnly)) {
+                            FeatureLookupError::DepOnly.hint()
+                        } else {
+                            ""
+                        };
+                        bail!(
+                            "none of the selected packages contains the feature `{}`{}",
+                            feature,
+                            hint,
+                        );
+                    }
+                    named_features.push(feature);
+                }
+                FeatureSpec::PackageFeature {
+                    package: package_name,
+                    feature,
+                    weak,
+                } => {
+                    // package/feature refers to a selected package if one matches.
+                    let member = package_set
+                        .packages(DependencyDirection::Forward)
+                        .find(|package| package.name() == package_name);
+                    if let (Some(member), false) = (member, weak) {
+                        let feature_id = lookup_feature(graph, member, feature).map_err(|err| {
+                            eyre!(
+                                "package `{}` does not have the feature `{}`{}",
+                                package_name,
+                                feature,
+                                err.hint(),
+                            )
+                        })?;
+                        initial_ids.push(feature_id);
+                        continue;
+                    }
+
+                    // Otherwise, it must refer to a dependency of a selected package.
+                    let links: Vec<_> = package_set
+                        .packages(DependencyDirection::Forward)
+                        .flat_map(|package| package.direct_links())
+                        .filter(|link| link.dep_name() == package_name)
+                        .collect();
+                    if links.is_empty() {
+                        bail!(
+                            "none of the selected packages has a dependency named `{}` (in `{}`)",
+                            package_name,
+                            spec,
+                        );
+                    }
+                    for link in links {
+                        let feature_id =
+                            lookup_feature(graph, link.to(), feature).map_err(|err| {
+                                eyre!(
+                                    "dependency `{}` of package `{}` does not have the feature `{}`{}",
+                                    package_name,
+                                    link.from().name(),
+                                    feature,
+                                    err.hint(),
+                                )
+                            })?;
+                        let optional_id =
+                            FeatureId::optional_dependency(link.from().id(), link.dep_name());
+                        let optional_id = if graph.feature_graph().contains(optional_id) {
+                            Some(optional_id)
+                        } else {
+                            None
+                        };
+                        match (optional_id, weak) {
+                            (Some(optional_id), false) => {
+                                initial_ids.push(optional_id);
+                                dep_ids.push((feature_id, None));
+                            }
+                            (optional_id, _) => dep_ids.push((feature_id, optional_id)),
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut feature_filter = feature_id_filter(
+            named_feature_filter(base_filter, named_features),
+            initial_ids,
+        );
+        let initials = package_set.to_feature_set(&mut feature_filter);
+        let mut features_only = features_only_set.to_feature_set(&mut feature_filter);
+
+        if !dep_ids.is_empty() {
+            // Weak features only apply if the optional dependency is enabled through other
+            // means.
+            let enabled = initials
+                .union(&features_only)
+                .to_feature_query(DependencyDirection::Forward)
+                .resolve();
+            let dep_ids = dep_ids.into_iter().filter_map(|(feature_id, optional_id)| {
+                let is_enabled = optional_id.map_or(true, |optional_id| {
+                    enabled
+                        .contains(optional_id)
+                        .expect("optional dependency feature is known")
+                });
+                is_enabled.then(|| feature_id)
+            });
+            features_only = features_only.union(&graph.feature_graph().resolve_ids(dep_ids)?);
+        }
+
+        Ok((initials, features_only))
+    }
+
+    /// Evaluates `--package`, `--workspace` and `--exclude` against the given graph, returning the
+    /// set of selected workspace packages.
+    ///
+    /// As with Cargo, `--workspace` selects every workspace member except those matched by
+    /// `--exclude`, and takes precedence over `--package`. Package names may be glob patterns such
+    /// as `guppy-*`, or regular expressions surrounded by slashes such as `/guppy-(cmdlib|edit)/`.
+    /// A `--package` pattern that doesn't match any workspace member is an error, while an
+    /// unmatched `--exclude` pattern is ignored.
+    pub fn make_package_set<'g>(&self, graph: &'g PackageGraph) -> Result<PackageSet<'g>> {
+        if self.workspace || self.packages.is_empty() {
+            if self.exclude.is_empty() {
+                return Ok(graph.resolve_workspace());
+            }
+            let exclude = parse_name_patterns(&self.exclude)?;
+            let ids = graph
+                .workspace()
+                .iter()
+                .filter(|package| {
+                    !exclude
+                        .iter()
+                        .any(|pattern| pattern.matches(package.name()))
+                })
+                .map(|package| package.id());
+            return Ok(graph.resolve_ids(ids)?);
+        }
+
+        let packages = parse_name_patterns(&self.packages)?;
+        Ok(graph.resolve_named(&packages)?)
+    }
+
+    /// Parses the list of features passed in.
+    fn feature_specs(&self) -> impl Iterator<Item = FeatureSpec<'_>> {
+        self.features
+            .iter()
+            .flat_map(|features| features.split(|c: char| c == ',' || c.is_whitespace()))
+            .filter(|feature| !feature.is_empty())
+            .map(FeatureSpec::parse)
+    }
+}
+
+/// Parses package names or patterns passed in through `--package` or `--exclude`.
+fn parse_name_patterns(specs: &[String]) -> Result<Vec<NamePattern>> {
+    specs
+        .iter()
+        .map(|spec| {
+            NamePattern::new(spec).wrap_err_with(|| format!("invalid package pattern `{}`", spec))
+        })
+        .collect()
+}
+
+/// A single feature passed in through `--features`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FeatureSpec<'a> {
+    /// A feature in each selected package.
+    Feature(&'a str),
+    /// `package/feature` or `package?/feature`.
+    PackageFeature {
+        package: &'a str,
+        feature: &'a str,
+        weak: bool,
+    },
+}
+
+impl<'a> FeatureSpec<'a> {
+    fn parse(s: &'a str) -> Self {
+        match s.split_once('/') {
+            Some((package, feature)) => match package.strip_suffix('?') {
+                Some(package) => FeatureSpec::PackageFeature {
+                    package,
+                    feature,
+                    weak: true,
+                },
+                None => FeatureSpec::PackageFeature {
+                    package,
+                    feature,
+                    weak: false,
+                },
+            },
+            None => FeatureSpec::Feature(s),
+        }
+    }
+}
+
+impl<'a> fmt::Display for FeatureSpec<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureSpec::Feature(feature) => write!(f, "{}", feature),
+            FeatureSpec::PackageFeature {
+                package,
+                feature,
+                weak,
+            } => write!(f, "{}{}/{}", package, if *weak { "?" } else { "" }, feature),
+        }
+    }
+}
+
+/// Looks up a feature passed in on the command line in the feature graph, returning its ID.
+///
+/// As with Cargo, this matches named features, including the implicit features for optional
+/// dependencies. Optional dependencies that are referred to with `dep:` don't have implicit
+/// features.
+fn lookup_feature<'g>(
+    graph: &'g PackageGraph,
+    package: PackageMetadata<'g>,
+    feature: &str,
+) -> Result<FeatureId<'g>, FeatureLookupError> {
+    let features = graph
+        .feature_graph()
+        .all_features_for(package.id())
+        .expect("package is known to the graph");
+    let mut dep_only = false;
+    for label in features.labels() {
+        match *label {
+            FeatureLabel::Named(named) if named == feature => {
+                return Ok(FeatureId::new(package.id(), *label));
+            }
+            FeatureLabel::OptionalDependency(dep_name) if dep_name == feature => dep_only = true,
+            _ => {}
+        }
+    }
+    if dep_only {
+        Err(FeatureLookupError::DepOnly)
+    } else {
+        Err(FeatureLookupError::Missing)
+    }
+}
+
+/// The reason a feature passed in on the command line couldn't be found.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum FeatureLookupError {
+    /// There's no feature or optional dependency by this name.
+    Missing,
+    /// There's an optional dependency by this name, but it's only referred to with `dep:`.
+    DepOnly,
+}
+
+impl FeatureLookupError {
+    /// Returns a hint to add to the error message.
+    fn hint(self) -> &'static str {
+        match self {
+            FeatureLookupError::Missing => "",
+            FeatureLookupError::DepOnly => {
+                " (an optional dependency has that name, but it is referred to with the `dep:` \
+                syntax in the features table, so it has no implicit feature)"
+            }
+        }
     }
 }
 
@@ -99,12 +398,56 @@ pub enum InitialsPlatformCmd {
     ProcMacrosOnTarget,
 }
 
+/// A kind of dependency edge: normal, build or dev.
+#[derive(ArgEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EdgeKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+/// Support for selecting the kinds of dependency edges to follow.
+///
+/// Edge kinds are specified with `--edge-kind`, which may be repeated or given a comma-separated
+/// list. `--include-dev` and `--include-build` are accepted as aliases for `--edge-kind dev` and
+/// `--edge-kind build`, and add to the default of normal edges if `--edge-kind` isn't specified.
+#[derive(Clone, Debug, Default, Parser)]
+pub struct EdgeKindOptions {
+    #[clap(long, arg_enum, value_name = "KIND")]
+    #[clap(multiple_occurrences = true, use_value_delimiter = true)]
+    /// Kinds of dependency edges to follow [default: normal]
+    pub edge_kind: Vec<EdgeKind>,
+
+    #[clap(long = "include-dev")]
+    /// Follow dev-dependencies as well (alias for --edge-kind dev)
+    pub include_dev: bool,
+
+    #[clap(long = "include-build")]
+    /// Follow build dependencies as well (alias for --edge-kind build)
+    pub include_build: bool,
+}
+
+impl EdgeKindOptions {
+    /// Returns true if edges of this kind should be followed.
+    pub fn includes(&self, kind: EdgeKind) -> bool {
+        let specified = if self.edge_kind.is_empty() {
+            kind == EdgeKind::Normal
+        } else {
+            self.edge_kind.contains(&kind)
+        };
+        specified
+            || (kind == EdgeKind::Dev && self.include_dev)
+            || (kind == EdgeKind::Build && self.include_build)
+    }
+}
+
 /// Support for options like the Cargo resolver version.
 #[derive(Clone, Debug, Parser)]
 pub struct CargoResolverOpts {
-    #[clap(long = "include-dev")]
-    /// Include dev-dependencies of initial packages (default: false)
-    pub include_dev: bool,
+    // Cargo builds always follow normal and build dependencies, so only dev-dependencies of
+    // initial packages can be toggled.
+    #[clap(flatten)]
+    pub edge_kind_opts: EdgeKindOptions,
 
     #[clap(long = "initials-platform")]
     #[clap(arg_enum, default_value_t = InitialsPlatformCmd::Standard)]
@@ -117,6 +460,25 @@ pub struct CargoResolverOpts {
     pub resolver_version: CargoResolverVersionCmd,
 }
 
+impl CargoResolverOpts {
+    /// Returns true if dev-dependencies of initial packages should be included.
+    ///
+    /// Returns an error if `--edge-kind` leaves out normal or build dependencies, since Cargo
+    /// builds always follow them.
+    pub fn include_dev(&self) -> Result<bool> {
+        let edge_kinds = &self.edge_kind_opts.edge_kind;
+        if !edge_kinds.is_empty()
+            && (!edge_kinds.contains(&EdgeKind::Normal) || !edge_kinds.contains(&EdgeKind::Build))
+        {
+            bail!(
+                "--edge-kind must include both normal and build, since Cargo builds always \
+                 follow them (use --include-dev to also follow dev-dependencies)"
+            );
+        }
+        Ok(self.edge_kind_opts.includes(EdgeKind::Dev))
+    }
+}
+
 impl CargoResolverVersionCmd {
     /// Converts to guppy's CargoResolverVersion.
     pub fn to_guppy(self) -> CargoResolverVersion {
@@ -141,12 +503,20 @@ impl InitialsPlatformCmd {
 
 /// Context for invoking the `cargo metadata` command.
 ///
-/// The options mirror Cargo's.
+/// The options mirror Cargo's. Metadata may also be read from a file or from standard input, in
+/// which case Cargo isn't invoked.
 #[derive(Clone, Debug, Parser)]
 pub struct CargoMetadataOptions {
     /// Path to Cargo.toml
-    #[clap(long)]
+    #[clap(long, conflicts_with = "metadata-path")]
     pub manifest_path: Option<PathBuf>,
+
+    /// Read `cargo metadata` JSON output from this file instead of running Cargo
+    ///
+    /// Use `-` to read from standard input. Files ending in `.gz` are decompressed, as are files
+    /// ending in `.zst` if the `zstd` feature is enabled.
+    #[clap(long, value_name = "PATH")]
+    pub metadata_path: Option<PathBuf>,
 }
 
 impl CargoMetadataOptions {
@@ -173,9 +543,62 @@ impl CargoMetadataOptions {
         }
         command
     }
+
+    /// Obtains `cargo metadata` output, either by reading it from `metadata_path` or by running
+    /// the command returned by [`make_command`](Self::make_command).
+    pub fn metadata(&self) -> Result<CargoMetadata> {
+        let metadata_path = match &self.metadata_path {
+            Some(metadata_path) => metadata_path,
+            None => {
+                debug!("running cargo metadata");
+                return Ok(self.make_command().exec()?);
+            }
+        };
+
+        let mut json = String::new();
+        debug!("reading metadata from {}", metadata_path.display());
+        if metadata_path == Path::new("-") {
+            io::stdin()
+                .read_to_string(&mut json)
+                .wrap_err("error reading metadata from standard input")?;
+        } else {
+            let file = File::open(metadata_path)
+                .wrap_err_with(|| format!("error opening {}", metadata_path.display()))?;
+            let mut reader: Box<dyn Read> =
+                match metadata_path.extension().and_then(|ext| ext.to_str()) {
+                    Some("gz") => Box::new(GzDecoder::new(file)),
+                    #[cfg(feature = "zstd")]
+                    Some("zst") => Box::new(
+                        zstd::Decoder::new(file).wrap_err("error initializing zstd decoder")?,
+                    ),
+                    #[cfg(not(feature = "zstd"))]
+                    Some("zst") => bail!(
+                        "error reading {}: zstd support isn't enabled (enable the `zstd` feature)",
+                        metadata_path.display()
+                    ),
+                    _ => Box::new(file),
+                };
+            reader
+                .read_to_string(&mut json)
+                .wrap_err_with(|| format!("error reading {}", metadata_path.display()))?;
+        }
+        Ok(CargoMetadata::parse_json(json)?)
+    }
+
+    /// Obtains `cargo metadata` output as with [`metadata`](Self::metadata), and builds a
+    /// `PackageGraph` from it.
+    pub fn build_graph(&self) -> Result<PackageGraph> {
+        let graph = self.metadata()?.build_graph()?;
+        debug!(
+            "built package graph with {} packages",
+            graph.package_count()
+        );
+        Ok(graph)
+    }
 }
 
-/// Parse a given triple, the string "current", or "any", into a platform.
+/// Parse a given triple, a comma-separated list of triples, the string "current", or "any", into a
+/// platform.
 ///
 /// TODO: This should eventually support JSON specs as well, probably.
 pub fn string_to_platform_spec(s: Option<&str>) -> Result<PlatformSpec> {
@@ -183,7 +606,272 @@ pub fn string_to_platform_spec(s: Option<&str>) -> Result<PlatformSpec> {
         Some("current") => Ok(PlatformSpec::current()?),
         Some("always") => Ok(PlatformSpec::Always),
         Some("any") => Ok(PlatformSpec::Any),
+        Some(triples) if triples.contains(',') => {
+            let platforms = triples
+                .split(',')
+                .map(|triple| Platform::new(triple.trim().to_owned(), TargetFeatures::Unknown))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(PlatformSpec::multi(platforms))
+        }
         Some(triple) => Ok(Platform::new(triple.to_owned(), TargetFeatures::Unknown)?.into()),
         None => Ok(PlatformSpec::Any),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    fn feature_sets<'g>(
+        graph: &'g PackageGraph,
+        args: &[&str],
+    ) -> Result<(FeatureSet<'g>, FeatureSet<'g>)> {
+        let opts = PackagesAndFeatures::try_parse_from(
+            std::iter::once("test").chain(args.iter().copied()),
+        )
+        .expect("arguments parsed");
+        opts.make_feature_sets(graph)
+    }
+
+    fn package_names<'g>(graph: &'g PackageGraph, args: &[&str]) -> Result<Vec<&'g str>> {
+        let opts = PackagesAndFeatures::try_parse_from(
+            std::iter::once("test").chain(args.iter().copied()),
+        )
+        .expect("arguments parsed");
+        let package_set = opts.make_package_set(graph)?;
+        let mut names: Vec<_> = package_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect();
+        names.sort_unstable();
+        Ok(names)
+    }
+
+    fn contains(feature_set: &FeatureSet<'_>, feature_id: FeatureId<'_>) -> bool {
+        feature_set
+            .contains(feature_id)
+            .expect("feature ID is known")
+    }
+
+    fn error_message(graph: &PackageGraph, args: &[&str]) -> String {
+        match feature_sets(graph, args) {
+            Ok(_) => panic!("{:?} should fail", args),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn features_namespaced_weak() {
+        let graph = JsonFixture::metadata_weak_namespaced_features().graph();
+        let package_id = graph
+            .workspace()
+            .member_by_name("namespaced-weak")
+            .expect("namespaced-weak is a workspace member")
+            .id();
+        let smallvec_id = graph
+            .resolve_package_name("smallvec")
+            .package_ids(DependencyDirection::Forward)
+            .next()
+            .expect("smallvec is present");
+        let arrayvec_id = graph
+            .resolve_package_name("arrayvec")
+            .package_ids(DependencyDirection::Forward)
+            .next()
+            .expect("arrayvec is present");
+
+        // feature
+        let (initials, _) = feature_sets(graph, &["--features", "foo"]).expect("valid features");
+        assert!(contains(&initials, FeatureId::named(package_id, "foo")));
+
+        // package/feature
+        let (initials, _) = feature_sets(
+            graph,
+            &["-p", "namespaced-weak", "--features", "namespaced-weak/bar"],
+        )
+        .expect("valid features");
+        assert!(contains(&initials, FeatureId::named(package_id, "bar")));
+
+        // dep-name/feature enables the optional dependency.
+        let (initials, features_only) =
+            feature_sets(graph, &["--features", "smallvec/union"]).expect("valid features");
+        assert!(contains(
+            &initials,
+            FeatureId::optional_dependency(package_id, "smallvec")
+        ));
+        assert!(contains(
+            &features_only,
+            FeatureId::named(smallvec_id, "union")
+        ));
+
+        // dep-name?/feature only applies if the dependency is enabled through other means.
+        let (initials, features_only) =
+            feature_sets(graph, &["--features", "arrayvec?/std"]).expect("valid features");
+        assert!(!contains(
+            &initials,
+            FeatureId::optional_dependency(package_id, "arrayvec")
+        ));
+        assert!(!contains(
+            &features_only,
+            FeatureId::named(arrayvec_id, "std")
+        ));
+        let (_, features_only) =
+            feature_sets(graph, &["--features", "foo,arrayvec?/std"]).expect("valid features");
+        assert!(contains(
+            &features_only,
+            FeatureId::named(arrayvec_id, "std")
+        ));
+
+        assert_eq!(
+            error_message(graph, &["--features", "nope"]),
+            "none of the selected packages contains the feature `nope`",
+        );
+        assert_eq!(
+            error_message(graph, &["--features", "pathdiff"]),
+            "none of the selected packages contains the feature `pathdiff` (an optional \
+            dependency has that name, but it is referred to with the `dep:` syntax in the \
+            features table, so it has no implicit feature)",
+        );
+        assert_eq!(
+            error_message(graph, &["--features", "namespaced-weak/nope"]),
+            "package `namespaced-weak` does not have the feature `nope`",
+        );
+        assert_eq!(
+            error_message(graph, &["--features", "nope/std"]),
+            "none of the selected packages has a dependency named `nope` (in `nope/std`)",
+        );
+        assert_eq!(
+            error_message(graph, &["--features", "smallvec?/nope"]),
+            "dependency `smallvec` of package `namespaced-weak` does not have the feature `nope`",
+        );
+    }
+
+    #[test]
+    fn features_implicit_optional() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let guppy_id = graph
+            .workspace()
+            .member_by_name("guppy")
+            .expect("guppy is a workspace member")
+            .id();
+        let target_spec_id = graph
+            .workspace()
+            .member_by_name("target-spec")
+            .expect("target-spec is a workspace member")
+            .id();
+
+        // Optional dependencies that aren't referred to with dep: have implicit features.
+        for features in ["guppy-summaries", "guppy/guppy-summaries"] {
+            let (initials, _) = feature_sets(graph, &["-p", "guppy", "--features", features])
+                .expect("valid features");
+            assert!(
+                contains(&initials, FeatureId::named(guppy_id, "guppy-summaries")),
+                "{} enables the implicit feature",
+                features
+            );
+        }
+
+        let (_, features_only) =
+            feature_sets(graph, &["-p", "guppy", "--features", "target-spec/serde"])
+                .expect("valid features");
+        assert!(contains(
+            &features_only,
+            FeatureId::named(target_spec_id, "serde")
+        ));
+    }
+
+    #[test]
+    fn packages_workspace_exclude() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let mut all: Vec<_> = graph
+            .workspace()
+            .iter()
+            .map(|package| package.name())
+            .collect();
+        all.sort_unstable();
+        let names = |args: &[&str]| package_names(graph, args).expect("valid packages");
+
+        assert_eq!(names(&[]), all, "entire workspace by default");
+        assert_eq!(names(&["--workspace"]), all);
+        assert_eq!(names(&["--all"]), all, "--all is an alias for --workspace");
+        assert_eq!(
+            names(&["--workspace", "-p", "guppy"]),
+            all,
+            "--workspace takes precedence over --package"
+        );
+
+        // Names without glob characters are matched exactly.
+        assert_eq!(names(&["-p", "guppy"]), ["guppy"]);
+        assert_eq!(
+            names(&["-p", "guppy-*"]),
+            ["guppy-benchmarks", "guppy-cmdlib", "guppy-summaries"]
+        );
+        assert_eq!(
+            names(&["-p", "guppy", "-p", "*-spec", "-p", "fixture?"]),
+            ["fixtures", "guppy", "target-spec"]
+        );
+        assert_eq!(
+            names(&["-p", "[ct]*"]),
+            ["cargo-compare", "cargo-guppy", "target-spec"]
+        );
+        // Regular expressions are surrounded by slashes, and must match the whole name.
+        assert_eq!(
+            names(&["-p", "/guppy-(cmdlib|summaries)/"]),
+            ["guppy-cmdlib", "guppy-summaries"]
+        );
+        assert_eq!(names(&["-p", "/guppy/"]), ["guppy"]);
+
+        assert_eq!(
+            names(&[
+                "--workspace",
+                "--exclude",
+                "guppy*",
+                "--exclude",
+                "cargo-guppy"
+            ]),
+            [
+                "cargo-compare",
+                "fixture-manager",
+                "fixtures",
+                "proptest-ext",
+                "target-spec"
+            ]
+        );
+        assert_eq!(
+            names(&["--workspace", "--exclude", "nope-*", "--exclude", "nope"]),
+            all,
+            "unmatched --exclude patterns are ignored"
+        );
+
+        let error_message = |args: &[&str]| match package_names(graph, args) {
+            Ok(_) => panic!("{:?} should fail", args),
+            Err(err) => err.to_string(),
+        };
+        assert_eq!(
+            error_message(&["-p", "nope-*"]),
+            "name pattern did not match any workspace packages: nope-*"
+        );
+        assert_eq!(
+            error_message(&["-p", "guppy", "-p", "nope"]),
+            "name pattern did not match any workspace packages: nope"
+        );
+        // Only packages in the workspace are matched.
+        assert_eq!(
+            error_message(&["-p", "serde*"]),
+            "name pattern did not match any workspace packages: serde*"
+        );
+        for invalid in ["guppy-[*", "/guppy-(/"] {
+            assert_eq!(
+                error_message(&["-p", invalid]),
+                format!("invalid package pattern `{}`", invalid),
+            );
+            assert_eq!(
+                error_message(&["--workspace", "--exclude", invalid]),
+                format!("invalid package pattern `{}`", invalid),
+            );
+        }
+
+        PackagesAndFeatures::try_parse_from(["test", "--exclude", "guppy"])
+            .expect_err("--exclude requires --workspace");
+    }
+}
@@ -31,6 +31,8 @@ impl PackagesAndFeatures {
                             .into_iter()
                             .map(|package| package.to_string())
                             .collect(),
+                        workspace: false,
+                        exclude: vec![],
                         features_only: features_only
                             .into_iter()
                             .map(|package| package.to_string())
@@ -0,0 +1,89 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Errors returned by `guppy-edit`.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{error, fmt, io};
+use toml_edit::TomlError;
+
+/// An error that occurred while reading, editing or writing out a manifest.
+#[derive(Debug)]
+pub struct EditError {
+    message: String,
+    path: Utf8PathBuf,
+    kind: EditErrorKind,
+}
+
+impl EditError {
+    /// Returns the message corresponding to the error.
+    #[inline]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the path of the manifest at which the error occurred.
+    #[inline]
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    pub(crate) fn io(
+        message: impl Into<String>,
+        path: impl Into<Utf8PathBuf>,
+        err: io::Error,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            path: path.into(),
+            kind: EditErrorKind::Io { err },
+        }
+    }
+
+    pub(crate) fn toml(
+        message: impl Into<String>,
+        path: impl Into<Utf8PathBuf>,
+        err: TomlError,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            path: path.into(),
+            kind: EditErrorKind::Toml { err },
+        }
+    }
+
+    pub(crate) fn misc(message: impl Into<String>, path: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            message: message.into(),
+            path: path.into(),
+            kind: EditErrorKind::Misc,
+        }
+    }
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "for path {}, {}", self.path, self.message)
+    }
+}
+
+impl error::Error for EditError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            EditErrorKind::Io { err } => Some(err),
+            EditErrorKind::Toml { err } => Some(err),
+            EditErrorKind::Misc => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum EditErrorKind {
+    Io { err: io::Error },
+    Toml { err: TomlError },
+    Misc,
+}
@@ -0,0 +1,62 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Format-preserving edits to `Cargo.toml` files.
+//!
+//! `guppy` reads the metadata for a workspace but doesn't write anything back. This crate provides
+//! the other half: adding, removing and updating dependency entries in a manifest while keeping
+//! its formatting and comments intact.
+//!
+//! Edits are made through a [`ManifestEditor`], which can be created for a path or for a package
+//! obtained from a [`PackageGraph`](guppy::graph::PackageGraph).
+//!
+//! # Examples
+//!
+//! ```rust
+//! use guppy::DependencyKind;
+//! use guppy_edit::{DependencySection, ManifestEditor};
+//!
+//! static MANIFEST: &str = r#"[package]
+//! name = "foo"
+//! version = "0.1.0"
+//!
+//! [dependencies]
+//! serde = "1.0" # Used for serialization.
+//! "#;
+//!
+//! let mut editor = ManifestEditor::parse("foo/Cargo.toml", MANIFEST).expect("manifest parsed");
+//! let normal = DependencySection::new(DependencyKind::Normal);
+//!
+//! // Update an existing dependency and add a new one.
+//! editor.set_dependency_version(&normal, "serde", "1.0.140").expect("version updated");
+//! editor
+//!     .insert_dependency(&DependencySection::new(DependencyKind::Build), "cc", "1.0")
+//!     .expect("dependency added");
+//!
+//! assert_eq!(
+//!     editor.to_string(),
+//!     r#"[package]
+//! name = "foo"
+//! version = "0.1.0"
+//!
+//! [dependencies]
+//! serde = "1.0.140" # Used for serialization.
+//!
+//! [build-dependencies]
+//! cc = "1.0"
+//! "#,
+//! );
+//!
+//! // editor.write() would write the changes out to foo/Cargo.toml.
+//! ```
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+mod errors;
+mod manifest;
+
+pub use errors::*;
+pub use manifest::*;
+// Re-export toml_edit so that callers can construct values to insert.
+pub use toml_edit;
@@ -0,0 +1,611 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::errors::EditError;
+use atomicwrites::{AtomicFile, OverwriteBehavior};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::{graph::PackageMetadata, DependencyKind};
+use std::{fmt, fs, io::Write, mem};
+use toml_edit::{Document, Item, Table, TableLike, Value};
+
+/// A section of a manifest that lists dependencies.
+///
+/// This is one of `[dependencies]`, `[dev-dependencies]` or `[build-dependencies]`, either at the
+/// top level or within a `[target.'<spec>']` table.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DependencySection {
+    kind: DependencyKind,
+    target: Option<String>,
+}
+
+impl DependencySection {
+    /// Creates a new `DependencySection` for a top-level section.
+    pub fn new(kind: DependencyKind) -> Self {
+        Self { kind, target: None }
+    }
+
+    /// Creates a new `DependencySection` for a section that only applies to the given target
+    /// specification, e.g. `cfg(unix)`.
+    pub fn for_target(kind: DependencyKind, target: impl Into<String>) -> Self {
+        Self {
+            kind,
+            target: Some(target.into()),
+        }
+    }
+
+    /// Returns the kind of dependencies in this section.
+    pub fn kind(&self) -> DependencyKind {
+        self.kind
+    }
+
+    /// Returns the target specification this section applies to, or `None` if this is a
+    /// top-level section.
+    pub fn target(&self) -> Option<&str> {
+        self.target.as_deref()
+    }
+
+    /// Returns the name of the table for this section, e.g. `dev-dependencies`.
+    pub fn table_name(&self) -> &'static str {
+        match self.kind {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Development => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+}
+
+impl fmt::Display for DependencySection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.target {
+            Some(target) => write!(f, "[target.'{}'.{}]", target, self.table_name()),
+            None => write!(f, "[{}]", self.table_name()),
+        }
+    }
+}
+
+/// An editor for a single `Cargo.toml` file.
+///
+/// Edits are made to an in-memory document that retains the formatting and comments of the
+/// original file. Call [`write`](Self::write) to write the changes out.
+#[derive(Clone, Debug)]
+pub struct ManifestEditor {
+    manifest_path: Utf8PathBuf,
+    original: String,
+    document: Document,
+}
+
+impl ManifestEditor {
+    /// Reads and parses the manifest at the given path.
+    pub fn read(manifest_path: impl Into<Utf8PathBuf>) -> Result<Self, EditError> {
+        let manifest_path = manifest_path.into();
+        let contents = fs::read_to_string(&manifest_path)
+            .map_err(|err| EditError::io("error reading manifest", &manifest_path, err))?;
+        Self::parse(manifest_path, contents)
+    }
+
+    /// Reads and parses the manifest for the given package.
+    pub fn for_package(package: PackageMetadata<'_>) -> Result<Self, EditError> {
+        Self::read(package.manifest_path())
+    }
+
+    /// Parses the contents of a manifest.
+    ///
+    /// `manifest_path` is used for error messages and by [`write`](Self::write).
+    pub fn parse(
+        manifest_path: impl Into<Utf8PathBuf>,
+        contents: impl Into<String>,
+    ) -> Result<Self, EditError> {
+        let manifest_path = manifest_path.into();
+        let original = contents.into();
+        let document = original
+            .parse::<Document>()
+            .map_err(|err| EditError::toml("error parsing manifest", &manifest_path, err))?;
+        Ok(Self {
+            manifest_path,
+            original,
+            document,
+        })
+    }
+
+    /// Returns the path to the manifest.
+    pub fn manifest_path(&self) -> &Utf8Path {
+        &self.manifest_path
+    }
+
+    /// Returns the contents of the manifest before any edits were made.
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    /// Returns the document being edited.
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Returns a mutable reference to the document being edited, for edits not covered by this
+    /// type.
+    pub fn document_mut(&mut self) -> &mut Document {
+        &mut self.document
+    }
+
+    /// Returns true if the edits made so far have changed the contents of the manifest.
+    pub fn is_changed(&self) -> bool {
+        self.document.to_string() != self.original
+    }
+
+    /// Returns all the dependency sections present in the manifest, in the order they're checked
+    /// by [`set_dependency_path`](Self::set_dependency_path).
+    pub fn sections(&self) -> Result<Vec<DependencySection>, EditError> {
+        let root = self.document.as_table();
+        let mut sections: Vec<_> = DependencyKind::VALUES
+            .iter()
+            .filter(|kind| root.contains_key(DependencySection::new(**kind).table_name()))
+            .map(|kind| DependencySection::new(*kind))
+            .collect();
+
+        if let Some(targets) = root.get("target") {
+            let targets = targets
+                .as_table_like()
+                .ok_or_else(|| EditError::misc("[target] is not a table", &self.manifest_path))?;
+            for (target, target_table) in targets.iter() {
+                // Entries in [target] that aren't tables can't contain dependencies.
+                let target_table = match target_table.as_table_like() {
+                    Some(target_table) => target_table,
+                    None => continue,
+                };
+                sections.extend(
+                    DependencyKind::VALUES
+                        .iter()
+                        .map(|kind| DependencySection::for_target(*kind, target))
+                        .filter(|section| target_table.contains_key(section.table_name())),
+                );
+            }
+        }
+
+        Ok(sections)
+    }
+
+    /// Returns the entry for a dependency in the given section, if it is present.
+    ///
+    /// `name` is the key the dependency is listed under, which may differ from the package name if
+    /// the dependency is renamed.
+    pub fn get_dependency(
+        &self,
+        section: &DependencySection,
+        name: &str,
+    ) -> Result<Option<&Item>, EditError> {
+        let root = self.document.as_table() as &dyn TableLike;
+        let parent = match &section.target {
+            Some(target) => {
+                let targets = match self.get_table(root, "target", "[target]")? {
+                    Some(targets) => targets,
+                    None => return Ok(None),
+                };
+                match self.get_table(targets, target, format!("[target.'{}']", target))? {
+                    Some(target_table) => target_table,
+                    None => return Ok(None),
+                }
+            }
+            None => root,
+        };
+        let table = self.get_table(parent, section.table_name(), section)?;
+        Ok(table.and_then(|table| table.get(name)))
+    }
+
+    /// Adds a dependency to the given section, replacing any existing entry with the same name.
+    ///
+    /// The section is created if it doesn't already exist. If an entry is replaced, the new entry
+    /// keeps its position and any comments around it.
+    ///
+    /// Returns the entry that was replaced, if any.
+    pub fn insert_dependency(
+        &mut self,
+        section: &DependencySection,
+        name: &str,
+        value: impl Into<Value>,
+    ) -> Result<Option<Item>, EditError> {
+        let manifest_path = &self.manifest_path;
+        let table = section_table_mut(manifest_path, &mut self.document, section, true)?
+            .expect("section was created");
+        let mut value = value.into();
+        match table.get_mut(name) {
+            Some(Item::Value(existing)) => {
+                copy_decor(existing, &mut value);
+                Ok(Some(Item::Value(mem::replace(existing, value))))
+            }
+            _ => Ok(table.insert(name, Item::Value(value))),
+        }
+    }
+
+    /// Removes a dependency from the given section.
+    ///
+    /// Returns the entry that was removed, or `None` if the dependency wasn't present.
+    pub fn remove_dependency(
+        &mut self,
+        section: &DependencySection,
+        name: &str,
+    ) -> Result<Option<Item>, EditError> {
+        let manifest_path = &self.manifest_path;
+        match section_table_mut(manifest_path, &mut self.document, section, false)? {
+            Some(table) => Ok(table.remove(name)),
+            None => Ok(None),
+        }
+    }
+
+    /// Updates the version requirement of a dependency in the given section.
+    ///
+    /// Both `name = "1.0"` and `name = { version = "1.0", ... }` forms are supported, and the
+    /// formatting of the entry is retained.
+    ///
+    /// Returns false if the dependency wasn't present.
+    pub fn set_dependency_version(
+        &mut self,
+        section: &DependencySection,
+        name: &str,
+        version: &str,
+    ) -> Result<bool, EditError> {
+        let manifest_path = &self.manifest_path;
+        let table = match section_table_mut(manifest_path, &mut self.document, section, false)? {
+            Some(table) => table,
+            None => return Ok(false),
+        };
+        let item = match table.get_mut(name) {
+            Some(item) => item,
+            None => return Ok(false),
+        };
+
+        match item {
+            Item::Value(value) if value.is_str() => {
+                replace_decorated(value, version);
+            }
+            item => {
+                let dep_table = item.as_table_like_mut().ok_or_else(|| {
+                    EditError::misc(
+                        format!("in section {}, {} is not a string or table", section, name),
+                        manifest_path,
+                    )
+                })?;
+                match dep_table.get_mut("version") {
+                    Some(Item::Value(value)) => {
+                        replace_decorated(value, version);
+                    }
+                    Some(_) => {
+                        return Err(EditError::misc(
+                            format!("in section {}, {}.version is not a string", section, name),
+                            manifest_path,
+                        ));
+                    }
+                    None => {
+                        dep_table.insert("version", Item::Value(version.into()));
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Updates the version of the package in `[package]`, retaining the formatting of the entry.
+    ///
+    /// Returns an error if `[package]` doesn't have a version string, for example if the version
+    /// is inherited from the workspace.
+    pub fn set_package_version(&mut self, version: &str) -> Result<(), EditError> {
+        match self
+            .document
+            .get_mut("package")
+            .and_then(|package| package.get_mut("version"))
+        {
+            Some(Item::Value(value)) if value.is_str() => {
+                replace_decorated(value, version);
+                Ok(())
+            }
+            _ => Err(EditError::misc(
+                "[package] doesn't have a version string",
+                &self.manifest_path,
+            )),
+        }
+    }
+
+    /// Updates the path of a dependency in every section it is listed in.
+    ///
+    /// The path is written out with forward slashes, including on Windows.
+    ///
+    /// Returns the number of entries that were updated. Returns an error if an entry with this
+    /// name doesn't have a `path` key.
+    pub fn set_dependency_path(&mut self, name: &str, path: &Utf8Path) -> Result<usize, EditError> {
+        let path = with_forward_slashes(path);
+        let mut count = 0;
+        for section in self.sections()? {
+            let manifest_path = &self.manifest_path;
+            let table = section_table_mut(manifest_path, &mut self.document, &section, false)?
+                .expect("section is present");
+            let item = match table.get_mut(name) {
+                Some(item) => item,
+                None => continue,
+            };
+            let dep_table = item.as_table_like_mut().ok_or_else(|| {
+                EditError::misc(
+                    format!("in section {}, {} is not a table", section, name),
+                    manifest_path,
+                )
+            })?;
+            match dep_table.get_mut("path") {
+                Some(Item::Value(value)) if value.is_str() => {
+                    replace_decorated(value, path.as_str());
+                    count += 1;
+                }
+                _ => {
+                    return Err(EditError::misc(
+                        format!("in section {}, {}.path is not a string", section, name),
+                        manifest_path,
+                    ));
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Writes the edited manifest out to its path.
+    ///
+    /// The file is replaced atomically.
+    pub fn write(&self) -> Result<(), EditError> {
+        let atomic_file = AtomicFile::new(&self.manifest_path, OverwriteBehavior::AllowOverwrite);
+        match atomic_file.write(|file| write!(file, "{}", self.document)) {
+            Ok(()) => Ok(()),
+            Err(atomicwrites::Error::Internal(err)) | Err(atomicwrites::Error::User(err)) => Err(
+                EditError::io("error writing manifest", &self.manifest_path, err),
+            ),
+        }
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn get_table<'t>(
+        &self,
+        parent: &'t dyn TableLike,
+        key: &str,
+        display_name: impl fmt::Display,
+    ) -> Result<Option<&'t dyn TableLike>, EditError> {
+        match parent.get(key) {
+            Some(item) => match item.as_table_like() {
+                Some(table) => Ok(Some(table)),
+                None => Err(EditError::misc(
+                    format!("{} is not a table", display_name),
+                    &self.manifest_path,
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+impl fmt::Display for ManifestEditor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+// ---
+// Helper functions
+// ---
+
+fn section_table_mut<'doc>(
+    manifest_path: &Utf8Path,
+    document: &'doc mut Document,
+    section: &DependencySection,
+    create: bool,
+) -> Result<Option<&'doc mut dyn TableLike>, EditError> {
+    let root = document.as_table_mut() as &mut dyn TableLike;
+    let parent = match &section.target {
+        Some(target) => {
+            let targets = match table_mut(manifest_path, root, "target", "[target]", create)? {
+                Some(targets) => targets,
+                None => return Ok(None),
+            };
+            match table_mut(
+                manifest_path,
+                targets,
+                target,
+                format!("[target.'{}']", target),
+                create,
+            )? {
+                Some(target_table) => target_table,
+                None => return Ok(None),
+            }
+        }
+        None => root,
+    };
+    table_mut(manifest_path, parent, section.table_name(), section, create)
+}
+
+fn table_mut<'t>(
+    manifest_path: &Utf8Path,
+    parent: &'t mut dyn TableLike,
+    key: &str,
+    display_name: impl fmt::Display,
+    create: bool,
+) -> Result<Option<&'t mut dyn TableLike>, EditError> {
+    if !parent.contains_key(key) {
+        if !create {
+            return Ok(None);
+        }
+        let mut table = Table::new();
+        table.set_implicit(true);
+        parent.insert(key, Item::Table(table));
+    }
+    match parent
+        .get_mut(key)
+        .expect("key is present")
+        .as_table_like_mut()
+    {
+        Some(table) => Ok(Some(table)),
+        None => Err(EditError::misc(
+            format!("{} is not a table", display_name),
+            manifest_path,
+        )),
+    }
+}
+
+/// Replaces the value while retaining the decor.
+fn replace_decorated(dest: &mut Value, new_value: impl Into<Value>) -> Value {
+    let mut new_value = new_value.into();
+    copy_decor(dest, &mut new_value);
+    mem::replace(dest, new_value)
+}
+
+/// Copies over the decor from `src` to `dest`, unless `dest` already has its own.
+fn copy_decor(src: &Value, dest: &mut Value) {
+    let decor = src.decor();
+    let new_decor = dest.decor_mut();
+    if let (Some(prefix), None) = (decor.prefix(), new_decor.prefix()) {
+        new_decor.set_prefix(prefix);
+    }
+    if let (Some(suffix), None) = (decor.suffix(), new_decor.suffix()) {
+        new_decor.set_suffix(suffix);
+    }
+}
+
+// Always write out paths with forward slashes, including on Windows.
+fn with_forward_slashes(path: &Utf8Path) -> Utf8PathBuf {
+    let components: Vec<_> = path.iter().collect();
+    components.join("/").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    static MANIFEST: &str = indoc! {r#"
+        [package]
+        name = "foo"
+        version = "0.1.0"
+
+        [dependencies]
+        # A comment about bar.
+        bar = { version = "1.0", path = "../bar" } # trailing
+        baz = "0.3"
+
+        [target.'cfg(unix)'.dev-dependencies]
+        bar = { path = "../bar", features = ["test"] }
+    "#};
+
+    fn editor() -> ManifestEditor {
+        ManifestEditor::parse("foo/Cargo.toml", MANIFEST).expect("manifest parsed")
+    }
+
+    #[test]
+    fn sections() {
+        let editor = editor();
+        assert_eq!(
+            editor.sections().expect("sections are valid"),
+            vec![
+                DependencySection::new(DependencyKind::Normal),
+                DependencySection::for_target(DependencyKind::Development, "cfg(unix)"),
+            ],
+        );
+        assert!(!editor.is_changed(), "no edits made");
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut editor = editor();
+        let normal = DependencySection::new(DependencyKind::Normal);
+        let build = DependencySection::new(DependencyKind::Build);
+
+        let replaced = editor
+            .insert_dependency(&normal, "baz", "0.4")
+            .expect("insert succeeded");
+        assert!(replaced.is_some(), "baz was replaced");
+        editor
+            .insert_dependency(&build, "cc", "1.0")
+            .expect("insert succeeded");
+        let removed = editor
+            .remove_dependency(&normal, "bar")
+            .expect("remove succeeded");
+        assert!(removed.is_some(), "bar was removed");
+        let removed = editor
+            .remove_dependency(&DependencySection::new(DependencyKind::Development), "bar")
+            .expect("remove succeeded");
+        assert!(removed.is_none(), "section doesn't exist");
+
+        assert_eq!(
+            editor.to_string(),
+            indoc! {r#"
+                [package]
+                name = "foo"
+                version = "0.1.0"
+
+                [dependencies]
+                baz = "0.4"
+
+                [target.'cfg(unix)'.dev-dependencies]
+                bar = { path = "../bar", features = ["test"] }
+
+                [build-dependencies]
+                cc = "1.0"
+            "#},
+        );
+    }
+
+    #[test]
+    fn set_version_and_path() {
+        let mut editor = editor();
+        let normal = DependencySection::new(DependencyKind::Normal);
+
+        assert!(editor
+            .set_dependency_version(&normal, "bar", "1.1")
+            .expect("set version succeeded"));
+        assert!(editor
+            .set_dependency_version(&normal, "baz", "0.4")
+            .expect("set version succeeded"));
+        assert!(!editor
+            .set_dependency_version(&normal, "quux", "0.1")
+            .expect("set version succeeded"));
+        editor
+            .set_package_version("0.2.0")
+            .expect("set package version succeeded");
+        assert_eq!(
+            editor
+                .set_dependency_path("bar", Utf8Path::new("../libs/bar"))
+                .expect("set path succeeded"),
+            2,
+            "both entries for bar updated"
+        );
+
+        assert_eq!(
+            editor.to_string(),
+            indoc! {r#"
+                [package]
+                name = "foo"
+                version = "0.2.0"
+
+                [dependencies]
+                # A comment about bar.
+                bar = { version = "1.1", path = "../libs/bar" } # trailing
+                baz = "0.4"
+
+                [target.'cfg(unix)'.dev-dependencies]
+                bar = { path = "../libs/bar", features = ["test"] }
+            "#},
+        );
+        assert!(editor.is_changed(), "edits made");
+
+        let err = editor
+            .set_dependency_path("baz", Utf8Path::new("../baz"))
+            .expect_err("baz is not a table");
+        assert_eq!(
+            err.message(),
+            "in section [dependencies], baz is not a table"
+        );
+
+        let mut editor =
+            ManifestEditor::parse("workspace/Cargo.toml", "[workspace]\nmembers = [\"foo\"]\n")
+                .expect("manifest parsed");
+        let err = editor
+            .set_package_version("0.2.0")
+            .expect_err("no package version");
+        assert_eq!(err.message(), "[package] doesn't have a version string");
+    }
+}
@@ -9,7 +9,10 @@
 //! summaries or through `SummaryDiff::new`.
 
 pub use crate::report::SummaryReport;
-use crate::{PackageInfo, PackageMap, PackageStatus, Summary, SummaryId, SummarySource};
+use crate::{
+    policy::DiffPolicy, EdgeSet, PackageInfo, PackageMap, PackageStatus, Summary, SummaryEdge,
+    SummaryId, SummarySource,
+};
 use diffus::{edit, Diffable};
 use semver::Version;
 use serde::{ser::SerializeStruct, Serialize};
@@ -68,6 +71,21 @@ use std::{
 /// old-status = "direct"
 /// old-features = ["std"]
 /// ```
+///
+/// If both summaries record dependency edges, changed edges are serialized as well:
+///
+/// ```toml
+/// [[target-edges.added]]
+/// [target-edges.added.from]
+/// name = "foo"
+/// version = "1.2.3"
+/// workspace-path = "foo"
+///
+/// [target-edges.added.to]
+/// name = "dep"
+/// version = "0.4.3"
+/// crates-io = true
+/// ```
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct SummaryDiff<'a> {
@@ -76,14 +94,39 @@ pub struct SummaryDiff<'a> {
 
     /// Diff of host packages.
     pub host_packages: PackageDiff<'a>,
+
+    /// Diff of dependency edges between target packages.
+    ///
+    /// This is empty unless both summaries support dependency edges.
+    #[serde(skip_serializing_if = "EdgeDiff::is_unchanged")]
+    pub target_edges: EdgeDiff<'a>,
+
+    /// Diff of dependency edges to host packages.
+    ///
+    /// This is empty unless both summaries support dependency edges.
+    #[serde(skip_serializing_if = "EdgeDiff::is_unchanged")]
+    pub host_edges: EdgeDiff<'a>,
 }
 
 impl<'a> SummaryDiff<'a> {
     /// Computes a diff between two summaries.
     pub fn new(old: &'a Summary, new: &'a Summary) -> Self {
+        // Comparing a summary with edges against one without would report every edge as changed.
+        let (target_edges, host_edges) =
+            if old.version.supports_edges() && new.version.supports_edges() {
+                (
+                    EdgeDiff::new(&old.target_edges, &new.target_edges),
+                    EdgeDiff::new(&old.host_edges, &new.host_edges),
+                )
+            } else {
+                (EdgeDiff::default(), EdgeDiff::default())
+            };
+
         Self {
             target_packages: PackageDiff::new(&old.target_packages, &new.target_packages),
             host_packages: PackageDiff::new(&old.host_packages, &new.host_packages),
+            target_edges,
+            host_edges,
         }
     }
 
@@ -94,7 +137,10 @@ impl<'a> SummaryDiff<'a> {
 
     /// Returns true if there are no changes in this diff.
     pub fn is_unchanged(&self) -> bool {
-        self.target_packages.is_unchanged() && self.host_packages.is_unchanged()
+        self.target_packages.is_unchanged()
+            && self.host_packages.is_unchanged()
+            && self.target_edges.is_unchanged()
+            && self.host_edges.is_unchanged()
     }
 
     /// Returns a report for this diff.
@@ -103,6 +149,15 @@ impl<'a> SummaryDiff<'a> {
     pub fn report<'b>(&'b self) -> SummaryReport<'a, 'b> {
         SummaryReport::new(self)
     }
+
+    /// Returns a diff with only the changes that violate the given policy.
+    ///
+    /// Changed packages that are acceptable under the policy are removed, and modified packages
+    /// only retain the details that aren't acceptable. Unchanged packages are left as-is. The
+    /// returned diff is unchanged if and only if all changes are acceptable.
+    pub fn apply_policy(&self, policy: &DiffPolicy) -> SummaryDiff<'a> {
+        policy.apply_summary(self)
+    }
 }
 
 /// Type alias for list entries in the `PackageDiff::unchanged` map.
@@ -306,6 +361,37 @@ impl<'a> Serialize for PackageDiff<'a> {
     }
 }
 
+/// A diff of dependency edges from a particular section of a summary.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EdgeDiff<'a> {
+    /// Edges present in the new summary but not in the old one.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub added: BTreeSet<&'a SummaryEdge>,
+
+    /// Edges present in the old summary but not in the new one.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub removed: BTreeSet<&'a SummaryEdge>,
+}
+
+impl<'a> EdgeDiff<'a> {
+    /// Constructs a new `EdgeDiff` from a pair of `EdgeSet` instances.
+    ///
+    /// An edge is keyed by the full summary IDs of both its ends, so a version change to either
+    /// end shows up as one removed and one added edge.
+    pub fn new(old: &'a EdgeSet, new: &'a EdgeSet) -> Self {
+        Self {
+            added: new.difference(old).collect(),
+            removed: old.difference(new).collect(),
+        }
+    }
+
+    /// Returns true if there are no changes in this diff.
+    pub fn is_unchanged(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
 /// The diff status for a particular summary ID and source.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "kebab-case", tag = "change")]
@@ -7,6 +7,9 @@
 //! platforms. A summary file can be checked into a repository, kept up to date and compared in CI,
 //! and allow for tracking results of builds over time.
 //!
+//! Starting with version 2 of the format, a summary may also record the direct dependency edges
+//! between packages, so that diffs can report changes like "`foo` now depends on `bar`".
+//!
 //! `guppy-summaries` is designed to be small and independent of the main `guppy` crate.
 //!
 //! # Examples
@@ -111,6 +114,7 @@
 #![warn(missing_docs)]
 
 pub mod diff;
+pub mod policy;
 // report::SummaryReport is exported through the diff module.
 mod report;
 mod summary;
@@ -0,0 +1,180 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Policies declaring which changes between summaries are acceptable.
+//!
+//! A `SummaryDiff` is either changed or unchanged, so checking it in CI fails on any churn. A
+//! [`DiffPolicy`] lists classes of changes that are acceptable, and
+//! [`SummaryDiff::apply_policy`](crate::diff::SummaryDiff::apply_policy) reduces a diff to the
+//! changes that violate the policy.
+
+use crate::{
+    diff::{EdgeDiff, PackageDiff, SummaryDiff, SummaryDiffStatus},
+    SummaryEdge, SummaryId, SummarySource,
+};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+
+/// A policy declaring which changes between two summaries are acceptable.
+///
+/// A policy can be deserialized from TOML. An example:
+///
+/// ```toml
+/// # Ignore all changes to these packages.
+/// ignore-packages = ["generated-bindings"]
+/// # Allow version changes to packages with a path source, e.g. workspace members.
+/// allow-path-version-changes = true
+/// # Allow features and optional dependencies to be added to these packages.
+/// allow-added-features = ["serde", "tokio"]
+/// ```
+///
+/// All changes are violations by default.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DiffPolicy {
+    /// Packages, by name, for which all changes are acceptable.
+    ///
+    /// This includes changes to dependency edges from or to these packages.
+    #[serde(default)]
+    pub ignore_packages: BTreeSet<String>,
+
+    /// If true, changes to the version of a package whose source is a path are acceptable.
+    ///
+    /// Dependency edges that only differ in the versions of such packages are acceptable as well.
+    #[serde(default)]
+    pub allow_path_version_changes: bool,
+
+    /// Packages, by name, for which added features and optional dependencies are acceptable.
+    #[serde(default)]
+    pub allow_added_features: BTreeSet<String>,
+}
+
+impl DiffPolicy {
+    /// Deserializes a policy from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    pub(crate) fn apply_summary<'a>(&self, diff: &SummaryDiff<'a>) -> SummaryDiff<'a> {
+        SummaryDiff {
+            target_packages: self.apply_package(&diff.target_packages),
+            host_packages: self.apply_package(&diff.host_packages),
+            target_edges: self.apply_edges(&diff.target_edges),
+            host_edges: self.apply_edges(&diff.host_edges),
+        }
+    }
+
+    fn apply_package<'a>(&self, diff: &PackageDiff<'a>) -> PackageDiff<'a> {
+        let changed = diff
+            .changed
+            .iter()
+            .filter_map(|(summary_id, status)| {
+                self.violations(summary_id, status)
+                    .map(|status| (*summary_id, status))
+            })
+            .collect();
+        PackageDiff {
+            changed,
+            unchanged: diff.unchanged.clone(),
+        }
+    }
+
+    fn apply_edges<'a>(&self, diff: &EdgeDiff<'a>) -> EdgeDiff<'a> {
+        let is_ignored = |edge: &SummaryEdge| {
+            self.ignore_packages.contains(&edge.from.name)
+                || self.ignore_packages.contains(&edge.to.name)
+        };
+        let mut added: BTreeSet<_> = diff
+            .added
+            .iter()
+            .copied()
+            .filter(|edge| !is_ignored(edge))
+            .collect();
+        let mut removed: BTreeSet<_> = diff
+            .removed
+            .iter()
+            .copied()
+            .filter(|edge| !is_ignored(edge))
+            .collect();
+
+        if self.allow_path_version_changes {
+            // Cancel out edges that are only added and removed because a path package's version
+            // changed.
+            let added_keys: HashSet<_> = added.iter().map(|edge| edge_key(edge)).collect();
+            let removed_keys: HashSet<_> = removed.iter().map(|edge| edge_key(edge)).collect();
+            added.retain(|edge| !removed_keys.contains(&edge_key(edge)));
+            removed.retain(|edge| !added_keys.contains(&edge_key(edge)));
+        }
+
+        EdgeDiff { added, removed }
+    }
+
+    /// Returns the parts of this status that violate the policy, or `None` if it's acceptable.
+    fn violations<'a>(
+        &self,
+        summary_id: &SummaryId,
+        status: &SummaryDiffStatus<'a>,
+    ) -> Option<SummaryDiffStatus<'a>> {
+        if self.ignore_packages.contains(&summary_id.name) {
+            return None;
+        }
+
+        let mut status = status.clone();
+        if let SummaryDiffStatus::Modified {
+            old_version,
+            old_source,
+            old_status,
+            added_features,
+            removed_features,
+            added_optional_deps,
+            removed_optional_deps,
+            ..
+        } = &mut status
+        {
+            let was_path = old_source.map_or(true, is_path);
+            if self.allow_path_version_changes && was_path && is_path(&summary_id.source) {
+                *old_version = None;
+            }
+            if self.allow_added_features.contains(&summary_id.name) {
+                added_features.clear();
+                added_optional_deps.clear();
+            }
+
+            let is_unchanged = old_version.is_none()
+                && old_source.is_none()
+                && old_status.is_none()
+                && added_features.is_empty()
+                && removed_features.is_empty()
+                && added_optional_deps.is_empty()
+                && removed_optional_deps.is_empty();
+            if is_unchanged {
+                return None;
+            }
+        }
+
+        Some(status)
+    }
+}
+
+type EdgeKey<'a> = [(&'a str, &'a SummarySource, Option<&'a Version>); 2];
+
+/// Returns a key for this edge that ignores the versions of packages with a path source.
+fn edge_key<'a>(edge: &'a SummaryEdge) -> EdgeKey<'a> {
+    let id_key = |summary_id: &'a SummaryId| {
+        let version = if is_path(&summary_id.source) {
+            None
+        } else {
+            Some(&summary_id.version)
+        };
+        (summary_id.name.as_str(), &summary_id.source, version)
+    };
+    [id_key(&edge.from), id_key(&edge.to)]
+}
+
+fn is_path(source: &SummarySource) -> bool {
+    matches!(
+        source,
+        SummarySource::Workspace { .. } | SummarySource::Path { .. }
+    )
+}
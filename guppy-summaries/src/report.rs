@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    diff::{changed_sort_key, PackageDiff, SummaryDiff, SummaryDiffStatus},
+    diff::{
+        changed_sort_key, EdgeDiff, PackageDiff, SummaryDiff, SummaryDiffStatus, SummaryDiffTag,
+    },
     SummaryId,
 };
 use std::fmt;
@@ -60,6 +62,12 @@ impl<'a, 'b> fmt::Display for SummaryReport<'a, 'b> {
                 PackageReport::new(&self.diff.host_packages, &self.sorted_host)
             )?;
         }
+        if !self.diff.target_edges.is_unchanged() {
+            writeln!(f, "target edges:\n{}", EdgeReport(&self.diff.target_edges))?;
+        }
+        if !self.diff.host_edges.is_unchanged() {
+            writeln!(f, "host edges:\n{}", EdgeReport(&self.diff.host_edges))?;
+        }
 
         Ok(())
     }
@@ -185,6 +193,33 @@ impl<'x> fmt::Display for PackageReport<'x> {
     }
 }
 
+struct EdgeReport<'x>(&'x EdgeDiff<'x>);
+
+impl<'x> fmt::Display for EdgeReport<'x> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let edges = self
+            .0
+            .added
+            .iter()
+            .map(|edge| (SummaryDiffTag::Added, edge))
+            .chain(
+                self.0
+                    .removed
+                    .iter()
+                    .map(|edge| (SummaryDiffTag::Removed, edge)),
+            );
+        for (tag, edge) in edges {
+            writeln!(
+                f,
+                "  {} {} {} -> {} {}",
+                tag, edge.from.name, edge.from.version, edge.to.name, edge.to.version
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 fn display_list<I>(f: &mut fmt::Formatter, items: I) -> fmt::Result
 where
     I: IntoIterator,
@@ -14,6 +14,9 @@ use toml::{value::Table, Serializer};
 /// A type representing a package map as used in `Summary` instances.
 pub type PackageMap = BTreeMap<SummaryId, PackageInfo>;
 
+/// A type representing a set of dependency edges as used in `Summary` instances.
+pub type EdgeSet = BTreeSet<SummaryEdge>;
+
 /// An in-memory representation of a build summary.
 ///
 /// The metadata parameter is customizable.
@@ -22,6 +25,16 @@ pub type PackageMap = BTreeMap<SummaryId, PackageInfo>;
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct Summary {
+    /// The version of the summary format.
+    ///
+    /// Version 1 is the default, and is not written out.
+    #[serde(
+        rename = "summary-version",
+        default,
+        skip_serializing_if = "SummaryVersion::is_v1"
+    )]
+    pub version: SummaryVersion,
+
     /// Extra metadata associated with the summary.
     ///
     /// This may be used for storing extra information about the summary.
@@ -47,6 +60,29 @@ pub struct Summary {
         skip_serializing_if = "PackageMap::is_empty"
     )]
     pub host_packages: PackageMap,
+
+    /// Direct dependency edges between packages built on the target platform.
+    ///
+    /// Requires summary version 2 or above.
+    #[serde(
+        rename = "target-edge",
+        default = "EdgeSet::new",
+        skip_serializing_if = "EdgeSet::is_empty"
+    )]
+    pub target_edges: EdgeSet,
+
+    /// Direct dependency edges to packages built on the host platform.
+    ///
+    /// The `from` end of an edge may be built on either platform: for example, a build dependency
+    /// of a target package is recorded here.
+    ///
+    /// Requires summary version 2 or above.
+    #[serde(
+        rename = "host-edge",
+        default = "EdgeSet::new",
+        skip_serializing_if = "EdgeSet::is_empty"
+    )]
+    pub host_edges: EdgeSet,
 }
 
 impl Summary {
@@ -63,13 +99,20 @@ impl Summary {
     }
 
     /// Deserializes a summary from the given string, with optional custom metadata.
+    ///
+    /// Returns an error if the summary has dependency edges but its version doesn't support them.
     pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
-        toml::from_str(s)
+        let summary: Self = toml::from_str(s)?;
+        summary
+            .check_version()
+            .map_err(<toml::de::Error as serde::de::Error>::custom)?;
+        Ok(summary)
     }
 
     /// Perform a diff of this summary against another.
     ///
-    /// This doesn't diff the metadata, just the initials and packages.
+    /// This doesn't diff the metadata, just the initials and packages. Dependency edges are only
+    /// diffed if both summaries support them.
     pub fn diff<'a>(&'a self, other: &'a Summary) -> SummaryDiff<'a> {
         SummaryDiff::new(self, other)
     }
@@ -82,11 +125,76 @@ impl Summary {
     }
 
     /// Serializes this summary into the given TOML string, using pretty TOML syntax.
+    ///
+    /// Returns an error if the summary has dependency edges but its version doesn't support them.
     pub fn write_to_string(&self, dst: &mut String) -> Result<(), toml::ser::Error> {
+        self.check_version()
+            .map_err(<toml::ser::Error as serde::ser::Error>::custom)?;
         let mut serializer = Serializer::pretty(dst);
         serializer.pretty_array(false);
         self.serialize(&mut serializer)
     }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn check_version(&self) -> Result<(), String> {
+        if !self.version.supports_edges()
+            && (!self.target_edges.is_empty() || !self.host_edges.is_empty())
+        {
+            return Err(format!(
+                "summary version {} doesn't support dependency edges (use version 2 or above)",
+                self.version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The version of the summary format.
+///
+/// Newer versions record more information. A summary's version is stored in the
+/// `summary-version` field.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[non_exhaustive]
+pub enum SummaryVersion {
+    /// The original summary format, recording packages and their features.
+    #[serde(rename = "1")]
+    V1,
+
+    /// Also records direct dependency edges between packages.
+    #[serde(rename = "2")]
+    V2,
+}
+
+impl SummaryVersion {
+    /// The latest summary version.
+    pub const LATEST: Self = SummaryVersion::V2;
+
+    /// Returns true if this version records dependency edges.
+    pub fn supports_edges(self) -> bool {
+        self >= SummaryVersion::V2
+    }
+
+    fn is_v1(&self) -> bool {
+        *self == SummaryVersion::V1
+    }
+}
+
+impl Default for SummaryVersion {
+    fn default() -> Self {
+        SummaryVersion::V1
+    }
+}
+
+impl fmt::Display for SummaryVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SummaryVersion::V1 => write!(f, "1"),
+            SummaryVersion::V2 => write!(f, "2"),
+        }
+    }
 }
 
 /// A unique identifier for a package in a build summary.
@@ -125,6 +233,24 @@ impl fmt::Display for SummaryId {
     }
 }
 
+/// A direct dependency edge between two packages in a build summary.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, Serialize, PartialEq, PartialOrd)]
+#[serde(rename_all = "kebab-case")]
+pub struct SummaryEdge {
+    /// The package that has the dependency.
+    pub from: SummaryId,
+
+    /// The package that is depended on.
+    pub to: SummaryId,
+}
+
+impl SummaryEdge {
+    /// Creates a new `SummaryEdge`.
+    pub fn new(from: SummaryId, to: SummaryId) -> Self {
+        Self { from, to }
+    }
+}
+
 /// The location of a package.
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, Serialize, PartialEq, PartialOrd)]
 #[serde(rename_all = "kebab-case", untagged)]
@@ -2,12 +2,12 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    diff::SummaryDiffStatus, PackageInfo, PackageMap, PackageStatus, Summary, SummaryId,
-    SummarySource,
+    diff::SummaryDiffStatus, policy::DiffPolicy, PackageInfo, PackageMap, PackageStatus, Summary,
+    SummaryEdge, SummaryId, SummarySource, SummaryVersion,
 };
 use pretty_assertions::assert_eq;
 use semver::Version;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 static SERIALIZED_SUMMARY: &str = r#"# This is a test @generated summary.
 
@@ -178,9 +178,9 @@ fn basic_roundtrip() {
     ];
 
     let summary = Summary {
-        metadata: Default::default(),
         target_packages: make_summary(target_packages),
         host_packages: make_summary(host_packages),
+        ..Summary::default()
     };
 
     let mut s = "# This is a test @generated summary.\n\n".to_string();
@@ -581,6 +581,251 @@ fn test_serialization() {
     println!("parsed output: {:?}", parsed);
 }
 
+#[test]
+fn apply_policy() {
+    static OLD: &str = r#"
+[[target-package]]
+name = 'foo'
+version = '1.2.3'
+workspace-path = 'foo'
+status = 'initial'
+features = ['default']
+
+[[target-package]]
+name = 'dep'
+version = '0.4.2'
+crates-io = true
+status = 'direct'
+features = ['std']
+
+[[target-package]]
+name = 'generated'
+version = '0.1.0'
+workspace-path = 'generated'
+status = 'workspace'
+features = []
+"#;
+
+    static NEW: &str = r#"
+[[target-package]]
+name = 'foo'
+version = '1.3.0'
+workspace-path = 'foo'
+status = 'initial'
+features = ['default', 'extra']
+
+[[target-package]]
+name = 'dep'
+version = '0.4.3'
+crates-io = true
+status = 'direct'
+features = ['alloc', 'std']
+
+[[target-package]]
+name = 'generated'
+version = '0.2.0'
+workspace-path = 'generated'
+status = 'workspace'
+features = ['all']
+"#;
+
+    let old = Summary::parse(OLD).expect("old summary is valid");
+    let new = Summary::parse(NEW).expect("new summary is valid");
+    let diff = old.diff(&new);
+
+    // The default policy allows nothing.
+    assert_eq!(diff.apply_policy(&DiffPolicy::default()), diff);
+
+    let policy = DiffPolicy::parse(
+        r#"
+ignore-packages = ["generated"]
+allow-path-version-changes = true
+allow-added-features = ["dep"]
+"#,
+    )
+    .expect("policy is valid");
+    let violations = diff.apply_policy(&policy);
+    assert!(violations.host_packages.is_unchanged());
+
+    let changed: BTreeMap<_, _> = violations
+        .target_packages
+        .changed
+        .iter()
+        .map(|(summary_id, status)| (summary_id.name.as_str(), status))
+        .collect();
+    assert_eq!(changed.len(), 2, "generated package is ignored");
+
+    // foo is a path dependency so its version bump is allowed, but its added feature isn't.
+    match changed["foo"] {
+        SummaryDiffStatus::Modified {
+            old_version,
+            added_features,
+            ..
+        } => {
+            assert_eq!(*old_version, None, "path version change allowed");
+            assert_eq!(added_features, &["extra"].iter().copied().collect());
+        }
+        other => panic!("expected Modified, found {:?}", other),
+    }
+
+    // dep is from crates.io so its version bump is a violation, but its added feature isn't.
+    match changed["dep"] {
+        SummaryDiffStatus::Modified {
+            old_version,
+            added_features,
+            ..
+        } => {
+            assert_eq!(*old_version, Some(&Version::new(0, 4, 2)));
+            assert!(added_features.is_empty(), "added features allowed");
+        }
+        other => panic!("expected Modified, found {:?}", other),
+    }
+
+    let policy = DiffPolicy::parse(
+        r#"
+ignore-packages = ["dep", "generated"]
+allow-path-version-changes = true
+allow-added-features = ["foo"]
+"#,
+    )
+    .expect("policy is valid");
+    assert!(diff.apply_policy(&policy).is_unchanged());
+}
+
+#[test]
+fn edges_roundtrip() {
+    static EDGES_SUMMARY: &str = r#"summary-version = '2'
+
+[[target-package]]
+name = 'foo'
+version = '1.2.3'
+workspace-path = 'foo'
+status = 'initial'
+features = []
+
+[[target-package]]
+name = 'dep'
+version = '0.4.2'
+crates-io = true
+status = 'direct'
+features = []
+
+[[host-package]]
+name = 'build-dep'
+version = '0.1.0'
+crates-io = true
+status = 'direct'
+features = []
+
+[[target-edge]]
+[target-edge.from]
+name = 'foo'
+version = '1.2.3'
+workspace-path = 'foo'
+
+[target-edge.to]
+name = 'dep'
+version = '0.4.2'
+crates-io = true
+
+[[host-edge]]
+[host-edge.from]
+name = 'foo'
+version = '1.2.3'
+workspace-path = 'foo'
+
+[host-edge.to]
+name = 'build-dep'
+version = '0.1.0'
+crates-io = true
+"#;
+
+    let foo = SummaryId::new(
+        "foo",
+        Version::new(1, 2, 3),
+        SummarySource::workspace("foo"),
+    );
+    let dep = SummaryId::new("dep", Version::new(0, 4, 2), SummarySource::crates_io());
+    let build_dep = SummaryId::new(
+        "build-dep",
+        Version::new(0, 1, 0),
+        SummarySource::crates_io(),
+    );
+
+    let summary = Summary::parse(EDGES_SUMMARY).expect("from_str succeeded");
+    assert_eq!(summary.version, SummaryVersion::V2);
+    assert_eq!(
+        summary.target_edges,
+        vec![SummaryEdge::new(foo.clone(), dep.clone())]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(
+        summary.host_edges,
+        vec![SummaryEdge::new(foo.clone(), build_dep)]
+            .into_iter()
+            .collect(),
+    );
+
+    let s = summary.to_string().expect("write succeeded");
+    assert_eq!(&s, EDGES_SUMMARY, "serialized representation matches");
+
+    // Edges require version 2.
+    let mut v1_summary = summary.clone();
+    v1_summary.version = SummaryVersion::V1;
+    v1_summary
+        .to_string()
+        .expect_err("version 1 can't be serialized with edges");
+    Summary::parse(EDGES_SUMMARY.trim_start_matches("summary-version = '2'"))
+        .expect_err("version 1 can't be parsed with edges");
+
+    // Diffing against a version 1 summary ignores edges.
+    v1_summary.target_edges.clear();
+    v1_summary.host_edges.clear();
+    assert!(v1_summary.diff(&summary).is_unchanged());
+
+    // foo now depends on dep 0.4.3.
+    let dep2 = SummaryId::new("dep", Version::new(0, 4, 3), SummarySource::crates_io());
+    let mut summary2 = summary.clone();
+    summary2.target_edges = vec![SummaryEdge::new(foo.clone(), dep2.clone())]
+        .into_iter()
+        .collect();
+    let diff = summary.diff(&summary2);
+    assert!(diff.host_edges.is_unchanged());
+    assert_eq!(
+        diff.target_edges.added.iter().copied().collect::<Vec<_>>(),
+        [&SummaryEdge::new(foo.clone(), dep2)],
+    );
+    assert_eq!(
+        diff.target_edges
+            .removed
+            .iter()
+            .copied()
+            .collect::<Vec<_>>(),
+        [&SummaryEdge::new(foo.clone(), dep)],
+    );
+    assert_eq!(
+        format!("{}", diff.report()),
+        "target edges:\n  A foo 1.2.3 -> dep 0.4.3\n  R foo 1.2.3 -> dep 0.4.2\n\n",
+    );
+
+    // A path version change is acceptable with allow-path-version-changes.
+    let foo2 = SummaryId::new("foo", Version::new(1, 3, 0), foo.source.clone());
+    let mut summary3 = summary.clone();
+    summary3.host_edges = summary
+        .host_edges
+        .iter()
+        .map(|edge| SummaryEdge::new(foo2.clone(), edge.to.clone()))
+        .collect();
+    let diff = summary.diff(&summary3);
+    assert!(diff.is_changed());
+    let policy = DiffPolicy::parse("allow-path-version-changes = true").expect("policy is valid");
+    assert!(diff.apply_policy(&policy).is_unchanged());
+    let policy = DiffPolicy::parse("ignore-packages = [\"build-dep\"]").expect("policy is valid");
+    assert!(diff.apply_policy(&policy).is_unchanged());
+    assert_eq!(diff.apply_policy(&DiffPolicy::default()), diff);
+}
+
 fn make_summary(list: Vec<(SummaryId, PackageStatus, Vec<&str>, Vec<&str>)>) -> PackageMap {
     list.into_iter()
         .map(|(summary_id, status, features, optional_deps)| {
@@ -3,6 +3,8 @@
 
 //! Contains types that describe errors and warnings that `guppy` methods can return.
 
+#[cfg(feature = "lockfile")]
+use crate::graph::ChecksumMismatch;
 use crate::{graph::feature::FeatureId, PackageId};
 use camino::Utf8PathBuf;
 use std::{error, fmt};
@@ -19,6 +21,9 @@ pub enum Error {
     MetadataParseError(serde_json::Error),
     /// An error occurred while serializing `cargo metadata` JSON.
     MetadataSerializeError(serde_json::Error),
+    /// `cargo metadata` JSON contained data that `guppy` doesn't understand, and strict parsing
+    /// was requested.
+    MetadataStrictError(ParseWarnings),
     /// An error occurred while constructing a `PackageGraph` from parsed metadata.
     PackageGraphConstructError(String),
     /// A package ID was unknown to this `PackageGraph`.
@@ -29,12 +34,53 @@ pub enum Error {
     UnknownWorkspacePath(Utf8PathBuf),
     /// A package specified by name was unknown to this workspace.
     UnknownWorkspaceName(String),
+    /// A [`NamePattern`](crate::graph::NamePattern) couldn't be parsed. Contains the pattern.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    InvalidNamePattern(String, Box<dyn error::Error + Send + Sync>),
+    /// A [`NamePattern`](crate::graph::NamePattern) didn't match any packages in this workspace.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    UnmatchedNamePattern(String),
+    /// A [`PackageIdSpec`](crate::graph::PackageIdSpec) couldn't be parsed. Contains the spec.
+    InvalidPackageIdSpec(String, Box<dyn error::Error + Send + Sync>),
+    /// A [`PackageIdSpec`](crate::graph::PackageIdSpec) didn't match any packages in this
+    /// `PackageGraph`.
+    UnmatchedPackageIdSpec(String),
     /// An error was returned by `target-spec`.
     TargetSpecError(String, TargetSpecError),
+    /// A build target was unknown to a package.
+    UnknownBuildTarget(PackageId, String),
+    /// A build target selected for a Cargo build simulation requires features that weren't
+    /// enabled.
+    RequiredFeaturesMissing {
+        /// The package ID the build target is in.
+        package_id: PackageId,
+        /// A description of the build target.
+        build_target: String,
+        /// The required features that weren't enabled.
+        features: Vec<String>,
+    },
     /// An internal error occurred within this `PackageGraph`.
     PackageGraphInternalError(String),
     /// An internal error occurred within this `FeatureGraph`.
     FeatureGraphInternalError(String),
+    /// A section of a package's `package.metadata` table couldn't be deserialized.
+    MetadataSectionError {
+        /// The package ID.
+        package_id: PackageId,
+        /// The key of the section within `package.metadata`.
+        key: String,
+        /// The error that occurred while deserializing the section.
+        err: serde_json::Error,
+    },
+    /// A [`LayersConfig`](crate::graph::LayersConfig) was invalid.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    InvalidLayersConfig(String),
     /// A summary ID was unknown to this `PackageGraph`.
     ///
     /// This is present if the `summaries` feature is enabled.
@@ -71,6 +117,57 @@ pub enum Error {
     /// An error occurred while serializing to TOML.
     #[cfg(feature = "summaries")]
     TomlSerializeError(toml::ser::Error),
+    /// An I/O error occurred while reading or writing a persisted `PackageGraph`.
+    ///
+    /// This is present if the `persist` feature is enabled.
+    #[cfg(feature = "persist")]
+    PersistIoError(std::io::Error),
+    /// An error occurred while encoding or decoding a persisted `PackageGraph`.
+    ///
+    /// This is present if the `persist` feature is enabled.
+    #[cfg(feature = "persist")]
+    PersistEncodingError(Box<dyn error::Error + Send + Sync>),
+    /// A persisted `PackageGraph` wasn't in a format supported by this version of `guppy`.
+    ///
+    /// This is present if the `persist` feature is enabled.
+    #[cfg(feature = "persist")]
+    PersistUnsupportedFormat(String),
+    /// A persisted `PackageGraph` is out of date, because `Cargo.lock` or a `Cargo.toml` in the
+    /// workspace changed since it was written out. Contains the workspace root.
+    ///
+    /// This is present if the `persist` feature is enabled.
+    #[cfg(feature = "persist")]
+    PersistStale(Utf8PathBuf),
+    /// An error occurred while reading or parsing `Cargo.lock` or a `Cargo.toml` file. Contains
+    /// the path to the file.
+    ///
+    /// This is present if the `lockfile` feature is enabled.
+    #[cfg(feature = "lockfile")]
+    LockfileReadError(Utf8PathBuf, Box<dyn error::Error + Send + Sync>),
+    /// `Cargo.lock` and the `Cargo.toml` files in the workspace couldn't be matched up, for
+    /// example because `Cargo.lock` is out of date.
+    ///
+    /// This is present if the `lockfile` feature is enabled.
+    #[cfg(feature = "lockfile")]
+    LockfileResolveError(String),
+    /// Checksums in a `Cargo.lock` file didn't match the ones in a `PackageGraph`. Contains the
+    /// mismatches, sorted by package ID.
+    ///
+    /// Returned by `PackageGraph::verify_checksums`. This is present if the `lockfile` feature is
+    /// enabled.
+    #[cfg(feature = "lockfile")]
+    ChecksumMismatch(Vec<ChecksumMismatch>),
+    /// An error occurred while reading or parsing `Cargo.lock` to generate a software bill of
+    /// materials. Contains the path to `Cargo.lock`.
+    ///
+    /// This is present if the `sbom` feature is enabled.
+    #[cfg(feature = "sbom")]
+    SbomLockfileError(Utf8PathBuf, Box<dyn error::Error + Send + Sync>),
+    /// An error occurred while writing out a software bill of materials.
+    ///
+    /// This is present if the `sbom` feature is enabled.
+    #[cfg(feature = "sbom")]
+    SbomWriteError(serde_json::Error),
 }
 
 impl Error {
@@ -92,6 +189,11 @@ impl fmt::Display for Error {
             CommandError(_) => write!(f, "`cargo metadata` execution failed"),
             MetadataParseError(_) => write!(f, "`cargo metadata` returned invalid JSON output"),
             MetadataSerializeError(_) => write!(f, "failed to serialize `cargo metadata` to JSON"),
+            MetadataStrictError(warnings) => write!(
+                f,
+                "`cargo metadata` returned output that guppy doesn't understand:\n{}",
+                warnings
+            ),
             PackageGraphConstructError(s) => write!(f, "failed to construct package graph: {}", s),
             UnknownPackageId(id) => write!(f, "unknown package ID: {}", id),
             UnknownFeatureId(package_id, feature) => {
@@ -99,9 +201,50 @@ impl fmt::Display for Error {
             }
             UnknownWorkspacePath(path) => write!(f, "unknown workspace path: {}", path),
             UnknownWorkspaceName(name) => write!(f, "unknown workspace package name: {}", name),
+            #[cfg(feature = "name-patterns")]
+            InvalidNamePattern(pattern, _) => write!(f, "invalid name pattern: {}", pattern),
+            #[cfg(feature = "name-patterns")]
+            UnmatchedNamePattern(pattern) => write!(
+                f,
+                "name pattern did not match any workspace packages: {}",
+                pattern
+            ),
+            InvalidPackageIdSpec(spec, err) => {
+                write!(f, "invalid package ID specification '{}': {}", spec, err)
+            }
+            UnmatchedPackageIdSpec(spec) => write!(
+                f,
+                "package ID specification did not match any packages: {}",
+                spec
+            ),
             TargetSpecError(msg, _) => write!(f, "target spec error while {}", msg),
+            UnknownBuildTarget(package_id, build_target) => write!(
+                f,
+                "unknown build target for package {}: {}",
+                package_id, build_target
+            ),
+            RequiredFeaturesMissing {
+                package_id,
+                build_target,
+                features,
+            } => write!(
+                f,
+                "for package {}, {} requires features that aren't enabled: {}",
+                package_id,
+                build_target,
+                features.join(", ")
+            ),
             PackageGraphInternalError(msg) => write!(f, "internal error in package graph: {}", msg),
             FeatureGraphInternalError(msg) => write!(f, "internal error in feature graph: {}", msg),
+            MetadataSectionError {
+                package_id, key, ..
+            } => write!(
+                f,
+                "for package {}, failed to deserialize `package.metadata.{}`",
+                package_id, key
+            ),
+            #[cfg(feature = "name-patterns")]
+            InvalidLayersConfig(msg) => write!(f, "invalid layers configuration: {}", msg),
             #[cfg(feature = "summaries")]
             UnknownSummaryId(summary_id) => write!(f, "unknown summary ID: {}", summary_id),
             #[cfg(feature = "summaries")]
@@ -146,6 +289,42 @@ impl fmt::Display for Error {
             }
             #[cfg(feature = "summaries")]
             TomlSerializeError(_) => write!(f, "failed to serialize to TOML"),
+            #[cfg(feature = "persist")]
+            PersistIoError(_) => write!(f, "I/O error while persisting package graph"),
+            #[cfg(feature = "persist")]
+            PersistEncodingError(_) => {
+                write!(f, "failed to encode or decode persisted package graph")
+            }
+            #[cfg(feature = "persist")]
+            PersistUnsupportedFormat(msg) => {
+                write!(f, "unsupported persisted package graph format: {}", msg)
+            }
+            #[cfg(feature = "persist")]
+            PersistStale(workspace_root) => write!(
+                f,
+                "persisted package graph for workspace {} is out of date",
+                workspace_root
+            ),
+            #[cfg(feature = "lockfile")]
+            LockfileReadError(path, _) => write!(f, "failed to read {}", path),
+            #[cfg(feature = "lockfile")]
+            LockfileResolveError(msg) => {
+                write!(f, "failed to resolve Cargo.lock against manifests: {}", msg)
+            }
+            #[cfg(feature = "lockfile")]
+            ChecksumMismatch(mismatches) => {
+                write!(f, "{} checksums didn't match", mismatches.len())?;
+                for mismatch in mismatches {
+                    write!(f, "\n  - {}", mismatch)?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "sbom")]
+            SbomLockfileError(path, _) => {
+                write!(f, "failed to read checksums for SBOM from {}", path)
+            }
+            #[cfg(feature = "sbom")]
+            SbomWriteError(_) => write!(f, "failed to write SBOM"),
         }
     }
 }
@@ -155,15 +334,27 @@ impl error::Error for Error {
         match self {
             MetadataParseError(err) => Some(err),
             MetadataSerializeError(err) => Some(err),
+            MetadataStrictError(_) => None,
             CommandError(err) => Some(err.as_ref()),
             PackageGraphConstructError(_) => None,
             UnknownPackageId(_) => None,
             UnknownFeatureId(_, _) => None,
             UnknownWorkspacePath(_) => None,
             UnknownWorkspaceName(_) => None,
+            #[cfg(feature = "name-patterns")]
+            InvalidNamePattern(_, err) => Some(err.as_ref()),
+            #[cfg(feature = "name-patterns")]
+            UnmatchedNamePattern(_) => None,
+            InvalidPackageIdSpec(_, err) => Some(err.as_ref()),
+            UnmatchedPackageIdSpec(_) => None,
             TargetSpecError(_, err) => Some(err),
+            UnknownBuildTarget(_, _) => None,
+            RequiredFeaturesMissing { .. } => None,
             PackageGraphInternalError(_) => None,
             FeatureGraphInternalError(_) => None,
+            MetadataSectionError { err, .. } => Some(err),
+            #[cfg(feature = "name-patterns")]
+            InvalidLayersConfig(_) => None,
             #[cfg(feature = "summaries")]
             UnknownSummaryId(_) => None,
             #[cfg(feature = "summaries")]
@@ -172,6 +363,119 @@ impl error::Error for Error {
             UnknownRegistryName { .. } => None,
             #[cfg(feature = "summaries")]
             TomlSerializeError(err) => Some(err),
+            #[cfg(feature = "persist")]
+            PersistIoError(err) => Some(err),
+            #[cfg(feature = "persist")]
+            PersistEncodingError(err) => Some(err.as_ref()),
+            #[cfg(feature = "persist")]
+            PersistUnsupportedFormat(_) => None,
+            #[cfg(feature = "persist")]
+            PersistStale(_) => None,
+            #[cfg(feature = "lockfile")]
+            LockfileReadError(_, err) => Some(err.as_ref()),
+            #[cfg(feature = "lockfile")]
+            LockfileResolveError(_) => None,
+            #[cfg(feature = "lockfile")]
+            ChecksumMismatch(_) => None,
+            #[cfg(feature = "sbom")]
+            SbomLockfileError(_, err) => Some(err.as_ref()),
+            #[cfg(feature = "sbom")]
+            SbomWriteError(err) => Some(err),
+        }
+    }
+}
+
+/// Warnings about `cargo metadata` output that `guppy` doesn't fully understand.
+///
+/// `cargo metadata` output can change across Cargo versions: newer versions may add fields, and
+/// older versions may be missing fields that `guppy` then assumes defaults for. Rather than
+/// failing, `guppy` records these cases as warnings.
+///
+/// Returned by [`CargoMetadata::parse_warnings`](crate::CargoMetadata::parse_warnings) and
+/// [`PackageGraph::parse_warnings`](crate::graph::PackageGraph::parse_warnings). To turn warnings
+/// into errors, use [`CargoMetadata::parse_json_strict`](crate::CargoMetadata::parse_json_strict)
+/// or [`MetadataCommand::strict`](crate::MetadataCommand::strict).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ParseWarnings {
+    pub(crate) warnings: Vec<ParseWarning>,
+}
+
+impl ParseWarnings {
+    /// Returns true if there are no warnings.
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Returns the number of warnings.
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    /// Returns an iterator over the warnings, in the order they were found in.
+    pub fn iter(&self) -> impl Iterator<Item = &ParseWarning> + ExactSizeIterator {
+        self.warnings.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ParseWarnings {
+    type Item = &'a ParseWarning;
+    type IntoIter = std::slice::Iter<'a, ParseWarning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.iter()
+    }
+}
+
+impl fmt::Display for ParseWarnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for warning in &self.warnings {
+            writeln!(f, "* {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single warning about `cargo metadata` output. Part of [`ParseWarnings`].
+///
+/// Locations are JSON paths into the `cargo metadata` output, with packages and resolve nodes
+/// identified by their package IDs, e.g. `packages[foo 0.1.0 (path+file:///foo)].edition`.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum ParseWarning {
+    /// The metadata format version isn't one that `guppy` supports.
+    UnsupportedFormatVersion(String),
+    /// A field that `guppy` doesn't know about was found. The field is ignored.
+    UnknownField {
+        /// The location of the field.
+        path: String,
+    },
+    /// A field that `guppy` expects was missing, and a default was assumed for it. This is
+    /// typically caused by older Cargo versions.
+    MissingField {
+        /// The location of the field.
+        path: String,
+    },
+    /// A dependency kind that `guppy` doesn't know about was found.
+    UnknownDependencyKind {
+        /// The location of the dependency kind.
+        path: String,
+        /// The dependency kind.
+        kind: String,
+    },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseWarning::*;
+        match self {
+            UnsupportedFormatVersion(version) => {
+                write!(f, "unsupported metadata format version: {}", version)
+            }
+            UnknownField { path } => write!(f, "unknown field (ignored): {}", path),
+            MissingField { path } => write!(f, "missing field (default assumed): {}", path),
+            UnknownDependencyKind { path, kind } => {
+                write!(f, "unknown dependency kind '{}': {}", kind, path)
+            }
         }
     }
 }
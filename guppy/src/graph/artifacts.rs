@@ -0,0 +1,278 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use cargo_metadata::{Dependency, DependencyKind};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::{collections::HashMap, fmt};
+
+/// An artifact dependency request, specified through `artifact = "bin"` and similar in
+/// `Cargo.toml`.
+///
+/// Artifact dependencies (also known as "bindeps") are an unstable Cargo feature that lets a
+/// package depend on binaries, `cdylib`s or `staticlib`s produced by another package, optionally
+/// built for a different platform. For more, see the
+/// [Cargo reference](https://doc.rust-lang.org/nightly/cargo/reference/unstable.html#artifact-dependencies).
+///
+/// Returned by [`DependencyReq::artifact`](crate::graph::DependencyReq::artifact).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ArtifactReq {
+    kinds: Vec<ArtifactKind>,
+    lib: bool,
+    target: Option<ArtifactTarget>,
+}
+
+impl ArtifactReq {
+    /// Creates a new artifact request.
+    pub fn new(kinds: Vec<ArtifactKind>, lib: bool, target: Option<ArtifactTarget>) -> Self {
+        Self { kinds, lib, target }
+    }
+
+    /// Returns the kinds of artifacts requested, as specified by the `artifact` key.
+    pub fn kinds(&self) -> &[ArtifactKind] {
+        &self.kinds
+    }
+
+    /// Returns true if the library target of the dependency is also made available, as specified
+    /// by `lib = true`.
+    pub fn lib(&self) -> bool {
+        self.lib
+    }
+
+    /// Returns the platform the artifacts are built for, as specified by the `target` key.
+    ///
+    /// If this is `None`, artifacts are built for the same platform as they would be for a regular
+    /// dependency of this kind.
+    pub fn target(&self) -> Option<&ArtifactTarget> {
+        self.target.as_ref()
+    }
+}
+
+/// A kind of artifact that can be requested through an artifact dependency.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum ArtifactKind {
+    /// All binaries in the package: `artifact = "bin"`.
+    AllBinaries,
+
+    /// A specific binary in the package: `artifact = "bin:<name>"`.
+    SelectedBinary(String),
+
+    /// A C-compatible dynamic library: `artifact = "cdylib"`.
+    Cdylib,
+
+    /// A C-compatible static library: `artifact = "staticlib"`.
+    Staticlib,
+
+    /// A kind of artifact not known to this version of guppy, such as one added in a newer version
+    /// of Cargo.
+    Other(String),
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactKind::AllBinaries => write!(f, "bin"),
+            ArtifactKind::SelectedBinary(name) => write!(f, "bin:{}", name),
+            ArtifactKind::Cdylib => write!(f, "cdylib"),
+            ArtifactKind::Staticlib => write!(f, "staticlib"),
+            ArtifactKind::Other(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+impl Serialize for ArtifactKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ArtifactKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match s.as_str() {
+            "bin" => Ok(ArtifactKind::AllBinaries),
+            "cdylib" => Ok(ArtifactKind::Cdylib),
+            "staticlib" => Ok(ArtifactKind::Staticlib),
+            other => match other.strip_prefix("bin:") {
+                Some(name) => Ok(ArtifactKind::SelectedBinary(name.to_owned())),
+                None => Ok(ArtifactKind::Other(s)),
+            },
+        }
+    }
+}
+
+/// The platform an artifact dependency is built for.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ArtifactTarget {
+    /// The platform being built for, even for build dependencies: `target = "target"`.
+    Target,
+
+    /// The platform with the given target triple.
+    Triple(String),
+}
+
+impl fmt::Display for ArtifactTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactTarget::Target => write!(f, "target"),
+            ArtifactTarget::Triple(triple) => write!(f, "{}", triple),
+        }
+    }
+}
+
+impl Serialize for ArtifactTarget {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ArtifactTarget {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        if s == "target" {
+            Ok(ArtifactTarget::Target)
+        } else {
+            Ok(ArtifactTarget::Triple(s))
+        }
+    }
+}
+
+/// Artifact dependency requests in `cargo metadata` output, which `cargo_metadata` doesn't parse.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ArtifactDeps {
+    reqs: HashMap<ArtifactDepKey, ArtifactReq>,
+}
+
+impl ArtifactDeps {
+    /// Collects artifact requests from `cargo metadata` JSON output.
+    pub(crate) fn from_metadata(metadata: &Value) -> Result<Self, serde_json::Error> {
+        let mut reqs = HashMap::new();
+        for package in packages(metadata) {
+            let package = RawPackage::deserialize(package)?;
+            for mut dep in package.dependencies {
+                if let Some(artifact) = dep.artifact.take() {
+                    reqs.insert(ArtifactDepKey::new(&package.id, dep), artifact);
+                }
+            }
+        }
+        Ok(Self { reqs })
+    }
+
+    /// Adds artifact requests back to serialized `cargo metadata` output.
+    pub(crate) fn write_to_metadata(&self, metadata: &mut Value) -> Result<(), serde_json::Error> {
+        if self.reqs.is_empty() {
+            return Ok(());
+        }
+        let packages = match metadata.get_mut("packages").and_then(Value::as_array_mut) {
+            Some(packages) => packages,
+            None => return Ok(()),
+        };
+        for package in packages {
+            let package_id = match package.get("id").and_then(Value::as_str) {
+                Some(id) => id.to_owned(),
+                None => continue,
+            };
+            let deps = match package
+                .get_mut("dependencies")
+                .and_then(Value::as_array_mut)
+            {
+                Some(deps) => deps,
+                None => continue,
+            };
+            for dep in deps {
+                let key = ArtifactDepKey::new(&package_id, RawDependency::deserialize(&*dep)?);
+                if let (Some(artifact), Some(dep)) = (self.reqs.get(&key), dep.as_object_mut()) {
+                    dep.insert("artifact".to_owned(), serde_json::to_value(artifact)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the artifact requests made by dependencies of the given package.
+    pub(crate) fn for_package(&self, package_id: &str) -> Self {
+        let reqs = self
+            .reqs
+            .iter()
+            .filter(|(key, _)| key.package_id == package_id)
+            .map(|(key, req)| (key.clone(), req.clone()))
+            .collect();
+        Self { reqs }
+    }
+
+    /// Returns the artifact request for this dependency of the given package, if any.
+    pub(crate) fn get(&self, package_id: &str, dep: &Dependency) -> Option<&ArtifactReq> {
+        if self.reqs.is_empty() {
+            return None;
+        }
+        let kind = match dep.kind {
+            DependencyKind::Normal => None,
+            DependencyKind::Development => Some("dev"),
+            DependencyKind::Build => Some("build"),
+            _ => return None,
+        };
+        let key = ArtifactDepKey {
+            package_id: package_id.to_owned(),
+            name: dep.name.clone(),
+            rename: dep.rename.clone(),
+            kind: kind.map(|kind| kind.to_owned()),
+            target: dep.target.as_ref().map(|target| target.to_string()),
+        };
+        self.reqs.get(&key)
+    }
+}
+
+fn packages(metadata: &Value) -> impl Iterator<Item = &Value> {
+    metadata
+        .get("packages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+/// Identifies a dependency entry within a package. Cargo doesn't allow the same dependency to be
+/// specified twice with the same kind and platform.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct ArtifactDepKey {
+    package_id: String,
+    name: String,
+    rename: Option<String>,
+    kind: Option<String>,
+    target: Option<String>,
+}
+
+impl ArtifactDepKey {
+    fn new(package_id: &str, dep: RawDependency) -> Self {
+        // Normal dependencies have a null kind in cargo metadata output, but cargo_metadata
+        // serializes them as "normal".
+        let kind = dep.kind.filter(|kind| kind != "normal");
+        Self {
+            package_id: package_id.to_owned(),
+            name: dep.name,
+            rename: dep.rename,
+            kind,
+            target: dep.target,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawPackage {
+    id: String,
+    #[serde(default)]
+    dependencies: Vec<RawDependency>,
+}
+
+#[derive(Deserialize)]
+struct RawDependency {
+    name: String,
+    #[serde(default)]
+    rename: Option<String>,
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default)]
+    artifact: Option<ArtifactReq>,
+}
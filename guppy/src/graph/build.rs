@@ -3,38 +3,58 @@
 
 use crate::{
     graph::{
-        cargo_version_matches, BuildTargetImpl, BuildTargetKindImpl, DepRequiredOrOptional,
-        DependencyReqImpl, NamedFeatureDep, OwnedBuildTargetId, PackageGraph, PackageGraphData,
-        PackageIx, PackageLinkImpl, PackageMetadataImpl, PackagePublishImpl, PackageSourceImpl,
-        WorkspaceImpl,
+        cargo_version_matches, intern::StringInterner, ArtifactDeps, ArtifactReq, BuildTargetImpl,
+        BuildTargetKindImpl, DepRequiredOrOptional, DependencyDeclarationImpl, DependencyReqImpl,
+        LocalPackageInput, MetadataUpdate, NamedFeatureDep, OwnedBuildTargetId, PackageGraph,
+        PackageGraphData, PackageIx, PackageLinkImpl, PackageMetadataImpl, PackagePublishImpl,
+        PackageSourceImpl, WorkspaceImpl,
     },
     sorted_set::SortedSet,
-    Error, PackageId,
+    CargoMetadata, Error, PackageId,
 };
 use camino::{Utf8Path, Utf8PathBuf};
-use cargo_metadata::{Dependency, DependencyKind, Metadata, Node, NodeDep, Package, Target};
+use cargo_metadata::{Dependency, DependencyKind, Node, NodeDep, Package, Target};
 use fixedbitset::FixedBitSet;
 use indexmap::{IndexMap, IndexSet};
 use once_cell::sync::OnceCell;
 use petgraph::prelude::*;
-use semver::Version;
+use semver::{Version, VersionReq};
 use smallvec::SmallVec;
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
 };
-use target_spec::TargetSpec;
 
 impl PackageGraph {
     /// Constructs a new `PackageGraph` instances from the given metadata.
-    pub(crate) fn build(metadata: Metadata) -> Result<Self, Error> {
+    pub(crate) fn build(metadata: CargoMetadata) -> Result<Self, Error> {
+        let (graph, _) = Self::build_impl(metadata, None)?;
+        Ok(graph)
+    }
+
+    /// Constructs a new `PackageGraph` from the given metadata, reusing data from `previous` for
+    /// packages that haven't changed.
+    pub(super) fn build_impl(
+        metadata: CargoMetadata,
+        previous: Option<&PackageGraph>,
+    ) -> Result<(Self, MetadataUpdate), Error> {
+        let CargoMetadata {
+            inner: metadata,
+            artifacts,
+            parse_warnings,
+        } = metadata;
+
         // resolve_nodes is missing if the metadata was generated with --no-deps.
         let resolve_nodes = metadata.resolve.map(|r| r.nodes).unwrap_or_default();
 
+        // Package IDs and feature names are interned so that each distinct string is only stored
+        // once.
+        let mut interner = StringInterner::new();
         let workspace_members: HashSet<_> = metadata
             .workspace_members
-            .into_iter()
-            .map(PackageId::from_metadata)
+            .iter()
+            .map(|id| interner.package_id(id))
             .collect();
 
         let workspace_root = metadata.workspace_root;
@@ -44,15 +64,20 @@ impl PackageGraph {
             resolve_nodes,
             &workspace_root,
             &workspace_members,
+            &artifacts,
+            interner,
         );
 
         let packages: HashMap<_, _> = metadata
             .packages
             .into_iter()
-            .map(|package| build_state.process_package(package))
+            .map(|package| match previous {
+                Some(previous) => build_state.update_package(package, previous),
+                None => build_state.process_package(package),
+            })
             .collect::<Result<_, _>>()?;
 
-        let dep_graph = build_state.finish();
+        let (dep_graph, mut update, local_inputs) = build_state.finish();
 
         let workspace = WorkspaceImpl::new(
             workspace_root,
@@ -62,15 +87,62 @@ impl PackageGraph {
             workspace_members,
         )?;
 
-        Ok(Self {
-            dep_graph,
-            sccs: OnceCell::new(),
-            feature_graph: OnceCell::new(),
-            data: PackageGraphData {
+        let (sccs, feature_graph) = match previous {
+            Some(previous) => {
+                update.removed = previous
+                    .data
+                    .packages
+                    .keys()
+                    .filter(|package_id| !packages.contains_key(*package_id))
+                    .cloned()
+                    .collect();
+                update.removed.sort_unstable();
+
+                // Cycles only depend on the structure of the graph, while the feature graph also
+                // refers to packages and links by index.
+                let same_structure = previous
+                    .dep_graph
+                    .node_weights()
+                    .eq(dep_graph.node_weights())
+                    && previous
+                        .dep_graph
+                        .raw_edges()
+                        .iter()
+                        .map(|edge| (edge.source(), edge.target()))
+                        .eq(dep_graph
+                            .raw_edges()
+                            .iter()
+                            .map(|edge| (edge.source(), edge.target())));
+                update.cycles_retained = same_structure;
+                update.feature_graph_retained = same_structure && update.feature_graph_retained;
+
+                let sccs = if update.cycles_retained {
+                    previous.sccs.clone()
+                } else {
+                    OnceCell::new()
+                };
+                let feature_graph = if update.feature_graph_retained {
+                    previous.feature_graph.clone()
+                } else {
+                    OnceCell::new()
+                };
+                (sccs, feature_graph)
+            }
+            None => (OnceCell::new(), OnceCell::new()),
+        };
+
+        let graph = Self {
+            dep_graph: Arc::new(dep_graph),
+            sccs,
+            feature_graph,
+            data: Arc::new(PackageGraphData {
                 packages,
                 workspace,
-            },
-        })
+                parse_warnings,
+                local_inputs,
+            }),
+        };
+        Ok((graph, update))
     }
 }
 
@@ -143,6 +215,10 @@ struct GraphBuildState<'a> {
     resolve_data: HashMap<PackageId, Vec<NodeDep>>,
     workspace_root: &'a Utf8Path,
     workspace_members: &'a HashSet<PackageId>,
+    artifacts: &'a ArtifactDeps,
+    interner: StringInterner,
+    local_inputs: HashMap<PackageId, LocalPackageInput>,
+    update: MetadataUpdate,
 }
 
 impl<'a> GraphBuildState<'a> {
@@ -151,6 +227,8 @@ impl<'a> GraphBuildState<'a> {
         resolve_nodes: Vec<Node>,
         workspace_root: &'a Utf8Path,
         workspace_members: &'a HashSet<PackageId>,
+        artifacts: &'a ArtifactDeps,
+        mut interner: StringInterner,
     ) -> Self {
         // No idea how many edges there are going to be, so use packages.len() as a reasonable lower
         // bound.
@@ -158,7 +236,7 @@ impl<'a> GraphBuildState<'a> {
         let package_data: HashMap<_, _> = packages
             .iter()
             .map(|package| {
-                let package_id = PackageId::from_metadata(package.id.clone());
+                let package_id = interner.package_id(&package.id);
                 let package_ix = dep_graph.add_node(package_id.clone());
                 (
                     package_id,
@@ -171,7 +249,7 @@ impl<'a> GraphBuildState<'a> {
             .into_iter()
             .map(|node| {
                 (
-                    PackageId::from_metadata(node.id),
+                    interner.package_id(&node.id),
                     // This used to return resolved features (node.features) as well but guppy
                     // now does its own feature handling, so it isn't used any more.
                     node.deps,
@@ -185,23 +263,120 @@ impl<'a> GraphBuildState<'a> {
             resolve_data,
             workspace_root,
             workspace_members,
+            artifacts,
+            interner,
+            local_inputs: HashMap::new(),
+            update: MetadataUpdate {
+                processed: vec![],
+                removed: vec![],
+                reused_count: 0,
+                cycles_retained: false,
+                feature_graph_retained: true,
+            },
         }
     }
 
+    /// Processes this package, or reuses its data from `previous` if it hasn't changed.
+    fn update_package(
+        &mut self,
+        package: Package,
+        previous: &PackageGraph,
+    ) -> Result<(PackageId, PackageMetadataImpl), Error> {
+        let package_id = self.interner.package_id(&package.id);
+        let old_metadata = match previous.data.metadata_impl(&package_id) {
+            Some(old_metadata) => old_metadata,
+            None => {
+                // A new package changes the feature graph as well.
+                self.update.feature_graph_retained = false;
+                self.update.processed.push(package_id);
+                return self.process_package(package);
+            }
+        };
+
+        let resolved_deps = self
+            .resolve_data
+            .get(&package_id)
+            .map_or(&[][..], |deps| deps.as_slice());
+        let is_local = self.workspace_members.contains(&package_id) || package.source.is_none();
+        let (reuse, links_and_features_eq) = if is_local {
+            let was_workspace = matches!(old_metadata.source, PackageSourceImpl::Workspace(_));
+            match previous.data.local_inputs.get(&package_id) {
+                // Source paths are relative to the workspace root.
+                Some(old_input)
+                    if was_workspace == self.workspace_members.contains(&package_id)
+                        && previous.workspace().root() == self.workspace_root =>
+                {
+                    let input = LocalPackageInput::new(&package, resolved_deps, self.artifacts);
+                    (old_input == &input, old_input.links_and_features_eq(&input))
+                }
+                _ => (false, false),
+            }
+        } else {
+            // Packages from registries and Git repositories are immutable, so only the resolved
+            // dependencies can change.
+            let old_deps: HashSet<_> = previous
+                .dep_graph
+                .edges_directed(old_metadata.package_ix, Outgoing)
+                .map(|edge| previous.dep_graph[edge.target()].repr())
+                .collect();
+            let new_deps: HashSet<_> = resolved_deps
+                .iter()
+                .map(|dep| dep.pkg.repr.as_str())
+                .collect();
+            let same_deps = old_deps == new_deps;
+            (same_deps, same_deps)
+        };
+
+        if !links_and_features_eq {
+            self.update.feature_graph_retained = false;
+        }
+        if !reuse {
+            self.update.processed.push(package_id);
+            return self.process_package(package);
+        }
+
+        // Reuse the package along with its outgoing edges, in the order they were added.
+        let (package_ix, _, _) = self.package_data(&package_id)?;
+        let resolved_deps = self.resolve_data.remove(&package_id).unwrap_or_default();
+        if is_local {
+            let input = LocalPackageInput::new(&package, &resolved_deps, self.artifacts);
+            self.local_inputs.insert(package_id.clone(), input);
+        }
+        let mut old_edges: Vec<_> = previous
+            .dep_graph
+            .edges_directed(old_metadata.package_ix, Outgoing)
+            .collect();
+        old_edges.sort_unstable_by_key(|edge| edge.id());
+        for edge in old_edges {
+            let (dep_ix, _, _) = self.package_data(&previous.dep_graph[edge.target()])?;
+            self.dep_graph
+                .add_edge(package_ix, dep_ix, edge.weight().clone());
+        }
+
+        self.update.reused_count += 1;
+        Ok((
+            package_id,
+            PackageMetadataImpl {
+                package_ix,
+                ..old_metadata.clone()
+            },
+        ))
+    }
+
     fn process_package(
         &mut self,
         package: Package,
     ) -> Result<(PackageId, PackageMetadataImpl), Error> {
-        let package_id = PackageId::from_metadata(package.id);
+        let package_id = self.interner.package_id(&package.id);
         let (package_ix, _, _) = self.package_data(&package_id)?;
 
         let source = if self.workspace_members.contains(&package_id) {
             PackageSourceImpl::Workspace(self.workspace_path(&package_id, &package.manifest_path)?)
-        } else if let Some(source) = package.source {
+        } else if let Some(source) = &package.source {
             if source.is_crates_io() {
                 PackageSourceImpl::CratesIo
             } else {
-                PackageSourceImpl::External(source.repr.into())
+                PackageSourceImpl::External(source.repr.as_str().into())
             }
         } else {
             // Path dependency: get the directory from the manifest path.
@@ -217,15 +392,23 @@ impl<'a> GraphBuildState<'a> {
             PackageSourceImpl::create_path(dirname, self.workspace_root)
         };
 
+        // resolved_deps is missing if the metadata was generated with --no-deps.
+        let resolved_deps = self.resolve_data.remove(&package_id).unwrap_or_default();
+
+        if matches!(
+            source,
+            PackageSourceImpl::Workspace(_) | PackageSourceImpl::Path(_)
+        ) {
+            let input = LocalPackageInput::new(&package, &resolved_deps, self.artifacts);
+            self.local_inputs.insert(package_id.clone(), input);
+        }
+
         let mut build_targets = BuildTargets::new(&package_id);
         for build_target in package.targets {
             build_targets.add(build_target)?;
         }
         let build_targets = build_targets.finish();
 
-        // resolved_deps is missing if the metadata was generated with --no-deps.
-        let resolved_deps = self.resolve_data.remove(&package_id).unwrap_or_default();
-
         let dep_resolver =
             DependencyResolver::new(&package_id, &self.package_data, &package.dependencies);
 
@@ -235,10 +418,17 @@ impl<'a> GraphBuildState<'a> {
             ..
         } in resolved_deps
         {
-            let dep_id = PackageId::from_metadata(pkg);
+            let dep_id = self.interner.package_id(&pkg);
             let (name, deps) = dep_resolver.resolve(&resolved_name, &dep_id)?;
             let (dep_idx, _, _) = self.package_data(&dep_id)?;
-            let edge = PackageLinkImpl::new(&package_id, name, &resolved_name, deps)?;
+            let edge = PackageLinkImpl::new(
+                &package_id,
+                name,
+                &resolved_name,
+                deps,
+                self.artifacts,
+                &mut self.interner,
+            )?;
             // Use update_edge instead of add_edge to prevent multiple edges from being added
             // between these two nodes.
             // XXX maybe check for an existing edge?
@@ -262,10 +452,8 @@ impl<'a> GraphBuildState<'a> {
             .into_iter()
             .filter_map(|dep| {
                 if dep.optional {
-                    match dep.rename {
-                        Some(rename) => Some(rename.into_boxed_str()),
-                        None => Some(dep.name.into_boxed_str()),
-                    }
+                    let name = dep.rename.as_ref().unwrap_or(&dep.name);
+                    Some(self.interner.intern(name))
                 } else {
                     None
                 }
@@ -282,7 +470,7 @@ impl<'a> GraphBuildState<'a> {
             .map(|(feature_name, deps)| {
                 let mut parsed_deps = SmallVec::with_capacity(deps.len());
                 for dep in deps {
-                    let dep = NamedFeatureDep::from_cargo_string(dep);
+                    let dep = NamedFeatureDep::from_cargo_string(&dep, &mut self.interner);
                     if let NamedFeatureDep::OptionalDependency(d) = &dep {
                         let index = optional_deps.get_index_of(d.as_ref()).ok_or_else(|| {
                             Error::PackageGraphConstructError(format!(
@@ -295,7 +483,7 @@ impl<'a> GraphBuildState<'a> {
                     }
                     parsed_deps.push(dep);
                 }
-                Ok((feature_name.into_boxed_str(), parsed_deps))
+                Ok((self.interner.intern(&feature_name), parsed_deps))
             })
             .collect::<Result<_, Error>>()?;
 
@@ -330,6 +518,10 @@ impl<'a> GraphBuildState<'a> {
                 links: package.links.map(|s| s.into()),
                 publish: PackagePublishImpl::new(package.publish),
                 default_run: package.default_run.map(|s| s.into()),
+                minimum_rust_version: package
+                    .rust_version
+                    .as_ref()
+                    .and_then(rust_version_from_req),
                 rust_version: package.rust_version,
                 named_features,
                 optional_deps,
@@ -338,6 +530,7 @@ impl<'a> GraphBuildState<'a> {
                 source,
                 build_targets,
                 has_default_feature,
+                checksum: None,
             },
         ))
     }
@@ -377,8 +570,15 @@ impl<'a> GraphBuildState<'a> {
         Ok(convert_forward_slashes(workspace_path).into_boxed_path())
     }
 
-    fn finish(self) -> Graph<PackageId, PackageLinkImpl, Directed, PackageIx> {
-        self.dep_graph
+    fn finish(
+        mut self,
+    ) -> (
+        Graph<PackageId, PackageLinkImpl, Directed, PackageIx>,
+        MetadataUpdate,
+        HashMap<PackageId, LocalPackageInput>,
+    ) {
+        self.update.processed.sort_unstable();
+        (self.dep_graph, self.update, self.local_inputs)
     }
 }
 
@@ -397,20 +597,25 @@ impl PackageSourceImpl {
     }
 }
 
+impl StringInterner {
+    fn package_id(&mut self, id: &cargo_metadata::PackageId) -> PackageId {
+        PackageId::from_interned(self.intern(&id.repr))
+    }
+}
+
 impl NamedFeatureDep {
-    fn from_cargo_string(input: impl Into<String>) -> Self {
-        let input = input.into();
+    fn from_cargo_string(input: &str, interner: &mut StringInterner) -> Self {
         match input.split_once('/') {
             Some((dep_name, feature)) => {
-                if let Some(dep_name_without_q) = dep_name.strip_suffix('?') {
-                    Self::dep_named_feature(dep_name_without_q, feature, true)
-                } else {
-                    Self::dep_named_feature(dep_name, feature, false)
-                }
+                let (dep_name, weak) = match dep_name.strip_suffix('?') {
+                    Some(dep_name_without_q) => (dep_name_without_q, true),
+                    None => (dep_name, false),
+                };
+                Self::dep_named_feature(interner.intern(dep_name), interner.intern(feature), weak)
             }
             None => match input.strip_prefix("dep:") {
-                Some(dep_name) => Self::optional_dependency(dep_name),
-                None => Self::named_feature(input),
+                Some(dep_name) => Self::optional_dependency(interner.intern(dep_name)),
+                None => Self::named_feature(interner.intern(input)),
             },
         }
     }
@@ -696,11 +901,16 @@ impl PackageLinkImpl {
         name: &str,
         resolved_name: &str,
         deps: impl IntoIterator<Item = &'a Dependency>,
+        artifacts: &ArtifactDeps,
+        interner: &mut StringInterner,
     ) -> Result<Self, Error> {
         let mut version_req = None;
+        let mut registry = None;
+        let mut declared_source = None;
         let mut normal = DependencyReqImpl::default();
         let mut build = DependencyReqImpl::default();
         let mut dev = DependencyReqImpl::default();
+        let mut declarations = vec![];
         for dep in deps {
             // Dev dependencies cannot be optional.
             if dep.kind == DependencyKind::Development && dep.optional {
@@ -714,25 +924,63 @@ impl PackageLinkImpl {
             if version_req.is_none() {
                 version_req = Some(dep.req.clone());
             }
+            if registry.is_none() {
+                registry = dep.registry.clone();
+            }
+            if declared_source.is_none() {
+                declared_source = dep.source.as_deref().map(Box::from);
+            }
 
-            match dep.kind {
-                DependencyKind::Normal => normal.add_instance(from_id, dep)?,
-                DependencyKind::Build => build.add_instance(from_id, dep)?,
-                DependencyKind::Development => dev.add_instance(from_id, dep)?,
+            let artifact = artifacts.get(from_id.repr(), dep);
+            let kind = match dep.kind {
+                DependencyKind::Normal => {
+                    normal.add_instance(from_id, dep, artifact, interner)?;
+                    crate::DependencyKind::Normal
+                }
+                DependencyKind::Build => {
+                    build.add_instance(from_id, dep, artifact, interner)?;
+                    crate::DependencyKind::Build
+                }
+                DependencyKind::Development => {
+                    dev.add_instance(from_id, dep, artifact, interner)?;
+                    crate::DependencyKind::Development
+                }
                 _ => {
                     // unknown dependency kind -- can't do much with this!
                     continue;
                 }
             };
+
+            let mut features: Vec<_> = dep
+                .features
+                .iter()
+                .map(|feature| interner.intern(feature))
+                .collect();
+            features.sort_unstable();
+            features.dedup();
+            declarations.push(DependencyDeclarationImpl {
+                kind,
+                target: dep
+                    .target
+                    .as_ref()
+                    .map(|target| target.to_string().into_boxed_str()),
+                version_req: dep.req.clone(),
+                optional: dep.optional,
+                uses_default_features: dep.uses_default_features,
+                features: features.into_boxed_slice(),
+            });
         }
 
         Ok(Self {
             dep_name: name.into(),
             resolved_name: resolved_name.into(),
             version_req: version_req.expect("at least one dependency instance"),
+            registry,
+            declared_source,
             normal,
             build,
             dev,
+            declarations: declarations.into_boxed_slice(),
         })
     }
 }
@@ -764,23 +1012,38 @@ impl PackageLinkImpl {
 /// causes this union-ing to *not* happen, so that's why we store all the features enabled by
 /// each target separately.
 impl DependencyReqImpl {
-    fn add_instance(&mut self, from_id: &PackageId, dep: &Dependency) -> Result<(), Error> {
+    fn add_instance(
+        &mut self,
+        from_id: &PackageId,
+        dep: &Dependency,
+        artifact: Option<&ArtifactReq>,
+        interner: &mut StringInterner,
+    ) -> Result<(), Error> {
+        // If several instances request artifacts, pick the first one seen.
+        if self.artifact.is_none() {
+            self.artifact = artifact.cloned();
+        }
         if dep.optional {
-            self.optional.add_instance(from_id, dep)
+            self.optional.add_instance(from_id, dep, interner)
         } else {
-            self.required.add_instance(from_id, dep)
+            self.required.add_instance(from_id, dep, interner)
         }
     }
 }
 
 impl DepRequiredOrOptional {
-    fn add_instance(&mut self, from_id: &PackageId, dep: &Dependency) -> Result<(), Error> {
+    fn add_instance(
+        &mut self,
+        from_id: &PackageId,
+        dep: &Dependency,
+        interner: &mut StringInterner,
+    ) -> Result<(), Error> {
         // target_spec is None if this is not a platform-specific dependency.
         let target_spec = match dep.target.as_ref() {
             Some(spec_or_triple) => {
                 // This is a platform-specific dependency, so add it to the list of specs.
                 let spec_or_triple = format!("{}", spec_or_triple);
-                let target_spec: TargetSpec = spec_or_triple.parse().map_err(|err| {
+                let target_spec = interner.target_spec(&spec_or_triple).map_err(|err| {
                     Error::PackageGraphConstructError(format!(
                         "for package '{}': for dependency '{}', parsing target '{}' failed: {}",
                         from_id, dep.name, spec_or_triple, err
@@ -800,7 +1063,7 @@ impl DepRequiredOrOptional {
 
         for feature in &dep.features {
             self.feature_targets
-                .entry(feature.clone())
+                .entry(interner.intern(feature))
                 .or_default()
                 .add_spec(target_spec.as_ref());
         }
@@ -818,6 +1081,19 @@ impl PackagePublishImpl {
     }
 }
 
+/// Converts a `rust-version` field, which cargo_metadata parses as a requirement like `^1.60`, into
+/// the version it names.
+fn rust_version_from_req(req: &VersionReq) -> Option<Version> {
+    match req.comparators.as_slice() {
+        [comparator] => Some(Version::new(
+            comparator.major,
+            comparator.minor.unwrap_or(0),
+            comparator.patch.unwrap_or(0),
+        )),
+        _ => None,
+    }
+}
+
 /// Replace backslashes in a relative path with forward slashes on Windows.
 #[track_caller]
 fn convert_forward_slashes<'a>(rel_path: impl Into<Cow<'a, Utf8Path>>) -> Utf8PathBuf {
@@ -843,24 +1119,53 @@ mod tests {
 
     #[test]
     fn test_parse_named_feature_dependency() {
+        let mut interner = StringInterner::new();
         assert_eq!(
-            NamedFeatureDep::from_cargo_string("dep/bar"),
+            NamedFeatureDep::from_cargo_string("dep/bar", &mut interner),
             NamedFeatureDep::dep_named_feature("dep", "bar", false),
         );
         assert_eq!(
-            NamedFeatureDep::from_cargo_string("dep?/bar"),
+            NamedFeatureDep::from_cargo_string("dep?/bar", &mut interner),
             NamedFeatureDep::dep_named_feature("dep", "bar", true),
         );
         assert_eq!(
-            NamedFeatureDep::from_cargo_string("dep:bar"),
+            NamedFeatureDep::from_cargo_string("dep:bar", &mut interner),
             NamedFeatureDep::optional_dependency("bar"),
         );
         assert_eq!(
-            NamedFeatureDep::from_cargo_string("foo-bar"),
+            NamedFeatureDep::from_cargo_string("foo-bar", &mut interner),
             NamedFeatureDep::named_feature("foo-bar"),
         );
     }
 
+    #[test]
+    fn test_intern_target_spec() {
+        use target_spec::TargetSpec;
+
+        let mut interner = StringInterner::new();
+        let cfg_expr = |spec: &TargetSpec| match spec {
+            TargetSpec::Expression(expr) => expr.cfg_expr() as *const _,
+            TargetSpec::Triple(_) => panic!("expected an expression"),
+        };
+
+        let unix = interner.target_spec("cfg(unix)").expect("valid spec");
+        let unix2 = interner.target_spec("cfg(unix)").expect("valid spec");
+        let windows = interner.target_spec("cfg(windows)").expect("valid spec");
+        assert_eq!(
+            cfg_expr(&unix),
+            cfg_expr(&unix2),
+            "specs with the same string share their parsed form"
+        );
+        assert_ne!(cfg_expr(&unix), cfg_expr(&windows));
+
+        interner
+            .target_spec("x86_64-unknown-linux-gnu")
+            .expect("valid triple");
+        interner
+            .target_spec("cfg(not(unix)")
+            .expect_err("missing ) at the end");
+    }
+
     #[test]
     fn test_create_path() {
         assert_eq!(
@@ -3,7 +3,7 @@
 
 use crate::sorted_set::SortedSet;
 use camino::Utf8Path;
-use std::{borrow::Borrow, cmp::Ordering};
+use std::{borrow::Borrow, cmp::Ordering, fmt};
 
 /// A build target in a package.
 ///
@@ -137,6 +137,19 @@ impl<'g> BuildTargetId<'g> {
     }
 }
 
+impl<'g> fmt::Display for BuildTargetId<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildTargetId::Library => write!(f, "library"),
+            BuildTargetId::BuildScript => write!(f, "build script"),
+            BuildTargetId::Binary(name) => write!(f, "binary '{}'", name),
+            BuildTargetId::Example(name) => write!(f, "example '{}'", name),
+            BuildTargetId::Test(name) => write!(f, "test '{}'", name),
+            BuildTargetId::Benchmark(name) => write!(f, "benchmark '{}'", name),
+        }
+    }
+}
+
 /// The type of build target (library or binary).
 ///
 /// Obtained through `BuildTarget::kind`.
@@ -4,10 +4,12 @@
 use crate::{
     graph::{
         cargo::{
+            cache::{FeatureEdgeCache, FeatureEdgeKey, PlatformEvaluator},
             CargoIntermediateSet, CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform,
         },
         feature::{ConditionalLink, FeatureLabel, FeatureQuery, FeatureSet, StandardFeatures},
-        DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageSet,
+        ArtifactTarget, BuildTargetId, DependencyDirection, PackageGraph, PackageIx, PackageLink,
+        PackageMetadata, PackageSet,
     },
     platform::{EnabledTernary, PlatformSpec},
     sorted_set::SortedSet,
@@ -15,10 +17,20 @@ use crate::{
 };
 use fixedbitset::FixedBitSet;
 use petgraph::{prelude::*, visit::VisitMap};
+use semver::Version;
+use std::sync::Arc;
 
 pub(super) struct CargoSetBuildState<'a> {
     opts: &'a CargoOptions<'a>,
     omitted_packages: SortedSet<NodeIndex<PackageIx>>,
+    // Packages whose rust-version isn't satisfied by the simulated Rust version.
+    rust_version_excluded: SortedSet<NodeIndex<PackageIx>>,
+    // Initials for which the selected build targets require dev-dependencies.
+    dev_target_packages: SortedSet<NodeIndex<PackageIx>>,
+    target: PlatformEvaluator<'a>,
+    host: PlatformEvaluator<'a>,
+    // Cached feature propagation decisions, used with the V2 resolver.
+    feature_edges: Option<Arc<FeatureEdgeCache>>,
 }
 
 impl<'a> CargoSetBuildState<'a> {
@@ -29,12 +41,109 @@ impl<'a> CargoSetBuildState<'a> {
         let omitted_packages: SortedSet<_> =
             graph.package_ixs(opts.omitted_packages.iter().copied())?;
 
+        let rust_version_excluded = match (&opts.rust_version, opts.ignore_rust_version) {
+            (Some(rust_version), false) => {
+                // Ignore pre-release information like "-nightly".
+                let rust_version =
+                    Version::new(rust_version.major, rust_version.minor, rust_version.patch);
+                graph
+                    .packages()
+                    .filter(|package| match package.minimum_rust_version() {
+                        Some(package_version) => package_version > &rust_version,
+                        None => false,
+                    })
+                    .map(|package| package.package_ix())
+                    .collect()
+            }
+            _ => vec![],
+        };
+
+        let mut dev_target_packages = Vec::new();
+        for (package_id, build_targets) in &opts.initials_build_targets {
+            let package = graph.metadata(package_id)?;
+            for build_target in build_targets {
+                if package.build_target(build_target).is_none() {
+                    return Err(Error::UnknownBuildTarget(
+                        (*package_id).clone(),
+                        build_target.to_string(),
+                    ));
+                }
+            }
+            let needs_dev = build_targets.iter().any(|build_target| {
+                matches!(
+                    build_target,
+                    BuildTargetId::Example(_)
+                        | BuildTargetId::Test(_)
+                        | BuildTargetId::Benchmark(_)
+                )
+            });
+            if needs_dev {
+                dev_target_packages.push(package.package_ix());
+            }
+        }
+
+        let rust_version_excluded = SortedSet::new(rust_version_excluded);
+        let feature_edges = match (opts.resolver, opts.resolver_cache) {
+            (CargoResolverVersion::V2, Some(cache)) => {
+                let key = FeatureEdgeKey {
+                    target_platform: opts.target_platform.clone(),
+                    host_platform: opts.host_platform.clone(),
+                    omitted: omitted_packages
+                        .iter()
+                        .chain(rust_version_excluded.iter())
+                        .copied()
+                        .collect(),
+                };
+                cache.feature_edge_cache(graph, key)
+            }
+            _ => None,
+        };
+
         Ok(Self {
             opts,
             omitted_packages,
+            rust_version_excluded,
+            dev_target_packages: SortedSet::new(dev_target_packages),
+            target: PlatformEvaluator::new(graph, &opts.target_platform, opts.resolver_cache),
+            host: PlatformEvaluator::new(graph, &opts.host_platform, opts.resolver_cache),
+            feature_edges,
         })
     }
 
+    /// Checks that the required features of any selected build targets are enabled in the
+    /// initials.
+    pub(super) fn check_required_features(&self, initials: &FeatureSet<'_>) -> Result<(), Error> {
+        let package_graph = initials.graph().package_graph;
+        for (package_id, build_targets) in &self.opts.initials_build_targets {
+            let package = package_graph.metadata(package_id)?;
+            for build_target_id in build_targets {
+                let build_target = package
+                    .build_target(build_target_id)
+                    .expect("build targets were checked in new()");
+                let missing: Vec<_> = build_target
+                    .required_features()
+                    .iter()
+                    // Features of the form "dep/feature" aren't simulated.
+                    .filter(|feature| !feature.contains('/'))
+                    .filter(|feature| {
+                        !initials
+                            .contains((*package_id, FeatureLabel::Named(feature.as_str())))
+                            .unwrap_or(false)
+                    })
+                    .cloned()
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(Error::RequiredFeaturesMissing {
+                        package_id: (*package_id).clone(),
+                        build_target: build_target_id.to_string(),
+                        features: missing,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub(super) fn build<'g>(
         self,
         initials: FeatureSet<'g>,
@@ -83,7 +192,57 @@ impl<'a> CargoSetBuildState<'a> {
     // ---
 
     fn is_omitted(&self, package_ix: NodeIndex<PackageIx>) -> bool {
-        self.omitted_packages.contains(&package_ix)
+        self.omitted_packages.contains(&package_ix) || self.is_rust_version_excluded(package_ix)
+    }
+
+    fn is_rust_version_excluded(&self, package_ix: NodeIndex<PackageIx>) -> bool {
+        self.rust_version_excluded.contains(&package_ix)
+    }
+
+    /// Returns true if this link is a normal or dev artifact dependency built for the host
+    /// platform.
+    fn is_host_artifact(&self, link: &PackageLink<'_>) -> bool {
+        [DependencyKind::Normal, DependencyKind::Development]
+            .iter()
+            .any(|kind| self.artifact_platform(link, *kind) == Some(ArtifactPlatform::Host))
+    }
+
+    /// Returns true if this link is a build artifact dependency built for the target platform.
+    fn is_target_build_artifact(&self, link: &PackageLink<'_>) -> bool {
+        self.artifact_platform(link, DependencyKind::Build) == Some(ArtifactPlatform::Target)
+    }
+
+    /// Returns the platform an artifact dependency of this kind is built for, if it's specified.
+    ///
+    /// Only the target and host platforms are simulated, so artifacts built for any other platform
+    /// are treated as if they were built for the target platform.
+    fn artifact_platform(
+        &self,
+        link: &PackageLink<'_>,
+        kind: DependencyKind,
+    ) -> Option<ArtifactPlatform> {
+        let triple = match link.req_for_kind(kind).artifact()?.target()? {
+            ArtifactTarget::Target => return Some(ArtifactPlatform::Target),
+            ArtifactTarget::Triple(triple) => triple,
+        };
+        let is_triple = |platform_spec: &PlatformSpec| match platform_spec {
+            PlatformSpec::Platform(platform) => platform.triple_str() == triple,
+            PlatformSpec::Multi(platforms) => platforms
+                .iter()
+                .any(|platform| platform.triple_str() == triple),
+            PlatformSpec::Always | PlatformSpec::Any => false,
+        };
+        if is_triple(&self.opts.host_platform) && !is_triple(&self.opts.target_platform) {
+            Some(ArtifactPlatform::Host)
+        } else {
+            Some(ArtifactPlatform::Target)
+        }
+    }
+
+    /// Returns true if dev-dependencies should be followed for this package, assuming it is an
+    /// initial.
+    fn include_dev_for(&self, package: PackageMetadata<'_>) -> bool {
+        self.opts.include_dev || self.dev_target_packages.contains(&package.package_ix())
     }
 
     fn build_set<'g>(
@@ -147,13 +306,15 @@ impl<'a> CargoSetBuildState<'a> {
         let mut proc_macro_edge_ixs = Vec::new();
         // This list will contain build dep edges out of target packages.
         let mut build_dep_edge_ixs = Vec::new();
+        // These lists will contain all edges followed to target and host packages, respectively.
+        let mut target_edge_ixs = Vec::new();
+        let mut host_edge_ixs = Vec::new();
 
         let is_enabled = |feature_set: &FeatureSet<'_>,
                           link: &PackageLink<'_>,
                           kind: DependencyKind,
-                          platform_spec: &PlatformSpec| {
+                          platform: &PlatformEvaluator<'_>| {
             let (from, to) = link.endpoints();
-            let req_status = link.req_for_kind(kind).status();
             // Check the complete set to figure out whether we look at required_on or
             // enabled_on.
             let consider_optional = feature_set
@@ -162,7 +323,10 @@ impl<'a> CargoSetBuildState<'a> {
                     // If the feature ID isn't present, it means the dependency wasn't declared
                     // as optional. In that case the value doesn't matter.
                     debug_assert!(
-                        req_status.optional_status().is_never(),
+                        link.req_for_kind(kind)
+                            .status()
+                            .optional_status()
+                            .is_never(),
                         "for {} -> {}, dep '{}' not declared as optional",
                         from.name(),
                         to.name(),
@@ -172,9 +336,9 @@ impl<'a> CargoSetBuildState<'a> {
                 });
 
             if consider_optional {
-                req_status.enabled_on(platform_spec) != EnabledTernary::Disabled
+                platform.enabled_on(link, kind) != EnabledTernary::Disabled
             } else {
-                req_status.required_on(platform_spec) != EnabledTernary::Disabled
+                platform.required_on(link, kind) != EnabledTernary::Disabled
             }
         };
 
@@ -182,11 +346,14 @@ impl<'a> CargoSetBuildState<'a> {
         let mut target_direct_deps =
             FixedBitSet::with_capacity(graph.package_graph.package_count());
         let mut host_direct_deps = FixedBitSet::with_capacity(graph.package_graph.package_count());
+        // Record packages that would have been followed but were excluded due to rust-version.
+        let mut rust_version_excluded =
+            FixedBitSet::with_capacity(graph.package_graph.package_count());
 
         // 2. Figure out what packages will be included on the target platform, i.e. normal + dev
         // (if requested).
-        let target_platform = &self.opts.target_platform;
-        let host_platform = &self.opts.host_platform;
+        let target_platform = &self.target;
+        let host_platform = &self.host;
 
         let target_packages = target_query.resolve_with_fn(|query, link| {
             let (from, to) = link.endpoints();
@@ -196,13 +363,13 @@ impl<'a> CargoSetBuildState<'a> {
                 target_direct_deps.visit(from.package_ix());
             }
 
-            if self.is_omitted(to.package_ix()) {
+            if self.omitted_packages.contains(&to.package_ix()) {
                 // Pretend that the omitted set doesn't exist.
                 return false;
             }
 
             let consider_dev =
-                self.opts.include_dev && query.starts_from(from.id()).expect("valid ID");
+                self.include_dev_for(from) && query.starts_from(from.id()).expect("valid ID");
             // Build dependencies are only considered if there's a build script.
             let consider_build = from.has_build_script();
 
@@ -215,22 +382,37 @@ impl<'a> CargoSetBuildState<'a> {
                             DependencyKind::Development,
                             target_platform,
                         ));
+            // Artifact dependencies can be built for the host platform instead.
+            let artifact_redirect = follow_target && self.is_host_artifact(&link);
+
+            // Build dependencies are evaluated against the host platform.
+            let build_enabled = consider_build
+                && is_enabled(target_set, &link, DependencyKind::Build, host_platform);
+            // Artifact build dependencies can be built for the target platform instead.
+            let build_on_target = build_enabled && self.is_target_build_artifact(&link);
+            let build_dep_redirect = build_enabled && !build_on_target;
+            follow_target |= build_on_target;
+
+            if self.is_rust_version_excluded(to.package_ix()) {
+                // This package would have been built, but its rust-version isn't satisfied.
+                if follow_target || build_dep_redirect {
+                    rust_version_excluded.visit(to.package_ix());
+                }
+                return false;
+            }
 
             // Proc macros build on the host, so for normal/dev dependencies redirect it to the host
             // instead.
             let proc_macro_redirect = follow_target && to.is_proc_macro();
 
-            // Build dependencies are evaluated against the host platform.
-            let build_dep_redirect = consider_build
-                && is_enabled(target_set, &link, DependencyKind::Build, host_platform);
-
             // Finally, process what needs to be done.
-            if build_dep_redirect || proc_macro_redirect {
+            if build_dep_redirect || proc_macro_redirect || artifact_redirect {
                 if from.in_workspace() {
                     // The 'to' node is either in the workspace or a direct dependency [a].
                     host_direct_deps.visit(to.package_ix());
                 }
                 host_ixs.push(to.package_ix());
+                host_edge_ixs.push(link.edge_ix());
             }
             if build_dep_redirect {
                 build_dep_edge_ixs.push(link.edge_ix());
@@ -239,10 +421,16 @@ impl<'a> CargoSetBuildState<'a> {
                 proc_macro_edge_ixs.push(link.edge_ix());
                 follow_target = false;
             }
+            if artifact_redirect {
+                follow_target = false;
+            }
 
-            if from.in_workspace() && follow_target {
-                // The 'to' node is either in the workspace or a direct dependency.
-                target_direct_deps.visit(to.package_ix());
+            if follow_target {
+                if from.in_workspace() {
+                    // The 'to' node is either in the workspace or a direct dependency.
+                    target_direct_deps.visit(to.package_ix());
+                }
+                target_edge_ixs.push(link.edge_ix());
             }
 
             follow_target
@@ -255,7 +443,7 @@ impl<'a> CargoSetBuildState<'a> {
             .query_from_parts(host_ixs, DependencyDirection::Forward)
             .resolve_with_fn(|_, link| {
                 let (from, to) = link.endpoints();
-                if self.is_omitted(to.package_ix()) {
+                if self.omitted_packages.contains(&to.package_ix()) {
                     // Pretend that the omitted set doesn't exist.
                     return false;
                 }
@@ -270,11 +458,20 @@ impl<'a> CargoSetBuildState<'a> {
                     || (consider_build
                         && is_enabled(host_set, &link, DependencyKind::Build, host_platform));
 
+                if self.is_rust_version_excluded(to.package_ix()) {
+                    // This package would have been built, but its rust-version isn't satisfied.
+                    if res {
+                        rust_version_excluded.visit(to.package_ix());
+                    }
+                    return false;
+                }
+
                 if res {
                     if from.in_workspace() {
                         // The 'to' node is either in the workspace or a direct dependency.
                         host_direct_deps.visit(to.package_ix());
                     }
+                    host_edge_ixs.push(link.edge_ix());
                     true
                 } else {
                     false
@@ -294,16 +491,22 @@ impl<'a> CargoSetBuildState<'a> {
         let target_direct_deps =
             PackageSet::from_included(graph.package_graph(), target_direct_deps);
         let host_direct_deps = PackageSet::from_included(graph.package_graph, host_direct_deps);
+        let rust_version_excluded =
+            PackageSet::from_included(graph.package_graph, rust_version_excluded);
 
         CargoSet {
             initials,
             features_only,
+            features_only_contributions: vec![],
             target_features,
             host_features,
             target_direct_deps,
             host_direct_deps,
             proc_macro_edge_ixs: SortedSet::new(proc_macro_edge_ixs),
             build_dep_edge_ixs: SortedSet::new(build_dep_edge_ixs),
+            target_edge_ixs: SortedSet::new(target_edge_ixs),
+            host_edge_ixs: SortedSet::new(host_edge_ixs),
+            rust_version_excluded,
         }
     }
 
@@ -318,7 +521,7 @@ impl<'a> CargoSetBuildState<'a> {
             if self.is_omitted(link.to().package_ix()) {
                 // Pretend that the omitted set doesn't exist.
                 false
-            } else if !avoid_dev_deps
+            } else if (!avoid_dev_deps || self.include_dev_for(link.from().package()))
                 && query
                     .starts_from(link.from().feature_id())
                     .expect("valid ID")
@@ -358,12 +561,6 @@ impl<'a> CargoSetBuildState<'a> {
             })
             .collect();
 
-        let is_enabled =
-            |link: &ConditionalLink<'_>, kind: DependencyKind, platform_spec: &PlatformSpec| {
-                let platform_status = link.status_for_kind(kind);
-                platform_status.enabled_on(platform_spec) != EnabledTernary::Disabled
-            };
-
         let target_query = if self.opts.initials_platform == InitialsPlatform::Host {
             // Empty query on the target.
             graph.query_from_parts(SortedSet::new(vec![]), DependencyDirection::Forward)
@@ -375,38 +572,22 @@ impl<'a> CargoSetBuildState<'a> {
         let target_query_2 = target_query.clone();
 
         // 1. Perform a feature query for the target.
-        let target_platform = &self.opts.target_platform;
-        let host_platform = &self.opts.host_platform;
         let target = target_query.resolve_with_fn(|query, link| {
-            let (from, to) = link.endpoints();
-
-            if self.is_omitted(to.package_ix()) {
-                // Pretend that the omitted set doesn't exist.
-                return false;
-            }
-
-            let consider_dev =
-                self.opts.include_dev && query.starts_from(from.feature_id()).expect("valid ID");
-            // This resolver doesn't check for whether this package has a build script.
-            let mut follow_target = is_enabled(&link, DependencyKind::Normal, target_platform)
-                || (consider_dev
-                    && is_enabled(&link, DependencyKind::Development, target_platform));
-
-            // Proc macros build on the host, so for normal/dev dependencies redirect it to the host
-            // instead.
-            let proc_macro_redirect = follow_target && to.package().is_proc_macro();
-
-            // Build dependencies are evaluated against the host platform.
-            let build_dep_redirect = is_enabled(&link, DependencyKind::Build, host_platform);
-
-            // Finally, process what needs to be done.
-            if build_dep_redirect || proc_macro_redirect {
-                host_ixs.push(to.feature_ix());
-            }
-            if proc_macro_redirect {
-                follow_target = false;
+            let from = link.from();
+            let from_initial = query.params.has_initial(from.feature_ix());
+            let (follow_target, redirect_host) = match &self.feature_edges {
+                // Decisions for links out of initials depend on the query, so they aren't cached.
+                Some(cache) if !from_initial => {
+                    cache.target_edge(&link, || self.v2_target_edge(&link, false))
+                }
+                _ => {
+                    let consider_dev = from_initial && self.include_dev_for(from.package());
+                    self.v2_target_edge(&link, consider_dev)
+                }
+            };
+            if redirect_host {
+                host_ixs.push(link.to().feature_ix());
             }
-
             follow_target
         });
 
@@ -414,25 +595,86 @@ impl<'a> CargoSetBuildState<'a> {
         let host = graph
             .query_from_parts(SortedSet::new(host_ixs), DependencyDirection::Forward)
             .resolve_with_fn(|_, link| {
-                let (from, to) = link.endpoints();
-                if self.is_omitted(to.package_ix()) {
-                    // Pretend that the omitted set doesn't exist.
-                    return false;
+                let from = link.from();
+                let from_initial = target_query_2.params.has_initial(from.feature_ix());
+                match &self.feature_edges {
+                    Some(cache) if !from_initial => {
+                        cache.host_edge(&link, || self.v2_host_edge(&link, false))
+                    }
+                    _ => {
+                        let consider_dev = from_initial && self.include_dev_for(from.package());
+                        self.v2_host_edge(&link, consider_dev)
+                    }
                 }
-                // During feature resolution, the v2 resolver doesn't check for whether this package
-                // has a build script. It also unifies dev dependencies of initials, even on the
-                // host platform.
-                let consider_dev = self.opts.include_dev
-                    && target_query_2
-                        .starts_from(from.feature_id())
-                        .expect("valid ID");
-
-                is_enabled(&link, DependencyKind::Normal, host_platform)
-                    || is_enabled(&link, DependencyKind::Build, host_platform)
-                    || (consider_dev
-                        && is_enabled(&link, DependencyKind::Development, host_platform))
             });
 
         CargoIntermediateSet::TargetHost { target, host }
     }
+
+    /// Returns whether this link is followed on the target with the V2 resolver, and whether it's
+    /// redirected to the host.
+    fn v2_target_edge(&self, link: &ConditionalLink<'_>, consider_dev: bool) -> (bool, bool) {
+        let to = link.to();
+        if self.is_omitted(to.package_ix()) {
+            // Pretend that the omitted set doesn't exist.
+            return (false, false);
+        }
+
+        // This resolver doesn't check for whether this package has a build script.
+        let mut follow_target = self.v2_is_enabled(link, DependencyKind::Normal, &self.target)
+            || (consider_dev
+                && self.v2_is_enabled(link, DependencyKind::Development, &self.target));
+        let package_link = link.package_link();
+        // Artifact dependencies can be built for the host platform instead.
+        let artifact_redirect = follow_target && self.is_host_artifact(&package_link);
+
+        // Build dependencies are evaluated against the host platform.
+        let build_enabled = self.v2_is_enabled(link, DependencyKind::Build, &self.host);
+        // Artifact build dependencies can be built for the target platform instead.
+        let build_on_target = build_enabled && self.is_target_build_artifact(&package_link);
+        let build_dep_redirect = build_enabled && !build_on_target;
+        follow_target |= build_on_target;
+
+        // Proc macros build on the host, so for normal/dev dependencies redirect it to the host
+        // instead.
+        let proc_macro_redirect = follow_target && to.package().is_proc_macro();
+
+        // Finally, process what needs to be done.
+        let redirect_host = build_dep_redirect || proc_macro_redirect || artifact_redirect;
+        if proc_macro_redirect || artifact_redirect {
+            follow_target = false;
+        }
+
+        (follow_target, redirect_host)
+    }
+
+    /// Returns whether this link is followed on the host with the V2 resolver.
+    fn v2_host_edge(&self, link: &ConditionalLink<'_>, consider_dev: bool) -> bool {
+        if self.is_omitted(link.to().package_ix()) {
+            // Pretend that the omitted set doesn't exist.
+            return false;
+        }
+        // During feature resolution, the v2 resolver doesn't check for whether this package
+        // has a build script. It also unifies dev dependencies of initials, even on the
+        // host platform.
+        self.v2_is_enabled(link, DependencyKind::Normal, &self.host)
+            || self.v2_is_enabled(link, DependencyKind::Build, &self.host)
+            || (consider_dev && self.v2_is_enabled(link, DependencyKind::Development, &self.host))
+    }
+
+    fn v2_is_enabled(
+        &self,
+        link: &ConditionalLink<'_>,
+        kind: DependencyKind,
+        platform: &PlatformEvaluator<'_>,
+    ) -> bool {
+        platform.conditional_enabled_on(link, kind) != EnabledTernary::Disabled
+    }
+}
+
+/// The platform an artifact dependency is built for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ArtifactPlatform {
+    Target,
+    Host,
 }
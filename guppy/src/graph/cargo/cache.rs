@@ -0,0 +1,356 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{feature::ConditionalLink, PackageGraph, PackageIx, PackageLink},
+    platform::{EnabledTernary, PlatformSpec},
+    sorted_set::SortedSet,
+    DependencyKind,
+};
+use petgraph::graph::NodeIndex;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// An optional cache used to speed up repeated Cargo build simulations on the same graph.
+///
+/// Tools like `hakari` and the determinator simulate hundreds of builds on the same package graph,
+/// each with slightly different initials. Much of the work in each simulation is in deciding, for
+/// every dependency and feature edge it visits, whether the edge is followed on the target and host
+/// platforms. Outside of the initials, those decisions only depend on the edge and the options
+/// used, so this cache memoizes them across simulations. In particular, the cache stores:
+///
+/// * whether each dependency edge is enabled on each platform simulated
+/// * with the V2 resolver, how features are propagated across each feature edge, for each
+///   combination of target platform, host platform and omitted packages simulated
+///
+/// Created with `PackageGraph::new_cargo_resolver_cache()`, and used by passing it into
+/// [`CargoOptions::set_resolver_cache`](crate::graph::cargo::CargoOptions::set_resolver_cache).
+/// The cache may be shared across threads, and across `CargoOptions` with different platforms.
+///
+/// ## Examples
+///
+/// ```
+/// use guppy::{
+///     graph::{
+///         cargo::{CargoOptions, CargoResolverVersion, CargoSet},
+///         feature::StandardFeatures,
+///     },
+///     platform::{Platform, TargetFeatures},
+///     CargoMetadata,
+/// };
+///
+/// let metadata = CargoMetadata::parse_json(include_str!(
+///     "../../../../fixtures/guppy/metadata_guppy_44b62fa.json"
+/// )).unwrap();
+/// let package_graph = metadata.build_graph().unwrap();
+///
+/// let cache = package_graph.new_cargo_resolver_cache();
+/// let mut cargo_opts = CargoOptions::new();
+/// cargo_opts
+///     .set_resolver(CargoResolverVersion::V2)
+///     .set_platform(Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap())
+///     .set_resolver_cache(&cache);
+///
+/// // Simulate a build of each workspace package. Later simulations reuse platform evaluations
+/// // from earlier ones.
+/// for package in package_graph.workspace().iter() {
+///     let initials = package.to_package_set().to_feature_set(StandardFeatures::Default);
+///     let features_only = package_graph.resolve_none().to_feature_set(StandardFeatures::All);
+///     CargoSet::new(initials, features_only, &cargo_opts).unwrap();
+/// }
+/// ```
+pub struct CargoResolverCache<'g> {
+    package_graph: &'g PackageGraph,
+    // A small number of platforms and options is used in practice, so lists are fine.
+    platforms: Mutex<Vec<(PlatformSpec, Arc<PlatformCache>)>>,
+    feature_edges: Mutex<Vec<(FeatureEdgeKey, Arc<FeatureEdgeCache>)>>,
+}
+
+impl<'g> CargoResolverCache<'g> {
+    /// Creates a new cache for Cargo build simulations on this package graph.
+    ///
+    /// This holds a shared reference to the package graph. This is to ensure that the cache is
+    /// invalidated if the package graph is mutated.
+    pub fn new(package_graph: &'g PackageGraph) -> Self {
+        Self {
+            package_graph,
+            platforms: Mutex::new(vec![]),
+            feature_edges: Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns the package graph this cache is for.
+    pub fn package_graph(&self) -> &'g PackageGraph {
+        self.package_graph
+    }
+
+    /// Returns the cache for this platform, creating it if necessary.
+    pub(super) fn platform_cache(&self, platform_spec: &PlatformSpec) -> Arc<PlatformCache> {
+        let mut platforms = self.platforms.lock().expect("cache lock is not poisoned");
+        if let Some((_, cache)) = platforms.iter().find(|(spec, _)| spec == platform_spec) {
+            return cache.clone();
+        }
+        let cache = Arc::new(PlatformCache::new(self.package_graph));
+        platforms.push((platform_spec.clone(), cache.clone()));
+        cache
+    }
+
+    /// Returns the cache of feature propagation decisions for these options, creating it if
+    /// necessary.
+    ///
+    /// Returns `None` if this cache is for a different package graph.
+    pub(super) fn feature_edge_cache(
+        &self,
+        package_graph: &PackageGraph,
+        key: FeatureEdgeKey,
+    ) -> Option<Arc<FeatureEdgeCache>> {
+        if !self.is_for(package_graph) {
+            return None;
+        }
+        let mut feature_edges = self
+            .feature_edges
+            .lock()
+            .expect("cache lock is not poisoned");
+        if let Some((_, cache)) = feature_edges.iter().find(|(k, _)| k == &key) {
+            return Some(cache.clone());
+        }
+        let cache = Arc::new(FeatureEdgeCache::new(self.package_graph));
+        feature_edges.push((key, cache.clone()));
+        Some(cache)
+    }
+
+    fn is_for(&self, package_graph: &PackageGraph) -> bool {
+        // A cache for a different graph would have different edge indexes.
+        std::ptr::eq(self.package_graph, package_graph)
+    }
+}
+
+impl<'g> fmt::Debug for CargoResolverCache<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let platforms = self.platforms.lock().expect("cache lock is not poisoned");
+        f.debug_struct("CargoResolverCache")
+            .field(
+                "platforms",
+                &platforms.iter().map(|(spec, _)| spec).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Evaluates whether dependency and feature edges are enabled on a platform, through a cache if
+/// one is available.
+#[derive(Clone, Debug)]
+pub(super) struct PlatformEvaluator<'a> {
+    platform_spec: &'a PlatformSpec,
+    cache: Option<Arc<PlatformCache>>,
+}
+
+impl<'a> PlatformEvaluator<'a> {
+    pub(super) fn new(
+        package_graph: &PackageGraph,
+        platform_spec: &'a PlatformSpec,
+        cache: Option<&CargoResolverCache<'_>>,
+    ) -> Self {
+        let cache = cache
+            .filter(|cache| cache.is_for(package_graph))
+            .map(|cache| cache.platform_cache(platform_spec));
+        Self {
+            platform_spec,
+            cache,
+        }
+    }
+
+    /// Returns the status of this dependency on this platform, assuming it isn't activated through
+    /// an optional dependency.
+    pub(super) fn required_on(
+        &self,
+        link: &PackageLink<'_>,
+        kind: DependencyKind,
+    ) -> EnabledTernary {
+        let eval = || {
+            link.req_for_kind(kind)
+                .status()
+                .required_on(self.platform_spec)
+        };
+        match &self.cache {
+            Some(cache) => get_or_eval(&cache.links[link_slot(link, kind, false)], eval),
+            None => eval(),
+        }
+    }
+
+    /// Returns the status of this dependency on this platform, assuming it's activated if
+    /// optional.
+    pub(super) fn enabled_on(
+        &self,
+        link: &PackageLink<'_>,
+        kind: DependencyKind,
+    ) -> EnabledTernary {
+        let eval = || {
+            link.req_for_kind(kind)
+                .status()
+                .enabled_on(self.platform_spec)
+        };
+        match &self.cache {
+            Some(cache) => get_or_eval(&cache.links[link_slot(link, kind, true)], eval),
+            None => eval(),
+        }
+    }
+
+    /// Returns the status of this feature dependency on this platform.
+    pub(super) fn conditional_enabled_on(
+        &self,
+        link: &ConditionalLink<'_>,
+        kind: DependencyKind,
+    ) -> EnabledTernary {
+        let eval = || link.status_for_kind(kind).enabled_on(self.platform_spec);
+        match &self.cache {
+            Some(cache) => {
+                let slot = link.edge_ix().index() * KIND_COUNT + kind_index(kind);
+                get_or_eval(&cache.conditional_links[slot], eval)
+            }
+            None => eval(),
+        }
+    }
+}
+
+/// Cached evaluations for a single platform.
+pub(super) struct PlatformCache {
+    // For each package edge and dependency kind: required_on and enabled_on.
+    links: Vec<AtomicU8>,
+    // For each feature edge and dependency kind: enabled_on.
+    conditional_links: Vec<AtomicU8>,
+}
+
+impl PlatformCache {
+    fn new(package_graph: &PackageGraph) -> Self {
+        let link_count = package_graph.link_count();
+        let conditional_link_count = package_graph.feature_graph().link_count();
+        Self {
+            links: (0..link_count * KIND_COUNT * 2)
+                .map(|_| AtomicU8::new(NOT_EVALUATED))
+                .collect(),
+            conditional_links: (0..conditional_link_count * KIND_COUNT)
+                .map(|_| AtomicU8::new(NOT_EVALUATED))
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Debug for PlatformCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlatformCache").finish_non_exhaustive()
+    }
+}
+
+/// The options that feature propagation decisions depend on, apart from the edge.
+#[derive(Debug, Eq, PartialEq)]
+pub(super) struct FeatureEdgeKey {
+    pub(super) target_platform: PlatformSpec,
+    pub(super) host_platform: PlatformSpec,
+    // Omitted packages, including ones excluded through rust-version.
+    pub(super) omitted: SortedSet<NodeIndex<PackageIx>>,
+}
+
+/// Cached feature propagation decisions for links not out of the initials, with the V2 resolver.
+pub(super) struct FeatureEdgeCache {
+    // For each feature edge, whether it's followed on the target and whether it's redirected to
+    // the host.
+    target: Vec<AtomicU8>,
+    // For each feature edge, whether it's followed on the host.
+    host: Vec<AtomicU8>,
+}
+
+impl FeatureEdgeCache {
+    fn new(package_graph: &PackageGraph) -> Self {
+        let conditional_link_count = package_graph.feature_graph().link_count();
+        Self {
+            target: (0..conditional_link_count)
+                .map(|_| AtomicU8::new(NOT_EVALUATED))
+                .collect(),
+            host: (0..conditional_link_count)
+                .map(|_| AtomicU8::new(NOT_EVALUATED))
+                .collect(),
+        }
+    }
+
+    /// Returns whether this link is followed on the target, and whether it's redirected to the
+    /// host.
+    pub(super) fn target_edge(
+        &self,
+        link: &ConditionalLink<'_>,
+        eval: impl FnOnce() -> (bool, bool),
+    ) -> (bool, bool) {
+        let slot = &self.target[link.edge_ix().index()];
+        let bits = get_or_eval_bits(slot, || {
+            let (follow_target, redirect_host) = eval();
+            follow_target as u8 | (redirect_host as u8) << 1
+        });
+        (bits & 1 != 0, bits & 2 != 0)
+    }
+
+    /// Returns whether this link is followed on the host.
+    pub(super) fn host_edge(
+        &self,
+        link: &ConditionalLink<'_>,
+        eval: impl FnOnce() -> bool,
+    ) -> bool {
+        let slot = &self.host[link.edge_ix().index()];
+        get_or_eval_bits(slot, || eval() as u8) != 0
+    }
+}
+
+impl fmt::Debug for FeatureEdgeCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeatureEdgeCache").finish_non_exhaustive()
+    }
+}
+
+const KIND_COUNT: usize = 3;
+const NOT_EVALUATED: u8 = 0;
+const EVALUATED: u8 = 0x80;
+
+fn kind_index(kind: DependencyKind) -> usize {
+    match kind {
+        DependencyKind::Normal => 0,
+        DependencyKind::Build => 1,
+        DependencyKind::Development => 2,
+    }
+}
+
+fn link_slot(link: &PackageLink<'_>, kind: DependencyKind, enabled: bool) -> usize {
+    (link.edge_ix().index() * KIND_COUNT + kind_index(kind)) * 2 + enabled as usize
+}
+
+fn get_or_eval(slot: &AtomicU8, eval: impl FnOnce() -> EnabledTernary) -> EnabledTernary {
+    let bits = get_or_eval_bits(slot, || match eval() {
+        EnabledTernary::Disabled => 0,
+        EnabledTernary::Unknown => 1,
+        EnabledTernary::Enabled => 2,
+    });
+    match bits {
+        0 => EnabledTernary::Disabled,
+        1 => EnabledTernary::Unknown,
+        _ => EnabledTernary::Enabled,
+    }
+}
+
+/// Returns the value stored in this slot, evaluating and storing it if necessary.
+///
+/// Values must fit in the low 7 bits.
+fn get_or_eval_bits(slot: &AtomicU8, eval: impl FnOnce() -> u8) -> u8 {
+    // Evaluations are deterministic, so if several threads race to fill in a slot they'll store
+    // the same value. Relaxed ordering is therefore sufficient.
+    match slot.load(Ordering::Relaxed) {
+        NOT_EVALUATED => {
+            let bits = eval();
+            slot.store(bits | EVALUATED, Ordering::Relaxed);
+            bits
+        }
+        stored => stored & !EVALUATED,
+    }
+}
@@ -3,17 +3,21 @@
 
 use crate::{
     graph::{
-        cargo::build::CargoSetBuildState,
-        feature::{FeatureGraph, FeatureSet},
-        DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageSet,
+        cargo::{build::CargoSetBuildState, CargoResolverCache},
+        feature::{FeatureGraph, FeatureId, FeatureSet},
+        BuildTargetId, DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageSet,
     },
     platform::PlatformSpec,
     sorted_set::SortedSet,
     Error, PackageId,
 };
 use petgraph::prelude::*;
+use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fmt};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
 
 /// Options for queries which simulate what Cargo does.
 ///
@@ -27,6 +31,10 @@ pub struct CargoOptions<'a> {
     pub(crate) host_platform: PlatformSpec,
     pub(crate) target_platform: PlatformSpec,
     pub(crate) omitted_packages: HashSet<&'a PackageId>,
+    pub(crate) rust_version: Option<Version>,
+    pub(crate) ignore_rust_version: bool,
+    pub(crate) initials_build_targets: HashMap<&'a PackageId, BTreeSet<BuildTargetId<'a>>>,
+    pub(crate) resolver_cache: Option<&'a CargoResolverCache<'a>>,
 }
 
 impl<'a> CargoOptions<'a> {
@@ -38,7 +46,8 @@ impl<'a> CargoOptions<'a> {
     /// * exclude dev-dependencies
     /// * do not build proc macros specified in the query on the target platform
     /// * resolve dependencies assuming any possible host or target platform
-    /// * do not omit any packages.
+    /// * do not omit any packages
+    /// * do not check `rust-version` constraints.
     pub fn new() -> Self {
         Self {
             resolver: CargoResolverVersion::V1,
@@ -47,6 +56,10 @@ impl<'a> CargoOptions<'a> {
             host_platform: PlatformSpec::Any,
             target_platform: PlatformSpec::Any,
             omitted_packages: HashSet::new(),
+            rust_version: None,
+            ignore_rust_version: false,
+            initials_build_targets: HashMap::new(),
+            resolver_cache: None,
         }
     }
 
@@ -112,6 +125,66 @@ impl<'a> CargoOptions<'a> {
         self.omitted_packages.extend(package_ids);
         self
     }
+
+    /// Sets the version of the Rust compiler to simulate builds with.
+    ///
+    /// If set, dependencies with a
+    /// [`rust-version`](crate::graph::PackageMetadata::minimum_rust_version) newer than this
+    /// version are excluded from the build, as if they had been omitted. The packages that were excluded this way are available through
+    /// [`CargoSet::rust_version_excluded`].
+    ///
+    /// Pre-release information in the version, such as `-nightly`, is ignored.
+    ///
+    /// The default is `None`, which means that `rust-version` constraints aren't checked.
+    pub fn set_rust_version(&mut self, rust_version: Option<Version>) -> &mut Self {
+        self.rust_version = rust_version;
+        self
+    }
+
+    /// If set to true, `rust-version` constraints are not checked even if a Rust version is set.
+    ///
+    /// This matches the behavior of `cargo build --ignore-rust-version`. The default is false.
+    pub fn set_ignore_rust_version(&mut self, ignore_rust_version: bool) -> &mut Self {
+        self.ignore_rust_version = ignore_rust_version;
+        self
+    }
+
+    /// Selects specific build targets to simulate building for a package in the initial set.
+    ///
+    /// By default, all initials are treated as package-level builds. Selecting build targets
+    /// allows simulations to match invocations like `cargo build -p foo --example bar` or
+    /// `cargo test -p foo --test integration`:
+    ///
+    /// * Dev-dependencies are followed for a package if `include_dev` is set, or if any of the
+    ///   selected targets is an example, test or benchmark.
+    /// * Simulating a build returns an error if a selected target doesn't exist, or if its
+    ///   [required features](crate::graph::BuildTarget::required_features) aren't enabled in the
+    ///   initials. (Cargo also errors out in this case.)
+    ///
+    /// This method is additive.
+    pub fn add_initials_build_targets(
+        &mut self,
+        package_id: &'a PackageId,
+        build_targets: impl IntoIterator<Item = BuildTargetId<'a>>,
+    ) -> &mut Self {
+        self.initials_build_targets
+            .entry(package_id)
+            .or_default()
+            .extend(build_targets);
+        self
+    }
+
+    /// Sets a cache to reuse platform evaluations across build simulations.
+    ///
+    /// Simulations produce the same results with or without a cache, but repeated simulations on
+    /// the same graph are faster with one. The cache is ignored if it was created for a different
+    /// package graph.
+    ///
+    /// For more, see the documentation for [`CargoResolverCache`].
+    pub fn set_resolver_cache(&mut self, cache: &'a CargoResolverCache<'a>) -> &mut Self {
+        self.resolver_cache = Some(cache);
+        self
+    }
 }
 
 impl<'a> Default for CargoOptions<'a> {
@@ -192,6 +265,114 @@ impl Default for InitialsPlatform {
     }
 }
 
+/// A builder for [`CargoSet`] instances with several named `features_only` contributions.
+///
+/// [`CargoSet::new`] accepts a single `features_only` set. In practice, the packages that take
+/// part in feature unification often come from several sources -- for example, a CI job may build
+/// some crates purely for their influence on features. `CargoSetBuilder` accepts any number of
+/// named contributions, unifies them into the `features_only` set, and records where each one
+/// came from. The contributions are then available through
+/// [`CargoSet::features_only_contributions`] and [`CargoSet::features_only_sources`].
+///
+/// ## Examples
+///
+/// ```
+/// use guppy::{
+///     graph::{
+///         cargo::{CargoOptions, CargoSetBuilder},
+///         feature::StandardFeatures,
+///     },
+///     CargoMetadata,
+/// };
+///
+/// let metadata = CargoMetadata::parse_json(include_str!(
+///     "../../../../fixtures/guppy/metadata_guppy_44b62fa.json"
+/// )).unwrap();
+/// let package_graph = metadata.build_graph().unwrap();
+///
+/// let initials = package_graph
+///     .resolve_workspace_names(["guppy"])
+///     .unwrap()
+///     .to_feature_set(StandardFeatures::Default);
+/// // Simulate a CI job that also builds cargo-guppy, only for its influence on features.
+/// let ci_packages = package_graph
+///     .resolve_workspace_names(["cargo-guppy"])
+///     .unwrap()
+///     .to_feature_set(StandardFeatures::Default);
+///
+/// let cargo_set = CargoSetBuilder::new(initials)
+///     .add_features_only("ci", ci_packages)
+///     .build(&CargoOptions::new())
+///     .unwrap();
+/// assert_eq!(cargo_set.features_only_contributions().len(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CargoSetBuilder<'g> {
+    initials: FeatureSet<'g>,
+    features_only: Vec<(String, FeatureSet<'g>)>,
+}
+
+impl<'g> CargoSetBuilder<'g> {
+    /// Creates a new `CargoSetBuilder` with these initials and no `features_only` contributions.
+    pub fn new(initials: FeatureSet<'g>) -> Self {
+        Self {
+            initials,
+            features_only: vec![],
+        }
+    }
+
+    /// Adds a named `features_only` contribution.
+    ///
+    /// If a contribution with the same name was added earlier, the features are added to it.
+    pub fn add_features_only(
+        &mut self,
+        name: impl Into<String>,
+        features: FeatureSet<'g>,
+    ) -> &mut Self {
+        let name = name.into();
+        match self
+            .features_only
+            .iter_mut()
+            .find(|(existing, _)| *existing == name)
+        {
+            Some((_, existing)) => *existing = existing.union(&features),
+            None => self.features_only.push((name, features)),
+        }
+        self
+    }
+
+    /// Returns the initials for this builder.
+    pub fn initials(&self) -> &FeatureSet<'g> {
+        &self.initials
+    }
+
+    /// Iterates over the `features_only` contributions added so far, in the order they were added.
+    pub fn features_only(&self) -> impl ExactSizeIterator<Item = (&str, &FeatureSet<'g>)> {
+        self.features_only
+            .iter()
+            .map(|(name, features)| (name.as_str(), features))
+    }
+
+    /// Simulates a Cargo build with these options.
+    ///
+    /// The `features_only` set for the build is the union of all the contributions. For more, see
+    /// the documentation for [`CargoSet::new`].
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any of the contributions were created from a different package graph than the
+    /// initials.
+    pub fn build(&self, opts: &CargoOptions<'_>) -> Result<CargoSet<'g>, Error> {
+        let features_only = self.features_only.iter().fold(
+            self.initials.graph().resolve_none(),
+            |acc, (_, features)| acc.union(features),
+        );
+        let mut cargo_set = CargoSet::new(self.initials.clone(), features_only, opts)?;
+        cargo_set.features_only_contributions = self.features_only.clone();
+        Ok(cargo_set)
+    }
+}
+
 /// A set of packages and features, as would be built by Cargo.
 ///
 /// Cargo implements a set of algorithms to figure out which packages or features are built in
@@ -200,12 +381,16 @@ impl Default for InitialsPlatform {
 pub struct CargoSet<'g> {
     pub(super) initials: FeatureSet<'g>,
     pub(super) features_only: FeatureSet<'g>,
+    pub(super) features_only_contributions: Vec<(String, FeatureSet<'g>)>,
     pub(super) target_features: FeatureSet<'g>,
     pub(super) host_features: FeatureSet<'g>,
     pub(super) target_direct_deps: PackageSet<'g>,
     pub(super) host_direct_deps: PackageSet<'g>,
     pub(super) proc_macro_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
     pub(super) build_dep_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
+    pub(super) target_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
+    pub(super) host_edge_ixs: SortedSet<EdgeIndex<PackageIx>>,
+    pub(super) rust_version_excluded: PackageSet<'g>,
 }
 
 assert_covariant!(CargoSet);
@@ -228,15 +413,15 @@ impl<'g> CargoSet<'g> {
     ///
     /// In many cases `features_only` is empty -- in that case you may wish to use
     /// `FeatureSet::into_cargo_set()`, and it may be more convenient to use that if the code is
-    /// written in a "fluent" style.
-    ///
-    ///
+    /// written in a "fluent" style. To build `features_only` out of several named sources, use
+    /// [`CargoSetBuilder`].
     pub fn new(
         initials: FeatureSet<'g>,
         features_only: FeatureSet<'g>,
         opts: &CargoOptions<'_>,
     ) -> Result<Self, Error> {
         let build_state = CargoSetBuildState::new(initials.graph().package_graph, opts)?;
+        build_state.check_required_features(&initials)?;
         Ok(build_state.build(initials, features_only))
     }
 
@@ -279,6 +464,57 @@ impl<'g> CargoSet<'g> {
         &self.features_only
     }
 
+    /// Returns the named contributions that make up [`features_only`](Self::features_only), in the
+    /// order they were added.
+    ///
+    /// This is empty unless the `CargoSet` was constructed through a [`CargoSetBuilder`].
+    pub fn features_only_contributions(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (&str, &FeatureSet<'g>)> {
+        self.features_only_contributions
+            .iter()
+            .map(|(name, features)| (name.as_str(), features))
+    }
+
+    /// Returns the names of the `features_only` contributions that may have caused this feature to
+    /// be built.
+    ///
+    /// A contribution is returned if this feature is reachable from it through features built on
+    /// either the target or the host platform. The names are returned in the order the
+    /// contributions were added. An empty list is returned if the feature isn't built, or if no
+    /// contribution leads to it.
+    ///
+    /// Returns an error if this feature ID was unknown.
+    pub fn features_only_sources<'a>(
+        &self,
+        feature_id: impl Into<FeatureId<'a>>,
+    ) -> Result<Vec<&str>, Error> {
+        let feature_id = feature_id.into();
+        let built = self.target_features.union(&self.host_features);
+        if !built.contains(feature_id)? {
+            return Ok(vec![]);
+        }
+
+        let sources = self
+            .features_only_contributions
+            .iter()
+            .filter_map(|(name, features)| {
+                let reachable = features
+                    .to_feature_query(DependencyDirection::Forward)
+                    .resolve_with_fn(|_, link| {
+                        built
+                            .contains(link.to().feature_id())
+                            .expect("feature IDs in the graph are known")
+                    });
+                reachable
+                    .contains(feature_id)
+                    .expect("feature ID already checked")
+                    .then(|| name.as_str())
+            })
+            .collect();
+        Ok(sources)
+    }
+
     /// Returns the feature set enabled on the target platform.
     ///
     /// This represents the packages and features that are included as code in the final build
@@ -344,6 +580,15 @@ impl<'g> CargoSet<'g> {
         ]
     }
 
+    /// Returns the set of packages that would have been built, but were excluded because their
+    /// `rust-version` isn't satisfied by the Rust version being simulated.
+    ///
+    /// This is empty unless [`CargoOptions::set_rust_version`] is set. Packages that are only
+    /// reachable through excluded packages are not included.
+    pub fn rust_version_excluded(&self) -> &PackageSet<'g> {
+        &self.rust_version_excluded
+    }
+
     /// Returns `PackageLink` instances for procedural macro dependencies from target packages.
     ///
     /// Procedural macros straddle the line between target and host: they're built for the host
@@ -383,6 +628,84 @@ impl<'g> CargoSet<'g> {
             .iter()
             .map(move |edge_ix| package_graph.edge_ix_to_link(*edge_ix))
     }
+
+    /// Returns `PackageLink` instances for dependencies followed to packages built on the target
+    /// platform.
+    ///
+    /// Both ends of each link are built on the target platform.
+    pub fn target_links<'a>(&'a self) -> impl ExactSizeIterator<Item = PackageLink<'g>> + 'a {
+        let package_graph = self.target_features.graph().package_graph;
+        self.target_edge_ixs
+            .iter()
+            .map(move |edge_ix| package_graph.edge_ix_to_link(*edge_ix))
+    }
+
+    /// Returns `PackageLink` instances for dependencies followed to packages built on the host
+    /// platform.
+    ///
+    /// ## Notes
+    ///
+    /// The `to` end of each link is built on the host platform, but the `from` end may be built on
+    /// either platform. In particular, this includes the links returned by
+    /// [`proc_macro_links`](Self::proc_macro_links) and
+    /// [`build_dep_links`](Self::build_dep_links).
+    pub fn host_links<'a>(&'a self) -> impl ExactSizeIterator<Item = PackageLink<'g>> + 'a {
+        let package_graph = self.target_features.graph().package_graph;
+        self.host_edge_ixs
+            .iter()
+            .map(move |edge_ix| package_graph.edge_ix_to_link(*edge_ix))
+    }
+
+    /// Returns the set of packages that are only built because of build dependencies of target
+    /// packages.
+    ///
+    /// This consists of the build dependencies returned by
+    /// [`build_dep_links`](Self::build_dep_links), along with their transitive dependencies on the
+    /// host platform, minus any packages that are also built on the target platform. The code in
+    /// these packages is run on the machine doing the compiling, but isn't included in the final
+    /// build artifacts.
+    ///
+    /// A package may be in both this set and [`proc_macro_closure`](Self::proc_macro_closure).
+    pub fn build_script_closure(&self) -> PackageSet<'g> {
+        self.host_closure(self.build_dep_links())
+    }
+
+    /// Returns the set of packages that are only built because of procedural macro dependencies of
+    /// target packages.
+    ///
+    /// This consists of the procedural macros returned by
+    /// [`proc_macro_links`](Self::proc_macro_links), along with their transitive dependencies on
+    /// the host platform, minus any packages that are also built on the target platform.
+    ///
+    /// A package may be in both this set and
+    /// [`build_script_closure`](Self::build_script_closure).
+    pub fn proc_macro_closure(&self) -> PackageSet<'g> {
+        self.host_closure(self.proc_macro_links())
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    /// Returns the packages reachable on the host platform from the `to` ends of these links,
+    /// excluding packages that are also built on the target platform.
+    fn host_closure(&self, links: impl Iterator<Item = PackageLink<'g>>) -> PackageSet<'g> {
+        let root_ids: HashSet<_> = links.map(|link| link.to().id()).collect();
+        let host_features = &self.host_features;
+        let closure = host_features
+            .filter(DependencyDirection::Forward, |feature| {
+                root_ids.contains(feature.package_id())
+            })
+            .to_feature_query(DependencyDirection::Forward)
+            .resolve_with_fn(|_, link| {
+                host_features
+                    .contains(link.to().feature_id())
+                    .expect("valid feature ID")
+            });
+        closure
+            .to_package_set()
+            .difference(&self.target_features.to_package_set())
+    }
 }
 
 /// Either the target or the host platform.
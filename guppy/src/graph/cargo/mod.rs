@@ -7,6 +7,10 @@
 //! module reimplements those algorithms using `guppy`'s data structures.
 
 pub(super) mod build;
+mod cache;
 mod cargo_api;
+mod unused;
 
+pub use cache::CargoResolverCache;
 pub use cargo_api::*;
+pub use unused::*;
@@ -0,0 +1,107 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        cargo::CargoSet,
+        feature::{FeatureId, FeatureSet, StandardFeatures},
+        DependencyDirection, PackageGraph,
+    },
+    Error,
+};
+
+/// Contains information about named features of workspace packages that aren't enabled by any of a
+/// collection of Cargo builds.
+///
+/// A named feature is *unused* if it is declared in the `[features]` section of a workspace
+/// package, but none of the given `CargoSet` instances enable it on either the target or the host
+/// platform. Features that are only enabled by `--all-features` builds or by hand-picked feature
+/// combinations are unused in this sense, and are candidates for deletion.
+///
+/// The `CargoSet` instances would typically simulate the builds a workspace cares about, for
+/// example `cargo build --workspace` with and without dev-dependencies.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{
+///     graph::{
+///         cargo::{CargoOptions, UnusedFeatures},
+///         feature::StandardFeatures,
+///         DependencyDirection,
+///     },
+///     CargoMetadata,
+/// };
+///
+/// let metadata = CargoMetadata::parse_json(include_str!(
+///     "../../../../fixtures/guppy/metadata_guppy_44b62fa.json"
+/// )).unwrap();
+/// let package_graph = metadata.build_graph().unwrap();
+///
+/// // Simulate `cargo build --workspace`.
+/// let cargo_set = package_graph
+///     .resolve_workspace()
+///     .to_feature_set(StandardFeatures::Default)
+///     .into_cargo_set(&CargoOptions::new())
+///     .unwrap();
+///
+/// let unused_features = UnusedFeatures::new(&package_graph, [&cargo_set]);
+/// for feature in unused_features.unused_set().features(DependencyDirection::Forward) {
+///     println!("{}: {}", feature.package().name(), feature.label());
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct UnusedFeatures<'g> {
+    used: FeatureSet<'g>,
+    unused: FeatureSet<'g>,
+}
+
+impl<'g> UnusedFeatures<'g> {
+    /// Computes the named features of workspace packages that aren't enabled by any of the given
+    /// Cargo builds.
+    pub fn new<'a>(
+        package_graph: &'g PackageGraph,
+        cargo_sets: impl IntoIterator<Item = &'a CargoSet<'g>>,
+    ) -> Self
+    where
+        'g: 'a,
+    {
+        let feature_graph = package_graph.feature_graph();
+        let used = cargo_sets
+            .into_iter()
+            .fold(feature_graph.resolve_none(), |used, cargo_set| {
+                used.union(cargo_set.target_features())
+                    .union(cargo_set.host_features())
+            });
+
+        let unused = package_graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::All)
+            .filter(DependencyDirection::Forward, |feature| {
+                feature.feature_id().is_named()
+            })
+            .difference(&used);
+
+        Self { used, unused }
+    }
+
+    /// Returns the set of features enabled by at least one of the Cargo builds.
+    ///
+    /// This includes features of packages outside the workspace.
+    pub fn used_set(&self) -> &FeatureSet<'g> {
+        &self.used
+    }
+
+    /// Returns the set of named workspace features that aren't enabled by any of the Cargo builds.
+    pub fn unused_set(&self) -> &FeatureSet<'g> {
+        &self.unused
+    }
+
+    /// Returns true if this feature is a named workspace feature that isn't enabled by any of the
+    /// Cargo builds.
+    ///
+    /// Returns an error if this feature ID isn't known.
+    pub fn is_unused<'a>(&self, feature_id: impl Into<FeatureId<'a>>) -> Result<bool, Error> {
+        self.unused.contains(feature_id)
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Package checksums from `Cargo.lock`.
+
+use crate::graph::PackageMetadata;
+#[cfg(feature = "lockfile")]
+use crate::{graph::PackageGraph, Error, PackageId};
+#[cfg(feature = "lockfile")]
+use camino::Utf8Path;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+#[cfg(feature = "lockfile")]
+use std::{fmt, fs, sync::Arc};
+
+/// The name, version and source of a package, as written out in `Cargo.lock`.
+pub(crate) type ChecksumKey = (String, String, String);
+
+/// Parses the checksums in the contents of a `Cargo.lock` file.
+///
+/// Both the current lockfile format and the older format, which lists checksums in a `[metadata]`
+/// section, are supported.
+pub(crate) fn parse_lockfile_checksums(
+    contents: &str,
+) -> Result<HashMap<ChecksumKey, String>, toml::de::Error> {
+    let lockfile: Lockfile = toml::from_str(contents)?;
+    let mut checksums = HashMap::new();
+
+    for package in lockfile.package {
+        if let (Some(source), Some(checksum)) = (package.source, package.checksum) {
+            checksums.insert((package.name, package.version, source), checksum);
+        }
+    }
+    // Older lockfiles store checksums as "checksum <name> <version> (<source>)" = "<checksum>".
+    for (key, checksum) in lockfile.metadata {
+        let spec = match key.strip_prefix("checksum ") {
+            Some(spec) => spec,
+            None => continue,
+        };
+        if checksum == "<none>" {
+            continue;
+        }
+        let mut parts = spec.splitn(3, ' ');
+        if let (Some(name), Some(version), Some(source)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            let source = source.trim_start_matches('(').trim_end_matches(')');
+            checksums.insert(
+                (name.to_owned(), version.to_owned(), source.to_owned()),
+                checksum,
+            );
+        }
+    }
+    Ok(checksums)
+}
+
+/// Returns the key for this package in the map returned by `parse_lockfile_checksums`, or `None`
+/// if it isn't from an external source.
+pub(crate) fn checksum_key(package: &PackageMetadata<'_>) -> Option<ChecksumKey> {
+    let source = package.source().external_source()?;
+    Some((
+        package.name().to_owned(),
+        package.version().to_string(),
+        source.to_owned(),
+    ))
+}
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockfilePackage>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct LockfilePackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    checksum: Option<String>,
+}
+
+/// Adding and verifying checksums from `Cargo.lock`.
+///
+/// Requires the `lockfile` feature to be enabled.
+#[cfg(feature = "lockfile")]
+impl PackageGraph {
+    /// Adds package checksums from the contents of a `Cargo.lock` file to this graph.
+    ///
+    /// `cargo metadata` doesn't include checksums, so they're only available through
+    /// [`PackageMetadata::checksum`] after this is called. Checksums already in the graph are
+    /// replaced, and packages that aren't in the lockfile keep their existing checksums, if any.
+    ///
+    /// Other clones of this graph are unaffected.
+    pub fn add_lockfile_checksums(&mut self, lockfile: &str) -> Result<(), Error> {
+        let path = self.workspace().root().join("Cargo.lock");
+        let checksums = parse_lockfile_checksums(lockfile)
+            .map_err(|err| Error::LockfileReadError(path, Box::new(err)))?;
+
+        let updates: Vec<_> = self
+            .packages()
+            .filter_map(|package| {
+                let checksum = checksums.get(&checksum_key(&package)?)?;
+                Some((package.id().clone(), checksum.as_str().into()))
+            })
+            .collect();
+        let data = Arc::make_mut(&mut self.data);
+        for (package_id, checksum) in updates {
+            if let Some(inner) = data.packages.get_mut(&package_id) {
+                inner.checksum = Some(checksum);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads package checksums from the `Cargo.lock` file at the root of the workspace, and adds
+    /// them to this graph.
+    ///
+    /// For more, see [`add_lockfile_checksums`](Self::add_lockfile_checksums).
+    pub fn read_lockfile_checksums(&mut self) -> Result<(), Error> {
+        let path = self.workspace().root().join("Cargo.lock");
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| Error::LockfileReadError(path, Box::new(err)))?;
+        self.add_lockfile_checksums(&contents)
+    }
+
+    /// Verifies that the checksums in the `Cargo.lock` file at `lockfile_path` match the ones in
+    /// this graph.
+    ///
+    /// Every package with a checksum in this graph must have the same checksum in the lockfile.
+    /// This can be used to check that a lockfile, for example one that was checked in or vendored,
+    /// refers to the same `.crate` files as the one the graph's checksums were read from.
+    ///
+    /// Packages without checksums in this graph aren't checked, so checksums must be added first
+    /// through [`add_lockfile_checksums`](Self::add_lockfile_checksums) or
+    /// [`read_lockfile_checksums`](Self::read_lockfile_checksums).
+    ///
+    /// Returns `Error::ChecksumMismatch` with all mismatches, sorted by package ID, if any are
+    /// found.
+    pub fn verify_checksums(&self, lockfile_path: impl AsRef<Utf8Path>) -> Result<(), Error> {
+        let lockfile_path = lockfile_path.as_ref();
+        let contents = fs::read_to_string(lockfile_path)
+            .map_err(|err| Error::LockfileReadError(lockfile_path.to_owned(), Box::new(err)))?;
+        let checksums = parse_lockfile_checksums(&contents)
+            .map_err(|err| Error::LockfileReadError(lockfile_path.to_owned(), Box::new(err)))?;
+
+        let mut mismatches: Vec<_> = self
+            .packages()
+            .filter_map(|package| {
+                let expected = package.checksum()?;
+                let actual = checksum_key(&package)
+                    .and_then(|key| checksums.get(&key))
+                    .map(|checksum| checksum.as_str());
+                (actual != Some(expected)).then(|| ChecksumMismatch {
+                    package_id: package.id().clone(),
+                    expected: expected.into(),
+                    actual: actual.map(|checksum| checksum.into()),
+                })
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            mismatches.sort_by(|a, b| a.package_id.cmp(&b.package_id));
+            Err(Error::ChecksumMismatch(mismatches))
+        }
+    }
+}
+
+/// A package whose checksum in a lockfile doesn't match the one in a `PackageGraph`.
+///
+/// Returned as part of `Error::ChecksumMismatch` by [`PackageGraph::verify_checksums`].
+///
+/// Requires the `lockfile` feature to be enabled.
+#[cfg(feature = "lockfile")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChecksumMismatch {
+    package_id: PackageId,
+    expected: Box<str>,
+    actual: Option<Box<str>>,
+}
+
+#[cfg(feature = "lockfile")]
+impl ChecksumMismatch {
+    /// Returns the ID of the package.
+    pub fn package_id(&self) -> &PackageId {
+        &self.package_id
+    }
+
+    /// Returns the checksum in the `PackageGraph`.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// Returns the checksum in the lockfile, or `None` if the lockfile doesn't have one for this
+    /// package.
+    pub fn actual(&self) -> Option<&str> {
+        self.actual.as_deref()
+    }
+}
+
+#[cfg(feature = "lockfile")]
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.actual {
+            Some(actual) => write!(
+                f,
+                "{}: expected checksum {}, found {}",
+                self.package_id, self.expected, actual
+            ),
+            None => write!(
+                f,
+                "{}: expected checksum {}, but the lockfile doesn't have one",
+                self.package_id, self.expected
+            ),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "lockfile"))]
+mod tests {
+    use super::*;
+    use crate::{graph::DependencyDirection, LockfileBuilder};
+
+    static LAZY_STATIC_CHECKSUM: &str =
+        "e2abad23fbc42b3700f2f279844dc832adb2b2eb069b2df918f455c4e18cc646";
+
+    fn inside_outside() -> LockfileBuilder {
+        let root = Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../fixtures/workspace/inside-outside/workspace");
+        let mut builder = LockfileBuilder::new(root);
+        builder.no_registry_cache();
+        builder
+    }
+
+    /// Returns the inside-outside graph without checksums, as it would be built from
+    /// `cargo metadata`.
+    fn inside_outside_without_checksums() -> PackageGraph {
+        inside_outside()
+            .exec()
+            .expect("metadata built from Cargo.lock")
+            .build_graph()
+            .expect("package graph built")
+    }
+
+    fn checksum<'g>(graph: &'g PackageGraph, name: &str) -> Option<&'g str> {
+        let package_set = graph.resolve_package_name(name);
+        let package = package_set
+            .packages(DependencyDirection::Forward)
+            .next()
+            .expect("package found");
+        package.checksum()
+    }
+
+    #[test]
+    fn parse_both_formats() {
+        let checksums = parse_lockfile_checksums(
+            r#"
+[[package]]
+name = "testcrate"
+version = "0.1.0"
+
+[[package]]
+name = "datatest"
+version = "0.4.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "0123456789abcdef"
+
+[metadata]
+"checksum regex 1.3.1 (registry+https://github.com/rust-lang/crates.io-index)" = "fedcba9876543210"
+"checksum walkdir 2.2.9 (registry+https://github.com/rust-lang/crates.io-index)" = "<none>"
+"#,
+        )
+        .expect("lockfile parsed");
+
+        let key = |name: &str, version: &str| {
+            (
+                name.to_owned(),
+                version.to_owned(),
+                "registry+https://github.com/rust-lang/crates.io-index".to_owned(),
+            )
+        };
+        assert_eq!(
+            checksums.len(),
+            2,
+            "only packages with checksums are listed"
+        );
+        assert_eq!(
+            checksums.get(&key("datatest", "0.4.2")).map(String::as_str),
+            Some("0123456789abcdef"),
+        );
+        assert_eq!(
+            checksums.get(&key("regex", "1.3.1")).map(String::as_str),
+            Some("fedcba9876543210"),
+        );
+    }
+
+    #[test]
+    fn lockfile_builder_checksums() {
+        let graph = inside_outside()
+            .build_graph()
+            .expect("package graph built from Cargo.lock");
+        assert_eq!(checksum(&graph, "lazy_static"), Some(LAZY_STATIC_CHECKSUM));
+        assert_eq!(
+            checksum(&graph, "main"),
+            None,
+            "workspace members have none"
+        );
+        let lockfile_path = graph.workspace().root().join("Cargo.lock");
+        graph
+            .verify_checksums(&lockfile_path)
+            .expect("checksums match the lockfile the graph was built from");
+    }
+
+    #[test]
+    fn add_and_verify() {
+        let mut graph = inside_outside_without_checksums();
+        assert_eq!(
+            checksum(&graph, "lazy_static"),
+            None,
+            "no checksums initially"
+        );
+
+        let original = graph.clone();
+        graph
+            .read_lockfile_checksums()
+            .expect("checksums read from Cargo.lock");
+        assert_eq!(checksum(&graph, "lazy_static"), Some(LAZY_STATIC_CHECKSUM));
+        assert_eq!(
+            checksum(&graph, "main"),
+            None,
+            "workspace members have none"
+        );
+        assert_eq!(
+            checksum(&original, "lazy_static"),
+            None,
+            "other clones are unaffected"
+        );
+
+        let lockfile_path = graph.workspace().root().join("Cargo.lock");
+        graph
+            .verify_checksums(&lockfile_path)
+            .expect("checksums match the lockfile they were read from");
+
+        // Change the checksum in the graph, so that it no longer matches Cargo.lock.
+        let contents = fs::read_to_string(&lockfile_path).expect("Cargo.lock read");
+        graph
+            .add_lockfile_checksums(&contents.replace(LAZY_STATIC_CHECKSUM, "0123456789abcdef"))
+            .expect("checksums added");
+        match graph.verify_checksums(&lockfile_path) {
+            Err(Error::ChecksumMismatch(mismatches)) => {
+                assert_eq!(mismatches.len(), 1, "one mismatch found");
+                assert_eq!(mismatches[0].expected(), "0123456789abcdef");
+                assert_eq!(mismatches[0].actual(), Some(LAZY_STATIC_CHECKSUM));
+            }
+            other => panic!("expected checksum mismatch, found {:?}", other),
+        }
+
+        match graph.verify_checksums(Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("missing.lock"))
+        {
+            Err(Error::LockfileReadError(..)) => {}
+            other => panic!("expected lockfile read error, found {:?}", other),
+        }
+    }
+}
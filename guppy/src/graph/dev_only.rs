@@ -0,0 +1,86 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Code for finding packages that are only used by tests and benchmarks.
+
+use crate::{
+    graph::{BuildTargetId, DependencyDirection, PackageGraph, PackageMetadata, PackageSet},
+    Error, PackageId,
+};
+
+/// Contains information about packages that are only required to build tests, benchmarks and
+/// examples.
+///
+/// A package is *dev-only* if it is a transitive dependency of the workspace, but it is not part
+/// of the *production closure*. The production closure consists of:
+/// * workspace packages with at least one library, binary or build script target, and
+/// * their transitive dependencies, following all links that aren't dev-only.
+///
+/// Dev-only packages are reachable only through `dev-dependencies`, or only from workspace
+/// packages that just contain tests, benchmarks or examples. They are candidates for being moved to
+/// `dev-dependencies`, or for removal from production builds.
+///
+/// Constructed through `PackageGraph::dev_only_packages`.
+#[derive(Clone, Debug)]
+pub struct DevOnlyPackages<'g> {
+    test_only_workspace: PackageSet<'g>,
+    production: PackageSet<'g>,
+    dev_only: PackageSet<'g>,
+}
+
+impl<'g> DevOnlyPackages<'g> {
+    pub(super) fn new(package_graph: &'g PackageGraph) -> Self {
+        let (production_roots, test_only_workspace) = package_graph
+            .resolve_workspace()
+            .partition(DependencyDirection::Forward, |package| {
+                !is_test_only(package)
+            });
+        let production = production_roots
+            .to_package_query(DependencyDirection::Forward)
+            .resolve_with_fn(|_, link| !link.dev_only());
+        let dev_only = package_graph
+            .query_workspace()
+            .resolve()
+            .difference(&production);
+
+        Self {
+            test_only_workspace,
+            production,
+            dev_only,
+        }
+    }
+
+    /// Returns the set of workspace packages that only have test, benchmark or example targets.
+    ///
+    /// These packages are always dev-only.
+    pub fn test_only_workspace_set(&self) -> &PackageSet<'g> {
+        &self.test_only_workspace
+    }
+
+    /// Returns the production closure: the set of packages that are required by workspace
+    /// libraries, binaries and build scripts.
+    pub fn production_set(&self) -> &PackageSet<'g> {
+        &self.production
+    }
+
+    /// Returns the set of packages that are only required by tests, benchmarks and examples.
+    pub fn dev_only_set(&self) -> &PackageSet<'g> {
+        &self.dev_only
+    }
+
+    /// Returns true if this package is dev-only.
+    ///
+    /// Returns an error if this package ID isn't known.
+    pub fn is_dev_only(&self, package_id: &PackageId) -> Result<bool, Error> {
+        self.dev_only.contains(package_id)
+    }
+}
+
+fn is_test_only(package: PackageMetadata<'_>) -> bool {
+    package.build_targets().all(|target| {
+        matches!(
+            target.id(),
+            BuildTargetId::Test(_) | BuildTargetId::Benchmark(_) | BuildTargetId::Example(_)
+        )
+    })
+}
@@ -14,10 +14,11 @@ use crate::{
 
 /// Contains information about dependency cycles in feature graphs.
 ///
-/// Cargo permits cycles if at least one of the links is dev-only. `Cycles` exposes information
-/// about such dependencies.
+/// A cycle is a set of features, possibly across several packages, that all transitively enable
+/// each other. Cargo permits cycles if at least one of the links is dev-only. Cycles that don't go
+/// through a dev-dependency often indicate a misconfigured optional dependency.
 ///
-/// Constructed through `PackageGraph::cycles`.
+/// Constructed through `FeatureGraph::cycles`.
 pub struct Cycles<'g> {
     feature_graph: FeatureGraph<'g>,
     sccs: &'g Sccs<FeatureIx>,
@@ -51,7 +52,7 @@ impl<'g> Cycles<'g> {
     ///
     /// Within a cycle, nodes are returned in non-dev order: if feature Foo has a dependency on Bar,
     /// and Bar has a dev-dependency on Foo, then Foo is returned before Bar.
-    pub fn all_cycles(&self) -> impl Iterator<Item = Vec<FeatureId<'g>>> + 'g {
+    pub fn all_cycles(&self) -> impl DoubleEndedIterator<Item = Vec<FeatureId<'g>>> + 'g {
         let dep_graph = self.feature_graph.dep_graph();
         let package_graph = self.feature_graph.package_graph;
         self.sccs.multi_sccs().map(move |class| {
@@ -22,6 +22,9 @@ use std::{fmt, slice, vec};
 pub struct FeatureList<'g> {
     package: PackageMetadata<'g>,
     labels: SortedSet<FeatureLabel<'g>>,
+    // If present, this has the same length as labels, with each entry corresponding to the label
+    // at the same index.
+    provenance: Option<Box<[FeatureProvenance]>>,
 }
 
 impl<'g> FeatureList<'g> {
@@ -33,6 +36,26 @@ impl<'g> FeatureList<'g> {
         Self {
             package,
             labels: labels.into_iter().collect(),
+            provenance: None,
+        }
+    }
+
+    /// Creates a new `FeatureList` from a package and an iterator over feature labels, along with
+    /// how each feature came to be enabled.
+    ///
+    /// If a label is repeated, the provenance that sorts first is used.
+    pub fn with_provenance(
+        package: PackageMetadata<'g>,
+        labels: impl IntoIterator<Item = (FeatureLabel<'g>, FeatureProvenance)>,
+    ) -> Self {
+        let mut labels: Vec<_> = labels.into_iter().collect();
+        labels.sort_unstable();
+        labels.dedup_by_key(|(label, _)| *label);
+        let (labels, provenance): (Vec<_>, Vec<_>) = labels.into_iter().unzip();
+        Self {
+            package,
+            labels: SortedSet::new(labels),
+            provenance: Some(provenance.into_boxed_slice()),
         }
     }
 
@@ -73,6 +96,34 @@ impl<'g> FeatureList<'g> {
         self.labels.as_slice()
     }
 
+    /// Returns how this feature label came to be enabled, if it's in this list and provenance
+    /// information is available.
+    ///
+    /// Provenance information is available for lists returned by
+    /// [`FeatureSet::packages_with_provenance`](crate::graph::feature::FeatureSet::packages_with_provenance).
+    pub fn provenance(&self, label: FeatureLabel<'_>) -> Option<FeatureProvenance> {
+        let provenance = self.provenance.as_ref()?;
+        let idx = self.labels.as_slice().binary_search(&label).ok()?;
+        Some(provenance[idx])
+    }
+
+    /// Returns an iterator over feature labels and how each one came to be enabled, if provenance
+    /// information is available.
+    ///
+    /// The iterator is guaranteed to be sorted and unique by label.
+    pub fn labels_with_provenance(
+        &self,
+    ) -> Option<impl Iterator<Item = (FeatureLabel<'g>, FeatureProvenance)> + '_> {
+        let provenance = self.provenance.as_ref()?;
+        Some(
+            self.labels
+                .as_slice()
+                .iter()
+                .copied()
+                .zip(provenance.iter().copied()),
+        )
+    }
+
     /// Returns an iterator containing all named features.
     ///
     /// The iterator is guaranteed to be sorted and unique.
@@ -102,7 +153,19 @@ impl<'g> FeatureList<'g> {
 
     /// Returns a pretty-printer over the list of feature labels.
     pub fn display_features<'a>(&'a self) -> DisplayFeatures<'g, 'a> {
-        DisplayFeatures(self.labels())
+        DisplayFeatures {
+            labels: self.labels(),
+            provenance: None,
+        }
+    }
+
+    /// Returns a pretty-printer over the list of feature labels, with each label followed by its
+    /// provenance in parentheses if provenance information is available.
+    pub fn display_features_with_provenance<'a>(&'a self) -> DisplayFeatures<'g, 'a> {
+        DisplayFeatures {
+            labels: self.labels(),
+            provenance: self.provenance.as_deref(),
+        }
     }
 
     /// Returns a vector of feature labels.
@@ -183,17 +246,53 @@ impl<'g, 'a> Iterator for Iter<'g, 'a> {
     }
 }
 
+/// How a feature in a [`FeatureList`] came to be enabled.
+///
+/// Returned by `FeatureList::provenance` for lists produced by
+/// `FeatureSet::packages_with_provenance`. If more than one of these applies to a feature, the
+/// first one listed here is used.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum FeatureProvenance {
+    /// The feature is enabled by default: it's either the base feature, or the package's `default`
+    /// feature depends on it.
+    Default,
+
+    /// The feature was explicitly requested, or is enabled by another feature of the same package
+    /// that was.
+    Explicit,
+
+    /// The feature was enabled by another package, or by another feature of the same package that
+    /// was.
+    Dependency,
+}
+
+impl fmt::Display for FeatureProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeatureProvenance::Default => write!(f, "default"),
+            FeatureProvenance::Explicit => write!(f, "explicit"),
+            FeatureProvenance::Dependency => write!(f, "dependency"),
+        }
+    }
+}
+
 /// A pretty-printer for a list of features.
 ///
-/// Returned by `FeatureList::display_filters`.
+/// Returned by `FeatureList::display_features` and `FeatureList::display_features_with_provenance`.
 #[derive(Clone, Copy, Debug)]
-pub struct DisplayFeatures<'g, 'a>(&'a [FeatureLabel<'g>]);
+pub struct DisplayFeatures<'g, 'a> {
+    labels: &'a [FeatureLabel<'g>],
+    provenance: Option<&'a [FeatureProvenance]>,
+}
 
 impl<'g, 'a> fmt::Display for DisplayFeatures<'g, 'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let len = self.0.len();
-        for (idx, label) in self.0.iter().enumerate() {
+        let len = self.labels.len();
+        for (idx, label) in self.labels.iter().enumerate() {
             write!(f, "{}", label)?;
+            if let Some(provenance) = self.provenance {
+                write!(f, " ({})", provenance[idx])?;
+            }
             if idx < len - 1 {
                 write!(f, ", ")?;
             }
@@ -22,7 +22,7 @@ use petgraph::{
     prelude::*,
     visit::{EdgeFiltered, IntoNodeReferences},
 };
-use std::{collections::HashMap, fmt, iter, iter::FromIterator};
+use std::{collections::HashMap, fmt, iter, iter::FromIterator, sync::Arc};
 
 // Some general notes about feature graphs:
 //
@@ -62,7 +62,7 @@ impl PackageGraph {
 
     pub(super) fn get_feature_graph(&self) -> &FeatureGraphImpl {
         self.feature_graph
-            .get_or_init(|| FeatureGraphImpl::new(self))
+            .get_or_init(|| Arc::new(FeatureGraphImpl::new(self)))
     }
 }
 
@@ -191,9 +191,37 @@ impl<'g> FeatureGraph<'g> {
         Ok(self.dep_graph().contains_edge(a_ix, b_ix))
     }
 
-    /// Returns information about dependency cycles.
+    /// Returns true if `feature_a` directly depends on `feature_b` through a dependency of the
+    /// given kind.
     ///
-    /// For more information, see the documentation for `Cycles`.
+    /// Only links across packages correspond to dependencies, so this returns false if both
+    /// features are in the same package. For links across packages, this returns true if the
+    /// [`ConditionalLink`] between them is [present](PlatformStatus::is_present) for this kind.
+    ///
+    /// This returns false if `feature_a` is the same as `feature_b`.
+    pub fn directly_depends_on_kind<'a>(
+        &self,
+        feature_a: impl Into<FeatureId<'a>>,
+        feature_b: impl Into<FeatureId<'a>>,
+        kind: DependencyKind,
+    ) -> Result<bool, Error> {
+        let feature_a = feature_a.into();
+        let feature_b = feature_b.into();
+        let a_ix = self.feature_ix(feature_a)?;
+        let b_ix = self.feature_ix(feature_b)?;
+        let edge_ix = match self.dep_graph().find_edge(a_ix, b_ix) {
+            Some(edge_ix) => edge_ix,
+            None => return Ok(false),
+        };
+        Ok(self
+            .edge_to_conditional_link(a_ix, b_ix, edge_ix, None)
+            .map_or(false, |(link, _)| link.status_for_kind(kind).is_present()))
+    }
+
+    /// Returns information about dependency cycles between features, across all packages.
+    ///
+    /// Each cycle is a strongly connected component of the feature graph with two or more
+    /// features. For more information, see the documentation for `Cycles`.
     pub fn cycles(&self) -> Cycles<'g> {
         Cycles::new(*self)
     }
@@ -308,7 +336,7 @@ impl<'g> FeatureGraph<'g> {
         has_path_connecting(self.dep_graph(), a_ix, b_ix, None)
     }
 
-    fn feature_ix_depends_on_no_conditional(
+    pub(super) fn feature_ix_depends_on_no_conditional(
         &self,
         a_ix: NodeIndex<FeatureIx>,
         b_ix: NodeIndex<FeatureIx>,
@@ -809,6 +837,22 @@ impl<'g> ConditionalLink<'g> {
         self.inner.dev_only()
     }
 
+    /// Returns true if this is a weak link, i.e. one specified as `foo?/b` in the `[features]`
+    /// section.
+    ///
+    /// A weak link does not activate the optional dependency `foo` by itself: `foo/b` is only
+    /// enabled if `foo` is activated through some other means. If a named feature specifies both
+    /// a weak and a non-weak form (e.g. `a = ["foo?/b", "foo/b"]`), the link is not weak.
+    pub fn is_weak(&self) -> bool {
+        matches!(
+            self.graph.dep_graph()[self.edge_ix],
+            FeatureEdge::NamedFeatureWithSlash {
+                weak_index: Some(_),
+                ..
+            }
+        )
+    }
+
     /// Returns the `PackageLink` from which this `ConditionalLink` was derived.
     pub fn package_link(&self) -> PackageLink<'g> {
         self.graph
@@ -820,8 +864,7 @@ impl<'g> ConditionalLink<'g> {
     // Helper methods
     // ---
 
-    #[allow(dead_code)]
-    pub(super) fn edge_ix(&self) -> EdgeIndex<FeatureIx> {
+    pub(in crate::graph) fn edge_ix(&self) -> EdgeIndex<FeatureIx> {
         self.edge_ix
     }
 
@@ -15,12 +15,15 @@ mod graph_impl;
 mod proptest_helpers;
 mod query;
 mod resolve;
+mod sample;
+mod trace;
 mod weak;
 
 pub(self) use build::*;
 pub use cycles::*;
-pub use feature_list::FeatureList;
+pub use feature_list::{FeatureList, FeatureProvenance};
 pub use graph_impl::*;
 pub use query::*;
 pub use resolve::*;
+pub use trace::*;
 pub use weak::*;
@@ -5,7 +5,8 @@ use crate::{
     debug_ignore::DebugIgnore,
     graph::{
         feature::{
-            ConditionalLink, FeatureGraph, FeatureId, FeatureLabel, FeatureMetadata, FeatureSet,
+            ConditionalLink, FeatureDepths, FeatureGraph, FeatureId, FeatureLabel, FeatureMetadata,
+            FeatureSet,
         },
         query_core::QueryParams,
         DependencyDirection, FeatureGraphSpec, FeatureIx, PackageIx, PackageMetadata,
@@ -180,6 +181,7 @@ pub fn feature_id_filter<'g: 'a, 'a>(
 pub struct FeatureQuery<'g> {
     pub(super) graph: DebugIgnore<FeatureGraph<'g>>,
     pub(in crate::graph) params: QueryParams<FeatureGraphSpec>,
+    pub(super) max_depth: Option<usize>,
 }
 
 assert_covariant!(FeatureQuery);
@@ -225,6 +227,7 @@ impl<'g> FeatureGraph<'g> {
         Ok(FeatureQuery {
             graph: DebugIgnore(*self),
             params: QueryParams::Forward(self.feature_ixs(feature_ids)?),
+            max_depth: None,
         })
     }
 
@@ -239,9 +242,53 @@ impl<'g> FeatureGraph<'g> {
         Ok(FeatureQuery {
             graph: DebugIgnore(*self),
             params: QueryParams::Reverse(self.feature_ixs(feature_ids)?),
+            max_depth: None,
         })
     }
 
+    /// Creates a new query that returns dependencies of the given feature IDs in the specified
+    /// direction, following at most `max_depth` links from them.
+    ///
+    /// Returns an error if any feature IDs are unknown.
+    pub fn query_directed_bounded<'a>(
+        &self,
+        feature_ids: impl IntoIterator<Item = impl Into<FeatureId<'a>>>,
+        dep_direction: DependencyDirection,
+        max_depth: usize,
+    ) -> Result<FeatureQuery<'g>, Error> {
+        let mut query = self.query_directed(feature_ids, dep_direction)?;
+        query.max_depth = Some(max_depth);
+        Ok(query)
+    }
+
+    /// Creates a new query that returns dependencies of the given feature IDs that are at most
+    /// `max_depth` links away from them.
+    ///
+    /// Links between features of the same package count towards the depth, so depths in a feature
+    /// graph are generally larger than those in the corresponding package graph. Use
+    /// [`FeatureQuery::resolve_depths`] to find out how far away each feature is.
+    ///
+    /// Returns an error if any feature IDs are unknown.
+    pub fn query_forward_bounded<'a>(
+        &self,
+        feature_ids: impl IntoIterator<Item = impl Into<FeatureId<'a>>>,
+        max_depth: usize,
+    ) -> Result<FeatureQuery<'g>, Error> {
+        self.query_directed_bounded(feature_ids, DependencyDirection::Forward, max_depth)
+    }
+
+    /// Creates a new query that returns reverse dependencies of the given feature IDs that are at
+    /// most `max_depth` links away from them.
+    ///
+    /// Returns an error if any feature IDs are unknown.
+    pub fn query_reverse_bounded<'a>(
+        &self,
+        feature_ids: impl IntoIterator<Item = impl Into<FeatureId<'a>>>,
+        max_depth: usize,
+    ) -> Result<FeatureQuery<'g>, Error> {
+        self.query_directed_bounded(feature_ids, DependencyDirection::Reverse, max_depth)
+    }
+
     pub(in crate::graph) fn query_from_parts(
         &self,
         feature_ixs: SortedSet<NodeIndex<FeatureIx>>,
@@ -254,6 +301,7 @@ impl<'g> FeatureGraph<'g> {
         FeatureQuery {
             graph: DebugIgnore(*self),
             params,
+            max_depth: None,
         }
     }
 }
@@ -269,6 +317,12 @@ impl<'g> FeatureQuery<'g> {
         self.params.direction()
     }
 
+    /// Returns the maximum number of links followed from the initial features, if this query is
+    /// bounded.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
     /// Returns the list of initial features specified in the query.
     ///
     /// The order of features is unspecified.
@@ -329,6 +383,18 @@ impl<'g> FeatureQuery<'g> {
         self.resolve_with(ResolverFn(resolver_fn))
     }
 
+    /// Resolves this query into a set of known feature IDs, along with the minimum number of links
+    /// followed to reach each of them from the initial features.
+    pub fn resolve_depths(self) -> FeatureDepths<'g> {
+        FeatureDepths::new(self)
+    }
+
+    /// Resolves this query into a set of known feature IDs along with their depths, using the
+    /// provided resolver to determine which links are followed.
+    pub fn resolve_depths_with(self, resolver: impl FeatureResolver<'g>) -> FeatureDepths<'g> {
+        FeatureDepths::with_resolver(self, resolver)
+    }
+
     // ---
     // Helper methods
     // ---
@@ -6,19 +6,29 @@ use crate::{
     graph::{
         cargo::{CargoOptions, CargoSet},
         feature::{
-            build::FeatureEdgeReference, ConditionalLink, FeatureEdge, FeatureGraph, FeatureId,
-            FeatureList, FeatureMetadata, FeatureQuery, FeatureResolver,
+            build::FeatureEdgeReference, trace::trace_impl, ConditionalLink, FeatureEdge,
+            FeatureGraph, FeatureId, FeatureList, FeatureMetadata, FeatureNode, FeatureProvenance,
+            FeatureQuery, FeatureResolver, FeatureTrace,
         },
         resolve_core::ResolveCore,
         DependencyDirection, FeatureGraphSpec, FeatureIx, PackageIx, PackageMetadata, PackageSet,
     },
-    petgraph_support::{dfs::BufferedEdgeFilterFn, IxBitSet},
+    petgraph_support::{
+        dfs::{BufferedEdgeFilterFn, SimpleEdgeFilterFn},
+        dot::{DotAttrs, DotFmt, DotVisitor, DotWrite},
+        edge_ref::GraphEdgeRef,
+        IxBitSet,
+    },
     sorted_set::SortedSet,
     Error, PackageId,
 };
 use fixedbitset::FixedBitSet;
 use itertools::Either;
-use petgraph::{graph::NodeIndex, visit::EdgeRef};
+use petgraph::{
+    graph::NodeIndex,
+    visit::{EdgeRef, NodeFiltered, NodeRef},
+};
+use std::{collections::HashMap, fmt, iter};
 
 impl<'g> FeatureGraph<'g> {
     /// Creates a new `FeatureSet` consisting of all members of this feature graph.
@@ -56,6 +66,43 @@ impl<'g> FeatureGraph<'g> {
             ),
         })
     }
+
+    /// Returns the set of features transitively enabled by this feature, across all packages.
+    ///
+    /// This includes the feature itself, the base features of the packages that get built because
+    /// of it, and the features enabled on them. Links that are only followed for dev-dependencies
+    /// aren't followed, since enabling a feature doesn't build them.
+    ///
+    /// Returns an error if the feature ID is unknown.
+    pub fn feature_closure<'a>(
+        &self,
+        feature_id: impl Into<FeatureId<'a>>,
+    ) -> Result<FeatureSet<'g>, Error> {
+        Ok(self
+            .query_forward(iter::once(feature_id.into()))?
+            .resolve_with_fn(|_, link| !link.dev_only()))
+    }
+
+    /// Constructs a representation of the [closure](Self::feature_closure) of this feature, and
+    /// the links between the features in it, in `dot` format.
+    ///
+    /// This is useful for auditing heavy named features.
+    ///
+    /// Returns an error if the feature ID is unknown.
+    pub fn feature_closure_dot<'a, V: FeatureDotVisitor + 'g>(
+        &self,
+        feature_id: impl Into<FeatureId<'a>>,
+        visitor: V,
+    ) -> Result<impl fmt::Display + 'g, Error> {
+        let closure = self.feature_closure(feature_id)?;
+        let node_filtered = NodeFiltered(self.dep_graph(), closure.core.included);
+        // Links between features in the closure that are only followed for dev-dependencies
+        // aren't part of it either, so skip them.
+        Ok(DotFmt::new(
+            node_filtered,
+            VisitorWrap::new(*self, visitor).skip_dev_only(),
+        ))
+    }
 }
 
 /// A set of resolved feature IDs in a feature graph.
@@ -72,6 +119,9 @@ assert_covariant!(FeatureSet);
 
 impl<'g> FeatureSet<'g> {
     pub(super) fn new(query: FeatureQuery<'g>) -> Self {
+        if query.max_depth.is_some() {
+            return Self::with_depths(query).0;
+        }
         let graph = query.graph;
         Self {
             graph,
@@ -79,12 +129,34 @@ impl<'g> FeatureSet<'g> {
         }
     }
 
+    fn with_depths(query: FeatureQuery<'g>) -> (Self, Vec<(NodeIndex<FeatureIx>, usize)>) {
+        let graph = query.graph;
+        let (core, depths) = ResolveCore::with_depths(
+            graph.dep_graph(),
+            query.params,
+            query.max_depth,
+            SimpleEdgeFilterFn(|_| true),
+        );
+        (Self { graph, core }, depths)
+    }
+
     pub(super) fn with_resolver(
         query: FeatureQuery<'g>,
-        mut resolver: impl FeatureResolver<'g>,
+        resolver: impl FeatureResolver<'g>,
     ) -> Self {
+        let track_depths = query.max_depth.is_some();
+        Self::with_resolver_impl(query, resolver, track_depths).0
+    }
+
+    /// If `track_depths` is false, the returned list of depths is empty.
+    fn with_resolver_impl(
+        query: FeatureQuery<'g>,
+        mut resolver: impl FeatureResolver<'g>,
+        track_depths: bool,
+    ) -> (Self, Vec<(NodeIndex<FeatureIx>, usize)>) {
         let graph = query.graph;
         let params = query.params.clone();
+        let max_depth = query.max_depth;
 
         // State used by the callback below.
         let mut buffer_states = graph
@@ -108,16 +180,17 @@ impl<'g> FeatureSet<'g> {
             .into_iter()
         };
 
-        let core = ResolveCore::with_buffered_edge_filter(
-            graph.dep_graph(),
-            params,
-            BufferedEdgeFilterFn(filter_fn),
-        );
+        let filter = BufferedEdgeFilterFn(filter_fn);
+        let (core, depths) = if track_depths {
+            ResolveCore::with_depths(graph.dep_graph(), params, max_depth, filter)
+        } else {
+            let core = ResolveCore::with_buffered_edge_filter(graph.dep_graph(), params, filter);
+            (core, vec![])
+        };
 
-        Self { graph, core }
+        (Self { graph, core }, depths)
     }
 
-    #[allow(dead_code)]
     pub(in crate::graph) fn from_included(
         graph: FeatureGraph<'g>,
         included: impl Into<FixedBitSet>,
@@ -163,6 +236,36 @@ impl<'g> FeatureSet<'g> {
             .any(|feature_ix| self.core.contains(feature_ix)))
     }
 
+    /// Returns a chain of feature dependencies explaining why this feature is in this set.
+    ///
+    /// The chain starts at a feature that no other feature in this set depends on, typically one
+    /// of the initial features the set was resolved from. If there are several possible chains, a
+    /// shortest one is returned.
+    ///
+    /// The chain only consists of links between features in this set. If this set was resolved
+    /// with a filter, such as through a `CargoSet`, a link may be returned even if it was filtered
+    /// out, as long as both of its ends are in this set.
+    ///
+    /// Returns `None` if this feature isn't in this set, and an error if this feature ID was
+    /// unknown.
+    pub fn trace<'a>(
+        &self,
+        feature_id: impl Into<FeatureId<'a>>,
+    ) -> Result<Option<FeatureTrace<'g>>, Error> {
+        let feature_ix = self.graph.feature_ix(feature_id.into())?;
+        if !self.core.contains(feature_ix) {
+            return Ok(None);
+        }
+        let initial_ixs = self.core.roots(
+            self.graph.dep_graph(),
+            self.graph.sccs(),
+            DependencyDirection::Forward,
+        );
+        let trace = trace_impl(self.graph.0, &self.core.included, &initial_ixs, feature_ix)
+            .expect("every feature in a set is reachable from a root");
+        Ok(Some(trace))
+    }
+
     /// Creates a new `FeatureQuery` from this set in the specified direction.
     ///
     /// This is equivalent to constructing a query from all the feature IDs in this set.
@@ -189,7 +292,7 @@ impl<'g> FeatureSet<'g> {
     /// Panics if the package graphs associated with `self` and `other` don't match.
     pub fn union(&self, other: &Self) -> Self {
         assert!(
-            ::std::ptr::eq(self.graph.package_graph, self.graph.package_graph),
+            ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph),
             "package graphs passed into union() match"
         );
         let mut res = self.clone();
@@ -204,7 +307,7 @@ impl<'g> FeatureSet<'g> {
     /// Panics if the package graphs associated with `self` and `other` don't match.
     pub fn intersection(&self, other: &Self) -> Self {
         assert!(
-            ::std::ptr::eq(self.graph.package_graph, self.graph.package_graph),
+            ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph),
             "package graphs passed into intersection() match"
         );
         let mut res = self.clone();
@@ -219,7 +322,7 @@ impl<'g> FeatureSet<'g> {
     /// Panics if the package graphs associated with `self` and `other` don't match.
     pub fn difference(&self, other: &Self) -> Self {
         assert!(
-            ::std::ptr::eq(self.graph.package_graph, self.graph.package_graph),
+            ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph),
             "package graphs passed into difference() match"
         );
         Self {
@@ -236,7 +339,7 @@ impl<'g> FeatureSet<'g> {
     /// Panics if the package graphs associated with `self` and `other` don't match.
     pub fn symmetric_difference(&self, other: &Self) -> Self {
         assert!(
-            ::std::ptr::eq(self.graph.package_graph, self.graph.package_graph),
+            ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph),
             "package graphs passed into symmetric_difference() match"
         );
         let mut res = self.clone();
@@ -244,6 +347,45 @@ impl<'g> FeatureSet<'g> {
         res
     }
 
+    /// Returns true if every feature in `self` is also in `other`.
+    ///
+    /// This is computed on the underlying bitsets, without looking at any feature metadata.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the package graphs associated with `self` and `other` don't match.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        assert!(
+            ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph),
+            "package graphs passed into is_subset() match"
+        );
+        self.core.is_subset(&other.core)
+    }
+
+    /// Returns true if every feature in `other` is also in `self`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the package graphs associated with `self` and `other` don't match.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns true if `self` and `other` have no features in common.
+    ///
+    /// This is computed on the underlying bitsets, without looking at any feature metadata.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the package graphs associated with `self` and `other` don't match.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        assert!(
+            ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph),
+            "package graphs passed into is_disjoint() match"
+        );
+        self.core.is_disjoint(&other.core)
+    }
+
     /// Returns a `PackageSet` on which a filter has been applied.
     ///
     /// Filters out all values for which the callback returns false.
@@ -454,6 +596,49 @@ impl<'g> FeatureSet<'g> {
             })
     }
 
+    /// Iterates over package metadatas and their corresponding features, along with how each
+    /// feature came to be enabled, in topological order in the direction specified.
+    ///
+    /// `initials` is the set of features that was explicitly requested, typically the initials
+    /// this set was resolved from. For each feature in this set:
+    /// * features enabled by default, if the package's default features are in this set, are
+    ///   tagged [`FeatureProvenance::Default`],
+    /// * features in `initials`, or enabled by features of the same package that are, are tagged
+    ///   [`FeatureProvenance::Explicit`], and
+    /// * all other features are tagged [`FeatureProvenance::Dependency`].
+    ///
+    /// ## Cycles
+    ///
+    /// The packages within a dependency cycle will be returned in non-dev order. When the direction
+    /// is forward, if package Foo has a dependency on Bar, and Bar has a cyclic dev-dependency on
+    /// Foo, then Foo is returned before Bar.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `initials` is from a different graph.
+    pub fn packages_with_provenance<'a>(
+        &'a self,
+        initials: &'a FeatureSet<'g>,
+        direction: DependencyDirection,
+    ) -> impl Iterator<Item = FeatureList<'g>> + 'a {
+        assert!(
+            ::std::ptr::eq(self.graph.package_graph, initials.graph.package_graph),
+            "package graphs passed into packages_with_provenance() match"
+        );
+        let package_graph = self.graph.package_graph;
+
+        package_graph
+            .sccs()
+            .node_iter(direction.into())
+            .filter_map(move |package_ix| {
+                let package_id = &package_graph.dep_graph()[package_ix];
+                let package = package_graph
+                    .metadata(package_id)
+                    .expect("valid package ID");
+                self.provenance_for_package_impl(package, initials)
+            })
+    }
+
     /// Returns the set of "root feature" IDs in the specified direction.
     ///
     /// * If direction is Forward, return the set of feature IDs that do not have any dependencies
@@ -524,6 +709,16 @@ impl<'g> FeatureSet<'g> {
             })
     }
 
+    /// Constructs a representation of the features in this set, and the links between them, in
+    /// `dot` format.
+    pub fn display_dot<'a, V: FeatureDotVisitor + 'g>(
+        &'a self,
+        visitor: V,
+    ) -> impl fmt::Display + 'a {
+        let node_filtered = NodeFiltered(self.graph.dep_graph(), &self.core.included);
+        DotFmt::new(node_filtered, VisitorWrap::new(*self.graph, visitor))
+    }
+
     // ---
     // Helper methods
     // ---
@@ -554,6 +749,62 @@ impl<'g> FeatureSet<'g> {
         }
     }
 
+    fn provenance_for_package_impl(
+        &self,
+        package: PackageMetadata<'g>,
+        initials: &FeatureSet<'g>,
+    ) -> Option<FeatureList<'g>> {
+        let graph = self.graph;
+        let dep_graph = graph.dep_graph();
+        let core = &self.core;
+
+        let included: Vec<_> = graph
+            .feature_ixs_for_package_ix(package.package_ix())
+            .filter(|feature_ix| core.contains(*feature_ix))
+            .collect();
+        if included.is_empty() {
+            return None;
+        }
+
+        // Find features of this package that are explicitly requested, or enabled by ones that
+        // are through edges within this package.
+        let mut explicit: Vec<_> = included
+            .iter()
+            .copied()
+            .filter(|feature_ix| initials.core.contains(*feature_ix))
+            .collect();
+        let mut stack = explicit.clone();
+        while let Some(feature_ix) = stack.pop() {
+            for edge in dep_graph.edges(feature_ix) {
+                let target = edge.target();
+                if included.contains(&target) && !explicit.contains(&target) {
+                    explicit.push(target);
+                    stack.push(target);
+                }
+            }
+        }
+
+        let default_ix = graph
+            .feature_ix(package.default_feature_id())
+            .expect("default feature ID is valid");
+        // Features are only enabled by default if default features are enabled at all.
+        let default_included = core.contains(default_ix);
+        let labels = included.iter().map(|&feature_ix| {
+            let label = FeatureId::node_to_feature(package, &dep_graph[feature_ix]);
+            let provenance = if default_included
+                && graph.feature_ix_depends_on_no_conditional(default_ix, feature_ix)
+            {
+                FeatureProvenance::Default
+            } else if explicit.contains(&feature_ix) {
+                FeatureProvenance::Explicit
+            } else {
+                FeatureProvenance::Dependency
+            };
+            (label, provenance)
+        });
+        Some(FeatureList::with_provenance(package, labels))
+    }
+
     /// Returns all the package ixs without topologically sorting them.
     pub(in crate::graph) fn ixs_unordered(
         &self,
@@ -595,6 +846,74 @@ impl<'g> FeatureSet<'g> {
     }
 }
 
+/// A set of resolved feature IDs, along with the minimum number of links followed to reach each of
+/// them from the initial features.
+///
+/// Created by `FeatureQuery::resolve_depths`.
+#[derive(Clone, Debug)]
+pub struct FeatureDepths<'g> {
+    feature_set: FeatureSet<'g>,
+    // In the order features were visited, so depths are nondecreasing.
+    order: Vec<(NodeIndex<FeatureIx>, usize)>,
+    depths: HashMap<NodeIndex<FeatureIx>, usize>,
+}
+
+assert_covariant!(FeatureDepths);
+
+impl<'g> FeatureDepths<'g> {
+    pub(super) fn new(query: FeatureQuery<'g>) -> Self {
+        let (feature_set, order) = FeatureSet::with_depths(query);
+        Self::from_parts(feature_set, order)
+    }
+
+    pub(super) fn with_resolver(
+        query: FeatureQuery<'g>,
+        resolver: impl FeatureResolver<'g>,
+    ) -> Self {
+        let (feature_set, order) = FeatureSet::with_resolver_impl(query, resolver, true);
+        Self::from_parts(feature_set, order)
+    }
+
+    fn from_parts(feature_set: FeatureSet<'g>, order: Vec<(NodeIndex<FeatureIx>, usize)>) -> Self {
+        let depths = order.iter().copied().collect();
+        Self {
+            feature_set,
+            order,
+            depths,
+        }
+    }
+
+    /// Returns the set of resolved feature IDs.
+    pub fn feature_set(&self) -> &FeatureSet<'g> {
+        &self.feature_set
+    }
+
+    /// Converts `self` into the set of resolved feature IDs.
+    pub fn into_feature_set(self) -> FeatureSet<'g> {
+        self.feature_set
+    }
+
+    /// Returns the depth of the given feature ID, or `None` if it wasn't resolved.
+    ///
+    /// Returns an error if the feature ID is unknown.
+    pub fn depth<'a>(&self, feature_id: impl Into<FeatureId<'a>>) -> Result<Option<usize>, Error> {
+        let feature_ix = self.feature_set.graph.feature_ix(feature_id.into())?;
+        Ok(self.depths.get(&feature_ix).copied())
+    }
+
+    /// Iterates over feature metadatas along with their depths, in nondecreasing order of depth.
+    ///
+    /// The order of features at the same depth is unspecified.
+    pub fn features<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (FeatureMetadata<'g>, usize)> + ExactSizeIterator + 'a {
+        let graph = self.feature_set.graph;
+        self.order
+            .iter()
+            .map(move |(feature_ix, depth)| (graph.metadata_for_ix(*feature_ix), *depth))
+    }
+}
+
 impl<'g> PartialEq for FeatureSet<'g> {
     fn eq(&self, other: &Self) -> bool {
         ::std::ptr::eq(self.graph.package_graph, other.graph.package_graph)
@@ -603,3 +922,155 @@ impl<'g> PartialEq for FeatureSet<'g> {
 }
 
 impl<'g> Eq for FeatureSet<'g> {}
+
+/// A visitor used for formatting `dot` graphs of features.
+pub trait FeatureDotVisitor {
+    /// Visits this feature. The implementation may output a label for this feature to the given
+    /// `DotWrite`.
+    fn visit_feature(&self, feature: FeatureMetadata<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result;
+
+    /// Visits the link between these two features. The implementation may output a label for this
+    /// link to the given `DotWrite`.
+    ///
+    /// `conditional_link` is `None` for links within a package, such as from a named feature to
+    /// the base feature.
+    fn visit_link(
+        &self,
+        from: FeatureMetadata<'_>,
+        to: FeatureMetadata<'_>,
+        conditional_link: Option<ConditionalLink<'_>>,
+        f: &mut DotWrite<'_, '_>,
+    ) -> fmt::Result;
+
+    /// Visits this feature. The implementation may output attributes other than the label, such
+    /// as `color` or `style`, to the given `DotAttrs`.
+    ///
+    /// The default implementation outputs no attributes.
+    fn visit_feature_attrs(
+        &self,
+        _feature: FeatureMetadata<'_>,
+        _attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Visits the link between these two features. The implementation may output attributes other
+    /// than the label, such as `color` or `style`, to the given `DotAttrs`.
+    ///
+    /// The default implementation outputs no attributes.
+    fn visit_link_attrs(
+        &self,
+        _from: FeatureMetadata<'_>,
+        _to: FeatureMetadata<'_>,
+        _conditional_link: Option<ConditionalLink<'_>>,
+        _attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Returns the name of the cluster this feature is drawn in, if any. Features in the same
+    /// cluster are drawn together, inside a box labeled with the name.
+    ///
+    /// The default implementation doesn't place features in clusters.
+    fn feature_cluster(&self, _feature: FeatureMetadata<'_>) -> Option<String> {
+        None
+    }
+}
+
+struct VisitorWrap<'g, V> {
+    graph: FeatureGraph<'g>,
+    inner: V,
+    skip_dev_only: bool,
+}
+
+impl<'g, V> VisitorWrap<'g, V> {
+    fn new(graph: FeatureGraph<'g>, inner: V) -> Self {
+        Self {
+            graph,
+            inner,
+            skip_dev_only: false,
+        }
+    }
+
+    /// Skips links that are only followed for dev-dependencies.
+    fn skip_dev_only(mut self) -> Self {
+        self.skip_dev_only = true;
+        self
+    }
+
+    fn metadata<NR>(&self, node: NR) -> FeatureMetadata<'g>
+    where
+        NR: NodeRef<NodeId = NodeIndex<FeatureIx>, Weight = FeatureNode>,
+    {
+        self.graph
+            .metadata_for_node(*node.weight())
+            .expect("visited node should have associated metadata")
+    }
+
+    fn link_parts<ER>(
+        &self,
+        edge: ER,
+    ) -> (
+        FeatureMetadata<'g>,
+        FeatureMetadata<'g>,
+        Option<ConditionalLink<'g>>,
+    )
+    where
+        ER: GraphEdgeRef<'g, FeatureEdge, FeatureIx>,
+    {
+        let edge = edge.into_edge_reference();
+        let dep_graph = self.graph.dep_graph();
+        let metadata = |feature_ix: NodeIndex<FeatureIx>| {
+            self.graph
+                .metadata_for_node(dep_graph[feature_ix])
+                .expect("visited edge should have associated metadata")
+        };
+        let conditional_link = self
+            .graph
+            .edge_to_conditional_link(edge.source(), edge.target(), edge.id(), Some(edge.weight()))
+            .map(|(link, _)| link);
+        (
+            metadata(edge.source()),
+            metadata(edge.target()),
+            conditional_link,
+        )
+    }
+}
+
+impl<'g, V, NR, ER> DotVisitor<NR, ER> for VisitorWrap<'g, V>
+where
+    V: FeatureDotVisitor,
+    NR: NodeRef<NodeId = NodeIndex<FeatureIx>, Weight = FeatureNode>,
+    ER: GraphEdgeRef<'g, FeatureEdge, FeatureIx>,
+{
+    fn visit_node(&self, node: NR, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        self.inner.visit_feature(self.metadata(node), f)
+    }
+
+    fn visit_edge(&self, edge: ER, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        let (from, to, conditional_link) = self.link_parts(edge);
+        self.inner.visit_link(from, to, conditional_link, f)
+    }
+
+    fn visit_node_attrs(&self, node: NR, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        self.inner.visit_feature_attrs(self.metadata(node), attrs)
+    }
+
+    fn visit_edge_attrs(&self, edge: ER, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        let (from, to, conditional_link) = self.link_parts(edge);
+        self.inner
+            .visit_link_attrs(from, to, conditional_link, attrs)
+    }
+
+    fn node_cluster(&self, node: NR) -> Option<String> {
+        self.inner.feature_cluster(self.metadata(node))
+    }
+
+    fn include_edge(&self, edge: ER) -> bool {
+        if !self.skip_dev_only {
+            return true;
+        }
+        let (_, _, conditional_link) = self.link_parts(edge);
+        conditional_link.map_or(true, |link| !link.dev_only())
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::graph::{
+    feature::{FeatureGraph, FeatureSet},
+    SampleRng,
+};
+use fixedbitset::FixedBitSet;
+
+/// ## Seeded sampling
+///
+/// The methods in this section generate feature sets at random, based on a seed. Unlike the
+/// property testing helpers, they don't require any optional features.
+impl<'g> FeatureGraph<'g> {
+    /// Returns `n` feature sets generated at random from this graph, based on `seed`.
+    ///
+    /// Each feature is included in each set with probability 1/2, independently of the others, so
+    /// sets aren't necessarily closed over dependencies. To get the features a Cargo build would
+    /// enable, query forward from the set and resolve the query.
+    ///
+    /// The same seed always produces the same sets for the same graph.
+    pub fn sample_feature_sets(&self, seed: u64, n: usize) -> Vec<FeatureSet<'g>> {
+        let count = self.feature_count();
+        let mut rng = SampleRng::new(seed);
+        (0..n)
+            .map(|_| {
+                let mut included = FixedBitSet::with_capacity(count);
+                for index in 0..count {
+                    included.set(index, rng.next_bool());
+                }
+                FeatureSet::from_included(*self, included)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Explanations for why a feature is included in a feature set.
+
+use crate::graph::{
+    feature::{ConditionalLink, FeatureEdge, FeatureGraph, FeatureId, FeatureLabel},
+    FeatureIx,
+};
+use fixedbitset::FixedBitSet;
+use petgraph::{graph::NodeIndex, visit::EdgeRef, Direction};
+use std::collections::{HashMap, VecDeque};
+
+/// A chain of feature dependencies explaining why a feature is included in a `FeatureSet`.
+///
+/// Returned by [`FeatureSet::trace`](crate::graph::feature::FeatureSet::trace).
+#[derive(Clone, Debug)]
+pub struct FeatureTrace<'g> {
+    initial: FeatureId<'g>,
+    steps: Vec<FeatureTraceStep<'g>>,
+}
+
+impl<'g> FeatureTrace<'g> {
+    /// Returns the feature at the start of the chain.
+    ///
+    /// No other feature in the set depends on this feature, so it must be one of the features the
+    /// set was resolved from.
+    pub fn initial(&self) -> FeatureId<'g> {
+        self.initial
+    }
+
+    /// Returns the feature that was traced, at the end of the chain.
+    pub fn feature(&self) -> FeatureId<'g> {
+        self.steps.last().map_or(self.initial, |step| step.to())
+    }
+
+    /// Returns the steps leading from the initial feature to the traced feature, in order.
+    ///
+    /// This is empty if the traced feature is itself the initial feature.
+    pub fn steps(&self) -> &[FeatureTraceStep<'g>] {
+        &self.steps
+    }
+}
+
+/// A single step in a [`FeatureTrace`]: a feature enabling another feature.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum FeatureTraceStep<'g> {
+    /// A feature enabling the base feature of its own package. Every feature does this.
+    Base {
+        /// The feature that enables the base feature.
+        from: FeatureId<'g>,
+        /// The base feature.
+        to: FeatureId<'g>,
+    },
+
+    /// A named feature enabling another named feature in the same package, through the
+    /// `[features]` section:
+    ///
+    /// ```toml
+    /// [features]
+    /// a = ["b"]
+    /// ```
+    NamedFeature {
+        /// The feature that enables the other feature.
+        from: FeatureId<'g>,
+        /// The feature that is enabled.
+        to: FeatureId<'g>,
+    },
+
+    /// A feature enabling an optional dependency of the same package, e.g. through `dep:foo` or
+    /// `foo/feature` in the `[features]` section.
+    OptionalDependency(ConditionalLink<'g>),
+
+    /// A feature in one package enabling a feature in a dependency. This includes features listed in
+    /// the `[dependencies]` section, and `foo/feature` in the `[features]` section.
+    Dependency(ConditionalLink<'g>),
+}
+
+impl<'g> FeatureTraceStep<'g> {
+    /// Returns the feature that enables the next feature.
+    pub fn from(&self) -> FeatureId<'g> {
+        match self {
+            FeatureTraceStep::Base { from, .. } | FeatureTraceStep::NamedFeature { from, .. } => {
+                *from
+            }
+            FeatureTraceStep::OptionalDependency(link) | FeatureTraceStep::Dependency(link) => {
+                link.from().feature_id()
+            }
+        }
+    }
+
+    /// Returns the feature that is enabled.
+    pub fn to(&self) -> FeatureId<'g> {
+        match self {
+            FeatureTraceStep::Base { to, .. } | FeatureTraceStep::NamedFeature { to, .. } => *to,
+            FeatureTraceStep::OptionalDependency(link) | FeatureTraceStep::Dependency(link) => {
+                link.to().feature_id()
+            }
+        }
+    }
+
+    /// Returns the conditional link for this step, if it has one.
+    pub fn conditional_link(&self) -> Option<ConditionalLink<'g>> {
+        match self {
+            FeatureTraceStep::Base { .. } | FeatureTraceStep::NamedFeature { .. } => None,
+            FeatureTraceStep::OptionalDependency(link) | FeatureTraceStep::Dependency(link) => {
+                Some(*link)
+            }
+        }
+    }
+}
+
+/// Finds a shortest chain from one of `initial_ixs` to `feature_ix`, following edges between
+/// features in `included`.
+pub(super) fn trace_impl<'g>(
+    graph: FeatureGraph<'g>,
+    included: &FixedBitSet,
+    initial_ixs: &[NodeIndex<FeatureIx>],
+    feature_ix: NodeIndex<FeatureIx>,
+) -> Option<FeatureTrace<'g>> {
+    let dep_graph = graph.dep_graph();
+    let package_graph = graph.package_graph();
+    let feature_id = |ix: NodeIndex<FeatureIx>| FeatureId::from_node(package_graph, &dep_graph[ix]);
+
+    let mut initials = FixedBitSet::with_capacity(dep_graph.node_count());
+    initials.extend(initial_ixs.iter().map(|ix| ix.index()));
+
+    // Walk the graph backwards from the traced feature. `parents` maps each feature visited to the
+    // edge through which it enables the feature it was reached from.
+    let mut parents = HashMap::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(feature_ix);
+    parents.insert(feature_ix, None);
+
+    while let Some(ix) = queue.pop_front() {
+        if initials.contains(ix.index()) {
+            let mut steps = vec![];
+            let mut current = ix;
+            while let Some(edge_ix) = parents[&current] {
+                let (source_ix, target_ix) =
+                    dep_graph.edge_endpoints(edge_ix).expect("valid edge index");
+                let step = match &dep_graph[edge_ix] {
+                    FeatureEdge::FeatureToBase => FeatureTraceStep::Base {
+                        from: feature_id(source_ix),
+                        to: feature_id(target_ix),
+                    },
+                    FeatureEdge::NamedFeature => FeatureTraceStep::NamedFeature {
+                        from: feature_id(source_ix),
+                        to: feature_id(target_ix),
+                    },
+                    edge => {
+                        let (link, _) = graph
+                            .edge_to_conditional_link(source_ix, target_ix, edge_ix, Some(edge))
+                            .expect("edge is a conditional link");
+                        match link.to().label() {
+                            FeatureLabel::OptionalDependency(_)
+                                if link.from().package_id() == link.to().package_id() =>
+                            {
+                                FeatureTraceStep::OptionalDependency(link)
+                            }
+                            _ => FeatureTraceStep::Dependency(link),
+                        }
+                    }
+                };
+                steps.push(step);
+                current = target_ix;
+            }
+
+            return Some(FeatureTrace {
+                initial: feature_id(ix),
+                steps,
+            });
+        }
+
+        for edge in dep_graph.edges_directed(ix, Direction::Incoming) {
+            let source_ix = edge.source();
+            if included.contains(source_ix.index()) && !parents.contains_key(&source_ix) {
+                parents.insert(source_ix, Some(edge.id()));
+                queue.push_back(source_ix);
+            }
+        }
+    }
+
+    None
+}
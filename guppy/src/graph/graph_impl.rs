@@ -1,12 +1,19 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+#[cfg(feature = "name-patterns")]
+use crate::graph::{Layers, LayersConfig, NamePattern};
 use crate::{
+    errors::ParseWarnings,
     graph::{
+        cargo::CargoResolverCache,
         cargo_version_matches,
         feature::{FeatureGraphImpl, FeatureId, FeatureLabel, FeatureNode},
-        BuildTarget, BuildTargetId, BuildTargetImpl, BuildTargetKind, Cycles, DependencyDirection,
-        OwnedBuildTargetId, PackageIx, PackageQuery, PackageSet,
+        intern::MemoryEstimate,
+        ArtifactReq, BuildTarget, BuildTargetId, BuildTargetImpl, BuildTargetKind, Cycles,
+        DependencyDeclaration, DependencyDeclarationImpl, DependencyDirection, DevOnlyPackages,
+        LocalPackageInput, MsrvViolation, OwnedBuildTargetId, PackageIx, PackageQuery, PackageSet,
+        PublishViolation, RequirementConflict,
     },
     petgraph_support::{scc::Sccs, topo::TopoWithCycles, IxBitSet},
     platform::{EnabledTernary, PlatformSpec, PlatformStatus, PlatformStatusImpl},
@@ -23,11 +30,17 @@ use petgraph::{
     visit::EdgeFiltered,
 };
 use semver::{Version, VersionReq};
+use serde::Deserialize;
 use smallvec::SmallVec;
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
-    fmt, iter,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    iter,
     iter::FromIterator,
+    mem,
+    sync::Arc,
 };
 
 /// A graph of packages and dependencies between them, parsed from metadata returned by `cargo
@@ -36,17 +49,20 @@ use std::{
 /// For examples on how to use `PackageGraph`, see
 /// [the `examples` directory](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy/examples)
 /// in this crate.
+///
+/// Cloning a `PackageGraph` is cheap: the underlying data, as well as any caches computed so far,
+/// are shared between clones.
 #[derive(Clone, Debug)]
 pub struct PackageGraph {
-    // Source of truth data.
-    pub(super) dep_graph: Graph<PackageId, PackageLinkImpl, Directed, PackageIx>,
+    // Source of truth data. This is immutable once constructed, and is shared between clones. Any
+    // APIs that mutate the graph must use Arc::make_mut to copy it on write.
+    pub(super) dep_graph: Arc<Graph<PackageId, PackageLinkImpl, Directed, PackageIx>>,
     // The strongly connected components of the graph, computed on demand.
-    pub(super) sccs: OnceCell<Sccs<PackageIx>>,
+    pub(super) sccs: OnceCell<Arc<Sccs<PackageIx>>>,
     // Feature graph, computed on demand.
-    pub(super) feature_graph: OnceCell<FeatureGraphImpl>,
-    // XXX Should this be in an Arc for quick cloning? Not clear how this would work with node
-    // filters though.
-    pub(super) data: PackageGraphData,
+    pub(super) feature_graph: OnceCell<Arc<FeatureGraphImpl>>,
+    // Per-package data, shared between clones similar to dep_graph.
+    pub(super) data: Arc<PackageGraphData>,
 }
 
 /// Per-package data for a PackageGraph instance.
@@ -54,6 +70,11 @@ pub struct PackageGraph {
 pub(super) struct PackageGraphData {
     pub(super) packages: HashMap<PackageId, PackageMetadataImpl>,
     pub(super) workspace: WorkspaceImpl,
+    pub(super) parse_warnings: ParseWarnings,
+    // Inputs for workspace and path packages, used to find changed packages while applying
+    // metadata updates. Packages from registries and Git repositories can't change without their
+    // package IDs changing, so their inputs aren't retained.
+    pub(super) local_inputs: HashMap<PackageId, LocalPackageInput>,
 }
 
 impl PackageGraph {
@@ -64,7 +85,7 @@ impl PackageGraph {
 
     /// Parses the given `Metadata` and constructs a `PackageGraph` from it.
     pub fn from_metadata(metadata: CargoMetadata) -> Result<Self, Error> {
-        Self::build(metadata.0)
+        Self::build(metadata)
     }
 
     /// Constructs a package graph from the given JSON output of `cargo metadata`.
@@ -82,6 +103,14 @@ impl PackageGraph {
         Self::from_metadata(metadata)
     }
 
+    /// Returns warnings about data in the `cargo metadata` output that `guppy` didn't fully
+    /// understand while constructing this graph.
+    ///
+    /// For more, see [`CargoMetadata::parse_warnings`].
+    pub fn parse_warnings(&self) -> &ParseWarnings {
+        &self.data.parse_warnings
+    }
+
     /// Verifies internal invariants on this graph. Not part of the documented API.
     #[doc(hidden)]
     pub fn verify(&self) -> Result<(), Error> {
@@ -272,6 +301,50 @@ impl PackageGraph {
         self.dep_graph.edge_count()
     }
 
+    /// Returns a rough estimate of the heap memory used by this graph, in bytes.
+    ///
+    /// Package IDs and feature names are interned while the graph is built, and strings shared
+    /// between several parts of the graph are only counted once. Data computed on demand, such as
+    /// the feature graph, and any inputs retained for
+    /// [`apply_metadata_update`](Self::apply_metadata_update) are not included.
+    ///
+    /// This is meant for comparisons and verification, not for precise accounting.
+    pub fn memory_usage_estimate(&self) -> usize {
+        let mut estimate = MemoryEstimate::new();
+
+        let dep_graph = &self.dep_graph;
+        estimate.add_slots::<petgraph::graph::Node<PackageId, PackageIx>>(dep_graph.node_count());
+        estimate
+            .add_slots::<petgraph::graph::Edge<PackageLinkImpl, PackageIx>>(dep_graph.edge_count());
+        for package_id in dep_graph.node_weights() {
+            estimate.add_shared(package_id.interned_repr());
+        }
+        for link in dep_graph.edge_weights() {
+            link.estimate_memory(&mut estimate);
+        }
+
+        let packages = &self.data.packages;
+        estimate.add_slots::<(PackageId, PackageMetadataImpl)>(packages.capacity());
+        for (package_id, inner) in packages {
+            estimate.add_shared(package_id.interned_repr());
+            inner.estimate_memory(&mut estimate);
+        }
+
+        let workspace = &self.data.workspace;
+        for (path, package_id) in &workspace.members_by_path {
+            estimate.add_slots::<(Utf8PathBuf, PackageId)>(1);
+            estimate.add_str(path.as_str());
+            estimate.add_shared(package_id.interned_repr());
+        }
+        for (name, package_id) in &workspace.members_by_name {
+            estimate.add_slots::<(Box<str>, PackageId)>(1);
+            estimate.add_str(name);
+            estimate.add_shared(package_id.interned_repr());
+        }
+
+        estimate.finish()
+    }
+
     /// Creates a new cache for `depends_on` queries.
     ///
     /// The cache is optional but can speed up some queries.
@@ -279,6 +352,15 @@ impl PackageGraph {
         DependsCache::new(self)
     }
 
+    /// Creates a new cache for Cargo build simulations.
+    ///
+    /// The cache is optional but can speed up repeated simulations through
+    /// [`CargoSet::new`](crate::graph::cargo::CargoSet::new). For more, see the documentation for
+    /// [`CargoResolverCache`].
+    pub fn new_cargo_resolver_cache(&self) -> CargoResolverCache<'_> {
+        CargoResolverCache::new(self)
+    }
+
     /// Returns true if `package_a` depends (directly or indirectly) on `package_b`.
     ///
     /// In other words, this returns true if `package_b` is a (possibly transitive) dependency of
@@ -307,6 +389,32 @@ impl PackageGraph {
         Ok(self.dep_graph.contains_edge(a_ix, b_ix))
     }
 
+    /// Returns true if `package_a` directly depends on `package_b` through a dependency of the
+    /// given kind.
+    ///
+    /// For example, with `DependencyKind::Normal`, this returns true if `package_b` is listed in
+    /// the `[dependencies]` section of `package_a` (for any platform), but false if it is only a
+    /// dev-dependency.
+    ///
+    /// This returns false if `package_a` is the same as `package_b`.
+    pub fn directly_depends_on_kind(
+        &self,
+        package_a: &PackageId,
+        package_b: &PackageId,
+        kind: DependencyKind,
+    ) -> Result<bool, Error> {
+        let a_ix = self.package_ix(package_a)?;
+        let b_ix = self.package_ix(package_b)?;
+        Ok(self
+            .dep_graph
+            .find_edge(a_ix, b_ix)
+            .map_or(false, |edge_ix| {
+                self.edge_ix_to_link(edge_ix)
+                    .req_for_kind(kind)
+                    .is_present()
+            }))
+    }
+
     /// Returns information about dependency cycles in this graph.
     ///
     /// For more information, see the documentation for `Cycles`.
@@ -314,6 +422,81 @@ impl PackageGraph {
         Cycles::new(self)
     }
 
+    /// Returns the strongly connected components (SCCs) of this graph, in the direction specified.
+    ///
+    /// Each package is in exactly one SCC. Packages that aren't part of a dependency cycle form an
+    /// SCC by themselves, while packages in a cycle (which Cargo only permits through
+    /// dev-dependencies) are grouped together.
+    ///
+    /// SCCs are returned in the topological order of the condensation graph, i.e. the graph formed
+    /// by collapsing each SCC into a single node:
+    /// * With `DependencyDirection::Forward`, an SCC is returned before the SCCs it depends on.
+    /// * With `DependencyDirection::Reverse`, an SCC is returned after the SCCs it depends on. This
+    ///   is the order in which packages can be built.
+    ///
+    /// For cycles of two or more packages only, see [`Cycles::all_cycles`].
+    pub fn strongly_connected_components(
+        &self,
+        direction: DependencyDirection,
+    ) -> impl ExactSizeIterator<Item = PackageSet<'_>> + '_ {
+        let package_count = self.package_count();
+        self.sccs().scc_iter(direction.into()).map(move |scc| {
+            let mut included = FixedBitSet::with_capacity(package_count);
+            included.extend(scc.iter().map(|ix| ix.index()));
+            PackageSet::from_included(self, included)
+        })
+    }
+
+    /// Returns information about packages that are only required by tests, benchmarks and
+    /// examples.
+    ///
+    /// For more information, see the documentation for `DevOnlyPackages`.
+    pub fn dev_only_packages(&self) -> DevOnlyPackages {
+        DevOnlyPackages::new(self)
+    }
+
+    /// Returns dependencies that would cause `cargo publish` to fail, because a publishable
+    /// package depends on a package that can't be published to the same registries.
+    ///
+    /// For more information, see the documentation for `PublishViolation`.
+    pub fn publish_violations(&self) -> impl Iterator<Item = PublishViolation<'_>> + '_ {
+        self.packages()
+            .flat_map(|package| package.direct_links())
+            .filter_map(PublishViolation::new)
+    }
+
+    /// Groups workspace members into layers, according to the given configuration.
+    ///
+    /// Returns an error if a name pattern is invalid or doesn't match any workspace members, if a
+    /// workspace member is in more than one layer, or if a layer allows an unknown layer.
+    ///
+    /// For more information, see the documentation for `Layers`.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    pub fn layers(&self, config: &LayersConfig) -> Result<Layers<'_>, Error> {
+        Layers::new(self, config)
+    }
+
+    /// Returns third-party packages whose `rust-version` is newer than the given minimum supported
+    /// Rust version (MSRV) for the workspace, in the order returned by `packages()`.
+    ///
+    /// Pre-release and build metadata in `workspace_msrv`, such as `-nightly`, are ignored.
+    ///
+    /// For more information, see the documentation for `MsrvViolation`.
+    pub fn msrv_violations<'g>(
+        &'g self,
+        workspace_msrv: &Version,
+    ) -> impl Iterator<Item = MsrvViolation<'g>> + 'g {
+        let workspace_msrv = Version::new(
+            workspace_msrv.major,
+            workspace_msrv.minor,
+            workspace_msrv.patch,
+        );
+        self.packages()
+            .filter_map(move |package| MsrvViolation::new(package, &workspace_msrv))
+    }
+
     // For more traversals, see query.rs.
 
     // ---
@@ -344,15 +527,15 @@ impl PackageGraph {
     pub(super) fn sccs(&self) -> &Sccs<PackageIx> {
         self.sccs.get_or_init(|| {
             let edge_filtered =
-                EdgeFiltered::from_fn(&self.dep_graph, |edge| !edge.weight().dev_only());
+                EdgeFiltered::from_fn(&*self.dep_graph, |edge| !edge.weight().dev_only());
             // Sort the entire graph without dev-only edges -- a correct graph would be cycle-free
             // but we don't currently do a consistency check for this so handle cycles.
             // TODO: should we check at construction time? or bubble up a warning somehow?
             let topo = TopoWithCycles::new(&edge_filtered);
 
-            Sccs::new(&self.dep_graph, |scc| {
+            Arc::new(Sccs::new(&*self.dep_graph, |scc| {
                 topo.sort_nodes(scc);
-            })
+            }))
         })
     }
 
@@ -453,7 +636,7 @@ impl<'g> DependsCache<'g> {
     pub fn new(package_graph: &'g PackageGraph) -> Self {
         Self {
             package_graph,
-            dfs_space: DfsSpace::new(&package_graph.dep_graph),
+            dfs_space: DfsSpace::new(&*package_graph.dep_graph),
         }
     }
 
@@ -610,6 +793,20 @@ impl<'g> Workspace<'g> {
             .collect()
     }
 
+    /// Returns an iterator over workspace members whose names match the given pattern, sorted by
+    /// name.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    pub fn members_matching<'a>(
+        &'a self,
+        pattern: &'a NamePattern,
+    ) -> impl Iterator<Item = PackageMetadata<'g>> + 'a {
+        self.iter_by_name()
+            .filter(move |(name, _)| pattern.matches(name))
+            .map(|(_, package)| package)
+    }
+
     /// Returns the freeform metadata table for this workspace.
     ///
     /// This is the same as the `workspace.metadata` section of `Cargo.toml`. This section is
@@ -617,6 +814,56 @@ impl<'g> Workspace<'g> {
     pub fn metadata_table(&self) -> &'g JsonValue {
         &self.inner.metadata_table
     }
+
+    /// Deserializes the section with the given key from the `package.metadata` table of every
+    /// workspace member, in the same order as [`iter`](Self::iter).
+    ///
+    /// Members that don't have this section are skipped. See
+    /// [`PackageMetadata::metadata_section`] for more.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use guppy::CargoMetadata;
+    /// use serde::Deserialize;
+    ///
+    /// // Read `[package.metadata.docs.rs]` from every workspace member that has it.
+    /// #[derive(Deserialize)]
+    /// #[serde(rename_all = "kebab-case")]
+    /// struct DocsConfig {
+    ///     rs: DocsRsConfig,
+    /// }
+    ///
+    /// #[derive(Deserialize)]
+    /// #[serde(rename_all = "kebab-case")]
+    /// struct DocsRsConfig {
+    ///     #[serde(default)]
+    ///     all_features: bool,
+    /// }
+    ///
+    /// let metadata = CargoMetadata::parse_json(include_str!(
+    ///     "../../../fixtures/guppy/metadata_guppy_44b62fa.json"
+    /// )).unwrap();
+    /// let graph = metadata.build_graph().unwrap();
+    /// for (package, config) in graph.workspace().metadata_section::<DocsConfig>("docs") {
+    ///     let config = config.expect("section is valid");
+    ///     assert!(config.rs.all_features, "{} is documented with all features", package.name());
+    /// }
+    /// ```
+    pub fn metadata_section<T>(
+        &self,
+        key: &'g str,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, Result<T, Error>)> + 'g
+    where
+        T: Deserialize<'g>,
+    {
+        self.iter().filter_map(move |package| {
+            package
+                .metadata_section(key)
+                .transpose()
+                .map(|section| (package, section))
+        })
+    }
 }
 
 #[cfg(feature = "rayon1")]
@@ -843,6 +1090,16 @@ impl<'g> PackageMetadata<'g> {
         PackageSource::new(&self.inner.source)
     }
 
+    /// Returns the checksum of this package's `.crate` file, as recorded in `Cargo.lock`.
+    ///
+    /// `cargo metadata` doesn't include checksums, so this is `None` unless they've been added
+    /// to the graph through `PackageGraph::add_lockfile_checksums` or
+    /// `PackageGraph::read_lockfile_checksums`, or the graph was built by `LockfileBuilder`. Only
+    /// packages from registries have checksums.
+    pub fn checksum(&self) -> Option<&'g str> {
+        self.inner.checksum.as_deref()
+    }
+
     /// Returns true if this package is in the workspace.
     ///
     /// For more detailed information, use `source()`.
@@ -917,6 +1174,29 @@ impl<'g> PackageMetadata<'g> {
         &self.inner.metadata_table
     }
 
+    /// Deserializes the section with the given key from the `package.metadata` table.
+    ///
+    /// For example, `metadata_section("foo")` deserializes the `[package.metadata.foo]` section
+    /// of `Cargo.toml`. Returns `None` if the section isn't present, or
+    /// `Error::MetadataSectionError` if it couldn't be deserialized into `T`.
+    pub fn metadata_section<T>(&self, key: &str) -> Result<Option<T>, Error>
+    where
+        T: Deserialize<'g>,
+    {
+        match self.metadata_table().get(key) {
+            Some(section) => {
+                T::deserialize(section)
+                    .map(Some)
+                    .map_err(|err| Error::MetadataSectionError {
+                        package_id: self.id().clone(),
+                        key: key.to_owned(),
+                        err,
+                    })
+            }
+            None => Ok(None),
+        }
+    }
+
     /// Returns the name of a native library this package links to, if specified.
     ///
     /// This is the same as the `links` field of `Cargo.toml`. See [The `links` Manifest
@@ -949,10 +1229,23 @@ impl<'g> PackageMetadata<'g> {
     /// This is the same as the `rust-version` field of `Cargo.toml`. For more, see [the
     /// `rust-version` field](https://doc.rust-lang.org/cargo/reference/manifest.html#the-rust-version-field)
     /// in the Cargo reference.
+    ///
+    /// The field is returned as a version requirement, e.g. `rust-version = "1.60"` is returned as
+    /// `^1.60`. To get the version it names, use
+    /// [`minimum_rust_version`](Self::minimum_rust_version).
     pub fn rust_version(&self) -> Option<&'g VersionReq> {
         self.inner.rust_version.as_ref()
     }
 
+    /// Returns the minimal Rust compiler version, which should be able to compile the package, if
+    /// specified, as a version rather than a requirement.
+    ///
+    /// The `rust-version` field may leave out the minor and patch components, in which case they
+    /// are filled in with zeroes. For example, `rust-version = "1.60"` is returned as `1.60.0`.
+    pub fn minimum_rust_version(&self) -> Option<&'g Version> {
+        self.inner.minimum_rust_version.as_ref()
+    }
+
     /// Returns all the build targets for this package.
     ///
     /// For more, see [Cargo
@@ -1177,14 +1470,76 @@ pub(crate) struct PackageMetadataImpl {
     pub(super) publish: PackagePublishImpl,
     pub(super) default_run: Option<Box<str>>,
     pub(super) rust_version: Option<VersionReq>,
-    pub(super) named_features: IndexMap<Box<str>, SmallVec<[NamedFeatureDep; 4]>>,
-    pub(super) optional_deps: IndexSet<Box<str>>,
+    pub(super) minimum_rust_version: Option<Version>,
+    pub(super) named_features: IndexMap<Arc<str>, SmallVec<[NamedFeatureDep; 4]>>,
+    pub(super) optional_deps: IndexSet<Arc<str>>,
 
     // Other information.
     pub(super) package_ix: NodeIndex<PackageIx>,
     pub(super) source: PackageSourceImpl,
     pub(super) build_targets: BTreeMap<OwnedBuildTargetId, BuildTargetImpl>,
     pub(super) has_default_feature: bool,
+    // Added from Cargo.lock, since cargo metadata doesn't include checksums.
+    pub(super) checksum: Option<Box<str>>,
+}
+
+impl PackageMetadataImpl {
+    fn estimate_memory(&self, estimate: &mut MemoryEstimate) {
+        estimate.add_str(&self.name);
+        estimate.add_str(self.manifest_path.as_str());
+        for s in self
+            .authors
+            .iter()
+            .chain(&self.categories)
+            .chain(&self.keywords)
+        {
+            estimate.add_slots::<String>(1);
+            estimate.add_str(s);
+        }
+        let optional_strs = [
+            &self.description,
+            &self.license,
+            &self.repository,
+            &self.homepage,
+            &self.documentation,
+            &self.links,
+            &self.default_run,
+            &self.checksum,
+        ];
+        for s in optional_strs.iter().copied().flatten() {
+            estimate.add_str(s);
+        }
+
+        estimate.add_slots::<(Arc<str>, SmallVec<[NamedFeatureDep; 4]>)>(self.named_features.len());
+        for (feature, deps) in &self.named_features {
+            estimate.add_shared(feature);
+            if deps.spilled() {
+                estimate.add_slots::<NamedFeatureDep>(deps.capacity());
+            }
+            for dep in deps {
+                match dep {
+                    NamedFeatureDep::NamedFeature(name)
+                    | NamedFeatureDep::OptionalDependency(name) => estimate.add_shared(name),
+                    NamedFeatureDep::DependencyNamedFeature {
+                        dep_name, feature, ..
+                    } => {
+                        estimate.add_shared(dep_name);
+                        estimate.add_shared(feature);
+                    }
+                }
+            }
+        }
+        estimate.add_slots::<Arc<str>>(self.optional_deps.len());
+        for dep in &self.optional_deps {
+            estimate.add_shared(dep);
+        }
+
+        estimate.add_slots::<(OwnedBuildTargetId, BuildTargetImpl)>(self.build_targets.len());
+        for target in self.build_targets.values() {
+            estimate.add_str(target.path.as_str());
+            estimate.add_str(&target.edition);
+        }
+    }
 }
 
 /// The source of a package.
@@ -1605,7 +1960,36 @@ pub(super) enum PackageSourceImpl {
 /// Locations that a package can be published to.
 ///
 /// Returned by [`PackageMetadata::publish`].
-#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+///
+/// # Ordering
+///
+/// `PackagePublish` instances form a lattice, ordered by the registries a package can be published
+/// to: `a <= b` if `b` can be published to every registry `a` can be published to.
+/// `Registries(&[])`, corresponding to `publish = false`, is the bottom element, and
+/// `Unrestricted` is the top element.
+///
+/// Lists of registries are compared as sets, so two lists with the same registries in a different
+/// order are equal, and two lists with different registries may be incomparable. For that reason,
+/// `PackagePublish` implements `PartialOrd` but not `Ord`.
+///
+/// ```
+/// use guppy::graph::PackagePublish;
+///
+/// let registries = &["my-registry".to_owned(), PackagePublish::CRATES_IO.to_owned()];
+/// let crates_io = &[PackagePublish::CRATES_IO.to_owned()];
+/// let my_registry = &["my-registry".to_owned()];
+///
+/// assert!(PackagePublish::Registries(&[]) < PackagePublish::Registries(crates_io));
+/// assert!(PackagePublish::Registries(crates_io) < PackagePublish::Registries(registries));
+/// assert!(PackagePublish::Registries(registries) < PackagePublish::Unrestricted);
+///
+/// // crates.io and my-registry are incomparable.
+/// assert_eq!(
+///     PackagePublish::Registries(crates_io).partial_cmp(&PackagePublish::Registries(my_registry)),
+///     None,
+/// );
+/// ```
+#[derive(Copy, Clone, Debug)]
 #[non_exhaustive]
 pub enum PackagePublish<'g> {
     /// Publication of this package is unrestricted.
@@ -1619,10 +2003,49 @@ pub enum PackagePublish<'g> {
     Registries(&'g [String]),
 }
 
-// TODO: implement PartialOrd/Ord for these as well using lattice rules
-
 assert_covariant!(PackagePublish);
 
+impl<'g> PartialEq for PackagePublish<'g> {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<'g> Eq for PackagePublish<'g> {}
+
+impl<'g> Hash for PackagePublish<'g> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        if let PackagePublish::Registries(registries) = self {
+            // Hash registries as a set, to be consistent with PartialEq.
+            let registries: BTreeSet<_> = registries.iter().collect();
+            registries.hash(state);
+        }
+    }
+}
+
+impl<'g> PartialOrd for PackagePublish<'g> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (PackagePublish::Unrestricted, PackagePublish::Unrestricted) => Some(Ordering::Equal),
+            (PackagePublish::Unrestricted, PackagePublish::Registries(_)) => {
+                Some(Ordering::Greater)
+            }
+            (PackagePublish::Registries(_), PackagePublish::Unrestricted) => Some(Ordering::Less),
+            (PackagePublish::Registries(a), PackagePublish::Registries(b)) => {
+                let a_in_b = a.iter().all(|registry| b.contains(registry));
+                let b_in_a = b.iter().all(|registry| a.contains(registry));
+                match (a_in_b, b_in_a) {
+                    (true, true) => Some(Ordering::Equal),
+                    (true, false) => Some(Ordering::Less),
+                    (false, true) => Some(Ordering::Greater),
+                    (false, false) => None,
+                }
+            }
+        }
+    }
+}
+
 impl<'g> PackagePublish<'g> {
     pub(super) fn new(inner: &'g PackagePublishImpl) -> Self {
         match inner {
@@ -1793,6 +2216,30 @@ impl<'g> PackageLink<'g> {
         &self.inner.version_req
     }
 
+    /// Returns the index URL of the registry specified for this dependency, if any.
+    ///
+    /// This is `None` for dependencies on `crates.io`, and for path and Git dependencies that don't
+    /// specify a `registry` key. Registry names are resolved to index URLs by Cargo.
+    ///
+    /// As with [`version_req`](Self::version_req), if a dependency is requested multiple times,
+    /// any of the specified registries may be returned.
+    pub fn registry(&self) -> Option<&'g str> {
+        self.inner.registry.as_deref()
+    }
+
+    /// Returns the source this dependency was declared with in `Cargo.toml`, as a `registry+` or
+    /// `git+` URL.
+    ///
+    /// This is `None` for path dependencies. If a `[patch]` section replaces this dependency, the
+    /// declared source is different from the source of [`to`](Self::to). The declared source can
+    /// be parsed with [`ExternalSource::new`](ExternalSource::new).
+    ///
+    /// As with [`version_req`](Self::version_req), if a dependency is requested multiple times,
+    /// any of the specified sources may be returned.
+    pub fn declared_source(&self) -> Option<&'g str> {
+        self.inner.declared_source.as_deref()
+    }
+
     /// Returns details about this dependency from the `[dependencies]` section.
     pub fn normal(&self) -> DependencyReq<'g> {
         DependencyReq {
@@ -1830,6 +2277,49 @@ impl<'g> PackageLink<'g> {
         self.inner.dev_only()
     }
 
+    /// Returns every declaration of this dependency in the `Cargo.toml` of the `from` package, in
+    /// the order they're listed in `cargo metadata` output.
+    ///
+    /// A dependency may be declared several times, in different sections and for different
+    /// platforms. The other methods on `PackageLink` combine all of these declarations.
+    pub fn declarations(&self) -> impl ExactSizeIterator<Item = DependencyDeclaration<'g>> + 'g {
+        self.inner
+            .declarations
+            .iter()
+            .map(DependencyDeclaration::new)
+    }
+
+    /// Returns differences in version requirements, default features and features between the
+    /// declarations of this dependency.
+    ///
+    /// Returns an empty list if this dependency is declared only once, or if all its declarations
+    /// agree.
+    ///
+    /// For more information, see the documentation for `RequirementConflict`.
+    pub fn requirement_conflicts(&self) -> Vec<RequirementConflict<'g>> {
+        self.requirement_conflicts_with(|_| true)
+    }
+
+    /// Returns differences between the declarations of this dependency that match the given
+    /// filter.
+    ///
+    /// For example, to ignore `[dev-dependencies]`, which often enable extra features for tests:
+    ///
+    /// ```
+    /// use guppy::{graph::PackageLink, DependencyKind};
+    ///
+    /// fn conflicts_without_dev<'g>(link: PackageLink<'g>) -> usize {
+    ///     link.requirement_conflicts_with(|decl| decl.kind() != DependencyKind::Development)
+    ///         .len()
+    /// }
+    /// ```
+    pub fn requirement_conflicts_with(
+        &self,
+        filter: impl FnMut(&DependencyDeclaration<'g>) -> bool,
+    ) -> Vec<RequirementConflict<'g>> {
+        RequirementConflict::find(*self, self.declarations().filter(filter).collect())
+    }
+
     // ---
     // Helper methods
     // ---
@@ -1865,9 +2355,12 @@ pub(crate) struct PackageLinkImpl {
     pub(super) dep_name: String,
     pub(super) resolved_name: String,
     pub(super) version_req: VersionReq,
+    pub(super) registry: Option<String>,
+    pub(super) declared_source: Option<Box<str>>,
     pub(super) normal: DependencyReqImpl,
     pub(super) build: DependencyReqImpl,
     pub(super) dev: DependencyReqImpl,
+    pub(super) declarations: Box<[DependencyDeclarationImpl]>,
 }
 
 impl PackageLinkImpl {
@@ -1875,6 +2368,36 @@ impl PackageLinkImpl {
     fn dev_only(&self) -> bool {
         self.normal.enabled().is_never() && self.build.enabled().is_never()
     }
+
+    fn estimate_memory(&self, estimate: &mut MemoryEstimate) {
+        estimate.add_str(&self.dep_name);
+        estimate.add_str(&self.resolved_name);
+        if let Some(registry) = &self.registry {
+            estimate.add_str(registry);
+        }
+        if let Some(declared_source) = &self.declared_source {
+            estimate.add_str(declared_source);
+        }
+        for req in [&self.normal, &self.build, &self.dev] {
+            for req_impl in [&req.required, &req.optional] {
+                estimate
+                    .add_slots::<(Arc<str>, PlatformStatusImpl)>(req_impl.feature_targets.len());
+                for feature in req_impl.feature_targets.keys() {
+                    estimate.add_shared(feature);
+                }
+            }
+        }
+        estimate.add_slots::<DependencyDeclarationImpl>(self.declarations.len());
+        for decl in self.declarations.iter() {
+            if let Some(target) = &decl.target {
+                estimate.add_str(target);
+            }
+            estimate.add_slots::<Arc<str>>(decl.features.len());
+            for feature in decl.features.iter() {
+                estimate.add_shared(feature);
+            }
+        }
+    }
 }
 
 /// Information about a specific kind of dependency (normal, build or dev) from a package to another
@@ -1939,6 +2462,13 @@ impl<'g> DependencyReq<'g> {
     pub fn feature_status(&self, feature: &str) -> EnabledStatus<'g> {
         self.inner.feature_status(feature)
     }
+
+    /// Returns the artifacts requested by this dependency, if it is an artifact dependency.
+    ///
+    /// Artifact dependencies are an unstable Cargo feature: see [`ArtifactReq`] for more.
+    pub fn artifact(&self) -> Option<&'g ArtifactReq> {
+        self.inner.artifact.as_ref()
+    }
 }
 
 /// Whether a dependency or feature is required, optional, or disabled.
@@ -2035,35 +2565,35 @@ impl<'g> EnabledStatus<'g> {
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub(super) enum NamedFeatureDep {
-    NamedFeature(Box<str>),
-    OptionalDependency(Box<str>),
+    NamedFeature(Arc<str>),
+    OptionalDependency(Arc<str>),
     DependencyNamedFeature {
-        dep_name: Box<str>,
-        feature: Box<str>,
+        dep_name: Arc<str>,
+        feature: Arc<str>,
         weak: bool,
     },
 }
 
 impl NamedFeatureDep {
     #[inline]
-    pub(super) fn named_feature(feature_name: impl Into<String>) -> Self {
-        Self::NamedFeature(feature_name.into().into_boxed_str())
+    pub(super) fn named_feature(feature_name: impl Into<Arc<str>>) -> Self {
+        Self::NamedFeature(feature_name.into())
     }
 
     #[inline]
-    pub(super) fn optional_dependency(dep_name: impl Into<String>) -> Self {
-        Self::OptionalDependency(dep_name.into().into_boxed_str())
+    pub(super) fn optional_dependency(dep_name: impl Into<Arc<str>>) -> Self {
+        Self::OptionalDependency(dep_name.into())
     }
 
     #[inline]
     pub(super) fn dep_named_feature(
-        dep_name: impl Into<String>,
-        feature: impl Into<String>,
+        dep_name: impl Into<Arc<str>>,
+        feature: impl Into<Arc<str>>,
         weak: bool,
     ) -> Self {
         Self::DependencyNamedFeature {
-            dep_name: dep_name.into().into_boxed_str(),
-            feature: feature.into().into_boxed_str(),
+            dep_name: dep_name.into(),
+            feature: feature.into(),
             weak,
         }
     }
@@ -2096,6 +2626,7 @@ impl fmt::Display for NamedFeatureDep {
 pub(super) struct DependencyReqImpl {
     pub(super) required: DepRequiredOrOptional,
     pub(super) optional: DepRequiredOrOptional,
+    pub(super) artifact: Option<ArtifactReq>,
 }
 
 impl DependencyReqImpl {
@@ -2144,11 +2675,94 @@ pub(super) struct DepRequiredOrOptional {
     pub(super) build_if: PlatformStatusImpl,
     pub(super) default_features_if: PlatformStatusImpl,
     pub(super) no_default_features_if: PlatformStatusImpl,
-    pub(super) feature_targets: BTreeMap<String, PlatformStatusImpl>,
+    pub(super) feature_targets: BTreeMap<Arc<str>, PlatformStatusImpl>,
 }
 
 impl DepRequiredOrOptional {
     pub(super) fn all_features(&self) -> impl Iterator<Item = &str> {
-        self.feature_targets.keys().map(|s| s.as_str())
+        self.feature_targets.keys().map(|s| &**s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn clone_shares_data() {
+        let graph = PackageGraph::from_json(JsonFixture::metadata1().json()).expect("valid JSON");
+        // Compute caches before cloning.
+        graph.sccs();
+        graph.feature_graph();
+
+        let cloned = graph.clone();
+        assert!(
+            Arc::ptr_eq(&graph.dep_graph, &cloned.dep_graph),
+            "dependency graph is shared"
+        );
+        assert!(Arc::ptr_eq(&graph.data, &cloned.data), "data is shared");
+        assert!(
+            Arc::ptr_eq(
+                graph.sccs.get().expect("sccs computed"),
+                cloned.sccs.get().expect("sccs cloned"),
+            ),
+            "sccs are shared"
+        );
+        assert!(
+            Arc::ptr_eq(
+                graph.feature_graph.get().expect("feature graph computed"),
+                cloned.feature_graph.get().expect("feature graph cloned"),
+            ),
+            "feature graph is shared"
+        );
+        cloned.verify().expect("cloned graph is valid");
+    }
+
+    #[test]
+    fn strings_are_interned() {
+        let graph = PackageGraph::from_json(JsonFixture::metadata1().json()).expect("valid JSON");
+
+        // Package IDs in the dependency graph are shared with the package map.
+        for package_id in graph.data.packages.keys() {
+            let package_ix = graph.package_ix(package_id).expect("valid package ID");
+            assert!(
+                Arc::ptr_eq(
+                    package_id.interned_repr(),
+                    graph.dep_graph[package_ix].interned_repr()
+                ),
+                "package ID {} is shared",
+                package_id
+            );
+        }
+
+        // Feature names are shared across packages.
+        let std_features: Vec<_> = graph
+            .data
+            .packages
+            .values()
+            .filter_map(|inner| inner.named_features.get_key_value("std"))
+            .map(|(feature, _)| feature)
+            .collect();
+        assert!(
+            std_features.len() > 1,
+            "multiple packages have a std feature"
+        );
+        assert!(
+            std_features
+                .iter()
+                .all(|feature| Arc::ptr_eq(feature, std_features[0])),
+            "std feature names are shared"
+        );
+
+        // Clones share all their data, so they have the same estimate.
+        let estimate = graph.memory_usage_estimate();
+        let id_bytes: usize = graph.package_ids().map(|id| id.repr().len()).sum();
+        assert!(estimate > id_bytes, "estimate includes package IDs");
+        assert_eq!(
+            estimate,
+            graph.clone().memory_usage_estimate(),
+            "estimate is deterministic"
+        );
     }
 }
@@ -0,0 +1,89 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! String interning for package graphs.
+//!
+//! Package IDs and feature names are heavily duplicated across a graph: every package has a
+//! `default` or `std` feature, and package IDs are referred to from several indexes. Interning
+//! them while building the graph means each distinct string is stored once.
+//!
+//! The same goes for the `cfg()` expressions platform-specific dependencies are declared under:
+//! a handful of them, like `cfg(unix)` and `cfg(windows)`, make up most of those found in practice.
+
+use std::{collections::HashSet, mem, sync::Arc};
+use target_spec::{TargetSpec, TargetSpecSet};
+
+/// Deduplicates strings while a `PackageGraph` is being built.
+#[derive(Debug, Default)]
+pub(super) struct StringInterner {
+    strings: HashSet<Arc<str>>,
+    target_specs: TargetSpecSet,
+}
+
+impl StringInterner {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a shared copy of `s`, allocating it if it hasn't been seen before.
+    pub(super) fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = s.into();
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Returns a target spec parsed from `spec_or_triple`, sharing the parsed form with earlier
+    /// specs that have the same string.
+    pub(super) fn target_spec(
+        &mut self,
+        spec_or_triple: &str,
+    ) -> Result<TargetSpec, target_spec::Error> {
+        let index = match self.target_specs.index_of(spec_or_triple) {
+            Some(index) => index,
+            None => self.target_specs.add(spec_or_triple.to_owned())?,
+        };
+        Ok(self
+            .target_specs
+            .get(index)
+            .expect("index returned by the spec set is valid")
+            .clone())
+    }
+}
+
+/// Accumulates an estimate of heap memory use, counting each shared string only once.
+#[derive(Debug, Default)]
+pub(super) struct MemoryEstimate {
+    seen: HashSet<*const u8>,
+    bytes: usize,
+}
+
+impl MemoryEstimate {
+    pub(super) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a shared string, if it hasn't been counted already.
+    pub(super) fn add_shared(&mut self, s: &Arc<str>) {
+        if self.seen.insert(s.as_ptr()) {
+            // Arc allocations also store the strong and weak reference counts.
+            self.bytes += s.len() + 2 * mem::size_of::<usize>();
+        }
+    }
+
+    /// Adds a string that owns its allocation.
+    pub(super) fn add_str(&mut self, s: &str) {
+        self.bytes += s.len();
+    }
+
+    /// Adds `count` slots of type `T`, e.g. the backing storage of a collection.
+    pub(super) fn add_slots<T>(&mut self, count: usize) {
+        self.bytes += count * mem::size_of::<T>();
+    }
+
+    pub(super) fn finish(self) -> usize {
+        self.bytes
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Code for enforcing dependency rules between layers of workspace members.
+
+use crate::{
+    graph::{DependencyDirection, NamePattern, PackageGraph, PackageLink, PackageSet},
+    Error, PackageId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+/// Configuration for [`Layers`], typically read from a TOML file.
+///
+/// Each layer consists of a set of workspace members, and lists the other layers it is allowed to
+/// depend on. A layer may always depend on itself, and workspace members that aren't in any layer
+/// aren't checked.
+///
+/// An example:
+///
+/// ```toml
+/// [layers.core]
+/// members = ["core-types", "core-crypto"]
+///
+/// [layers.storage]
+/// # Members are name patterns: exact names, globs or /regexes/.
+/// members = ["storage-*"]
+/// allow = ["core"]
+///
+/// [layers.api]
+/// members = ["api-service"]
+/// allow = ["core", "storage"]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LayersConfig {
+    /// Whether to check dev-only dependencies as well.
+    ///
+    /// By default, links that are only dev-dependencies aren't checked, since they don't affect
+    /// production builds.
+    #[serde(default)]
+    pub include_dev: bool,
+
+    /// The layers in this configuration, keyed by name.
+    #[serde(default)]
+    pub layers: BTreeMap<String, LayerConfig>,
+}
+
+/// Configuration for a single layer in a [`LayersConfig`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LayerConfig {
+    /// Name patterns for the workspace members in this layer. For the syntax, see
+    /// [`NamePattern::new`].
+    pub members: Vec<String>,
+
+    /// The names of other layers that members of this layer may depend on.
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+/// Workspace members grouped into layers, along with rules for which layers may depend on each
+/// other.
+///
+/// Layers are used to enforce an architecture within a workspace, for example that low-level
+/// `core` packages don't depend on higher-level `api` packages. Only direct dependencies between
+/// workspace members are checked: if `api` may depend on `storage`, and `storage` on `core`, `api`
+/// must still list `core` to depend on it directly.
+///
+/// Constructed through `PackageGraph::layers`.
+#[derive(Clone, Debug)]
+pub struct Layers<'g> {
+    include_dev: bool,
+    workspace: PackageSet<'g>,
+    layers: Vec<LayerImpl<'g>>,
+    layer_ixs: HashMap<&'g PackageId, usize>,
+}
+
+#[derive(Clone, Debug)]
+struct LayerImpl<'g> {
+    name: String,
+    members: PackageSet<'g>,
+    allow: BTreeSet<usize>,
+}
+
+impl<'g> Layers<'g> {
+    pub(super) fn new(
+        package_graph: &'g PackageGraph,
+        config: &LayersConfig,
+    ) -> Result<Self, Error> {
+        let name_ixs: HashMap<&str, usize> = config
+            .layers
+            .keys()
+            .enumerate()
+            .map(|(idx, name)| (name.as_str(), idx))
+            .collect();
+
+        let mut layers = Vec::with_capacity(config.layers.len());
+        let mut layer_ixs = HashMap::new();
+        for (idx, (name, layer)) in config.layers.iter().enumerate() {
+            let patterns = layer
+                .members
+                .iter()
+                .map(NamePattern::new)
+                .collect::<Result<Vec<_>, _>>()?;
+            let members = package_graph.resolve_named(&patterns)?;
+            for package in members.packages(DependencyDirection::Forward) {
+                if let Some(other_idx) = layer_ixs.insert(package.id(), idx) {
+                    return Err(Error::InvalidLayersConfig(format!(
+                        "workspace member {} is in both layers {} and {}",
+                        package.name(),
+                        config
+                            .layers
+                            .keys()
+                            .nth(other_idx)
+                            .expect("valid layer index"),
+                        name,
+                    )));
+                }
+            }
+
+            let allow = layer
+                .allow
+                .iter()
+                .map(|allowed| {
+                    name_ixs.get(allowed.as_str()).copied().ok_or_else(|| {
+                        Error::InvalidLayersConfig(format!(
+                            "layer {} allows unknown layer {}",
+                            name, allowed
+                        ))
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+
+            layers.push(LayerImpl {
+                name: name.clone(),
+                members,
+                allow,
+            });
+        }
+
+        Ok(Self {
+            include_dev: config.include_dev,
+            workspace: package_graph.resolve_workspace(),
+            layers,
+            layer_ixs,
+        })
+    }
+
+    /// Returns the names of the layers, in sorted order.
+    pub fn layer_names(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        self.layers.iter().map(|layer| layer.name.as_str())
+    }
+
+    /// Returns the workspace members in the given layer, or `None` if there's no such layer.
+    pub fn members(&self, layer_name: &str) -> Option<&PackageSet<'g>> {
+        self.layers
+            .iter()
+            .find(|layer| layer.name == layer_name)
+            .map(|layer| &layer.members)
+    }
+
+    /// Returns the name of the layer this package is in, or `None` if it isn't in any layer.
+    pub fn layer_of(&self, package_id: &PackageId) -> Option<&str> {
+        self.layer_ixs
+            .get(package_id)
+            .map(|idx| self.layers[*idx].name.as_str())
+    }
+
+    /// Returns the workspace members that aren't in any layer.
+    ///
+    /// Dependencies from and to these packages aren't checked.
+    pub fn unassigned(&self) -> PackageSet<'g> {
+        self.layers
+            .iter()
+            .fold(self.workspace.clone(), |unassigned, layer| {
+                unassigned.difference(&layer.members)
+            })
+    }
+
+    /// Returns the dependencies between layers that aren't allowed, sorted by the names of the
+    /// packages on either side.
+    pub fn violations(&self) -> Vec<LayerViolation<'g, '_>> {
+        let mut violations = vec![];
+        for (from_idx, layer) in self.layers.iter().enumerate() {
+            for package in layer.members.packages(DependencyDirection::Forward) {
+                for link in package.direct_links() {
+                    if !self.include_dev && link.dev_only() {
+                        continue;
+                    }
+                    let to_idx = match self.layer_ixs.get(link.to().id()) {
+                        Some(to_idx) => *to_idx,
+                        None => continue,
+                    };
+                    if to_idx != from_idx && !layer.allow.contains(&to_idx) {
+                        violations.push(LayerViolation {
+                            link,
+                            from_layer: &layer.name,
+                            to_layer: &self.layers[to_idx].name,
+                        });
+                    }
+                }
+            }
+        }
+        violations.sort_by(|a, b| {
+            (a.link.from().name(), a.link.to().name())
+                .cmp(&(b.link.from().name(), b.link.to().name()))
+        });
+        violations
+    }
+}
+
+/// A dependency from a member of one layer on a member of another layer that it isn't allowed to
+/// depend on.
+///
+/// Returned by [`Layers::violations`].
+#[derive(Copy, Clone, Debug)]
+pub struct LayerViolation<'g, 'layers> {
+    link: PackageLink<'g>,
+    from_layer: &'layers str,
+    to_layer: &'layers str,
+}
+
+impl<'g, 'layers> LayerViolation<'g, 'layers> {
+    /// Returns the link that violates the rules.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the name of the layer the dependent package is in.
+    pub fn from_layer(&self) -> &'layers str {
+        self.from_layer
+    }
+
+    /// Returns the name of the layer the dependency is in.
+    pub fn to_layer(&self) -> &'layers str {
+        self.to_layer
+    }
+}
+
+impl<'g, 'layers> fmt::Display for LayerViolation<'g, 'layers> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (layer {}) depends on {} (layer {}), which isn't allowed",
+            self.link.from().name(),
+            self.from_layer,
+            self.link.to().name(),
+            self.to_layer,
+        )
+    }
+}
@@ -10,32 +10,68 @@ use crate::PackageId;
 use petgraph::prelude::*;
 use std::fmt;
 
+mod artifacts;
 mod build;
 mod build_targets;
 pub mod cargo;
+#[cfg(any(feature = "lockfile", feature = "sbom"))]
+mod checksums;
 mod cycles;
+mod dev_only;
 pub mod feature;
 mod graph_impl;
+mod intern;
+#[cfg(feature = "name-patterns")]
+mod layers;
+mod msrv;
+#[cfg(feature = "name-patterns")]
+mod name_pattern;
+#[cfg(feature = "persist")]
+mod persist;
+mod pkgid_spec;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
+mod publish;
 mod query;
 mod query_core;
+mod requirements;
 mod resolve;
 mod resolve_core;
+mod sample;
+#[cfg(feature = "sbom")]
+pub mod sbom;
 #[cfg(feature = "summaries")]
 pub mod summaries;
+mod update;
 
-pub use crate::petgraph_support::dot::DotWrite;
+pub use crate::petgraph_support::dot::{DotAttrs, DotWrite};
+pub(crate) use artifacts::ArtifactDeps;
+pub use artifacts::{ArtifactKind, ArtifactReq, ArtifactTarget};
 pub use build_targets::*;
+#[cfg(feature = "lockfile")]
+pub use checksums::ChecksumMismatch;
 pub use cycles::*;
+pub use dev_only::*;
 pub use graph_impl::*;
+#[cfg(feature = "name-patterns")]
+pub use layers::*;
+pub use msrv::*;
+#[cfg(feature = "name-patterns")]
+pub use name_pattern::NamePattern;
 use once_cell::sync::Lazy;
 use petgraph::graph::IndexType;
+pub use pkgid_spec::PackageIdSpec;
 #[cfg(feature = "proptest1")]
 pub use proptest_helpers::*;
+pub use publish::*;
 pub use query::*;
+pub(crate) use requirements::DependencyDeclarationImpl;
+pub use requirements::{DependencyDeclaration, RequirementConflict, RequirementConflictKind};
 pub use resolve::*;
+pub(crate) use sample::SampleRng;
 use semver::{Version, VersionReq};
+pub(crate) use update::LocalPackageInput;
+pub use update::MetadataUpdate;
 
 /// The direction in which to follow dependencies.
 ///
@@ -145,7 +181,7 @@ impl GraphSpec for FeatureGraphSpec {
 // A requirement of "*" filters out pre-release versions with the semver crate,
 // but cargo accepts them.
 // See https://github.com/steveklabnik/semver/issues/98.
-fn cargo_version_matches(req: &VersionReq, version: &Version) -> bool {
+pub(crate) fn cargo_version_matches(req: &VersionReq, version: &Version) -> bool {
     static MAJOR_WILDCARD: Lazy<VersionReq> = Lazy::new(|| VersionReq::parse("*").unwrap());
 
     req == &*MAJOR_WILDCARD || req.matches(version)
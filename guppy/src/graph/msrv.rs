@@ -0,0 +1,45 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Code for finding dependencies that require a newer Rust version than the workspace supports.
+
+use crate::graph::PackageMetadata;
+use semver::Version;
+
+/// A third-party package that declares a `rust-version` newer than the minimum supported Rust
+/// version (MSRV) of the workspace.
+///
+/// Building the workspace with its MSRV will fail if such a package is built. Workspace members
+/// aren't checked, since their `rust-version` is usually what defines the MSRV in the first place.
+///
+/// Constructed through `PackageGraph::msrv_violations`.
+#[derive(Copy, Clone, Debug)]
+pub struct MsrvViolation<'g> {
+    package: PackageMetadata<'g>,
+    rust_version: &'g Version,
+}
+
+impl<'g> MsrvViolation<'g> {
+    pub(super) fn new(package: PackageMetadata<'g>, workspace_msrv: &Version) -> Option<Self> {
+        if package.in_workspace() {
+            return None;
+        }
+        match package.minimum_rust_version() {
+            Some(rust_version) if rust_version > workspace_msrv => Some(Self {
+                package,
+                rust_version,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the package that requires a newer Rust version.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the Rust version required by this package.
+    pub fn rust_version(&self) -> &'g Version {
+        self.rust_version
+    }
+}
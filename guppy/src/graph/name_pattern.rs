@@ -0,0 +1,150 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{PackageGraph, PackageIx},
+    Error,
+};
+use petgraph::graph::NodeIndex;
+use regex::Regex;
+use std::fmt;
+
+/// A pattern that matches package names.
+///
+/// A `NamePattern` is one of:
+/// * an exact name, such as `guppy`
+/// * a glob pattern, such as `storage-*`, using the same syntax as Cargo's `--package` option
+/// * a regular expression, such as `libra-(types|crypto)`, that must match the entire name
+///
+/// Name patterns are used to select workspace members, through methods like
+/// [`PackageGraph::query_named`](crate::graph::PackageGraph::query_named) and
+/// [`PackageGraph::resolve_named`](crate::graph::PackageGraph::resolve_named).
+#[derive(Clone, Debug)]
+pub struct NamePattern {
+    // The string form of this pattern, as accepted by `NamePattern::new`.
+    spec: String,
+    kind: NamePatternKind,
+}
+
+#[derive(Clone, Debug)]
+enum NamePatternKind {
+    Exact,
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl NamePattern {
+    /// Parses a name pattern from a string.
+    ///
+    /// * A string surrounded by slashes, such as `/storage-.*/`, is a regular expression. (Package
+    ///   names can't contain slashes, so this is unambiguous.)
+    /// * As with Cargo, a string containing any of `*`, `?`, `[` or `]` is a glob pattern.
+    /// * Anything else is an exact name.
+    ///
+    /// Returns an error if the glob pattern or regular expression is invalid.
+    pub fn new(spec: impl Into<String>) -> Result<Self, Error> {
+        let spec = spec.into();
+        if let Some(regex) = spec
+            .strip_prefix('/')
+            .and_then(|rest| rest.strip_suffix('/'))
+        {
+            Self::regex(regex)
+        } else if spec.contains(|c| matches!(c, '*' | '?' | '[' | ']')) {
+            Self::glob(spec)
+        } else {
+            Ok(Self::exact(spec))
+        }
+    }
+
+    /// Creates a new name pattern that matches exactly this name.
+    pub fn exact(name: impl Into<String>) -> Self {
+        Self {
+            spec: name.into(),
+            kind: NamePatternKind::Exact,
+        }
+    }
+
+    /// Creates a new name pattern out of a glob, such as `storage-*`.
+    ///
+    /// Returns an error if the glob pattern is invalid.
+    pub fn glob(pattern: impl Into<String>) -> Result<Self, Error> {
+        let spec = pattern.into();
+        let pattern = glob::Pattern::new(&spec)
+            .map_err(|err| Error::InvalidNamePattern(spec.clone(), Box::new(err)))?;
+        Ok(Self {
+            spec,
+            kind: NamePatternKind::Glob(pattern),
+        })
+    }
+
+    /// Creates a new name pattern out of a regular expression.
+    ///
+    /// The regular expression is anchored: it must match the entire name, not just a part of it.
+    ///
+    /// Returns an error if the regular expression is invalid.
+    pub fn regex(pattern: impl AsRef<str>) -> Result<Self, Error> {
+        let pattern = pattern.as_ref();
+        let spec = format!("/{}/", pattern);
+        let regex = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|err| Error::InvalidNamePattern(spec.clone(), Box::new(err)))?;
+        Ok(Self {
+            spec,
+            kind: NamePatternKind::Regex(regex),
+        })
+    }
+
+    /// Returns the string form of this pattern.
+    ///
+    /// Passing this string into [`NamePattern::new`] results in an equivalent pattern.
+    pub fn as_str(&self) -> &str {
+        &self.spec
+    }
+
+    /// Returns true if this pattern matches exactly one name.
+    pub fn is_exact(&self) -> bool {
+        matches!(self.kind, NamePatternKind::Exact)
+    }
+
+    /// Returns true if this pattern matches the given name.
+    pub fn matches(&self, name: &str) -> bool {
+        match &self.kind {
+            NamePatternKind::Exact => self.spec == name,
+            NamePatternKind::Glob(pattern) => pattern.matches(name),
+            NamePatternKind::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+impl fmt::Display for NamePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
+
+impl PackageGraph {
+    /// Returns the indexes of workspace members matched by any of these patterns.
+    ///
+    /// Returns an error if any pattern didn't match any workspace members.
+    pub(super) fn named_package_ixs<'a, B>(
+        &self,
+        patterns: impl IntoIterator<Item = &'a NamePattern>,
+    ) -> Result<B, Error>
+    where
+        B: FromIterator<NodeIndex<PackageIx>>,
+    {
+        let workspace = self.workspace();
+        let mut package_ixs = Vec::new();
+        for pattern in patterns {
+            let len = package_ixs.len();
+            package_ixs.extend(
+                workspace
+                    .members_matching(pattern)
+                    .map(|package| package.package_ix()),
+            );
+            if package_ixs.len() == len {
+                return Err(Error::UnmatchedNamePattern(pattern.to_string()));
+            }
+        }
+        Ok(package_ixs.into_iter().collect())
+    }
+}
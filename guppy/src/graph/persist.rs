@@ -0,0 +1,270 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Persisting package graphs to disk and reloading them.
+
+use crate::{graph::PackageGraph, CargoMetadata, Error};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    hash::Hasher,
+    io::{self, Read, Write},
+};
+use twox_hash::XxHash64;
+
+/// Magic bytes at the start of every persisted package graph.
+const MAGIC: &[u8; 8] = b"guppypg\0";
+
+/// The version of the persisted format. This must be bumped whenever the format changes.
+const FORMAT_VERSION: u32 = 2;
+
+impl CargoMetadata {
+    /// Writes out this metadata in a binary format that can be read back as a `PackageGraph` with
+    /// [`PackageGraph::deserialize`].
+    ///
+    /// Along with the metadata, a hash of `Cargo.lock` and of every `Cargo.toml` in the workspace
+    /// is written out. If any of these files change, the persisted graph is considered out of
+    /// date.
+    ///
+    /// `PackageGraph` instances don't retain the metadata they were built from, so persisting is
+    /// done from the `CargoMetadata` before it's turned into a graph.
+    ///
+    /// The format is versioned, but it is only meant to be read back by the same version of
+    /// `guppy`: do not use it for long-term storage.
+    ///
+    /// Requires the `persist` feature to be enabled.
+    pub fn persist(&self, writer: &mut impl Write) -> Result<(), Error> {
+        let inputs = self.persist_inputs();
+        let inputs_hash = hash_inputs(&inputs)?;
+        let header = PersistHeader {
+            workspace_root: self.inner.workspace_root.clone(),
+            inputs,
+            inputs_hash,
+        };
+        serialize_impl(writer, &header, self)
+    }
+
+    /// Returns the files that, if changed, invalidate a persisted graph.
+    fn persist_inputs(&self) -> Vec<Utf8PathBuf> {
+        let workspace_root = &self.inner.workspace_root;
+        let mut inputs = vec![
+            workspace_root.join("Cargo.lock"),
+            workspace_root.join("Cargo.toml"),
+        ];
+        let members: HashSet<_> = self.inner.workspace_members.iter().collect();
+        inputs.extend(
+            self.inner
+                .packages
+                .iter()
+                .filter(|package| members.contains(&package.id))
+                .map(|package| package.manifest_path.clone()),
+        );
+        inputs.sort_unstable();
+        inputs.dedup();
+        inputs
+    }
+}
+
+impl PackageGraph {
+    /// Reads back a `PackageGraph` from metadata written out by [`CargoMetadata::persist`].
+    ///
+    /// Returns `Error::PersistStale` if `Cargo.lock` or any of the `Cargo.toml` files in the
+    /// workspace changed since the metadata was written out. In that case, the graph should be
+    /// rebuilt by running `cargo metadata` again.
+    ///
+    /// Only the metadata is persisted, not the graph built from it. Reloading skips running
+    /// `cargo metadata`, but the graph is still rebuilt from the metadata, the same way
+    /// [`from_metadata`](Self::from_metadata) does.
+    ///
+    /// Requires the `persist` feature to be enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use guppy::{graph::PackageGraph, Error, MetadataCommand};
+    /// use std::fs::File;
+    ///
+    /// let graph = match File::open("target/package-graph.bin") {
+    ///     Ok(mut file) => match PackageGraph::deserialize(&mut file) {
+    ///         Ok(graph) => Some(graph),
+    ///         Err(Error::PersistStale(_)) => None,
+    ///         Err(err) => panic!("failed to read persisted graph: {}", err),
+    ///     },
+    ///     Err(_) => None,
+    /// };
+    /// let graph = match graph {
+    ///     Some(graph) => graph,
+    ///     None => {
+    ///         let metadata = MetadataCommand::new().exec().unwrap();
+    ///         let mut file = File::create("target/package-graph.bin").unwrap();
+    ///         metadata.persist(&mut file).unwrap();
+    ///         metadata.build_graph().unwrap()
+    ///     }
+    /// };
+    /// ```
+    pub fn deserialize(reader: &mut impl Read) -> Result<Self, Error> {
+        deserialize_impl(reader, hash_inputs)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct PersistHeader {
+    workspace_root: Utf8PathBuf,
+    // Files that the graph depends on, and a hash of their contents.
+    inputs: Vec<Utf8PathBuf>,
+    inputs_hash: u64,
+}
+
+fn serialize_impl(
+    writer: &mut impl Write,
+    header: &PersistHeader,
+    metadata: &CargoMetadata,
+) -> Result<(), Error> {
+    writer.write_all(MAGIC).map_err(Error::PersistIoError)?;
+    writer
+        .write_all(&FORMAT_VERSION.to_le_bytes())
+        .map_err(Error::PersistIoError)?;
+
+    let mut serializer = serde_cbor::Serializer::new(serde_cbor::ser::IoWrite::new(writer));
+    header
+        .serialize(&mut serializer)
+        .map_err(|err| Error::PersistEncodingError(Box::new(err)))?;
+    // CargoMetadata::serialize writes out JSON, so call the Serialize impl explicitly.
+    Serialize::serialize(metadata, &mut serializer)
+        .map_err(|err| Error::PersistEncodingError(Box::new(err)))
+}
+
+fn deserialize_impl<R: Read>(
+    reader: &mut R,
+    hash_inputs: impl FnOnce(&[Utf8PathBuf]) -> Result<u64, Error>,
+) -> Result<PackageGraph, Error> {
+    let mut magic = [0; 8];
+    reader
+        .read_exact(&mut magic)
+        .map_err(Error::PersistIoError)?;
+    if &magic != MAGIC {
+        return Err(Error::PersistUnsupportedFormat(
+            "not a persisted package graph".to_owned(),
+        ));
+    }
+    let mut version = [0; 4];
+    reader
+        .read_exact(&mut version)
+        .map_err(Error::PersistIoError)?;
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(Error::PersistUnsupportedFormat(format!(
+            "format version {} (expected {})",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    let mut deserializer = serde_cbor::Deserializer::from_reader(reader);
+    let header = PersistHeader::deserialize(&mut deserializer)
+        .map_err(|err| Error::PersistEncodingError(Box::new(err)))?;
+    // Check the header before reading the rest of the graph, since that's the expensive part.
+    if hash_inputs(&header.inputs)? != header.inputs_hash {
+        return Err(Error::PersistStale(header.workspace_root));
+    }
+
+    let metadata = CargoMetadata::deserialize(&mut deserializer)
+        .map_err(|err| Error::PersistEncodingError(Box::new(err)))?;
+    deserializer
+        .end()
+        .map_err(|err| Error::PersistEncodingError(Box::new(err)))?;
+    PackageGraph::build(metadata)
+}
+
+/// Hashes the contents of the given files. Files that don't exist are hashed as missing.
+fn hash_inputs(inputs: &[Utf8PathBuf]) -> Result<u64, Error> {
+    let mut hasher = XxHash64::with_seed(0);
+    for path in inputs {
+        hasher.write(path.as_str().as_bytes());
+        match read_input(path)? {
+            Some(contents) => {
+                hasher.write_u8(1);
+                hasher.write_u64(contents.len() as u64);
+                hasher.write(&contents);
+            }
+            None => hasher.write_u8(0),
+        }
+    }
+    Ok(hasher.finish())
+}
+
+fn read_input(path: &Utf8Path) -> Result<Option<Vec<u8>>, Error> {
+    match std::fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::PersistIoError(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+
+    fn persist(inputs_hash: u64) -> (CargoMetadata, Vec<u8>) {
+        let metadata =
+            CargoMetadata::parse_json(JsonFixture::metadata1().json()).expect("valid JSON");
+        let header = PersistHeader {
+            workspace_root: metadata.inner.workspace_root.clone(),
+            inputs: metadata.persist_inputs(),
+            inputs_hash,
+        };
+        let mut buf = vec![];
+        serialize_impl(&mut buf, &header, &metadata).expect("metadata serialized");
+        (metadata, buf)
+    }
+
+    #[test]
+    fn persist_roundtrip() {
+        let (metadata, buf) = persist(42);
+        let mut inputs_seen = vec![];
+        let new_graph = deserialize_impl(&mut buf.as_slice(), |inputs| {
+            inputs_seen = inputs.to_vec();
+            Ok(42)
+        })
+        .expect("graph deserialized");
+        assert_eq!(inputs_seen, metadata.persist_inputs());
+
+        let graph = metadata.build_graph().expect("valid graph");
+
+        assert_eq!(new_graph.workspace().root(), graph.workspace().root());
+        let mut package_ids: Vec<_> = graph.package_ids().collect();
+        package_ids.sort_unstable();
+        let mut new_package_ids: Vec<_> = new_graph.package_ids().collect();
+        new_package_ids.sort_unstable();
+        assert_eq!(package_ids, new_package_ids, "package IDs match");
+        assert_eq!(
+            graph.link_count(),
+            new_graph.link_count(),
+            "link count matches"
+        );
+    }
+
+    #[test]
+    fn persist_invalidation() {
+        let (metadata, buf) = persist(42);
+        match deserialize_impl(&mut buf.as_slice(), |_| Ok(43)) {
+            Err(Error::PersistStale(workspace_root)) => {
+                assert_eq!(workspace_root, metadata.inner.workspace_root);
+            }
+            other => panic!("expected stale error, got {:?}", other.map(|_| ())),
+        }
+
+        let mut bad_version = buf.clone();
+        bad_version[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            deserialize_impl(&mut bad_version.as_slice(), |_| Ok(42)),
+            Err(Error::PersistUnsupportedFormat(_))
+        ));
+
+        assert!(matches!(
+            deserialize_impl(&mut &b"not a graph"[..], |_| Ok(42)),
+            Err(Error::PersistUnsupportedFormat(_))
+        ));
+    }
+}
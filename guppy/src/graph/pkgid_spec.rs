@@ -0,0 +1,345 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{ExternalSource, PackageGraph, PackageIx, PackageMetadata, PackageSource},
+    Error,
+};
+use petgraph::graph::NodeIndex;
+use semver::{Prerelease, Version};
+use std::fmt;
+
+/// A Cargo package ID specification, as accepted by commands like `cargo pkgid` and
+/// `cargo build --package`.
+///
+/// A `PackageIdSpec` is one of:
+/// * a package name, optionally followed by a version: `serde`, `serde@1.0.130` or
+///   `serde:1.0.130`
+/// * a URL for the package's source, optionally followed by a fragment with a name, a version or
+///   both: `https://github.com/rust-lang/crates.io-index#serde@1.0.130`,
+///   `https://github.com/foo/bar#baz`, or `file:///path/to/bar#0.1.0`
+///
+/// Versions may be partial: `serde@1` matches every version of `serde` with major version 1. If a
+/// URL doesn't have a name in its fragment, the last segment of its path is used as the name.
+///
+/// URLs may be prefixed with the kind of source, as in `registry+https://...`, `git+https://...`
+/// or `path+file:///...`. Query strings in URLs, such as `?rev=...` in Git sources, are ignored.
+///
+/// For the full grammar, see [the Cargo
+/// book](https://doc.rust-lang.org/cargo/reference/pkgid-spec.html). Package ID specifications
+/// are resolved through
+/// [`PackageGraph::resolve_pkgid_specs`](crate::graph::PackageGraph::resolve_pkgid_specs).
+///
+/// # Examples
+///
+/// ```
+/// use guppy::graph::PackageIdSpec;
+///
+/// let spec = PackageIdSpec::parse("https://github.com/rust-lang/crates.io-index#serde@1.0")
+///     .expect("valid spec");
+/// assert_eq!(spec.name(), "serde");
+/// assert_eq!(spec.version(), Some("1.0"));
+/// assert_eq!(spec.url(), Some("https://github.com/rust-lang/crates.io-index"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct PackageIdSpec {
+    // The string form of this spec, as accepted by `PackageIdSpec::parse`.
+    spec: String,
+    name: String,
+    version: Option<PartialVersion>,
+    url: Option<SpecUrl>,
+}
+
+impl PackageIdSpec {
+    /// Parses a package ID specification from a string.
+    ///
+    /// Returns an error if the string isn't a valid package ID specification.
+    pub fn parse(spec: impl Into<String>) -> Result<Self, Error> {
+        let spec = spec.into();
+        match Self::parse_impl(&spec) {
+            Ok((name, version, url)) => Ok(Self {
+                spec,
+                name,
+                version,
+                url,
+            }),
+            Err(err) => Err(Error::InvalidPackageIdSpec(spec, err.into())),
+        }
+    }
+
+    /// Returns the string form of this spec.
+    pub fn as_str(&self) -> &str {
+        &self.spec
+    }
+
+    /// Returns the package name matched by this spec.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the version, possibly partial, matched by this spec.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_ref().map(|version| version.spec.as_str())
+    }
+
+    /// Returns the URL of the package source matched by this spec, without a prefix indicating the
+    /// kind of source.
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_ref().map(|url| url.url.as_str())
+    }
+
+    /// Returns true if this spec matches the given package.
+    pub fn matches(&self, package: &PackageMetadata<'_>) -> bool {
+        if package.name() != self.name {
+            return false;
+        }
+        if let Some(version) = &self.version {
+            if !version.matches(package.version()) {
+                return false;
+            }
+        }
+        match &self.url {
+            Some(url) => url.matches(package),
+            None => true,
+        }
+    }
+
+    fn parse_impl(spec: &str) -> Result<(String, Option<PartialVersion>, Option<SpecUrl>), String> {
+        if spec.contains("://") {
+            let (url, fragment) = match spec.split_once('#') {
+                Some((url, fragment)) => (url, Some(fragment)),
+                None => (spec, None),
+            };
+            let url = SpecUrl::parse(url)?;
+            let (name, version) = match fragment {
+                // A fragment that's a valid version only has a version in it.
+                Some(fragment) => match PartialVersion::parse(fragment) {
+                    Ok(version) => (url.last_segment()?, Some(version)),
+                    Err(_) => parse_name_and_version(fragment)?,
+                },
+                None => (url.last_segment()?, None),
+            };
+            Ok((name, version, Some(url)))
+        } else if spec.contains('/') {
+            Err(format!(
+                "package names can't contain '/' (for a path, use a URL like file:///{})",
+                spec.trim_start_matches('/')
+            ))
+        } else {
+            let (name, version) = parse_name_and_version(spec)?;
+            Ok((name, version, None))
+        }
+    }
+
+    fn package_ixs<'g>(
+        &'g self,
+        graph: &'g PackageGraph,
+    ) -> impl Iterator<Item = NodeIndex<PackageIx>> + 'g {
+        graph
+            .packages()
+            .filter(move |package| self.matches(package))
+            .map(|package| package.package_ix())
+    }
+}
+
+impl fmt::Display for PackageIdSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
+
+impl PackageGraph {
+    /// Returns the indexes of packages matched by any of these specs.
+    ///
+    /// Returns an error if any spec didn't match any packages.
+    pub(super) fn pkgid_spec_ixs<'a, B>(
+        &self,
+        specs: impl IntoIterator<Item = &'a PackageIdSpec>,
+    ) -> Result<B, Error>
+    where
+        B: FromIterator<NodeIndex<PackageIx>>,
+    {
+        let mut package_ixs = Vec::new();
+        for spec in specs {
+            let len = package_ixs.len();
+            package_ixs.extend(spec.package_ixs(self));
+            if package_ixs.len() == len {
+                return Err(Error::UnmatchedPackageIdSpec(spec.to_string()));
+            }
+        }
+        Ok(package_ixs.into_iter().collect())
+    }
+}
+
+fn parse_name_and_version(s: &str) -> Result<(String, Option<PartialVersion>), String> {
+    let (name, version) = match s.split_once(|c| c == '@' || c == ':') {
+        Some((name, version)) => (name, Some(PartialVersion::parse(version)?)),
+        None => (s, None),
+    };
+    validate_name(name)?;
+    Ok((name.to_owned(), version))
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("package name is empty".to_owned());
+    }
+    match name
+        .chars()
+        .find(|c| !(c.is_alphanumeric() || *c == '-' || *c == '_'))
+    {
+        Some(c) => Err(format!(
+            "invalid character '{}' in package name '{}'",
+            c, name
+        )),
+        None => Ok(()),
+    }
+}
+
+/// A version that may be missing its minor and patch components.
+#[derive(Clone, Debug)]
+struct PartialVersion {
+    spec: String,
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    pre: Prerelease,
+}
+
+impl PartialVersion {
+    fn parse(spec: &str) -> Result<Self, String> {
+        if let Ok(version) = Version::parse(spec) {
+            return Ok(Self {
+                spec: spec.to_owned(),
+                major: version.major,
+                minor: Some(version.minor),
+                patch: Some(version.patch),
+                pre: version.pre,
+            });
+        }
+
+        let invalid = || format!("invalid version '{}'", spec);
+        let mut components = spec.split('.');
+        let major = components.next().ok_or_else(invalid)?;
+        let minor = components.next();
+        if components.next().is_some() {
+            // A version with three components that isn't valid semver.
+            return Err(invalid());
+        }
+        let parse = |component: &str| -> Result<u64, String> {
+            if component.is_empty() || !component.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            component.parse().map_err(|_| invalid())
+        };
+        Ok(Self {
+            spec: spec.to_owned(),
+            major: parse(major)?,
+            minor: minor.map(parse).transpose()?,
+            patch: None,
+            pre: Prerelease::EMPTY,
+        })
+    }
+
+    fn matches(&self, version: &Version) -> bool {
+        self.major == version.major
+            && self.minor.map_or(true, |minor| minor == version.minor)
+            // Pre-release identifiers can only be specified along with the patch version.
+            && self
+                .patch
+                .map_or(true, |patch| patch == version.patch && self.pre == version.pre)
+    }
+}
+
+/// The URL part of a package ID specification.
+#[derive(Clone, Debug)]
+struct SpecUrl {
+    kind: Option<SourceKind>,
+    // The URL without a kind prefix, query string or trailing slash.
+    url: String,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum SourceKind {
+    Registry,
+    Git,
+    Path,
+}
+
+impl SpecUrl {
+    fn parse(url: &str) -> Result<Self, String> {
+        let (kind, rest) = if let Some(rest) = url.strip_prefix(ExternalSource::REGISTRY_PLUS) {
+            (Some(SourceKind::Registry), rest)
+        } else if let Some(rest) = url.strip_prefix(ExternalSource::GIT_PLUS) {
+            (Some(SourceKind::Git), rest)
+        } else if let Some(rest) = url.strip_prefix("path+") {
+            (Some(SourceKind::Path), rest)
+        } else {
+            (None, url)
+        };
+
+        let rest = match rest.split_once('?') {
+            Some((rest, _query)) => rest,
+            None => rest,
+        };
+        let (scheme, path) = rest
+            .split_once("://")
+            .ok_or_else(|| format!("invalid URL '{}'", url))?;
+        let path = path.trim_end_matches('/');
+        if scheme.is_empty() || path.is_empty() {
+            return Err(format!("invalid URL '{}'", url));
+        }
+        if kind == Some(SourceKind::Path) && scheme != "file" {
+            return Err(format!("path source '{}' must be a file:// URL", url));
+        }
+
+        Ok(Self {
+            kind,
+            url: format!("{}://{}", scheme, path),
+        })
+    }
+
+    fn last_segment(&self) -> Result<String, String> {
+        let (_, path) = self.url.split_once("://").expect("URL was validated");
+        let segment = path.rsplit('/').next().unwrap_or_default();
+        validate_name(segment)
+            .map_err(|err| format!("can't infer package name from URL '{}': {}", self.url, err))?;
+        Ok(segment.to_owned())
+    }
+
+    fn matches(&self, package: &PackageMetadata<'_>) -> bool {
+        let (kind, url) = match package.source() {
+            PackageSource::Workspace(_) | PackageSource::Path(_) => {
+                let dir = match package.manifest_path().parent() {
+                    Some(dir) => dir,
+                    None => return false,
+                };
+                (SourceKind::Path, format!("file://{}", dir))
+            }
+            source => match source.parse_external() {
+                Some(ExternalSource::Registry(registry)) => {
+                    (SourceKind::Registry, registry.to_owned())
+                }
+                Some(ExternalSource::Git { repository, .. }) => {
+                    (SourceKind::Git, repository.to_owned())
+                }
+                // Unknown kinds of sources can't be matched.
+                _ => return false,
+            },
+        };
+
+        if self.kind.map_or(false, |self_kind| self_kind != kind) {
+            return false;
+        }
+        match kind {
+            SourceKind::Git => normalize_git_url(&self.url) == normalize_git_url(&url),
+            SourceKind::Registry | SourceKind::Path => self.url == url.trim_end_matches('/'),
+        }
+    }
+}
+
+/// Git URLs are matched regardless of trailing slashes or `.git` suffixes.
+fn normalize_git_url(url: &str) -> &str {
+    let url = url.trim_end_matches('/');
+    url.strip_suffix(".git").unwrap_or(url)
+}
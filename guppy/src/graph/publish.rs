@@ -0,0 +1,67 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Code for finding dependencies that would cause `cargo publish` to fail.
+
+use crate::graph::{PackageLink, PackageMetadata, PackagePublish};
+
+/// A dependency of a publishable package on a package that can't be published to every registry
+/// the dependent can be published to.
+///
+/// While publishing, Cargo replaces path dependencies with registry dependencies, so the
+/// dependency must be available in the same registry. For example, if `foo` can be published to
+/// crates.io but depends on `bar`, which has `publish = false` or is restricted to a private
+/// registry, `cargo publish` for `foo` will fail.
+///
+/// Constructed through `PackageGraph::publish_violations`.
+#[derive(Copy, Clone, Debug)]
+pub struct PublishViolation<'g> {
+    link: PackageLink<'g>,
+}
+
+impl<'g> PublishViolation<'g> {
+    pub(super) fn new(link: PackageLink<'g>) -> Option<Self> {
+        let (from, to) = link.endpoints();
+        // Only local packages are replaced with registry dependencies, and dev-dependencies are
+        // stripped while publishing.
+        if !from.source().is_local() || !to.source().is_local() || link.dev_only() {
+            return None;
+        }
+        if from.publish() <= to.publish() {
+            None
+        } else {
+            Some(Self { link })
+        }
+    }
+
+    /// Returns the link from the publishable package to its dependency.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the package that can't be published because of this dependency.
+    pub fn from(&self) -> PackageMetadata<'g> {
+        self.link.from()
+    }
+
+    /// Returns the dependency with the stricter set of registries.
+    pub fn to(&self) -> PackageMetadata<'g> {
+        self.link.to()
+    }
+
+    /// Returns the registries that `from` can be published to, but `to` can't.
+    ///
+    /// Returns `None` if `from` can be published to any registry, in which case `to` is
+    /// restricted to the registries returned by `self.to().publish()`.
+    pub fn missing_registries(&self) -> Option<Vec<&'g str>> {
+        match (self.from().publish(), self.to().publish()) {
+            (PackagePublish::Registries(from), to) => Some(
+                from.iter()
+                    .filter(|registry| !to.can_publish_to(registry))
+                    .map(|registry| registry.as_str())
+                    .collect(),
+            ),
+            (PackagePublish::Unrestricted, _) => None,
+        }
+    }
+}
@@ -1,11 +1,13 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+#[cfg(feature = "name-patterns")]
+use crate::graph::NamePattern;
 use crate::{
     graph::{
         feature::{FeatureFilter, FeatureQuery},
         query_core::QueryParams,
-        DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageMetadata,
+        DependencyDirection, PackageDepths, PackageGraph, PackageIx, PackageLink, PackageMetadata,
         PackageResolver, PackageSet, ResolverFn,
     },
     sorted_set::SortedSet,
@@ -23,6 +25,7 @@ pub struct PackageQuery<'g> {
     // The fields are pub(super) for access within the graph module.
     pub(super) graph: &'g PackageGraph,
     pub(super) params: QueryParams<PackageGraph>,
+    pub(super) max_depth: Option<usize>,
 }
 
 assert_covariant!(PackageQuery);
@@ -83,6 +86,23 @@ impl PackageGraph {
         Ok(self.query_from_parts(package_ixs, DependencyDirection::Forward))
     }
 
+    /// Creates a new forward query over the workspace packages matched by the given name patterns.
+    ///
+    /// This is similar to `cargo`'s `--package` option, which accepts glob patterns. Patterns may
+    /// also be regular expressions: see [`NamePattern`] for more.
+    ///
+    /// Returns an error if any pattern didn't match any workspace packages.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    pub fn query_named<'a>(
+        &self,
+        patterns: impl IntoIterator<Item = &'a NamePattern>,
+    ) -> Result<PackageQuery, Error> {
+        let package_ixs = self.named_package_ixs(patterns)?;
+        Ok(self.query_from_parts(package_ixs, DependencyDirection::Forward))
+    }
+
     /// Creates a new query that returns transitive dependencies of the given packages in the
     /// specified direction.
     ///
@@ -108,6 +128,7 @@ impl PackageGraph {
         Ok(PackageQuery {
             graph: self,
             params: QueryParams::Forward(self.package_ixs(package_ids)?),
+            max_depth: None,
         })
     }
 
@@ -121,9 +142,85 @@ impl PackageGraph {
         Ok(PackageQuery {
             graph: self,
             params: QueryParams::Reverse(self.package_ixs(package_ids)?),
+            max_depth: None,
         })
     }
 
+    /// Creates a new query that follows each of the given packages in its own direction.
+    ///
+    /// Packages paired with `DependencyDirection::Forward` have their transitive dependencies
+    /// included, and packages paired with `DependencyDirection::Reverse` have their transitive
+    /// reverse dependencies included. A package may be listed in both directions.
+    ///
+    /// For more information, see the documentation for `MixedPackageQuery`.
+    ///
+    /// Returns an error if any package IDs are unknown.
+    pub fn query_mixed<'g, 'a>(
+        &'g self,
+        initials: impl IntoIterator<Item = (&'a PackageId, DependencyDirection)>,
+    ) -> Result<MixedPackageQuery<'g>, Error> {
+        let mut forward_ixs = vec![];
+        let mut reverse_ixs = vec![];
+        for (package_id, direction) in initials {
+            let package_ix = self.package_ix(package_id)?;
+            match direction {
+                DependencyDirection::Forward => forward_ixs.push(package_ix),
+                DependencyDirection::Reverse => reverse_ixs.push(package_ix),
+            }
+        }
+        Ok(MixedPackageQuery {
+            forward: self
+                .query_from_parts(SortedSet::new(forward_ixs), DependencyDirection::Forward),
+            reverse: self
+                .query_from_parts(SortedSet::new(reverse_ixs), DependencyDirection::Reverse),
+        })
+    }
+
+    /// Creates a new query that returns dependencies of the given packages in the specified
+    /// direction, following at most `max_depth` links from them.
+    ///
+    /// Returns an error if any package IDs are unknown.
+    pub fn query_directed_bounded<'g, 'a>(
+        &'g self,
+        package_ids: impl IntoIterator<Item = &'a PackageId>,
+        dep_direction: DependencyDirection,
+        max_depth: usize,
+    ) -> Result<PackageQuery<'g>, Error> {
+        let mut query = self.query_directed(package_ids, dep_direction)?;
+        query.max_depth = Some(max_depth);
+        Ok(query)
+    }
+
+    /// Creates a new query that returns dependencies of the given packages that are at most
+    /// `max_depth` links away from them.
+    ///
+    /// With a `max_depth` of 0, only the given packages are returned. With a `max_depth` of 1,
+    /// their direct dependencies are returned as well, and so on. Use
+    /// [`PackageQuery::resolve_depths`] to find out how far away each package is.
+    ///
+    /// The depth limit isn't carried over to queries created through `to_feature_query`.
+    ///
+    /// Returns an error if any package IDs are unknown.
+    pub fn query_forward_bounded<'g, 'a>(
+        &'g self,
+        package_ids: impl IntoIterator<Item = &'a PackageId>,
+        max_depth: usize,
+    ) -> Result<PackageQuery<'g>, Error> {
+        self.query_directed_bounded(package_ids, DependencyDirection::Forward, max_depth)
+    }
+
+    /// Creates a new query that returns reverse dependencies of the given packages that are at
+    /// most `max_depth` links away from them.
+    ///
+    /// Returns an error if any package IDs are unknown.
+    pub fn query_reverse_bounded<'g, 'a>(
+        &'g self,
+        package_ids: impl IntoIterator<Item = &'a PackageId>,
+        max_depth: usize,
+    ) -> Result<PackageQuery<'g>, Error> {
+        self.query_directed_bounded(package_ids, DependencyDirection::Reverse, max_depth)
+    }
+
     pub(super) fn query_from_parts(
         &self,
         package_ixs: SortedSet<NodeIndex<PackageIx>>,
@@ -136,6 +233,7 @@ impl PackageGraph {
         PackageQuery {
             graph: self,
             params,
+            max_depth: None,
         }
     }
 }
@@ -165,6 +263,12 @@ impl<'g> PackageQuery<'g> {
         })
     }
 
+    /// Returns the maximum number of links followed from the initial packages, if this query is
+    /// bounded.
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
     /// Returns true if the query starts from the given package ID.
     ///
     /// Returns an error if this package ID is unknown.
@@ -205,4 +309,91 @@ impl<'g> PackageQuery<'g> {
     ) -> PackageSet<'g> {
         self.resolve_with(ResolverFn(resolver_fn))
     }
+
+    /// Resolves this query into a set of known packages, along with the minimum number of links
+    /// followed to reach each of them from the initial packages.
+    pub fn resolve_depths(self) -> PackageDepths<'g> {
+        PackageDepths::new(self)
+    }
+
+    /// Resolves this query into a set of known packages along with their depths, using the
+    /// provided resolver to determine which links are followed.
+    pub fn resolve_depths_with(self, resolver: impl PackageResolver<'g>) -> PackageDepths<'g> {
+        PackageDepths::with_resolver(self, resolver)
+    }
+}
+
+/// A query over a package graph that follows dependencies forward from some packages, and
+/// reverse dependencies from others.
+///
+/// This is useful for impact analysis. For example, a query that goes forward and reverse from the
+/// same package returns everything that's affected by a change to it, along with everything it
+/// needs to build.
+///
+/// A `MixedPackageQuery` consists of two `PackageQuery` instances, one in each direction. Resolving
+/// it resolves both, and returns the union of the results.
+///
+/// Constructed through `PackageGraph::query_mixed`.
+#[derive(Clone, Debug)]
+pub struct MixedPackageQuery<'g> {
+    forward: PackageQuery<'g>,
+    reverse: PackageQuery<'g>,
+}
+
+assert_covariant!(MixedPackageQuery);
+
+impl<'g> MixedPackageQuery<'g> {
+    /// Returns the package graph on which the query is going to be executed.
+    pub fn graph(&self) -> &'g PackageGraph {
+        self.forward.graph
+    }
+
+    /// Returns the query over the packages whose dependencies are followed.
+    pub fn forward(&self) -> &PackageQuery<'g> {
+        &self.forward
+    }
+
+    /// Returns the query over the packages whose reverse dependencies are followed.
+    pub fn reverse(&self) -> &PackageQuery<'g> {
+        &self.reverse
+    }
+
+    /// Returns true if the query starts from the given package ID in the given direction.
+    ///
+    /// Returns an error if this package ID is unknown.
+    pub fn starts_from(
+        &self,
+        package_id: &PackageId,
+        direction: DependencyDirection,
+    ) -> Result<bool, Error> {
+        match direction {
+            DependencyDirection::Forward => self.forward.starts_from(package_id),
+            DependencyDirection::Reverse => self.reverse.starts_from(package_id),
+        }
+    }
+
+    /// Resolves this query into a set of known packages, following every link found along the
+    /// way.
+    pub fn resolve(self) -> PackageSet<'g> {
+        self.forward.resolve().union(&self.reverse.resolve())
+    }
+
+    /// Resolves this query into a set of known packages, using the provided resolver to
+    /// determine which links are followed.
+    ///
+    /// The resolver is called with the query in the direction that's being followed, so
+    /// `query.direction()` can be used to tell the two directions apart.
+    pub fn resolve_with(self, mut resolver: impl PackageResolver<'g>) -> PackageSet<'g> {
+        let forward = self.forward.resolve_with(&mut resolver);
+        forward.union(&self.reverse.resolve_with(&mut resolver))
+    }
+
+    /// Resolves this query into a set of known packages, using the provided resolver function
+    /// to determine which links are followed.
+    pub fn resolve_with_fn(
+        self,
+        resolver_fn: impl FnMut(&PackageQuery<'g>, PackageLink<'g>) -> bool,
+    ) -> PackageSet<'g> {
+        self.resolve_with(ResolverFn(resolver_fn))
+    }
 }
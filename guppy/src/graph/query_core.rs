@@ -10,9 +10,9 @@ use fixedbitset::FixedBitSet;
 use petgraph::{
     graph::IndexType,
     prelude::*,
-    visit::{IntoEdges, IntoNeighbors, Visitable},
+    visit::{IntoEdges, IntoNeighbors, VisitMap, Visitable},
 };
-use std::fmt;
+use std::{collections::VecDeque, fmt};
 
 pub(super) enum QueryParams<G: GraphSpec> {
     Forward(SortedSet<NodeIndex<G::Ix>>),
@@ -127,3 +127,44 @@ where
     let len = reachable.count_ones(..);
     (reachable, len)
 }
+
+/// Returns the nodes reachable from the roots along with their depths, i.e. the minimum number of
+/// edges followed to reach them.
+///
+/// Nodes are returned in the order they're visited, so depths are nondecreasing. If `max_depth`
+/// is specified, edges out of nodes at that depth aren't followed.
+pub(super) fn depth_map_buffered_filter<G, Ix>(
+    graph: G,
+    mut filter: impl BufferedEdgeFilter<G>,
+    roots: impl IntoIterator<Item = G::NodeId>,
+    max_depth: Option<usize>,
+) -> Vec<(NodeIndex<Ix>, usize)>
+where
+    G: Visitable<NodeId = NodeIndex<Ix>, Map = FixedBitSet> + IntoEdges,
+    Ix: IndexType,
+{
+    // This is a breadth-first search, which finds the minimum depth of each node the first time
+    // it's discovered.
+    let mut discovered = graph.visit_map();
+    let mut queue: VecDeque<_> = roots
+        .into_iter()
+        .filter(|root| discovered.visit(*root))
+        .map(|root| (root, 0))
+        .collect();
+    let mut depths = Vec::with_capacity(queue.len());
+    while let Some((ix, depth)) = queue.pop_front() {
+        depths.push((ix, depth));
+        if max_depth.map_or(false, |max_depth| depth >= max_depth) {
+            continue;
+        }
+        for edge in graph.edges(ix) {
+            for edge in filter.filter(edge) {
+                let next = edge.target();
+                if discovered.visit(next) {
+                    queue.push_back((next, depth + 1));
+                }
+            }
+        }
+    }
+    depths
+}
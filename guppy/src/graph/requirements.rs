@@ -0,0 +1,259 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Code for finding conflicting requirements between declarations of the same dependency.
+
+use crate::{graph::PackageLink, DependencyKind};
+use semver::VersionReq;
+use std::{collections::BTreeSet, fmt, sync::Arc};
+
+/// A single declaration of a dependency in a `Cargo.toml` file.
+///
+/// A package may declare the same dependency several times: in the `[dependencies]`,
+/// `[build-dependencies]` and `[dev-dependencies]` sections, and in `[target]` sections for
+/// specific platforms. A [`PackageLink`] combines all of them, and this struct represents one of
+/// them.
+///
+/// Returned by [`PackageLink::declarations`].
+#[derive(Copy, Clone, Debug)]
+pub struct DependencyDeclaration<'g> {
+    inner: &'g DependencyDeclarationImpl,
+}
+
+assert_covariant!(DependencyDeclaration);
+
+impl<'g> DependencyDeclaration<'g> {
+    pub(super) fn new(inner: &'g DependencyDeclarationImpl) -> Self {
+        Self { inner }
+    }
+
+    /// Returns the section this dependency was declared in.
+    pub fn kind(&self) -> DependencyKind {
+        self.inner.kind
+    }
+
+    /// Returns the platform this dependency was declared for, as a target triple or a `cfg()`
+    /// expression.
+    ///
+    /// Returns `None` if this dependency isn't platform-specific.
+    pub fn target(&self) -> Option<&'g str> {
+        self.inner.target.as_deref()
+    }
+
+    /// Returns the version requirement in this declaration.
+    pub fn version_req(&self) -> &'g VersionReq {
+        &self.inner.version_req
+    }
+
+    /// Returns true if this declaration is marked `optional = true`.
+    pub fn is_optional(&self) -> bool {
+        self.inner.optional
+    }
+
+    /// Returns the value of `default-features` in this declaration.
+    ///
+    /// This is true if `default-features` isn't specified. To also account for the `default`
+    /// feature being listed explicitly, use
+    /// [`default_features_enabled`](Self::default_features_enabled).
+    pub fn uses_default_features(&self) -> bool {
+        self.inner.uses_default_features
+    }
+
+    /// Returns true if this declaration enables default features of the dependency.
+    ///
+    /// Default features are enabled either through `default-features = true` (or leaving it out),
+    /// or through `default-features = false` with `default` in the list of features.
+    pub fn default_features_enabled(&self) -> bool {
+        self.inner.uses_default_features || self.inner.features.iter().any(|f| &**f == "default")
+    }
+
+    /// Returns the features listed in this declaration, in sorted order.
+    pub fn features(&self) -> impl ExactSizeIterator<Item = &'g str> {
+        self.inner.features.iter().map(|feature| &**feature)
+    }
+
+    /// Returns the features listed in this declaration, other than `default`.
+    ///
+    /// `default` is covered by [`default_features_enabled`](Self::default_features_enabled).
+    fn named_features(&self) -> BTreeSet<&'g str> {
+        self.features()
+            .filter(|feature| *feature != "default")
+            .collect()
+    }
+}
+
+/// Displays the `Cargo.toml` section this dependency was declared in, e.g.
+/// `[target.'cfg(unix)'.build-dependencies]`.
+impl<'g> fmt::Display for DependencyDeclaration<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let section = match self.kind() {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Development => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        };
+        match self.target() {
+            Some(target) => write!(f, "[target.'{}'.{}]", target, section),
+            None => write!(f, "[{}]", section),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct DependencyDeclarationImpl {
+    pub(super) kind: DependencyKind,
+    pub(super) target: Option<Box<str>>,
+    pub(super) version_req: VersionReq,
+    pub(super) optional: bool,
+    pub(super) uses_default_features: bool,
+    // Sorted and deduplicated.
+    pub(super) features: Box<[Arc<str>]>,
+}
+
+/// A difference between declarations of the same dependency by a package.
+///
+/// When a dependency is declared several times, for example in both the `[dependencies]` and
+/// `[target.'cfg(unix)'.build-dependencies]` sections, the effective requirements depend on the
+/// platform and on whether the dependency is built for the host or the target. Differences
+/// between declarations are often unintentional, and can lead to surprising builds.
+///
+/// Default features are considered enabled by a declaration that has `default-features = false`
+/// but lists the `default` feature, so such a declaration doesn't conflict with one that leaves
+/// `default-features` out.
+///
+/// Returned by [`PackageLink::requirement_conflicts`].
+#[derive(Clone, Debug)]
+pub struct RequirementConflict<'g> {
+    link: PackageLink<'g>,
+    kind: RequirementConflictKind,
+    declarations: Vec<DependencyDeclaration<'g>>,
+}
+
+impl<'g> RequirementConflict<'g> {
+    /// Finds conflicts between the given declarations of a dependency.
+    pub(super) fn find(
+        link: PackageLink<'g>,
+        declarations: Vec<DependencyDeclaration<'g>>,
+    ) -> Vec<Self> {
+        let mut conflicts = vec![];
+        let first = match declarations.first() {
+            Some(first) => *first,
+            None => return conflicts,
+        };
+
+        let first_features = first.named_features();
+        for kind in RequirementConflictKind::VALUES {
+            let differs = declarations[1..].iter().any(|decl| match kind {
+                RequirementConflictKind::VersionReq => decl.version_req() != first.version_req(),
+                RequirementConflictKind::DefaultFeatures => {
+                    decl.default_features_enabled() != first.default_features_enabled()
+                }
+                RequirementConflictKind::Features => decl.named_features() != first_features,
+            });
+            if differs {
+                conflicts.push(Self {
+                    link,
+                    kind: *kind,
+                    declarations: declarations.clone(),
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// Returns the link between the package and the dependency it declares several times.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the kind of requirement that differs between declarations.
+    pub fn kind(&self) -> RequirementConflictKind {
+        self.kind
+    }
+
+    /// Returns the declarations that were compared, in the order they're listed in `cargo
+    /// metadata` output.
+    pub fn declarations(&self) -> &[DependencyDeclaration<'g>] {
+        &self.declarations
+    }
+}
+
+impl<'g> fmt::Display for RequirementConflict<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} declares {} with different {}: ",
+            self.link.from().name(),
+            self.link.dep_name(),
+            self.kind.description(),
+        )?;
+        for (idx, decl) in self.declarations.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} ", decl)?;
+            match self.kind {
+                RequirementConflictKind::VersionReq => write!(f, "\"{}\"", decl.version_req())?,
+                RequirementConflictKind::DefaultFeatures => {
+                    write!(f, "default-features = {}", decl.default_features_enabled())?
+                }
+                RequirementConflictKind::Features => {
+                    write!(f, "features = [")?;
+                    for (idx, feature) in decl.named_features().into_iter().enumerate() {
+                        if idx > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "\"{}\"", feature)?;
+                    }
+                    write!(f, "]")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The kind of requirement that differs in a [`RequirementConflict`].
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum RequirementConflictKind {
+    /// Declarations specify different version requirements.
+    VersionReq,
+
+    /// Some declarations enable default features, while others don't.
+    DefaultFeatures,
+
+    /// Declarations list different features, other than `default`.
+    Features,
+}
+
+impl RequirementConflictKind {
+    /// A list of all the possible values of `RequirementConflictKind`.
+    pub const VALUES: &'static [Self; 3] = &[
+        RequirementConflictKind::VersionReq,
+        RequirementConflictKind::DefaultFeatures,
+        RequirementConflictKind::Features,
+    ];
+
+    /// Returns a string representing this kind of conflict.
+    pub fn to_str(self) -> &'static str {
+        match self {
+            RequirementConflictKind::VersionReq => "version-req",
+            RequirementConflictKind::DefaultFeatures => "default-features",
+            RequirementConflictKind::Features => "features",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            RequirementConflictKind::VersionReq => "version requirements",
+            RequirementConflictKind::DefaultFeatures => "default-features",
+            RequirementConflictKind::Features => "features",
+        }
+    }
+}
+
+impl fmt::Display for RequirementConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_str())
+    }
+}
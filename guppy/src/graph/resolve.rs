@@ -1,16 +1,19 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+#[cfg(feature = "name-patterns")]
+use crate::graph::NamePattern;
 use crate::{
     debug_ignore::DebugIgnore,
     graph::{
         feature::{FeatureFilter, FeatureSet},
         resolve_core::{ResolveCore, Topo},
-        DependencyDirection, PackageGraph, PackageIx, PackageLink, PackageLinkImpl,
+        DependencyDirection, PackageGraph, PackageIdSpec, PackageIx, PackageLink, PackageLinkImpl,
         PackageMetadata, PackageQuery,
     },
     petgraph_support::{
-        dot::{DotFmt, DotVisitor, DotWrite},
+        dfs::SimpleEdgeFilterFn,
+        dot::{DotAttrs, DotFmt, DotVisitor, DotWrite},
         edge_ref::GraphEdgeRef,
         IxBitSet,
     },
@@ -23,7 +26,7 @@ use petgraph::{
     prelude::*,
     visit::{NodeFiltered, NodeRef},
 };
-use std::fmt;
+use std::{collections::HashMap, fmt};
 
 impl PackageGraph {
     /// Creates a new `PackageSet` consisting of all members of this package graph.
@@ -126,6 +129,48 @@ impl PackageGraph {
         })
     }
 
+    /// Creates a new `PackageSet` consisting of the workspace packages matched by the given name
+    /// patterns.
+    ///
+    /// This does not include transitive dependencies. To do so, use `query_named`.
+    ///
+    /// Returns an error if any pattern didn't match any workspace packages.
+    ///
+    /// This is present if the `name-patterns` feature is enabled.
+    #[cfg(feature = "name-patterns")]
+    pub fn resolve_named<'a>(
+        &self,
+        patterns: impl IntoIterator<Item = &'a NamePattern>,
+    ) -> Result<PackageSet, Error> {
+        let included: IxBitSet = self.named_package_ixs(patterns)?;
+        Ok(PackageSet {
+            graph: DebugIgnore(self),
+            core: ResolveCore::from_included(included),
+        })
+    }
+
+    /// Creates a new `PackageSet` consisting of the packages matched by the given Cargo package ID
+    /// specifications.
+    ///
+    /// Unlike the other `resolve_` methods, this isn't restricted to the workspace: specs like
+    /// `serde@1.0.130` match third-party packages as well. A spec that matches more than one
+    /// package, such as `serde` if several versions of it are in the graph, includes all of them.
+    /// For more about the syntax, see [`PackageIdSpec`].
+    ///
+    /// This does not include transitive dependencies. To do so, use `query_directed` on the result.
+    ///
+    /// Returns an error if any spec didn't match any packages.
+    pub fn resolve_pkgid_specs<'a>(
+        &self,
+        specs: impl IntoIterator<Item = &'a PackageIdSpec>,
+    ) -> Result<PackageSet, Error> {
+        let included: IxBitSet = self.pkgid_spec_ixs(specs)?;
+        Ok(PackageSet {
+            graph: DebugIgnore(self),
+            core: ResolveCore::from_included(included),
+        })
+    }
+
     /// Creates a new `PackageSet` consisting of packages with the given name.
     ///
     /// The result is empty if there are no packages with the given name.
@@ -163,6 +208,9 @@ assert_covariant!(PackageSet);
 
 impl<'g> PackageSet<'g> {
     pub(super) fn new(query: PackageQuery<'g>) -> Self {
+        if query.max_depth.is_some() {
+            return Self::with_depths(query).0;
+        }
         let graph = query.graph;
         Self {
             graph: DebugIgnore(graph),
@@ -170,6 +218,21 @@ impl<'g> PackageSet<'g> {
         }
     }
 
+    fn with_depths(query: PackageQuery<'g>) -> (Self, Vec<(NodeIndex<PackageIx>, usize)>) {
+        let graph = query.graph;
+        let (core, depths) = ResolveCore::with_depths(
+            graph.dep_graph(),
+            query.params,
+            query.max_depth,
+            SimpleEdgeFilterFn(|_| true),
+        );
+        let package_set = Self {
+            graph: DebugIgnore(graph),
+            core,
+        };
+        (package_set, depths)
+    }
+
     pub(super) fn from_included(graph: &'g PackageGraph, included: impl Into<FixedBitSet>) -> Self {
         Self {
             graph: DebugIgnore(graph),
@@ -181,6 +244,9 @@ impl<'g> PackageSet<'g> {
         query: PackageQuery<'g>,
         mut resolver: impl PackageResolver<'g>,
     ) -> Self {
+        if query.max_depth.is_some() {
+            return Self::with_resolver_depths(query, resolver).0;
+        }
         let graph = query.graph;
         let params = query.params.clone();
         Self {
@@ -192,6 +258,28 @@ impl<'g> PackageSet<'g> {
         }
     }
 
+    fn with_resolver_depths(
+        query: PackageQuery<'g>,
+        mut resolver: impl PackageResolver<'g>,
+    ) -> (Self, Vec<(NodeIndex<PackageIx>, usize)>) {
+        let graph = query.graph;
+        let params = query.params.clone();
+        let (core, depths) = ResolveCore::with_depths(
+            graph.dep_graph(),
+            params,
+            query.max_depth,
+            SimpleEdgeFilterFn(|edge| {
+                let link = graph.edge_ref_to_link(edge);
+                resolver.accept(&query, link)
+            }),
+        );
+        let package_set = Self {
+            graph: DebugIgnore(graph),
+            core,
+        };
+        (package_set, depths)
+    }
+
     /// Returns the number of packages in this set.
     pub fn len(&self) -> usize {
         self.core.len()
@@ -290,6 +378,45 @@ impl<'g> PackageSet<'g> {
         res
     }
 
+    /// Returns true if every package in `self` is also in `other`.
+    ///
+    /// This is computed on the underlying bitsets, without looking at any package metadata.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the package graphs associated with `self` and `other` don't match.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        assert!(
+            ::std::ptr::eq(self.graph.0, other.graph.0),
+            "package graphs passed into is_subset() match"
+        );
+        self.core.is_subset(&other.core)
+    }
+
+    /// Returns true if every package in `other` is also in `self`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the package graphs associated with `self` and `other` don't match.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns true if `self` and `other` have no packages in common.
+    ///
+    /// This is computed on the underlying bitsets, without looking at any package metadata.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the package graphs associated with `self` and `other` don't match.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        assert!(
+            ::std::ptr::eq(self.graph.0, other.graph.0),
+            "package graphs passed into is_disjoint() match"
+        );
+        self.core.is_disjoint(&other.core)
+    }
+
     /// Returns a `PackageSet` on which a filter has been applied.
     ///
     /// Filters out all values for which the callback returns false.
@@ -543,6 +670,77 @@ impl<'g> PackageSet<'g> {
     }
 }
 
+/// A set of resolved packages, along with the minimum number of links followed to reach each of
+/// them from the initial packages.
+///
+/// Created by `PackageQuery::resolve_depths`.
+#[derive(Clone, Debug)]
+pub struct PackageDepths<'g> {
+    package_set: PackageSet<'g>,
+    // In the order packages were visited, so depths are nondecreasing.
+    order: Vec<(NodeIndex<PackageIx>, usize)>,
+    depths: HashMap<NodeIndex<PackageIx>, usize>,
+}
+
+assert_covariant!(PackageDepths);
+
+impl<'g> PackageDepths<'g> {
+    pub(super) fn new(query: PackageQuery<'g>) -> Self {
+        let (package_set, order) = PackageSet::with_depths(query);
+        Self::from_parts(package_set, order)
+    }
+
+    pub(super) fn with_resolver(
+        query: PackageQuery<'g>,
+        resolver: impl PackageResolver<'g>,
+    ) -> Self {
+        let (package_set, order) = PackageSet::with_resolver_depths(query, resolver);
+        Self::from_parts(package_set, order)
+    }
+
+    fn from_parts(package_set: PackageSet<'g>, order: Vec<(NodeIndex<PackageIx>, usize)>) -> Self {
+        let depths = order.iter().copied().collect();
+        Self {
+            package_set,
+            order,
+            depths,
+        }
+    }
+
+    /// Returns the set of resolved packages.
+    pub fn package_set(&self) -> &PackageSet<'g> {
+        &self.package_set
+    }
+
+    /// Converts `self` into the set of resolved packages.
+    pub fn into_package_set(self) -> PackageSet<'g> {
+        self.package_set
+    }
+
+    /// Returns the depth of the given package, or `None` if it wasn't resolved.
+    ///
+    /// Returns an error if the package ID is unknown.
+    pub fn depth(&self, package_id: &PackageId) -> Result<Option<usize>, Error> {
+        let package_ix = self.package_set.graph.package_ix(package_id)?;
+        Ok(self.depths.get(&package_ix).copied())
+    }
+
+    /// Iterates over package metadatas along with their depths, in nondecreasing order of depth.
+    ///
+    /// The order of packages at the same depth is unspecified.
+    pub fn packages<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (PackageMetadata<'g>, usize)> + ExactSizeIterator + 'a {
+        let graph = self.package_set.graph.0;
+        self.order.iter().map(move |(package_ix, depth)| {
+            let package = graph
+                .metadata(&graph.dep_graph[*package_ix])
+                .expect("known package IDs");
+            (package, *depth)
+        })
+    }
+}
+
 impl<'g> PartialEq for PackageSet<'g> {
     fn eq(&self, other: &Self) -> bool {
         ::std::ptr::eq(self.graph.0, other.graph.0) && self.core == other.core
@@ -602,6 +800,38 @@ pub trait PackageDotVisitor {
     /// Visits this dependency link. The implementation may output a label for this link to the
     /// given `DotWrite`.
     fn visit_link(&self, link: PackageLink<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result;
+
+    /// Visits this package. The implementation may output attributes other than the label, such
+    /// as `color` or `style`, to the given `DotAttrs`.
+    ///
+    /// The default implementation outputs no attributes.
+    fn visit_package_attrs(
+        &self,
+        _package: PackageMetadata<'_>,
+        _attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Visits this dependency link. The implementation may output attributes other than the label,
+    /// such as `color` or `style`, to the given `DotAttrs`.
+    ///
+    /// The default implementation outputs no attributes.
+    fn visit_link_attrs(
+        &self,
+        _link: PackageLink<'_>,
+        _attrs: &mut DotAttrs<'_, '_>,
+    ) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Returns the name of the cluster this package is drawn in, if any. Packages in the same
+    /// cluster are drawn together, inside a box labeled with the name.
+    ///
+    /// The default implementation doesn't place packages in clusters.
+    fn package_cluster(&self, _package: PackageMetadata<'_>) -> Option<String> {
+        None
+    }
 }
 
 struct VisitorWrap<'g, V> {
@@ -613,6 +843,15 @@ impl<'g, V> VisitorWrap<'g, V> {
     fn new(graph: &'g PackageGraph, inner: V) -> Self {
         Self { graph, inner }
     }
+
+    fn metadata<NR>(&self, node: NR) -> PackageMetadata<'g>
+    where
+        NR: NodeRef<NodeId = NodeIndex<PackageIx>, Weight = PackageId>,
+    {
+        self.graph
+            .metadata(node.weight())
+            .expect("visited node should have associated metadata")
+    }
 }
 
 impl<'g, V, NR, ER> DotVisitor<NR, ER> for VisitorWrap<'g, V>
@@ -622,15 +861,24 @@ where
     ER: GraphEdgeRef<'g, PackageLinkImpl, PackageIx>,
 {
     fn visit_node(&self, node: NR, f: &mut DotWrite<'_, '_>) -> fmt::Result {
-        let metadata = self
-            .graph
-            .metadata(node.weight())
-            .expect("visited node should have associated metadata");
-        self.inner.visit_package(metadata, f)
+        self.inner.visit_package(self.metadata(node), f)
     }
 
     fn visit_edge(&self, edge: ER, f: &mut DotWrite<'_, '_>) -> fmt::Result {
         let link = self.graph.edge_ref_to_link(edge.into_edge_reference());
         self.inner.visit_link(link, f)
     }
+
+    fn visit_node_attrs(&self, node: NR, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        self.inner.visit_package_attrs(self.metadata(node), attrs)
+    }
+
+    fn visit_edge_attrs(&self, edge: ER, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        let link = self.graph.edge_ref_to_link(edge.into_edge_reference());
+        self.inner.visit_link_attrs(link, attrs)
+    }
+
+    fn node_cluster(&self, node: NR) -> Option<String> {
+        self.inner.package_cluster(self.metadata(node))
+    }
 }
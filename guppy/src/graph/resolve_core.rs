@@ -4,7 +4,10 @@
 use crate::{
     debug_ignore::DebugIgnore,
     graph::{
-        query_core::{all_visit_map, reachable_map, reachable_map_buffered_filter, QueryParams},
+        query_core::{
+            all_visit_map, depth_map_buffered_filter, reachable_map, reachable_map_buffered_filter,
+            QueryParams,
+        },
         DependencyDirection, GraphSpec,
     },
     petgraph_support::{
@@ -112,6 +115,41 @@ impl<G: GraphSpec> ResolveCore<G> {
         }
     }
 
+    /// Also returns the depth of each node, in nondecreasing order. If `max_depth` is specified,
+    /// only nodes up to that depth are included.
+    ///
+    /// The arguments to the edge filter are the (source, target, edge ix), unreversed.
+    #[allow(clippy::type_complexity)]
+    pub(super) fn with_depths<'g>(
+        graph: &'g Graph<G::Node, G::Edge, Directed, G::Ix>,
+        params: QueryParams<G>,
+        max_depth: Option<usize>,
+        filter: impl BufferedEdgeFilter<&'g Graph<G::Node, G::Edge, Directed, G::Ix>>,
+    ) -> (Self, Vec<(NodeIndex<G::Ix>, usize)>) {
+        let depths = match params {
+            QueryParams::Forward(initials) => depth_map_buffered_filter(
+                graph,
+                filter,
+                initials.into_inner().into_vec(),
+                max_depth,
+            ),
+            QueryParams::Reverse(initials) => depth_map_buffered_filter(
+                Reversed(graph),
+                ReversedBufferedFilter(filter),
+                initials.into_inner().into_vec(),
+                max_depth,
+            ),
+        };
+        let mut included = FixedBitSet::with_capacity(graph.node_count());
+        included.extend(depths.iter().map(|(ix, _)| ix.index()));
+        let core = Self {
+            included,
+            len: depths.len(),
+            _phantom: PhantomData,
+        };
+        (core, depths)
+    }
+
     pub(super) fn from_included<T: Into<FixedBitSet>>(included: T) -> Self {
         let included = included.into();
         let len = included.count_ones(..);
@@ -162,6 +200,15 @@ impl<G: GraphSpec> ResolveCore<G> {
         self.invalidate_caches();
     }
 
+    pub(super) fn is_subset(&self, other: &Self) -> bool {
+        // A larger set can never be a subset, so skip looking at the bits in that case.
+        self.len <= other.len && self.included.is_subset(&other.included)
+    }
+
+    pub(super) fn is_disjoint(&self, other: &Self) -> bool {
+        self.is_empty() || other.is_empty() || self.included.is_disjoint(&other.included)
+    }
+
     pub(super) fn invalidate_caches(&mut self) {
         self.len = self.included.count_ones(..);
     }
@@ -0,0 +1,70 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Deterministic random sampling from package graphs.
+
+use crate::graph::{PackageGraph, PackageSet};
+use fixedbitset::FixedBitSet;
+
+/// ## Seeded sampling
+///
+/// The methods in this section pick packages at random, based on a seed. Unlike the property
+/// testing helpers, they don't require any optional features, which makes them suitable for
+/// fuzzers and other test harnesses that need reproducible inputs.
+impl PackageGraph {
+    /// Returns a set of `n` packages chosen uniformly at random from this graph, based on `seed`.
+    ///
+    /// The same seed always produces the same set for the same graph. If `n` is greater than the
+    /// number of packages, every package is returned.
+    pub fn sample_packages(&self, seed: u64, n: usize) -> PackageSet<'_> {
+        let count = self.package_count();
+        let n = n.min(count);
+        let mut rng = SampleRng::new(seed);
+
+        // Partial Fisher-Yates shuffle: the first n indexes end up as a uniform random sample.
+        let mut indexes: Vec<usize> = (0..count).collect();
+        for i in 0..n {
+            let j = i + rng.next_index(count - i);
+            indexes.swap(i, j);
+        }
+
+        let mut included = FixedBitSet::with_capacity(count);
+        for &index in &indexes[..n] {
+            included.insert(index);
+        }
+        PackageSet::from_included(self, included)
+    }
+}
+
+/// A pseudorandom number generator for seeded sampling, using the SplitMix64 algorithm.
+///
+/// This is implemented here rather than pulled in from an external crate, so that the values
+/// generated for a seed don't change across versions of dependencies.
+#[derive(Clone, Debug)]
+pub(crate) struct SampleRng {
+    state: u64,
+}
+
+impl SampleRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a value in the range `0..bound`. `bound` must be greater than 0.
+    pub(crate) fn next_index(&mut self, bound: usize) -> usize {
+        // Multiply and keep the high bits, which is close enough to uniform for sampling purposes.
+        ((u128::from(self.next_u64()) * bound as u128) >> 64) as usize
+    }
+
+    pub(crate) fn next_bool(&mut self) -> bool {
+        self.next_u64() >> 63 == 1
+    }
+}
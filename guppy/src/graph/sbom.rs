@@ -0,0 +1,827 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Software bills of materials (SBOMs) for sets of packages.
+//!
+//! An SBOM lists the packages that make up a piece of software, along with their sources,
+//! checksums and licenses. [`SbomBuilder`] converts a [`PackageSet`] or a
+//! [`CargoSet`](crate::graph::cargo::CargoSet) into an SBOM in one of the formats listed in
+//! [`SbomFormat`].
+//!
+//! Requires the `sbom` feature to be enabled.
+
+use crate::{
+    graph::{
+        cargo::CargoSet,
+        checksums::{checksum_key, parse_lockfile_checksums, ChecksumKey},
+        DependencyDirection, ExternalSource, PackageGraph, PackageMetadata, PackageSet,
+    },
+    Error, PackageId,
+};
+use camino::Utf8PathBuf;
+use serde::Serialize;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    hash::{Hash, Hasher},
+    io,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The format of a software bill of materials.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum SbomFormat {
+    /// [CycloneDX 1.4](https://cyclonedx.org/docs/1.4/json/), in JSON format.
+    CycloneDx,
+
+    /// [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/), in JSON format.
+    Spdx,
+}
+
+impl SbomFormat {
+    /// Returns the name of this format, as accepted by [`FromStr`].
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SbomFormat::CycloneDx => "cyclonedx",
+            SbomFormat::Spdx => "spdx",
+        }
+    }
+}
+
+impl fmt::Display for SbomFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cyclonedx" => Ok(SbomFormat::CycloneDx),
+            "spdx" => Ok(SbomFormat::Spdx),
+            other => Err(format!(
+                "unknown SBOM format '{}' (expected 'cyclonedx' or 'spdx')",
+                other
+            )),
+        }
+    }
+}
+
+/// Generates a software bill of materials for a set of packages.
+///
+/// Each package is listed with its name, version, source, declared license and, for packages
+/// downloaded from a registry, its checksum as recorded in `Cargo.lock`. Dependency relationships
+/// between the packages in the set are included as well.
+///
+/// # Examples
+///
+/// ```
+/// use guppy::{graph::sbom::{SbomBuilder, SbomFormat}, CargoMetadata};
+///
+/// let metadata = CargoMetadata::parse_json(include_str!(
+///     "../../../fixtures/small/metadata1.json"
+/// )).unwrap();
+/// let package_graph = metadata.build_graph().unwrap();
+///
+/// let package_set = package_graph.query_workspace().resolve();
+/// let mut builder = SbomBuilder::new(&package_graph, package_set);
+/// builder.set_timestamp("2022-06-01T00:00:00Z");
+///
+/// let sbom = builder.build(SbomFormat::CycloneDx);
+/// assert_eq!(sbom["bomFormat"], "CycloneDX");
+/// ```
+#[derive(Clone, Debug)]
+pub struct SbomBuilder<'g> {
+    graph: &'g PackageGraph,
+    package_set: PackageSet<'g>,
+    name: Option<String>,
+    timestamp: Option<String>,
+    spdx_namespace: Option<String>,
+    checksums: HashMap<ChecksumKey, String>,
+}
+
+impl<'g> SbomBuilder<'g> {
+    /// Creates a new `SbomBuilder` for the given set of packages.
+    pub fn new(graph: &'g PackageGraph, package_set: PackageSet<'g>) -> Self {
+        Self {
+            graph,
+            package_set,
+            name: None,
+            timestamp: None,
+            spdx_namespace: None,
+            checksums: HashMap::new(),
+        }
+    }
+
+    /// Creates a new `SbomBuilder` for the packages that would be built by Cargo, on either the
+    /// target or the host platform.
+    pub fn from_cargo_set(cargo_set: &CargoSet<'g>) -> Self {
+        let package_set = cargo_set
+            .target_features()
+            .to_package_set()
+            .union(&cargo_set.host_features().to_package_set());
+        Self::new(cargo_set.package_graph(), package_set)
+    }
+
+    /// Sets the name of the software this SBOM describes.
+    ///
+    /// The default is the name of the workspace root directory.
+    pub fn set_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the time this SBOM was created at, as an RFC 3339 timestamp such as
+    /// `2022-06-01T00:00:00Z`.
+    ///
+    /// The default is the current time. Set this to produce reproducible output.
+    pub fn set_timestamp(&mut self, timestamp: impl Into<String>) -> &mut Self {
+        self.timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Sets the namespace of SPDX documents, which must be a unique URI for every document.
+    ///
+    /// The default is a URI under `https://spdx.org/spdxdocs/` derived from the name, the timestamp
+    /// and the set of packages.
+    pub fn set_spdx_namespace(&mut self, namespace: impl Into<String>) -> &mut Self {
+        self.spdx_namespace = Some(namespace.into());
+        self
+    }
+
+    /// Adds package checksums from the contents of a `Cargo.lock` file.
+    ///
+    /// Both the current lockfile format and the older format, which lists checksums in a
+    /// `[metadata]` section, are supported. Checksums added to the graph through
+    /// `PackageGraph::add_lockfile_checksums` are used for packages not listed here.
+    pub fn add_lockfile_checksums(&mut self, lockfile: &str) -> Result<&mut Self, Error> {
+        let path = self.lockfile_path();
+        let checksums = parse_lockfile_checksums(lockfile)
+            .map_err(|err| Error::SbomLockfileError(path, Box::new(err)))?;
+        self.checksums.extend(checksums);
+        Ok(self)
+    }
+
+    /// Reads package checksums from the `Cargo.lock` file at the root of the workspace.
+    pub fn read_lockfile_checksums(&mut self) -> Result<&mut Self, Error> {
+        let path = self.lockfile_path();
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| Error::SbomLockfileError(path, Box::new(err)))?;
+        self.add_lockfile_checksums(&contents)
+    }
+
+    /// Builds an SBOM in the given format, as a JSON value.
+    pub fn build(&self, format: SbomFormat) -> serde_json::Value {
+        let res = match format {
+            SbomFormat::CycloneDx => serde_json::to_value(self.cyclonedx()),
+            SbomFormat::Spdx => serde_json::to_value(self.spdx()),
+        };
+        res.expect("SBOM serialization is infallible")
+    }
+
+    /// Builds an SBOM in the given format, and writes it out as pretty-printed JSON.
+    pub fn write_json(&self, format: SbomFormat, writer: impl io::Write) -> Result<(), Error> {
+        let res = match format {
+            SbomFormat::CycloneDx => serde_json::to_writer_pretty(writer, &self.cyclonedx()),
+            SbomFormat::Spdx => serde_json::to_writer_pretty(writer, &self.spdx()),
+        };
+        res.map_err(Error::SbomWriteError)
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn lockfile_path(&self) -> Utf8PathBuf {
+        self.graph.workspace().root().join("Cargo.lock")
+    }
+
+    fn name(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            None => self
+                .graph
+                .workspace()
+                .root()
+                .file_name()
+                .unwrap_or("workspace"),
+        }
+    }
+
+    fn timestamp(&self) -> String {
+        match &self.timestamp {
+            Some(timestamp) => timestamp.clone(),
+            None => {
+                let secs = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs());
+                format_timestamp(secs)
+            }
+        }
+    }
+
+    fn checksum(&self, package: &PackageMetadata<'g>) -> Option<&str> {
+        // Checksums added to the builder take precedence over ones already in the graph.
+        checksum_key(package)
+            .and_then(|key| self.checksums.get(&key))
+            .map(|checksum| checksum.as_str())
+            .or_else(|| package.checksum())
+    }
+
+    /// Returns the dependencies of each package within the set, sorted by package ID.
+    fn dependencies(&self) -> BTreeMap<&'g PackageId, BTreeSet<&'g PackageId>> {
+        let mut dependencies: BTreeMap<_, BTreeSet<_>> = self
+            .package_set
+            .package_ids(DependencyDirection::Forward)
+            .map(|package_id| (package_id, BTreeSet::new()))
+            .collect();
+        for link in self.package_set.links(DependencyDirection::Forward) {
+            dependencies
+                .entry(link.from().id())
+                .or_default()
+                .insert(link.to().id());
+        }
+        dependencies
+    }
+
+    /// Returns the packages this SBOM describes: workspace members in the set, or the roots of the
+    /// set if there are none.
+    fn described_packages(&self) -> Vec<PackageMetadata<'g>> {
+        let members: Vec<_> = self
+            .package_set
+            .packages(DependencyDirection::Forward)
+            .filter(|package| package.in_workspace())
+            .collect();
+        if members.is_empty() {
+            self.package_set
+                .root_packages(DependencyDirection::Forward)
+                .collect()
+        } else {
+            members
+        }
+    }
+
+    fn cyclonedx(&self) -> CycloneDxBom<'_> {
+        let components = self
+            .package_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| {
+                let mut external_references = vec![];
+                if let Some(url) = package.homepage() {
+                    external_references.push(CycloneDxReference {
+                        reference_type: "website",
+                        url,
+                    });
+                }
+                if let Some(url) = package.repository() {
+                    external_references.push(CycloneDxReference {
+                        reference_type: "vcs",
+                        url,
+                    });
+                }
+                if let Some(url) = package.documentation() {
+                    external_references.push(CycloneDxReference {
+                        reference_type: "documentation",
+                        url,
+                    });
+                }
+
+                CycloneDxComponent {
+                    component_type: "library",
+                    bom_ref: package.id().repr(),
+                    name: package.name(),
+                    version: package.version().to_string(),
+                    description: package.description(),
+                    author: (!package.authors().is_empty()).then(|| package.authors().join(", ")),
+                    licenses: package
+                        .license()
+                        .map(|license| {
+                            vec![CycloneDxLicense {
+                                expression: license_expression(license),
+                            }]
+                        })
+                        .unwrap_or_default(),
+                    hashes: self
+                        .checksum(&package)
+                        .map(|checksum| {
+                            vec![CycloneDxHash {
+                                alg: "SHA-256",
+                                content: checksum,
+                            }]
+                        })
+                        .unwrap_or_default(),
+                    purl: purl(&package),
+                    external_references,
+                }
+            })
+            .collect();
+
+        let dependencies = self
+            .dependencies()
+            .into_iter()
+            .map(|(from, to)| CycloneDxDependency {
+                dependency_ref: from.repr(),
+                depends_on: to.into_iter().map(|to| to.repr()).collect(),
+            })
+            .collect();
+
+        CycloneDxBom {
+            bom_format: "CycloneDX",
+            spec_version: "1.4",
+            version: 1,
+            metadata: CycloneDxMetadata {
+                timestamp: self.timestamp(),
+                tools: vec![CycloneDxTool {
+                    name: "guppy",
+                    version: env!("CARGO_PKG_VERSION"),
+                }],
+                component: CycloneDxMetadataComponent {
+                    component_type: "application",
+                    name: self.name(),
+                },
+            },
+            components,
+            dependencies,
+        }
+    }
+
+    fn spdx(&self) -> SpdxDocument<'_> {
+        let spdx_ids: HashMap<_, _> = self
+            .package_set
+            .packages(DependencyDirection::Forward)
+            .enumerate()
+            .map(|(idx, package)| {
+                let spdx_id = format!(
+                    "SPDXRef-Package-{}-{}-{}",
+                    idx,
+                    spdx_id_component(package.name()),
+                    spdx_id_component(&package.version().to_string()),
+                );
+                (package.id(), spdx_id)
+            })
+            .collect();
+
+        let packages = self
+            .package_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| {
+                let license_declared = package
+                    .license()
+                    .map_or_else(|| NOASSERTION.to_owned(), license_expression);
+                SpdxPackage {
+                    name: package.name(),
+                    spdx_id: spdx_ids[package.id()].clone(),
+                    version_info: package.version().to_string(),
+                    download_location: download_location(&package),
+                    files_analyzed: false,
+                    license_concluded: NOASSERTION,
+                    license_declared,
+                    copyright_text: NOASSERTION,
+                    description: package.description(),
+                    homepage: package.homepage(),
+                    checksums: self
+                        .checksum(&package)
+                        .map(|checksum| {
+                            vec![SpdxChecksum {
+                                algorithm: "SHA256",
+                                checksum_value: checksum,
+                            }]
+                        })
+                        .unwrap_or_default(),
+                    external_refs: purl(&package)
+                        .map(|purl| {
+                            vec![SpdxExternalRef {
+                                reference_category: "PACKAGE-MANAGER",
+                                reference_type: "purl",
+                                reference_locator: purl,
+                            }]
+                        })
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let mut relationships: Vec<_> = self
+            .described_packages()
+            .into_iter()
+            .map(|package| SpdxRelationship {
+                spdx_element_id: "SPDXRef-DOCUMENT".to_owned(),
+                relationship_type: "DESCRIBES",
+                related_spdx_element: spdx_ids[package.id()].clone(),
+            })
+            .collect();
+        for (from, to) in self.dependencies() {
+            relationships.extend(to.into_iter().map(|to| SpdxRelationship {
+                spdx_element_id: spdx_ids[from].clone(),
+                relationship_type: "DEPENDS_ON",
+                related_spdx_element: spdx_ids[to].clone(),
+            }));
+        }
+
+        let timestamp = self.timestamp();
+        let namespace = match &self.spdx_namespace {
+            Some(namespace) => namespace.clone(),
+            None => {
+                let mut hasher = DefaultHasher::new();
+                self.name().hash(&mut hasher);
+                timestamp.hash(&mut hasher);
+                for package_id in self.package_set.package_ids(DependencyDirection::Forward) {
+                    package_id.hash(&mut hasher);
+                }
+                format!(
+                    "https://spdx.org/spdxdocs/{}-{:016x}",
+                    spdx_id_component(self.name()),
+                    hasher.finish()
+                )
+            }
+        };
+
+        SpdxDocument {
+            spdx_version: "SPDX-2.3",
+            data_license: "CC0-1.0",
+            spdx_id: "SPDXRef-DOCUMENT",
+            name: self.name(),
+            document_namespace: namespace,
+            creation_info: SpdxCreationInfo {
+                created: timestamp,
+                creators: vec![format!("Tool: guppy-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            packages,
+            relationships,
+        }
+    }
+}
+
+const NOASSERTION: &str = "NOASSERTION";
+
+/// Converts a license field into an SPDX license expression.
+///
+/// Older crates separate licenses with `/`, which is equivalent to `OR`.
+fn license_expression(license: &str) -> String {
+    if license.contains('/') {
+        license
+            .split('/')
+            .map(|license| license.trim())
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    } else {
+        license.to_owned()
+    }
+}
+
+/// Returns a [package URL](https://github.com/package-url/purl-spec) for a package, if it was
+/// downloaded from crates.io.
+fn purl(package: &PackageMetadata<'_>) -> Option<String> {
+    match package.source().parse_external()? {
+        ExternalSource::Registry(ExternalSource::CRATES_IO_URL) => Some(format!(
+            "pkg:cargo/{}@{}",
+            package.name(),
+            package.version()
+        )),
+        _ => None,
+    }
+}
+
+/// Returns the SPDX download location for a package.
+fn download_location(package: &PackageMetadata<'_>) -> String {
+    match package.source().parse_external() {
+        Some(ExternalSource::Registry(ExternalSource::CRATES_IO_URL)) => format!(
+            "https://crates.io/api/v1/crates/{}/{}/download",
+            package.name(),
+            package.version()
+        ),
+        Some(ExternalSource::Git {
+            repository,
+            resolved,
+            ..
+        }) => format!("git+{}@{}", repository, resolved),
+        _ => NOASSERTION.to_owned(),
+    }
+}
+
+/// SPDX identifiers may only contain letters, numbers, `.` and `-`.
+fn spdx_id_component(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Formats seconds since the Unix epoch as an RFC 3339 timestamp in UTC.
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let secs_of_day = secs % 86400;
+
+    // Convert days since the epoch to a civil date, from
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxBom<'a> {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: CycloneDxMetadata<'a>,
+    components: Vec<CycloneDxComponent<'a>>,
+    dependencies: Vec<CycloneDxDependency<'a>>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxMetadata<'a> {
+    timestamp: String,
+    tools: Vec<CycloneDxTool>,
+    component: CycloneDxMetadataComponent<'a>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxTool {
+    name: &'static str,
+    version: &'static str,
+}
+
+#[derive(Serialize)]
+struct CycloneDxMetadataComponent<'a> {
+    #[serde(rename = "type")]
+    component_type: &'static str,
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxComponent<'a> {
+    #[serde(rename = "type")]
+    component_type: &'static str,
+    #[serde(rename = "bom-ref")]
+    bom_ref: &'a str,
+    name: &'a str,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<CycloneDxLicense>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<CycloneDxHash<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_references: Vec<CycloneDxReference<'a>>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxLicense {
+    expression: String,
+}
+
+#[derive(Serialize)]
+struct CycloneDxHash<'a> {
+    alg: &'static str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+struct CycloneDxReference<'a> {
+    #[serde(rename = "type")]
+    reference_type: &'static str,
+    url: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxDependency<'a> {
+    #[serde(rename = "ref")]
+    dependency_ref: &'a str,
+    depends_on: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxDocument<'a> {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: &'a str,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage<'a>>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage<'a> {
+    name: &'a str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    version_info: String,
+    download_location: String,
+    files_analyzed: bool,
+    license_concluded: &'static str,
+    license_declared: String,
+    copyright_text: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    homepage: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<SpdxChecksum<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_refs: Vec<SpdxExternalRef>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum<'a> {
+    algorithm: &'static str,
+    checksum_value: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CargoMetadata;
+
+    fn metadata1() -> PackageGraph {
+        CargoMetadata::parse_json(include_str!("../../../fixtures/small/metadata1.json"))
+            .expect("fixture is valid JSON")
+            .build_graph()
+            .expect("fixture is a valid graph")
+    }
+
+    static LOCKFILE: &str = r#"
+[[package]]
+name = "testcrate"
+version = "0.1.0"
+dependencies = ["datatest"]
+
+[[package]]
+name = "datatest"
+version = "0.4.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "0123456789abcdef"
+
+[metadata]
+"checksum regex 1.3.1 (registry+https://github.com/rust-lang/crates.io-index)" = "fedcba9876543210"
+"checksum walkdir 2.2.9 (registry+https://github.com/rust-lang/crates.io-index)" = "<none>"
+"#;
+
+    fn component<'a>(sbom: &'a serde_json::Value, name: &str) -> &'a serde_json::Value {
+        sbom["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|component| component["name"] == name)
+            .unwrap_or_else(|| panic!("component {} not found", name))
+    }
+
+    #[test]
+    fn sbom_cyclonedx() {
+        let graph = metadata1();
+        let mut builder = SbomBuilder::new(&graph, graph.resolve_all());
+        builder
+            .set_timestamp("2022-06-01T00:00:00Z")
+            .add_lockfile_checksums(LOCKFILE)
+            .expect("lockfile is valid");
+        let sbom = builder.build(SbomFormat::CycloneDx);
+
+        assert_eq!(sbom["specVersion"], "1.4");
+        assert_eq!(sbom["metadata"]["timestamp"], "2022-06-01T00:00:00Z");
+        assert_eq!(sbom["metadata"]["component"]["name"], "testcrate");
+        assert_eq!(
+            sbom["components"].as_array().unwrap().len(),
+            graph.package_ids().len()
+        );
+
+        let datatest = component(&sbom, "datatest");
+        assert_eq!(datatest["purl"], "pkg:cargo/datatest@0.4.2");
+        assert_eq!(
+            datatest["licenses"][0]["expression"], "MIT OR Apache-2.0",
+            "legacy license separators are converted"
+        );
+        assert_eq!(datatest["hashes"][0]["content"], "0123456789abcdef");
+        assert_eq!(
+            component(&sbom, "regex")["hashes"][0]["content"],
+            "fedcba9876543210",
+            "checksums in [metadata] are read"
+        );
+        assert!(component(&sbom, "walkdir").get("hashes").is_none());
+
+        let testcrate = component(&sbom, "testcrate");
+        assert!(testcrate.get("purl").is_none());
+        let testcrate_deps = sbom["dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|dep| dep["ref"] == testcrate["bom-ref"])
+            .expect("testcrate has dependencies");
+        assert!(testcrate_deps["dependsOn"]
+            .as_array()
+            .unwrap()
+            .contains(&datatest["bom-ref"]));
+    }
+
+    #[test]
+    fn sbom_spdx() {
+        let graph = metadata1();
+        let mut builder = SbomBuilder::new(&graph, graph.resolve_all());
+        builder
+            .set_name("my project")
+            .set_timestamp("2022-06-01T00:00:00Z");
+        let sbom = builder.build(SbomFormat::Spdx);
+
+        assert_eq!(sbom["spdxVersion"], "SPDX-2.3");
+        assert_eq!(sbom["name"], "my project");
+        assert!(sbom["documentNamespace"]
+            .as_str()
+            .unwrap()
+            .starts_with("https://spdx.org/spdxdocs/my-project-"));
+        assert_eq!(
+            builder.build(SbomFormat::Spdx),
+            sbom,
+            "output is reproducible"
+        );
+
+        let packages = sbom["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), graph.package_ids().len());
+        let datatest = packages
+            .iter()
+            .find(|package| package["name"] == "datatest")
+            .unwrap();
+        assert_eq!(
+            datatest["downloadLocation"],
+            "https://crates.io/api/v1/crates/datatest/0.4.2/download"
+        );
+        assert_eq!(datatest["licenseDeclared"], "MIT OR Apache-2.0");
+
+        let relationships = sbom["relationships"].as_array().unwrap();
+        let describes: Vec<_> = relationships
+            .iter()
+            .filter(|rel| rel["relationshipType"] == "DESCRIBES")
+            .collect();
+        assert_eq!(describes.len(), 1, "only the workspace member is described");
+        assert!(relationships
+            .iter()
+            .any(|rel| rel["relationshipType"] == "DEPENDS_ON"
+                && rel["relatedSpdxElement"] == datatest["SPDXID"]));
+    }
+
+    #[test]
+    fn sbom_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+}
@@ -6,26 +6,46 @@
 //! Requires the `summaries` feature to be enabled.
 
 mod package_set;
+mod summary_set;
 
 use crate::{
     graph::{
         cargo::{CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform},
         feature::FeatureSet,
-        DependencyDirection, PackageGraph, PackageMetadata, PackageSet, PackageSource,
+        DependencyDirection, PackageGraph, PackageLink, PackageMetadata, PackageSet, PackageSource,
     },
     platform::PlatformSpecSummary,
     Error,
 };
 pub use guppy_summaries::*;
 pub use package_set::*;
+use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeSet;
+pub use summary_set::*;
 
 impl<'g> CargoSet<'g> {
     /// Creates a build summary with the given options.
     ///
+    /// The summary is in version 1 of the format. To use a newer version, call
+    /// [`to_summary_with_version`](Self::to_summary_with_version).
+    ///
     /// Requires the `summaries` feature to be enabled.
     pub fn to_summary(&self, opts: &CargoOptions<'_>) -> Result<Summary, Error> {
+        self.to_summary_with_version(opts, SummaryVersion::V1)
+    }
+
+    /// Creates a build summary with the given options, in the given version of the format.
+    ///
+    /// With version 2 and above, the summary also records the dependency edges followed while
+    /// building this `CargoSet`.
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_summary_with_version(
+        &self,
+        opts: &CargoOptions<'_>,
+        version: SummaryVersion,
+    ) -> Result<Summary, Error> {
         let initials = self.initials();
         let metadata =
             CargoOptionsSummary::new(initials.graph().package_graph, self.features_only(), opts)?;
@@ -36,11 +56,22 @@ impl<'g> CargoSet<'g> {
         summary.target_packages =
             target_features.to_package_map(initials, self.target_direct_deps());
         summary.host_packages = host_features.to_package_map(initials, self.host_direct_deps());
+        summary.version = version;
+        if version.supports_edges() {
+            summary.target_edges = to_edge_set(self.target_links());
+            summary.host_edges = to_edge_set(self.host_links());
+        }
 
         Ok(summary)
     }
 }
 
+fn to_edge_set<'g>(links: impl Iterator<Item = PackageLink<'g>>) -> EdgeSet {
+    links
+        .map(|link| SummaryEdge::new(link.from().to_summary_id(), link.to().to_summary_id()))
+        .collect()
+}
+
 impl<'g> FeatureSet<'g> {
     /// Creates a `PackageMap` from this `FeatureSet`.
     ///
@@ -157,6 +188,14 @@ pub struct CargoOptionsSummary {
     #[serde(skip_serializing_if = "PackageSetSummary::is_empty", default)]
     pub omitted_packages: PackageSetSummary,
 
+    /// The Rust version builds were simulated with, if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub rust_version: Option<Version>,
+
+    /// Whether `rust-version` constraints were ignored.
+    #[serde(skip_serializing_if = "is_false", default)]
+    pub ignore_rust_version: bool,
+
     /// The packages that formed the features-only set.
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub features_only: Vec<FeaturesOnlySummary>,
@@ -197,6 +236,8 @@ impl CargoOptionsSummary {
             host_platform: PlatformSpecSummary::new(&opts.host_platform),
             target_platform: PlatformSpecSummary::new(&opts.target_platform),
             omitted_packages,
+            rust_version: opts.rust_version.clone(),
+            ignore_rust_version: opts.ignore_rust_version,
             features_only,
         })
     }
@@ -225,6 +266,8 @@ impl CargoOptionsSummary {
             .set_target_platform(self.target_platform.to_platform_spec().map_err(|err| {
                 Error::TargetSpecError("parsing target platform".to_string(), err)
             })?)
+            .set_rust_version(self.rust_version.clone())
+            .set_ignore_rust_version(self.ignore_rust_version)
             .add_omitted_packages(omitted_packages.package_ids(DependencyDirection::Forward));
         Ok(options)
     }
@@ -321,6 +364,10 @@ impl<'g> PartialEq<SummarySource> for PackageSource<'g> {
     }
 }
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -341,5 +388,7 @@ proc-macros-on-target = false
             InitialsPlatform::from(summary.initials_platform),
             InitialsPlatform::Standard
         );
+        assert_eq!(summary.rust_version, None);
+        assert!(!summary.ignore_rust_version);
     }
 }
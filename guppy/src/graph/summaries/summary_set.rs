@@ -0,0 +1,316 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    graph::{
+        feature::{FeatureId, FeatureLabel, FeatureSet},
+        DependencyDirection, PackageGraph, PackageMetadata, PackageSet,
+    },
+    Error,
+};
+use guppy_summaries::{SummaryId, SummarySource};
+use smallvec::SmallVec;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A set of packages and features that isn't tied to a specific `PackageGraph`.
+///
+/// Set operations on [`PackageSet`] and [`FeatureSet`] require both sets to come from the same
+/// graph. A `SummarySet` identifies packages by [`SummaryId`] instead, so sets obtained from
+/// different graphs -- for example, the same workspace at two different commits -- can be combined
+/// and then resolved against any graph.
+///
+/// A package is considered to be in a `SummarySet` if at least one of its features is. Sets
+/// converted from a `PackageSet` contain the base feature of every package.
+///
+/// Requires the `summaries` feature to be enabled.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SummarySet {
+    // Invariant: feature sets are never empty.
+    features: BTreeMap<SummaryId, BTreeSet<OwnedFeatureLabel>>,
+}
+
+impl<'g> PackageSet<'g> {
+    /// Converts this `PackageSet` to a [`SummarySet`] containing the base feature for every
+    /// package.
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_summary_set(&self) -> SummarySet {
+        SummarySet::from_package_set(self)
+    }
+}
+
+impl<'g> FeatureSet<'g> {
+    /// Converts this `FeatureSet` to a [`SummarySet`].
+    ///
+    /// Requires the `summaries` feature to be enabled.
+    pub fn to_summary_set(&self) -> SummarySet {
+        SummarySet::from_feature_set(self)
+    }
+}
+
+impl SummarySet {
+    /// Creates a new, empty `SummarySet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a `SummarySet` from a [`PackageSet`], containing the base feature for every
+    /// package.
+    pub fn from_package_set(package_set: &PackageSet<'_>) -> Self {
+        let features = package_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| {
+                let mut labels = BTreeSet::new();
+                labels.insert(OwnedFeatureLabel::Base);
+                (package.to_summary_id(), labels)
+            })
+            .collect();
+        Self { features }
+    }
+
+    /// Constructs a `SummarySet` from a [`FeatureSet`].
+    pub fn from_feature_set(feature_set: &FeatureSet<'_>) -> Self {
+        let features = feature_set
+            .packages_with_features(DependencyDirection::Forward)
+            .map(|feature_list| {
+                let labels = feature_list
+                    .labels()
+                    .iter()
+                    .map(|&label| OwnedFeatureLabel::new(label))
+                    .collect();
+                (feature_list.package().to_summary_id(), labels)
+            })
+            .collect();
+        Self { features }
+    }
+
+    /// Returns the number of packages in this set.
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    /// Returns true if no packages are in this set.
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+
+    /// Returns true if this set contains the given package.
+    pub fn contains_package(&self, summary_id: &SummaryId) -> bool {
+        self.features.contains_key(summary_id)
+    }
+
+    /// Returns true if this set contains the given feature for a package.
+    pub fn contains(&self, summary_id: &SummaryId, label: FeatureLabel<'_>) -> bool {
+        self.features.get(summary_id).map_or(false, |labels| {
+            labels.contains(&OwnedFeatureLabel::new(label))
+        })
+    }
+
+    /// Iterates over the summary IDs of all packages in this set, in sorted order.
+    pub fn summary_ids(&self) -> impl ExactSizeIterator<Item = &SummaryId> {
+        self.features.keys()
+    }
+
+    /// Iterates over the features for a package in this set, in sorted order.
+    ///
+    /// Returns `None` if the package isn't in this set.
+    pub fn features_for(
+        &self,
+        summary_id: &SummaryId,
+    ) -> Option<impl ExactSizeIterator<Item = FeatureLabel<'_>>> {
+        self.features
+            .get(summary_id)
+            .map(|labels| labels.iter().map(|label| label.as_label()))
+    }
+
+    // ---
+    // Set operations
+    // ---
+
+    /// Returns a `SummarySet` that contains all features present in at least one of `self` and
+    /// `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut res = self.clone();
+        for (summary_id, labels) in &other.features {
+            res.features
+                .entry(summary_id.clone())
+                .or_default()
+                .extend(labels.iter().cloned());
+        }
+        res
+    }
+
+    /// Returns a `SummarySet` that contains all features present in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let features = self
+            .features
+            .iter()
+            .filter_map(|(summary_id, labels)| {
+                let other_labels = other.features.get(summary_id)?;
+                let labels: BTreeSet<_> = labels.intersection(other_labels).cloned().collect();
+                (!labels.is_empty()).then(|| (summary_id.clone(), labels))
+            })
+            .collect();
+        Self { features }
+    }
+
+    /// Returns a `SummarySet` that contains all features present in `self` but not `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        let features = self
+            .features
+            .iter()
+            .filter_map(|(summary_id, labels)| {
+                let labels: BTreeSet<_> = match other.features.get(summary_id) {
+                    Some(other_labels) => labels.difference(other_labels).cloned().collect(),
+                    None => labels.clone(),
+                };
+                (!labels.is_empty()).then(|| (summary_id.clone(), labels))
+            })
+            .collect();
+        Self { features }
+    }
+
+    /// Returns a `SummarySet` that contains all features present in exactly one of `self` and
+    /// `other`.
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.difference(other).union(&other.difference(self))
+    }
+
+    // ---
+    // Resolution
+    // ---
+
+    /// Splits this set into the features that are known to `graph`, and the ones that aren't.
+    ///
+    /// The first `SummarySet` can always be resolved against `graph`.
+    pub fn partition_known(&self, graph: &PackageGraph) -> (Self, Self) {
+        let index = SummaryIdIndex::new(graph);
+        let feature_graph = graph.feature_graph();
+
+        let mut known = Self::new();
+        let mut unknown = Self::new();
+        for (summary_id, labels) in &self.features {
+            let feature_list = index
+                .get(summary_id)
+                .map(|package| feature_graph.all_features_for(package.id()))
+                .transpose()
+                .expect("package IDs obtained from the graph are valid");
+            let (known_labels, unknown_labels): (BTreeSet<_>, BTreeSet<_>) =
+                labels.iter().cloned().partition(|label| {
+                    feature_list
+                        .as_ref()
+                        .map_or(false, |list| list.contains(label.as_label()))
+                });
+            if !known_labels.is_empty() {
+                known.features.insert(summary_id.clone(), known_labels);
+            }
+            if !unknown_labels.is_empty() {
+                unknown.features.insert(summary_id.clone(), unknown_labels);
+            }
+        }
+
+        (known, unknown)
+    }
+
+    /// Resolves the packages in this set against `graph`, returning a [`PackageSet`].
+    ///
+    /// Returns an error if any packages weren't found in `graph`. To ignore unknown packages, call
+    /// [`partition_known`](Self::partition_known) first.
+    pub fn to_package_set<'g>(&self, graph: &'g PackageGraph) -> Result<PackageSet<'g>, Error> {
+        let index = SummaryIdIndex::new(graph);
+        let package_ids = self
+            .features
+            .keys()
+            .map(|summary_id| Ok(index.get_or_err(summary_id)?.id()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        graph.resolve_ids(package_ids)
+    }
+
+    /// Resolves the features in this set against `graph`, returning a [`FeatureSet`].
+    ///
+    /// Returns an error if any packages or features weren't found in `graph`. To ignore unknown
+    /// packages and features, call [`partition_known`](Self::partition_known) first.
+    pub fn to_feature_set<'g>(&self, graph: &'g PackageGraph) -> Result<FeatureSet<'g>, Error> {
+        let index = SummaryIdIndex::new(graph);
+        let mut feature_ids = Vec::new();
+        for (summary_id, labels) in &self.features {
+            let package_id = index.get_or_err(summary_id)?.id();
+            feature_ids.extend(
+                labels
+                    .iter()
+                    .map(|label| FeatureId::new(package_id, label.as_label())),
+            );
+        }
+        graph.feature_graph().resolve_ids(feature_ids)
+    }
+}
+
+/// An owned version of [`FeatureLabel`].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum OwnedFeatureLabel {
+    Base,
+    Named(String),
+    OptionalDependency(String),
+}
+
+impl OwnedFeatureLabel {
+    fn new(label: FeatureLabel<'_>) -> Self {
+        match label {
+            FeatureLabel::Base => OwnedFeatureLabel::Base,
+            FeatureLabel::Named(name) => OwnedFeatureLabel::Named(name.to_owned()),
+            FeatureLabel::OptionalDependency(name) => {
+                OwnedFeatureLabel::OptionalDependency(name.to_owned())
+            }
+        }
+    }
+
+    fn as_label(&self) -> FeatureLabel<'_> {
+        match self {
+            OwnedFeatureLabel::Base => FeatureLabel::Base,
+            OwnedFeatureLabel::Named(name) => FeatureLabel::Named(name),
+            OwnedFeatureLabel::OptionalDependency(name) => FeatureLabel::OptionalDependency(name),
+        }
+    }
+}
+
+/// Looks up packages by summary ID, with the same semantics as
+/// `PackageGraph::metadata_by_summary_id`.
+///
+/// `metadata_by_summary_id` does a linear search, which is too slow to do for every package in a
+/// set.
+struct SummaryIdIndex<'g> {
+    graph: &'g PackageGraph,
+    by_name: HashMap<&'g str, SmallVec<[PackageMetadata<'g>; 2]>>,
+}
+
+impl<'g> SummaryIdIndex<'g> {
+    fn new(graph: &'g PackageGraph) -> Self {
+        let mut by_name: HashMap<_, SmallVec<[_; 2]>> = HashMap::new();
+        for package in graph.packages() {
+            by_name.entry(package.name()).or_default().push(package);
+        }
+        Self { graph, by_name }
+    }
+
+    fn get(&self, summary_id: &SummaryId) -> Option<PackageMetadata<'g>> {
+        match &summary_id.source {
+            SummarySource::Workspace { workspace_path } => {
+                self.graph.workspace().member_by_path(workspace_path).ok()
+            }
+            _ => self
+                .by_name
+                .get(summary_id.name.as_str())?
+                .iter()
+                .find(|package| {
+                    package.version() == &summary_id.version
+                        && package.source() == summary_id.source
+                })
+                .copied(),
+        }
+    }
+
+    fn get_or_err(&self, summary_id: &SummaryId) -> Result<PackageMetadata<'g>, Error> {
+        self.get(summary_id)
+            .ok_or_else(|| Error::UnknownSummaryId(summary_id.clone()))
+    }
+}
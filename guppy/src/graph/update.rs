@@ -0,0 +1,117 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for updating a `PackageGraph` in place with new `cargo metadata` output.
+
+use crate::{
+    graph::{ArtifactDeps, PackageGraph},
+    CargoMetadata, Error, PackageId,
+};
+use cargo_metadata::{NodeDep, Package};
+
+impl PackageGraph {
+    /// Updates this graph with new `cargo metadata` output, reusing data for packages that
+    /// haven't changed.
+    ///
+    /// This is meant for long-running tools, such as IDE integrations or watch modes, that need to
+    /// keep a graph up to date as manifests are edited. The result is the same as constructing a
+    /// new graph through [`from_metadata`](Self::from_metadata), but:
+    /// * Packages from registries and Git repositories are reused as long as they resolve to the
+    ///   same dependencies, since they can't change without their package IDs changing.
+    /// * Workspace and path packages are reused if their entries in the metadata are unchanged.
+    /// * The cached feature graph and dependency cycles are kept if the change doesn't affect
+    ///   them. For example, editing the description of a workspace member invalidates neither,
+    ///   while adding a dependency invalidates both.
+    ///
+    /// Other clones of this graph are unaffected.
+    ///
+    /// Returns an error if the new metadata is invalid, in which case this graph is left
+    /// unchanged.
+    pub fn apply_metadata_update(
+        &mut self,
+        metadata: CargoMetadata,
+    ) -> Result<MetadataUpdate, Error> {
+        let (graph, update) = Self::build_impl(metadata, Some(self))?;
+        *self = graph;
+        Ok(update)
+    }
+}
+
+/// A summary of the work done by
+/// [`PackageGraph::apply_metadata_update`](PackageGraph::apply_metadata_update).
+#[derive(Clone, Debug)]
+pub struct MetadataUpdate {
+    pub(super) processed: Vec<PackageId>,
+    pub(super) removed: Vec<PackageId>,
+    pub(super) reused_count: usize,
+    pub(super) cycles_retained: bool,
+    pub(super) feature_graph_retained: bool,
+}
+
+impl MetadataUpdate {
+    /// Returns the packages that were processed from scratch, in sorted order.
+    ///
+    /// These are packages that are new, or whose metadata or resolved dependencies changed.
+    pub fn processed_packages(&self) -> &[PackageId] {
+        &self.processed
+    }
+
+    /// Returns the packages that are no longer part of the graph, in sorted order.
+    pub fn removed_packages(&self) -> &[PackageId] {
+        &self.removed
+    }
+
+    /// Returns the number of packages whose data was reused from the previous graph.
+    pub fn reused_count(&self) -> usize {
+        self.reused_count
+    }
+
+    /// Returns true if the dependency cycles, if previously computed, were carried over.
+    ///
+    /// Cycles are carried over if every package and dependency edge is unchanged.
+    pub fn cycles_retained(&self) -> bool {
+        self.cycles_retained
+    }
+
+    /// Returns true if the feature graph, if previously computed, was carried over.
+    ///
+    /// The feature graph is carried over if, in addition to packages and dependency edges, the
+    /// features and dependency specifications of every package are unchanged.
+    pub fn feature_graph_retained(&self) -> bool {
+        self.feature_graph_retained
+    }
+}
+
+/// The inputs a workspace or path package was built from.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LocalPackageInput {
+    package: Package,
+    // The resolved dependencies of this package, as (name, package ID) pairs.
+    resolved_deps: Vec<(String, String)>,
+    artifacts: ArtifactDeps,
+}
+
+impl LocalPackageInput {
+    pub(super) fn new(
+        package: &Package,
+        resolved_deps: &[NodeDep],
+        artifacts: &ArtifactDeps,
+    ) -> Self {
+        Self {
+            package: package.clone(),
+            resolved_deps: resolved_deps
+                .iter()
+                .map(|dep| (dep.name.clone(), dep.pkg.repr.clone()))
+                .collect(),
+            artifacts: artifacts.for_package(&package.id.repr),
+        }
+    }
+
+    /// Returns true if the dependency edges and features built from these inputs are the same.
+    pub(super) fn links_and_features_eq(&self, other: &Self) -> bool {
+        self.package.dependencies == other.package.dependencies
+            && self.package.features == other.package.features
+            && self.resolved_deps == other.resolved_deps
+            && self.artifacts == other.artifacts
+    }
+}
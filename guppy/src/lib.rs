@@ -48,10 +48,20 @@
 //!
 //! # Optional features
 //!
+//! * `lockfile`: Support for building a `PackageGraph` from `Cargo.lock` and `Cargo.toml` files
+//!   without running `cargo metadata`, through [`LockfileBuilder`](crate::LockfileBuilder), and
+//!   for adding and verifying package checksums from `Cargo.lock`.
+//! * `name-patterns`: Support for selecting workspace members by glob or regex patterns through
+//!   [`NamePattern`](crate::graph::NamePattern), and for enforcing dependency rules between
+//!   groups of them through [`Layers`](crate::graph::Layers).
+//! * `persist`: Support for persisting `cargo metadata` output to disk and reloading it as a
+//!   `PackageGraph`, to avoid running `cargo metadata` again if `Cargo.lock` hasn't changed.
 //! * `proptest1`: Support for [property-based testing](https://jessitron.com/2013/04/25/property-based-testing-what-is-it/)
 //!   using the [`proptest`](https://altsysrq.github.io/proptest-book/intro.html) framework.
 //! * `rayon1`: Support for parallel iterators through [Rayon](docs.rs/rayon/1) (preliminary work
 //!   so far, more parallel iterators to be added in the future).
+//! * `sbom`: Support for generating software bills of materials in the CycloneDX and SPDX formats,
+//!   through [`SbomBuilder`](crate::graph::sbom::SbomBuilder).
 //! * `summaries`: Support for writing out [build summaries](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy-summaries).
 //!
 //! # Examples
@@ -96,7 +106,10 @@ pub use debug_ignore;
 mod dependency_kind;
 pub mod errors;
 pub mod graph;
+#[cfg(feature = "lockfile")]
+mod lockfile;
 mod metadata_command;
+mod metadata_compat;
 mod package_id;
 pub(crate) mod petgraph_support;
 pub mod platform;
@@ -106,6 +119,8 @@ mod unit_tests;
 
 pub use dependency_kind::*;
 pub use errors::Error;
+#[cfg(feature = "lockfile")]
+pub use lockfile::LockfileBuilder;
 pub use metadata_command::*;
 pub use package_id::PackageId;
 
@@ -0,0 +1,1388 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Building package graphs from `Cargo.lock` and `Cargo.toml` files, without running
+//! `cargo metadata`.
+
+use crate::{
+    graph::{cargo_version_matches, PackageGraph},
+    CargoMetadata, Error,
+};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use glob::Pattern;
+use once_cell::sync::Lazy;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    env, fs,
+    path::PathBuf,
+};
+use toml::{value::Table, Value as TomlValue};
+
+static CRATES_IO_SOURCE: &str = "registry+https://github.com/rust-lang/crates.io-index";
+
+static EMPTY_TABLE: Lazy<Table> = Lazy::new(Table::new);
+
+/// A builder for constructing a `PackageGraph` from `Cargo.lock` and the `Cargo.toml` files in a
+/// workspace, without running `cargo metadata`.
+///
+/// This is useful in environments where running `cargo metadata` is slow or not possible, for
+/// example in CI sandboxes without network access. Since `Cargo.lock` records much less
+/// information than `cargo metadata`, the resulting graph may have reduced fidelity:
+///
+/// * Workspace members and path dependencies are read from their `Cargo.toml` files, and are
+///   represented accurately. Build targets are inferred from `Cargo.toml` and from the standard
+///   file layout, the same way Cargo does.
+/// * Registry packages are read from `Cargo.toml` files in Cargo's download cache if they're
+///   present there. See [`cargo_home`](Self::cargo_home).
+/// * Other packages, such as registry packages that haven't been downloaded and Git
+///   dependencies, only have a library target and no features. All of their dependencies are
+///   treated as normal, non-optional dependencies with exact version requirements. The manifest
+///   paths for these packages don't exist on disk.
+///
+/// Cargo configuration files, such as `.cargo/config.toml`, aren't read.
+///
+/// Requires the `lockfile` feature to be enabled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use guppy::LockfileBuilder;
+///
+/// let package_graph = LockfileBuilder::new("path/to/workspace").build_graph().unwrap();
+/// for package in package_graph.workspace().iter() {
+///     println!("workspace member: {}", package.name());
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LockfileBuilder {
+    workspace_root: Utf8PathBuf,
+    cargo_home: CargoHome,
+}
+
+#[derive(Clone, Debug)]
+enum CargoHome {
+    Default,
+    Path(Utf8PathBuf),
+    Disabled,
+}
+
+impl LockfileBuilder {
+    /// Creates a new builder for the workspace at the given root directory.
+    ///
+    /// The directory must contain `Cargo.toml` and `Cargo.lock`.
+    pub fn new(workspace_root: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            workspace_root: workspace_root.into(),
+            cargo_home: CargoHome::Default,
+        }
+    }
+
+    /// Sets the Cargo home directory, used to look up the `Cargo.toml` files of registry packages
+    /// that have already been downloaded.
+    ///
+    /// If unset, this will use the `$CARGO_HOME` environment variable, or else `$HOME/.cargo`.
+    pub fn cargo_home(&mut self, path: impl Into<Utf8PathBuf>) -> &mut Self {
+        self.cargo_home = CargoHome::Path(path.into());
+        self
+    }
+
+    /// Disables looking up registry packages in Cargo's download cache.
+    ///
+    /// With this option, the result only depends on the files in the workspace and its path
+    /// dependencies.
+    pub fn no_registry_cache(&mut self) -> &mut Self {
+        self.cargo_home = CargoHome::Disabled;
+        self
+    }
+
+    /// Reads `Cargo.lock` and the `Cargo.toml` files in the workspace, and returns a
+    /// `CargoMetadata` equivalent to what `cargo metadata --all-features` would produce, with the
+    /// caveats listed in the [type documentation](Self).
+    pub fn exec(&self) -> Result<CargoMetadata, Error> {
+        self.exec_impl().map(|(metadata, _)| metadata)
+    }
+
+    /// Reads `Cargo.lock` and the `Cargo.toml` files in the workspace, and returns a
+    /// `PackageGraph`.
+    ///
+    /// Unlike graphs built from `cargo metadata`, this graph has the checksums of registry
+    /// packages in `Cargo.lock`. For more, see
+    /// [`PackageMetadata::checksum`](crate::graph::PackageMetadata::checksum).
+    pub fn build_graph(&self) -> Result<PackageGraph, Error> {
+        let (metadata, lockfile) = self.exec_impl()?;
+        let mut graph = metadata.build_graph()?;
+        graph.add_lockfile_checksums(&lockfile)?;
+        Ok(graph)
+    }
+
+    /// Returns the metadata along with the contents of `Cargo.lock`.
+    fn exec_impl(&self) -> Result<(CargoMetadata, String), Error> {
+        let root = fs::canonicalize(&self.workspace_root)
+            .map_err(|err| Error::LockfileReadError(self.workspace_root.clone(), Box::new(err)))?;
+        let root = Utf8PathBuf::try_from(root)
+            .map_err(|err| Error::LockfileReadError(self.workspace_root.clone(), Box::new(err)))?;
+        let cargo_home = match &self.cargo_home {
+            CargoHome::Default => env::var_os("CARGO_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))
+                .and_then(|path| Utf8PathBuf::try_from(path).ok()),
+            CargoHome::Path(path) => Some(path.clone()),
+            CargoHome::Disabled => None,
+        };
+
+        let lockfile_path = root.join("Cargo.lock");
+        let contents = read_file(&lockfile_path)?;
+        let lockfile: RawLockfile = toml::from_str(&contents)
+            .map_err(|err| Error::LockfileReadError(lockfile_path.clone(), Box::new(err)))?;
+
+        let workspace = WorkspaceInfo::discover(root)?;
+        let resolver = LockResolver::new(&workspace, lockfile.package, cargo_home.as_deref())?;
+        let metadata = resolver.into_metadata(&workspace)?;
+        Ok((metadata, contents))
+    }
+}
+
+impl<'a> TryFrom<&'a LockfileBuilder> for PackageGraph {
+    type Error = Error;
+
+    fn try_from(builder: &'a LockfileBuilder) -> Result<Self, Self::Error> {
+        builder.build_graph()
+    }
+}
+
+// ---
+// Cargo.lock parsing
+// ---
+
+#[derive(Deserialize)]
+struct RawLockfile {
+    #[serde(default)]
+    package: Vec<LockPackage>,
+}
+
+#[derive(Deserialize)]
+struct LockPackage {
+    name: String,
+    version: Version,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+impl LockPackage {
+    /// Returns the keys that dependency strings in `Cargo.lock` can use to refer to this entry.
+    /// Dependency strings look like "name", "name version" or "name version (source)", depending
+    /// on what's required to disambiguate them.
+    fn dep_keys(&self) -> impl Iterator<Item = DepKey> + '_ {
+        let name_only = DepKey {
+            name: self.name.clone(),
+            version: None,
+            source: None,
+        };
+        let with_version = DepKey {
+            version: Some(self.version.clone()),
+            ..name_only.clone()
+        };
+        let with_source = self.source.as_ref().map(|source| DepKey {
+            source: Some(source.clone()),
+            ..with_version.clone()
+        });
+        std::iter::once(name_only)
+            .chain(std::iter::once(with_version))
+            .chain(with_source)
+    }
+}
+
+/// A parsed dependency string from `Cargo.lock`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct DepKey {
+    name: String,
+    version: Option<Version>,
+    source: Option<String>,
+}
+
+impl DepKey {
+    /// Parses a dependency string, returning `None` if the version is invalid.
+    fn parse(dep_str: &str) -> Option<Self> {
+        let mut parts = dep_str.splitn(3, ' ');
+        let name = parts.next()?.to_owned();
+        let version = match parts.next() {
+            Some(version) => Some(Version::parse(version).ok()?),
+            None => None,
+        };
+        let source = parts.next().map(|source| {
+            source
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .to_owned()
+        });
+        Some(Self {
+            name,
+            version,
+            source,
+        })
+    }
+}
+
+// ---
+// Workspace discovery
+// ---
+
+/// The workspace root, along with every package read from a `Cargo.toml` on disk.
+struct WorkspaceInfo {
+    root: Utf8PathBuf,
+    root_manifest: Table,
+    local_packages: Vec<ManifestPackage>,
+}
+
+impl WorkspaceInfo {
+    fn discover(root: Utf8PathBuf) -> Result<Self, Error> {
+        let root_manifest = read_manifest(&root.join("Cargo.toml"))?;
+        let mut info = Self {
+            root,
+            root_manifest,
+            local_packages: vec![],
+        };
+
+        // Queue up workspace members first, then path dependencies as they're found.
+        let mut queue: Vec<Utf8PathBuf> = vec![];
+        if info.root_manifest.contains_key("package") {
+            queue.push(info.root.clone());
+        }
+        queue.extend(info.member_dirs()?);
+        // [patch] sections may also point to local packages.
+        for patches in info.table("patch").values().filter_map(TomlValue::as_table) {
+            for (key, value) in patches {
+                let spec = DepSpec::parse(&info, key, value, &info.root, None, None)?;
+                queue.extend(spec.path);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        while let Some(dir) = queue.pop() {
+            if !seen.insert(dir.clone()) {
+                continue;
+            }
+            let manifest = read_manifest(&dir.join("Cargo.toml"))?;
+            let package = ManifestPackage::new(&info, dir.join("Cargo.toml"), manifest, None)?;
+            for spec in &package.deps {
+                queue.extend(spec.path.clone());
+            }
+            info.local_packages.push(package);
+        }
+
+        let members: Vec<_> = info
+            .local_packages
+            .iter()
+            .map(|package| info.is_member(package.dir()))
+            .collect();
+        for (package, is_member) in info.local_packages.iter_mut().zip(members) {
+            package.is_member = is_member;
+        }
+        Ok(info)
+    }
+
+    fn is_workspace(&self) -> bool {
+        self.root_manifest.contains_key("workspace")
+    }
+
+    /// Returns a table in `[workspace]`, or an empty table if it isn't present.
+    fn workspace_table(&self, key: &str) -> &Table {
+        self.table("workspace")
+            .get(key)
+            .and_then(TomlValue::as_table)
+            .unwrap_or(&EMPTY_TABLE)
+    }
+
+    /// Returns a top-level table in the root `Cargo.toml`, or an empty table if it isn't present.
+    fn table(&self, key: &str) -> &Table {
+        self.root_manifest
+            .get(key)
+            .and_then(TomlValue::as_table)
+            .unwrap_or(&EMPTY_TABLE)
+    }
+
+    fn workspace_paths(&self, key: &str) -> Vec<&str> {
+        self.table("workspace")
+            .get(key)
+            .and_then(TomlValue::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(TomlValue::as_str)
+            .collect()
+    }
+
+    /// Expands `workspace.members`, including glob patterns.
+    fn member_dirs(&self) -> Result<Vec<Utf8PathBuf>, Error> {
+        let mut dirs = vec![];
+        for pattern in self.workspace_paths("members") {
+            let mut candidates = vec![self.root.clone()];
+            let is_glob = is_glob_pattern(pattern);
+            for component in Utf8Path::new(pattern).components() {
+                let component = component.as_str();
+                if !is_glob_pattern(component) {
+                    candidates = candidates
+                        .into_iter()
+                        .map(|dir| normalize_path(&dir.join(component)))
+                        .collect();
+                    continue;
+                }
+                let component = Pattern::new(component).map_err(|err| {
+                    Error::LockfileResolveError(format!(
+                        "{}: invalid pattern `{}` in workspace.members: {}",
+                        self.root.join("Cargo.toml"),
+                        pattern,
+                        err
+                    ))
+                })?;
+                let mut next = vec![];
+                for dir in candidates {
+                    let entries = match dir.read_dir_utf8() {
+                        Ok(entries) => entries,
+                        Err(_) => continue,
+                    };
+                    for entry in entries.flatten() {
+                        if entry.path().is_dir() && component.matches(entry.file_name()) {
+                            next.push(entry.path().to_path_buf());
+                        }
+                    }
+                }
+                next.sort_unstable();
+                candidates = next;
+            }
+            // Glob patterns may match directories that aren't packages.
+            dirs.extend(
+                candidates
+                    .into_iter()
+                    .filter(|dir| !is_glob || dir.join("Cargo.toml").is_file())
+                    .filter(|dir| !self.is_excluded(dir)),
+            );
+        }
+        Ok(dirs)
+    }
+
+    fn is_excluded(&self, dir: &Utf8Path) -> bool {
+        self.workspace_paths("exclude")
+            .into_iter()
+            .any(|exclude| dir.starts_with(normalize_path(&self.root.join(exclude))))
+    }
+
+    /// Path dependencies within the workspace directory are members, unless they're excluded.
+    fn is_member(&self, dir: &Utf8Path) -> bool {
+        if dir == self.root {
+            return true;
+        }
+        self.is_workspace() && dir.starts_with(&self.root) && !self.is_excluded(dir)
+    }
+}
+
+/// A package read from its `Cargo.toml`.
+struct ManifestPackage {
+    manifest_path: Utf8PathBuf,
+    manifest: Table,
+    name: String,
+    version: Version,
+    // Set for registry packages read from Cargo's cache.
+    source: Option<String>,
+    deps: Vec<DepSpec>,
+    is_member: bool,
+}
+
+impl ManifestPackage {
+    fn new(
+        workspace: &WorkspaceInfo,
+        manifest_path: Utf8PathBuf,
+        manifest: Table,
+        source: Option<String>,
+    ) -> Result<Self, Error> {
+        let dir = manifest_path
+            .parent()
+            .expect("manifest path has a parent")
+            .to_path_buf();
+        let mut package = Self {
+            manifest_path,
+            manifest,
+            name: String::new(),
+            version: Version::new(0, 0, 0),
+            source,
+            deps: vec![],
+            is_member: false,
+        };
+        package.name = match package.field(workspace, "name") {
+            Some(TomlValue::String(name)) => name,
+            _ => return Err(package.error("missing `package.name`")),
+        };
+        if let Some(version) = package.field(workspace, "version") {
+            package.version = version
+                .as_str()
+                .and_then(|version| Version::parse(version).ok())
+                .ok_or_else(|| package.error("invalid `package.version`"))?;
+        }
+
+        let mut deps = vec![];
+        let mut add_deps = |table: &Table, target: Option<&str>| -> Result<(), Error> {
+            for (section, kind) in DEP_SECTIONS {
+                let section = match table.get(*section).and_then(TomlValue::as_table) {
+                    Some(section) => section,
+                    None => continue,
+                };
+                for (key, value) in section {
+                    deps.push(DepSpec::parse(workspace, key, value, &dir, *kind, target)?);
+                }
+            }
+            Ok(())
+        };
+        add_deps(&package.manifest, None)?;
+        if let Some(targets) = package.manifest.get("target").and_then(TomlValue::as_table) {
+            for (target, table) in targets {
+                if let Some(table) = table.as_table() {
+                    add_deps(table, Some(target))?;
+                }
+            }
+        }
+        package.deps = deps;
+        Ok(package)
+    }
+
+    fn dir(&self) -> &Utf8Path {
+        self.manifest_path
+            .parent()
+            .expect("manifest path has a parent")
+    }
+
+    fn error(&self, message: &str) -> Error {
+        Error::LockfileResolveError(format!("{}: {}", self.manifest_path, message))
+    }
+
+    fn package_table(&self) -> Option<&Table> {
+        self.manifest.get("package").and_then(TomlValue::as_table)
+    }
+
+    /// Returns a field in `[package]`, looking it up in `[workspace.package]` if it's inherited.
+    fn field(&self, workspace: &WorkspaceInfo, key: &str) -> Option<TomlValue> {
+        let value = self.package_table()?.get(key)?;
+        if is_inherited(value) {
+            workspace.workspace_table("package").get(key).cloned()
+        } else {
+            Some(value.clone())
+        }
+    }
+
+    fn str_field(&self, workspace: &WorkspaceInfo, key: &str) -> Option<String> {
+        self.field(workspace, key)
+            .and_then(|value| value.as_str().map(str::to_owned))
+    }
+
+    fn lib_name(&self) -> String {
+        self.manifest
+            .get("lib")
+            .and_then(|lib| lib.get("name"))
+            .and_then(TomlValue::as_str)
+            .map_or_else(|| self.name.replace('-', "_"), str::to_owned)
+    }
+
+    fn to_json(
+        &self,
+        workspace: &WorkspaceInfo,
+        id: &str,
+        dependencies: Vec<JsonValue>,
+    ) -> JsonValue {
+        let edition = self
+            .str_field(workspace, "edition")
+            .unwrap_or_else(|| "2015".to_owned());
+        let publish = match self.field(workspace, "publish") {
+            Some(TomlValue::Boolean(false)) => json!([]),
+            Some(TomlValue::Array(registries)) => json!(registries),
+            _ => JsonValue::Null,
+        };
+        let features: BTreeMap<_, _> = self
+            .manifest
+            .get("features")
+            .and_then(TomlValue::as_table)
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), string_array(Some(value))))
+            .collect();
+        let metadata = self
+            .package_table()
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| serde_json::to_value(metadata).ok())
+            .unwrap_or(JsonValue::Null);
+
+        json!({
+            "name": self.name,
+            "version": self.version.to_string(),
+            "id": id,
+            "source": self.source,
+            "authors": string_array(self.field(workspace, "authors").as_ref()),
+            "description": self.str_field(workspace, "description"),
+            "dependencies": dependencies,
+            "license": self.str_field(workspace, "license"),
+            "license_file": self.str_field(workspace, "license-file"),
+            "targets": self.targets(&edition),
+            "features": features,
+            "manifest_path": self.manifest_path,
+            "categories": string_array(self.field(workspace, "categories").as_ref()),
+            "keywords": string_array(self.field(workspace, "keywords").as_ref()),
+            "readme": self.str_field(workspace, "readme"),
+            "repository": self.str_field(workspace, "repository"),
+            "homepage": self.str_field(workspace, "homepage"),
+            "documentation": self.str_field(workspace, "documentation"),
+            "edition": edition,
+            "metadata": metadata,
+            "links": self.str_field(workspace, "links"),
+            "publish": publish,
+            "default_run": self.str_field(workspace, "default-run"),
+            "rust_version": self.str_field(workspace, "rust-version"),
+        })
+    }
+
+    /// Infers build targets the same way Cargo does: from explicit sections in `Cargo.toml`, and
+    /// from the standard file layout.
+    fn targets(&self, edition: &str) -> Vec<JsonValue> {
+        let dir = self.dir();
+        let package = self.package_table();
+        let auto = |key: &str| {
+            package
+                .and_then(|package| package.get(key))
+                .and_then(TomlValue::as_bool)
+                .unwrap_or(true)
+        };
+        let mut targets = vec![];
+
+        let lib = self.manifest.get("lib").and_then(TomlValue::as_table);
+        let lib_path = lib
+            .and_then(|lib| lib.get("path"))
+            .and_then(TomlValue::as_str)
+            .map_or_else(|| dir.join("src/lib.rs"), |path| dir.join(path));
+        if lib.is_some() || lib_path.is_file() {
+            let is_proc_macro = lib.map_or(false, |lib| {
+                ["proc-macro", "proc_macro"]
+                    .iter()
+                    .any(|key| lib.get(*key).and_then(TomlValue::as_bool) == Some(true))
+            });
+            let kinds = if is_proc_macro {
+                vec!["proc-macro".to_owned()]
+            } else {
+                let crate_types = ["crate-type", "crate_type"]
+                    .iter()
+                    .find_map(|key| lib.and_then(|lib| lib.get(*key)));
+                match string_array(crate_types) {
+                    crate_types if crate_types.is_empty() => vec!["lib".to_owned()],
+                    crate_types => crate_types,
+                }
+            };
+            targets.push(target_json(
+                &self.lib_name(),
+                &kinds,
+                &[],
+                &lib_path,
+                edition,
+            ));
+        }
+
+        let mut auto_bins = vec![];
+        if auto("autobins") {
+            let main_path = dir.join("src/main.rs");
+            if main_path.is_file() {
+                auto_bins.push((self.name.clone(), main_path));
+            }
+            auto_bins.extend(auto_targets(&dir.join("src/bin")));
+        }
+        self.add_targets(&mut targets, "bin", "src/bin", auto_bins, edition);
+        for (kind, auto_key, subdir) in [
+            ("example", "autoexamples", "examples"),
+            ("test", "autotests", "tests"),
+            ("bench", "autobenches", "benches"),
+        ] {
+            let auto_found = if auto(auto_key) {
+                auto_targets(&dir.join(subdir))
+            } else {
+                vec![]
+            };
+            self.add_targets(&mut targets, kind, subdir, auto_found, edition);
+        }
+
+        let build_path = match package.and_then(|package| package.get("build")) {
+            Some(TomlValue::String(path)) => Some(dir.join(path)),
+            Some(TomlValue::Boolean(false)) => None,
+            _ => Some(dir.join("build.rs")).filter(|path| path.is_file()),
+        };
+        if let Some(build_path) = build_path {
+            targets.push(target_json(
+                "build-script-build",
+                &["custom-build".to_owned()],
+                &[],
+                &build_path,
+                edition,
+            ));
+        }
+
+        targets
+    }
+
+    /// Adds explicitly specified targets (e.g. `[[bin]]`) of this kind, followed by
+    /// automatically discovered ones that weren't already specified.
+    fn add_targets(
+        &self,
+        targets: &mut Vec<JsonValue>,
+        kind: &str,
+        default_dir: &str,
+        auto_found: Vec<(String, Utf8PathBuf)>,
+        edition: &str,
+    ) {
+        let dir = self.dir();
+        let kinds = [kind.to_owned()];
+        let mut names = HashSet::new();
+        let mut paths = HashSet::new();
+        for target in self
+            .manifest
+            .get(kind)
+            .and_then(TomlValue::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(TomlValue::as_table)
+        {
+            let name = match target.get("name").and_then(TomlValue::as_str) {
+                Some(name) => name,
+                None => continue,
+            };
+            let path = match target.get("path").and_then(TomlValue::as_str) {
+                Some(path) => dir.join(path),
+                None if kind == "bin" && name == self.name => {
+                    Some(dir.join(default_dir).join(format!("{}.rs", name)))
+                        .filter(|path| path.is_file())
+                        .unwrap_or_else(|| dir.join("src/main.rs"))
+                }
+                None => dir.join(default_dir).join(format!("{}.rs", name)),
+            };
+            let required_features = string_array(target.get("required-features"));
+            targets.push(target_json(
+                name,
+                &kinds,
+                &required_features,
+                &path,
+                edition,
+            ));
+            names.insert(name.to_owned());
+            paths.insert(path);
+        }
+        for (name, path) in auto_found {
+            if names.contains(&name) || paths.contains(&path) {
+                continue;
+            }
+            targets.push(target_json(&name, &kinds, &[], &path, edition));
+        }
+    }
+}
+
+static DEP_SECTIONS: &[(&str, Option<&str>)] = &[
+    ("dependencies", None),
+    ("dev-dependencies", Some("dev")),
+    ("dev_dependencies", Some("dev")),
+    ("build-dependencies", Some("build")),
+    ("build_dependencies", Some("build")),
+];
+
+/// A dependency read from `Cargo.toml`, with workspace inheritance applied.
+#[derive(Clone, Debug)]
+struct DepSpec {
+    // The key in the dependency table: the renamed name if `package` is specified.
+    key: String,
+    package: String,
+    req: VersionReq,
+    kind: Option<&'static str>,
+    target: Option<String>,
+    optional: bool,
+    default_features: bool,
+    features: Vec<String>,
+    registry: Option<String>,
+    path: Option<Utf8PathBuf>,
+    git: Option<String>,
+}
+
+impl DepSpec {
+    fn parse(
+        workspace: &WorkspaceInfo,
+        key: &str,
+        value: &TomlValue,
+        base_dir: &Utf8Path,
+        kind: Option<&'static str>,
+        target: Option<&str>,
+    ) -> Result<Self, Error> {
+        let error = |message: &str| {
+            Error::LockfileResolveError(format!(
+                "{}: dependency '{}': {}",
+                base_dir.join("Cargo.toml"),
+                key,
+                message
+            ))
+        };
+        let parse_req = |req: &str| VersionReq::parse(req).map_err(|err| error(&err.to_string()));
+
+        let table = match value {
+            TomlValue::String(req) => {
+                return Ok(Self {
+                    key: key.to_owned(),
+                    package: key.to_owned(),
+                    req: parse_req(req)?,
+                    kind,
+                    target: target.map(str::to_owned),
+                    optional: false,
+                    default_features: true,
+                    features: vec![],
+                    registry: None,
+                    path: None,
+                    git: None,
+                })
+            }
+            TomlValue::Table(table) => table,
+            _ => return Err(error("invalid dependency specification")),
+        };
+
+        let mut spec = if is_inherited(value) {
+            let inherited = workspace
+                .workspace_table("dependencies")
+                .get(key)
+                .ok_or_else(|| error("not found in `workspace.dependencies`"))?;
+            // Paths in `workspace.dependencies` are relative to the workspace root.
+            Self::parse(workspace, key, inherited, &workspace.root, kind, target)?
+        } else {
+            let str_field = |key: &str| table.get(key).and_then(TomlValue::as_str);
+            let git = str_field("git").map(|url| {
+                let reference = ["branch", "tag", "rev"]
+                    .iter()
+                    .find_map(|key| str_field(key).map(|value| format!("?{}={}", key, value)));
+                format!("git+{}{}", url, reference.unwrap_or_default())
+            });
+            Self {
+                key: key.to_owned(),
+                package: str_field("package").unwrap_or(key).to_owned(),
+                req: parse_req(str_field("version").unwrap_or("*"))?,
+                kind,
+                target: target.map(str::to_owned),
+                optional: false,
+                default_features: ["default-features", "default_features"]
+                    .iter()
+                    .find_map(|key| table.get(*key).and_then(TomlValue::as_bool))
+                    .unwrap_or(true),
+                features: vec![],
+                registry: str_field("registry").map(str::to_owned),
+                path: str_field("path").map(|path| normalize_path(&base_dir.join(path))),
+                git,
+            }
+        };
+        // `optional` and `features` can be specified alongside `workspace = true`. Features are
+        // additive.
+        spec.optional = table
+            .get("optional")
+            .and_then(TomlValue::as_bool)
+            .unwrap_or(false);
+        spec.features.extend(string_array(table.get("features")));
+        Ok(spec)
+    }
+
+    fn matches(&self, package: &LockPackage) -> bool {
+        self.package == package.name && cargo_version_matches(&self.req, &package.version)
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let source = if self.path.is_some() {
+            None
+        } else if let Some(git) = &self.git {
+            Some(git.as_str())
+        } else if self.registry.is_none() {
+            Some(CRATES_IO_SOURCE)
+        } else {
+            None
+        };
+        let rename = if self.key != self.package {
+            Some(&self.key)
+        } else {
+            None
+        };
+        json!({
+            "name": self.package,
+            "source": source,
+            "req": self.req.to_string(),
+            "kind": self.kind,
+            "rename": rename,
+            "optional": self.optional,
+            "uses_default_features": self.default_features,
+            "features": self.features,
+            "target": self.target,
+            "registry": self.registry,
+            "path": self.path,
+        })
+    }
+}
+
+// ---
+// Resolution
+// ---
+
+/// Matches up `Cargo.lock` entries with packages read from `Cargo.toml` files.
+struct LockResolver {
+    packages: Vec<LockPackage>,
+    ids: Vec<String>,
+    // Indexes into WorkspaceInfo::local_packages, for packages without a source.
+    local_indexes: Vec<Option<usize>>,
+    // Packages read from Cargo's download cache.
+    cached: HashMap<usize, ManifestPackage>,
+    // Every key that a dependency string can use, to the indexes of the packages it matches.
+    dep_indexes: HashMap<DepKey, Vec<usize>>,
+}
+
+impl LockResolver {
+    fn new(
+        workspace: &WorkspaceInfo,
+        packages: Vec<LockPackage>,
+        cargo_home: Option<&Utf8Path>,
+    ) -> Result<Self, Error> {
+        let mut local_used = vec![false; workspace.local_packages.len()];
+        let mut ids = Vec::with_capacity(packages.len());
+        let mut local_indexes = Vec::with_capacity(packages.len());
+        let mut cached = HashMap::new();
+
+        for (idx, package) in packages.iter().enumerate() {
+            match &package.source {
+                Some(source) => {
+                    ids.push(format!("{} {} ({})", package.name, package.version, source));
+                    local_indexes.push(None);
+                    if let Some(cached_package) = cargo_home
+                        .and_then(|cargo_home| find_cached(workspace, cargo_home, package))
+                    {
+                        cached.insert(idx, cached_package);
+                    }
+                }
+                None => {
+                    let mut candidates =
+                        workspace
+                            .local_packages
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, local)| {
+                                local.name == package.name && local.version == package.version
+                            });
+                    let (local_idx, local) = candidates.next().ok_or_else(|| {
+                        Error::LockfileResolveError(format!(
+                            "Cargo.lock: no Cargo.toml found for local package {} {}",
+                            package.name, package.version
+                        ))
+                    })?;
+                    if candidates.next().is_some() {
+                        return Err(Error::LockfileResolveError(format!(
+                            "Cargo.lock: multiple Cargo.toml files found for local package {} {}",
+                            package.name, package.version
+                        )));
+                    }
+                    local_used[local_idx] = true;
+                    ids.push(format!(
+                        "{} {} (path+file://{})",
+                        package.name,
+                        package.version,
+                        local.dir()
+                    ));
+                    local_indexes.push(Some(local_idx));
+                }
+            }
+        }
+
+        if let Some(local) = workspace
+            .local_packages
+            .iter()
+            .zip(local_used)
+            .find_map(|(local, used)| if used { None } else { Some(local) })
+        {
+            return Err(Error::LockfileResolveError(format!(
+                "{}: package {} {} not found in Cargo.lock (is it up to date?)",
+                local.manifest_path, local.name, local.version
+            )));
+        }
+
+        let mut dep_indexes: HashMap<_, Vec<_>> = HashMap::new();
+        for (idx, package) in packages.iter().enumerate() {
+            for key in package.dep_keys() {
+                dep_indexes.entry(key).or_default().push(idx);
+            }
+        }
+
+        Ok(Self {
+            packages,
+            ids,
+            local_indexes,
+            cached,
+            dep_indexes,
+        })
+    }
+
+    fn manifest_package<'a>(
+        &'a self,
+        workspace: &'a WorkspaceInfo,
+        idx: usize,
+    ) -> Option<&'a ManifestPackage> {
+        match self.local_indexes[idx] {
+            Some(local_idx) => Some(&workspace.local_packages[local_idx]),
+            None => self.cached.get(&idx),
+        }
+    }
+
+    fn resolve_dep_str(&self, from: &LockPackage, dep_str: &str) -> Result<usize, Error> {
+        let candidates = DepKey::parse(dep_str)
+            .and_then(|key| self.dep_indexes.get(&key))
+            .map_or(&[][..], |indexes| indexes.as_slice());
+        match candidates {
+            [idx] => Ok(*idx),
+            [] => Err(Error::LockfileResolveError(format!(
+                "Cargo.lock: for package {} {}, dependency '{}' not found",
+                from.name, from.version, dep_str
+            ))),
+            _ => Err(Error::LockfileResolveError(format!(
+                "Cargo.lock: for package {} {}, dependency '{}' is ambiguous",
+                from.name, from.version, dep_str
+            ))),
+        }
+    }
+
+    fn into_metadata(self, workspace: &WorkspaceInfo) -> Result<CargoMetadata, Error> {
+        let mut packages = vec![];
+        let mut nodes = vec![];
+        let mut workspace_members = vec![];
+        let mut root = None;
+
+        for (idx, package) in self.packages.iter().enumerate() {
+            let id = &self.ids[idx];
+            let manifest_package = self.manifest_package(workspace, idx);
+            let mut dependencies: Vec<_> = manifest_package
+                .map(|manifest_package| {
+                    manifest_package.deps.iter().map(DepSpec::to_json).collect()
+                })
+                .unwrap_or_default();
+
+            // Group resolved dependencies by their resolved names.
+            let mut node_deps: BTreeMap<(String, usize), Vec<JsonValue>> = BTreeMap::new();
+            let mut dep_ids = vec![];
+            for dep_str in &package.dependencies {
+                let dep_idx = self.resolve_dep_str(package, dep_str)?;
+                let dep_package = &self.packages[dep_idx];
+                dep_ids.push(self.ids[dep_idx].clone());
+                let dep_lib_name = self
+                    .manifest_package(workspace, dep_idx)
+                    .map_or_else(|| dep_package.name.replace('-', "_"), |dep| dep.lib_name());
+
+                let specs: Vec<_> = manifest_package
+                    .into_iter()
+                    .flat_map(|manifest_package| &manifest_package.deps)
+                    .filter(|spec| spec.matches(dep_package))
+                    .collect();
+                if specs.is_empty() {
+                    // Cargo.lock doesn't say how this dependency was specified: treat it as a
+                    // normal dependency on the exact version.
+                    let spec = DepSpec {
+                        key: dep_package.name.clone(),
+                        package: dep_package.name.clone(),
+                        req: VersionReq::parse(&format!("={}", dep_package.version))
+                            .expect("exact version requirement is valid"),
+                        kind: None,
+                        target: None,
+                        optional: false,
+                        default_features: true,
+                        features: vec![],
+                        registry: None,
+                        path: None,
+                        git: None,
+                    };
+                    let mut dep_json = spec.to_json();
+                    dep_json["source"] = json!(dep_package.source);
+                    dependencies.push(dep_json);
+                    node_deps
+                        .entry((dep_lib_name.clone(), dep_idx))
+                        .or_default()
+                        .push(json!({ "kind": null, "target": null }));
+                }
+                for spec in specs {
+                    let resolved_name = if spec.key != spec.package {
+                        spec.key.replace('-', "_")
+                    } else {
+                        dep_lib_name.clone()
+                    };
+                    node_deps
+                        .entry((resolved_name, dep_idx))
+                        .or_default()
+                        .push(json!({ "kind": spec.kind, "target": spec.target }));
+                }
+            }
+
+            packages.push(match manifest_package {
+                Some(manifest_package) => manifest_package.to_json(workspace, id, dependencies),
+                None => external_json(workspace, package, id, dependencies),
+            });
+            nodes.push(json!({
+                "id": id,
+                "dependencies": dep_ids,
+                "deps": node_deps
+                    .into_iter()
+                    .map(|((name, dep_idx), dep_kinds)| json!({
+                        "name": name,
+                        "pkg": self.ids[dep_idx],
+                        "dep_kinds": dep_kinds,
+                    }))
+                    .collect::<Vec<_>>(),
+                "features": [],
+            }));
+
+            if let Some(manifest_package) = manifest_package {
+                if manifest_package.is_member && self.local_indexes[idx].is_some() {
+                    workspace_members.push(id.clone());
+                    if manifest_package.dir() == workspace.root {
+                        root = Some(id.clone());
+                    }
+                }
+            }
+        }
+
+        let workspace_metadata = workspace
+            .table("workspace")
+            .get("metadata")
+            .and_then(|metadata| serde_json::to_value(metadata).ok())
+            .unwrap_or(JsonValue::Null);
+        let value = json!({
+            "packages": packages,
+            "workspace_members": workspace_members,
+            "resolve": { "nodes": nodes, "root": root },
+            "workspace_root": workspace.root,
+            "target_directory": workspace.root.join("target"),
+            "version": 1,
+            "metadata": workspace_metadata,
+        });
+        CargoMetadata::from_value(value).map_err(Error::MetadataParseError)
+    }
+}
+
+/// Looks for the `Cargo.toml` of a registry package in Cargo's download cache.
+fn find_cached(
+    workspace: &WorkspaceInfo,
+    cargo_home: &Utf8Path,
+    package: &LockPackage,
+) -> Option<ManifestPackage> {
+    let source = package.source.as_deref()?;
+    if !source.starts_with("registry+") {
+        return None;
+    }
+    let dir_name = format!("{}-{}", package.name, package.version);
+    let mut registry_dirs: Vec<_> = cargo_home
+        .join("registry/src")
+        .read_dir_utf8()
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+    registry_dirs.sort_unstable();
+    registry_dirs.into_iter().find_map(|registry_dir| {
+        let manifest_path = registry_dir.join(&dir_name).join("Cargo.toml");
+        let manifest = read_manifest(&manifest_path).ok()?;
+        // If the manifest can't be understood, fall back to the information in Cargo.lock.
+        let cached =
+            ManifestPackage::new(workspace, manifest_path, manifest, Some(source.to_owned()))
+                .ok()?;
+        if cached.name == package.name && cached.version == package.version {
+            Some(cached)
+        } else {
+            None
+        }
+    })
+}
+
+/// Returns the metadata for a package that's only known through `Cargo.lock`.
+fn external_json(
+    workspace: &WorkspaceInfo,
+    package: &LockPackage,
+    id: &str,
+    dependencies: Vec<JsonValue>,
+) -> JsonValue {
+    // This directory doesn't exist, but the layout is similar to Cargo's download cache.
+    let dir = workspace
+        .root
+        .join("target")
+        .join("lockfile-packages")
+        .join(format!("{}-{}", package.name, package.version));
+    json!({
+        "name": package.name,
+        "version": package.version.to_string(),
+        "id": id,
+        "source": package.source,
+        "dependencies": dependencies,
+        "targets": [target_json(
+            &package.name.replace('-', "_"),
+            &["lib".to_owned()],
+            &[],
+            &dir.join("src/lib.rs"),
+            "2015",
+        )],
+        "features": {},
+        "manifest_path": dir.join("Cargo.toml"),
+    })
+}
+
+// ---
+// Helpers
+// ---
+
+fn read_file(path: &Utf8Path) -> Result<String, Error> {
+    fs::read_to_string(path)
+        .map_err(|err| Error::LockfileReadError(path.to_path_buf(), Box::new(err)))
+}
+
+fn read_manifest(path: &Utf8Path) -> Result<Table, Error> {
+    toml::from_str(&read_file(path)?)
+        .map_err(|err| Error::LockfileReadError(path.to_path_buf(), Box::new(err)))
+}
+
+/// Returns true if this is `{ workspace = true, ... }`.
+fn is_inherited(value: &TomlValue) -> bool {
+    value.get("workspace").and_then(TomlValue::as_bool) == Some(true)
+}
+
+fn string_array(value: Option<&TomlValue>) -> Vec<String> {
+    value
+        .and_then(TomlValue::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.as_str().map(str::to_owned))
+        .collect()
+}
+
+fn target_json(
+    name: &str,
+    kinds: &[String],
+    required_features: &[String],
+    src_path: &Utf8Path,
+    edition: &str,
+) -> JsonValue {
+    let is_lib = !kinds.iter().any(|kind| {
+        matches!(
+            kind.as_str(),
+            "bin" | "example" | "test" | "bench" | "custom-build"
+        )
+    });
+    let crate_types = if is_lib {
+        kinds.to_vec()
+    } else {
+        vec!["bin".to_owned()]
+    };
+    json!({
+        "name": name,
+        "kind": kinds,
+        "crate_types": crate_types,
+        "required-features": required_features,
+        "src_path": src_path,
+        "edition": edition,
+        "doctest": is_lib,
+    })
+}
+
+/// Finds `<dir>/*.rs` and `<dir>/*/main.rs`, sorted by name.
+fn auto_targets(dir: &Utf8Path) -> Vec<(String, Utf8PathBuf)> {
+    let mut found = vec![];
+    let entries = match dir.read_dir_utf8() {
+        Ok(entries) => entries,
+        Err(_) => return found,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_file() && path.extension() == Some("rs") {
+            if let Some(stem) = path.file_stem() {
+                found.push((stem.to_owned(), path.to_path_buf()));
+            }
+        } else if path.join("main.rs").is_file() {
+            found.push((entry.file_name().to_owned(), path.join("main.rs")));
+        }
+    }
+    found.sort_unstable();
+    found
+}
+
+/// Lexically normalizes a path, resolving `.` and `..` components. Cargo doesn't resolve symlinks
+/// in paths to dependencies either.
+fn normalize_path(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Returns true if a path in `workspace.members` is a glob pattern, using the same special
+/// characters as Cargo.
+fn is_glob_pattern(path: &str) -> bool {
+    path.contains(|c| c == '*' || c == '?' || c == '[')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        graph::{BuildTargetId, BuildTargetKind},
+        platform::{EnabledTernary, PlatformSpec},
+        PackageId,
+    };
+
+    fn inside_outside() -> PackageGraph {
+        let root = Utf8Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../fixtures/workspace/inside-outside/workspace");
+        LockfileBuilder::new(root)
+            .no_registry_cache()
+            .build_graph()
+            .expect("package graph built from Cargo.lock")
+    }
+
+    #[test]
+    fn lockfile_resolve_dep_str() {
+        let lock_package = |name: &str, version: &str, source: &str| LockPackage {
+            name: name.to_owned(),
+            version: version.parse().expect("valid version"),
+            source: Some(source.to_owned()),
+            dependencies: vec![],
+        };
+        let workspace = WorkspaceInfo {
+            root: Utf8PathBuf::from("/fake/workspace"),
+            root_manifest: Table::new(),
+            local_packages: vec![],
+        };
+        let resolver = LockResolver::new(
+            &workspace,
+            vec![
+                lock_package("foo", "1.0.0", CRATES_IO_SOURCE),
+                lock_package("foo", "2.0.0", CRATES_IO_SOURCE),
+                lock_package("foo", "2.0.0", "git+https://example.com/foo"),
+                lock_package("bar", "1.0.0", CRATES_IO_SOURCE),
+            ],
+            None,
+        )
+        .expect("resolver created");
+
+        let from = lock_package("from", "0.1.0", CRATES_IO_SOURCE);
+        let resolve = |dep_str: &str| resolver.resolve_dep_str(&from, dep_str).ok();
+        assert_eq!(resolve("bar"), Some(3));
+        assert_eq!(resolve("bar 1.0.0"), Some(3));
+        assert_eq!(resolve("foo 1.0.0"), Some(0));
+        assert_eq!(
+            resolve(&format!("foo 2.0.0 ({})", CRATES_IO_SOURCE)),
+            Some(1)
+        );
+        assert_eq!(resolve("foo 2.0.0 (git+https://example.com/foo)"), Some(2));
+
+        // Ambiguous or missing dependencies.
+        assert_eq!(resolve("foo"), None);
+        assert_eq!(resolve("foo 2.0.0"), None);
+        assert_eq!(resolve("foo 3.0.0"), None);
+        assert_eq!(resolve("foo not-a-version"), None);
+        assert_eq!(resolve("baz"), None);
+    }
+
+    #[test]
+    fn lockfile_workspace() {
+        let graph = inside_outside();
+        let workspace = graph.workspace();
+        let mut members: Vec<_> = workspace.iter().map(|package| package.name()).collect();
+        members.sort_unstable();
+        assert_eq!(members, ["internal", "internal-macro", "main", "side"]);
+        assert_eq!(
+            workspace.member_by_path("internal").unwrap().name(),
+            "internal"
+        );
+
+        let internal = workspace.member_by_name("internal").unwrap();
+        let link = |from: &str, to: &str| {
+            workspace
+                .member_by_name(from)
+                .unwrap()
+                .direct_links()
+                .find(|link| link.to().name() == to)
+                .unwrap_or_else(|| panic!("{} -> {} link found", from, to))
+        };
+
+        // Dependency kinds, platforms and features are read from Cargo.toml.
+        let lazy_static = link("internal", "lazy_static");
+        assert!(lazy_static.normal().is_present());
+        assert!(lazy_static.dev().is_present());
+        assert!(lazy_static.to().source().is_crates_io());
+        assert!(link("internal", "bytes").dev_only());
+        let x86_active = link("internal", "x86-active");
+        let status = x86_active.normal().status();
+        assert_eq!(
+            status.enabled_on(&PlatformSpec::Any),
+            EnabledTernary::Enabled
+        );
+        assert_eq!(
+            status.enabled_on(&PlatformSpec::Always),
+            EnabledTernary::Disabled
+        );
+        assert_eq!(
+            status.required_on(&PlatformSpec::Any),
+            EnabledTernary::Disabled
+        );
+
+        // Path dependencies outside the workspace are present, with their features.
+        let external = link("internal", "external").to();
+        assert!(!external.in_workspace());
+        assert_eq!(
+            external.source().local_path(),
+            Some(Utf8Path::new("../external"))
+        );
+        assert!(external
+            .named_features()
+            .any(|feature| feature == "side-feature"));
+        assert!(external
+            .direct_links()
+            .any(|link| link.to().name() == "bytes" && link.normal().is_present()));
+
+        // Build targets are inferred from the file layout.
+        let main = workspace.member_by_name("main").unwrap();
+        assert!(main.build_target(&BuildTargetId::BuildScript).is_some());
+        let internal_macro = workspace.member_by_name("internal-macro").unwrap();
+        assert!(internal_macro.is_proc_macro());
+        let lib = internal.build_target(&BuildTargetId::Library).unwrap();
+        assert_eq!(
+            lib.kind(),
+            BuildTargetKind::LibraryOrExample(&["lib".to_owned()])
+        );
+
+        // Packages outside the workspace are only known through Cargo.lock.
+        let spin = graph
+            .packages()
+            .find(|package| package.name() == "spin")
+            .expect("spin found");
+        assert!(spin.source().is_crates_io());
+        assert!(graph
+            .metadata(&PackageId::new(
+                "lazy_static 1.4.0 (registry+https://github.com/rust-lang/crates.io-index)"
+            ))
+            .unwrap()
+            .direct_links()
+            .any(|link| link.to().id() == spin.id()));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(is_glob_pattern("crates/*"));
+        assert!(is_glob_pattern("tools/cargo-?"));
+        assert!(is_glob_pattern("crates/[ab]*"));
+        assert!(!is_glob_pattern("crates/foo"));
+        assert_eq!(
+            normalize_path(Utf8Path::new("/a/b/../c/./d")),
+            Utf8PathBuf::from("/a/c/d")
+        );
+    }
+}
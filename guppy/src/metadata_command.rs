@@ -1,10 +1,25 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{graph::PackageGraph, Error};
+use crate::{
+    errors::ParseWarnings,
+    graph::{ArtifactDeps, PackageGraph},
+    metadata_compat::check_metadata,
+    platform::Platform,
+    Error,
+};
 use cargo_metadata::CargoOpt;
-use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, io, path::PathBuf, process::Command};
+use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt, io,
+    path::PathBuf,
+    process::Command,
+    str::from_utf8,
+};
+use target_spec::TargetSpec;
 
 /// A builder for configuring `cargo metadata` invocations.
 ///
@@ -24,6 +39,8 @@ use std::{convert::TryFrom, io, path::PathBuf, process::Command};
 #[derive(Clone, Debug, Default)]
 pub struct MetadataCommand {
     inner: cargo_metadata::MetadataCommand,
+    filter_platforms: Option<Vec<Platform>>,
+    strict: bool,
 }
 
 impl MetadataCommand {
@@ -35,7 +52,11 @@ impl MetadataCommand {
         let mut inner = cargo_metadata::MetadataCommand::new();
         // Always use --all-features so that we get a full view of the graph.
         inner.features(CargoOpt::AllFeatures);
-        Self { inner }
+        Self {
+            inner,
+            filter_platforms: None,
+            strict: false,
+        }
     }
 
     /// Sets the path to the `cargo` executable.
@@ -78,6 +99,29 @@ impl MetadataCommand {
         self
     }
 
+    /// Prunes packages that can never be built on any of the given platforms from the output of
+    /// `cargo metadata`.
+    ///
+    /// This produces a smaller `PackageGraph`, which uses less memory and is faster to query. See
+    /// [`CargoMetadata::filter_platforms`] for more.
+    ///
+    /// Unlike `cargo metadata --filter-platform`, this accepts any number of platforms, and
+    /// platforms with unknown target features are handled conservatively.
+    pub fn filter_platforms(&mut self, platforms: impl IntoIterator<Item = Platform>) -> &mut Self {
+        self.filter_platforms = Some(platforms.into_iter().collect());
+        self
+    }
+
+    /// Fails if the output of `cargo metadata` contains data that `guppy` doesn't understand.
+    ///
+    /// By default, such data is recorded as [`ParseWarnings`] and parsing continues. Strict mode
+    /// is useful in CI, to detect when a new Cargo version produces information that `guppy`
+    /// misses. See [`CargoMetadata::parse_json_strict`] for more.
+    pub fn strict(&mut self) -> &mut Self {
+        self.strict = true;
+        self
+    }
+
     // *Do not* implement features.
 
     /// Arbitrary flags to pass to `cargo metadata`. These will be added to the end of the
@@ -104,9 +148,37 @@ impl MetadataCommand {
     }
 
     /// Runs the configured `cargo metadata` and returns a deserialized `CargoMetadata`.
+    ///
+    /// If [`filter_platforms`](Self::filter_platforms) was called, the returned metadata is pruned
+    /// accordingly.
     pub fn exec(&self) -> Result<CargoMetadata, Error> {
-        let inner = self.inner.exec().map_err(Error::command_error)?;
-        Ok(CargoMetadata(inner))
+        // This doesn't use cargo_metadata's exec, because the JSON needs to be parsed by
+        // CargoMetadata::parse_json to retain fields that cargo_metadata doesn't know about.
+        let output = self
+            .cargo_command()
+            .output()
+            .map_err(|err| Error::command_error(err.into()))?;
+        if !output.status.success() {
+            let stderr =
+                String::from_utf8(output.stderr).map_err(|err| Error::command_error(err.into()))?;
+            return Err(Error::command_error(cargo_metadata::Error::CargoMetadata {
+                stderr,
+            }));
+        }
+        let json = from_utf8(&output.stdout)
+            .map_err(|err| Error::command_error(err.into()))?
+            .lines()
+            .find(|line| line.starts_with('{'))
+            .ok_or_else(|| Error::command_error(cargo_metadata::Error::NoJson))?;
+        let mut metadata = if self.strict {
+            CargoMetadata::parse_json_strict(json)?
+        } else {
+            CargoMetadata::parse_json(json)?
+        };
+        if let Some(platforms) = &self.filter_platforms {
+            metadata.filter_platforms(platforms);
+        }
+        Ok(metadata)
     }
 
     /// Runs the configured `cargo metadata` and returns a parsed `PackageGraph`.
@@ -143,20 +215,178 @@ impl<'a> TryFrom<&'a MetadataCommand> for PackageGraph {
 /// Cargo workspace, use [`MetadataCommand`](MetadataCommand).
 ///
 /// This struct implements `serde::Serialize` and `Deserialize`.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(transparent)]
-pub struct CargoMetadata(pub(crate) cargo_metadata::Metadata);
+#[derive(Clone, Debug)]
+pub struct CargoMetadata {
+    pub(crate) inner: cargo_metadata::Metadata,
+    // Artifact dependency requests, which cargo_metadata doesn't know about.
+    pub(crate) artifacts: ArtifactDeps,
+    // Data in the JSON that wasn't fully understood.
+    pub(crate) parse_warnings: ParseWarnings,
+}
 
 impl CargoMetadata {
     /// Deserializes this JSON blob into a `CargoMetadata`.
+    ///
+    /// Data that `guppy` doesn't fully understand, such as fields added by newer versions of
+    /// Cargo, is recorded and made available through [`parse_warnings`](Self::parse_warnings).
     pub fn parse_json(json: impl AsRef<str>) -> Result<Self, Error> {
-        let inner = serde_json::from_str(json.as_ref()).map_err(Error::MetadataParseError)?;
-        Ok(Self(inner))
+        let value: Value =
+            serde_json::from_str(json.as_ref()).map_err(Error::MetadataParseError)?;
+        let parse_warnings = check_metadata(&value);
+        let mut metadata = Self::from_value(value).map_err(Error::MetadataParseError)?;
+        metadata.parse_warnings = parse_warnings;
+        Ok(metadata)
+    }
+
+    /// Deserializes this JSON blob into a `CargoMetadata`, failing if it contains data that
+    /// `guppy` doesn't fully understand.
+    ///
+    /// Returns [`Error::MetadataStrictError`] if [`parse_json`](Self::parse_json) would have
+    /// produced any warnings.
+    pub fn parse_json_strict(json: impl AsRef<str>) -> Result<Self, Error> {
+        let metadata = Self::parse_json(json)?;
+        if metadata.parse_warnings.is_empty() {
+            Ok(metadata)
+        } else {
+            Err(Error::MetadataStrictError(metadata.parse_warnings))
+        }
+    }
+
+    /// Returns warnings about data in the JSON that `guppy` didn't fully understand.
+    ///
+    /// Warnings are only produced by [`parse_json`](Self::parse_json). They're empty for metadata
+    /// obtained in other ways, e.g. by deserializing a `CargoMetadata` through `serde`.
+    pub fn parse_warnings(&self) -> &ParseWarnings {
+        &self.parse_warnings
     }
 
     /// Serializes this metadata into the given writer.
     pub fn serialize(&self, writer: &mut impl io::Write) -> Result<(), Error> {
-        serde_json::to_writer(writer, &self.0).map_err(Error::MetadataSerializeError)
+        let value = self.to_value().map_err(Error::MetadataSerializeError)?;
+        serde_json::to_writer(writer, &value).map_err(Error::MetadataSerializeError)
+    }
+
+    pub(crate) fn from_value(value: Value) -> Result<Self, serde_json::Error> {
+        let artifacts = ArtifactDeps::from_metadata(&value)?;
+        let inner = serde_json::from_value(value)?;
+        Ok(Self {
+            inner,
+            artifacts,
+            parse_warnings: ParseWarnings::default(),
+        })
+    }
+
+    fn to_value(&self) -> Result<Value, serde_json::Error> {
+        let mut value = serde_json::to_value(&self.inner)?;
+        self.artifacts.write_to_metadata(&mut value)?;
+        Ok(value)
+    }
+
+    /// Prunes packages that can never be built on any of the given platforms.
+    ///
+    /// Dependency edges that are only enabled on other platforms are removed, and then packages
+    /// that are no longer reachable from the workspace are removed. Dependencies whose platform
+    /// can't be evaluated, e.g. because it depends on unknown target features, are retained.
+    ///
+    /// If no platforms are passed in, only dependencies that aren't platform-specific are
+    /// retained. This does nothing if the metadata was generated with `--no-deps`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use guppy::{platform::{Platform, TargetFeatures}, CargoMetadata};
+    ///
+    /// let mut metadata = CargoMetadata::parse_json(include_str!(
+    ///     "../../fixtures/small/metadata1.json"
+    /// )).unwrap();
+    /// let platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+    /// metadata.filter_platforms(&[platform]);
+    ///
+    /// // winapi is only used on Windows, so it is pruned.
+    /// let graph = metadata.build_graph().unwrap();
+    /// assert_eq!(graph.resolve_package_name("winapi").len(), 0);
+    /// ```
+    pub fn filter_platforms<'a>(
+        &mut self,
+        platforms: impl IntoIterator<Item = &'a Platform>,
+    ) -> &mut Self {
+        let platforms: Vec<_> = platforms.into_iter().collect();
+        let metadata = &mut self.inner;
+        let resolve = match &mut metadata.resolve {
+            Some(resolve) => resolve,
+            None => return self,
+        };
+        let packages_by_id: HashMap<_, _> = metadata
+            .packages
+            .iter()
+            .map(|package| (&package.id, package))
+            .collect();
+
+        for node in &mut resolve.nodes {
+            let package = packages_by_id.get(&node.id);
+            // Vec::retain_mut isn't available on the minimum supported Rust version, so compute
+            // which edges to keep first.
+            let keep: Vec<_> = node
+                .deps
+                .iter_mut()
+                .map(|dep| {
+                    if dep.dep_kinds.is_empty() {
+                        // dep_kinds is empty for metadata generated by Cargo versions before 1.41.
+                        // Fall back to the dependencies in the manifest, which are matched by
+                        // name only: this may retain more edges than necessary, but never fewer.
+                        let dep_name = match packages_by_id.get(&dep.pkg) {
+                            Some(dep_package) => &dep_package.name,
+                            None => return true,
+                        };
+                        let mut targets = package
+                            .into_iter()
+                            .flat_map(|package| package.dependencies.iter())
+                            .filter(|dependency| &dependency.name == dep_name)
+                            .map(|dependency| dependency.target.as_ref())
+                            .peekable();
+                        targets.peek().is_none()
+                            || targets.any(|target| enabled_on_any(&platforms, target))
+                    } else {
+                        dep.dep_kinds.retain(|dep_kind| {
+                            enabled_on_any(&platforms, dep_kind.target.as_ref())
+                        });
+                        !dep.dep_kinds.is_empty()
+                    }
+                })
+                .collect();
+            let mut keep = keep.into_iter();
+            node.deps
+                .retain(|_| keep.next().expect("one flag per dependency"));
+            let remaining: HashSet<_> = node.deps.iter().map(|dep| &dep.pkg).collect();
+            node.dependencies
+                .retain(|package_id| remaining.contains(package_id));
+        }
+
+        // Find packages reachable from the workspace through the remaining edges.
+        let mut reachable: HashSet<_> = metadata.workspace_members.iter().cloned().collect();
+        let mut stack: Vec<_> = reachable.iter().cloned().collect();
+        let deps_by_id: HashMap<_, _> = resolve
+            .nodes
+            .iter()
+            .map(|node| (&node.id, &node.deps))
+            .collect();
+        while let Some(package_id) = stack.pop() {
+            for dep in deps_by_id
+                .get(&package_id)
+                .into_iter()
+                .flat_map(|deps| deps.iter())
+            {
+                if reachable.insert(dep.pkg.clone()) {
+                    stack.push(dep.pkg.clone());
+                }
+            }
+        }
+
+        resolve.nodes.retain(|node| reachable.contains(&node.id));
+        metadata
+            .packages
+            .retain(|package| reachable.contains(&package.id));
+        self
     }
 
     /// Parses this metadata and builds a `PackageGraph` from it.
@@ -165,6 +395,35 @@ impl CargoMetadata {
     }
 }
 
+/// Returns true if a dependency with the given `target` may be enabled on any of `platforms`.
+fn enabled_on_any(platforms: &[&Platform], target: Option<&impl fmt::Display>) -> bool {
+    match target {
+        Some(target) => match target.to_string().parse::<TargetSpec>() {
+            Ok(spec) => platforms
+                .iter()
+                .any(|platform| spec.eval(platform) != Some(false)),
+            // Let building the graph report this error.
+            Err(_) => true,
+        },
+        None => true,
+    }
+}
+
+impl Serialize for CargoMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_value()
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for CargoMetadata {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Self::from_value(value).map_err(D::Error::custom)
+    }
+}
+
 impl TryFrom<CargoMetadata> for PackageGraph {
     type Error = Error;
 
@@ -0,0 +1,281 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Checks `cargo metadata` output against the format `guppy` understands.
+//!
+//! `guppy` parses `cargo metadata` output leniently: unknown fields are ignored, and missing
+//! fields are filled in with defaults. The checks here record each of those cases as a
+//! [`ParseWarning`], so that information that `guppy` misses doesn't go unnoticed.
+
+use crate::errors::{ParseWarning, ParseWarnings};
+use serde_json::{Map, Value};
+
+/// The metadata format version understood by `guppy`.
+const FORMAT_VERSION: u64 = 1;
+
+// The fields below are the ones understood by `cargo_metadata`, along with `artifact`, which is
+// read by `guppy` directly.
+
+const METADATA_FIELDS: &[&str] = &[
+    "packages",
+    "workspace_members",
+    "resolve",
+    "workspace_root",
+    "target_directory",
+    "metadata",
+    "version",
+];
+
+const PACKAGE_FIELDS: &[&str] = &[
+    "name",
+    "version",
+    "authors",
+    "id",
+    "source",
+    "description",
+    "dependencies",
+    "license",
+    "license_file",
+    "targets",
+    "features",
+    "manifest_path",
+    "categories",
+    "keywords",
+    "readme",
+    "repository",
+    "homepage",
+    "documentation",
+    "edition",
+    "metadata",
+    "links",
+    "publish",
+    "default_run",
+    "rust_version",
+];
+// Fields that older Cargo versions don't produce, and that are defaulted if missing.
+const PACKAGE_DEFAULTED_FIELDS: &[&str] = &["edition"];
+
+const DEPENDENCY_FIELDS: &[&str] = &[
+    "name",
+    "source",
+    "req",
+    "kind",
+    "optional",
+    "uses_default_features",
+    "features",
+    "target",
+    "rename",
+    "registry",
+    "path",
+    "artifact",
+];
+
+const TARGET_FIELDS: &[&str] = &[
+    "name",
+    "kind",
+    "crate_types",
+    "required-features",
+    "src_path",
+    "edition",
+    "doctest",
+    "test",
+    "doc",
+];
+const TARGET_DEFAULTED_FIELDS: &[&str] = &["edition", "doctest", "test", "doc"];
+
+const RESOLVE_FIELDS: &[&str] = &["nodes", "root"];
+
+const NODE_FIELDS: &[&str] = &["id", "deps", "dependencies", "features"];
+const NODE_DEFAULTED_FIELDS: &[&str] = &["deps"];
+
+const NODE_DEP_FIELDS: &[&str] = &["name", "pkg", "dep_kinds"];
+const NODE_DEP_DEFAULTED_FIELDS: &[&str] = &["dep_kinds"];
+
+const DEP_KIND_FIELDS: &[&str] = &["kind", "target"];
+
+// null stands for a normal dependency.
+const DEPENDENCY_KINDS: &[&str] = &["normal", "dev", "build"];
+
+/// Returns warnings for everything in this `cargo metadata` output that `guppy` doesn't fully
+/// understand.
+pub(crate) fn check_metadata(metadata: &Value) -> ParseWarnings {
+    let mut checker = Checker::default();
+    let metadata = match metadata.as_object() {
+        Some(metadata) => metadata,
+        // Deserialization will report an error.
+        None => return checker.finish(),
+    };
+
+    match metadata.get("version") {
+        Some(version) if version.as_u64() == Some(FORMAT_VERSION) => {}
+        Some(version) => checker.push(ParseWarning::UnsupportedFormatVersion(version.to_string())),
+        // Deserialization will report an error.
+        None => {}
+    }
+    checker.check_fields("", metadata, METADATA_FIELDS, &[]);
+
+    for package in objects(metadata.get("packages")) {
+        let path = format!("packages[{}]", id_label(package));
+        checker.check_fields(&path, package, PACKAGE_FIELDS, PACKAGE_DEFAULTED_FIELDS);
+        for (idx, dep) in objects(package.get("dependencies")).enumerate() {
+            let path = format!("{}.dependencies[{}]", path, idx);
+            checker.check_fields(&path, dep, DEPENDENCY_FIELDS, &[]);
+            checker.check_kind(&path, dep);
+        }
+        for (idx, target) in objects(package.get("targets")).enumerate() {
+            let path = format!("{}.targets[{}]", path, idx);
+            checker.check_fields(&path, target, TARGET_FIELDS, TARGET_DEFAULTED_FIELDS);
+        }
+    }
+
+    if let Some(resolve) = metadata.get("resolve").and_then(Value::as_object) {
+        checker.check_fields("resolve", resolve, RESOLVE_FIELDS, &[]);
+        for node in objects(resolve.get("nodes")) {
+            let path = format!("resolve.nodes[{}]", id_label(node));
+            checker.check_fields(&path, node, NODE_FIELDS, NODE_DEFAULTED_FIELDS);
+            for (idx, dep) in objects(node.get("deps")).enumerate() {
+                let path = format!("{}.deps[{}]", path, idx);
+                checker.check_fields(&path, dep, NODE_DEP_FIELDS, NODE_DEP_DEFAULTED_FIELDS);
+                for (idx, dep_kind) in objects(dep.get("dep_kinds")).enumerate() {
+                    let path = format!("{}.dep_kinds[{}]", path, idx);
+                    checker.check_fields(&path, dep_kind, DEP_KIND_FIELDS, &[]);
+                    checker.check_kind(&path, dep_kind);
+                }
+            }
+        }
+    }
+
+    checker.finish()
+}
+
+#[derive(Debug, Default)]
+struct Checker {
+    warnings: Vec<ParseWarning>,
+}
+
+impl Checker {
+    fn push(&mut self, warning: ParseWarning) {
+        self.warnings.push(warning);
+    }
+
+    fn check_fields(
+        &mut self,
+        path: &str,
+        object: &Map<String, Value>,
+        known: &[&str],
+        defaulted: &[&str],
+    ) {
+        for field in object.keys() {
+            if !known.contains(&field.as_str()) {
+                self.push(ParseWarning::UnknownField {
+                    path: join(path, field),
+                });
+            }
+        }
+        for field in defaulted {
+            if !object.contains_key(*field) {
+                self.push(ParseWarning::MissingField {
+                    path: join(path, field),
+                });
+            }
+        }
+    }
+
+    fn check_kind(&mut self, path: &str, object: &Map<String, Value>) {
+        match object.get("kind") {
+            None | Some(Value::Null) => {}
+            Some(Value::String(kind)) if DEPENDENCY_KINDS.contains(&kind.as_str()) => {}
+            Some(kind) => self.push(ParseWarning::UnknownDependencyKind {
+                path: join(path, "kind"),
+                kind: match kind {
+                    Value::String(kind) => kind.clone(),
+                    other => other.to_string(),
+                },
+            }),
+        }
+    }
+
+    fn finish(self) -> ParseWarnings {
+        ParseWarnings {
+            warnings: self.warnings,
+        }
+    }
+}
+
+fn objects(value: Option<&Value>) -> impl Iterator<Item = &Map<String, Value>> {
+    value
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object)
+}
+
+fn id_label(object: &Map<String, Value>) -> &str {
+    object.get("id").and_then(Value::as_str).unwrap_or("?")
+}
+
+fn join(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        field.to_owned()
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn check_metadata_warnings() {
+        let metadata = json!({
+            "packages": [{
+                "name": "foo",
+                "version": "0.1.0",
+                "id": "foo 0.1.0 (path+file:///foo)",
+                "edition": "2021",
+                "public": true,
+                "dependencies": [
+                    { "name": "bar", "kind": null },
+                    { "name": "baz", "kind": "artifact-only" },
+                ],
+                "targets": [{ "name": "foo", "edition": "2021", "doctest": true, "test": true }],
+            }],
+            "workspace_members": [],
+            "workspace_default_members": [],
+            "workspace_root": "/foo",
+            "target_directory": "/foo/target",
+            "version": 2,
+            "resolve": {
+                "nodes": [{
+                    "id": "foo 0.1.0 (path+file:///foo)",
+                    "deps": [{ "name": "bar", "pkg": "bar 0.1.0 (path+file:///bar)" }],
+                }],
+                "root": null,
+            },
+        });
+
+        let warnings = check_metadata(&metadata);
+        let expected = vec![
+            ParseWarning::UnsupportedFormatVersion("2".to_owned()),
+            ParseWarning::UnknownField {
+                path: "workspace_default_members".to_owned(),
+            },
+            ParseWarning::UnknownField {
+                path: "packages[foo 0.1.0 (path+file:///foo)].public".to_owned(),
+            },
+            ParseWarning::UnknownDependencyKind {
+                path: "packages[foo 0.1.0 (path+file:///foo)].dependencies[1].kind".to_owned(),
+                kind: "artifact-only".to_owned(),
+            },
+            ParseWarning::MissingField {
+                path: "packages[foo 0.1.0 (path+file:///foo)].targets[0].doc".to_owned(),
+            },
+            ParseWarning::MissingField {
+                path: "resolve.nodes[foo 0.1.0 (path+file:///foo)].deps[0].dep_kinds".to_owned(),
+            },
+        ];
+        assert_eq!(warnings.iter().cloned().collect::<Vec<_>>(), expected);
+    }
+}
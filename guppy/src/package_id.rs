@@ -1,28 +1,33 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use std::fmt;
+use std::{fmt, sync::Arc};
 
 /// An "opaque" identifier for a package.
 #[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[allow(clippy::derive_hash_xor_eq)] // safe because the same PartialEq impl is used everywhere
 pub struct PackageId {
-    /// The underlying string representation of an ID.
-    repr: Box<str>,
+    /// The underlying string representation of an ID. This is shared between all copies of an ID
+    /// within a package graph.
+    repr: Arc<str>,
 }
 
 impl PackageId {
     /// Creates a new `PackageId`.
     pub fn new(s: impl Into<Box<str>>) -> Self {
-        Self { repr: s.into() }
-    }
-
-    pub(super) fn from_metadata(id: cargo_metadata::PackageId) -> Self {
         Self {
-            repr: id.repr.into_boxed_str(),
+            repr: s.into().into(),
         }
     }
 
+    pub(crate) fn from_interned(repr: Arc<str>) -> Self {
+        Self { repr }
+    }
+
+    pub(crate) fn interned_repr(&self) -> &Arc<str> {
+        &self.repr
+    }
+
     /// Returns the inner representation of a package ID. This is generally an opaque string and its
     /// precise format is subject to change.
     pub fn repr(&self) -> &str {
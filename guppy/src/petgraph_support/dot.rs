@@ -5,7 +5,10 @@ use petgraph::{
     prelude::*,
     visit::{GraphProp, IntoEdgeReferences, IntoNodeReferences, NodeIndexable, NodeRef},
 };
-use std::fmt::{self, Write};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
 
 static INDENT: &str = "    ";
 
@@ -19,7 +22,35 @@ pub trait DotVisitor<NR, ER> {
     /// `DotWrite`.
     fn visit_edge(&self, edge: ER, f: &mut DotWrite<'_, '_>) -> fmt::Result;
 
-    // TODO: allow more customizations? more labels, colors etc to be set?
+    /// Visits this node. The implementation may output attributes other than the label, such as
+    /// colors, to the given `DotAttrs`.
+    ///
+    /// The default implementation outputs no attributes.
+    fn visit_node_attrs(&self, _node: NR, _attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Visits this edge. The implementation may output attributes other than the label, such as
+    /// colors, to the given `DotAttrs`.
+    ///
+    /// The default implementation outputs no attributes.
+    fn visit_edge_attrs(&self, _edge: ER, _attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        Ok(())
+    }
+
+    /// Returns the name of the cluster this node is drawn in, if any.
+    ///
+    /// The default implementation doesn't place nodes in clusters.
+    fn node_cluster(&self, _node: NR) -> Option<String> {
+        None
+    }
+
+    /// Returns true if this edge should be drawn.
+    ///
+    /// The default implementation draws every edge.
+    fn include_edge(&self, _edge: ER) -> bool {
+        true
+    }
 }
 
 /// A visitor for formatting graph labels that outputs `fmt::Display` impls for node and edge
@@ -56,6 +87,22 @@ where
     fn visit_edge(&self, edge: ER, f: &mut DotWrite<'_, '_>) -> fmt::Result {
         (*self).visit_edge(edge, f)
     }
+
+    fn visit_node_attrs(&self, node: NR, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        (*self).visit_node_attrs(node, attrs)
+    }
+
+    fn visit_edge_attrs(&self, edge: ER, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        (*self).visit_edge_attrs(edge, attrs)
+    }
+
+    fn node_cluster(&self, node: NR) -> Option<String> {
+        (*self).node_cluster(node)
+    }
+
+    fn include_edge(&self, edge: ER) -> bool {
+        (*self).include_edge(edge)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -80,19 +127,30 @@ where
     pub fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "{} {{", graph_type(&self.graph))?;
 
+        // Nodes in clusters are written out after all the other nodes, grouped by cluster.
+        let mut clusters = BTreeMap::new();
         for node in self.graph.node_references() {
-            write!(
-                f,
-                "{}{} [label=\"",
-                INDENT,
-                (&self.graph).to_index(node.id())
-            )?;
-            self.visitor.visit_node(node, &mut DotWrite::new(f))?;
-            writeln!(f, "\"]")?;
+            match self.visitor.node_cluster(node) {
+                Some(cluster) => clusters.entry(cluster).or_insert_with(Vec::new).push(node),
+                None => self.fmt_node(node, INDENT, f)?,
+            }
+        }
+        for (idx, (cluster, nodes)) in clusters.into_iter().enumerate() {
+            writeln!(f, "{}subgraph cluster_{} {{", INDENT, idx)?;
+            write!(f, "{}{}label=\"", INDENT, INDENT)?;
+            write!(DotWrite::new(f), "{}", cluster)?;
+            writeln!(f, "\"")?;
+            for node in nodes {
+                self.fmt_node(node, &INDENT.repeat(2), f)?;
+            }
+            writeln!(f, "{}}}", INDENT)?;
         }
 
         let edge_str = edge_str(&self.graph);
         for edge in self.graph.edge_references() {
+            if !self.visitor.include_edge(edge) {
+                continue;
+            }
             write!(
                 f,
                 "{}{} {} {} [label=\"",
@@ -102,11 +160,31 @@ where
                 (&self.graph).to_index(edge.target())
             )?;
             self.visitor.visit_edge(edge, &mut DotWrite::new(f))?;
-            writeln!(f, "\"]")?;
+            write!(f, "\"")?;
+            self.visitor.visit_edge_attrs(edge, &mut DotAttrs::new(f))?;
+            writeln!(f, "]")?;
         }
 
         writeln!(f, "}}")
     }
+
+    fn fmt_node<'a>(
+        &'a self,
+        node: <&'a G as IntoNodeReferences>::NodeRef,
+        indent: &str,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "{}{} [label=\"",
+            indent,
+            (&self.graph).to_index(node.id())
+        )?;
+        self.visitor.visit_node(node, &mut DotWrite::new(f))?;
+        write!(f, "\"")?;
+        self.visitor.visit_node_attrs(node, &mut DotAttrs::new(f))?;
+        writeln!(f, "]")
+    }
 }
 
 impl<G, V> fmt::Display for DotFmt<G, V>
@@ -155,6 +233,27 @@ impl<'a, 'b> DotWrite<'a, 'b> {
     }
 }
 
+/// A write target for attributes of `dot` nodes and edges, other than their labels.
+pub struct DotAttrs<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+}
+
+impl<'a, 'b> DotAttrs<'a, 'b> {
+    fn new(f: &'a mut fmt::Formatter<'b>) -> Self {
+        Self { f }
+    }
+
+    /// Writes out an attribute with the given name and value.
+    ///
+    /// The name must be a valid graphviz attribute name such as `color` or `style`. The value is
+    /// escaped the same way labels written to `DotWrite` are.
+    pub fn attr(&mut self, name: &str, value: impl fmt::Display) -> fmt::Result {
+        write!(self.f, ", {}=\"", name)?;
+        write!(DotWrite::new(self.f), "{}", value)?;
+        self.f.write_str("\"")
+    }
+}
+
 impl<'a, 'b> Write for DotWrite<'a, 'b> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for c in s.chars() {
@@ -59,6 +59,14 @@ impl<Ix: IndexType> Sccs<Ix> {
         }
     }
 
+    /// Iterate over all SCCs, including single-element ones, in the direction specified.
+    pub fn scc_iter(&self, direction: Direction) -> SccIter<'_, Ix> {
+        SccIter {
+            sccs: self.sccs.iter(),
+            direction,
+        }
+    }
+
     /// Returns all the SCCs with more than one element.
     pub fn multi_sccs(&self) -> impl Iterator<Item = &[NodeIndex<Ix>]> + DoubleEndedIterator {
         self.sccs.iter().filter(|scc| scc.len() > 1)
@@ -125,6 +133,31 @@ impl<Ix: IndexType> Sccs<Ix> {
     }
 }
 
+/// An iterator over strongly connected components.
+#[derive(Clone, Debug)]
+pub(crate) struct SccIter<'a, Ix> {
+    sccs: nested::Iter<'a, Vec<NodeIndex<Ix>>>,
+    direction: Direction,
+}
+
+impl<'a, Ix: IndexType> Iterator for SccIter<'a, Ix> {
+    type Item = &'a [NodeIndex<Ix>];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // As with NodeIter, outgoing implies iterating over the sccs in forward order.
+        match self.direction {
+            Direction::Outgoing => self.sccs.next(),
+            Direction::Incoming => self.sccs.next_back(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.sccs.size_hint()
+    }
+}
+
+impl<'a, Ix: IndexType> ExactSizeIterator for SccIter<'a, Ix> {}
+
 /// An iterator over the nodes of strongly connected components.
 #[derive(Clone, Debug)]
 pub(crate) struct NodeIter<'a, Ix> {
@@ -62,8 +62,8 @@ impl<'g> PlatformStatus<'g> {
 
     /// Evaluates whether this dependency is enabled on the given platform spec.
     ///
-    /// Returns `Unknown` if the result was unknown, which may happen if evaluating against an
-    /// individual platform and its target features are unknown.
+    /// Returns `Unknown` if the result was unknown, which may happen if evaluating against
+    /// individual platforms and their target features are unknown.
     pub fn enabled_on(&self, platform_spec: &PlatformSpec) -> EnabledTernary {
         match (self, platform_spec) {
             (PlatformStatus::Always, _) => EnabledTernary::Enabled,
@@ -74,6 +74,9 @@ impl<'g> PlatformStatus<'g> {
             (PlatformStatus::PlatformDependent { eval }, PlatformSpec::Platform(platform)) => {
                 eval.eval(platform)
             }
+            (PlatformStatus::PlatformDependent { eval }, PlatformSpec::Multi(platforms)) => {
+                eval.eval_any(platforms.iter().map(|platform| &**platform))
+            }
             (PlatformStatus::PlatformDependent { .. }, PlatformSpec::Always) => {
                 EnabledTernary::Disabled
             }
@@ -173,6 +176,23 @@ impl<'g> PlatformEval<'g> {
         }
         res
     }
+
+    /// Runs this evaluator against each of the given platforms, returning `Enabled` if it is
+    /// enabled on any of them.
+    pub fn eval_any<'a>(
+        &self,
+        platforms: impl IntoIterator<Item = &'a Platform>,
+    ) -> EnabledTernary {
+        let mut res = EnabledTernary::Disabled;
+        for platform in platforms {
+            res = res | self.eval(platform);
+            // Short-circuit evaluation if possible.
+            if res == EnabledTernary::Enabled {
+                break;
+            }
+        }
+        res
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -10,11 +10,12 @@ use std::sync::Arc;
 ///
 /// Some uses of `guppy` care about a single platform, and others care about queries against the
 /// intersection of all hypothetical platforms, or against a union of any of them. `PlatformSpec`
-/// handles the
+/// handles all of these cases, as well as queries against a fixed set of platforms, such as the
+/// platforms a project supports.
 ///
 /// `PlatformSpec` does not currently support expressions, but it might in the future, using an
 /// [SMT solver](https://en.wikipedia.org/wiki/Satisfiability_modulo_theories).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 #[non_exhaustive]
 pub enum PlatformSpec {
     /// The intersection of all platforms.
@@ -35,6 +36,17 @@ pub enum PlatformSpec {
     /// [`EnabledTernary::Unknown`] if a platform is not enabled.
     Platform(Arc<Platform>),
 
+    /// The union of a set of individual platforms.
+    ///
+    /// Dependency queries performed against this variant will return [`EnabledTernary::Enabled`] if
+    /// a dependency is enabled on any of these platforms, and [`EnabledTernary::Disabled`] if it is
+    /// disabled on all of them. Otherwise, they will return [`EnabledTernary::Unknown`].
+    ///
+    /// This is equivalent to performing a query against each platform separately and combining the
+    /// results, but only requires a single query. An empty set of platforms doesn't match any
+    /// platform-dependent dependencies.
+    Multi(Vec<Arc<Platform>>),
+
     /// The union of all platforms.
     ///
     /// Dependency queries performed against this variant will return [`EnabledTernary::Enabled`] if
@@ -54,6 +66,23 @@ impl PlatformSpec {
     pub fn current() -> Result<Self, TargetSpecError> {
         Ok(PlatformSpec::Platform(Arc::new(Platform::current()?)))
     }
+
+    /// Returns a `PlatformSpec` corresponding to the union of the given platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use guppy::platform::{Platform, PlatformSpec, TargetFeatures};
+    ///
+    /// let platform_spec = PlatformSpec::multi(
+    ///     ["x86_64-unknown-linux-gnu", "x86_64-apple-darwin", "x86_64-pc-windows-msvc"]
+    ///         .iter()
+    ///         .map(|triple| Platform::new(*triple, TargetFeatures::Unknown).unwrap()),
+    /// );
+    /// ```
+    pub fn multi<T: Into<Arc<Platform>>>(platforms: impl IntoIterator<Item = T>) -> Self {
+        PlatformSpec::Multi(platforms.into_iter().map(Into::into).collect())
+    }
 }
 
 impl<T: Into<Arc<Platform>>> From<T> for PlatformSpec {
@@ -75,6 +75,27 @@ pub enum PlatformSpecSummary {
     /// ```
     Platform(PlatformSummary),
 
+    /// The union of a set of individual platforms.
+    ///
+    /// This is converted to and from [`PlatformSpec::Multi`], and is serialized as a map with a
+    /// `platforms` key.
+    ///
+    /// # Examples
+    ///
+    /// Deserialize a list of target triples.
+    ///
+    /// ```
+    /// # use guppy::platform::PlatformSpecSummary;
+    /// let spec: PlatformSpecSummary = toml::from_str(r#"
+    /// platforms = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"]
+    /// "#).unwrap();
+    /// match spec {
+    ///     PlatformSpecSummary::Multi(platforms) => assert_eq!(platforms.len(), 2),
+    ///     other => panic!("expected Multi, found {:?}", other),
+    /// }
+    /// ```
+    Multi(Vec<PlatformSummary>),
+
     /// The union of all platforms.
     ///
     /// This is converted to and from [`PlatformSpec::Any`], and is serialized as the string
@@ -111,6 +132,12 @@ impl PlatformSpecSummary {
             PlatformSpec::Platform(platform) => {
                 PlatformSpecSummary::Platform(platform.to_summary())
             }
+            PlatformSpec::Multi(platforms) => PlatformSpecSummary::Multi(
+                platforms
+                    .iter()
+                    .map(|platform| platform.to_summary())
+                    .collect(),
+            ),
             PlatformSpec::Any => PlatformSpecSummary::Any,
         }
     }
@@ -124,6 +151,12 @@ impl PlatformSpecSummary {
             PlatformSpecSummary::Platform(platform) => {
                 Ok(PlatformSpec::Platform(Arc::new(platform.to_platform()?)))
             }
+            PlatformSpecSummary::Multi(platforms) => Ok(PlatformSpec::Multi(
+                platforms
+                    .iter()
+                    .map(|platform| Ok(Arc::new(platform.to_platform()?)))
+                    .collect::<Result<_, TargetSpecError>>()?,
+            )),
             PlatformSpecSummary::Any => Ok(PlatformSpec::Any),
         }
     }
@@ -143,7 +176,7 @@ impl Default for PlatformSpecSummary {
 
 mod serde_impl {
     use super::*;
-    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
     use std::collections::BTreeSet;
     use target_spec::summaries::TargetFeaturesSummary;
 
@@ -156,10 +189,18 @@ mod serde_impl {
                 PlatformSpecSummary::Always => Spec { spec: "always" }.serialize(serializer),
                 PlatformSpecSummary::Any => Spec { spec: "any" }.serialize(serializer),
                 PlatformSpecSummary::Platform(platform) => platform.serialize(serializer),
+                PlatformSpecSummary::Multi(platforms) => {
+                    Platforms { platforms }.serialize(serializer)
+                }
             }
         }
     }
 
+    #[derive(Serialize)]
+    struct Platforms<'a> {
+        platforms: &'a [PlatformSummary],
+    }
+
     // Ideally we'd serialize always or any as just those strings, but that runs into ValueAfterTable
     // issues with toml. So serialize always/any as "spec = always" etc.
     #[derive(Serialize)]
@@ -197,6 +238,17 @@ mod serde_impl {
                     target_features,
                     flags,
                 })),
+                PlatformSpecSummaryDeserialize::Multi { platforms } => platforms
+                    .into_iter()
+                    .map(|platform| match platform {
+                        PlatformSpecSummary::Platform(platform) => Ok(platform),
+                        other => Err(D::Error::custom(format!(
+                            "expected an individual platform in `platforms`, found {:?}",
+                            other
+                        ))),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(PlatformSpecSummary::Multi),
             }
         }
     }
@@ -218,6 +270,9 @@ mod serde_impl {
             #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
             flags: BTreeSet<String>,
         },
+        Multi {
+            platforms: Vec<PlatformSpecSummary>,
+        },
     }
 }
 
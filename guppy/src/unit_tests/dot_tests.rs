@@ -1,7 +1,7 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::petgraph_support::dot::{DisplayVisitor, DotFmt, DotVisitor, DotWrite};
+use crate::petgraph_support::dot::{DisplayVisitor, DotAttrs, DotFmt, DotVisitor, DotWrite};
 use petgraph::{
     prelude::*,
     visit::{EdgeRef, NodeRef},
@@ -57,6 +57,38 @@ fn dot_fmt() {
     );
 }
 
+#[test]
+fn dot_fmt_attrs_clusters() {
+    let mut graph = Graph::new();
+    let a = graph.add_node("A");
+    let b1 = graph.add_node("B1");
+    let c = graph.add_node("C");
+    let b2 = graph.add_node("B2");
+    graph.add_edge(a, b1, 100);
+    graph.add_edge(b1, c, 200);
+    graph.add_edge(a, b2, 300);
+
+    let dot_fmt = DotFmt::new(&graph, ClusterVisitor);
+    let output = format!("{}", dot_fmt);
+    static EXPECTED_DOT: &str = r#"digraph {
+    0 [label="A", color="blue"]
+    subgraph cluster_0 {
+        label="B \"cluster\""
+        1 [label="B1", color="blue"]
+        3 [label="B2", color="blue"]
+    }
+    subgraph cluster_1 {
+        label="C cluster"
+        2 [label="C", color="blue"]
+    }
+    0 -> 1 [label="100"]
+    1 -> 2 [label="200", style="dashed"]
+    0 -> 3 [label="300"]
+}
+"#;
+    assert_eq!(&output, EXPECTED_DOT, "dot output matches");
+}
+
 /// A visitor for formatting graph labels that outputs `fmt::Display` impls for node and edge
 /// weights.
 ///
@@ -81,3 +113,42 @@ where
         write!(f, "{}", edge.weight())
     }
 }
+
+/// A visitor that colors all nodes, clusters nodes other than `A` by their first letter, and makes
+/// the edge with weight 200 dashed.
+#[derive(Copy, Clone, Debug)]
+pub struct ClusterVisitor;
+
+impl<NR, ER> DotVisitor<NR, ER> for ClusterVisitor
+where
+    NR: NodeRef,
+    ER: EdgeRef<Weight = i32>,
+    NR::Weight: fmt::Display,
+{
+    fn visit_node(&self, node: NR, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        write!(f, "{}", node.weight())
+    }
+
+    fn visit_edge(&self, edge: ER, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        write!(f, "{}", edge.weight())
+    }
+
+    fn visit_node_attrs(&self, _node: NR, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        attrs.attr("color", "blue")
+    }
+
+    fn visit_edge_attrs(&self, edge: ER, attrs: &mut DotAttrs<'_, '_>) -> fmt::Result {
+        if *edge.weight() == 200 {
+            attrs.attr("style", "dashed")?;
+        }
+        Ok(())
+    }
+
+    fn node_cluster(&self, node: NR) -> Option<String> {
+        match &node.weight().to_string()[..1] {
+            "A" => None,
+            "B" => Some(r#"B "cluster""#.to_owned()),
+            other => Some(format!("{} cluster", other)),
+        }
+    }
+}
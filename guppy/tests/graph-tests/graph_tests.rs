@@ -5,12 +5,23 @@ use fixtures::{
     json::{self, JsonFixture},
     package_id,
 };
-use guppy::graph::{
-    feature::{named_feature_filter, FeatureId, FeatureLabel, StandardFeatures},
-    BuildTargetId, BuildTargetKind, DependencyDirection, DotWrite, PackageDotVisitor, PackageLink,
-    PackageMetadata,
+use guppy::{
+    errors::ParseWarning,
+    graph::{
+        cargo::{CargoOptions, CargoSetBuilder, InitialsPlatform, UnusedFeatures},
+        feature::{
+            named_feature_filter, ConditionalLink, FeatureDotVisitor, FeatureId, FeatureLabel,
+            FeatureMetadata, FeatureProvenance, FeatureSet, StandardFeatures,
+        },
+        ArtifactKind, ArtifactReq, ArtifactTarget, BuildTargetId, BuildTargetKind,
+        DependencyDirection, DotWrite, ExternalSource, PackageDotVisitor, PackageGraph,
+        PackageIdSpec, PackageLink, PackageMetadata, PackageSet, RequirementConflictKind,
+    },
+    platform::{Platform, TargetFeatures},
+    CargoMetadata, DependencyKind, Error, Version, VersionReq,
 };
-use std::{fmt, iter};
+use serde::Deserialize;
+use std::{cmp::Ordering, collections::BTreeMap, fmt, iter};
 
 mod small {
     use super::*;
@@ -135,8 +146,437 @@ mod small {
         assert_eq!(root_ids, expected, "feature graph root IDs match");
     }
 
+    #[test]
+    fn metadata1_feature_closure_dot() {
+        let metadata1 = JsonFixture::metadata1();
+        let graph = metadata1.graph();
+        let feature_graph = graph.feature_graph();
+        let mach = graph
+            .packages()
+            .find(|package| package.name() == "mach")
+            .expect("mach is in the graph");
+
+        // Feature indexes aren't stable across builds of the graph, so compare nodes and edges by
+        // their labels.
+        let expected = vec![
+            "libc/[base]",
+            "libc/std",
+            "libc/use_std",
+            "mach/[base]",
+            "mach/default",
+            "mach/deprecated",
+            "mach/use_std",
+            "libc/std -> libc/[base] []",
+            "libc/use_std -> libc/[base] []",
+            "libc/use_std -> libc/std []",
+            "mach/[base] -> libc/[base] [libc]",
+            "mach/default -> mach/[base] []",
+            "mach/default -> mach/deprecated []",
+            "mach/default -> mach/use_std []",
+            "mach/deprecated -> mach/[base] []",
+            "mach/use_std -> libc/use_std [libc]",
+            "mach/use_std -> mach/[base] []",
+        ];
+        let default_id = FeatureId::named(mach.id(), "default");
+        let dot = feature_graph
+            .feature_closure_dot(default_id, FeatureNameVisitor)
+            .expect("valid feature ID")
+            .to_string();
+        assert_eq!(
+            expected,
+            dot_by_labels(&dot),
+            "feature closure dot output matches"
+        );
+
+        let closure = feature_graph
+            .feature_closure(default_id)
+            .expect("valid feature ID");
+        assert_eq!(closure.len(), 7, "seven features in the closure");
+
+        let feature_set = feature_graph
+            .query_forward(iter::once(default_id))
+            .expect("valid feature ID")
+            .resolve();
+        assert_eq!(
+            dot,
+            feature_set.display_dot(FeatureNameVisitor).to_string(),
+            "feature set dot output matches"
+        );
+
+        assert!(
+            feature_graph
+                .feature_closure_dot(
+                    FeatureId::named(mach.id(), "nonexistent"),
+                    FeatureNameVisitor
+                )
+                .is_err(),
+            "unknown feature is rejected"
+        );
+    }
+
     proptest_suite!(metadata1);
 
+    #[test]
+    fn metadata1_sample() {
+        let metadata1 = JsonFixture::metadata1();
+        let graph = metadata1.graph();
+        let ids = |package_set: &PackageSet<'_>| {
+            package_set
+                .package_ids(DependencyDirection::Forward)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        let sample = graph.sample_packages(42, 5);
+        assert_eq!(sample.len(), 5, "5 packages sampled");
+        assert_eq!(
+            ids(&sample),
+            ids(&graph.sample_packages(42, 5)),
+            "same seed produces the same sample"
+        );
+        assert_ne!(
+            ids(&sample),
+            ids(&graph.sample_packages(43, 5)),
+            "different seeds produce different samples"
+        );
+        assert_eq!(graph.sample_packages(42, 0).len(), 0, "empty sample");
+        assert_eq!(
+            graph.sample_packages(42, usize::MAX).len(),
+            graph.package_count(),
+            "oversized sample includes every package"
+        );
+
+        let feature_graph = graph.feature_graph();
+        let feature_sets = feature_graph.sample_feature_sets(42, 3);
+        assert_eq!(feature_sets.len(), 3, "3 feature sets sampled");
+        let feature_ids = |feature_sets: &[_]| -> Vec<Vec<_>> {
+            feature_sets
+                .iter()
+                .map(|feature_set: &FeatureSet<'_>| {
+                    feature_set
+                        .feature_ids(DependencyDirection::Forward)
+                        .collect()
+                })
+                .collect()
+        };
+        let feature_ids_1 = feature_ids(&feature_sets);
+        assert_eq!(
+            feature_ids_1,
+            feature_ids(&feature_graph.sample_feature_sets(42, 3)),
+            "same seed produces the same feature sets"
+        );
+        assert_ne!(
+            feature_ids_1[0], feature_ids_1[1],
+            "feature sets within a sample differ"
+        );
+        for feature_set in &feature_sets {
+            assert!(
+                feature_set.len() < feature_graph.feature_count(),
+                "feature sets are proper subsets"
+            );
+        }
+    }
+
+    #[test]
+    fn metadata1_bounded() {
+        let metadata1 = JsonFixture::metadata1();
+        let graph = metadata1.graph();
+        let region = package_id(json::METADATA1_REGION);
+        let names = |package_set: &PackageSet<'_>| {
+            let mut names: Vec<_> = package_set
+                .packages(DependencyDirection::Forward)
+                .map(|package| package.name().to_owned())
+                .collect();
+            names.sort_unstable();
+            names
+        };
+
+        let package_set = graph
+            .query_forward_bounded(iter::once(&region), 0)
+            .unwrap()
+            .resolve();
+        assert_eq!(
+            names(&package_set),
+            vec!["region"],
+            "depth 0 is the initial"
+        );
+
+        let query = graph.query_forward_bounded(iter::once(&region), 1).unwrap();
+        assert_eq!(query.max_depth(), Some(1), "max depth matches");
+        assert_eq!(
+            names(&query.clone().resolve()),
+            vec!["bitflags", "libc", "mach", "region", "winapi"],
+            "depth 1 includes direct dependencies"
+        );
+
+        // libc is both a direct dependency and a dependency of mach, so its depth is 1.
+        let depths = query.resolve_depths();
+        let libc = graph
+            .metadata(&region)
+            .unwrap()
+            .direct_links()
+            .find(|link| link.to().name() == "libc")
+            .expect("region depends on libc")
+            .to();
+        assert_eq!(
+            depths.depth(libc.id()).unwrap(),
+            Some(1),
+            "libc depth matches"
+        );
+        assert_eq!(
+            depths.depth(&region).unwrap(),
+            Some(0),
+            "region depth matches"
+        );
+        assert_eq!(
+            depths.depth(&package_id(json::METADATA1_DTOA)).unwrap(),
+            None,
+            "dtoa isn't resolved"
+        );
+        let all_depths: Vec<_> = depths.packages().map(|(_, depth)| depth).collect();
+        assert_eq!(all_depths, vec![0, 1, 1, 1, 1], "depths are in order");
+
+        // Unbounded depths include the winapi-* packages at depth 2.
+        let depths = graph
+            .query_forward(iter::once(&region))
+            .unwrap()
+            .resolve_depths();
+        assert_eq!(depths.package_set().len(), 7, "all dependencies resolved");
+        let max_depth = depths.packages().map(|(_, depth)| depth).max();
+        assert_eq!(max_depth, Some(2), "max depth matches");
+
+        // Resolvers are honored.
+        let package_set = graph
+            .query_forward_bounded(iter::once(&region), 2)
+            .unwrap()
+            .resolve_with_fn(|_, link| link.to().name() != "libc");
+        assert_eq!(
+            names(&package_set),
+            vec![
+                "bitflags",
+                "mach",
+                "region",
+                "winapi",
+                "winapi-i686-pc-windows-gnu",
+                "winapi-x86_64-pc-windows-gnu"
+            ],
+            "libc is skipped"
+        );
+
+        let package_set = graph
+            .query_reverse_bounded(iter::once(&package_id(json::METADATA1_DTOA)), 1)
+            .unwrap()
+            .resolve();
+        assert_eq!(
+            names(&package_set),
+            vec!["dtoa", "serde_yaml"],
+            "reverse depth 1 includes direct dependents"
+        );
+
+        // Links between features within a package count towards the depth.
+        let feature_graph = graph.feature_graph();
+        let feature_set = feature_graph
+            .query_forward_bounded(iter::once(FeatureId::base(&region)), 0)
+            .unwrap()
+            .resolve();
+        assert_eq!(feature_set.len(), 1, "depth 0 is the initial feature");
+        let depths = feature_graph
+            .query_forward(iter::once(FeatureId::base(&region)))
+            .unwrap()
+            .resolve_depths();
+        let all_depths: Vec<_> = depths.features().map(|(_, depth)| depth).collect();
+        assert!(
+            all_depths.windows(2).all(|w| w[0] <= w[1]),
+            "feature depths are nondecreasing"
+        );
+        assert_eq!(
+            depths.feature_set().len(),
+            feature_graph
+                .query_forward(iter::once(FeatureId::base(&region)))
+                .unwrap()
+                .resolve()
+                .len(),
+            "unbounded depths resolve all features"
+        );
+    }
+
+    #[test]
+    fn metadata1_filter_platforms() {
+        let metadata1 = JsonFixture::metadata1();
+        let filtered_graph = |triple: &'static str| {
+            let platform = Platform::new(triple, TargetFeatures::Unknown).expect("known triple");
+            let mut metadata = CargoMetadata::parse_json(metadata1.json()).expect("valid JSON");
+            metadata.filter_platforms(&[platform]);
+            metadata.build_graph().expect("valid graph")
+        };
+
+        let linux_graph = filtered_graph("x86_64-unknown-linux-gnu");
+        assert_eq!(
+            linux_graph.resolve_package_name("winapi").len(),
+            0,
+            "winapi pruned on Linux"
+        );
+        assert!(
+            linux_graph.package_count() < metadata1.graph().package_count(),
+            "some packages pruned on Linux"
+        );
+
+        let windows_graph = filtered_graph("x86_64-pc-windows-msvc");
+        assert_eq!(
+            windows_graph.resolve_package_name("winapi").len(),
+            1,
+            "winapi retained on Windows"
+        );
+    }
+
+    #[test]
+    fn metadata_parse_warnings() {
+        // metadata1 was generated by an old version of Cargo that didn't output some fields.
+        let metadata1 = JsonFixture::metadata1();
+        assert!(
+            metadata1
+                .graph()
+                .parse_warnings()
+                .iter()
+                .all(|warning| matches!(warning, ParseWarning::MissingField { .. })),
+            "metadata1 only has missing fields"
+        );
+        assert!(
+            !metadata1.graph().parse_warnings().is_empty(),
+            "metadata1 has warnings"
+        );
+
+        let builddep = JsonFixture::metadata_builddep();
+        assert!(
+            builddep.graph().parse_warnings().is_empty(),
+            "builddep is fully understood"
+        );
+        CargoMetadata::parse_json_strict(builddep.json()).expect("strict parsing succeeds");
+
+        // Simulate a newer version of Cargo adding a field.
+        let mut value: serde_json::Value =
+            serde_json::from_str(builddep.json()).expect("builddep is valid JSON");
+        value
+            .as_object_mut()
+            .expect("metadata is an object")
+            .insert("new_field".to_owned(), serde_json::Value::Bool(true));
+        let json = value.to_string();
+
+        let graph = PackageGraph::from_json(&json).expect("non-strict parsing succeeds");
+        let warnings: Vec<_> = graph.parse_warnings().iter().cloned().collect();
+        assert_eq!(
+            warnings,
+            [ParseWarning::UnknownField {
+                path: "new_field".to_owned()
+            }],
+            "unknown field recorded"
+        );
+        match CargoMetadata::parse_json_strict(&json) {
+            Err(Error::MetadataStrictError(strict_warnings)) => {
+                assert_eq!(&strict_warnings, graph.parse_warnings(), "warnings match");
+            }
+            other => panic!("expected strict parsing to fail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn metadata1_apply_update() {
+        let metadata1 = JsonFixture::metadata1();
+        let testcrate = package_id(json::METADATA1_TESTCRATE);
+        let mut graph = metadata1.graph().clone();
+        // Compute the feature graph and cycles so that they can be carried over.
+        let feature_count = graph.feature_graph().feature_count();
+        graph.cycles();
+
+        let mut value: serde_json::Value =
+            serde_json::from_str(metadata1.json()).expect("metadata1 is valid JSON");
+        let mut apply = |graph: &mut PackageGraph, edit: &dyn Fn(&mut serde_json::Value)| {
+            edit(&mut value);
+            let metadata = CargoMetadata::parse_json(value.to_string()).expect("valid metadata");
+            let update = graph
+                .apply_metadata_update(metadata)
+                .expect("update succeeds");
+            let expected = PackageGraph::from_json(value.to_string()).expect("valid metadata");
+            assert_eq!(
+                graph.package_count(),
+                expected.package_count(),
+                "package count"
+            );
+            assert_eq!(graph.link_count(), expected.link_count(), "link count");
+            assert_eq!(
+                graph.feature_graph().feature_count(),
+                expected.feature_graph().feature_count(),
+                "feature count"
+            );
+            update
+        };
+        fn testcrate_json(value: &mut serde_json::Value) -> &mut serde_json::Value {
+            value["packages"]
+                .as_array_mut()
+                .expect("packages is an array")
+                .iter_mut()
+                .find(|package| package["id"] == json::METADATA1_TESTCRATE)
+                .expect("testcrate is present")
+        }
+
+        // Editing the description only reprocesses testcrate, and keeps everything else.
+        let update = apply(&mut graph, &|value| {
+            testcrate_json(value)["description"] = "updated".into();
+        });
+        assert_eq!(update.processed_packages(), [&testcrate]);
+        assert!(update.removed_packages().is_empty(), "no packages removed");
+        assert_eq!(update.reused_count(), graph.package_count() - 1);
+        assert!(update.cycles_retained(), "cycles retained");
+        assert!(update.feature_graph_retained(), "feature graph retained");
+        assert_eq!(
+            graph
+                .metadata(&testcrate)
+                .expect("testcrate exists")
+                .description(),
+            Some("updated"),
+        );
+
+        // Adding a feature invalidates the feature graph, but not cycles.
+        let update = apply(&mut graph, &|value| {
+            testcrate_json(value)["features"] = serde_json::json!({ "foo": [] });
+        });
+        assert_eq!(update.processed_packages(), [&testcrate]);
+        assert!(update.cycles_retained(), "cycles retained");
+        assert!(
+            !update.feature_graph_retained(),
+            "feature graph invalidated"
+        );
+        assert_eq!(graph.feature_graph().feature_count(), feature_count + 1);
+
+        // Removing testcrate's dependencies invalidates everything.
+        let update = apply(&mut graph, &|value| {
+            testcrate_json(value)["dependencies"] = serde_json::json!([]);
+            let node = value["resolve"]["nodes"]
+                .as_array_mut()
+                .expect("nodes is an array")
+                .iter_mut()
+                .find(|node| node["id"] == json::METADATA1_TESTCRATE)
+                .expect("testcrate node is present");
+            node["deps"] = serde_json::json!([]);
+            node["dependencies"] = serde_json::json!([]);
+        });
+        assert_eq!(update.processed_packages(), [&testcrate]);
+        assert!(!update.cycles_retained(), "cycles invalidated");
+        assert!(
+            !update.feature_graph_retained(),
+            "feature graph invalidated"
+        );
+        assert_eq!(
+            graph
+                .metadata(&testcrate)
+                .expect("testcrate exists")
+                .direct_links()
+                .count(),
+            0,
+            "testcrate has no dependencies"
+        );
+    }
+
     #[test]
     fn metadata2() {
         let metadata2 = JsonFixture::metadata2();
@@ -232,6 +672,248 @@ mod small {
         }
     }
 
+    #[test]
+    fn metadata_cycle_features_cycles() {
+        let metadata = JsonFixture::metadata_cycle_features();
+        let feature_graph = metadata.graph().feature_graph();
+        let base_id = package_id(METADATA_CYCLE_FEATURES_BASE);
+        let helper_id = package_id(json::METADATA_CYCLE_FEATURES_HELPER);
+
+        // base dev-depends on helper, and helper depends on base with the helper-enable feature.
+        // This forms a single cycle through the features of base that are reachable from helper.
+        let cycles = feature_graph.cycles();
+        // The order of features within a cycle depends on the order in which named features are
+        // added to the graph, so sort them before comparing.
+        let all_cycles: Vec<_> = cycles
+            .all_cycles()
+            .map(|mut cycle| {
+                cycle.sort();
+                cycle
+            })
+            .collect();
+        let mut expected = vec![vec![
+            FeatureId::base(&helper_id),
+            FeatureId::base(&base_id),
+            FeatureId::named(&base_id, "default"),
+            FeatureId::named(&base_id, "default-enable"),
+            FeatureId::named(&base_id, "default-transitive"),
+            FeatureId::named(&base_id, "helper-enable"),
+            FeatureId::named(&base_id, "helper-transitive"),
+        ]];
+        expected[0].sort();
+        assert_eq!(all_cycles, expected, "feature cycles match");
+
+        for &a in &expected[0] {
+            for &b in &expected[0] {
+                assert!(
+                    cycles.is_cyclic(a, b).expect("valid feature IDs"),
+                    "{} and {} are in the same cycle",
+                    a,
+                    b,
+                );
+                assert!(
+                    feature_graph.depends_on(a, b).expect("valid feature IDs"),
+                    "within cycle, {} depends on {}",
+                    a,
+                    b,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn metadata_cycle_features_closure_dot() {
+        let metadata = JsonFixture::metadata_cycle_features();
+        let feature_graph = metadata.graph().feature_graph();
+        let helper_id = package_id(json::METADATA_CYCLE_FEATURES_HELPER);
+
+        // helper depends on base, and base dev-depends on helper, so the closure of helper contains
+        // both ends of base's dev-only links to helper. Those links aren't drawn.
+        let closure = feature_graph
+            .feature_closure(FeatureId::base(&helper_id))
+            .expect("valid feature ID");
+        let dev_only_links: Vec<_> = closure
+            .conditional_links(DependencyDirection::Forward)
+            .filter(|link| link.dev_only())
+            .map(|link| {
+                let (from, to) = link.endpoints();
+                format!(
+                    "{}/{} -> {}/{} [{}]",
+                    from.package().name(),
+                    from.label(),
+                    to.package().name(),
+                    to.label(),
+                    link.package_link().dep_name(),
+                )
+            })
+            .collect();
+        assert!(
+            !dev_only_links.is_empty(),
+            "closure contains both ends of dev-only links"
+        );
+
+        let closure_dot = dot_by_labels(
+            &feature_graph
+                .feature_closure_dot(FeatureId::base(&helper_id), FeatureNameVisitor)
+                .expect("valid feature ID")
+                .to_string(),
+        );
+        let set_dot = dot_by_labels(&closure.display_dot(FeatureNameVisitor).to_string());
+        for link in &dev_only_links {
+            assert!(
+                !closure_dot.contains(link),
+                "dev-only link {} isn't drawn in closure dot output",
+                link
+            );
+            assert!(
+                set_dot.contains(link),
+                "dev-only link {} is drawn in feature set dot output",
+                link
+            );
+        }
+        let expected: Vec<_> = set_dot
+            .iter()
+            .filter(|line| !dev_only_links.contains(line))
+            .cloned()
+            .collect();
+        assert_eq!(
+            closure_dot, expected,
+            "closure dot output is feature set dot output without dev-only links"
+        );
+    }
+
+    #[test]
+    fn metadata_cycle_features_directly_depends_on_kind() {
+        let metadata = JsonFixture::metadata_cycle_features();
+        let graph = metadata.graph();
+        let feature_graph = graph.feature_graph();
+        let base_id = package_id(METADATA_CYCLE_FEATURES_BASE);
+        let helper_id = package_id(json::METADATA_CYCLE_FEATURES_HELPER);
+
+        // base dev-depends on helper, and helper depends normally on base.
+        for (a, b, kind, expected) in [
+            (&base_id, &helper_id, DependencyKind::Development, true),
+            (&base_id, &helper_id, DependencyKind::Normal, false),
+            (&helper_id, &base_id, DependencyKind::Normal, true),
+            (&helper_id, &base_id, DependencyKind::Development, false),
+            (&helper_id, &base_id, DependencyKind::Build, false),
+            (&base_id, &base_id, DependencyKind::Normal, false),
+        ] {
+            assert_eq!(
+                graph
+                    .directly_depends_on_kind(a, b, kind)
+                    .expect("valid package IDs"),
+                expected,
+                "{} -> {} ({:?})",
+                a,
+                b,
+                kind,
+            );
+        }
+
+        let helper_enable = FeatureId::named(&base_id, "helper-enable");
+        assert!(feature_graph
+            .directly_depends_on_kind(
+                FeatureId::base(&helper_id),
+                helper_enable,
+                DependencyKind::Normal
+            )
+            .expect("valid feature IDs"));
+        assert!(!feature_graph
+            .directly_depends_on_kind(
+                FeatureId::base(&helper_id),
+                helper_enable,
+                DependencyKind::Development
+            )
+            .expect("valid feature IDs"));
+
+        // Links within a package aren't dependencies of any kind.
+        let default_id = FeatureId::named(&base_id, "default");
+        let default_enable = FeatureId::named(&base_id, "default-enable");
+        assert!(feature_graph
+            .directly_depends_on(default_id, default_enable)
+            .expect("valid feature IDs"));
+        assert!(!feature_graph
+            .directly_depends_on_kind(default_id, default_enable, DependencyKind::Normal)
+            .expect("valid feature IDs"));
+    }
+
+    #[test]
+    fn metadata_cycle_features_only_sources() {
+        let metadata = JsonFixture::metadata_cycle_features();
+        let graph = metadata.graph();
+        let base_id = package_id(METADATA_CYCLE_FEATURES_BASE);
+        let helper_id = package_id(json::METADATA_CYCLE_FEATURES_HELPER);
+        let feature_set_for = |id, features| {
+            graph
+                .resolve_ids([id])
+                .expect("valid package ID")
+                .to_feature_set(features)
+        };
+
+        let mut builder = CargoSetBuilder::new(feature_set_for(&base_id, StandardFeatures::None));
+        builder
+            .add_features_only(
+                "helper",
+                feature_set_for(&helper_id, StandardFeatures::None),
+            )
+            .add_features_only(
+                "base-default",
+                feature_set_for(&base_id, StandardFeatures::Default),
+            );
+        let cargo_set = builder
+            .build(&CargoOptions::new())
+            .expect("cargo set resolved");
+
+        let names: Vec<_> = cargo_set
+            .features_only_contributions()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(
+            names,
+            ["helper", "base-default"],
+            "contributions are in order"
+        );
+        let union = builder.features_only().fold(
+            graph.feature_graph().resolve_none(),
+            |acc, (_, features)| acc.union(features),
+        );
+        assert_eq!(
+            cargo_set.features_only(),
+            &union,
+            "features_only is the union"
+        );
+
+        // helper-enable is only enabled through the helper.
+        assert_eq!(
+            cargo_set
+                .features_only_sources(FeatureId::named(&base_id, "helper-enable"))
+                .expect("valid feature ID"),
+            ["helper"],
+        );
+        // helper depends on base with default features, so it also enables default-enable.
+        assert_eq!(
+            cargo_set
+                .features_only_sources(FeatureId::named(&base_id, "default-enable"))
+                .expect("valid feature ID"),
+            ["helper", "base-default"],
+        );
+        // The helper itself isn't built, so it has no sources.
+        assert!(cargo_set
+            .features_only_sources(FeatureId::base(&helper_id))
+            .expect("valid feature ID")
+            .is_empty());
+        cargo_set
+            .features_only_sources(FeatureId::named(&base_id, "unknown"))
+            .expect_err("unknown feature ID");
+
+        // A cargo set constructed directly has no contributions.
+        let cargo_set = feature_set_for(&base_id, StandardFeatures::Default)
+            .into_cargo_set(&CargoOptions::new())
+            .expect("cargo set resolved");
+        assert_eq!(cargo_set.features_only_contributions().len(), 0);
+    }
+
     proptest_suite!(metadata_cycle_features);
 
     #[test]
@@ -313,6 +995,82 @@ mod small {
 
     proptest_suite!(metadata_targets1);
 
+    #[test]
+    fn metadata_targets1_requirement_conflicts() {
+        let package_graph = JsonFixture::metadata_targets1().graph();
+        let testcrate = package_graph
+            .metadata(&package_id(json::METADATA_TARGETS1_TESTCRATE))
+            .expect("valid package ID");
+        let link_to = |id: &str| {
+            testcrate
+                .link_to(&package_id(id))
+                .expect("valid package ID")
+                .expect("link exists")
+        };
+
+        let bytes_link = link_to(json::METADATA_TARGETS1_BYTES);
+        let sections: Vec<_> = bytes_link
+            .declarations()
+            .map(|decl| decl.to_string())
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                "[dependencies]",
+                "[target.'cfg(target_arch = \"x86\")'.dependencies]",
+                "[target.'x86_64-unknown-linux-gnu'.build-dependencies]",
+            ],
+            "bytes declarations"
+        );
+        let kinds: Vec<_> = bytes_link
+            .requirement_conflicts()
+            .iter()
+            .map(|conflict| conflict.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            RequirementConflictKind::VALUES,
+            "bytes has every kind of conflict"
+        );
+
+        let dep_a_link = link_to(json::METADATA_TARGETS1_DEP_A);
+        let conflicts = dep_a_link.requirement_conflicts();
+        let kinds: Vec<_> = conflicts.iter().map(|conflict| conflict.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                RequirementConflictKind::DefaultFeatures,
+                RequirementConflictKind::Features
+            ],
+            "path dependencies on dep-a have no version requirement"
+        );
+        assert_eq!(
+            conflicts[0].declarations().len(),
+            dep_a_link.declarations().len(),
+            "every declaration is included"
+        );
+
+        // Only the normal declarations of dep-a: they differ in features, and all enable default
+        // features.
+        let kinds: Vec<_> = dep_a_link
+            .requirement_conflicts_with(|decl| decl.kind() == DependencyKind::Normal)
+            .iter()
+            .map(|conflict| conflict.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            [RequirementConflictKind::Features],
+            "normal declarations of dep-a"
+        );
+
+        for link in testcrate.direct_links() {
+            if link.to().name() == "lazy_static" {
+                assert_eq!(link.declarations().len(), 1, "each lazy_static is separate");
+                assert!(link.requirement_conflicts().is_empty());
+            }
+        }
+    }
+
     #[test]
     fn metadata_build_targets1() {
         let metadata_build_targets1 = JsonFixture::metadata_build_targets1();
@@ -340,38 +1098,499 @@ mod small {
         ));
     }
 
-    // No need for proptests because this is a really simple test.
-}
+    #[test]
+    fn metadata_proc_macro1_host_closures() {
+        let metadata = JsonFixture::metadata_proc_macro1();
+        let graph = metadata.graph();
+        let macro_id = package_id(json::METADATA_PROC_MACRO1_MACRO);
+        let macro_set = graph.resolve_ids([&macro_id]).expect("valid package ID");
 
-mod large {
-    use super::*;
-    use fixtures::dep_helpers::GraphAssert;
+        let cargo_set_for = |ids: &[&str], initials_platform| {
+            let ids: Vec<_> = ids.iter().map(|id| package_id(*id)).collect();
+            let mut opts = CargoOptions::new();
+            opts.set_initials_platform(initials_platform);
+            graph
+                .resolve_ids(&ids)
+                .expect("valid package IDs")
+                .to_feature_set(StandardFeatures::Default)
+                .into_cargo_set(&opts)
+                .expect("cargo set resolved")
+        };
 
-    #[test]
-    fn metadata_libra() {
-        let metadata_libra = JsonFixture::metadata_libra();
-        metadata_libra.verify();
-    }
+        // A normal dependency on a proc macro.
+        let cargo_set = cargo_set_for(
+            &[json::METADATA_PROC_MACRO1_NORMAL_USER],
+            InitialsPlatform::Standard,
+        );
+        assert_eq!(
+            cargo_set.proc_macro_closure(),
+            macro_set,
+            "normal-user: macro is in the proc macro closure"
+        );
+        assert!(
+            cargo_set.build_script_closure().is_empty(),
+            "normal-user: build script closure is empty"
+        );
 
-    proptest_suite!(metadata_libra);
+        // build-user doesn't have a build script, so its build dependency isn't built.
+        let cargo_set = cargo_set_for(
+            &[json::METADATA_PROC_MACRO1_BUILD_USER],
+            InitialsPlatform::Standard,
+        );
+        assert!(
+            cargo_set.proc_macro_closure().is_empty(),
+            "build-user: proc macro closure is empty"
+        );
+        assert!(
+            cargo_set.build_script_closure().is_empty(),
+            "build-user: build script closure is empty"
+        );
 
-    #[test]
-    fn metadata_libra_f0091a4() {
-        let metadata = JsonFixture::metadata_libra_f0091a4();
-        metadata.verify();
+        // If the proc macro is also built on the target, it isn't in either closure.
+        let cargo_set = cargo_set_for(
+            &[
+                json::METADATA_PROC_MACRO1_NORMAL_USER,
+                json::METADATA_PROC_MACRO1_MACRO,
+            ],
+            InitialsPlatform::ProcMacrosOnTarget,
+        );
+        assert!(
+            cargo_set.proc_macro_closure().is_empty(),
+            "macro built on target: proc macro closure is empty"
+        );
     }
 
-    proptest_suite!(metadata_libra_f0091a4);
+    #[test]
+    fn metadata_builddep_host_closures() {
+        let metadata = JsonFixture::metadata_builddep();
+        let graph = metadata.graph();
+        let package_named = |name: &str| {
+            graph
+                .packages()
+                .find(|package| package.name() == name)
+                .expect("package is present")
+        };
+        let main = package_named("main");
+        let builddep = package_named("builddep");
+
+        let cargo_set = graph
+            .resolve_ids([main.id()])
+            .expect("valid package ID")
+            .to_feature_set(StandardFeatures::Default)
+            .into_cargo_set(&CargoOptions::new())
+            .expect("cargo set resolved");
+        assert_eq!(
+            cargo_set.build_script_closure(),
+            graph
+                .resolve_ids([builddep.id()])
+                .expect("valid package ID"),
+            "builddep is in the build script closure"
+        );
+        assert!(
+            cargo_set.proc_macro_closure().is_empty(),
+            "proc macro closure is empty"
+        );
+    }
 
+    #[cfg(feature = "summaries")]
     #[test]
-    fn metadata_libra_9ffd93b() {
-        let metadata = JsonFixture::metadata_libra_9ffd93b();
-        metadata.verify();
+    fn metadata_builddep_summary_edges() {
+        use guppy::graph::summaries::{Summary, SummaryEdge, SummaryVersion};
 
+        let metadata = JsonFixture::metadata_builddep();
         let graph = metadata.graph();
-        graph.assert_depends_on(
-            &package_id(json::METADATA_LIBRA_ADMISSION_CONTROL_SERVICE),
-            &package_id(json::METADATA_LIBRA_EXECUTOR_UTILS),
+        let main = graph
+            .packages()
+            .find(|package| package.name() == "main")
+            .expect("main is present");
+        let builddep = graph
+            .packages()
+            .find(|package| package.name() == "builddep")
+            .expect("builddep is present");
+
+        let cargo_opts = CargoOptions::new();
+        let cargo_set = graph
+            .resolve_ids([main.id()])
+            .expect("valid package ID")
+            .to_feature_set(StandardFeatures::Default)
+            .into_cargo_set(&cargo_opts)
+            .expect("cargo set resolved");
+
+        let summary = cargo_set.to_summary(&cargo_opts).expect("summary created");
+        assert_eq!(summary.version, SummaryVersion::V1);
+        assert!(summary.target_edges.is_empty() && summary.host_edges.is_empty());
+
+        let summary = cargo_set
+            .to_summary_with_version(&cargo_opts, SummaryVersion::V2)
+            .expect("summary created");
+        assert!(
+            summary.host_edges.contains(&SummaryEdge::new(
+                main.to_summary_id(),
+                builddep.to_summary_id()
+            )),
+            "main -> builddep is a host edge"
+        );
+        for edge in &summary.target_edges {
+            assert!(summary.target_packages.contains_key(&edge.from));
+            assert!(summary.target_packages.contains_key(&edge.to));
+        }
+        for edge in &summary.host_edges {
+            assert!(summary.host_packages.contains_key(&edge.to));
+        }
+
+        let summary_str = summary.to_string().expect("summary serialized");
+        assert_eq!(
+            Summary::parse(&summary_str).expect("summary parsed"),
+            summary,
+            "summary roundtrips"
+        );
+    }
+
+    // No need for proptests because this is a really simple test.
+
+    #[test]
+    fn metadata_alternate_registries_build_targets() {
+        let metadata = JsonFixture::metadata_alternate_registries();
+        metadata.verify();
+        let graph = metadata.graph();
+
+        let debug_ignore = package_id(json::METADATA_ALTERNATE_REGISTRIES_DEBUG_IGNORE);
+        let serde_json = graph
+            .packages()
+            .find(|package| package.name() == "serde_json")
+            .expect("serde_json is present");
+        let initials = graph
+            .resolve_ids([&debug_ignore])
+            .expect("valid package ID")
+            .to_feature_set(StandardFeatures::All);
+
+        // Building just the library doesn't pull in dev-dependencies.
+        let mut opts = CargoOptions::new();
+        opts.add_initials_build_targets(&debug_ignore, [BuildTargetId::Library]);
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&opts)
+            .expect("library build target is valid");
+        assert!(
+            !cargo_set
+                .target_features()
+                .contains_package(serde_json.id())
+                .expect("valid package ID"),
+            "library build doesn't include dev-dependencies"
+        );
+
+        // Building a test target does.
+        let mut opts = CargoOptions::new();
+        opts.add_initials_build_targets(&debug_ignore, [BuildTargetId::Test("serde")]);
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&opts)
+            .expect("test build target is valid");
+        assert!(
+            cargo_set
+                .target_features()
+                .contains_package(serde_json.id())
+                .expect("valid package ID"),
+            "test build includes dev-dependencies"
+        );
+
+        // The test target requires the "serde" feature.
+        let no_features = graph
+            .resolve_ids([&debug_ignore])
+            .expect("valid package ID")
+            .to_feature_set(StandardFeatures::None);
+        match no_features.into_cargo_set(&opts) {
+            Err(Error::RequiredFeaturesMissing { features, .. }) => {
+                assert_eq!(features, vec!["serde".to_owned()], "missing features match");
+            }
+            other => panic!("expected RequiredFeaturesMissing, found {:?}", other),
+        }
+
+        // Unknown build targets produce an error.
+        let mut opts = CargoOptions::new();
+        opts.add_initials_build_targets(&debug_ignore, [BuildTargetId::Binary("nonexistent")]);
+        assert!(
+            matches!(
+                initials.into_cargo_set(&opts),
+                Err(Error::UnknownBuildTarget(..))
+            ),
+            "unknown build target is an error"
+        );
+    }
+
+    #[test]
+    fn metadata_alternate_registries_link_registry() {
+        let metadata = JsonFixture::metadata_alternate_registries();
+        let graph = metadata.graph();
+        let debug_ignore = graph
+            .metadata(&package_id(
+                json::METADATA_ALTERNATE_REGISTRIES_DEBUG_IGNORE,
+            ))
+            .expect("debug-ignore is present");
+
+        let mut alt_registry_count = 0;
+        for link in debug_ignore.direct_links() {
+            let to_alt_registry = link.to().source().parse_external()
+                == Some(ExternalSource::Registry(
+                    json::METADATA_ALTERNATE_REGISTRY_URL,
+                ));
+            if to_alt_registry {
+                alt_registry_count += 1;
+                assert_eq!(
+                    link.registry(),
+                    Some(json::METADATA_ALTERNATE_REGISTRY_URL),
+                    "registry for {} matches",
+                    link.dep_name(),
+                );
+            } else {
+                assert_eq!(link.registry(), None, "no registry for {}", link.dep_name());
+            }
+        }
+        assert!(
+            alt_registry_count > 0,
+            "at least one dependency from the alternate registry"
+        );
+    }
+
+    #[test]
+    fn metadata_alternate_registries_rust_version() {
+        // None of the fixtures have rust-version set, so add one to serde.
+        let json = JsonFixture::metadata_alternate_registries().json().replace(
+            "\"id\":\"serde 1.0.130 (",
+            "\"rust_version\":\"1.60\",\"id\":\"serde 1.0.130 (",
+        );
+        let graph = PackageGraph::from_json(&json).expect("valid JSON");
+
+        let debug_ignore = package_id(json::METADATA_ALTERNATE_REGISTRIES_DEBUG_IGNORE);
+        let initials = graph
+            .resolve_ids([&debug_ignore])
+            .expect("valid package ID")
+            .to_feature_set(StandardFeatures::All);
+        let contains_serde = |packages: &guppy::graph::PackageSet<'_>| {
+            packages
+                .packages(DependencyDirection::Forward)
+                .any(|package| package.name() == "serde")
+        };
+
+        // Without a Rust version set, rust-version isn't checked.
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&CargoOptions::new())
+            .expect("cargo set built");
+        assert!(contains_serde(
+            &cargo_set.target_features().to_package_set()
+        ));
+        assert!(cargo_set.rust_version_excluded().is_empty());
+
+        // An older Rust version excludes serde.
+        let mut opts = CargoOptions::new();
+        opts.set_rust_version(Some(Version::new(1, 56, 0)));
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&opts)
+            .expect("cargo set built");
+        assert!(
+            !contains_serde(&cargo_set.target_features().to_package_set()),
+            "serde excluded from target set"
+        );
+        assert!(
+            contains_serde(cargo_set.rust_version_excluded()),
+            "serde reported as excluded"
+        );
+
+        // Ignoring rust-version includes it again.
+        opts.set_ignore_rust_version(true);
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&opts)
+            .expect("cargo set built");
+        assert!(contains_serde(
+            &cargo_set.target_features().to_package_set()
+        ));
+        assert!(cargo_set.rust_version_excluded().is_empty());
+
+        // Pre-release versions of a new enough Rust are treated as satisfying rust-version.
+        let mut opts = CargoOptions::new();
+        opts.set_rust_version(Some("1.60.0-nightly".parse().expect("valid version")));
+        let cargo_set = initials.into_cargo_set(&opts).expect("cargo set built");
+        assert!(contains_serde(
+            &cargo_set.target_features().to_package_set()
+        ));
+        assert!(cargo_set.rust_version_excluded().is_empty());
+    }
+
+    #[test]
+    fn metadata_alternate_registries_msrv_violations() {
+        let json = JsonFixture::metadata_alternate_registries().json().replace(
+            "\"id\":\"serde 1.0.130 (",
+            "\"rust_version\":\"1.60\",\"id\":\"serde 1.0.130 (",
+        );
+        let graph = PackageGraph::from_json(&json).expect("valid JSON");
+
+        // serde is in the fixture twice, once from each registry.
+        let serde_ids: Vec<_> = graph
+            .packages()
+            .filter(|package| package.name() == "serde")
+            .map(|package| {
+                assert_eq!(
+                    package.rust_version(),
+                    Some(&VersionReq::parse("1.60").expect("valid requirement"))
+                );
+                assert_eq!(
+                    package.minimum_rust_version(),
+                    Some(&Version::new(1, 60, 0))
+                );
+                package.id()
+            })
+            .collect();
+        assert_eq!(serde_ids.len(), 2, "two copies of serde");
+
+        let violations: Vec<_> = graph
+            .msrv_violations(&Version::new(1, 56, 0))
+            .map(|violation| {
+                assert_eq!(violation.rust_version(), &Version::new(1, 60, 0));
+                violation.package().id()
+            })
+            .collect();
+        assert_eq!(violations, serde_ids);
+
+        // Newer Rust versions, including pre-releases, satisfy rust-version.
+        for msrv in ["1.60.0", "1.60.0-nightly", "1.70.0"] {
+            let msrv: Version = msrv.parse().expect("valid version");
+            assert_eq!(
+                graph.msrv_violations(&msrv).count(),
+                0,
+                "no violations for MSRV {}",
+                msrv
+            );
+        }
+    }
+
+    #[test]
+    fn metadata_alternate_registries_artifact() {
+        // None of the fixtures have artifact dependencies, so turn debug-ignore's dependency on
+        // unicode-xid into one built for the host platform.
+        let debug_ignore = package_id(json::METADATA_ALTERNATE_REGISTRIES_DEBUG_IGNORE);
+        let mut json: serde_json::Value =
+            serde_json::from_str(JsonFixture::metadata_alternate_registries().json())
+                .expect("valid JSON");
+        let package = json["packages"]
+            .as_array_mut()
+            .expect("packages is an array")
+            .iter_mut()
+            .find(|package| package["id"] == debug_ignore.repr())
+            .expect("debug-ignore found");
+        let dep = package["dependencies"]
+            .as_array_mut()
+            .expect("dependencies is an array")
+            .iter_mut()
+            .find(|dep| dep["name"] == "unicode-xid")
+            .expect("unicode-xid found");
+        dep["artifact"] = serde_json::json!({
+            // Unknown kinds, e.g. ones added by newer versions of Cargo, are preserved.
+            "kinds": ["bin", "bin:foo", "dylib"],
+            "lib": true,
+            "target": "x86_64-unknown-linux-gnu",
+        });
+        let json = json.to_string();
+
+        let expected = ArtifactReq::new(
+            vec![
+                ArtifactKind::AllBinaries,
+                ArtifactKind::SelectedBinary("foo".to_owned()),
+                ArtifactKind::Other("dylib".to_owned()),
+            ],
+            true,
+            Some(ArtifactTarget::Triple(
+                "x86_64-unknown-linux-gnu".to_owned(),
+            )),
+        );
+        let check_graph = |graph: &PackageGraph| {
+            let metadata = graph.metadata(&debug_ignore).expect("valid package ID");
+            for link in metadata.direct_links() {
+                let artifact = link.normal().artifact();
+                if link.to().name() == "unicode-xid" {
+                    assert_eq!(artifact, Some(&expected), "artifact request matches");
+                } else {
+                    assert_eq!(artifact, None, "not an artifact dependency");
+                }
+            }
+        };
+
+        let metadata = CargoMetadata::parse_json(&json).expect("valid JSON");
+        let mut serialized = vec![];
+        metadata
+            .serialize(&mut serialized)
+            .expect("metadata serialized");
+        let serialized = String::from_utf8(serialized).expect("serialized JSON is valid UTF-8");
+        check_graph(&metadata.build_graph().expect("valid graph"));
+        check_graph(&PackageGraph::from_json(&serialized).expect("serialized JSON is valid"));
+
+        // unicode-xid is built for the host if it's Linux, and for the target otherwise.
+        let graph = PackageGraph::from_json(&json).expect("valid JSON");
+        let contains_unicode_xid = |packages: &PackageSet<'_>| {
+            packages
+                .packages(DependencyDirection::Forward)
+                .any(|package| package.name() == "unicode-xid")
+        };
+        let cargo_set = |host: &'static str, target: &'static str| {
+            let platform = |triple| Platform::new(triple, TargetFeatures::Unknown).expect("known");
+            let mut opts = CargoOptions::new();
+            opts.set_host_platform(platform(host))
+                .set_target_platform(platform(target));
+            graph
+                .resolve_ids([&debug_ignore])
+                .expect("valid package ID")
+                .to_feature_set(StandardFeatures::All)
+                .into_cargo_set(&opts)
+                .expect("cargo set built")
+        };
+
+        let linux_host = cargo_set("x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc");
+        assert!(contains_unicode_xid(
+            &linux_host.host_features().to_package_set()
+        ));
+        assert!(!contains_unicode_xid(
+            &linux_host.target_features().to_package_set()
+        ));
+
+        // unicode-xid is also a host dependency through serde_derive, so only check the target.
+        let windows_host = cargo_set("x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu");
+        assert!(contains_unicode_xid(
+            &windows_host.target_features().to_package_set()
+        ));
+    }
+}
+
+mod large {
+    use super::*;
+    use fixtures::dep_helpers::GraphAssert;
+
+    #[test]
+    fn metadata_libra() {
+        let metadata_libra = JsonFixture::metadata_libra();
+        metadata_libra.verify();
+    }
+
+    proptest_suite!(metadata_libra);
+
+    #[test]
+    fn metadata_libra_f0091a4() {
+        let metadata = JsonFixture::metadata_libra_f0091a4();
+        metadata.verify();
+    }
+
+    proptest_suite!(metadata_libra_f0091a4);
+
+    #[test]
+    fn metadata_libra_9ffd93b() {
+        let metadata = JsonFixture::metadata_libra_9ffd93b();
+        metadata.verify();
+
+        let graph = metadata.graph();
+        graph.assert_depends_on(
+            &package_id(json::METADATA_LIBRA_ADMISSION_CONTROL_SERVICE),
+            &package_id(json::METADATA_LIBRA_EXECUTOR_UTILS),
             DependencyDirection::Forward,
             "admission-control-service should depend on executor-utils",
         );
@@ -444,6 +1663,614 @@ mod large {
     }
 
     proptest_suite!(metadata_libra_9ffd93b);
+
+    #[cfg(feature = "name-patterns")]
+    #[test]
+    fn named_patterns_libra() {
+        use guppy::graph::NamePattern;
+
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+
+        let resolve_named = |specs: &[&str]| -> Result<Vec<&str>, Error> {
+            let patterns = specs
+                .iter()
+                .map(|spec| NamePattern::new(*spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut names: Vec<_> = graph
+                .resolve_named(&patterns)?
+                .packages(DependencyDirection::Forward)
+                .map(|package| package.name())
+                .collect();
+            names.sort_unstable();
+            Ok(names)
+        };
+
+        assert_eq!(
+            resolve_named(&["storage-*"]).expect("glob is valid"),
+            ["storage-client", "storage-proto", "storage-service"],
+            "glob pattern"
+        );
+        assert_eq!(
+            resolve_named(&["/libra-crypto(-derive)?/", "storage-proto"]).expect("regex is valid"),
+            ["libra-crypto", "libra-crypto-derive", "storage-proto"],
+            "regex and exact patterns"
+        );
+        assert_eq!(
+            resolve_named(&["/crypto/"])
+                .expect_err("regex is anchored")
+                .to_string(),
+            "name pattern did not match any workspace packages: /crypto/",
+            "regexes must match the entire name"
+        );
+        assert!(
+            matches!(
+                resolve_named(&["/libra-(/"]),
+                Err(Error::InvalidNamePattern(..))
+            ),
+            "invalid regex"
+        );
+
+        let patterns = [NamePattern::glob("storage-*").expect("glob is valid")];
+        let query_set = graph
+            .query_named(&patterns)
+            .expect("pattern matches")
+            .resolve();
+        let named_set = graph.resolve_named(&patterns).expect("pattern matches");
+        assert!(
+            query_set.len() > named_set.len() && query_set.intersection(&named_set) == named_set,
+            "query_named includes transitive dependencies"
+        );
+    }
+
+    #[test]
+    fn query_mixed_libra() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let workspace = graph.workspace();
+        let types = workspace
+            .member_by_name("libra-types")
+            .expect("libra-types is a workspace member");
+        let storage_client = workspace
+            .member_by_name("storage-client")
+            .expect("storage-client is a workspace member");
+
+        // Everything affected by a change to libra-types, plus everything it needs.
+        let query = graph
+            .query_mixed([
+                (types.id(), DependencyDirection::Forward),
+                (types.id(), DependencyDirection::Reverse),
+            ])
+            .expect("valid package IDs");
+        assert!(query
+            .starts_from(types.id(), DependencyDirection::Reverse)
+            .expect("valid package ID"));
+        assert_eq!(query.forward().direction(), DependencyDirection::Forward);
+        assert_eq!(query.reverse().direction(), DependencyDirection::Reverse);
+        let forward = graph
+            .query_forward([types.id()])
+            .expect("valid package ID")
+            .resolve();
+        let reverse = graph
+            .query_reverse([types.id()])
+            .expect("valid package ID")
+            .resolve();
+        let mixed = query.resolve();
+        assert_eq!(mixed, forward.union(&reverse), "mixed query is the union");
+        assert!(
+            mixed.len() > forward.len() && mixed.len() > reverse.len(),
+            "both directions contribute packages"
+        );
+
+        // Different packages in each direction, with dev-only links skipped in reverse only.
+        let query = graph
+            .query_mixed([
+                (storage_client.id(), DependencyDirection::Forward),
+                (types.id(), DependencyDirection::Reverse),
+            ])
+            .expect("valid package IDs");
+        assert!(!query
+            .starts_from(types.id(), DependencyDirection::Forward)
+            .expect("valid package ID"));
+        let mixed = query.resolve_with_fn(|query, link| {
+            query.direction() == DependencyDirection::Forward || !link.dev_only()
+        });
+        let forward = graph
+            .query_forward([storage_client.id()])
+            .expect("valid package ID")
+            .resolve();
+        let reverse = graph
+            .query_reverse([types.id()])
+            .expect("valid package ID")
+            .resolve_with_fn(|_, link| !link.dev_only());
+        assert_eq!(
+            mixed,
+            forward.union(&reverse),
+            "resolver applies per direction"
+        );
+
+        assert!(matches!(
+            graph.query_mixed([(
+                &package_id(json::METADATA1_TESTCRATE),
+                DependencyDirection::Forward
+            )]),
+            Err(Error::UnknownPackageId(_))
+        ));
+    }
+
+    #[cfg(feature = "name-patterns")]
+    #[test]
+    fn layers_libra() {
+        use guppy::graph::{LayerConfig, LayersConfig};
+
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+
+        let layer = |members: &[&str], allow: &[&str]| LayerConfig {
+            members: members.iter().map(|member| member.to_string()).collect(),
+            allow: allow.iter().map(|name| name.to_string()).collect(),
+        };
+        let mut config = LayersConfig::default();
+        config.layers.insert(
+            "crypto".to_owned(),
+            layer(&["/libra-crypto(-derive)?/"], &[]),
+        );
+        config
+            .layers
+            .insert("proto".to_owned(), layer(&["libra-prost-ext"], &[]));
+        config.layers.insert(
+            "types".to_owned(),
+            layer(&["libra-types"], &["crypto", "proto"]),
+        );
+        config
+            .layers
+            .insert("storage".to_owned(), layer(&["storage-*"], &["types"]));
+
+        let violations = |config: &LayersConfig| -> Vec<(String, String)> {
+            let layers = graph.layers(config).expect("valid layers config");
+            layers
+                .violations()
+                .iter()
+                .map(|violation| {
+                    let (from, to) = violation.link().endpoints();
+                    assert_eq!(layers.layer_of(from.id()), Some(violation.from_layer()));
+                    assert_eq!(layers.layer_of(to.id()), Some(violation.to_layer()));
+                    (from.name().to_owned(), to.name().to_owned())
+                })
+                .collect()
+        };
+
+        let layers = graph.layers(&config).expect("valid layers config");
+        assert_eq!(
+            layers.layer_names().collect::<Vec<_>>(),
+            ["crypto", "proto", "storage", "types"],
+            "layer names are sorted"
+        );
+        assert_eq!(
+            layers
+                .members("storage")
+                .expect("storage layer exists")
+                .len(),
+            3,
+            "storage-* matches three packages"
+        );
+        let unassigned = layers.unassigned();
+        assert_eq!(
+            unassigned.len() + 7,
+            graph.workspace().member_count(),
+            "seven workspace members are in layers"
+        );
+        let proptest_helpers = graph
+            .workspace()
+            .member_by_name("libra-proptest-helpers")
+            .expect("libra-proptest-helpers is a workspace member");
+        assert!(unassigned
+            .contains(proptest_helpers.id())
+            .expect("valid package ID"));
+        assert_eq!(layers.layer_of(proptest_helpers.id()), None);
+
+        // Dependencies on unassigned packages like libra-proptest-helpers aren't checked.
+        let expected = [
+            ("storage-client", "libra-crypto"),
+            ("storage-proto", "libra-crypto"),
+            ("storage-service", "libra-crypto"),
+        ];
+        assert_eq!(
+            violations(&config),
+            expected.map(|(from, to)| (from.to_owned(), to.to_owned())),
+            "storage doesn't allow crypto"
+        );
+
+        config.include_dev = true;
+        let with_dev = violations(&config);
+        assert_eq!(
+            with_dev.len(),
+            4,
+            "one more violation with dev-dependencies"
+        );
+        assert!(with_dev.contains(&("storage-proto".to_owned(), "libra-prost-ext".to_owned())));
+
+        config
+            .layers
+            .get_mut("storage")
+            .expect("storage layer exists")
+            .allow
+            .extend(["crypto".to_owned(), "proto".to_owned()]);
+        assert_eq!(violations(&config), [], "all dependencies are allowed");
+
+        let mut overlapping = config.clone();
+        overlapping
+            .layers
+            .insert("all-storage".to_owned(), layer(&["storage-proto"], &[]));
+        assert_eq!(
+            graph
+                .layers(&overlapping)
+                .expect_err("storage-proto is in two layers")
+                .to_string(),
+            "invalid layers configuration: workspace member storage-proto is in both layers \
+             all-storage and storage",
+        );
+
+        let mut unknown = config.clone();
+        unknown
+            .layers
+            .insert("api".to_owned(), layer(&["libra-node"], &["missing"]));
+        assert!(matches!(
+            graph.layers(&unknown),
+            Err(Error::InvalidLayersConfig(_))
+        ));
+
+        let mut unmatched = config;
+        unmatched
+            .layers
+            .insert("api".to_owned(), layer(&["no-such-package"], &[]));
+        assert!(matches!(
+            graph.layers(&unmatched),
+            Err(Error::UnmatchedNamePattern(_))
+        ));
+    }
+
+    #[test]
+    fn pkgid_specs_libra() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+
+        let resolve_specs = |specs: &[&str]| -> Result<Vec<String>, Error> {
+            let specs = specs
+                .iter()
+                .map(|spec| PackageIdSpec::parse(*spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut packages: Vec<_> = graph
+                .resolve_pkgid_specs(&specs)?
+                .packages(DependencyDirection::Forward)
+                .map(|package| format!("{} {}", package.name(), package.version()))
+                .collect();
+            packages.sort_unstable();
+            Ok(packages)
+        };
+
+        assert_eq!(
+            resolve_specs(&["rand"]).expect("spec is valid"),
+            ["rand 0.4.6", "rand 0.5.6", "rand 0.6.5", "rand 0.7.3"],
+            "name matches every version"
+        );
+        assert_eq!(
+            resolve_specs(&["rand@0.7", "structopt:0.2.18"]).expect("specs are valid"),
+            ["rand 0.7.3", "structopt 0.2.18"],
+            "partial and full versions"
+        );
+        assert_eq!(
+            resolve_specs(&[
+                "https://github.com/rust-lang/crates.io-index#rand@0.5",
+                "registry+https://github.com/rust-lang/crates.io-index#h2@0.1.26",
+            ])
+            .expect("specs are valid"),
+            ["h2 0.1.26", "rand 0.5.6"],
+            "registry URLs"
+        );
+        assert_eq!(
+            resolve_specs(&[
+                "https://github.com/tikv/rust-rocksdb#librocksdb_sys",
+                "git+https://github.com/tikv/rust-rocksdb.git?rev=72e45c3f3283302c825d53c3cd7154f4cd9e8f5b#libtitan_sys@0.0.1",
+            ])
+            .expect("specs are valid"),
+            ["librocksdb_sys 0.1.0", "libtitan_sys 0.0.1"],
+            "git URLs"
+        );
+        assert_eq!(
+            resolve_specs(&["file:///Users/fakeuser/local/libra/execution/executor"])
+                .expect("spec is valid"),
+            ["executor 0.1.0"],
+            "path URL with the name inferred from the path"
+        );
+
+        assert_eq!(
+            resolve_specs(&["https://github.com/rust-lang/crates.io-index#executor"])
+                .expect_err("executor is a workspace package")
+                .to_string(),
+            "package ID specification did not match any packages: \
+             https://github.com/rust-lang/crates.io-index#executor",
+        );
+        assert!(
+            matches!(
+                resolve_specs(&["git+https://github.com/rust-lang/crates.io-index#rand"]),
+                Err(Error::UnmatchedPackageIdSpec(_))
+            ),
+            "kind of source must match"
+        );
+        for invalid in [
+            "libra/executor",
+            "rand@x",
+            "rand@1.2.3.4",
+            "@1.0",
+            "https://#foo",
+            "file:///",
+        ] {
+            assert!(
+                matches!(
+                    PackageIdSpec::parse(invalid),
+                    Err(Error::InvalidPackageIdSpec(..))
+                ),
+                "{} is an invalid spec",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn publish_violations_libra() {
+        let fixture = JsonFixture::metadata_libra_9ffd93b();
+        fn violations(graph: &PackageGraph) -> BTreeMap<(&str, &str), Option<Vec<&str>>> {
+            graph
+                .publish_violations()
+                .map(|violation| {
+                    let link = violation.link();
+                    assert!(!link.dev_only(), "dev-only links are skipped");
+                    assert!(
+                        !matches!(
+                            violation
+                                .from()
+                                .publish()
+                                .partial_cmp(&violation.to().publish()),
+                            Some(Ordering::Less | Ordering::Equal)
+                        ),
+                        "{} can be published to registries that {} can't",
+                        violation.from().name(),
+                        violation.to().name(),
+                    );
+                    (
+                        (violation.from().name(), violation.to().name()),
+                        violation.missing_registries(),
+                    )
+                })
+                .collect()
+        }
+
+        // In libra, every violation is a dependency on a package with `publish = false`.
+        let graph = fixture.graph();
+        let base_violations = violations(graph);
+        assert_eq!(base_violations.len(), 26, "number of violations");
+        assert_eq!(
+            base_violations.get(&("functional-tests", "libra-crypto")),
+            Some(&None),
+            "functional-tests can be published anywhere"
+        );
+
+        // Restrict both functional-tests and libra-crypto to a private registry.
+        let mut json: serde_json::Value = serde_json::from_str(fixture.json()).expect("valid JSON");
+        for package in json["packages"]
+            .as_array_mut()
+            .expect("packages is an array")
+            .iter_mut()
+            .filter(|package| {
+                package["name"] == "functional-tests" || package["name"] == "libra-crypto"
+            })
+        {
+            package["publish"] = serde_json::json!(["my-registry"]);
+        }
+        let graph = PackageGraph::from_json(json.to_string()).expect("valid graph");
+        let violations = violations(&graph);
+
+        assert!(
+            !violations.contains_key(&("functional-tests", "libra-crypto")),
+            "libra-crypto can be published to the same registries as functional-tests"
+        );
+        assert_eq!(
+            violations.get(&("functional-tests", "libra-types")),
+            Some(&Some(vec!["my-registry"])),
+            "libra-types can't be published to my-registry"
+        );
+        for ((from, to), missing) in &violations {
+            if *to == "libra-crypto" {
+                assert_eq!(missing, &None, "{} can be published anywhere", from);
+            }
+        }
+        for (from, to) in base_violations.keys() {
+            assert!(
+                (*from, *to) == ("functional-tests", "libra-crypto")
+                    || violations.contains_key(&(*from, *to)),
+                "{} -> {} is still a violation",
+                from,
+                to,
+            );
+        }
+    }
+
+    #[test]
+    fn dev_only_packages_libra() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let dev_only_packages = graph.dev_only_packages();
+
+        let names = |set: &PackageSet<'static>| -> Vec<&'static str> {
+            let mut names: Vec<_> = set
+                .packages(DependencyDirection::Forward)
+                .map(|package| package.name())
+                .collect();
+            names.sort_unstable();
+            names
+        };
+
+        assert_eq!(
+            names(dev_only_packages.test_only_workspace_set()),
+            vec!["ir-testsuite"],
+            "ir-testsuite only has test targets"
+        );
+        assert_eq!(
+            names(dev_only_packages.dev_only_set()),
+            vec![
+                "assert_approx_eq",
+                "assert_matches",
+                "bitvec",
+                "cached",
+                "goldenfile",
+                "ir-testsuite",
+                "radium",
+                "ripemd160",
+                "statistical",
+                "stats_alloc",
+            ],
+            "dev-only packages match"
+        );
+
+        let all = graph.query_workspace().resolve();
+        let production = dev_only_packages.production_set();
+        assert!(
+            production
+                .intersection(dev_only_packages.dev_only_set())
+                .is_empty(),
+            "production and dev-only sets are disjoint"
+        );
+        assert_eq!(
+            production.union(dev_only_packages.dev_only_set()),
+            all,
+            "production and dev-only sets cover the workspace closure"
+        );
+        assert!(
+            !dev_only_packages
+                .is_dev_only(&package_id(json::METADATA_LIBRA_EXECUTOR_UTILS))
+                .expect("valid package ID"),
+            "executor-utils is depended on by admission-control-service"
+        );
+    }
+
+    #[test]
+    fn unused_features_libra() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let workspace_set = |features| {
+            graph
+                .resolve_workspace()
+                .to_feature_set(features)
+                .into_cargo_set(&CargoOptions::new())
+                .expect("valid cargo set")
+        };
+
+        let default_set = workspace_set(StandardFeatures::Default);
+        let unused_features = UnusedFeatures::new(graph, [&default_set]);
+        let unused = unused_features.unused_set();
+        assert!(
+            !unused.is_empty(),
+            "some features are unused in default builds"
+        );
+        for feature in unused.features(DependencyDirection::Forward) {
+            assert!(
+                feature.package().in_workspace(),
+                "unused feature {} is in the workspace",
+                feature.feature_id(),
+            );
+            assert!(
+                feature.feature_id().is_named(),
+                "unused feature {} is named",
+                feature.feature_id(),
+            );
+            assert!(
+                !default_set
+                    .target_features()
+                    .contains(feature.feature_id())
+                    .expect("valid feature ID")
+                    && !default_set
+                        .host_features()
+                        .contains(feature.feature_id())
+                        .expect("valid feature ID"),
+                "unused feature {} isn't built",
+                feature.feature_id(),
+            );
+        }
+        assert!(
+            unused.intersection(unused_features.used_set()).is_empty(),
+            "used and unused sets are disjoint"
+        );
+
+        // Building with all features enables every workspace feature.
+        let all_set = workspace_set(StandardFeatures::All);
+        let unused_features = UnusedFeatures::new(graph, [&default_set, &all_set]);
+        assert!(
+            unused_features.unused_set().is_empty(),
+            "no features are unused with --all-features"
+        );
+    }
+
+    #[cfg(feature = "summaries")]
+    #[test]
+    fn summary_set_across_graphs() {
+        let old_graph = JsonFixture::metadata_libra_f0091a4().graph();
+        let new_graph = JsonFixture::metadata_libra_9ffd93b().graph();
+
+        let old_set = old_graph.query_workspace().resolve().to_summary_set();
+        let new_set = new_graph.query_workspace().resolve().to_summary_set();
+        assert_eq!(old_set.len(), old_graph.query_workspace().resolve().len());
+
+        // Packages present in both graphs can be resolved against either graph.
+        let common = old_set.intersection(&new_set);
+        assert!(!common.is_empty(), "some packages are common");
+        let common_old = common
+            .to_package_set(old_graph)
+            .expect("common packages known");
+        let common_new = common
+            .to_package_set(new_graph)
+            .expect("common packages known");
+        assert_eq!(common_old.len(), common.len());
+        assert_eq!(common_new.len(), common.len());
+        assert_eq!(
+            common_old.to_summary_set(),
+            common_new.to_summary_set(),
+            "common packages round-trip through both graphs"
+        );
+
+        // Packages only in the new graph can't be resolved against the old one.
+        let added = new_set.difference(&old_set);
+        assert!(!added.is_empty(), "some packages were added");
+        let (known, unknown) = added.partition_known(old_graph);
+        assert!(
+            known.is_empty(),
+            "added packages are unknown to the old graph"
+        );
+        assert_eq!(unknown, added);
+        added
+            .to_package_set(old_graph)
+            .expect_err("added packages are unknown to the old graph");
+        added
+            .to_package_set(new_graph)
+            .expect("added packages are known to the new graph");
+
+        let changed = old_set.symmetric_difference(&new_set);
+        assert_eq!(changed, added.union(&old_set.difference(&new_set)));
+        assert_eq!(old_set.union(&new_set).len(), common.len() + changed.len());
+
+        // Feature sets round-trip as well.
+        let old_features = old_graph
+            .query_workspace()
+            .resolve()
+            .to_feature_set(StandardFeatures::Default);
+        let old_feature_summary = old_features.to_summary_set();
+        assert_eq!(
+            old_feature_summary
+                .to_feature_set(old_graph)
+                .expect("all features known")
+                .len(),
+            old_features.len(),
+        );
+        let (known, _) = old_feature_summary.partition_known(new_graph);
+        let new_features = known
+            .to_feature_set(new_graph)
+            .expect("known features can be resolved");
+        assert_eq!(new_features.to_summary_set(), known);
+    }
 }
 
 mod guppy_tests {
@@ -469,6 +2296,160 @@ mod guppy_tests {
         assert_eq!(graph.link_count(), 0, "no-deps => no edges");
     }
 
+    #[test]
+    fn metadata_section() {
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        #[serde(rename_all = "kebab-case")]
+        struct DocsRs {
+            all_features: bool,
+        }
+
+        let graph = JsonFixture::metadata_guppy_44b62fa().graph();
+        let workspace = graph.workspace();
+
+        let sections: Vec<_> = workspace
+            .metadata_section::<BTreeMap<String, DocsRs>>("docs")
+            .map(|(package, section)| (package.name(), section.expect("valid section")))
+            .collect();
+        let names: Vec<_> = sections.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            vec!["guppy", "target-spec", "hakari"],
+            "sorted by path"
+        );
+        for (name, section) in &sections {
+            assert_eq!(
+                section.get("rs"),
+                Some(&DocsRs { all_features: true }),
+                "docs.rs section for {} matches",
+                name
+            );
+        }
+
+        assert_eq!(
+            workspace.metadata_section::<bool>("nonexistent").count(),
+            0,
+            "no packages have a nonexistent section"
+        );
+
+        let package = workspace
+            .member_by_name("guppy")
+            .expect("guppy is a workspace member");
+        match package.metadata_section::<Vec<String>>("docs") {
+            Err(Error::MetadataSectionError {
+                package_id, key, ..
+            }) => {
+                assert_eq!(&package_id, package.id());
+                assert_eq!(key, "docs");
+            }
+            other => panic!("expected metadata section error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn metadata_guppy_78cb7e8_provenance() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let initials = graph
+            .resolve_workspace_names(["guppy"])
+            .expect("guppy is a workspace member")
+            .to_feature_set(named_feature_filter(
+                StandardFeatures::Default,
+                ["summaries"],
+            ));
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&CargoOptions::new())
+            .expect("cargo set built");
+
+        let feature_lists: BTreeMap<_, _> = cargo_set
+            .target_features()
+            .packages_with_provenance(&initials, DependencyDirection::Forward)
+            .map(|feature_list| (feature_list.package().name(), feature_list))
+            .collect();
+        let provenance = |name: &str, label: FeatureLabel<'_>| {
+            feature_lists[name]
+                .provenance(label)
+                .unwrap_or_else(|| panic!("{} has provenance for {}", name, label))
+        };
+
+        assert_eq!(
+            provenance("guppy", FeatureLabel::Base),
+            FeatureProvenance::Default
+        );
+        assert_eq!(
+            provenance("guppy", FeatureLabel::Named("summaries")),
+            FeatureProvenance::Explicit
+        );
+        // Enabled by the "summaries" feature of the same package.
+        assert_eq!(
+            provenance("guppy", FeatureLabel::OptionalDependency("guppy-summaries")),
+            FeatureProvenance::Explicit
+        );
+        assert_eq!(
+            provenance("target-spec", FeatureLabel::Named("summaries")),
+            FeatureProvenance::Dependency
+        );
+        assert_eq!(
+            provenance("serde", FeatureLabel::Named("std")),
+            FeatureProvenance::Default
+        );
+        assert_eq!(
+            provenance("serde", FeatureLabel::Named("derive")),
+            FeatureProvenance::Dependency
+        );
+        assert_eq!(
+            feature_lists["serde"].provenance(FeatureLabel::Named("alloc")),
+            None,
+            "alloc isn't enabled"
+        );
+
+        // Lists without provenance information match the ones with it.
+        for feature_list in cargo_set
+            .target_features()
+            .packages_with_features(DependencyDirection::Forward)
+        {
+            let with_provenance = &feature_lists[feature_list.package().name()];
+            assert_eq!(feature_list.labels(), with_provenance.labels());
+            assert!(feature_list.labels_with_provenance().is_none());
+            assert_eq!(
+                with_provenance
+                    .labels_with_provenance()
+                    .expect("provenance is available")
+                    .count(),
+                feature_list.labels().len(),
+            );
+        }
+    }
+
+    #[test]
+    fn metadata_guppy_78cb7e8_provenance_no_default() {
+        let graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let initials = graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default);
+        let cargo_set = initials
+            .clone()
+            .into_cargo_set(&CargoOptions::new())
+            .expect("cargo set built");
+
+        let byteorder = cargo_set
+            .target_features()
+            .packages_with_provenance(&initials, DependencyDirection::Forward)
+            .find(|feature_list| feature_list.package().name() == "byteorder")
+            .expect("byteorder is in the cargo set");
+        // byteorder is depended on with default-features = false, and "std" (one of its default
+        // features) is enabled by a dependent package.
+        assert!(
+            !byteorder.contains(FeatureLabel::Named("default")),
+            "default features are off for byteorder"
+        );
+        assert_eq!(
+            byteorder.provenance(FeatureLabel::Named("std")),
+            Some(FeatureProvenance::Dependency),
+            "std is enabled by a dependency, not by default"
+        );
+    }
+
     proptest_suite!(metadata_guppy_44b62fa);
 }
 
@@ -483,3 +2464,52 @@ impl PackageDotVisitor for NameVisitor {
         write!(f, "{}", link.dep_name())
     }
 }
+
+/// Returns the nodes and edges of a `dot` graph, with node indexes replaced by labels.
+fn dot_by_labels(dot: &str) -> Vec<String> {
+    let mut labels = BTreeMap::new();
+    let mut nodes = vec![];
+    let mut edges = vec![];
+    for line in dot.lines().map(|line| line.trim()) {
+        let (head, label) = match line.split_once(" [label=\"") {
+            Some((head, rest)) => (head, rest.trim_end_matches("\"]")),
+            None => continue,
+        };
+        match head.split_once(" -> ") {
+            Some((from, to)) => edges.push((from.to_owned(), to.to_owned(), label.to_owned())),
+            None => {
+                labels.insert(head.to_owned(), label.to_owned());
+                nodes.push(label.to_owned());
+            }
+        }
+    }
+    nodes.sort();
+    let mut edges: Vec<_> = edges
+        .into_iter()
+        .map(|(from, to, label)| format!("{} -> {} [{}]", labels[&from], labels[&to], label))
+        .collect();
+    edges.sort();
+    nodes.extend(edges);
+    nodes
+}
+
+struct FeatureNameVisitor;
+
+impl FeatureDotVisitor for FeatureNameVisitor {
+    fn visit_feature(&self, feature: FeatureMetadata<'_>, f: &mut DotWrite<'_, '_>) -> fmt::Result {
+        write!(f, "{}/{}", feature.package().name(), feature.label())
+    }
+
+    fn visit_link(
+        &self,
+        _from: FeatureMetadata<'_>,
+        _to: FeatureMetadata<'_>,
+        conditional_link: Option<ConditionalLink<'_>>,
+        f: &mut DotWrite<'_, '_>,
+    ) -> fmt::Result {
+        match conditional_link {
+            Some(link) => write!(f, "{}", link.package_link().dep_name()),
+            None => Ok(()),
+        }
+    }
+}
@@ -4,8 +4,9 @@
 use fixtures::dep_helpers::{assert_link_order, GraphAssert, GraphMetadata, GraphQuery, GraphSet};
 use guppy::{
     graph::{
+        cargo::{CargoOptions, CargoResolverCache, CargoSet},
         feature::{FeatureId, FeatureLabel, FeatureSet, StandardFeatures},
-        DependencyDirection, PackageGraph, Prop010Resolver,
+        DependencyDirection, PackageGraph, PackageLink, Prop010Resolver,
     },
     PackageId,
 };
@@ -261,6 +262,23 @@ macro_rules! proptest_suite {
                     query_starts_from(feature_graph, query_ids, direction, test_ids);
                 });
             }
+
+            #[test]
+            fn proptest_cargo_set_resolver_cache() {
+                let fixture = JsonFixture::$name();
+                let package_graph = fixture.graph();
+                let feature_graph = package_graph.feature_graph();
+                // Share the cache across cases so that it's reused across different options.
+                let cache = package_graph.new_cargo_resolver_cache();
+
+                proptest!(|(
+                    initials in feature_graph.prop010_set_strategy(),
+                    features_only in feature_graph.prop010_set_strategy(),
+                    cargo_opts in package_graph.prop010_cargo_options_strategy(),
+                )| {
+                    cargo_set_resolver_cache(&cache, initials, features_only, cargo_opts);
+                });
+            }
         }
     }
 }
@@ -478,6 +496,7 @@ fn resolve_ops_impl<G: GraphAssert<'static>>(
         ResolveTree::Union(a, b) => {
             let (resolve_a, hashset_a) = resolve_ops_impl(graph, a);
             let (resolve_b, hashset_b) = resolve_ops_impl(graph, b);
+            assert_set_relations(&resolve_a, &hashset_a, &resolve_b, &hashset_b);
             (
                 resolve_a.union(&resolve_b),
                 hashset_a.union(&hashset_b).copied().collect(),
@@ -486,6 +505,7 @@ fn resolve_ops_impl<G: GraphAssert<'static>>(
         ResolveTree::Intersection(a, b) => {
             let (resolve_a, hashset_a) = resolve_ops_impl(graph, a);
             let (resolve_b, hashset_b) = resolve_ops_impl(graph, b);
+            assert_set_relations(&resolve_a, &hashset_a, &resolve_b, &hashset_b);
             (
                 resolve_a.intersection(&resolve_b),
                 hashset_a.intersection(&hashset_b).copied().collect(),
@@ -494,6 +514,7 @@ fn resolve_ops_impl<G: GraphAssert<'static>>(
         ResolveTree::Difference(a, b) => {
             let (resolve_a, hashset_a) = resolve_ops_impl(graph, a);
             let (resolve_b, hashset_b) = resolve_ops_impl(graph, b);
+            assert_set_relations(&resolve_a, &hashset_a, &resolve_b, &hashset_b);
             (
                 resolve_a.difference(&resolve_b),
                 hashset_a.difference(&hashset_b).copied().collect(),
@@ -502,6 +523,7 @@ fn resolve_ops_impl<G: GraphAssert<'static>>(
         ResolveTree::SymmetricDifference(a, b) => {
             let (resolve_a, hashset_a) = resolve_ops_impl(graph, a);
             let (resolve_b, hashset_b) = resolve_ops_impl(graph, b);
+            assert_set_relations(&resolve_a, &hashset_a, &resolve_b, &hashset_b);
             (
                 resolve_a.symmetric_difference(&resolve_b),
                 hashset_a
@@ -513,6 +535,29 @@ fn resolve_ops_impl<G: GraphAssert<'static>>(
     }
 }
 
+fn assert_set_relations<'g, S: GraphSet<'g>>(
+    resolve_a: &S,
+    hashset_a: &HashSet<S::Id>,
+    resolve_b: &S,
+    hashset_b: &HashSet<S::Id>,
+) {
+    assert_eq!(
+        resolve_a.is_subset(resolve_b),
+        hashset_a.is_subset(hashset_b),
+        "is_subset on resolve and hashset match"
+    );
+    assert_eq!(
+        resolve_b.is_subset(resolve_a),
+        hashset_b.is_subset(hashset_a),
+        "reverse is_subset on resolve and hashset match"
+    );
+    assert_eq!(
+        resolve_a.is_disjoint(resolve_b),
+        hashset_a.is_disjoint(hashset_b),
+        "is_disjoint on resolve and hashset match"
+    );
+}
+
 pub(super) fn package_feature_set_roundtrip(
     package_graph: &PackageGraph,
     query_ids: Vec<&PackageId>,
@@ -612,3 +657,54 @@ pub(super) fn query_starts_from<'g, G: GraphAssert<'g>>(
 }
 
 // TODO: More tests for FeatureFilter implementations.
+
+/// Test that build simulations produce the same results with and without a resolver cache.
+pub(super) fn cargo_set_resolver_cache<'g>(
+    cache: &'g CargoResolverCache<'g>,
+    initials: FeatureSet<'g>,
+    features_only: FeatureSet<'g>,
+    mut cargo_opts: CargoOptions<'g>,
+) {
+    let uncached = CargoSet::new(initials.clone(), features_only.clone(), &cargo_opts)
+        .expect("cargo set built without cache");
+    cargo_opts.set_resolver_cache(cache);
+    let cached =
+        CargoSet::new(initials, features_only, &cargo_opts).expect("cargo set built with cache");
+
+    assert_eq!(
+        uncached.target_features(),
+        cached.target_features(),
+        "target features match"
+    );
+    assert_eq!(
+        uncached.host_features(),
+        cached.host_features(),
+        "host features match"
+    );
+    assert_eq!(
+        uncached.target_direct_deps(),
+        cached.target_direct_deps(),
+        "target direct deps match"
+    );
+    assert_eq!(
+        uncached.host_direct_deps(),
+        cached.host_direct_deps(),
+        "host direct deps match"
+    );
+
+    let link_ids = |links: &mut dyn Iterator<Item = PackageLink<'g>>| -> Vec<_> {
+        links
+            .map(|link| (link.from().id(), link.to().id()))
+            .collect()
+    };
+    assert_eq!(
+        link_ids(&mut uncached.target_links()),
+        link_ids(&mut cached.target_links()),
+        "target links match"
+    );
+    assert_eq!(
+        link_ids(&mut uncached.host_links()),
+        link_ids(&mut cached.host_links()),
+        "host links match"
+    );
+}
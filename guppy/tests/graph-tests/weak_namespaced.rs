@@ -8,7 +8,11 @@ use fixtures::{
 };
 use guppy::graph::{
     cargo::{CargoOptions, CargoResolverVersion, CargoSet},
-    feature::{named_feature_filter, FeatureLabel, FeatureSet, StandardFeatures},
+    feature::{
+        named_feature_filter, FeatureId, FeatureLabel, FeatureSet, FeatureTraceStep,
+        StandardFeatures,
+    },
+    DependencyDirection,
 };
 use target_spec::Platform;
 
@@ -358,6 +362,78 @@ fn test_feature_presence() {
     )));
 }
 
+#[test]
+fn feature_trace() {
+    let cargo_set = make_linux_cargo_set(feature_set_fn(&["smallvec", "smallvec-union"]));
+    let main_id = package_id(json::METADATA_WEAK_NAMESPACED_ID);
+    let smallvec_id = package_id(json::METADATA_WEAK_NAMESPACED_SMALLVEC);
+    let target_features = cargo_set.target_features();
+
+    // smallvec-union turns on smallvec/union directly.
+    let trace = target_features
+        .trace((&smallvec_id, FeatureLabel::Named("union")))
+        .expect("valid feature ID")
+        .expect("smallvec/union is enabled");
+    assert_eq!(
+        trace.initial(),
+        FeatureId::named(&main_id, "smallvec-union"),
+        "initial feature matches"
+    );
+    assert_eq!(trace.steps().len(), 1, "one step");
+    assert!(matches!(
+        trace.steps()[0],
+        FeatureTraceStep::Dependency(link) if link.to().feature_id() == trace.feature()
+    ));
+
+    // smallvec turns on foo, which turns on the optional dependency arrayvec.
+    let arrayvec_id = FeatureId::optional_dependency(&main_id, "arrayvec");
+    let trace = target_features
+        .trace(arrayvec_id)
+        .expect("valid feature ID")
+        .expect("dep:arrayvec is enabled");
+    assert_eq!(trace.initial(), FeatureId::named(&main_id, "smallvec"));
+    assert_eq!(trace.feature(), arrayvec_id);
+    let steps: Vec<_> = trace
+        .steps()
+        .iter()
+        .map(|step| (step.from(), step.to()))
+        .collect();
+    assert_eq!(
+        steps,
+        vec![
+            (
+                FeatureId::named(&main_id, "smallvec"),
+                FeatureId::named(&main_id, "foo")
+            ),
+            (FeatureId::named(&main_id, "foo"), arrayvec_id),
+        ],
+        "steps match"
+    );
+    assert!(matches!(
+        trace.steps()[0],
+        FeatureTraceStep::NamedFeature { .. }
+    ));
+    assert!(matches!(
+        trace.steps()[1],
+        FeatureTraceStep::OptionalDependency(_)
+    ));
+
+    // Initial features are explained by themselves.
+    let trace = target_features
+        .trace(FeatureId::named(&main_id, "smallvec"))
+        .expect("valid feature ID")
+        .expect("smallvec is enabled");
+    assert!(trace.steps().is_empty(), "no steps for initial feature");
+
+    assert!(
+        target_features
+            .trace(FeatureId::named(&main_id, "bar"))
+            .expect("valid feature ID")
+            .is_none(),
+        "bar is not enabled"
+    );
+}
+
 /// Test situations where edges have to be upgraded, e.g.
 ///
 /// [features]
@@ -410,6 +486,62 @@ fn test_edge_upgrades() {
     }
 }
 
+#[test]
+fn weak_links() {
+    let feature_graph = JsonFixture::metadata_weak_namespaced_features()
+        .graph()
+        .feature_graph();
+    let main_id = package_id(json::METADATA_WEAK_NAMESPACED_ID);
+    let arrayvec_id = package_id(json::METADATA_WEAK_NAMESPACED_ARRAYVEC);
+    let smallvec_id = package_id(json::METADATA_WEAK_NAMESPACED_SMALLVEC);
+    let tinyvec_id = package_id(json::METADATA_WEAK_NAMESPACED_TINYVEC);
+
+    let links: Vec<_> = feature_graph
+        .resolve_all()
+        .conditional_links(DependencyDirection::Forward)
+        .collect();
+    let is_weak = |from: &'static str, to: FeatureId<'_>| {
+        let from = FeatureId::named(&main_id, from);
+        let link = links
+            .iter()
+            .find(|link| link.from().feature_id() == from && link.to().feature_id() == to)
+            .unwrap_or_else(|| panic!("link from {} to {} not found", from, to));
+        link.is_weak()
+    };
+
+    let smallvec_union = FeatureId::named(&smallvec_id, "union");
+    let dep_smallvec = FeatureId::optional_dependency(&main_id, "smallvec");
+    let arrayvec_std = FeatureId::named(&arrayvec_id, "std");
+
+    assert!(is_weak("smallvec-union", smallvec_union));
+    assert!(is_weak("smallvec-union", dep_smallvec));
+    assert!(is_weak("baz", arrayvec_std));
+    assert!(is_weak(
+        "windows-weak",
+        FeatureId::named(&tinyvec_id, "rustc_1_40")
+    ));
+
+    assert!(!is_weak("bar", arrayvec_std));
+    assert!(!is_weak(
+        "foo",
+        FeatureId::optional_dependency(&main_id, "arrayvec")
+    ));
+    assert!(!is_weak(
+        "windows-non-weak",
+        FeatureId::named(&tinyvec_id, "rustc_1_40")
+    ));
+    // Specifying both the weak and non-weak forms upgrades the link to non-weak.
+    for feature_name in ["upgrade1", "upgrade2"] {
+        assert!(!is_weak(feature_name, smallvec_union), "{}", feature_name);
+        assert!(!is_weak(feature_name, dep_smallvec), "{}", feature_name);
+    }
+    // dep:smallvec activates the optional dependency, but smallvec?/union remains weak.
+    for feature_name in ["upgrade7", "upgrade8"] {
+        assert!(is_weak(feature_name, smallvec_union), "{}", feature_name);
+        assert!(!is_weak(feature_name, dep_smallvec), "{}", feature_name);
+    }
+}
+
 fn feature_set_fn(named_features: &[&str]) -> FeatureSet<'static> {
     JsonFixture::metadata_weak_namespaced_features()
         .graph()
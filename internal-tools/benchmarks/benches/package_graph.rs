@@ -3,7 +3,12 @@
 
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use guppy::{
-    graph::{DependencyDirection, PackageGraph, PackageMetadata},
+    graph::{
+        cargo::{CargoOptions, CargoResolverVersion, CargoSet},
+        feature::StandardFeatures,
+        DependencyDirection, PackageGraph, PackageMetadata,
+    },
+    platform::{Platform, TargetFeatures},
     PackageId,
 };
 use proptest::{collection::vec, prelude::*};
@@ -91,6 +96,39 @@ pub fn query_benchmarks(c: &mut Criterion) {
     });
 }
 
+pub fn cargo_set_benchmarks(c: &mut Criterion) {
+    let package_graph = make_package_graph();
+    let cache = package_graph.new_cargo_resolver_cache();
+
+    let mut cargo_opts = CargoOptions::new();
+    cargo_opts
+        .set_resolver(CargoResolverVersion::V2)
+        .set_include_dev(true)
+        .set_platform(Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap());
+    let mut cached_opts = cargo_opts.clone();
+    cached_opts.set_resolver_cache(&cache);
+
+    // Simulate a build of each workspace package, similar to what hakari does.
+    let build_workspace = |cargo_opts: &CargoOptions<'_>| {
+        let features_only = package_graph
+            .resolve_none()
+            .to_feature_set(StandardFeatures::All);
+        for package in package_graph.workspace().iter() {
+            let initials = package
+                .to_package_set()
+                .to_feature_set(StandardFeatures::Default);
+            black_box(CargoSet::new(initials, features_only.clone(), cargo_opts).unwrap());
+        }
+    };
+
+    c.bench_function("cargo_set_workspace", |b| {
+        b.iter(|| build_workspace(&cargo_opts))
+    });
+    c.bench_function("cargo_set_workspace_cache", |b| {
+        b.iter(|| build_workspace(&cached_opts))
+    });
+}
+
 fn make_package_graph() -> PackageGraph {
     // Use this package graph as a large and representative one.
     PackageGraph::from_json(include_str!(
@@ -132,5 +170,10 @@ fn ids_directions_strategy(
     )
 }
 
-criterion_group!(benches, construct_benchmarks, query_benchmarks);
+criterion_group!(
+    benches,
+    construct_benchmarks,
+    query_benchmarks,
+    cargo_set_benchmarks
+);
 criterion_main!(benches);
@@ -8,7 +8,7 @@ use cargo::{
         resolver::{features::FeaturesFor, CliFeatures, ForceAllTargets, HasDevUnits},
         FeatureValue, PackageIdSpec, Workspace,
     },
-    ops::resolve_ws_with_opts,
+    ops::{resolve_ws_with_opts, Packages},
     util::interning::InternedString,
     Config,
 };
@@ -73,18 +73,19 @@ impl GuppyCargoCommon {
         let target_data = RustcTargetData::new(&workspace, &[compile_kind])?;
 
         let cli_features = self.cargo_make_cli_features();
-        let packages = &self.pf.packages;
-        let specs: Vec<_> = if packages.is_empty() {
+        let specs: Vec<_> = if !self.pf.workspace && self.pf.packages.is_empty() {
             // Pass in the entire workspace.
             workspace
                 .members()
                 .map(|package| PackageIdSpec::from_package_id(package.package_id()))
                 .collect()
         } else {
-            packages
-                .iter()
-                .map(|spec| PackageIdSpec::parse(spec))
-                .collect::<anyhow::Result<_>>()?
+            Packages::from_flags(
+                self.pf.workspace,
+                self.pf.exclude.clone(),
+                self.pf.packages.clone(),
+            )?
+            .to_package_id_specs(&workspace)?
         };
 
         let ws_resolve = resolve_ws_with_opts(
@@ -2,19 +2,20 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::common::GuppyCargoCommon;
+use fixtures::temp_workspace::{TempWorkspace, INSIDE_OUTSIDE_DIR};
 use guppy::graph::{cargo::CargoResolverVersion, PackageGraph};
 use guppy_cmdlib::CargoMetadataOptions;
 use once_cell::sync::Lazy;
 use proptest::prelude::*;
-use std::{env, io::Write, path::Path};
-use tempfile::TempDir;
+use std::{env, path::Path};
 
 // ---
-// Paths to fixtures, relative to the cargo-compare directory (the one with Cargo.toml)
+// Paths to fixtures. Copied directories are relative to the fixtures directory, and workspaces
+// within them are relative to the copy. Other workspaces are relative to the cargo-compare
+// directory (the one with Cargo.toml).
 // ---
-pub(super) static INSIDE_OUTSIDE_WORKSPACE: &str =
-    "../../fixtures/workspace/inside-outside/workspace";
-pub(super) static INSIDE_OUTSIDE_COPY_DIR: &str = "../../fixtures/workspace/inside-outside";
+pub(super) static INSIDE_OUTSIDE_WORKSPACE: &str = "workspace";
+pub(super) static INSIDE_OUTSIDE_COPY_DIR: &str = INSIDE_OUTSIDE_DIR;
 pub(super) static CARGO_GUPPY_WORKSPACE: &str = ".";
 
 #[derive(Debug)]
@@ -23,7 +24,7 @@ pub struct Fixture {
     graph: PackageGraph,
     resolver: CargoResolverVersion,
     // Held on to to keep the temp dir around for the duration of the test.
-    _temp_dir: Option<TempDir>,
+    _temp_workspace: Option<TempWorkspace>,
 }
 
 macro_rules! define_fixture {
@@ -48,55 +49,24 @@ impl Fixture {
         resolver: CargoResolverVersion,
         copy_dir: Option<&str>,
     ) -> Self {
-        // Assume that the workspace is relative to `CARGO_MANIFEST_DIR`.
-        let orig_workspace_dir = Path::new(CARGO_MANIFEST_DIR).join(workspace_dir);
-
-        let (temp_dir, workspace_dir) = if let Some(copy_dir) = copy_dir {
-            // Create a temp dir and copy the files over.
-            let temp_dir = tempfile::Builder::new()
-                .prefix("cargo-compare")
-                .tempdir()
-                .expect("tempdir created");
-            let copy_opts = fs_extra::dir::CopyOptions::new();
-
-            let copy_from = Path::new(CARGO_MANIFEST_DIR).join(copy_dir);
-            fs_extra::dir::copy(&copy_from, temp_dir.path(), &copy_opts)
-                .expect("copying dir was successful");
-
-            for entry in std::fs::read_dir(temp_dir.path()).unwrap() {
-                let entry = entry.unwrap();
-                println!("{:?}", entry);
-            }
-
-            // Grab the path to `Cargo.toml`. (fs_extra copies the directory into the tempdir so we
-            // need to use the parent.)
-            let relpath = pathdiff::diff_paths(&orig_workspace_dir, copy_from.parent().unwrap())
-                .expect("both paths are absolute");
-            let workspace_dir = temp_dir.path().join(&relpath);
-            let workspace_dir = workspace_dir.canonicalize().unwrap_or_else(|err| {
-                panic!(
-                    "new workspace_dir {} canonicalized: {}",
-                    workspace_dir.display(),
-                    err,
-                )
-            });
-            let workspace_manifest_path = workspace_dir.join("Cargo.toml");
-
-            let mut open_opts = std::fs::OpenOptions::new();
-            open_opts.append(true).write(true);
-            let mut f = open_opts
-                .open(&workspace_manifest_path)
-                .expect("successfully opened Cargo.toml");
+        let (temp_workspace, workspace_dir) = if let Some(copy_dir) = copy_dir {
+            let temp_workspace = TempWorkspace::new(copy_dir, workspace_dir);
             let resolver_version = match resolver {
                 CargoResolverVersion::V1 | CargoResolverVersion::V1Install => "1",
                 CargoResolverVersion::V2 => "2",
                 _ => panic!("unknown resolver {:?}", resolver),
             };
-            writeln!(f, "resolver = \"{}\"", resolver_version).expect("file written successfully");
+            let manifest = temp_workspace.read("Cargo.toml");
+            temp_workspace.write(
+                "Cargo.toml",
+                format!("{}resolver = \"{}\"\n", manifest, resolver_version),
+            );
 
-            (Some(temp_dir), workspace_dir)
+            let workspace_dir = temp_workspace.root().as_std_path().to_path_buf();
+            (Some(temp_workspace), workspace_dir)
         } else {
-            (None, orig_workspace_dir)
+            // Assume that the workspace is relative to `CARGO_MANIFEST_DIR`.
+            (None, Path::new(CARGO_MANIFEST_DIR).join(workspace_dir))
         };
 
         if !workspace_dir.is_dir() {
@@ -107,6 +77,7 @@ impl Fixture {
         }
         let metadata_opts = CargoMetadataOptions {
             manifest_path: Some(workspace_dir.join("Cargo.toml")),
+            metadata_path: None,
         };
         let graph = metadata_opts
             .make_command()
@@ -117,7 +88,7 @@ impl Fixture {
             metadata_opts,
             graph,
             resolver,
-            _temp_dir: temp_dir,
+            _temp_workspace: temp_workspace,
         }
     }
 
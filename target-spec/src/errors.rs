@@ -53,7 +53,7 @@ impl error::Error for Error {
 /// An error returned in case a `TargetExpression` cannot be parsed.
 #[derive(Debug, PartialEq)]
 pub struct ExpressionParseError {
-    inner: cfg_expr::ParseError,
+    pub(crate) inner: cfg_expr::ParseError,
 }
 
 impl ExpressionParseError {
@@ -0,0 +1,549 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{errors::ExpressionParseError, spec::eval_predicate, Error, Platform};
+use cfg_expr::{
+    error::Reason,
+    expr::lexer::{Lexer, LexerToken, Token},
+    targets::{Arch, Env, Family, Os, Panic, Vendor},
+    ParseError, Predicate, TargetPredicate,
+};
+use std::{fmt, iter::Peekable, ops::Range, str::FromStr};
+
+/// The syntax tree of a parsed `cfg()` expression.
+///
+/// Unlike [`TargetExpression::eval`](crate::TargetExpression::eval), which only understands the
+/// predicates Cargo does, a `CfgExpr` treats every predicate as either a name or a key-value pair.
+/// This allows expressions to be inspected, and to be evaluated against arbitrary values through
+/// [`CfgProvider`].
+///
+/// Obtained through [`TargetExpression::cfg_expr`](crate::TargetExpression::cfg_expr), or by
+/// parsing a string with [`CfgExpr::parse`].
+///
+/// # Examples
+///
+/// ```
+/// use target_spec::{CfgExpr, TargetExpression};
+///
+/// let expr: TargetExpression = "cfg(all(unix, not(target_os = \"macos\")))".parse().unwrap();
+/// assert_eq!(
+///     expr.cfg_expr(),
+///     &CfgExpr::All(vec![
+///         CfgExpr::Name("unix".to_owned()),
+///         CfgExpr::Not(Box::new(CfgExpr::KeyValue {
+///             key: "target_os".to_owned(),
+///             value: "macos".to_owned(),
+///         })),
+///     ]),
+/// );
+/// assert_eq!(expr.cfg_expr().to_string(), "all(unix, not(target_os = \"macos\"))");
+/// ```
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CfgExpr {
+    /// `all(...)`: true if every inner expression is true. `all()` is always true.
+    All(Vec<CfgExpr>),
+
+    /// `any(...)`: true if at least one inner expression is true. `any()` is always false.
+    Any(Vec<CfgExpr>),
+
+    /// `not(...)`: true if the inner expression is false.
+    Not(Box<CfgExpr>),
+
+    /// A single name, such as `unix` or `debug_assertions`.
+    Name(String),
+
+    /// A key-value pair, such as `target_os = "linux"` or `feature = "serde"`.
+    KeyValue {
+        /// The key, such as `target_os`.
+        key: String,
+
+        /// The value, without the surrounding quotes.
+        value: String,
+    },
+}
+
+impl CfgExpr {
+    /// Parses a `cfg()` expression, with or without the surrounding `cfg()`.
+    ///
+    /// Unlike [`TargetExpression::new`](crate::TargetExpression::new), any key is accepted,
+    /// including ones that `target-spec` doesn't know about such as `target_abi`.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        // Spans are relative to the expression inside `cfg()`, as with `cfg-expr`.
+        let original = match input.strip_prefix("cfg(") {
+            Some(rest) if rest.ends_with(')') => &rest[..rest.len() - 1],
+            _ => input,
+        };
+        let tokens = Lexer::new(input)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| Error::InvalidExpression(ExpressionParseError::new(err)))?;
+        let mut parser = Parser {
+            original,
+            tokens: tokens.into_iter().peekable(),
+        };
+
+        let expr = parser.parse_expr(None)?;
+        match parser.tokens.next() {
+            None => Ok(expr),
+            Some(LexerToken {
+                token: Token::CloseParen,
+                span,
+            }) => Err(parser.error(span, Reason::UnopenedParens)),
+            Some(LexerToken { span, .. }) => {
+                Err(parser.error(span, Reason::MultipleRootPredicates))
+            }
+        }
+    }
+
+    /// Evaluates this expression against the values returned by the given provider.
+    ///
+    /// Returns `Some(true)` if there's a match, `Some(false)` if there's none, or `None` if the
+    /// result depends on values the provider returned `None` for.
+    pub fn eval<P: CfgProvider + ?Sized>(&self, provider: &P) -> Option<bool> {
+        match self {
+            CfgExpr::All(exprs) => {
+                let mut result = Some(true);
+                for expr in exprs {
+                    match expr.eval(provider) {
+                        Some(true) => {}
+                        Some(false) => return Some(false),
+                        None => result = None,
+                    }
+                }
+                result
+            }
+            CfgExpr::Any(exprs) => {
+                let mut result = Some(false);
+                for expr in exprs {
+                    match expr.eval(provider) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => result = None,
+                    }
+                }
+                result
+            }
+            CfgExpr::Not(expr) => expr.eval(provider).map(|result| !result),
+            CfgExpr::Name(name) => provider.has_name(name),
+            CfgExpr::KeyValue { key, value } => provider.has_key_value(key, value),
+        }
+    }
+}
+
+impl FromStr for CfgExpr {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        Self::parse(input)
+    }
+}
+
+struct Parser<'a> {
+    original: &'a str,
+    tokens: Peekable<std::vec::IntoIter<LexerToken<'a>>>,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses a single expression. `open_paren` is the position of the innermost unclosed `(`,
+    /// if any.
+    fn parse_expr(&mut self, open_paren: Option<usize>) -> Result<CfgExpr, Error> {
+        let LexerToken { token, span } = match self.tokens.next() {
+            Some(token) => token,
+            None => {
+                let len = self.original.len();
+                return Err(match open_paren {
+                    Some(pos) => self.error(pos..len, Reason::UnclosedParens),
+                    None => self.error(0..len, Reason::Empty),
+                });
+            }
+        };
+
+        match token {
+            Token::Key(key) => {
+                if self.next_if(&Token::Equals).is_none() {
+                    return Ok(CfgExpr::Name(key.to_owned()));
+                }
+                match self.tokens.next() {
+                    Some(LexerToken {
+                        token: Token::Value(value),
+                        ..
+                    }) => Ok(CfgExpr::KeyValue {
+                        key: key.to_owned(),
+                        value: value.to_owned(),
+                    }),
+                    other => Err(self.unexpected(other, &["<quoted value>"])),
+                }
+            }
+            Token::All | Token::Any | Token::Not => {
+                let open_pos = match self.next_if(&Token::OpenParen) {
+                    Some(open_span) => open_span.start,
+                    None => {
+                        let next = self.tokens.next();
+                        return Err(self.unexpected(next, &["("]));
+                    }
+                };
+                let (mut exprs, close_end) = self.parse_list(open_pos)?;
+                match token {
+                    Token::All => Ok(CfgExpr::All(exprs)),
+                    Token::Any => Ok(CfgExpr::Any(exprs)),
+                    _ if exprs.len() == 1 => Ok(CfgExpr::Not(Box::new(
+                        exprs.pop().expect("checked length above"),
+                    ))),
+                    _ => Err(self.error(span.start..close_end, Reason::InvalidNot(exprs.len()))),
+                }
+            }
+            Token::CloseParen if open_paren.is_none() => {
+                Err(self.error(span, Reason::UnopenedParens))
+            }
+            _ => Err(self.error(span, Reason::Unexpected(&["<key>", "all", "any", "not"]))),
+        }
+    }
+
+    /// Parses a comma-separated list of expressions after a `(`, through the matching `)`.
+    ///
+    /// Returns the expressions, and the end of the span of the `)`.
+    fn parse_list(&mut self, open_pos: usize) -> Result<(Vec<CfgExpr>, usize), Error> {
+        let mut exprs = vec![];
+        loop {
+            if let Some(close_span) = self.next_if(&Token::CloseParen) {
+                return Ok((exprs, close_span.end));
+            }
+            exprs.push(self.parse_expr(Some(open_pos))?);
+            match self.tokens.next() {
+                Some(LexerToken {
+                    token: Token::CloseParen,
+                    span,
+                }) => return Ok((exprs, span.end)),
+                Some(LexerToken {
+                    token: Token::Comma,
+                    ..
+                }) => {}
+                None => {
+                    let len = self.original.len();
+                    return Err(self.error(open_pos..len, Reason::UnclosedParens));
+                }
+                other => return Err(self.unexpected(other, &[",", ")"])),
+            }
+        }
+    }
+
+    fn next_if(&mut self, expected: &Token<'_>) -> Option<Range<usize>> {
+        self.tokens
+            .next_if(|token| &token.token == expected)
+            .map(|token| token.span)
+    }
+
+    fn unexpected(
+        &self,
+        token: Option<LexerToken<'_>>,
+        expected: &'static [&'static str],
+    ) -> Error {
+        let span = match token {
+            Some(token) => token.span,
+            None => self.original.len()..self.original.len(),
+        };
+        self.error(span, Reason::Unexpected(expected))
+    }
+
+    fn error(&self, span: Range<usize>, reason: Reason) -> Error {
+        Error::InvalidExpression(ExpressionParseError::new(ParseError {
+            original: self.original.to_owned(),
+            span,
+            reason,
+        }))
+    }
+}
+
+/// Displays the expression in `cfg()` syntax, without the surrounding `cfg()`.
+impl fmt::Display for CfgExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (func, exprs) = match self {
+            CfgExpr::All(exprs) => ("all", &exprs[..]),
+            CfgExpr::Any(exprs) => ("any", &exprs[..]),
+            CfgExpr::Not(expr) => ("not", std::slice::from_ref(&**expr)),
+            CfgExpr::Name(name) => return write!(f, "{}", name),
+            CfgExpr::KeyValue { key, value } => return write!(f, "{} = \"{}\"", key, value),
+        };
+        write!(f, "{}(", func)?;
+        for (idx, expr) in exprs.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", expr)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Provides the values that a [`CfgExpr`] is evaluated against.
+///
+/// [`Platform`] implements this trait the same way Cargo evaluates `cfg()` expressions. Custom
+/// implementations can be used to evaluate expressions with other keys, for example `feature`
+/// or newer keys like `target_abi`, and can delegate to a `Platform` for everything else.
+///
+/// # Examples
+///
+/// ```
+/// use target_spec::{CfgExpr, CfgProvider, Platform, TargetFeatures};
+///
+/// struct WithAbi {
+///     platform: Platform,
+///     abi: &'static str,
+/// }
+///
+/// impl CfgProvider for WithAbi {
+///     fn has_name(&self, name: &str) -> Option<bool> {
+///         self.platform.has_name(name)
+///     }
+///
+///     fn has_key_value(&self, key: &str, value: &str) -> Option<bool> {
+///         match key {
+///             "target_abi" => Some(value == self.abi),
+///             _ => self.platform.has_key_value(key, value),
+///         }
+///     }
+/// }
+///
+/// let provider = WithAbi {
+///     platform: Platform::new("aarch64-apple-ios", TargetFeatures::Unknown).unwrap(),
+///     abi: "sim",
+/// };
+/// let expr = CfgExpr::parse("cfg(all(target_os = \"ios\", target_abi = \"sim\"))").unwrap();
+/// assert_eq!(expr.eval(&provider), Some(true));
+/// // `target-spec` doesn't know about `target_abi`, so a `Platform` evaluates it to false.
+/// assert_eq!(expr.eval(&provider.platform), Some(false));
+/// ```
+pub trait CfgProvider {
+    /// Returns whether a single name like `unix` or `foo` is set.
+    ///
+    /// Returns `None` if this is unknown.
+    fn has_name(&self, name: &str) -> Option<bool>;
+
+    /// Returns whether `key` is set to `value`, as in `target_os = "linux"`.
+    ///
+    /// Keys like `target_feature` may be set to several values at once. Returns `None` if this is
+    /// unknown.
+    fn has_key_value(&self, key: &str, value: &str) -> Option<bool>;
+}
+
+/// Evaluates names and key-value pairs the same way as
+/// [`TargetExpression::eval`](crate::TargetExpression::eval).
+impl CfgProvider for Platform {
+    fn has_name(&self, name: &str) -> Option<bool> {
+        eval_single(self, name, None)
+    }
+
+    fn has_key_value(&self, key: &str, value: &str) -> Option<bool> {
+        eval_single(self, key, Some(value))
+    }
+}
+
+fn eval_single(platform: &Platform, key: &str, value: Option<&str>) -> Option<bool> {
+    match to_predicate(key, value) {
+        Some(pred) => eval_predicate(platform, &pred),
+        // Predicates that Cargo can't parse can't be set either.
+        None => Some(false),
+    }
+}
+
+/// Converts a name or key-value pair to the predicate `cfg-expr` parses it as, or `None` if it
+/// isn't valid.
+fn to_predicate<'a>(key: &'a str, value: Option<&'a str>) -> Option<Predicate<'a>> {
+    let pred = match (key, value) {
+        ("unix", None) | ("windows", None) => {
+            Predicate::Target(TargetPredicate::Family(Family::new(key.to_owned())))
+        }
+        ("test", None) => Predicate::Test,
+        ("debug_assertions", None) => Predicate::DebugAssertions,
+        ("proc_macro", None) => Predicate::ProcMacro,
+        ("unix", Some(_))
+        | ("windows", Some(_))
+        | ("test", Some(_))
+        | ("debug_assertions", Some(_))
+        | ("proc_macro", Some(_))
+        | ("feature", None)
+        | ("panic", None) => return None,
+        ("feature", Some(value)) => Predicate::Feature(value),
+        ("panic", Some(value)) => {
+            Predicate::Target(TargetPredicate::Panic(Panic::new(value.to_owned())))
+        }
+        (key, value) if key.starts_with("target_") => {
+            let value = value?;
+            let target = match &key["target_".len()..] {
+                "arch" => TargetPredicate::Arch(Arch::new(value.to_owned())),
+                "feature" if value.is_empty() => return None,
+                "feature" => return Some(Predicate::TargetFeature(value)),
+                "os" => TargetPredicate::Os(Os::new(value.to_owned())),
+                "family" => TargetPredicate::Family(Family::new(value.to_owned())),
+                "env" => TargetPredicate::Env(Env::new(value.to_owned())),
+                "endian" => TargetPredicate::Endian(value.parse().ok()?),
+                "has_atomic" => TargetPredicate::HasAtomic(value.parse().ok()?),
+                "pointer_width" => TargetPredicate::PointerWidth(value.parse().ok()?),
+                "vendor" => TargetPredicate::Vendor(Vendor::new(value.to_owned())),
+                _ => return None,
+            };
+            Predicate::Target(target)
+        }
+        (key, None) => Predicate::Flag(key),
+        (key, Some(val)) => Predicate::KeyValue { key, val },
+    };
+    Some(pred)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TargetExpression, TargetFeatures};
+
+    #[test]
+    fn test_parse_tree() {
+        let expr: TargetExpression =
+            "cfg(any(windows, all(unix, target_pointer_width = \"64\",), not(foo)))"
+                .parse()
+                .unwrap();
+        assert_eq!(
+            expr.cfg_expr(),
+            &CfgExpr::Any(vec![
+                CfgExpr::Name("windows".to_owned()),
+                CfgExpr::All(vec![
+                    CfgExpr::Name("unix".to_owned()),
+                    CfgExpr::KeyValue {
+                        key: "target_pointer_width".to_owned(),
+                        value: "64".to_owned(),
+                    },
+                ]),
+                CfgExpr::Not(Box::new(CfgExpr::Name("foo".to_owned()))),
+            ]),
+        );
+        assert_eq!(
+            expr.cfg_expr().to_string(),
+            "any(windows, all(unix, target_pointer_width = \"64\"), not(foo))",
+        );
+
+        let expr: TargetExpression = "cfg(all())".parse().unwrap();
+        assert_eq!(expr.cfg_expr(), &CfgExpr::All(vec![]));
+    }
+
+    #[test]
+    fn test_eval_with_platform() {
+        let mut platforms = vec![
+            Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap(),
+            Platform::new("i686-pc-windows-msvc", TargetFeatures::none()).unwrap(),
+            Platform::new("aarch64-apple-darwin", TargetFeatures::All).unwrap(),
+        ];
+        platforms[0].add_flags(["foo"]);
+
+        let inputs = [
+            "cfg(unix)",
+            "cfg(not(windows))",
+            "cfg(all(unix, target_arch = \"x86_64\"))",
+            "cfg(any(target_os = \"macos\", target_env = \"msvc\"))",
+            "cfg(target_feature = \"sse2\")",
+            "cfg(not(target_feature = \"sse2\"))",
+            "cfg(any(foo, bar))",
+            "cfg(all(test, debug_assertions))",
+            "cfg(not(feature = \"serde\"))",
+            "cfg(bogus_key = \"bogus_value\")",
+            "cfg(any())",
+            "cfg(all())",
+            "cfg(all(target_endian = \"little\", target_pointer_width = \"64\"))",
+            "cfg(any(target_vendor = \"apple\", target_family = \"wasm\"))",
+            "cfg(target_has_atomic = \"64\")",
+            "cfg(panic = \"unwind\")",
+        ];
+        for input in inputs {
+            let expr: TargetExpression = input.parse().unwrap();
+            for platform in &platforms {
+                assert_eq!(
+                    expr.eval_with(platform),
+                    expr.eval(platform),
+                    "{} on {}",
+                    input,
+                    platform.triple_str(),
+                );
+            }
+        }
+
+        // Predicates that Cargo can't parse are never set.
+        let platform = &platforms[0];
+        assert_eq!(platform.has_name("target_os"), Some(false));
+        assert_eq!(platform.has_key_value("unix", "true"), Some(false));
+        assert_eq!(platform.has_key_value("target_abi", "sim"), Some(false));
+        assert_eq!(
+            platform.has_key_value("target_pointer_width", "sixty-four"),
+            Some(false)
+        );
+        assert_eq!(platform.has_name("foo"), Some(true));
+    }
+
+    #[test]
+    fn test_eval_with_unknown() {
+        struct Features(&'static [&'static str]);
+
+        impl CfgProvider for Features {
+            fn has_name(&self, _name: &str) -> Option<bool> {
+                None
+            }
+
+            fn has_key_value(&self, key: &str, value: &str) -> Option<bool> {
+                match key {
+                    "feature" => Some(self.0.contains(&value)),
+                    _ => None,
+                }
+            }
+        }
+
+        let provider = Features(&["serde"]);
+        let eval = |input: &str| CfgExpr::parse(input).unwrap().eval(&provider);
+        assert_eq!(eval("cfg(feature = \"serde\")"), Some(true));
+        assert_eq!(eval("cfg(not(feature = \"std\"))"), Some(true));
+        assert_eq!(eval("cfg(all(feature = \"serde\", unix))"), None);
+        assert_eq!(eval("cfg(all(feature = \"std\", unix))"), Some(false));
+        assert_eq!(eval("cfg(any(feature = \"serde\", unix))"), Some(true));
+        assert_eq!(eval("cfg(any(feature = \"std\", unix))"), None);
+        assert_eq!(
+            eval("any(feature = \"std\", feature = \"serde\")"),
+            Some(true)
+        );
+        assert_eq!(eval("cfg(target_abi = \"sim\")"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let reason = |input: &str| match CfgExpr::parse(input).expect_err("invalid expression") {
+            Error::InvalidExpression(err) => err.inner.reason,
+            other => panic!("expected InvalidExpression, got {:?}", other),
+        };
+
+        assert_eq!(reason("cfg()"), Reason::Empty);
+        assert_eq!(reason("cfg(unix, windows)"), Reason::MultipleRootPredicates);
+        assert_eq!(reason("cfg(all(unix)"), Reason::UnclosedParens);
+        assert_eq!(reason("cfg(unix))"), Reason::UnopenedParens);
+        assert_eq!(reason("cfg(not(unix, windows))"), Reason::InvalidNot(2));
+        assert_eq!(reason("cfg(not())"), Reason::InvalidNot(0));
+        assert_eq!(
+            reason("cfg(target_os =)"),
+            Reason::Unexpected(&["<quoted value>"])
+        );
+        assert_eq!(reason("cfg(all unix)"), Reason::Unexpected(&["("]));
+        assert_eq!(
+            reason("cfg(any(unix windows))"),
+            Reason::Unexpected(&[",", ")"])
+        );
+        assert_eq!(
+            reason("cfg(all(unix, \"x\"))"),
+            Reason::Unexpected(&["<key>", "all", "any", "not"])
+        );
+        assert_eq!(reason("cfg(foo = \"bar)"), Reason::UnclosedQuotes);
+
+        // Unknown target keys are rejected by TargetExpression, but not by CfgExpr.
+        let input = "cfg(target_abi = \"sim\")";
+        assert!(input.parse::<TargetExpression>().is_err());
+        assert_eq!(
+            CfgExpr::parse(input).expect("any key is accepted"),
+            CfgExpr::KeyValue {
+                key: "target_abi".to_owned(),
+                value: "sim".to_owned(),
+            },
+        );
+    }
+}
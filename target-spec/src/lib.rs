@@ -34,7 +34,11 @@
 //! assert_eq!(eval("x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"), Ok(Some(false)));
 //! ```
 //!
-//! For more advanced usage, see [`Platform`] and [`TargetSpec`].
+//! For more advanced usage, see [`Platform`] and [`TargetSpec`]. To evaluate many specs against
+//! the same platforms, see [`TargetSpecSet`].
+//!
+//! To inspect the parsed tree of a `cfg()` expression, or to evaluate it against keys that Cargo
+//! doesn't understand, such as `feature` or `target_abi`, see [`CfgExpr`] and [`CfgProvider`].
 //!
 //! ## Optional features
 //!
@@ -55,17 +59,21 @@
 #![cfg_attr(doc_cfg, feature(doc_cfg, doc_auto_cfg))]
 
 pub mod errors;
+mod expr;
 mod platform;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
 mod simple_eval;
 mod spec;
+mod spec_set;
 #[cfg(feature = "summaries")]
 pub mod summaries;
 mod triple;
 
 pub use errors::Error;
+pub use expr::*;
 pub use platform::*;
 pub use simple_eval::*;
 pub use spec::*;
+pub use spec_set::*;
 pub use triple::*;
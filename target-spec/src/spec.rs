@@ -1,8 +1,9 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::{errors::ExpressionParseError, Error, Platform, Triple};
+use crate::{errors::ExpressionParseError, CfgExpr, CfgProvider, Error, Platform, Triple};
 use cfg_expr::{Expression, Predicate};
+use once_cell::sync::OnceCell;
 use std::{borrow::Cow, str::FromStr, sync::Arc};
 
 /// A parsed target specification or triple, as found in a `Cargo.toml` file.
@@ -84,6 +85,9 @@ impl FromStr for TargetSpec {
 #[derive(Clone, Debug)]
 pub struct TargetExpression {
     inner: Arc<Expression>,
+    // Most expressions are only ever evaluated against platforms, so the syntax tree is built on
+    // first use rather than parsing every expression twice.
+    cfg_expr: Arc<OnceCell<CfgExpr>>,
 }
 
 impl TargetExpression {
@@ -96,6 +100,7 @@ impl TargetExpression {
             .map_err(|err| Error::InvalidExpression(ExpressionParseError::new(err)))?;
         Ok(Self {
             inner: Arc::new(expr),
+            cfg_expr: Arc::new(OnceCell::new()),
         })
     }
 
@@ -105,35 +110,57 @@ impl TargetExpression {
         self.inner.original()
     }
 
+    /// Returns the syntax tree of this expression.
+    ///
+    /// The syntax tree is built the first time this is called, and is shared by clones of `self`.
+    pub fn cfg_expr(&self) -> &CfgExpr {
+        self.cfg_expr.get_or_init(|| {
+            // CfgExpr::parse accepts a superset of what Expression::parse does.
+            CfgExpr::parse(self.inner.original())
+                .expect("expression was already parsed by cfg-expr")
+        })
+    }
+
     /// Evaluates this expression against the given platform.
     ///
     /// Returns `Some(true)` if there's a match, `Some(false)` if there's none, or `None` if the
     /// result of the evaluation is unknown (typically found if target features are involved).
     pub fn eval(&self, platform: &Platform) -> Option<bool> {
-        self.inner.eval(|pred| {
-            match pred {
-                Predicate::Target(target) => Some(platform.triple().matches(target)),
-                Predicate::TargetFeature(feature) => platform.target_features().matches(feature),
-                Predicate::Test | Predicate::DebugAssertions | Predicate::ProcMacro => {
-                    // Known families that always evaluate to false. See
-                    // https://docs.rs/cargo-platform/0.1.1/src/cargo_platform/lib.rs.html#76.
-                    Some(false)
-                }
-                Predicate::Feature(_) => {
-                    // NOTE: This is not supported by Cargo which always evaluates this to false. See
-                    // https://github.com/rust-lang/cargo/issues/7442 for more details.
-                    Some(false)
-                }
-                Predicate::Flag(flag) => {
-                    // This returns false by default but true in some cases.
-                    Some(platform.has_flag(flag))
-                }
-                Predicate::KeyValue { .. } => {
-                    // This is always interpreted by Cargo as false.
-                    Some(false)
-                }
-            }
-        })
+        self.inner.eval(|pred| eval_predicate(platform, pred))
+    }
+
+    /// Evaluates this expression against the values returned by the given provider.
+    ///
+    /// With a [`Platform`] as the provider, this returns the same results as
+    /// [`eval`](Self::eval). For more, see [`CfgProvider`].
+    pub fn eval_with<P: CfgProvider + ?Sized>(&self, provider: &P) -> Option<bool> {
+        self.cfg_expr().eval(provider)
+    }
+}
+
+/// Evaluates a single predicate against the given platform, the same way Cargo does.
+pub(crate) fn eval_predicate(platform: &Platform, pred: &Predicate<'_>) -> Option<bool> {
+    match pred {
+        Predicate::Target(target) => Some(platform.triple().matches(target)),
+        Predicate::TargetFeature(feature) => platform.target_features().matches(feature),
+        Predicate::Test | Predicate::DebugAssertions | Predicate::ProcMacro => {
+            // Known families that always evaluate to false. See
+            // https://docs.rs/cargo-platform/0.1.1/src/cargo_platform/lib.rs.html#76.
+            Some(false)
+        }
+        Predicate::Feature(_) => {
+            // NOTE: This is not supported by Cargo which always evaluates this to false. See
+            // https://github.com/rust-lang/cargo/issues/7442 for more details.
+            Some(false)
+        }
+        Predicate::Flag(flag) => {
+            // This returns false by default but true in some cases.
+            Some(platform.has_flag(flag))
+        }
+        Predicate::KeyValue { .. } => {
+            // This is always interpreted by Cargo as false.
+            Some(false)
+        }
     }
 }
 
@@ -264,4 +291,20 @@ mod tests {
         let res = TargetSpec::new("cfg(not(unix)");
         res.expect_err("missing ) at the end");
     }
+
+    #[test]
+    fn test_cfg_expr_lazy() {
+        let expr: TargetExpression = "cfg(all(unix, target_os = \"linux\"))".parse().unwrap();
+        assert!(expr.cfg_expr.get().is_none(), "syntax tree not built yet");
+
+        let cloned = expr.clone();
+        assert_eq!(
+            expr.cfg_expr().to_string(),
+            "all(unix, target_os = \"linux\")"
+        );
+        assert!(
+            std::ptr::eq(expr.cfg_expr(), cloned.cfg_expr()),
+            "clones share the syntax tree"
+        );
+    }
 }
@@ -0,0 +1,249 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{Error, Platform, TargetSpec};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// A collection of target specifications, parsed once and evaluated against platforms in bulk.
+///
+/// Real-world dependency graphs tend to repeat the same handful of `cfg()` expressions many times
+/// over. A `TargetSpecSet` stores each distinct specification exactly once, so that its parsed
+/// form is shared by every caller that adds it. Each spec is identified by the index returned
+/// when it was added.
+///
+/// Evaluating the set against a platform computes the results for every spec in a single pass.
+/// These results are memoized by platform, so repeated evaluations against the same platform
+/// are cheap.
+///
+/// ## Examples
+///
+/// ```
+/// use target_spec::{Platform, TargetFeatures, TargetSpecSet};
+///
+/// let mut spec_set = TargetSpecSet::new();
+/// let windows = spec_set.add("cfg(windows)").unwrap();
+/// let x86_64 = spec_set.add("cfg(target_arch = \"x86_64\")").unwrap();
+/// let features = spec_set.add("cfg(target_feature = \"sse2\")").unwrap();
+/// // Adding the same spec again returns the existing index.
+/// assert_eq!(spec_set.add("cfg(windows)").unwrap(), windows);
+/// assert_eq!(spec_set.len(), 3);
+///
+/// let i686_windows = Platform::new("i686-pc-windows-gnu", TargetFeatures::Unknown).unwrap();
+/// let results = spec_set.eval(&i686_windows);
+/// assert_eq!(results[windows], Some(true), "i686 Windows is Windows");
+/// assert_eq!(results[x86_64], Some(false), "i686 Windows is not x86_64");
+/// assert_eq!(results[features], None, "i686 Windows features are unknown");
+///
+/// // Results for individual specs are served from the same cache.
+/// assert_eq!(spec_set.eval_index(windows, &i686_windows), Some(Some(true)));
+/// ```
+#[derive(Debug, Default)]
+pub struct TargetSpecSet {
+    specs: Vec<TargetSpec>,
+    indexes: HashMap<Box<str>, usize>,
+    cache: Mutex<HashMap<Platform, Arc<[Option<bool>]>>>,
+}
+
+impl TargetSpecSet {
+    /// Creates a new, empty `TargetSpecSet`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses a target specification or triple and adds it to this set, returning its index.
+    ///
+    /// If a spec with the same string was added earlier, it is not parsed again, and the
+    /// existing index is returned.
+    ///
+    /// Returns an error if the string could not be parsed.
+    pub fn add(&mut self, input: impl Into<Cow<'static, str>>) -> Result<usize, Error> {
+        let input = input.into();
+        if let Some(&index) = self.indexes.get(input.as_ref()) {
+            return Ok(index);
+        }
+        let spec = TargetSpec::new(input)?;
+        Ok(self.add_spec(spec))
+    }
+
+    /// Adds an already-parsed target specification to this set, returning its index.
+    ///
+    /// If a spec with the same string was added earlier, `spec` is dropped and the existing index
+    /// is returned.
+    pub fn add_spec(&mut self, spec: TargetSpec) -> usize {
+        let key = spec_key(&spec);
+        if let Some(&index) = self.indexes.get(key.as_ref()) {
+            return index;
+        }
+
+        let index = self.specs.len();
+        self.indexes.insert(key.into(), index);
+        self.specs.push(spec);
+        // Cached results don't cover the new spec, so throw them away.
+        self.cache_mut().clear();
+        index
+    }
+
+    /// Returns the number of distinct specs in this set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    /// Returns true if this set has no specs.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// Returns the spec at this index, or `None` if the index is out of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&TargetSpec> {
+        self.specs.get(index)
+    }
+
+    /// Returns the index of the spec with this string, or `None` if it isn't in this set.
+    pub fn index_of(&self, input: &str) -> Option<usize> {
+        self.indexes.get(input).copied()
+    }
+
+    /// Iterates over the specs in this set, in the order they were added.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &TargetSpec> {
+        self.specs.iter()
+    }
+
+    /// Evaluates every spec in this set against the given platform.
+    ///
+    /// The returned slice has one entry per spec, in index order. Each entry is `Some(true)` if
+    /// there's a match, `Some(false)` if there's none, or `None` if the result of the evaluation
+    /// is unknown (typically found if target features are involved).
+    ///
+    /// Results are memoized by platform.
+    pub fn eval(&self, platform: &Platform) -> Arc<[Option<bool>]> {
+        let mut cache = self.cache.lock().expect("cache lock poisoned");
+        if let Some(results) = cache.get(platform) {
+            return results.clone();
+        }
+
+        let results: Arc<[Option<bool>]> =
+            self.specs.iter().map(|spec| spec.eval(platform)).collect();
+        cache.insert(platform.clone(), results.clone());
+        results
+    }
+
+    /// Evaluates the spec at this index against the given platform.
+    ///
+    /// Returns `None` if the index is out of bounds, otherwise the result of the evaluation as
+    /// described in [`eval`](Self::eval).
+    pub fn eval_index(&self, index: usize, platform: &Platform) -> Option<Option<bool>> {
+        if index >= self.specs.len() {
+            return None;
+        }
+        Some(self.eval(platform)[index])
+    }
+
+    /// Clears memoized evaluation results.
+    pub fn clear_cache(&mut self) {
+        self.cache_mut().clear();
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn cache_mut(&mut self) -> &mut HashMap<Platform, Arc<[Option<bool>]>> {
+        self.cache.get_mut().expect("cache lock poisoned")
+    }
+}
+
+impl Clone for TargetSpecSet {
+    fn clone(&self) -> Self {
+        let cache = self.cache.lock().expect("cache lock poisoned").clone();
+        Self {
+            specs: self.specs.clone(),
+            indexes: self.indexes.clone(),
+            cache: Mutex::new(cache),
+        }
+    }
+}
+
+// Returns the string this spec would have been parsed from. (The expression string doesn't include
+// the surrounding `cfg()`.)
+fn spec_key(spec: &TargetSpec) -> Cow<'_, str> {
+    match spec {
+        TargetSpec::Triple(triple) => Cow::Borrowed(triple.as_str()),
+        TargetSpec::Expression(expr) => Cow::Owned(format!("cfg({})", expr.expression_str())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetFeatures;
+
+    #[test]
+    fn test_dedup() {
+        let mut spec_set = TargetSpecSet::new();
+        let unix = spec_set.add("cfg(unix)").unwrap();
+        let linux = spec_set.add("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(spec_set.add("cfg(unix)").unwrap(), unix);
+        assert_eq!(
+            spec_set.add_spec(TargetSpec::new("x86_64-unknown-linux-gnu").unwrap()),
+            linux
+        );
+        assert_eq!(spec_set.len(), 2);
+        assert_eq!(spec_set.index_of("cfg(unix)"), Some(unix));
+        assert_eq!(spec_set.index_of("cfg(windows)"), None);
+
+        spec_set
+            .add("cfg(not(unix)")
+            .expect_err("missing ) at the end");
+        assert_eq!(spec_set.len(), 2, "failed add doesn't change the set");
+    }
+
+    #[test]
+    fn test_eval_matches_spec() {
+        let inputs = [
+            "cfg(unix)",
+            "cfg(windows)",
+            "cfg(all(unix, target_arch = \"x86_64\"))",
+            "cfg(target_feature = \"sse2\")",
+            "cfg(foo)",
+            "i686-pc-windows-msvc",
+        ];
+        let mut spec_set = TargetSpecSet::new();
+        for input in inputs {
+            spec_set.add(input).unwrap();
+        }
+
+        let mut platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+            .expect("known platform");
+        let results = spec_set.eval(&platform);
+        assert_eq!(results.len(), inputs.len());
+        for (input, result) in inputs.iter().zip(results.iter()) {
+            let spec: TargetSpec = input.parse().unwrap();
+            assert_eq!(*result, spec.eval(&platform), "result for {}", input);
+        }
+
+        // A platform with different flags is cached separately.
+        platform.add_flags(["foo"]);
+        let foo = spec_set.index_of("cfg(foo)").unwrap();
+        assert_eq!(spec_set.eval_index(foo, &platform), Some(Some(true)));
+        assert_eq!(spec_set.eval_index(inputs.len(), &platform), None);
+    }
+
+    #[test]
+    fn test_add_after_eval() {
+        let platform = Platform::new("i686-pc-windows-msvc", TargetFeatures::none()).unwrap();
+        let mut spec_set = TargetSpecSet::new();
+        spec_set.add("cfg(unix)").unwrap();
+        assert_eq!(&*spec_set.eval(&platform), &[Some(false)]);
+
+        // Adding a spec must not return stale cached results.
+        let windows = spec_set.add("cfg(windows)").unwrap();
+        assert_eq!(spec_set.eval_index(windows, &platform), Some(Some(true)));
+    }
+}
@@ -5,7 +5,7 @@ use crate::{errors::TripleParseError, Platform};
 use cfg_expr::{
     expr::TargetMatcher,
     target_lexicon,
-    targets::{get_builtin_target_by_triple, TargetInfo},
+    targets::{get_builtin_target_by_triple, TargetInfo, ALL_BUILTINS},
     TargetPredicate,
 };
 use std::{borrow::Cow, cmp::Ordering, hash, str::FromStr};
@@ -39,12 +39,32 @@ impl Triple {
         Ok(Self { inner })
     }
 
+    /// Returns an iterator over all the triples built into `rustc` that this version of
+    /// `target-spec` knows about.
+    ///
+    /// This can be used to find the platforms a [`TargetSpec`](crate::TargetSpec) matches.
+    pub fn builtins() -> impl ExactSizeIterator<Item = Triple> {
+        ALL_BUILTINS.iter().map(|target_info| Self {
+            inner: TripleInner::Builtin(target_info),
+        })
+    }
+
     /// Returns the string corresponding to this triple.
     #[inline]
     pub fn as_str(&self) -> &str {
         self.inner.as_str()
     }
 
+    /// Returns true if this triple is built into `rustc`, rather than parsed through
+    /// `target-lexicon`.
+    ///
+    /// Most strings made up of a known architecture followed by other components can be parsed
+    /// into a `Triple`, so this can be used to check whether a string is a real target.
+    #[inline]
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.inner, TripleInner::Builtin(_))
+    }
+
     /// Evaluates this triple against the given platform.
     ///
     /// This simply compares `self` against the `Triple` the platform is based on, ignoring
@@ -187,6 +207,8 @@ mod tests {
             binary_format: BinaryFormat::Macho,
         };
 
+        assert!(!target.is_builtin(), "x86_64-pc-darwin isn't builtin");
+
         let actual_triple = match target.inner {
             TripleInner::Lexicon { lexicon_triple, .. } => lexicon_triple,
             TripleInner::Builtin(_) => {
@@ -198,4 +220,23 @@ mod tests {
             "lexicon triple matched correctly"
         );
     }
+
+    #[test]
+    fn test_is_builtin() {
+        let target = super::Triple::new("x86_64-unknown-linux-gnu").expect("builtin triple");
+        assert!(target.is_builtin(), "x86_64-unknown-linux-gnu is builtin");
+    }
+
+    #[test]
+    fn test_builtins() {
+        for triple in super::Triple::builtins() {
+            assert!(triple.is_builtin(), "{} is builtin", triple.as_str());
+            let parsed = super::Triple::new(triple.as_str().to_owned()).expect("builtin parses");
+            assert_eq!(parsed, triple, "{} round-trips", triple.as_str());
+        }
+        assert!(
+            super::Triple::builtins().any(|triple| triple.as_str() == "x86_64-unknown-linux-gnu"),
+            "x86_64-unknown-linux-gnu is in the list of builtins"
+        );
+    }
 }
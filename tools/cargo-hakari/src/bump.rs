@@ -0,0 +1,205 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Bumping the version of a published workspace-hack crate.
+
+use crate::{helpers::regenerate_lockfile, output::OutputContext, published::ManifestDiff};
+use camino::Utf8Path;
+use color_eyre::{
+    eyre::{bail, eyre, WrapErr},
+    Result,
+};
+use guppy::{graph::PackageMetadata, Version, VersionReq};
+use guppy_edit::ManifestEditor;
+use hakari::{cli_ops::hakari_version_req, summaries::BumpSummary, HakariBuilder};
+use log::info;
+use owo_colors::OwoColorize;
+
+/// Bumps the version of the workspace-hack crate if its contents changed since the last release.
+///
+/// Returns the exit status.
+pub(crate) fn bump_hakari(
+    builder: &HakariBuilder<'_>,
+    summary: &BumpSummary,
+    exact_versions: bool,
+    since: Option<&str>,
+    dry_run: bool,
+    output: OutputContext,
+) -> Result<i32> {
+    let hakari_package = *builder
+        .hakari_package()
+        .ok_or_else(|| eyre!("hakari-package must be specified in hakari.toml"))?;
+    let workspace_root = builder.graph().workspace().root();
+    let name = hakari_package.name();
+    let version = hakari_package.version();
+
+    if hakari_package.publish().is_never() {
+        bail!(
+            "{} isn't published (publish = false), so its version doesn't need to be bumped",
+            name
+        );
+    }
+
+    let rev = match since {
+        Some(rev) => rev.to_owned(),
+        None => {
+            let tag = summary.tag_name(name, version);
+            if !git_tag_exists(workspace_root, &tag)? {
+                info!(
+                    "release tag {} for {} v{} not found: assuming this version is unreleased",
+                    tag.style(output.styles.config_path),
+                    name.style(output.styles.package_name),
+                    version.style(output.styles.package_version),
+                );
+                return Ok(0);
+            }
+            tag
+        }
+    };
+
+    let manifest_path = hakari_package.manifest_path();
+    let rel_manifest_path = manifest_path
+        .strip_prefix(workspace_root)
+        .expect("workspace package manifest is in workspace root");
+    let released_contents = git_show(workspace_root, &rev, rel_manifest_path)?;
+    let contents = std::fs::read_to_string(manifest_path)
+        .wrap_err_with(|| format!("error reading {}", manifest_path))?;
+
+    let diff = ManifestDiff::new(&released_contents, &contents)?;
+    if diff.is_empty() {
+        info!(
+            "{} has no dependency changes since {}",
+            name.style(output.styles.package_name),
+            rev.style(output.styles.config_path),
+        );
+        return Ok(0);
+    }
+
+    let new_version = summary.level.bump(version);
+    info!(
+        "{} changed since {}:\n{}",
+        name.style(output.styles.package_name),
+        rev.style(output.styles.config_path),
+        diff,
+    );
+    info!(
+        "{} {} from v{} to v{}",
+        if dry_run { "would bump" } else { "bumping" },
+        name.style(output.styles.package_name),
+        version.style(output.styles.package_version),
+        new_version.style(output.styles.package_version),
+    );
+
+    let dependents = dependents_to_update(hakari_package, &new_version, exact_versions);
+    if !dependents.is_empty() {
+        let names: Vec<_> = dependents
+            .iter()
+            .map(|(package, _)| package.name())
+            .collect();
+        info!(
+            "{} version requirements in: {}",
+            if dry_run { "would update" } else { "updating" },
+            names.join(", "),
+        );
+    }
+
+    if dry_run {
+        return Ok(1);
+    }
+
+    let mut editor = ManifestEditor::read(manifest_path)?;
+    editor.set_package_version(&new_version.to_string())?;
+    editor.write()?;
+
+    let version_req = hakari_version_req(&new_version, exact_versions);
+    for (package, dep_name) in dependents {
+        update_version_req(package, dep_name, &version_req)?;
+    }
+
+    regenerate_lockfile(workspace_root, output)
+        .wrap_err("error updating Cargo.lock after bumping version")?;
+    Ok(0)
+}
+
+/// Returns the workspace packages that depend on the workspace-hack, along with the name of the
+/// dependency, whose version requirements must be updated.
+fn dependents_to_update<'g>(
+    hakari_package: PackageMetadata<'g>,
+    new_version: &Version,
+    exact_versions: bool,
+) -> Vec<(PackageMetadata<'g>, &'g str)> {
+    let mut dependents: Vec<_> = hakari_package
+        .reverse_direct_links()
+        .filter(|link| link.from().in_workspace() && link.version_req() != &VersionReq::STAR)
+        // With exact versions, requirements are kept in sync with the version. Otherwise, they
+        // only need to be updated if they no longer match.
+        .filter(|link| exact_versions || !link.version_req().matches(new_version))
+        .map(|link| (link.from(), link.dep_name()))
+        .collect();
+    dependents.sort_by_key(|(package, _)| package.name());
+    dependents.dedup_by_key(|(package, _)| package.id());
+    dependents
+}
+
+/// Updates the version requirement for the given dependency in every section it's listed in with a
+/// version.
+fn update_version_req(
+    package: PackageMetadata<'_>,
+    dep_name: &str,
+    version_req: &str,
+) -> Result<()> {
+    let mut editor = ManifestEditor::for_package(package)?;
+    for section in editor.sections()? {
+        let has_version = match editor.get_dependency(&section, dep_name)? {
+            Some(item) => {
+                item.is_str()
+                    || item
+                        .as_table_like()
+                        .map_or(false, |dep| dep.contains_key("version"))
+            }
+            None => false,
+        };
+        if has_version {
+            editor.set_dependency_version(&section, dep_name, version_req)?;
+        }
+    }
+    editor.write()?;
+    Ok(())
+}
+
+fn git_tag_exists(workspace_root: &Utf8Path, tag: &str) -> Result<bool> {
+    let output = duct::cmd!(
+        "git",
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        format!("refs/tags/{}", tag)
+    )
+    .dir(workspace_root)
+    .stdout_null()
+    .unchecked()
+    .run()
+    .wrap_err("error running git rev-parse")?;
+    Ok(output.status.success())
+}
+
+fn git_show(workspace_root: &Utf8Path, rev: &str, rel_path: &Utf8Path) -> Result<String> {
+    // Git expects forward slashes in paths.
+    let spec = format!("{}:{}", rev, rel_path.as_str().replace('\\', "/"));
+    let output = duct::cmd!("git", "show", &spec)
+        .dir(workspace_root)
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .wrap_err("error running git show")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "error reading {} at {}:\n{}",
+            rel_path,
+            rev,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    String::from_utf8(output.stdout).wrap_err_with(|| format!("{} is not valid UTF-8", spec))
+}
@@ -0,0 +1,109 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Shell completions and man pages generated from the command-line interface.
+
+use crate::{command::Args, output::OutputContext};
+use camino::Utf8Path;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use clap_mangen::Man;
+use color_eyre::eyre::{Result, WrapErr};
+use log::info;
+use owo_colors::OwoColorize;
+use std::{fs, io};
+
+/// The name of the executable that completions and man pages are generated for.
+const BIN_NAME: &str = "cargo-hakari";
+
+/// Returns the full tree of `cargo hakari` commands, ready for introspection.
+pub(crate) fn cli_command() -> clap::Command<'static> {
+    let mut command = Args::command().name(BIN_NAME).bin_name(BIN_NAME);
+    command.build();
+    command
+}
+
+/// Prints out shell completions for `cargo hakari` to standard output.
+pub(crate) fn print_completions(shell: Shell) {
+    let mut command = cli_command();
+    clap_complete::generate(shell, &mut command, BIN_NAME, &mut io::stdout());
+}
+
+/// Writes out a man page for `cargo hakari` and each of its subcommands to `out_dir`.
+///
+/// Subcommands are written out as `cargo-hakari-<subcommand>.1`, with nested subcommands
+/// separated by further dashes.
+pub(crate) fn write_manpages(out_dir: &Utf8Path, output: &OutputContext) -> Result<()> {
+    fs::create_dir_all(out_dir)
+        .wrap_err_with(|| format!("error creating output directory {}", out_dir))?;
+
+    let command = cli_command();
+    let source = format!("{} {}", BIN_NAME, command.get_version().unwrap_or_default());
+    let mut pages = Vec::new();
+    collect_pages(&command, BIN_NAME.to_owned(), &mut pages);
+
+    for (page_name, command) in pages {
+        let path = out_dir.join(format!("{}.1", page_name));
+        let mut contents = Vec::new();
+        Man::new(command.name(&page_name))
+            .source(&source)
+            .render(&mut contents)
+            .wrap_err_with(|| format!("error rendering man page for {}", page_name))?;
+        fs::write(&path, contents).wrap_err_with(|| format!("error writing {}", path))?;
+        info!("wrote {}", path.style(output.styles.config_path));
+    }
+    Ok(())
+}
+
+/// Collects every command in the tree along with the name of its man page, skipping the
+/// auto-generated `help` subcommands.
+fn collect_pages<'help>(
+    command: &clap::Command<'help>,
+    page_name: String,
+    pages: &mut Vec<(String, clap::Command<'help>)>,
+) {
+    pages.push((page_name.clone(), command.clone()));
+    for subcommand in command.get_subcommands() {
+        if subcommand.get_name() == "help" {
+            continue;
+        }
+        collect_pages(
+            subcommand,
+            format!("{}-{}", page_name, subcommand.get_name()),
+            pages,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_tree() {
+        cli_command().debug_assert();
+
+        let mut pages = Vec::new();
+        collect_pages(&cli_command(), BIN_NAME.to_owned(), &mut pages);
+        let page_names: Vec<_> = pages.iter().map(|(name, _)| name.as_str()).collect();
+        for expected in [
+            "cargo-hakari",
+            "cargo-hakari-generate",
+            "cargo-hakari-completions",
+            "cargo-hakari-manpages",
+            "cargo-hakari-workspaces",
+            "cargo-hakari-workspaces-manage-deps",
+        ] {
+            assert!(
+                page_names.contains(&expected),
+                "{} is in the list of man pages: {:?}",
+                expected,
+                page_names
+            );
+        }
+        assert!(
+            !page_names.iter().any(|name| name.ends_with("-help")),
+            "help subcommands are skipped"
+        );
+    }
+}
@@ -2,22 +2,31 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    bump::bump_hakari,
+    cli_docs::{print_completions, write_manpages},
     helpers::{read_contents, regenerate_lockfile},
-    output::{OutputContext, OutputOpts},
+    output::{OutputContext, OutputOpts, Styles},
     publish::publish_hakari,
+    published::{ManifestDiff, PublishedManifest},
+    watch::WorkspaceWatcher,
+    workspaces::{exec_workspaces, WorkspacesConfig},
 };
 use camino::{Utf8Path, Utf8PathBuf};
-use clap::Parser;
+use clap::{ArgEnum, Parser};
+use clap_complete::Shell;
 use color_eyre::eyre::{bail, eyre, Result, WrapErr};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use guppy::{
-    graph::{PackageGraph, PackageSet},
+    graph::{DependencyDirection, PackageGraph, PackageMetadata, PackageSet},
     MetadataCommand,
 };
 use hakari::{
     cli_ops::{HakariInit, WorkspaceOps},
     diffy::PatchFormatter,
-    summaries::{HakariConfig, DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH},
-    HakariBuilder, HakariCargoToml, HakariOutputOptions, TomlOutError,
+    report::{HakariReport, ReportOrder},
+    stats::{HakariStats, HakariStatsDiff},
+    summaries::{HakariConfig, ManageDepsSummary, DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH},
+    Hakari, HakariBuilder, HakariCargoToml, HakariOutputOptions, TomlOutError,
 };
 use log::{error, info};
 use owo_colors::OwoColorize;
@@ -85,6 +94,14 @@ enum Command {
         #[clap(long)]
         skip_config: bool,
 
+        /// Directory with templates for the workspace-hack crate, instead of the built-in ones
+        ///
+        /// The directory must contain a `Cargo.toml-in` file. Files ending with `-in` have the
+        /// placeholders `%PACKAGE_NAME%` and `%EDITION%` replaced, and are written out without
+        /// the `-in` suffix. Other files are copied as-is.
+        #[clap(long, value_name = "DIR")]
+        template_dir: Option<Utf8PathBuf>,
+
         /// Print operations that need to be performed, but do not actually perform them.
         ///
         /// Exits with status 1 if any operations need to be performed. Can be combined with
@@ -97,6 +114,79 @@ enum Command {
         yes: bool,
     },
 
+    /// Perform all checks suitable for CI in a single invocation
+    ///
+    /// Checks that the hakari config is valid, that the contents of the workspace-hack crate are
+    /// up-to-date (as with `generate --diff`), that no workspace-hack dependencies need to be added
+    /// or removed (as with `manage-deps --dry-run`), and that verification succeeds (as with
+    /// `verify`). All checks are performed even if some of them fail, and a report is printed at
+    /// the end.
+    ///
+    /// The exit status is the sum of the following values, one for each failed check:
+    ///
+    /// * 2: the config couldn't be read or resolved (further checks are skipped)
+    /// * 4: the contents of the workspace-hack crate are out of date
+    /// * 8: workspace-hack dependencies need to be added or removed
+    /// * 16: verification failed
+    Check,
+
+    /// Bump the version of a published workspace-hack crate if its contents changed
+    ///
+    /// Compares the dependencies in the workspace-hack's Cargo.toml against the ones at its last
+    /// release, found through a git tag for its current version (configured through the [bump]
+    /// section of the config). If they're different, increments the version of the workspace-hack
+    /// and updates version requirements in dependent crates as needed. If the tag doesn't exist,
+    /// the current version is assumed to be unreleased and nothing is done.
+    ///
+    /// Run `cargo hakari generate` first to ensure the contents are up-to-date.
+    Bump {
+        /// Compare against this git revision instead of the release tag
+        #[clap(long, value_name = "REV")]
+        since: Option<String>,
+
+        /// Print the new version, but do not actually write it out.
+        ///
+        /// Exits with status 1 if the version needs to be bumped. Can be combined with `--quiet`.
+        #[clap(long, short = 'n')]
+        dry_run: bool,
+    },
+
+    /// Run a command across several workspaces
+    ///
+    /// The workspaces are listed in a TOML config file, e.g. `workspaces = ["a", "b/c"]`, with
+    /// paths relative to the directory containing the config file. Each workspace must have its
+    /// own hakari config.
+    ///
+    /// All workspaces are processed even if some of them fail, and a summary is printed at the
+    /// end. Exits with the highest exit status across all workspaces.
+    Workspaces {
+        /// Path to the config file listing workspace roots
+        #[clap(long)]
+        config: Utf8PathBuf,
+
+        #[clap(subcommand)]
+        command: WorkspacesCommand,
+    },
+
+    /// Generate shell completions for cargo-hakari
+    ///
+    /// The completion script is printed to standard output. Completions are generated for the
+    /// `cargo-hakari` executable.
+    Completions {
+        /// The shell to generate completions for
+        #[clap(arg_enum)]
+        shell: Shell,
+    },
+
+    /// Generate man pages for cargo-hakari and all of its subcommands
+    ///
+    /// One page is written out per command, e.g. `cargo-hakari.1` and `cargo-hakari-generate.1`.
+    Manpages {
+        /// Directory to write man pages to (created if it doesn't exist)
+        #[clap(long, value_name = "DIR")]
+        out_dir: Utf8PathBuf,
+    },
+
     #[clap(flatten)]
     WithBuilder(CommandWithBuilder),
 }
@@ -104,16 +194,31 @@ enum Command {
 impl Command {
     fn exec(self, output: OutputOpts) -> Result<i32> {
         let output = output.init();
+        let command = match self {
+            // These commands don't operate on the workspace in the current directory.
+            Command::Workspaces { config, command } => return command.exec(&config, output),
+            Command::Completions { shell } => {
+                print_completions(shell);
+                return Ok(0);
+            }
+            Command::Manpages { out_dir } => {
+                write_manpages(&out_dir, &output)?;
+                return Ok(0);
+            }
+            command => command,
+        };
+
         let metadata_command = MetadataCommand::new();
         let package_graph = metadata_command
             .build_graph()
             .context("building package graph failed")?;
 
-        match self {
+        match command {
             Command::Initialize {
                 path,
                 package_name,
                 skip_config,
+                template_dir,
                 dry_run,
                 yes,
             } => {
@@ -131,6 +236,10 @@ impl Command {
                 let mut init = HakariInit::new(&package_graph, package_name, &workspace_path)
                     .with_context(|| "error initializing Hakari package")?;
                 init.set_cargo_toml_comment(CARGO_TOML_COMMENT);
+                if let Some(template_dir) = &template_dir {
+                    init.set_template_dir(template_dir)
+                        .with_context(|| "error initializing Hakari package")?;
+                }
                 if !skip_config {
                     init.set_config(DEFAULT_CONFIG_PATH.as_ref(), CONFIG_COMMENT)
                         .with_context(|| "error initializing Hakari package")?;
@@ -156,10 +265,96 @@ impl Command {
                     Ok(())
                 })
             }
+            Command::Check => check(&package_graph, &output),
+            Command::Bump { since, dry_run } => {
+                let (config_path, config) = read_config(&package_graph)?;
+                let builder = config
+                    .builder
+                    .to_hakari_builder(&package_graph)
+                    .wrap_err_with(|| {
+                        format!("error resolving Hakari config at {}", config_path)
+                    })?;
+                bump_hakari(
+                    &builder,
+                    &config.bump,
+                    config.output.exact_versions(),
+                    since.as_deref(),
+                    dry_run,
+                    output,
+                )
+            }
+            Command::WithBuilder(CommandWithBuilder::Generate { watch: true, .. }) => {
+                watch_generate(package_graph, output)
+            }
             Command::WithBuilder(cmd) => {
-                let (builder, hakari_output) = make_builder_and_output(&package_graph)?;
-                cmd.exec(builder, hakari_output, output)
+                let (builder, hakari_output, filter) = make_builder_and_output(&package_graph)?;
+                cmd.exec(builder, hakari_output, &filter, output)
+            }
+            Command::Workspaces { .. } | Command::Completions { .. } | Command::Manpages { .. } => {
+                unreachable!("handled above")
+            }
+        }
+    }
+}
+
+/// Commands that can be run across several workspaces.
+#[derive(Debug, Parser)]
+enum WorkspacesCommand {
+    /// Generate or update the contents of the workspace-hack crate in each workspace
+    Generate {
+        /// Print a diff of contents instead of writing them out. Can be combined with `--quiet`.
+        ///
+        /// Exits with status 1 if the contents are different in any workspace.
+        #[clap(long)]
+        diff: bool,
+    },
+
+    /// Perform verification of the workspace-hack crate in each workspace
+    ///
+    /// Exits with status 1 if verification failed in any workspace.
+    Verify,
+
+    /// Manage dependencies from workspace crates to workspace-hack in each workspace
+    ManageDeps {
+        /// Print operations that need to be performed, but do not actually perform them.
+        ///
+        /// Exits with status 1 if any operations need to be performed in any workspace. Can be
+        /// combined with `--quiet`.
+        #[clap(long, short = 'n', conflicts_with = "yes")]
+        dry_run: bool,
+
+        /// Proceed with the operation without prompting for confirmation.
+        #[clap(long, short, conflicts_with = "dry-run")]
+        yes: bool,
+    },
+}
+
+impl WorkspacesCommand {
+    fn exec(self, config_path: &Utf8Path, output: OutputContext) -> Result<i32> {
+        let workspace_roots = WorkspacesConfig::read_workspace_roots(config_path)?;
+        exec_workspaces(&workspace_roots, &output, |workspace_root| {
+            let mut metadata_command = MetadataCommand::new();
+            metadata_command.current_dir(workspace_root);
+            let package_graph = metadata_command
+                .build_graph()
+                .with_context(|| format!("building package graph at {} failed", workspace_root))?;
+            let (builder, hakari_output, filter) = make_builder_and_output(&package_graph)?;
+            self.to_command_with_builder()
+                .exec(builder, hakari_output, &filter, output.clone())
+        })
+    }
+
+    fn to_command_with_builder(&self) -> CommandWithBuilder {
+        match *self {
+            WorkspacesCommand::Generate { diff } => {
+                CommandWithBuilder::Generate { diff, watch: false }
             }
+            WorkspacesCommand::Verify => CommandWithBuilder::Verify,
+            WorkspacesCommand::ManageDeps { dry_run, yes } => CommandWithBuilder::ManageDeps {
+                packages: PackageSelection { packages: vec![] },
+                dry_run,
+                yes,
+            },
         }
     }
 }
@@ -173,6 +368,15 @@ enum CommandWithBuilder {
         /// Exits with status 1 if the contents are different.
         #[clap(long)]
         diff: bool,
+
+        /// Keep running, and update the workspace-hack whenever manifests change.
+        ///
+        /// Watches `Cargo.toml` and `Cargo.lock` files in the workspace, along with the hakari
+        /// config. On every change, regenerates the contents of the workspace-hack crate and adds
+        /// or removes workspace-hack dependencies as `manage-deps --yes` would, printing out
+        /// changes as they're applied.
+        #[clap(long, conflicts_with = "diff")]
+        watch: bool,
     },
 
     /// Perform verification of the workspace-hack crate
@@ -180,13 +384,17 @@ enum CommandWithBuilder {
     /// Check that the workspace-hack crate succeeds at its goal of building one version of
     /// every non-omitted third-party crate.
     ///
-    /// Exits with status 1 if verification failed.
+    /// If verification fails, changes that would fix it are suggested where possible. Exits with
+    /// status 1 if verification failed.
     Verify,
 
     /// Manage dependencies from workspace crates to workspace-hack.
     ///
     /// * Add the dependency to all non-excluded workspace crates.
     /// * Remove the dependency from all excluded workspace crates.
+    ///
+    /// By default, only operates on the workspace crates selected by the [manage-deps] section of
+    /// the config.
     ManageDeps {
         #[clap(flatten)]
         packages: PackageSelection,
@@ -204,6 +412,9 @@ enum CommandWithBuilder {
     },
 
     /// Remove dependencies from workspace crates to workspace-hack.
+    ///
+    /// By default, only operates on the workspace crates selected by the [manage-deps] section of
+    /// the config.
     RemoveDeps {
         #[clap(flatten)]
         packages: PackageSelection,
@@ -226,7 +437,8 @@ enum CommandWithBuilder {
     /// two different feature sets by different crates in the workspace (unless the
     /// output-single-feature option is set to true). The explain command prints out a table
     /// consisting of the different feature sets that got built; and, for each feature set, the
-    /// workspace crates and options that resulted in it.
+    /// workspace crates and options that resulted in it. Each feature set is followed by feature
+    /// chains, showing how each of its features is enabled starting from a workspace crate.
     ///
     /// Adding the initial set of dependencies to the workspace-hack can cause further dependencies
     /// to be added if they're built with a second feature set. These cases are marked as
@@ -236,9 +448,44 @@ enum CommandWithBuilder {
     /// dependency, and the workspace crates responsible for them. Further investigation can be done
     /// through `cargo tree`. In the future, the scope of this command may be extended to provide
     /// information about intermediate dependencies as well.
+    ///
+    /// With `--all`, a report of every dependency in the workspace-hack is printed instead,
+    /// grouped by the workspace crates that build them. This is useful for periodic audits of the
+    /// workspace-hack.
     Explain {
         /// The name of the dependency, as present in the workspace-hack.
-        dep_name: String,
+        #[clap(required_unless_present = "all")]
+        dep_name: Option<String>,
+
+        /// Print a report of every dependency in the workspace-hack
+        #[clap(long, conflicts_with = "dep-name")]
+        all: bool,
+
+        /// With --all, the order to print workspace crates and dependencies in [default: name]
+        ///
+        /// `cost` sorts by estimated build cost, highest first. The cost of a dependency is
+        /// estimated as the number of features enabled for it multiplied by the number of
+        /// packages it pulls in.
+        #[clap(long, arg_enum, requires = "all", conflicts_with = "dep-name")]
+        sort_by: Option<ReportSortBy>,
+    },
+
+    /// Print metrics about the impact of the workspace-hack.
+    ///
+    /// Prints the number of dependencies unified by the workspace-hack, the number of features
+    /// added to them beyond their minimal builds, and an estimate of the number of duplicate
+    /// dependency builds avoided.
+    ///
+    /// Metrics can be written out as JSON with `--json`, and compared against later with
+    /// `--compare`, to track the health of the workspace-hack over time.
+    Stats {
+        /// Print metrics as JSON to standard output
+        #[clap(long)]
+        json: bool,
+
+        /// Also print changes since metrics written out by an earlier `stats --json`
+        #[clap(long, value_name = "PATH", conflicts_with = "json")]
+        compare: Option<Utf8PathBuf>,
     },
 
     /// Publish a package after temporarily removing the workspace-hack dependency from it.
@@ -258,6 +505,23 @@ enum CommandWithBuilder {
         pass_through: Vec<String>,
     },
 
+    /// Show how the workspace-hack crate differs from a published version.
+    ///
+    /// With `--published`, compares the dependencies in the workspace-hack's Cargo.toml against
+    /// the latest version of the crate published to a registry. The published crate is downloaded
+    /// through Cargo.
+    ///
+    /// Exits with status 1 if the dependencies are different.
+    Diff {
+        /// Compare against the latest version published to the registry (currently required)
+        #[clap(long)]
+        published: bool,
+
+        /// The registry to fetch the published version from (default: crates.io)
+        #[clap(long)]
+        registry: Option<String>,
+    },
+
     /// Disables the workspace-hack crate.
     ///
     /// Removes all the generated contents from the workspace-hack crate.
@@ -275,45 +539,29 @@ impl CommandWithBuilder {
         self,
         builder: HakariBuilder<'_>,
         hakari_output: HakariOutputOptions,
+        filter: &ManageDepsFilter,
         output: OutputContext,
     ) -> Result<i32> {
         let hakari_package = *builder
             .hakari_package()
             .expect("hakari-package must be specified in hakari.toml");
+        let workspace_root = builder.graph().workspace().root();
 
         match self {
-            CommandWithBuilder::Generate { diff } => {
-                let package_graph = builder.graph();
+            CommandWithBuilder::Generate { diff, watch } => {
+                debug_assert!(!watch, "--watch is handled by Command::exec");
                 let hakari = builder.compute();
-                let toml_out = match hakari.to_toml_string(&hakari_output) {
-                    Ok(toml_out) => toml_out,
-                    Err(TomlOutError::UnrecognizedRegistry {
-                        package_id,
-                        registry_url,
-                    }) => {
-                        // Print out a better error message for this more common use case.
-                        let package = package_graph
-                            .metadata(&package_id)
-                            .expect("package ID obtained from the same graph");
-                        error!(
-                            "unrecognized registry URL {} found for {} v{}\n\
-                             (add to [registries] section of {})",
-                            registry_url.style(output.styles.registry_url),
-                            package.name().style(output.styles.package_name),
-                            package.version().style(output.styles.package_version),
-                            "hakari.toml".style(output.styles.config_path),
-                        );
-                        // 102 is picked pretty arbitrarily because regular errors exit with 101.
-                        return Ok(102);
-                    }
-                    Err(err) => Err(err).with_context(|| "error generating new hakari.toml")?,
+                let toml_out = match generate_toml(&hakari, &hakari_output, &output)? {
+                    Some(toml_out) => toml_out,
+                    // 102 is picked pretty arbitrarily because regular errors exit with 101.
+                    None => return Ok(102),
                 };
 
                 let existing_toml = hakari
                     .read_toml()
                     .expect("hakari-package must be specified")?;
 
-                write_to_cargo_toml(existing_toml, &toml_out, diff, output)
+                write_to_cargo_toml(existing_toml, &toml_out, diff, workspace_root, output)
             }
             CommandWithBuilder::Verify => match builder.verify() {
                 Ok(()) => {
@@ -342,7 +590,7 @@ impl CommandWithBuilder {
                 yes,
             } => {
                 let ops = builder
-                    .manage_dep_ops(&packages.to_package_set(builder.graph())?)
+                    .manage_dep_ops(&packages.to_package_set(builder.graph(), filter)?)
                     .expect("hakari-package must be specified in hakari.toml");
                 if ops.is_empty() {
                     info!("no operations to perform");
@@ -350,7 +598,7 @@ impl CommandWithBuilder {
                 }
 
                 apply_on_dialog(dry_run, yes, &ops, &output, || {
-                    regenerate_lockfile(output.clone())
+                    regenerate_lockfile(workspace_root, output.clone())
                 })
             }
             CommandWithBuilder::RemoveDeps {
@@ -359,7 +607,7 @@ impl CommandWithBuilder {
                 yes,
             } => {
                 let ops = builder
-                    .remove_dep_ops(&packages.to_package_set(builder.graph())?, false)
+                    .remove_dep_ops(&packages.to_package_set(builder.graph(), filter)?, false)
                     .expect("hakari-package must be specified in hakari.toml");
                 if ops.is_empty() {
                     info!("no operations to perform");
@@ -367,13 +615,29 @@ impl CommandWithBuilder {
                 }
 
                 apply_on_dialog(dry_run, yes, &ops, &output, || {
-                    regenerate_lockfile(output.clone())
+                    regenerate_lockfile(workspace_root, output.clone())
                 })
             }
             CommandWithBuilder::Explain {
-                dep_name: crate_name,
+                dep_name,
+                all,
+                sort_by,
             } => {
                 let hakari = builder.compute();
+                if all {
+                    let report = hakari.report();
+                    info!(
+                        "\n{}",
+                        format_report(
+                            &report,
+                            sort_by.unwrap_or(ReportSortBy::Name).into(),
+                            &output
+                        )
+                    );
+                    return Ok(0);
+                }
+
+                let crate_name = dep_name.expect("clap ensures a dependency name without --all");
                 let toml_name_map = hakari.toml_name_map();
                 let dep = toml_name_map.get(crate_name.as_str()).ok_or_else(|| {
                     eyre!(
@@ -393,6 +657,43 @@ impl CommandWithBuilder {
                 info!("\n{}", display);
                 Ok(0)
             }
+            CommandWithBuilder::Stats { json, compare } => {
+                let stats = builder.compute().stats();
+                if json {
+                    let json = serde_json::to_string_pretty(&stats)
+                        .wrap_err("error serializing stats to JSON")?;
+                    println!("{}", json);
+                    return Ok(0);
+                }
+
+                info!(
+                    "{} unifies {} dependencies:\n\
+                    * features added beyond minimal builds: {}\n\
+                    * estimated duplicate builds avoided: {}",
+                    hakari_package.name().style(output.styles.package_name),
+                    stats.unified_count,
+                    stats.features_added_count,
+                    stats.duplicate_builds_avoided,
+                );
+
+                if let Some(compare) = compare {
+                    let old_json = std::fs::read_to_string(&compare)
+                        .wrap_err_with(|| format!("error reading {}", compare))?;
+                    let old: HakariStats = serde_json::from_str(&old_json)
+                        .wrap_err_with(|| format!("error deserializing stats from {}", compare))?;
+                    let diff = stats.diff(&old);
+                    if diff.is_unchanged() {
+                        info!("no changes since {}", compare);
+                    } else {
+                        info!(
+                            "changes since {}:\n{}",
+                            compare,
+                            format_stats_diff(&diff, &output)
+                        );
+                    }
+                }
+                Ok(0)
+            }
             CommandWithBuilder::Publish {
                 package,
                 pass_through,
@@ -400,11 +701,49 @@ impl CommandWithBuilder {
                 publish_hakari(&package, builder, &pass_through, output)?;
                 Ok(0)
             }
+            CommandWithBuilder::Diff {
+                published,
+                registry,
+            } => {
+                if !published {
+                    bail!("`cargo hakari diff` currently requires `--published`");
+                }
+                let local_contents = std::fs::read_to_string(hakari_package.manifest_path())
+                    .wrap_err_with(|| {
+                        format!("error reading {}", hakari_package.manifest_path())
+                    })?;
+
+                info!(
+                    "fetching published version of {}",
+                    hakari_package.name().style(output.styles.package_name),
+                );
+                let published =
+                    PublishedManifest::fetch(hakari_package.name(), registry.as_deref())?;
+                let diff = ManifestDiff::new(&published.contents, &local_contents)?;
+                if diff.is_empty() {
+                    info!(
+                        "no dependency differences with published {} v{}",
+                        hakari_package.name().style(output.styles.package_name),
+                        published.version.style(output.styles.package_version),
+                    );
+                    Ok(0)
+                } else {
+                    info!(
+                        "dependency differences with published {} v{}:
+
+{}",
+                        hakari_package.name().style(output.styles.package_name),
+                        published.version.style(output.styles.package_version),
+                        diff,
+                    );
+                    Ok(1)
+                }
+            }
             CommandWithBuilder::Disable { diff } => {
                 let existing_toml = builder
                     .read_toml()
                     .expect("hakari-package must be specified")?;
-                write_to_cargo_toml(existing_toml, DISABLE_MESSAGE, diff, output)
+                write_to_cargo_toml(existing_toml, DISABLE_MESSAGE, diff, workspace_root, output)
             }
         }
     }
@@ -414,21 +753,75 @@ impl CommandWithBuilder {
 #[derive(Debug, Parser)]
 struct PackageSelection {
     #[clap(long = "package", short)]
-    /// Packages to operate on (default: entire workspace)
+    /// Packages to operate on (default: workspace crates selected by [manage-deps] in the config)
     packages: Vec<String>,
 }
 
 impl PackageSelection {
     /// Converts this selection into a `PackageSet`.
-    fn to_package_set<'g>(&self, graph: &'g PackageGraph) -> Result<PackageSet<'g>> {
+    ///
+    /// The filter is only applied if no packages were explicitly specified.
+    fn to_package_set<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        filter: &ManageDepsFilter,
+    ) -> Result<PackageSet<'g>> {
         if !self.packages.is_empty() {
             Ok(graph.resolve_workspace_names(&self.packages)?)
         } else {
-            Ok(graph.resolve_workspace())
+            Ok(graph
+                .resolve_workspace()
+                .filter(DependencyDirection::Forward, |package| {
+                    filter.is_match(package)
+                }))
         }
     }
 }
 
+/// Globs from the `[manage-deps]` section of the config.
+#[derive(Clone, Debug)]
+struct ManageDepsFilter {
+    // None means that all packages are included.
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl ManageDepsFilter {
+    fn new(summary: &ManageDepsSummary) -> Result<Self> {
+        let include = if summary.include.is_empty() {
+            None
+        } else {
+            Some(Self::build_glob_set(&summary.include)?)
+        };
+        let exclude = Self::build_glob_set(&summary.exclude)?;
+        Ok(Self { include, exclude })
+    }
+
+    /// Returns true if this workspace package is selected by the filter.
+    fn is_match(&self, package: PackageMetadata<'_>) -> bool {
+        let workspace_path = package
+            .source()
+            .workspace_path()
+            .expect("package is in workspace");
+        self.is_match_impl(package.name(), workspace_path)
+    }
+
+    fn is_match_impl(&self, name: &str, workspace_path: &Utf8Path) -> bool {
+        let matches =
+            |glob_set: &GlobSet| glob_set.is_match(name) || glob_set.is_match(workspace_path);
+        self.include.as_ref().map_or(true, matches) && !matches(&self.exclude)
+    }
+
+    fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            let glob = Glob::new(glob).wrap_err_with(|| format!("invalid glob '{}'", glob))?;
+            builder.add(glob);
+        }
+        Ok(builder.build()?)
+    }
+}
+
 // ---
 // Helper methods
 // ---
@@ -454,32 +847,447 @@ fn cwd_rel_to_workspace_rel(path: &Utf8Path, workspace_root: &Utf8Path) -> Resul
         })
 }
 
-fn make_builder_and_output(
-    package_graph: &PackageGraph,
-) -> Result<(HakariBuilder<'_>, HakariOutputOptions)> {
+fn read_config(package_graph: &PackageGraph) -> Result<(Utf8PathBuf, HakariConfig)> {
     let (config_path, contents) = read_contents(
         package_graph.workspace().root(),
         [DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH],
     )
     .wrap_err("error reading Hakari config")?;
 
-    let config: HakariConfig = contents
+    let mut config: HakariConfig = contents
         .parse()
         .wrap_err_with(|| format!("error deserializing Hakari config at {}", config_path))?;
+    config
+        .builder
+        .add_discovered_platforms(package_graph.workspace().root())
+        .wrap_err_with(|| {
+            format!(
+                "error discovering platforms for Hakari config at {}",
+                config_path
+            )
+        })?;
+    Ok((config_path, config))
+}
+
+fn make_builder_and_output(
+    package_graph: &PackageGraph,
+) -> Result<(HakariBuilder<'_>, HakariOutputOptions, ManageDepsFilter)> {
+    let (config_path, config) = read_config(package_graph)?;
 
     let builder = config
         .builder
         .to_hakari_builder(package_graph)
         .wrap_err_with(|| format!("error resolving Hakari config at {}", config_path))?;
     let hakari_output = config.output.to_options();
+    let filter = ManageDepsFilter::new(&config.manage_deps).wrap_err_with(|| {
+        format!(
+            "error resolving [manage-deps] section of Hakari config at {}",
+            config_path
+        )
+    })?;
+
+    Ok((builder, hakari_output, filter))
+}
+
+/// Generates the new contents of the workspace-hack's `Cargo.toml`.
+///
+/// Returns `None` if an unrecognized registry was found. An error message is printed out in that
+/// case.
+fn generate_toml(
+    hakari: &Hakari<'_>,
+    hakari_output: &HakariOutputOptions,
+    output: &OutputContext,
+) -> Result<Option<String>> {
+    match hakari.to_toml_string(hakari_output) {
+        Ok(toml_out) => Ok(Some(toml_out)),
+        Err(TomlOutError::UnrecognizedRegistry {
+            package_id,
+            registry_url,
+        }) => {
+            // Print out a better error message for this more common use case.
+            let package = hakari
+                .builder()
+                .graph()
+                .metadata(&package_id)
+                .expect("package ID obtained from the same graph");
+            error!(
+                "unrecognized registry URL {} found for {} v{}\n\
+                 (add to [registries] section of {})",
+                registry_url.style(output.styles.registry_url),
+                package.name().style(output.styles.package_name),
+                package.version().style(output.styles.package_version),
+                "hakari.toml".style(output.styles.config_path),
+            );
+            Ok(None)
+        }
+        Err(err) => Err(err).with_context(|| "error generating new hakari.toml"),
+    }
+}
+
+/// A check performed by `cargo hakari check`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum CheckKind {
+    Config,
+    Generate,
+    ManageDeps,
+    Verify,
+}
+
+impl CheckKind {
+    const ALL: [Self; 4] = [
+        CheckKind::Config,
+        CheckKind::Generate,
+        CheckKind::ManageDeps,
+        CheckKind::Verify,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CheckKind::Config => "config",
+            CheckKind::Generate => "generate",
+            CheckKind::ManageDeps => "manage-deps",
+            CheckKind::Verify => "verify",
+        }
+    }
 
-    Ok((builder, hakari_output))
+    /// The value added to the exit status if this check fails.
+    fn exit_status(self) -> i32 {
+        match self {
+            CheckKind::Config => 2,
+            CheckKind::Generate => 4,
+            CheckKind::ManageDeps => 8,
+            CheckKind::Verify => 16,
+        }
+    }
+
+    /// Describes how to fix a failure of this check, or how to get more information about it.
+    fn fix_hint(self, styles: &Styles) -> String {
+        let command = match self {
+            CheckKind::Config => {
+                return format!(
+                    "fix the config, or create one with {}",
+                    "cargo hakari init".style(styles.command)
+                )
+            }
+            CheckKind::Generate => "cargo hakari generate",
+            CheckKind::ManageDeps => "cargo hakari manage-deps",
+            CheckKind::Verify => "cargo hakari verify",
+        };
+        format!("run {}", command.style(styles.command))
+    }
+}
+
+/// Performs all the checks for `cargo hakari check`, and prints out a report.
+///
+/// Errors while performing a check are printed out and count as failures of that check, so that
+/// the remaining checks still run.
+fn check(package_graph: &PackageGraph, output: &OutputContext) -> Result<i32> {
+    let mut failed = vec![];
+    let mut skipped = false;
+
+    let config = make_builder_and_output(package_graph).and_then(|(builder, output, filter)| {
+        let hakari_package = *builder
+            .hakari_package()
+            .ok_or_else(|| eyre!("hakari-package must be specified in hakari.toml"))?;
+        Ok((builder, output, filter, hakari_package))
+    });
+    match config {
+        Ok((builder, hakari_output, filter, hakari_package)) => {
+            let results = [
+                (
+                    CheckKind::Generate,
+                    check_generate(&builder, &hakari_output, hakari_package, output),
+                ),
+                (
+                    CheckKind::ManageDeps,
+                    check_manage_deps(package_graph, &builder, &filter, output),
+                ),
+                (
+                    CheckKind::Verify,
+                    Ok(check_verify(builder, hakari_package, output)),
+                ),
+            ];
+            for (kind, result) in results {
+                match result {
+                    Ok(true) => {}
+                    Ok(false) => failed.push(kind),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        failed.push(kind);
+                    }
+                }
+            }
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            failed.push(CheckKind::Config);
+            skipped = true;
+        }
+    }
+
+    let mut report = String::new();
+    for kind in CheckKind::ALL {
+        let status = if failed.contains(&kind) {
+            format!("failed ({})", kind.fix_hint(&output.styles))
+        } else if skipped && kind != CheckKind::Config {
+            "skipped".to_owned()
+        } else {
+            "ok".to_owned()
+        };
+        report.push_str(&format!("  {:<12} {}\n", kind.name(), status));
+    }
+    info!("check results:\n{}", report);
+
+    Ok(failed.iter().map(|kind| kind.exit_status()).sum())
+}
+
+/// Checks that the contents of the workspace-hack crate are up-to-date. Returns true on success.
+fn check_generate(
+    builder: &HakariBuilder<'_>,
+    hakari_output: &HakariOutputOptions,
+    hakari_package: PackageMetadata<'_>,
+    output: &OutputContext,
+) -> Result<bool> {
+    let hakari = builder.clone().compute();
+    let toml_out = match generate_toml(&hakari, hakari_output, output)? {
+        Some(toml_out) => toml_out,
+        // An error message has already been printed out.
+        None => return Ok(false),
+    };
+    let existing_toml = hakari
+        .read_toml()
+        .ok_or_else(|| eyre!("hakari-package must be specified in hakari.toml"))??;
+    if existing_toml.is_changed(&toml_out) {
+        info!(
+            "contents of {} are out of date:\n{}",
+            hakari_package.name().style(output.styles.package_name),
+            format_patch(&existing_toml, &toml_out, output),
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Checks that no workspace-hack dependencies need to be added or removed. Returns true on
+/// success.
+fn check_manage_deps(
+    package_graph: &PackageGraph,
+    builder: &HakariBuilder<'_>,
+    filter: &ManageDepsFilter,
+    output: &OutputContext,
+) -> Result<bool> {
+    let packages = PackageSelection { packages: vec![] }.to_package_set(package_graph, filter)?;
+    let ops = builder
+        .manage_dep_ops(&packages)
+        .ok_or_else(|| eyre!("hakari-package must be specified in hakari.toml"))?;
+    if !ops.is_empty() {
+        let mut display = ops.display();
+        if output.color.is_enabled() {
+            display.colorize();
+        }
+        info!("operations to perform:\n\n{}", display);
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Checks that the workspace-hack crate succeeds at its goal. Returns true on success.
+fn check_verify(
+    builder: HakariBuilder<'_>,
+    hakari_package: PackageMetadata<'_>,
+    output: &OutputContext,
+) -> bool {
+    match builder.verify() {
+        Ok(()) => true,
+        Err(errs) => {
+            let mut display = errs.display();
+            if output.color.is_enabled() {
+                display.colorize();
+            }
+            info!(
+                "{} didn't work correctly:\n{}",
+                hakari_package.name().style(output.styles.package_name),
+                display,
+            );
+            false
+        }
+    }
+}
+
+/// Runs `generate` and `manage-deps` every time a manifest in the workspace changes.
+///
+/// This only returns on errors with the file watcher itself. Errors while updating the
+/// workspace-hack are printed out, and the watch continues.
+fn watch_generate(package_graph: PackageGraph, output: OutputContext) -> Result<i32> {
+    let workspace_root = package_graph.workspace().root().to_owned();
+    let watcher = WorkspaceWatcher::new(&workspace_root)?;
+
+    let mut package_graph = Some(package_graph);
+    loop {
+        if let Some(package_graph) = &package_graph {
+            if let Err(err) = generate_and_manage_deps(package_graph, &output) {
+                error!("{:?}", err);
+            }
+            // Don't react to the changes made above.
+            watcher.discard_changes();
+        }
+
+        info!(
+            "watching {} for changes (press Ctrl-C to exit)",
+            workspace_root.style(output.styles.config_path),
+        );
+        let changed = watcher.wait_for_changes()?;
+        let changed: Vec<_> = changed.iter().map(|path| path.as_str()).collect();
+        info!("detected changes to {}", changed.join(", "));
+
+        let mut metadata_command = MetadataCommand::new();
+        metadata_command.current_dir(&workspace_root);
+        package_graph = match metadata_command.build_graph() {
+            Ok(package_graph) => Some(package_graph),
+            Err(err) => {
+                // This is usually caused by a manifest that's in the middle of being edited.
+                error!("building package graph failed: {}", err);
+                None
+            }
+        };
+    }
+}
+
+fn generate_and_manage_deps(package_graph: &PackageGraph, output: &OutputContext) -> Result<()> {
+    let (builder, hakari_output, filter) = make_builder_and_output(package_graph)?;
+    let workspace_root = package_graph.workspace().root();
+
+    let hakari = builder.compute();
+    if let Some(toml_out) = generate_toml(&hakari, &hakari_output, output)? {
+        let existing_toml = hakari
+            .read_toml()
+            .expect("hakari-package must be specified")?;
+        if existing_toml.is_changed(&toml_out) {
+            info!(
+                "updating contents:\n{}",
+                format_patch(&existing_toml, &toml_out, output)
+            );
+            existing_toml
+                .write_to_file(&toml_out)
+                .with_context(|| "error writing updated Hakari contents")?;
+            regenerate_lockfile(workspace_root, output.clone())?;
+        } else {
+            info!("no changes detected");
+        }
+    }
+
+    let packages = PackageSelection { packages: vec![] }.to_package_set(package_graph, &filter)?;
+    let ops = hakari
+        .builder()
+        .manage_dep_ops(&packages)
+        .expect("hakari-package must be specified in hakari.toml");
+    if !ops.is_empty() {
+        let mut display = ops.display();
+        if output.color.is_enabled() {
+            display.colorize();
+        }
+        info!("applying operations:\n\n{}", display);
+        ops.apply()?;
+        regenerate_lockfile(workspace_root, output.clone())?;
+    }
+
+    Ok(())
+}
+
+/// The order in which `explain --all` prints its report.
+#[derive(ArgEnum, Copy, Clone, Debug)]
+enum ReportSortBy {
+    Name,
+    Cost,
+}
+
+impl From<ReportSortBy> for ReportOrder {
+    fn from(sort_by: ReportSortBy) -> Self {
+        match sort_by {
+            ReportSortBy::Name => ReportOrder::Name,
+            ReportSortBy::Cost => ReportOrder::Cost,
+        }
+    }
+}
+
+fn format_report(report: &HakariReport<'_>, order: ReportOrder, output: &OutputContext) -> String {
+    let mut lines = vec![];
+    for group in report.by_workspace_package(order) {
+        lines.push(format!(
+            "{} builds {} dependencies (estimated cost {}):",
+            group.package.name().style(output.styles.package_name),
+            group.dependencies.len(),
+            group.estimated_cost(),
+        ));
+        for dep in &group.dependencies {
+            let metadata = dep.metadata();
+            let features = if dep.features().len() == 0 {
+                "no features".to_owned()
+            } else {
+                format!(
+                    "features: {}",
+                    dep.features().collect::<Vec<_>>().join(", ")
+                )
+            };
+            lines.push(format!(
+                "  {} v{}, {} (estimated cost {})",
+                metadata.name().style(output.styles.package_name),
+                metadata.version().style(output.styles.package_version),
+                features,
+                dep.estimated_cost(),
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+fn format_stats_diff(diff: &HakariStatsDiff<'_>, output: &OutputContext) -> String {
+    let mut lines = vec![
+        format!("* unified dependencies: {:+}", diff.unified_count),
+        format!(
+            "* features added beyond minimal builds: {:+}",
+            diff.features_added_count
+        ),
+        format!(
+            "* estimated duplicate builds avoided: {:+}",
+            diff.duplicate_builds_avoided
+        ),
+    ];
+    for (prefix, deps) in [
+        ("newly unified", &diff.added),
+        ("no longer unified", &diff.removed),
+    ] {
+        for (name, version) in deps {
+            lines.push(format!(
+                "* {}: {} v{}",
+                prefix,
+                name.style(output.styles.package_name),
+                version.style(output.styles.package_version),
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+fn format_patch(
+    existing_toml: &HakariCargoToml,
+    new_contents: &str,
+    output: &OutputContext,
+) -> String {
+    let patch = existing_toml.diff_toml(new_contents);
+    let mut formatter = PatchFormatter::new();
+    if output.color.is_enabled() {
+        formatter = formatter.with_color();
+    }
+    let patch_str = formatter.fmt_patch(&patch).to_string();
+    patch_str
 }
 
 fn write_to_cargo_toml(
     existing_toml: HakariCargoToml,
     new_contents: &str,
     diff: bool,
+    workspace_root: &Utf8Path,
     output: OutputContext,
 ) -> Result<i32> {
     if diff {
@@ -503,7 +1311,7 @@ fn write_to_cargo_toml(
                 .write_to_file(new_contents)
                 .with_context(|| "error writing updated Hakari contents")?;
             info!("contents updated");
-            regenerate_lockfile(output)?;
+            regenerate_lockfile(workspace_root, output)?;
         }
         Ok(0)
     }
@@ -552,3 +1360,35 @@ fn apply_on_dialog(
         Ok(1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manage_deps_filter() {
+        let summary: ManageDepsSummary = toml::from_str(
+            r#"
+            include = ["crates/**"]
+            exclude = ["*-bench", "crates/fuzz/**"]
+            "#,
+        )
+        .expect("summary parsed");
+        let filter = ManageDepsFilter::new(&summary).expect("globs are valid");
+
+        assert!(filter.is_match_impl("foo", "crates/foo".as_ref()));
+        assert!(!filter.is_match_impl("foo-bench", "crates/foo-bench".as_ref()));
+        assert!(!filter.is_match_impl("foo-fuzz", "crates/fuzz/foo".as_ref()));
+        assert!(!filter.is_match_impl("bar", "tools/bar".as_ref()));
+        // Names are matched as well as paths.
+        assert!(filter.is_match_impl("crates/bar", "tools/bar".as_ref()));
+
+        let filter = ManageDepsFilter::new(&ManageDepsSummary::default()).expect("no globs");
+        assert!(filter.is_match_impl("foo-bench", "benches/foo".as_ref()));
+
+        let summary: ManageDepsSummary =
+            toml::from_str(r#"include = ["{"]"#).expect("summary parsed");
+        let err = ManageDepsFilter::new(&summary).expect_err("invalid glob");
+        assert_eq!(err.to_string(), "invalid glob '{'");
+    }
+}
@@ -38,6 +38,9 @@
 //! * *"1"*: `workspace-hack = { path = ...}`. (Note the lack of a trailing space.)
 //! * *"2"*: `workspace-hack = { version = "0.1", path = ... }`. This is required for the advanced
 //!   setup documented in the [Publishing](crate::publishing) section.
+//! * *"3"*: `workspace-hack = { version = "0.1", path = ..., registry = ... }`. The same as "2",
+//!   but also adds the registry the workspace-hack is published to, if any. See
+//!   [hakari-registry](#hakari-registry) below.
 //!
 //! Defaults to "1", but starting `cargo hakari 0.9.8`, `.config/hakari.toml` files created by
 //! `cargo hakari init` set it to "2".
@@ -46,6 +49,24 @@
 //! dep-format-version = "2"
 //! ```
 //!
+//! ## hakari-registry
+//!
+//! The name of the alternate registry the workspace-hack crate is published to. With
+//! `dep-format-version = "3"`, this is added as the `registry` key of `workspace-hack = ...` lines.
+//! The registry should also be listed in the [registries](#registries) section, so that
+//! `cargo hakari manage-deps` can tell whether existing lines are up to date.
+//!
+//! If not specified, and the workspace-hack's `package.publish` field lists exactly one registry
+//! other than `crates-io`, that registry is used.
+//!
+//! ```toml
+//! dep-format-version = "3"
+//! hakari-registry = "my-registry"
+//!
+//! [registries]
+//! my-registry = { index = "https://my-intranet:8080/git/index" }
+//! ```
+//!
 //! ## platforms
 //!
 //! Platforms to run specific queries on.
@@ -66,6 +87,31 @@
 //! ]
 //! ```
 //!
+//! ## discover-platforms
+//!
+//! Sources to discover more platforms from, so that the platforms `cargo hakari` runs queries on
+//! match the targets the workspace is actually built for. Discovered platforms are added to the
+//! ones in [platforms](#platforms).
+//!
+//! * `cargo-config`: read `build.target` from `.cargo/config.toml` (or `.cargo/config`) at the
+//!   root of the workspace. Configuration in parent directories or in `CARGO_HOME` isn't read,
+//!   and custom targets specified as paths to `.json` files are skipped.
+//! * `rust-toolchain`: read `toolchain.targets` from `rust-toolchain.toml` (or `rust-toolchain`)
+//!   at the root of the workspace. A `rust-toolchain` file with just a channel name on one line
+//!   is in the legacy format and has no targets.
+//! * `ci-globs`: globs for CI configuration files, relative to the root of the workspace. Every
+//!   target triple built into `rustc` that's mentioned in a matching file is discovered.
+//!
+//! Platforms are discovered every time `cargo hakari` reads its configuration, so there's no
+//! list to keep in sync by hand.
+//!
+//! ```toml
+//! [discover-platforms]
+//! cargo-config = true
+//! rust-toolchain = true
+//! ci-globs = [".github/workflows/*.yml"]
+//! ```
+//!
 //! ## traversal-excludes
 //!
 //! Crates to exclude while traversing the dependency graph.
@@ -129,6 +175,47 @@
 //! ]
 //! ```
 //!
+//! ### Platform-specific final excludes
+//!
+//! Some crates only break builds on some platforms when their features are unified. These can be
+//! excluded on just those platforms by specifying them under `final-excludes.platform`, keyed by a
+//! target triple or a `cfg()` expression.
+//!
+//! These crates are removed from the sections for [`platforms`](#platforms) that the expression
+//! matches. They are also moved out of the shared `[dependencies]` sections and into the sections
+//! for the remaining platforms, so they aren't unified at all on platforms not listed in
+//! `platforms`.
+//!
+//! With platform-specific excludes, crates excluded on all platforms are specified in
+//! `final-excludes.packages` instead.
+//!
+//! ```toml
+//! platforms = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"]
+//!
+//! [final-excludes.packages]
+//! workspace-members = ["my-crate"]
+//!
+//! [final-excludes.platform.'cfg(windows)']
+//! third-party = [{ name = "openssl-sys" }]
+//! ```
+//!
+//! ## unify-only
+//!
+//! Names of crates to restrict the final output to.
+//!
+//! If specified, only crates with these names will be included in the output. All other crates
+//! are still considered while computing feature sets, but are removed at the very end. All
+//! versions of a crate with a given name are included.
+//!
+//! This is generally useful for unifying a few heavy dependencies, such as proc-macro
+//! infrastructure, without adding every dependency to the workspace-hack crate.
+//!
+//! Defaults to an empty list, which means that all crates are included.
+//!
+//! ```toml
+//! unify-only = ["syn", "proc-macro2", "quote"]
+//! ```
+//!
 //! ## registries
 //!
 //! Alternate registries,
@@ -145,6 +232,42 @@
 //! my-registry = { index = "https://my-intranet:8080/git/index" }
 //! ```
 //!
+//! ## manage-deps
+//!
+//! Workspace crates that `cargo hakari manage-deps` and `cargo hakari remove-deps` operate on.
+//!
+//! `include` and `exclude` are lists of globs, matched against both crate names and paths
+//! relative to the workspace root. A crate is operated on if it matches `include` (or if
+//! `include` is empty), and doesn't match `exclude`. Crates that aren't operated on are left
+//! untouched: the workspace-hack dependency is neither added to nor removed from them.
+//!
+//! Crates passed in explicitly with `--package` are always operated on.
+//!
+//! This is generally useful for crates that shouldn't depend on the workspace-hack crate, such as
+//! benchmarks and fuzz targets.
+//!
+//! Defaults to empty lists, which means that all workspace crates are operated on.
+//!
+//! ```toml
+//! [manage-deps]
+//! exclude = ["*-bench", "fuzz/**"]
+//! ```
+//!
+//! ## bump
+//!
+//! Options for `cargo hakari bump`, for workspaces that publish the workspace-hack crate. See
+//! [Publishing](crate::publishing#publishing-the-full-workspace-hack-crate) for more.
+//!
+//! * `level`: the part of the version to increment, `"patch"` or `"minor"`. Defaults to `"patch"`.
+//! * `tag-format`: the format of release tags, with `{name}` and `{version}` replaced by the
+//!   name and version of the workspace-hack crate. Defaults to `"{name}-{version}"`.
+//!
+//! ```toml
+//! [bump]
+//! level = "minor"
+//! tag-format = "{name}-v{version}"
+//! ```
+//!
 //! # Output options
 //!
 //! ## exact-versions
@@ -162,6 +285,54 @@
 //! exact-versions = true
 //! ```
 //!
+//! ## version-format
+//!
+//! The format of version requirements for registry dependencies. One of:
+//! * `"caret"`: a semver range such as `"1"` or `"0.4"`, or the full version if `exact-versions`
+//!   is turned on.
+//! * `"exact"`: an exact pin to the version in `Cargo.lock`, such as `"=1.0.130"`.
+//! * `"exact-with-build-metadata"`: an exact pin that also includes build metadata, such as
+//!   `"=0.4.0+zstd.1.5.2"`.
+//!
+//! Exact pins make the workspace-hack crate's `Cargo.toml` fully determined by `Cargo.lock`, so
+//! this is best used with a checked-in `Cargo.lock` file.
+//!
+//! Defaults to `"caret"`.
+//!
+//! ```toml
+//! version-format = "exact"
+//! ```
+//!
+//! ## registry-annotations
+//!
+//! With `registry-annotations` turned on, each registry dependency is followed by a comment naming
+//! the registry it was resolved from: `crates-io`, or the name of an alternate registry listed in
+//! `[registries]`.
+//!
+//! Defaults to false.
+//!
+//! ```toml
+//! registry-annotations = true
+//! ```
+//!
+//! ## per-platform
+//!
+//! By default, dependencies that are unified on every platform are output in a shared
+//! `[dependencies]` section, which Cargo applies to all platforms -- including ones not listed in
+//! `platforms`. With `per-platform` turned on, every dependency is instead output in a
+//! `[target.<triple>.dependencies]` section for each platform it is unified on. Builds on
+//! platforms not listed in `platforms` then don't pull in any dependencies through the
+//! workspace-hack crate.
+//!
+//! This has no effect if `platforms` is empty.
+//!
+//! Defaults to false.
+//!
+//! ```toml
+//! platforms = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"]
+//! per-platform = true
+//! ```
+//!
 //! # Advanced options
 //!
 //! ## unify-target-host
@@ -181,6 +352,28 @@
 //! unify-target-host = "replicate-target-on-host"
 //! ```
 //!
+//! ## patched-deps
+//!
+//! Controls how dependencies replaced through `[patch]` sections in the workspace `Cargo.toml`
+//! are output. Workspace packages declare these dependencies as coming from a registry, but Cargo
+//! builds them from the Git repository or path they're patched to.
+//!
+//! Possible values:
+//! * *"source"*: output dependency lines that point at the patched source, such as
+//!   `my-dep = { git = "https://github.com/example/my-dep", ... }` or
+//!   `my-dep = { path = "../my-dep", ... }`.
+//! * *"warn"*: leave patched dependencies out of the workspace-hack, and list them in a comment at
+//!   the end of the generated section instead. This is useful if the workspace-hack crate is
+//!   published, since published crates can't have Git or path dependencies.
+//!
+//! For more, see the documentation for [`PatchedDeps`](hakari::PatchedDeps).
+//!
+//! Defaults to `"source"`.
+//!
+//! ```toml
+//! patched-deps = "warn"
+//! ```
+//!
 //! ## output-single-feature
 //!
 //! By default, `cargo hakari` only outputs lines corresponding to third-party dependencies which
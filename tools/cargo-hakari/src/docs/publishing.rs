@@ -68,6 +68,10 @@
 //! dep-format-version = "2"
 //! ```
 //!
+//! If the workspace-hack is published to an alternate registry, use `dep-format-version = "3"`
+//! instead, which also adds the `registry` field. For more, see
+//! [hakari-registry](crate::config#hakari-registry).
+//!
 //! Then run `cargo hakari manage-deps` to update the `workspace-hack = ...` lines.
 //!
 //! ---
@@ -104,3 +108,35 @@
 //!
 //! Run `cargo hakari generate` to restore the workspace-hack's contents. You can also use your
 //! source control system's commands to do so, such as with `git restore`.
+//!
+//! ---
+//!
+//! To check how the local workspace-hack differs from the version published to the registry, run
+//! `cargo hakari diff --published`. For registries other than `crates.io`, use the `--registry`
+//! flag. The published version should be the stub crate, so every dependency in the local
+//! workspace-hack will usually show up as added.
+//!
+//! # Publishing the full workspace-hack crate
+//!
+//! Some workspaces publish the workspace-hack crate with its full contents, releasing a new
+//! version whenever they change. For such workspaces, `cargo hakari bump` looks up the git tag for
+//! the workspace-hack's current version (by default `<name>-<version>`), and compares the
+//! dependencies at that tag against the local ones. If they're different, it increments the
+//! version of the workspace-hack, and updates the `version` field in `my-workspace-hack = ...`
+//! lines in other `Cargo.toml` files as needed.
+//!
+//! If the tag for the current version doesn't exist, the version is assumed to be unreleased and
+//! isn't bumped again. To compare against a different revision, use `--since`.
+//!
+//! The part of the version to increment and the format of tags can be set in the `[bump]`
+//! section of `.config/hakari.toml`:
+//!
+//! ```toml
+//! [bump]
+//! # "patch" (default) or "minor"
+//! level = "minor"
+//! tag-format = "my-workspace-hack-v{version}"
+//! ```
+//!
+//! With `exact-versions = true`, the `version` field in other `Cargo.toml` files is always
+//! updated to the new version. Otherwise, it's only updated if it no longer matches.
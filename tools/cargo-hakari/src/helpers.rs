@@ -35,13 +35,14 @@ pub(crate) fn read_contents(
     bail!("none of these paths were found:\n{}", paths_tried_str)
 }
 
-/// Regenerate the lockfile after dependency updates.
-pub(crate) fn regenerate_lockfile(output: OutputContext) -> Result<()> {
+/// Regenerate the lockfile for the workspace at the given root after dependency updates.
+pub(crate) fn regenerate_lockfile(workspace_root: &Utf8Path, output: OutputContext) -> Result<()> {
     // This seems to be the cheapest way to update the lockfile.
     // cargo update -p <hakari-package> can sometimes cause unnecessary index updates.
     let cargo_cli = CargoCli::new("tree", output);
     cargo_cli
         .to_expression()
+        .dir(workspace_root)
         .stdout_null()
         .run()
         .wrap_err("updating Cargo.lock failed")?;
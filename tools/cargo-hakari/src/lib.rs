@@ -65,6 +65,9 @@
 //! <img src="https://user-images.githubusercontent.com/180618/135726175-dc00dd0c-68a1-455f-a13d-0dd24f545ca6.png">
 //! </p>
 //!
+//! To start from your own `Cargo.toml` and other files instead of the built-in ones, pass in
+//! `--template-dir`. See `cargo hakari init --help` for more.
+//!
 //! ### 3. Generate the `Cargo.toml`
 //!
 //! Generate or update the contents of a workspace-hack crate:
@@ -85,6 +88,9 @@
 //! <img src="https://user-images.githubusercontent.com/180618/135725773-c71fc4cd-8b7d-4a8e-b97c-d84a2b3b3662.png">
 //! </p>
 //!
+//! During large dependency refactors, `cargo hakari generate --watch` keeps running and performs
+//! both of these steps whenever a `Cargo.toml` or `Cargo.lock` in the workspace changes.
+//!
 //! ## Making hakari work well
 //!
 //! These are things that are not absolutely necessary to do, but will make `cargo hakari` work
@@ -113,6 +119,17 @@
 //! If either of these commands exits with a non-zero status, you can choose to fail CI or produce
 //! a warning message.
 //!
+//! Alternatively, run all of these checks, along with `cargo hakari verify`, in one go:
+//!
+//! ```sh
+//! cargo hakari check
+//! ```
+//!
+//! `cargo hakari check` prints out a report at the end, and its exit status indicates which
+//! checks failed: 2 if the config is invalid, plus 4 if the workspace-hack `Cargo.toml` is out of
+//! date, plus 8 if workspace crates need dependencies added or removed, plus 16 if verification
+//! failed.
+//!
 //! For an example, see [this GitHub action used by
 //! `cargo-guppy`](https://github.com/facebookincubator/cargo-guppy/blob/main/.github/workflows/hakari.yml).
 //!
@@ -135,6 +152,15 @@
 //! <img src="https://user-images.githubusercontent.com/180618/144933657-c45cf719-ecaf-49e0-b2c7-c8d12adf11c0.png" width=550>
 //! </p>
 //!
+//! To audit the entire workspace-hack, print out every dependency in it grouped by the workspace
+//! crates that build it, along with the features enabled and a rough estimate of its build cost:
+//!
+//! ```sh
+//! cargo hakari explain --all
+//! ```
+//!
+//! Pass in `--sort-by cost` to list the most expensive crates and dependencies first.
+//!
 //! ### Does the workspace-hack ensure that each dependency is built with exactly one feature set?
 //!
 //! ```sh
@@ -142,11 +168,64 @@
 //! ```
 //!
 //! If some dependencies are built with more than one feature set, this command will print out
-//! details about them. **This is always a bug**---if you encounter it, [a bug
+//! details about them, along with suggested fixes such as regenerating the workspace-hack or
+//! adding a platform to `hakari.toml`. **This is always a bug**---if you encounter it, [a bug
 //! report](https://github.com/facebookincubator/cargo-guppy/issues/new) with more information would
 //! be greatly appreciated!
 //!
+//! ### How much does the workspace-hack help?
+//!
+//! Print out metrics about the impact of the workspace-hack: the number of dependencies it
+//! unifies, the number of features it adds to them beyond their minimal builds, and an estimate of
+//! the number of duplicate dependency builds it avoids:
+//!
+//! ```sh
+//! cargo hakari stats
+//! ```
+//!
+//! To track these metrics over time, write them out as JSON, and compare against them later:
+//!
+//! ```sh
+//! cargo hakari stats --json > hakari-stats.json
+//! # ... some time later
+//! cargo hakari stats --compare hakari-stats.json
+//! ```
+//!
 //! ###
+//! ## Managing several workspaces
+//!
+//! To run `generate`, `verify` or `manage-deps` across several workspaces, e.g. in a repository
+//! containing more than one Rust workspace, list the workspace roots in a TOML file:
+//!
+//! ```toml
+//! ## Paths are relative to the directory containing this file.
+//! workspaces = ["service-a", "libs/common"]
+//! ```
+//!
+//! Then run:
+//!
+//! ```sh
+//! cargo hakari workspaces --config workspaces.toml generate
+//! ```
+//!
+//! Each workspace must have its own hakari config. All workspaces are processed even if some of
+//! them fail, and a summary is printed at the end.
+//!
+//! ## Shell completions and man pages
+//!
+//! Print out a completion script for `cargo-hakari` (supported shells are `bash`, `elvish`, `fish`,
+//! `powershell` and `zsh`):
+//!
+//! ```sh
+//! cargo hakari completions bash > cargo-hakari.bash
+//! ```
+//!
+//! Write out man pages for `cargo hakari` and each of its subcommands to a directory:
+//!
+//! ```sh
+//! cargo hakari manpages --out-dir man/
+//! ```
+//!
 //! ## Publishing a crate
 //!
 //! If you publish crates to `crates.io` or other registries, see the
@@ -215,12 +294,17 @@
 //!   option, or
 //! * there is a bugfix involved.
 
+mod bump;
 mod cargo_cli;
+mod cli_docs;
 mod command;
 mod docs;
 mod helpers;
 mod output;
 mod publish;
+mod published;
+mod watch;
+mod workspaces;
 
 pub use docs::*;
 
@@ -165,7 +165,7 @@ impl<'g> TempRemoveDepInner<'g> {
         }
 
         add_ops.apply()?;
-        regenerate_lockfile(self.output)?;
+        regenerate_lockfile(self.builder.graph().workspace().root(), self.output)?;
         Ok(())
     }
 }
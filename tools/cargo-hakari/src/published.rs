@@ -0,0 +1,346 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Comparing the workspace-hack against a version published to a registry.
+
+use camino::Utf8Path;
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use guppy::MetadataCommand;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryInto,
+    fmt,
+};
+use toml::Value;
+
+/// The name of the stub package used to download published crates.
+static PROBE_NAME: &str = "cargo-hakari-published-probe";
+
+/// The `Cargo.toml` of a published version of a crate.
+#[derive(Clone, Debug)]
+pub(crate) struct PublishedManifest {
+    pub(crate) version: String,
+    pub(crate) contents: String,
+}
+
+impl PublishedManifest {
+    /// Fetches the latest published version of the given crate.
+    ///
+    /// There isn't a stable way to download a crate through Cargo, so this creates a stub package
+    /// that depends on the crate and runs `cargo metadata` on it. This makes Cargo download the
+    /// crate into its cache, with the usual registry configuration and authentication.
+    pub(crate) fn fetch(package_name: &str, registry: Option<&str>) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("cargo-hakari-")
+            .tempdir()
+            .wrap_err("error creating temporary directory")?;
+        let root: &Utf8Path = dir
+            .path()
+            .try_into()
+            .wrap_err("temporary directory is invalid UTF-8")?;
+
+        let registry_spec = match registry {
+            Some(registry) => format!(", registry = \"{}\"", registry),
+            None => String::new(),
+        };
+        // The empty [workspace] section ensures that the stub isn't treated as part of an
+        // enclosing workspace.
+        let cargo_toml = format!(
+            "[package]\n\
+             name = \"{}\"\n\
+             version = \"0.0.0\"\n\
+             edition = \"2018\"\n\
+             publish = false\n\
+             \n\
+             [dependencies]\n\
+             {} = {{ version = \"*\"{} }}\n\
+             \n\
+             [workspace]\n",
+            PROBE_NAME, package_name, registry_spec,
+        );
+        std::fs::write(root.join("Cargo.toml"), cargo_toml)
+            .wrap_err("error writing stub Cargo.toml")?;
+        std::fs::create_dir(root.join("src")).wrap_err("error creating stub src directory")?;
+        std::fs::write(root.join("src/lib.rs"), "").wrap_err("error writing stub lib.rs")?;
+
+        let mut command = MetadataCommand::new();
+        command.current_dir(root);
+        let graph = command
+            .build_graph()
+            .wrap_err_with(|| format!("error fetching published versions of {}", package_name))?;
+
+        let package = graph
+            .workspace()
+            .member_by_name(PROBE_NAME)?
+            .direct_links()
+            .map(|link| link.to())
+            .find(|package| package.name() == package_name)
+            .ok_or_else(|| eyre!("published package {} not found", package_name))?;
+        let contents = std::fs::read_to_string(package.manifest_path()).wrap_err_with(|| {
+            format!(
+                "error reading published Cargo.toml at {}",
+                package.manifest_path()
+            )
+        })?;
+
+        Ok(Self {
+            version: package.version().to_string(),
+            contents,
+        })
+    }
+}
+
+/// Dependency-level differences between two `Cargo.toml` files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct ManifestDiff {
+    // Keyed by the section and the dependency name.
+    changes: BTreeMap<(String, String), DepChange>,
+}
+
+impl ManifestDiff {
+    /// Computes the differences between the dependencies of `old` and `new`.
+    pub(crate) fn new(old: &str, new: &str) -> Result<Self> {
+        let old = parse_deps(old).wrap_err("error parsing old Cargo.toml")?;
+        let mut new = parse_deps(new).wrap_err("error parsing new Cargo.toml")?;
+
+        let mut changes = BTreeMap::new();
+        for (key, old_spec) in old {
+            match new.remove(&key) {
+                Some(new_spec) if new_spec == old_spec => {}
+                Some(new_spec) => {
+                    changes.insert(
+                        key,
+                        DepChange::Modified {
+                            old: old_spec,
+                            new: new_spec,
+                        },
+                    );
+                }
+                None => {
+                    changes.insert(key, DepChange::Removed(old_spec));
+                }
+            }
+        }
+        changes.extend(
+            new.into_iter()
+                .map(|(key, new_spec)| (key, DepChange::Added(new_spec))),
+        );
+
+        Ok(Self { changes })
+    }
+
+    /// Returns true if there are no differences.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for ManifestDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut current_section = None;
+        for ((section, name), change) in &self.changes {
+            if current_section != Some(section) {
+                writeln!(f, "[{}]", section)?;
+                current_section = Some(section);
+            }
+            match change {
+                DepChange::Added(spec) => writeln!(f, "  + {}{}", name, spec)?,
+                DepChange::Removed(spec) => writeln!(f, "  - {}{}", name, spec)?,
+                DepChange::Modified { old, new } => {
+                    writeln!(f, "  ~ {}{}", name, old)?;
+                    writeln!(f, "    -> {}{}", name, new)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum DepChange {
+    Added(DepSpec),
+    Removed(DepSpec),
+    Modified { old: DepSpec, new: DepSpec },
+}
+
+/// The parts of a dependency specification that are compared.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct DepSpec {
+    package: Option<String>,
+    version: Option<String>,
+    features: BTreeSet<String>,
+    default_features: bool,
+    optional: bool,
+}
+
+impl DepSpec {
+    fn new(value: &Value) -> Result<Self> {
+        match value {
+            Value::String(version) => Ok(Self {
+                package: None,
+                version: Some(version.clone()),
+                features: BTreeSet::new(),
+                default_features: true,
+                optional: false,
+            }),
+            Value::Table(table) => {
+                let str_field =
+                    |key: &str| table.get(key).and_then(Value::as_str).map(str::to_owned);
+                let bool_field = |keys: &[&str], default: bool| {
+                    keys.iter()
+                        .find_map(|key| table.get(*key).and_then(Value::as_bool))
+                        .unwrap_or(default)
+                };
+                let features = table
+                    .get("features")
+                    .and_then(Value::as_array)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|feature| feature.as_str().map(str::to_owned))
+                    .collect();
+                Ok(Self {
+                    package: str_field("package"),
+                    version: str_field("version"),
+                    features,
+                    default_features: bool_field(&["default-features", "default_features"], true),
+                    optional: bool_field(&["optional"], false),
+                })
+            }
+            other => Err(eyre!("invalid dependency specification: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for DepSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(package) = &self.package {
+            write!(f, " (package {})", package)?;
+        }
+        if let Some(version) = &self.version {
+            write!(f, " {}", version)?;
+        }
+        if !self.default_features {
+            write!(f, ", no default features")?;
+        }
+        if !self.features.is_empty() {
+            let features: Vec<_> = self.features.iter().map(|s| s.as_str()).collect();
+            write!(f, ", features: {}", features.join(", "))?;
+        }
+        if self.optional {
+            write!(f, ", optional")?;
+        }
+        Ok(())
+    }
+}
+
+static DEP_KINDS: &[&str] = &["dependencies", "build-dependencies", "dev-dependencies"];
+
+/// Returns all the dependencies in a `Cargo.toml`, keyed by the section and the dependency name.
+fn parse_deps(contents: &str) -> Result<BTreeMap<(String, String), DepSpec>> {
+    let manifest: Value = toml::from_str(contents)?;
+    let mut deps = BTreeMap::new();
+
+    let mut add_deps = |section: String, table: Option<&Value>| -> Result<()> {
+        let table = match table.and_then(Value::as_table) {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+        for (name, value) in table {
+            let spec = DepSpec::new(value)
+                .wrap_err_with(|| format!("in [{}], for dependency {}", section, name))?;
+            deps.insert((section.clone(), name.clone()), spec);
+        }
+        Ok(())
+    };
+
+    for kind in DEP_KINDS {
+        add_deps((*kind).to_owned(), manifest.get(*kind))?;
+    }
+    if let Some(targets) = manifest.get("target").and_then(Value::as_table) {
+        for (target, target_table) in targets {
+            for kind in DEP_KINDS {
+                let section = format!("target.{}.{}", toml_key(target), kind);
+                add_deps(section, target_table.get(*kind))?;
+            }
+        }
+    }
+
+    Ok(deps)
+}
+
+/// Quotes a TOML key if necessary.
+fn toml_key(key: &str) -> String {
+    let is_bare = key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if is_bare {
+        key.to_owned()
+    } else {
+        format!("'{}'", key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_diff() {
+        // The published Cargo.toml is normalized by Cargo, so it uses expanded tables.
+        let published = r#"
+        [package]
+        name = "my-workspace-hack"
+        version = "0.1.0"
+
+        [dependencies.serde]
+        version = "1.0.100"
+        features = ["derive"]
+
+        [dependencies.log]
+        version = "0.4"
+
+        [target."cfg(unix)".dependencies.libc]
+        version = "0.2"
+        default-features = false
+        "#;
+        let local = r#"
+        [package]
+        name = "my-workspace-hack"
+        version = "0.1.0"
+
+        ### BEGIN HAKARI SECTION
+        [dependencies]
+        serde = { version = "1.0.137", features = ["derive", "std"] }
+        log = "0.4"
+
+        [build-dependencies]
+        cc = { version = "1", default-features = false }
+
+        [target.'cfg(unix)'.dependencies]
+        libc = { version = "0.2", default-features = false }
+        ### END HAKARI SECTION
+        "#;
+
+        let diff = ManifestDiff::new(published, local).expect("manifests parsed");
+        assert_eq!(
+            diff.to_string(),
+            "[build-dependencies]\n  \
+               + cc 1, no default features\n\
+             [dependencies]\n  \
+               ~ serde 1.0.100, features: derive\n    \
+                 -> serde 1.0.137, features: derive, std\n",
+        );
+
+        let diff = ManifestDiff::new(local, local).expect("manifests parsed");
+        assert!(diff.is_empty(), "no differences with self");
+
+        let diff = ManifestDiff::new(local, published).expect("manifests parsed");
+        assert!(
+            diff.to_string().contains("  - cc 1, no default features\n"),
+            "reversed diff has removals"
+        );
+    }
+}
@@ -0,0 +1,146 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for watching a workspace for manifest changes.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{Result, WrapErr};
+use hakari::summaries::{DEFAULT_CONFIG_PATH, FALLBACK_CONFIG_PATH};
+use log::debug;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+/// How long to wait for changes to settle before reporting them.
+///
+/// Editors and `cargo` often write several files in quick succession, and it's better to react
+/// to them all at once.
+const SETTLE_TIME: Duration = Duration::from_millis(300);
+
+/// Watches a workspace for changes to `Cargo.toml`, `Cargo.lock` and hakari config files.
+pub(crate) struct WorkspaceWatcher {
+    workspace_root: Utf8PathBuf,
+    // The watcher stops sending events once it's dropped, so it needs to be kept around.
+    _watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+}
+
+impl WorkspaceWatcher {
+    /// Starts watching the workspace at this root.
+    pub(crate) fn new(workspace_root: &Utf8Path) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).wrap_err("error initializing file watcher")?;
+        watcher
+            .watch(workspace_root.as_std_path(), RecursiveMode::Recursive)
+            .wrap_err_with(|| format!("error watching {}", workspace_root))?;
+
+        Ok(Self {
+            workspace_root: workspace_root.to_owned(),
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    /// Blocks until at least one relevant file has changed, then waits for changes to settle.
+    ///
+    /// Returns the changed paths, relative to the workspace root.
+    pub(crate) fn wait_for_changes(&self) -> Result<BTreeSet<Utf8PathBuf>> {
+        let mut changed = BTreeSet::new();
+        while changed.is_empty() {
+            let event = self
+                .receiver
+                .recv()
+                .wrap_err("file watcher stopped unexpectedly")?;
+            self.record_event(event, &mut changed);
+        }
+
+        loop {
+            match self.receiver.recv_timeout(SETTLE_TIME) {
+                Ok(event) => self.record_event(event, &mut changed),
+                Err(RecvTimeoutError::Timeout) => return Ok(changed),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(RecvTimeoutError::Disconnected)
+                        .wrap_err("file watcher stopped unexpectedly")
+                }
+            }
+        }
+    }
+
+    /// Discards changes observed so far.
+    ///
+    /// This is used to ignore changes made by `cargo hakari` itself.
+    pub(crate) fn discard_changes(&self) {
+        while self.receiver.recv_timeout(SETTLE_TIME).is_ok() {}
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn record_event(&self, event: notify::Result<Event>, changed: &mut BTreeSet<Utf8PathBuf>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                // Errors are usually transient (e.g. a watched directory being removed), so just
+                // log them.
+                debug!("file watcher error: {}", err);
+                return;
+            }
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        changed.extend(
+            event
+                .paths
+                .into_iter()
+                .filter_map(|path| self.relevant_path(path)),
+        );
+    }
+
+    /// Returns the path relative to the workspace root if changes to it are relevant.
+    fn relevant_path(&self, path: PathBuf) -> Option<Utf8PathBuf> {
+        let path = Utf8PathBuf::try_from(path).ok()?;
+        let rel_path = path.strip_prefix(&self.workspace_root).ok()?;
+        is_relevant(rel_path).then(|| rel_path.to_owned())
+    }
+}
+
+fn is_relevant(rel_path: &Utf8Path) -> bool {
+    // Packaged crates in the target directory have Cargo.toml files, but they aren't part of the
+    // workspace.
+    if rel_path.starts_with("target") {
+        return false;
+    }
+    if rel_path == Utf8Path::new(DEFAULT_CONFIG_PATH)
+        || rel_path == Utf8Path::new(FALLBACK_CONFIG_PATH)
+    {
+        return true;
+    }
+    matches!(rel_path.file_name(), Some("Cargo.toml" | "Cargo.lock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relevant_paths() {
+        assert!(is_relevant("Cargo.toml".as_ref()));
+        assert!(is_relevant("Cargo.lock".as_ref()));
+        assert!(is_relevant("crates/foo/Cargo.toml".as_ref()));
+        assert!(is_relevant(DEFAULT_CONFIG_PATH.as_ref()));
+        assert!(is_relevant(FALLBACK_CONFIG_PATH.as_ref()));
+
+        assert!(!is_relevant("crates/foo/src/lib.rs".as_ref()));
+        assert!(!is_relevant(".config/nextest.toml".as_ref()));
+        assert!(!is_relevant("target/package/foo-0.1.0/Cargo.toml".as_ref()));
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Support for running commands across several workspaces.
+
+use crate::output::OutputContext;
+use camino::{Utf8Path, Utf8PathBuf};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use log::{error, info};
+use owo_colors::OwoColorize;
+use serde::Deserialize;
+
+/// Configuration for `cargo hakari workspaces`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct WorkspacesConfig {
+    /// Paths to workspace roots, relative to the directory containing the config file.
+    workspaces: Vec<String>,
+}
+
+impl WorkspacesConfig {
+    /// Reads the config at the given path, returning the absolute paths to workspace roots.
+    pub(crate) fn read_workspace_roots(config_path: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+        let contents = std::fs::read_to_string(config_path)
+            .wrap_err_with(|| format!("error reading workspaces config at {}", config_path))?;
+        let config: WorkspacesConfig = toml::from_str(&contents).wrap_err_with(|| {
+            format!("error deserializing workspaces config at {}", config_path)
+        })?;
+        if config.workspaces.is_empty() {
+            bail!("no workspaces specified in {}", config_path);
+        }
+
+        let config_dir = match config_path.parent() {
+            Some(dir) if !dir.as_str().is_empty() => dir,
+            _ => Utf8Path::new("."),
+        };
+        Ok(config.workspace_roots(config_dir))
+    }
+
+    fn workspace_roots(&self, config_dir: &Utf8Path) -> Vec<Utf8PathBuf> {
+        self.workspaces
+            .iter()
+            .map(|workspace| config_dir.join(workspace))
+            .collect()
+    }
+}
+
+/// Runs `exec_one` for each workspace root, then reports aggregated results.
+///
+/// Workspaces are processed in order, and a failure in one workspace doesn't prevent the others
+/// from being processed. Returns the highest exit status across all workspaces, or an error if any
+/// of them failed.
+pub(crate) fn exec_workspaces(
+    workspace_roots: &[Utf8PathBuf],
+    output: &OutputContext,
+    mut exec_one: impl FnMut(&Utf8Path) -> Result<i32>,
+) -> Result<i32> {
+    let mut results = Vec::with_capacity(workspace_roots.len());
+    for workspace_root in workspace_roots {
+        info!(
+            "{} {}",
+            "workspace".style(output.styles.command),
+            workspace_root.style(output.styles.config_path),
+        );
+        let result = exec_one(workspace_root);
+        if let Err(err) = &result {
+            error!("{:?}", err);
+        }
+        results.push((workspace_root, result));
+    }
+
+    let mut summary = String::new();
+    let mut exit_status = 0;
+    let mut failed = 0;
+    for (workspace_root, result) in &results {
+        let status = match result {
+            Ok(0) => "ok".to_owned(),
+            Ok(status) => {
+                exit_status = exit_status.max(*status);
+                format!("exited with status {}", status)
+            }
+            Err(_) => {
+                failed += 1;
+                "failed".to_owned()
+            }
+        };
+        summary.push_str(&format!(
+            "  {}: {}\n",
+            workspace_root.style(output.styles.config_path),
+            status
+        ));
+    }
+    info!("summary for {} workspaces:\n{}", results.len(), summary);
+
+    if failed > 0 {
+        bail!("{} of {} workspaces failed", failed, results.len());
+    }
+    Ok(exit_status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_workspaces_config() {
+        let config: WorkspacesConfig = toml::from_str(
+            r#"
+            workspaces = ["service-a", "libs/common"]
+            "#,
+        )
+        .expect("config parsed");
+        assert_eq!(
+            config.workspace_roots("/repo".as_ref()),
+            vec![
+                Utf8PathBuf::from("/repo/service-a"),
+                Utf8PathBuf::from("/repo/libs/common")
+            ],
+        );
+
+        toml::from_str::<WorkspacesConfig>("workspace = []")
+            .expect_err("unknown fields are rejected");
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! End-to-end tests that run the `cargo hakari` binary against copies of fixture workspaces.
+
+use fixtures::temp_workspace::TempWorkspace;
+use std::process::Command;
+use toml::Value;
+
+static HAKARI_PACKAGE: &str = "my-workspace-hack";
+static MEMBERS: &[&str] = &["internal", "internal-macro", "main", "side"];
+
+fn hakari<'a>(workspace: &TempWorkspace, args: impl IntoIterator<Item = &'a str>) -> Command {
+    // Pass in "hakari" as the first argument, the same way Cargo does for subcommands.
+    let mut command = workspace.command(
+        env!("CARGO_BIN_EXE_cargo-hakari"),
+        std::iter::once("hakari").chain(args),
+    );
+    command.arg("--color=never");
+    command
+}
+
+fn manifest(workspace: &TempWorkspace, rel_path: &str) -> Value {
+    let contents = workspace.read(rel_path);
+    contents
+        .parse()
+        .unwrap_or_else(|err| panic!("{} is valid TOML: {}", rel_path, err))
+}
+
+fn has_hakari_dep(workspace: &TempWorkspace, member: &str) -> bool {
+    let manifest = manifest(workspace, &format!("{}/Cargo.toml", member));
+    manifest["dependencies"].get(HAKARI_PACKAGE).is_some()
+}
+
+fn git<'a>(workspace: &TempWorkspace, args: impl IntoIterator<Item = &'a str>) {
+    let mut command = workspace.command(
+        "git",
+        [
+            "-c",
+            "user.name=hakari-test",
+            "-c",
+            "user.email=hakari-test@example.com",
+            "-c",
+            "commit.gpgsign=false",
+        ],
+    );
+    command.args(args);
+    workspace.run(&mut command, 0);
+}
+
+fn hakari_version(workspace: &TempWorkspace) -> String {
+    let hakari_manifest = manifest(workspace, "my-workspace-hack/Cargo.toml");
+    hakari_manifest["package"]["version"]
+        .as_str()
+        .expect("version is a string")
+        .to_owned()
+}
+
+fn hakari_version_req(workspace: &TempWorkspace, member: &str) -> String {
+    let manifest = manifest(workspace, &format!("{}/Cargo.toml", member));
+    manifest["dependencies"][HAKARI_PACKAGE]["version"]
+        .as_str()
+        .expect("version requirement is a string")
+        .to_owned()
+}
+
+/// Runs `init`, `generate` and `manage-deps` against the inside-outside fixture.
+fn initialized_workspace() -> TempWorkspace {
+    let workspace = TempWorkspace::inside_outside();
+    workspace.run(
+        &mut hakari(&workspace, ["init", HAKARI_PACKAGE, "--yes"]),
+        0,
+    );
+    workspace.run(&mut hakari(&workspace, ["generate"]), 0);
+    workspace.run(&mut hakari(&workspace, ["manage-deps", "--yes"]), 0);
+    workspace
+}
+
+#[test]
+fn init_generate_manage_deps_verify() {
+    let workspace = TempWorkspace::inside_outside();
+
+    workspace.run(
+        &mut hakari(&workspace, ["init", HAKARI_PACKAGE, "--yes"]),
+        0,
+    );
+    let root_manifest = manifest(&workspace, "Cargo.toml");
+    let workspace_members = root_manifest["workspace"]["members"]
+        .as_array()
+        .expect("workspace.members is an array");
+    assert!(
+        workspace_members.contains(&Value::from(HAKARI_PACKAGE)),
+        "init adds the workspace-hack to workspace members"
+    );
+    assert!(
+        workspace.exists(".config/hakari.toml"),
+        "init writes out hakari.toml"
+    );
+    let hakari_manifest = manifest(&workspace, "my-workspace-hack/Cargo.toml");
+    assert_eq!(
+        hakari_manifest["package"]["name"].as_str(),
+        Some(HAKARI_PACKAGE),
+        "package name is correct"
+    );
+    assert!(
+        hakari_manifest.get("dependencies").is_none(),
+        "init doesn't generate dependencies"
+    );
+
+    // Contents are out of date before generate is run.
+    workspace.run(&mut hakari(&workspace, ["generate", "--diff"]), 1);
+    workspace.run(&mut hakari(&workspace, ["generate"]), 0);
+    workspace.run(&mut hakari(&workspace, ["generate", "--diff"]), 0);
+    let hakari_contents = workspace.read("my-workspace-hack/Cargo.toml");
+    assert!(hakari_contents.contains("### BEGIN HAKARI SECTION"));
+    let hakari_manifest = manifest(&workspace, "my-workspace-hack/Cargo.toml");
+    for dep in ["external", "transitive", "lazy_static"] {
+        assert!(
+            hakari_manifest["dependencies"].get(dep).is_some(),
+            "generate adds {} to the workspace-hack",
+            dep
+        );
+    }
+
+    for member in MEMBERS {
+        assert!(
+            !has_hakari_dep(&workspace, member),
+            "{} doesn't depend on the workspace-hack before manage-deps",
+            member
+        );
+    }
+    workspace.run(&mut hakari(&workspace, ["manage-deps", "--dry-run"]), 1);
+    workspace.run(&mut hakari(&workspace, ["manage-deps", "--yes"]), 0);
+    workspace.run(&mut hakari(&workspace, ["manage-deps", "--dry-run"]), 0);
+    for member in MEMBERS {
+        assert!(
+            has_hakari_dep(&workspace, member),
+            "{} depends on the workspace-hack after manage-deps",
+            member
+        );
+    }
+
+    let stdout = workspace.run(&mut hakari(&workspace, ["verify"]), 0);
+    assert!(
+        stdout.is_empty(),
+        "verify doesn't print anything to stdout: {}",
+        stdout
+    );
+    workspace.run(&mut hakari(&workspace, ["check"]), 0);
+}
+
+#[test]
+fn generate_detects_changes() {
+    let workspace = initialized_workspace();
+
+    // Add a dependency on a new feature, which should be unified into the workspace-hack.
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "[dependencies]\n",
+            "[dependencies]\ntransitive = { path = \"../../transitive\", features = [\"dev-feature\"] }\n",
+        ),
+    );
+    workspace.run(&mut hakari(&workspace, ["generate", "--diff"]), 1);
+    workspace.run(&mut hakari(&workspace, ["generate"]), 0);
+    workspace.run(&mut hakari(&workspace, ["generate", "--diff"]), 0);
+    let hakari_manifest = manifest(&workspace, "my-workspace-hack/Cargo.toml");
+    let features = hakari_manifest["dependencies"]["transitive"]["features"]
+        .as_array()
+        .expect("features is an array");
+    assert!(
+        features.contains(&Value::from("dev-feature")),
+        "generate adds the new feature to the workspace-hack"
+    );
+    workspace.run(&mut hakari(&workspace, ["verify"]), 0);
+}
+
+#[test]
+fn check_reports_all_failures() {
+    let workspace = initialized_workspace();
+    workspace.run(&mut hakari(&workspace, ["check"]), 0);
+
+    // Make the workspace-hack out of date, and remove dependencies on it. Every failure is
+    // reported, including verify since the workspace-hack no longer unifies features.
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "[dependencies]\n",
+            "[dependencies]\ntransitive = { path = \"../../transitive\", features = [\"dev-feature\"] }\n",
+        ),
+    );
+    workspace.run(&mut hakari(&workspace, ["remove-deps", "--yes"]), 0);
+    workspace.run(&mut hakari(&workspace, ["check"]), 4 + 8 + 16);
+
+    // A config without hakari-package fails the config check, and the others are skipped.
+    let config = workspace.read(".config/hakari.toml");
+    workspace.write(
+        ".config/hakari.toml",
+        config.replace("hakari-package = \"my-workspace-hack\"\n", ""),
+    );
+    let output = hakari(&workspace, ["check"])
+        .output()
+        .expect("cargo hakari check ran");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("hakari-package must be specified in hakari.toml"),
+        "missing hakari-package reported: {}",
+        stderr
+    );
+    assert!(
+        stderr
+            .contains("config       failed (fix the config, or create one with cargo hakari init)")
+            && stderr.contains("generate     skipped"),
+        "report printed: {}",
+        stderr
+    );
+}
+
+#[test]
+fn remove_deps_and_disable() {
+    let workspace = initialized_workspace();
+
+    workspace.run(&mut hakari(&workspace, ["remove-deps", "--yes"]), 0);
+    for member in MEMBERS {
+        assert!(
+            !has_hakari_dep(&workspace, member),
+            "{} doesn't depend on the workspace-hack after remove-deps",
+            member
+        );
+    }
+
+    workspace.run(&mut hakari(&workspace, ["disable"]), 0);
+    let hakari_manifest = manifest(&workspace, "my-workspace-hack/Cargo.toml");
+    assert!(
+        hakari_manifest.get("dependencies").is_none(),
+        "disable removes generated dependencies"
+    );
+}
+
+#[test]
+fn bump() {
+    let workspace = initialized_workspace();
+    // The workspace-hack must be published for its version to be bumped.
+    let hakari_contents = workspace.read("my-workspace-hack/Cargo.toml");
+    workspace.write(
+        "my-workspace-hack/Cargo.toml",
+        hakari_contents.replace("publish = false\n", ""),
+    );
+    workspace.run(&mut hakari(&workspace, ["manage-deps", "--yes"]), 0);
+    assert_eq!(hakari_version_req(&workspace, "main"), "0.1");
+
+    // Without a release tag, the current version is assumed to be unreleased.
+    git(&workspace, ["init", "--quiet"]);
+    git(&workspace, ["add", "--all"]);
+    git(
+        &workspace,
+        ["commit", "--quiet", "--message", "initial commit"],
+    );
+    workspace.run(&mut hakari(&workspace, ["bump", "--dry-run"]), 0);
+
+    git(&workspace, ["tag", "my-workspace-hack-0.1.0"]);
+    workspace.run(&mut hakari(&workspace, ["bump", "--dry-run"]), 0);
+
+    // Change the contents of the workspace-hack.
+    let side_manifest = workspace.read("side/Cargo.toml");
+    workspace.write(
+        "side/Cargo.toml",
+        side_manifest.replace(
+            "[dependencies]\n",
+            "[dependencies]\ntransitive = { path = \"../../transitive\", features = [\"dev-feature\"] }\n",
+        ),
+    );
+    workspace.run(&mut hakari(&workspace, ["generate"]), 0);
+    workspace.run(&mut hakari(&workspace, ["bump", "--dry-run"]), 1);
+    assert_eq!(
+        hakari_version(&workspace),
+        "0.1.0",
+        "dry run doesn't bump the version"
+    );
+
+    workspace.run(&mut hakari(&workspace, ["bump"]), 0);
+    assert_eq!(hakari_version(&workspace), "0.1.1", "patch version bumped");
+    assert_eq!(
+        hakari_version_req(&workspace, "main"),
+        "0.1",
+        "requirements that still match aren't updated"
+    );
+    assert!(
+        workspace.read("Cargo.lock").contains("0.1.1"),
+        "lockfile regenerated"
+    );
+
+    // --since compares against a revision other than the release tag: the initial commit here.
+    let config = workspace.read(".config/hakari.toml");
+    workspace.write(
+        ".config/hakari.toml",
+        format!("{}\n[bump]\nlevel = \"minor\"\n", config),
+    );
+    workspace.run(&mut hakari(&workspace, ["bump", "--dry-run"]), 0);
+    workspace.run(
+        &mut hakari(&workspace, ["bump", "--since", "HEAD", "--dry-run"]),
+        1,
+    );
+    workspace.run(&mut hakari(&workspace, ["bump", "--since", "HEAD"]), 0);
+    assert_eq!(hakari_version(&workspace), "0.2.0", "minor version bumped");
+    for member in MEMBERS {
+        assert_eq!(
+            hakari_version_req(&workspace, member),
+            "0.2",
+            "requirement in {} updated",
+            member
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::graph::summaries::{serialize_forward_slashes, Summary};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, hash::Hasher};
+use twox_hash::XxHash64;
+
+/// A compact summary of a workspace at a base revision, used in place of the old `PackageGraph`.
+///
+/// Storing `cargo metadata` output for a base revision can take up tens of megabytes. A
+/// `BaseSummary` only stores, for each workspace package, its path and a fingerprint of each
+/// simulated Cargo build. It can be generated at the base revision through
+/// [`Determinator::base_summary`](crate::Determinator::base_summary), serialized to TOML, and
+/// later passed in to [`Determinator::with_base_summary`](crate::Determinator::with_base_summary).
+///
+/// Fingerprints can only be compared if the build simulations are the same, so the summary must
+/// be generated with the same Cargo options, [`DeterminatorConfig`](crate::DeterminatorConfig) and
+/// features-only packages as the determinator it's later used with. Otherwise, every package will
+/// be marked changed.
+///
+/// # Examples
+///
+/// ```
+/// use determinator::{BaseSummary, Determinator};
+/// use guppy::CargoMetadata;
+///
+/// let old_metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/guppy/metadata_guppy_78cb7e8.json")).unwrap();
+/// let old = old_metadata.build_graph().unwrap();
+///
+/// // At the base revision, generate a summary and store it somewhere.
+/// let summary = Determinator::new(&old, &old).base_summary();
+/// let serialized = summary.to_string().unwrap();
+///
+/// // Later, use the summary in place of the old graph.
+/// let new_metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/guppy/metadata_guppy_869476c.json")).unwrap();
+/// let new = new_metadata.build_graph().unwrap();
+/// let summary = BaseSummary::parse(&serialized).unwrap();
+/// let mut determinator = Determinator::with_base_summary(&summary, &new);
+/// determinator.add_changed_paths(vec!["guppy/src/lib.rs"]);
+/// let determinator_set = determinator.compute();
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BaseSummary {
+    /// The workspace packages at the base revision, keyed by name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub packages: BTreeMap<String, BasePackageSummary>,
+}
+
+impl BaseSummary {
+    /// Deserializes a summary from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Serializes this summary to a TOML string.
+    pub fn to_string(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    /// Returns the name of the workspace package at this path, relative to the workspace root.
+    pub fn member_by_path(&self, path: impl AsRef<Utf8Path>) -> Option<&str> {
+        let path = path.as_ref();
+        self.packages
+            .iter()
+            .find(|(_, package)| package.path == path)
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Information about a single workspace package in a [`BaseSummary`].
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BasePackageSummary {
+    /// The path to this package, relative to the workspace root.
+    #[serde(serialize_with = "serialize_forward_slashes")]
+    pub path: Utf8PathBuf,
+
+    /// Fingerprints of the packages and features built in each simulated Cargo build, in the
+    /// order the simulations are run.
+    pub fingerprints: Vec<String>,
+}
+
+/// Returns a fingerprint for the packages and features in this summary.
+///
+/// The metadata is ignored, matching the way summaries are compared.
+pub(crate) fn fingerprint(summary: &Summary) -> String {
+    let summary = Summary {
+        metadata: Default::default(),
+        ..summary.clone()
+    };
+    let serialized = summary
+        .to_string()
+        .expect("serializing a summary without metadata should succeed");
+
+    // Use a fixed seed to ensure stable fingerprints.
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(serialized.as_bytes());
+    format!("{:016x}", hasher.finish())
+}
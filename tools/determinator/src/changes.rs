@@ -0,0 +1,170 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{errors::NameStatusParseError, Utf8Paths0};
+use camino::Utf8Path;
+
+/// A single change to a file between two revisions.
+///
+/// Passing in a list of changes through
+/// [`Determinator::add_changes`](crate::Determinator::add_changes) gives the determinator more
+/// information than a plain list of paths. In particular, if a file is renamed across package
+/// boundaries, the old path is attributed to the package it used to be in, and the new path to
+/// the package it is now in.
+///
+/// A list of changes can be obtained from Git through [`PathChange::parse_name_status`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+pub enum PathChange<'a> {
+    /// A file was added.
+    Added(&'a Utf8Path),
+
+    /// A file was modified, or its type was changed.
+    Modified(&'a Utf8Path),
+
+    /// A file was deleted.
+    Deleted(&'a Utf8Path),
+
+    /// A file was moved from one path to another.
+    Renamed {
+        /// The path in the old revision.
+        from: &'a Utf8Path,
+        /// The path in the new revision.
+        to: &'a Utf8Path,
+    },
+
+    /// A file was copied from one path to another. The source of the copy is unchanged.
+    Copied {
+        /// The path that was copied from.
+        from: &'a Utf8Path,
+        /// The path that was copied to.
+        to: &'a Utf8Path,
+    },
+}
+
+impl<'a> PathChange<'a> {
+    /// Parses changes produced by Git's `--name-status -z` option.
+    ///
+    /// To obtain a list of changes between two revisions (omit `<new rev>` if comparing against
+    /// the working directory), run:
+    ///
+    /// ```text
+    /// git diff -z --name-status --find-renames <old rev> <new rev>
+    /// ```
+    ///
+    /// Pass the output of this command into [`Utf8Paths0`], then call this method. For more about
+    /// choosing revisions to compare, see the documentation for `Utf8Paths0`.
+    ///
+    /// Unmerged files are treated as modified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use determinator::{PathChange, Utf8Paths0};
+    ///
+    /// let paths = Utf8Paths0::new("M\0foo/src/lib.rs\0R087\0foo/src/bar.rs\0bar/src/lib.rs\0");
+    /// let changes = PathChange::parse_name_status(&paths).expect("valid output");
+    /// assert_eq!(
+    ///     changes,
+    ///     vec![
+    ///         PathChange::Modified("foo/src/lib.rs".into()),
+    ///         PathChange::Renamed {
+    ///             from: "foo/src/bar.rs".into(),
+    ///             to: "bar/src/lib.rs".into(),
+    ///         },
+    ///     ],
+    /// );
+    /// ```
+    pub fn parse_name_status(paths: &'a Utf8Paths0) -> Result<Vec<Self>, NameStatusParseError> {
+        let mut iter = paths.iter();
+        let mut changes = vec![];
+        while let Some(status) = iter.next() {
+            let status = status.as_str();
+            let mut next_path = || {
+                iter.next()
+                    .ok_or_else(|| NameStatusParseError::MissingPath {
+                        status: status.to_owned(),
+                    })
+            };
+            // Renames and copies are followed by a similarity score, e.g. "R087".
+            let change = match status.as_bytes().first() {
+                Some(b'A') => PathChange::Added(next_path()?),
+                Some(b'M') | Some(b'T') | Some(b'U') => PathChange::Modified(next_path()?),
+                Some(b'D') => PathChange::Deleted(next_path()?),
+                Some(b'R') => PathChange::Renamed {
+                    from: next_path()?,
+                    to: next_path()?,
+                },
+                Some(b'C') => PathChange::Copied {
+                    from: next_path()?,
+                    to: next_path()?,
+                },
+                _ => return Err(NameStatusParseError::UnknownStatus(status.to_owned())),
+            };
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+
+    /// Returns the path this change refers to in the old revision, if the file existed there.
+    pub fn old_path(&self) -> Option<&'a Utf8Path> {
+        match self {
+            PathChange::Added(_) => None,
+            PathChange::Modified(path) | PathChange::Deleted(path) => Some(path),
+            PathChange::Renamed { from, .. } | PathChange::Copied { from, .. } => Some(from),
+        }
+    }
+
+    /// Returns the path this change refers to in the new revision, if the file exists there.
+    pub fn new_path(&self) -> Option<&'a Utf8Path> {
+        match self {
+            PathChange::Deleted(_) => None,
+            PathChange::Added(path) | PathChange::Modified(path) => Some(path),
+            PathChange::Renamed { to, .. } | PathChange::Copied { to, .. } => Some(to),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_name_status() {
+        let paths = Utf8Paths0::new(
+            "A\0a/new.rs\0M\0a/lib.rs\0T\0a/link\0D\0b/old.rs\0R100\0b/x.rs\0c/x.rs\0C075\0c/y.rs\0d/y.rs",
+        );
+        let changes = PathChange::parse_name_status(&paths).expect("valid output");
+        assert_eq!(
+            changes,
+            vec![
+                PathChange::Added("a/new.rs".into()),
+                PathChange::Modified("a/lib.rs".into()),
+                PathChange::Modified("a/link".into()),
+                PathChange::Deleted("b/old.rs".into()),
+                PathChange::Renamed {
+                    from: "b/x.rs".into(),
+                    to: "c/x.rs".into()
+                },
+                PathChange::Copied {
+                    from: "c/y.rs".into(),
+                    to: "d/y.rs".into()
+                },
+            ]
+        );
+
+        assert_eq!(
+            PathChange::parse_name_status(&Utf8Paths0::new("")),
+            Ok(vec![])
+        );
+        assert_eq!(
+            PathChange::parse_name_status(&Utf8Paths0::new("R100\0b/x.rs\0")),
+            Err(NameStatusParseError::MissingPath {
+                status: "R100".to_owned()
+            })
+        );
+        assert_eq!(
+            PathChange::parse_name_status(&Utf8Paths0::new("a/lib.rs\0")),
+            Err(NameStatusParseError::UnknownStatus("a/lib.rs".to_owned()))
+        );
+    }
+}
@@ -2,26 +2,37 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    base_summary::{fingerprint, BasePackageSummary, BaseSummary},
+    changes::PathChange,
     errors::RulesError,
     rules::{
-        DeterminatorPostRule, DeterminatorRules, MarkChangedImpl, PathMatch, PathRuleImpl,
-        RulesImpl,
+        DeterminatorPostRule, DeterminatorRules, MarkChangedImpl, PathMatch, RuleMatch, RulesImpl,
     },
 };
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use globset::Candidate;
 use guppy::{
     graph::{
         cargo::{CargoOptions, CargoSet},
         feature::{FeatureFilter, FeatureSet, StandardFeatures},
-        DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace,
+        summaries::{PackageInfo, PackageStatus, Summary},
+        ArtifactKind, BuildTargetId, DependencyDirection, PackageGraph, PackageMetadata,
+        PackageSet, PackageSource,
     },
     platform::PlatformSpec,
     PackageId,
 };
 use petgraph::{graphmap::GraphMap, Directed};
 use rayon::prelude::*;
-use std::collections::{hash_map::Entry, HashMap, HashSet};
+use serde::Serialize;
+use std::{
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt, fs, io, iter,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 /// Determine target dependencies from changed files and packages in a workspace.
 ///
@@ -30,31 +41,59 @@ use std::collections::{hash_map::Entry, HashMap, HashSet};
 /// This struct has two lifetime parameters:
 /// * `'g` stands for the lifetime of the new graph. The `DeterminatorSet` will be bound to this
 ///   lifetime.
-/// * `'a` is the lifetime of the old graph, Cargo options, and changed paths. The `DeterminatorSet`
-///   will not be bound to this lifetime.
+/// * `'a` is the lifetime of the old graph or [`BaseSummary`], Cargo options, and changed paths.
+///   The `DeterminatorSet` will not be bound to this lifetime.
 #[derive(Clone, Debug)]
 pub struct Determinator<'g, 'a> {
-    old: &'a PackageGraph,
+    base: DeterminatorBase<'a>,
     new: &'g PackageGraph,
     rules: RulesImpl<'g>,
+    path_deps: PathDependencies<'g>,
     cargo_options: Option<&'a CargoOptions<'a>>,
-    old_features_only: Option<FeatureSet<'a>>,
-    new_features_only: Option<FeatureSet<'g>>,
-    changed_paths: Vec<&'a Utf8Path>,
+    config: DeterminatorConfig,
+    features_only: Option<FeaturesOnly<'g>>,
+    attribute_targets: bool,
+    progress_callback: Option<ProgressCallback<'g, 'a>>,
 }
 
 impl<'g, 'a> Determinator<'g, 'a> {
     /// Creates a new instance of `Determinator` with old and new package graphs.
+    ///
+    /// If rules in package metadata can't be resolved, they're ignored. Call
+    /// [`set_rules`](Self::set_rules) to get the error.
     pub fn new(old: &'a PackageGraph, new: &'g PackageGraph) -> Self {
+        Self::new_impl(DeterminatorBase::new(old), new)
+    }
+
+    /// Creates a new instance of `Determinator` with a summary of the old workspace and a new
+    /// package graph.
+    ///
+    /// The summary is used in place of the old package graph: build simulations for the new graph
+    /// are compared against the fingerprints stored in it. For more, see the documentation for
+    /// [`BaseSummary`].
+    ///
+    /// If rules in package metadata can't be resolved, they're ignored. Call
+    /// [`set_rules`](Self::set_rules) to get the error.
+    pub fn with_base_summary(old: &'a BaseSummary, new: &'g PackageGraph) -> Self {
+        Self::new_impl(DeterminatorBase::from_summary(old), new)
+    }
+
+    fn new_impl(base: DeterminatorBase<'a>, new: &'g PackageGraph) -> Self {
+        let rules = RulesImpl::new(new, &DeterminatorRules::default()).unwrap_or_else(|_| {
+            let mut rules = DeterminatorRules::default();
+            rules.use_package_metadata_rules = false;
+            RulesImpl::new(new, &rules).expect("default rules should parse")
+        });
         Self {
-            old,
+            base,
             new,
-            rules: RulesImpl::new(new, &DeterminatorRules::default())
-                .expect("default rules should parse"),
+            rules,
+            path_deps: PathDependencies::new(new),
             cargo_options: None,
-            old_features_only: None,
-            new_features_only: None,
-            changed_paths: vec![],
+            config: DeterminatorConfig::new(),
+            features_only: None,
+            attribute_targets: false,
+            progress_callback: None,
         }
     }
 
@@ -94,8 +133,26 @@ impl<'g, 'a> Determinator<'g, 'a> {
         &mut self,
         paths: impl IntoIterator<Item = &'a (impl AsRef<Utf8Path> + ?Sized + 'a)>,
     ) -> &mut Self {
-        self.changed_paths
-            .extend(paths.into_iter().map(|path| path.as_ref()));
+        self.base.add_changed_paths(paths);
+        self
+    }
+
+    /// Adds a list of changes, including information about renamed and copied files.
+    ///
+    /// Paths added through [`add_changed_paths`](Self::add_changed_paths) are always looked up in
+    /// the new workspace. With this method, paths that no longer exist (deleted files, and the
+    /// sources of renames) are instead attributed to the package they were in in the old
+    /// workspace. This means that:
+    /// * a file that is moved from one package to another marks both packages changed, and
+    /// * a file that was inside a package that has since been removed from the workspace doesn't
+    ///   cause every package to be marked changed.
+    ///
+    /// The sources of copies are unchanged, so they aren't considered.
+    ///
+    /// Changes can be obtained from Git through
+    /// [`PathChange::parse_name_status`](crate::PathChange::parse_name_status).
+    pub fn add_changes(&mut self, changes: impl IntoIterator<Item = PathChange<'a>>) -> &mut Self {
+        self.base.add_changes(changes);
         self
     }
 
@@ -111,13 +168,15 @@ impl<'g, 'a> Determinator<'g, 'a> {
     pub fn match_path(
         &self,
         path: impl AsRef<Utf8Path>,
-        match_cb: impl FnMut(&'g PackageId),
+        mut match_cb: impl FnMut(&'g PackageId),
     ) -> PathMatch {
+        let workspace = self.new.workspace();
         process_path(
             path.as_ref(),
-            &self.new.workspace(),
-            &self.rules.path_rules,
-            match_cb,
+            |ancestor| Some(Some(workspace.member_by_path(ancestor).ok()?.id())),
+            &self.rules,
+            &self.path_deps,
+            |id, _| match_cb(id),
         )
     }
 
@@ -141,6 +200,16 @@ impl<'g, 'a> Determinator<'g, 'a> {
         self
     }
 
+    /// Configures which build simulations are run for each workspace package.
+    ///
+    /// By default, every package is simulated with no features, default features and all
+    /// features, on the platforms specified by the Cargo options. For more, see the documentation
+    /// for [`DeterminatorConfig`].
+    pub fn set_config(&mut self, config: DeterminatorConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
     /// Returns the default Cargo options used by the determinator.
     ///
     /// These are the same as the defaults returned by [`CargoOptions::new`](CargoOptions::new),
@@ -172,43 +241,141 @@ impl<'g, 'a> Determinator<'g, 'a> {
         workspace_names: impl IntoIterator<Item = &'b str>,
         features: StandardFeatures,
     ) -> Result<&mut Self, guppy::Error> {
-        let old_workspace = self.old.workspace();
-        let mut old_names = vec![];
-        let new_names: Vec<_> = workspace_names
-            .into_iter()
-            .inspect(|&name| {
-                if old_workspace.contains_name(name) {
-                    old_names.push(name);
-                }
-            })
-            .collect();
+        let names: Vec<_> = workspace_names.into_iter().map(String::from).collect();
 
         // Missing package name in new workspace => error.
-        let new_features_only = self
+        let new_set = self
             .new
-            .resolve_workspace_names(new_names)?
-            .to_feature_set(features);
-        let old_features_only = self
-            .old
-            .resolve_workspace_names(old_names)
-            .expect("old names were checked already")
+            .resolve_workspace_names(&names)?
             .to_feature_set(features);
 
-        self.new_features_only = Some(new_features_only);
-        self.old_features_only = Some(old_features_only);
+        self.features_only = Some(FeaturesOnly {
+            names,
+            features,
+            new_set,
+        });
         Ok(self)
     }
 
+    /// Configures whether changed paths are attributed to individual build targets.
+    ///
+    /// By default, a change to any file in a package marks the package changed, and everything
+    /// that depends on it affected. With this option enabled, a path that only belongs to test,
+    /// benchmark, example or binary targets still marks its package changed, but doesn't cause
+    /// dependents of the package to be affected, since other packages can't depend on those
+    /// targets. The targets are recorded in [`DeterminatorSet::changed_targets`].
+    ///
+    /// The exception is binaries that other packages depend on through artifact dependencies
+    /// (an unstable Cargo feature, specified with `artifact = "bin"` or similar): paths attributed
+    /// to them mark the whole package changed.
+    ///
+    /// A path is attributed to the target whose source root it is, or otherwise to the targets
+    /// whose source roots are in its closest ancestor directory. For example, `benches/foo.rs`
+    /// and `benches/common/mod.rs` are attributed to the benchmarks in `benches`. Paths that
+    /// can't be attributed this way, such as `Cargo.toml` or `build.rs`, and paths attributed to
+    /// a library or build script, mark the whole package changed as usual.
+    ///
+    /// This is disabled by default, because a library may read files that belong to other
+    /// targets, e.g. through `include_str!`.
+    pub fn set_attribute_targets(&mut self, attribute_targets: bool) -> &mut Self {
+        self.attribute_targets = attribute_targets;
+        self
+    }
+
+    /// Sets a callback that's called as Cargo build simulations for workspace packages complete.
+    ///
+    /// This can be used to render a progress bar for large workspaces. Simulations are run in
+    /// parallel, so the callback may be called concurrently from several threads, and the
+    /// `completed` counts it observes may arrive out of order.
+    ///
+    /// For more about the stages reported, see [`ProgressStage`].
+    pub fn set_progress_callback(
+        &mut self,
+        callback: impl Fn(DeterminatorProgress<'g>) + Send + Sync + 'a,
+    ) -> &mut Self {
+        self.progress_callback = Some(ProgressCallback(Arc::new(callback)));
+        self
+    }
+
     /// Uses the old and new sets and the list of changed files to compute the list
     /// of projects that is affected.
     pub fn compute(&self) -> DeterminatorSet<'g> {
-        let mut build_state = BuildState::new(self);
+        let shared = SharedState::new(self);
+        self.compute_impl(&shared, &self.base)
+    }
 
-        // 1-2. Process every changed path.
-        for path in &self.changed_paths {
-            build_state = match build_state.process_path(path) {
-                Some(build_state) => build_state,
-                None => {
+    /// Computes a [`BaseSummary`] for the new graph.
+    ///
+    /// The summary can be stored, then used as the base for a future determinator run through
+    /// [`with_base_summary`](Self::with_base_summary) or
+    /// [`DeterminatorBase::from_summary`]. Build simulations are run with the Cargo options,
+    /// config and features-only packages currently configured.
+    pub fn base_summary(&self) -> BaseSummary {
+        let default_options = Determinator::default_cargo_options();
+        let cargo_options = self.cargo_options.unwrap_or(&default_options);
+        let platform_options = self.config.platform_options(cargo_options);
+
+        let build_cache = CargoBuildCache::new(self);
+        let packages = self
+            .new
+            .workspace()
+            .iter_by_name()
+            .map(|(name, package)| {
+                let path = package
+                    .source()
+                    .workspace_path()
+                    .expect("workspace packages have workspace paths");
+                let fingerprints =
+                    build_cache.result_cache[package.id()].fingerprints(&platform_options);
+                let summary = BasePackageSummary {
+                    path: path.to_owned(),
+                    fingerprints,
+                };
+                (name.to_owned(), summary)
+            })
+            .collect();
+
+        BaseSummary { packages }
+    }
+
+    /// Computes the list of affected projects against several bases in one run.
+    ///
+    /// Each [`DeterminatorBase`] consists of an old package graph and a list of changes relative
+    /// to it. For example, a CI run for a pull request may wish to compute affected sets relative
+    /// to both the merge-base with the main branch and the previous commit on the pull request.
+    ///
+    /// This is equivalent to calling [`compute`](Self::compute) once for each base, except that
+    /// Cargo build simulations for the new graph are only run once and shared across all bases.
+    /// The old graph and changes passed into [`new`](Self::new),
+    /// [`add_changed_paths`](Self::add_changed_paths) and [`add_changes`](Self::add_changes) are
+    /// not used.
+    ///
+    /// Returns a `DeterminatorSet` for each base, in the same order.
+    pub fn compute_bases(&self, bases: &[DeterminatorBase<'_>]) -> Vec<DeterminatorSet<'g>> {
+        let shared = SharedState::new(self);
+        bases
+            .iter()
+            .map(|base| self.compute_impl(&shared, base))
+            .collect()
+    }
+
+    fn compute_impl(
+        &self,
+        shared: &SharedState<'g>,
+        base: &DeterminatorBase<'_>,
+    ) -> DeterminatorSet<'g> {
+        let mut build_state = BuildState::new(self, shared, base);
+
+        // 1-2. Process every changed and removed path.
+        let paths = base
+            .changed_paths
+            .iter()
+            .map(|path| (*path, false))
+            .chain(base.removed_paths.iter().map(|path| (*path, true)));
+        for (path, removed) in paths {
+            build_state = match build_state.process_path(path, removed) {
+                Ok(build_state) => build_state,
+                Err(cause) => {
                     // The build state was discarded, which means that the entire workspace is
                     // changed and affected.
                     let path_changed_set = self.new.resolve_workspace();
@@ -218,6 +385,16 @@ impl<'g, 'a> Determinator<'g, 'a> {
                         // This is an empty set.
                         summary_changed_set: self.new.resolve_none(),
                         affected_set,
+                        changed_targets: BTreeMap::new(),
+                        rule_matches: BTreeMap::new(),
+                        causes: AffectedCauses {
+                            graph: self.new,
+                            changed: HashMap::new(),
+                            all_changed: Some(cause),
+                            states: HashMap::new(),
+                            parents: HashMap::new(),
+                            all_rule: None,
+                        },
                     };
                 }
             }
@@ -238,20 +415,164 @@ impl<'g, 'a> Determinator<'g, 'a> {
 
         // 5. The affected set is the transitive closure of the graph constructed by looking at both
         // the build cache and Cargo rules.
-        let affected_set = build_state.reverse_index.affected_closure(
+        // Packages where only targets that other packages can't depend on changed are treated
+        // like summary changes, so that their dependents aren't affected.
+        let (affected_set, trace) = shared.reverse_index.affected_closure(
             self.new,
             &build_state.path_changed_ids,
             &build_state.summary_changed_ids,
+            &build_state.changed_targets,
         );
 
+        // 6. Record the package rules that marked affected packages changed.
+        let mut rule_matches = build_state.rule_matches;
+        for package_rule in &self.rules.package_rules {
+            let packages = match &package_rule.mark_changed {
+                MarkChangedImpl::Packages(packages) => packages,
+                MarkChangedImpl::All => continue,
+            };
+            let triggered = package_rule
+                .on_affected
+                .package_ids(DependencyDirection::Forward)
+                .any(|id| affected_set.contains(id).unwrap_or(false));
+            if triggered {
+                for package in packages {
+                    rule_matches
+                        .entry(package.id())
+                        .or_default()
+                        .insert(package_rule.rule_match.clone());
+                }
+            }
+        }
+
+        // 7. Keep track of why each package is affected, for `DeterminatorSet::why`.
+        let mut changed = build_state.change_causes;
+        changed.extend(
+            build_state
+                .summary_changed_ids
+                .iter()
+                .map(|id| (*id, ChangeCause::BuildSummary)),
+        );
+        let package_rules = &self.rules.package_rules;
+        let affected_edge = |edge: ReverseIndexEdge| match edge {
+            ReverseIndexEdge::PackageRule(index) => {
+                AffectedEdge::PackageRule(package_rules[index].rule_match.clone())
+            }
+            ReverseIndexEdge::CargoBuild => AffectedEdge::Dependency,
+        };
+        let causes = AffectedCauses {
+            graph: self.new,
+            changed,
+            all_changed: None,
+            states: trace.states,
+            parents: trace
+                .parents
+                .into_iter()
+                .map(|(node, (parent, edge))| (node, (parent, affected_edge(edge))))
+                .collect(),
+            all_rule: trace
+                .all_rule
+                .map(|(node, index)| (node, package_rules[index].rule_match.clone())),
+        };
+
         DeterminatorSet {
             path_changed_set,
             summary_changed_set,
             affected_set,
+            changed_targets: build_state.changed_targets,
+            rule_matches,
+            causes,
         }
     }
 }
 
+/// An old package graph or [`BaseSummary`], and a list of changes relative to it, used as a base
+/// for [`Determinator::compute_bases`].
+///
+/// The lifetime `'a` is the lifetime of the old graph or summary, and changed paths.
+#[derive(Clone, Debug)]
+pub struct DeterminatorBase<'a> {
+    old: OldWorkspace<'a>,
+    changed_paths: Vec<&'a Utf8Path>,
+    removed_paths: Vec<&'a Utf8Path>,
+}
+
+impl<'a> DeterminatorBase<'a> {
+    /// Creates a new `DeterminatorBase` with the old package graph and no changes.
+    pub fn new(old: &'a PackageGraph) -> Self {
+        Self {
+            old: OldWorkspace::Graph(old),
+            changed_paths: vec![],
+            removed_paths: vec![],
+        }
+    }
+
+    /// Creates a new `DeterminatorBase` with a summary of the old workspace and no changes.
+    ///
+    /// For more, see the documentation for [`BaseSummary`].
+    pub fn from_summary(old: &'a BaseSummary) -> Self {
+        Self {
+            old: OldWorkspace::Summary(old),
+            changed_paths: vec![],
+            removed_paths: vec![],
+        }
+    }
+
+    /// Returns the old package graph, or `None` if this base was created from a summary.
+    pub fn old(&self) -> Option<&'a PackageGraph> {
+        match self.old {
+            OldWorkspace::Graph(graph) => Some(graph),
+            OldWorkspace::Summary(_) => None,
+        }
+    }
+
+    /// Returns the summary of the old workspace, or `None` if this base was created from a package
+    /// graph.
+    pub fn old_summary(&self) -> Option<&'a BaseSummary> {
+        match self.old {
+            OldWorkspace::Graph(_) => None,
+            OldWorkspace::Summary(summary) => Some(summary),
+        }
+    }
+
+    /// Adds a list of changed paths relative to this base.
+    ///
+    /// For more, see [`Determinator::add_changed_paths`].
+    pub fn add_changed_paths(
+        &mut self,
+        paths: impl IntoIterator<Item = &'a (impl AsRef<Utf8Path> + ?Sized + 'a)>,
+    ) -> &mut Self {
+        self.changed_paths
+            .extend(paths.into_iter().map(|path| path.as_ref()));
+        self
+    }
+
+    /// Adds a list of changes relative to this base, including information about renamed and
+    /// copied files.
+    ///
+    /// For more, see [`Determinator::add_changes`].
+    pub fn add_changes(&mut self, changes: impl IntoIterator<Item = PathChange<'a>>) -> &mut Self {
+        for change in changes {
+            match change {
+                PathChange::Added(path) | PathChange::Modified(path) => {
+                    self.changed_paths.push(path);
+                }
+                PathChange::Deleted(path) => {
+                    self.removed_paths.push(path);
+                }
+                PathChange::Renamed { from, to } => {
+                    self.removed_paths.push(from);
+                    self.changed_paths.push(to);
+                }
+                PathChange::Copied { to, .. } => {
+                    self.changed_paths.push(to);
+                }
+            }
+        }
+        self
+    }
+}
+
 /// The result of a `Determinator` computation.
 ///
 /// The lifetime `'g` is tied to the *new* `PackageGraph` passed to a `Determinator`.
@@ -275,52 +596,749 @@ pub struct DeterminatorSet<'g> {
     /// This does not include packages marked changed through a path. For example, if a path rule
     /// caused all packages to be marked changed, further steps aren't run and this set is empty.
     pub summary_changed_set: PackageSet<'g>,
+
+    /// For packages in `path_changed_set` whose changed files all belong to test, benchmark,
+    /// example or binary targets, the targets that changed.
+    ///
+    /// This is only populated if [`Determinator::set_attribute_targets`] is enabled. Packages in
+    /// `path_changed_set` that aren't in this map should be considered entirely changed.
+    pub changed_targets: BTreeMap<&'g PackageId, BTreeSet<BuildTargetId<'g>>>,
+
+    /// For packages marked changed by path or package rules, the rules that marked them changed.
+    ///
+    /// Packages marked changed only because a file inside them changed, or because of a simulated
+    /// Cargo build, aren't in this map. Rules that mark all packages changed aren't recorded: if
+    /// such a rule was matched, this map is empty.
+    pub rule_matches: BTreeMap<&'g PackageId, BTreeSet<RuleMatch>>,
+
+    causes: AffectedCauses<'g>,
+}
+
+impl<'g> DeterminatorSet<'g> {
+    /// Explains why the workspace package with this name is in the affected set.
+    ///
+    /// The explanation starts from the change that marked a package changed -- a changed path, a
+    /// rule or a simulated build -- and includes every dependency edge and package rule followed
+    /// from there to this package.
+    ///
+    /// Returns `Ok(None)` if the package isn't affected, and an error if the package isn't a member
+    /// of the new workspace.
+    pub fn why(&self, package_name: &str) -> Result<Option<AffectedExplanation<'g>>, guppy::Error> {
+        let package = self.causes.graph.workspace().member_by_name(package_name)?;
+        if !self
+            .affected_set
+            .contains(package.id())
+            .expect("package is in the new graph")
+        {
+            return Ok(None);
+        }
+        Ok(Some(self.causes.explain(package)))
+    }
+
+    /// Returns the names of the workspace packages in the affected set, in lexicographic order.
+    ///
+    /// The order in which `affected_set` returns packages depends on the internals of the package
+    /// graph, so it may change across runs. Use this method for output that should be stable,
+    /// such as logs.
+    pub fn sorted_names(&self) -> Vec<&'g str> {
+        sorted_names(&self.affected_set)
+    }
+
+    /// Converts the affected set into a build summary.
+    ///
+    /// Every package in the affected set is recorded as an [`Initial`](PackageStatus::Initial)
+    /// target package with no features. The names of the packages in `path_changed_set` and
+    /// `summary_changed_set` are stored in the metadata, under the `path-changed` and
+    /// `summary-changed` keys.
+    ///
+    /// The summary can be read by anything that understands
+    /// [guppy summaries](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy-summaries),
+    /// such as `cargo guppy diff-summaries`.
+    pub fn to_summary(&self) -> Summary {
+        let metadata = AffectedSummaryMetadata {
+            path_changed: sorted_names(&self.path_changed_set),
+            summary_changed: sorted_names(&self.summary_changed_set),
+        };
+        let mut summary = Summary::with_metadata(&metadata)
+            .expect("serializing lists of package names should succeed");
+        summary.target_packages = self
+            .affected_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| {
+                let info = PackageInfo {
+                    status: PackageStatus::Initial,
+                    features: BTreeSet::new(),
+                    optional_deps: BTreeSet::new(),
+                };
+                (package.to_summary_id(), info)
+            })
+            .collect();
+        summary
+    }
+
+    /// Writes out the summary returned by [`to_summary`](Self::to_summary) to the given path, for
+    /// downstream jobs to consume.
+    pub fn write_summary(&self, path: impl AsRef<Utf8Path>) -> io::Result<()> {
+        let summary = self
+            .to_summary()
+            .to_string()
+            .expect("serializing a summary without edges should succeed");
+        fs::write(path.as_ref(), summary)
+    }
+}
+
+/// Metadata stored in the summary written out by [`DeterminatorSet::write_summary`].
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct AffectedSummaryMetadata<'g> {
+    path_changed: Vec<&'g str>,
+    summary_changed: Vec<&'g str>,
+}
+
+fn sorted_names<'g>(package_set: &PackageSet<'g>) -> Vec<&'g str> {
+    let mut names: Vec<_> = package_set
+        .packages(DependencyDirection::Forward)
+        .filter(|package| package.in_workspace())
+        .map(|package| package.name())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+/// An explanation of why a package is in the affected set.
+///
+/// Returned by [`DeterminatorSet::why`]. The `Display` implementation prints out the chain of
+/// causes, one per line, starting from the change.
+#[derive(Clone, Debug)]
+pub struct AffectedExplanation<'g> {
+    /// The package being explained.
+    pub package: PackageMetadata<'g>,
+
+    /// The change that started the chain.
+    ///
+    /// This change marked the first package in the chain changed: `steps[0].from`, or `package`
+    /// itself if `steps` is empty.
+    pub cause: ChangeCause,
+
+    /// The edges followed through the reverse dependency graph, starting from the package marked
+    /// changed by `cause` and ending at `package`.
+    ///
+    /// Empty if `package` was marked changed by `cause` directly.
+    pub steps: Vec<AffectedStep<'g>>,
+}
+
+impl<'g> AffectedExplanation<'g> {
+    /// Returns the package that `cause` marked changed.
+    pub fn changed_package(&self) -> PackageMetadata<'g> {
+        match self.steps.first() {
+            Some(step) => step.from,
+            None => self.package,
+        }
+    }
+}
+
+impl<'g> fmt::Display for AffectedExplanation<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was marked changed: {}",
+            self.changed_package().name(),
+            self.cause
+        )?;
+        for step in &self.steps {
+            writeln!(f)?;
+            match &step.edge {
+                AffectedEdge::Dependency => {
+                    write!(f, "{} depends on {}", step.to.name(), step.from.name())?
+                }
+                AffectedEdge::PackageRule(rule_match) => write!(
+                    f,
+                    "{} was marked changed by {} because {} is affected",
+                    step.to.name(),
+                    rule_match,
+                    step.from.name()
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The change that started a chain of affected packages.
+///
+/// Part of an [`AffectedExplanation`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ChangeCause {
+    /// A path inside the package changed.
+    Path(Utf8PathBuf),
+
+    /// A path inside a path dependency of the package, outside the workspace, changed.
+    PathDependency(Utf8PathBuf),
+
+    /// A changed path matched a path rule that marked the package changed.
+    PathRule {
+        /// The changed path.
+        path: Utf8PathBuf,
+        /// The rule that was matched.
+        rule_match: RuleMatch,
+    },
+
+    /// A changed path caused all packages to be marked changed.
+    AllChanged {
+        /// The changed path.
+        path: Utf8PathBuf,
+        /// The rule that marked all packages changed, or `None` if the path wasn't matched to any
+        /// rules or packages.
+        rule_match: Option<RuleMatch>,
+    },
+
+    /// The dependencies or features of the package changed in a simulated Cargo build.
+    BuildSummary,
+}
+
+impl fmt::Display for ChangeCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeCause::Path(path) => write!(f, "{} changed", path),
+            ChangeCause::PathDependency(path) => {
+                write!(f, "{} changed in a path dependency", path)
+            }
+            ChangeCause::PathRule { path, rule_match } => {
+                write!(f, "{} changed and matched {}", path, rule_match)
+            }
+            ChangeCause::AllChanged {
+                path,
+                rule_match: Some(rule_match),
+            } => write!(
+                f,
+                "{} changed and matched {}, which marks all packages changed",
+                path, rule_match
+            ),
+            ChangeCause::AllChanged {
+                path,
+                rule_match: None,
+            } => write!(
+                f,
+                "{} changed outside all packages, which marks all packages changed",
+                path
+            ),
+            ChangeCause::BuildSummary => {
+                write!(
+                    f,
+                    "its dependencies or features changed in a simulated build"
+                )
+            }
+        }
+    }
+}
+
+/// An edge through the reverse dependency graph, from an affected package to another one.
+///
+/// Part of an [`AffectedExplanation`].
+#[derive(Clone, Debug)]
+pub struct AffectedStep<'g> {
+    /// The affected package.
+    pub from: PackageMetadata<'g>,
+
+    /// The package that `from` caused to be affected.
+    pub to: PackageMetadata<'g>,
+
+    /// The kind of edge between the two packages.
+    pub edge: AffectedEdge,
+}
+
+/// The kind of an [`AffectedStep`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum AffectedEdge {
+    /// `to` depends on `from` in a simulated Cargo build.
+    Dependency,
+
+    /// A package rule marked `to` changed because `from` was affected.
+    PackageRule(RuleMatch),
+}
+
+/// The information used by [`DeterminatorSet::why`].
+#[derive(Clone, Debug)]
+struct AffectedCauses<'g> {
+    graph: &'g PackageGraph,
+    // How each path-changed and summary-changed package was marked changed.
+    changed: HashMap<&'g PackageId, ChangeCause>,
+    // Set if a changed path caused all packages to be marked changed.
+    all_changed: Option<ChangeCause>,
+    // The rest are from the affected closure: see `AffectedTrace`.
+    states: HashMap<&'g PackageId, FollowCargoBuild>,
+    parents: HashMap<TraceNode<'g>, (TraceNode<'g>, AffectedEdge)>,
+    all_rule: Option<(TraceNode<'g>, RuleMatch)>,
+}
+
+impl<'g> AffectedCauses<'g> {
+    fn explain(&self, package: PackageMetadata<'g>) -> AffectedExplanation<'g> {
+        if let Some(cause) = &self.all_changed {
+            return AffectedExplanation {
+                package,
+                cause: cause.clone(),
+                steps: vec![],
+            };
+        }
+
+        if let Some(cause) = self.changed.get(package.id()) {
+            return AffectedExplanation {
+                package,
+                cause: cause.clone(),
+                steps: vec![],
+            };
+        }
+
+        let metadata = |id| self.graph.metadata(id).expect("valid package ID");
+        let mut steps = vec![];
+        let mut node = match self.states.get(package.id()) {
+            Some(&follow) => (package.id(), follow),
+            None => {
+                // The package is only affected because a package rule marked all packages changed.
+                let (from, rule_match) = self
+                    .all_rule
+                    .as_ref()
+                    .expect("affected packages were either visited or marked changed by a rule");
+                steps.push(AffectedStep {
+                    from: metadata(from.0),
+                    to: package,
+                    edge: AffectedEdge::PackageRule(rule_match.clone()),
+                });
+                *from
+            }
+        };
+        while let Some((parent, edge)) = self.parents.get(&node) {
+            steps.push(AffectedStep {
+                from: metadata(parent.0),
+                to: metadata(node.0),
+                edge: edge.clone(),
+            });
+            node = *parent;
+        }
+        steps.reverse();
+
+        let cause = self
+            .changed
+            .get(node.0)
+            .expect("the start of every chain was marked changed")
+            .clone();
+        AffectedExplanation {
+            package,
+            cause,
+            steps,
+        }
+    }
+}
+
+/// Configures the Cargo build simulations run by a [`Determinator`].
+///
+/// The determinator simulates a build of every workspace package for each combination of feature
+/// set and platform in this configuration, in both the old and new graphs. Simulating fewer
+/// combinations trades some accuracy for speed: a dependency change that only shows up in a
+/// combination that isn't simulated won't be detected. On large workspaces, the
+/// [`none_only`](Self::none_only) mode is several times faster than the default.
+///
+/// Set through [`Determinator::set_config`].
+///
+/// # Examples
+///
+/// ```
+/// use determinator::DeterminatorConfig;
+/// use guppy::{graph::feature::StandardFeatures, platform::PlatformSpec};
+///
+/// // Only simulate builds with default features, on any platform.
+/// let mut config = DeterminatorConfig::new();
+/// config
+///     .set_features([StandardFeatures::Default])
+///     .add_platforms([PlatformSpec::Any]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DeterminatorConfig {
+    features: Vec<StandardFeatures>,
+    platforms: Vec<PlatformSpec>,
+}
+
+impl DeterminatorConfig {
+    /// Creates a new `DeterminatorConfig` with the default settings:
+    /// * simulate builds with no features, default features and all features
+    /// * use the platforms specified by the Cargo options
+    pub fn new() -> Self {
+        Self {
+            features: vec![
+                StandardFeatures::None,
+                StandardFeatures::Default,
+                StandardFeatures::All,
+            ],
+            platforms: vec![],
+        }
+    }
+
+    /// Creates a new `DeterminatorConfig` that only simulates builds with no features enabled, on
+    /// the platforms specified by the Cargo options.
+    ///
+    /// This is the fastest mode, but changes to dependencies that are only pulled in through
+    /// features aren't detected.
+    pub fn none_only() -> Self {
+        Self {
+            features: vec![StandardFeatures::None],
+            platforms: vec![],
+        }
+    }
+
+    /// Sets the feature sets to simulate builds for.
+    ///
+    /// Duplicates are removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `features` is empty.
+    pub fn set_features(
+        &mut self,
+        features: impl IntoIterator<Item = StandardFeatures>,
+    ) -> &mut Self {
+        let mut features: Vec<_> = features.into_iter().collect();
+        features.sort_unstable();
+        features.dedup();
+        assert!(
+            !features.is_empty(),
+            "at least one feature set must be simulated"
+        );
+        self.features = features;
+        self
+    }
+
+    /// Adds platforms to simulate builds on.
+    ///
+    /// Each platform is used as both the host and the target platform, overriding the platforms
+    /// specified by the Cargo options. If no platforms are added, the platforms specified by the
+    /// Cargo options are used.
+    pub fn add_platforms(
+        &mut self,
+        platforms: impl IntoIterator<Item = PlatformSpec>,
+    ) -> &mut Self {
+        self.platforms.extend(platforms);
+        self
+    }
+
+    /// Returns the feature sets that builds are simulated for.
+    pub fn features(&self) -> &[StandardFeatures] {
+        &self.features
+    }
+
+    /// Returns the platforms that builds are simulated on.
+    ///
+    /// If this is empty, the platforms specified by the Cargo options are used.
+    pub fn platforms(&self) -> &[PlatformSpec] {
+        &self.platforms
+    }
+
+    /// Returns the Cargo options for every platform to simulate builds on.
+    fn platform_options<'a>(&self, cargo_options: &CargoOptions<'a>) -> Vec<CargoOptions<'a>> {
+        if self.platforms.is_empty() {
+            return vec![cargo_options.clone()];
+        }
+        self.platforms
+            .iter()
+            .map(|platform| {
+                let mut cargo_options = cargo_options.clone();
+                cargo_options.set_platform(platform.clone());
+                cargo_options
+            })
+            .collect()
+    }
+}
+
+impl Default for DeterminatorConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // ---
 // Private structures
 // ---
 
+/// The old workspace in a `DeterminatorBase`.
+#[derive(Copy, Clone, Debug)]
+enum OldWorkspace<'a> {
+    Graph(&'a PackageGraph),
+    Summary(&'a BaseSummary),
+}
+
+/// Progress of Cargo build simulations, reported through
+/// [`Determinator::set_progress_callback`].
+#[derive(Copy, Clone, Debug)]
+pub struct DeterminatorProgress<'g> {
+    stage: ProgressStage,
+    package: PackageMetadata<'g>,
+    completed: usize,
+    total: usize,
+}
+
+impl<'g> DeterminatorProgress<'g> {
+    /// Returns the stage of work this progress is for.
+    pub fn stage(&self) -> ProgressStage {
+        self.stage
+    }
+
+    /// Returns the workspace package that was just processed.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the number of workspace packages processed so far in this stage, including this
+    /// one.
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Returns the total number of workspace packages to process in this stage.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+/// A stage of work reported through [`Determinator::set_progress_callback`].
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ProgressStage {
+    /// Cargo builds are being simulated for each package in the new workspace.
+    ///
+    /// This stage runs once for every call to [`Determinator::compute`],
+    /// [`Determinator::compute_bases`] or [`Determinator::base_summary`].
+    Simulate,
+
+    /// The build simulations for each package in the new workspace are being compared against
+    /// the base. If the base is a package graph, builds are also simulated for the old package.
+    ///
+    /// This stage runs once for every base, unless a path change caused every package to be
+    /// marked changed.
+    Compare,
+}
+
+/// A callback set through `Determinator::set_progress_callback`.
+#[derive(Clone)]
+struct ProgressCallback<'g, 'a>(Arc<dyn Fn(DeterminatorProgress<'g>) + Send + Sync + 'a>);
+
+impl<'g, 'a> fmt::Debug for ProgressCallback<'g, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Counts packages processed in a stage, and reports them to the progress callback if one is set.
+struct ProgressTracker<'b, 'g, 'a> {
+    callback: Option<&'b ProgressCallback<'g, 'a>>,
+    stage: ProgressStage,
+    completed: AtomicUsize,
+    total: usize,
+}
+
+impl<'b, 'g, 'a> ProgressTracker<'b, 'g, 'a> {
+    fn new(determinator: &'b Determinator<'g, 'a>, stage: ProgressStage) -> Self {
+        Self {
+            callback: determinator.progress_callback.as_ref(),
+            stage,
+            completed: AtomicUsize::new(0),
+            total: determinator.new.workspace().member_count(),
+        }
+    }
+
+    fn package_done(&self, package: PackageMetadata<'g>) {
+        if let Some(callback) = self.callback {
+            let completed = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+            (callback.0)(DeterminatorProgress {
+                stage: self.stage,
+                package,
+                completed,
+                total: self.total,
+            });
+        }
+    }
+}
+
+/// Features-only packages configured through `Determinator::set_features_only`.
+#[derive(Clone, Debug)]
+struct FeaturesOnly<'g> {
+    names: Vec<String>,
+    features: StandardFeatures,
+    new_set: FeatureSet<'g>,
+}
+
+/// State that only depends on the new graph, and can be shared across bases.
+#[derive(Debug)]
+struct SharedState<'g> {
+    build_cache: CargoBuildCache<'g>,
+    reverse_index: ReverseIndex<'g>,
+}
+
+impl<'g> SharedState<'g> {
+    fn new(determinator: &Determinator<'g, '_>) -> Self {
+        let build_cache = CargoBuildCache::new(determinator);
+        let reverse_index = ReverseIndex::new(determinator, &build_cache);
+        Self {
+            build_cache,
+            reverse_index,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct BuildState<'g, 'a, 'b> {
     determinator: &'b Determinator<'g, 'a>,
+    shared: &'b SharedState<'g>,
+    old: OldState<'b>,
     path_changed_ids: HashSet<&'g PackageId>,
     summary_changed_ids: HashSet<&'g PackageId>,
-    build_cache: CargoBuildCache<'g>,
-    reverse_index: ReverseIndex<'g>,
+    // Packages in path_changed_ids where only targets that other packages can't depend on changed.
+    changed_targets: BTreeMap<&'g PackageId, BTreeSet<BuildTargetId<'g>>>,
+    rule_matches: BTreeMap<&'g PackageId, BTreeSet<RuleMatch>>,
+    // The first path that marked each package in path_changed_ids changed.
+    change_causes: HashMap<&'g PackageId, ChangeCause>,
 }
 
 impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
-    fn new(determinator: &'b Determinator<'g, 'a>) -> Self {
-        let build_cache = CargoBuildCache::new(determinator);
-        let reverse_index = ReverseIndex::new(determinator, &build_cache);
+    fn new(
+        determinator: &'b Determinator<'g, 'a>,
+        shared: &'b SharedState<'g>,
+        base: &'b DeterminatorBase<'_>,
+    ) -> Self {
+        let old = match base.old {
+            OldWorkspace::Graph(graph) => {
+                // Package names that aren't in the old graph are ignored.
+                let features_only = match &determinator.features_only {
+                    Some(features_only) => {
+                        let old_workspace = graph.workspace();
+                        graph
+                            .resolve_workspace_names(
+                                features_only
+                                    .names
+                                    .iter()
+                                    .filter(|name| old_workspace.contains_name(name.as_str())),
+                            )
+                            .expect("old names were checked already")
+                            .to_feature_set(features_only.features)
+                    }
+                    None => graph.feature_graph().resolve_none(),
+                };
+                OldState::Graph {
+                    graph,
+                    features_only,
+                }
+            }
+            // Features-only packages were already accounted for when the summary was generated.
+            OldWorkspace::Summary(summary) => OldState::Summary(summary),
+        };
         Self {
             determinator,
+            shared,
+            old,
             path_changed_ids: HashSet::new(),
             summary_changed_ids: HashSet::new(),
-            build_cache,
-            reverse_index,
+            changed_targets: BTreeMap::new(),
+            rule_matches: BTreeMap::new(),
+            change_causes: HashMap::new(),
         }
     }
 
-    // A return value of None stands for all packages in the workspace changed.
-    fn process_path(mut self, path: &Utf8Path) -> Option<Self> {
+    // An error return value stands for all packages in the workspace changed, and contains the
+    // cause.
+    //
+    // Removed paths are looked up in the old workspace, then mapped to the new workspace by name.
+    fn process_path(mut self, path: &Utf8Path, removed: bool) -> Result<Self, ChangeCause> {
+        let old = &self.old;
+        let new = self.determinator.new;
+        let new_workspace = new.workspace();
+        let attribute_targets = self.determinator.attribute_targets;
+        let path_changed_ids = &mut self.path_changed_ids;
+        let changed_targets = &mut self.changed_targets;
+        let rule_matches = &mut self.rule_matches;
+        let change_causes = &mut self.change_causes;
         let status = process_path(
             path,
-            &self.determinator.new.workspace(),
-            &self.determinator.rules.path_rules,
-            |id| {
-                self.path_changed_ids.insert(id);
+            |ancestor| {
+                if removed {
+                    let old_name = old.member_by_path(ancestor)?;
+                    Some(
+                        new_workspace
+                            .member_by_name(old_name)
+                            .ok()
+                            .map(|package| package.id()),
+                    )
+                } else {
+                    Some(Some(new_workspace.member_by_path(ancestor).ok()?.id()))
+                }
+            },
+            &self.determinator.rules,
+            &self.determinator.path_deps,
+            |id, source| {
+                if let MatchSource::Rule(rule_match) = source {
+                    rule_matches
+                        .entry(id)
+                        .or_default()
+                        .insert(rule_match.clone());
+                }
+                change_causes.entry(id).or_insert_with(|| match source {
+                    MatchSource::Rule(rule_match) => ChangeCause::PathRule {
+                        path: path.to_owned(),
+                        rule_match: rule_match.clone(),
+                    },
+                    MatchSource::Ancestor => ChangeCause::Path(path.to_owned()),
+                    MatchSource::PathDependency => ChangeCause::PathDependency(path.to_owned()),
+                });
+                let targets = if attribute_targets && source == MatchSource::Ancestor {
+                    let package = new.metadata(id).expect("valid package ID");
+                    attribute_targets_for(package, path)
+                } else {
+                    None
+                };
+                // Only track targets if every earlier path for this package was also attributed to
+                // targets.
+                let first_change = path_changed_ids.insert(id);
+                match targets {
+                    Some(targets) if first_change => {
+                        changed_targets.insert(id, targets);
+                    }
+                    Some(targets) => {
+                        if let Some(existing) = changed_targets.get_mut(id) {
+                            existing.extend(targets);
+                        }
+                    }
+                    None => {
+                        changed_targets.remove(id);
+                    }
+                }
             },
         );
         match status {
-            PathMatch::RuleMatchedAll | PathMatch::NoMatches => None,
-            PathMatch::RuleMatched(_) | PathMatch::AncestorMatched => Some(self),
+            PathMatch::RuleMatchedAll => {
+                // Matching stops at the first rule that marks all packages changed.
+                let rule_match = self
+                    .determinator
+                    .rules
+                    .matching_path_rules(&Candidate::new(path))
+                    .find(|rule| matches!(rule.mark_changed, MarkChangedImpl::All))
+                    .map(|rule| rule.rule_match.clone());
+                Err(ChangeCause::AllChanged {
+                    path: path.to_owned(),
+                    rule_match,
+                })
+            }
+            PathMatch::NoMatches => Err(ChangeCause::AllChanged {
+                path: path.to_owned(),
+                rule_match: None,
+            }),
+            PathMatch::RuleMatched(_)
+            | PathMatch::AncestorMatched
+            | PathMatch::PathDependencyMatched
+            | PathMatch::WorkspaceExcluded => Ok(self),
         }
     }
 
     fn process_build_summaries(&mut self) {
         // For each workspace package, if its build summaries have changed mark it changed.
+        let progress = ProgressTracker::new(self.determinator, ProgressStage::Compare);
         let summary_changed_ids: Vec<_> = self
             .determinator
             .new
@@ -328,9 +1346,10 @@ impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
             .par_iter_by_name()
             .filter_map(|(name, package)| {
                 // Don't include packages already marked as changed through paths. (This is documented.)
-                if !self.path_changed_ids.contains(package.id())
-                    && self.build_summaries_changed(name, package)
-                {
+                let changed = !self.path_changed_ids.contains(package.id())
+                    && self.build_summaries_changed(name, package);
+                progress.package_done(package);
+                if changed {
                     Some(package.id())
                 } else {
                     None
@@ -341,88 +1360,252 @@ impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
     }
 
     fn build_summaries_changed(&self, name: &str, package: PackageMetadata<'g>) -> bool {
-        // Look up the package in the old metadata by path. (Workspace packages are uniquely
-        // identified by both name and path -- this could be done by name as well).
-        let old_workspace = self.determinator.old.workspace();
-        let old_package = match old_workspace.member_by_name(name) {
-            Ok(package) => package,
-            Err(_) => {
-                // Member not found: this is new or renamed.
-                return true;
-            }
-        };
-
         let default_options = Determinator::default_cargo_options();
         let cargo_options = self.determinator.cargo_options.unwrap_or(&default_options);
 
-        let default_features_only = self.determinator.old.feature_graph().resolve_none();
-        let features_only = self
-            .determinator
-            .old_features_only
-            .as_ref()
-            .unwrap_or(&default_features_only);
+        let platform_options = self.determinator.config.platform_options(cargo_options);
+        let new_result = &self.shared.build_cache.result_cache[package.id()];
+
+        // Look up the package in the old workspace by name. (Workspace packages are uniquely
+        // identified by both name and path -- this could be done by path as well).
+        match &self.old {
+            OldState::Graph {
+                graph,
+                features_only,
+            } => {
+                let old_package = match graph.workspace().member_by_name(name) {
+                    Ok(package) => package,
+                    Err(_) => {
+                        // Member not found: this is new or renamed.
+                        return true;
+                    }
+                };
+                let old_result = BuildResult::new(
+                    old_package,
+                    &self.determinator.config,
+                    &platform_options,
+                    features_only,
+                );
+                new_result.is_changed(&old_result, &platform_options)
+            }
+            OldState::Summary(summary) => match summary.packages.get(name) {
+                Some(old_package) => {
+                    new_result.fingerprints(&platform_options) != old_package.fingerprints
+                }
+                // Member not found: this is new or renamed.
+                None => true,
+            },
+        }
+    }
+}
+
+/// The old workspace, as used by a `BuildState`.
+#[derive(Debug)]
+enum OldState<'b> {
+    Graph {
+        graph: &'b PackageGraph,
+        features_only: FeatureSet<'b>,
+    },
+    Summary(&'b BaseSummary),
+}
 
-        let old_result = BuildResult::new(old_package, cargo_options, features_only);
-        let new_result = &self.build_cache.result_cache[package.id()];
-        new_result.is_changed(&old_result, cargo_options)
+impl<'b> OldState<'b> {
+    /// Returns the name of the old workspace package at this path.
+    fn member_by_path(&self, path: &Utf8Path) -> Option<&'b str> {
+        match self {
+            OldState::Graph { graph, .. } => {
+                Some(graph.workspace().member_by_path(path).ok()?.name())
+            }
+            OldState::Summary(summary) => summary.member_by_path(path),
+        }
     }
 }
 
+// `member_by_path` returns `Some(Some(id))` if the path is a workspace member, and `Some(None)` if
+// the path used to be a workspace member that has since been removed.
+//
+// `match_cb` is called with the reason each package was matched.
 fn process_path<'g>(
     path: &Utf8Path,
-    workspace: &Workspace<'g>,
-    path_rules: &[PathRuleImpl<'g>],
-    mut match_cb: impl FnMut(&'g PackageId),
+    mut member_by_path: impl FnMut(&Utf8Path) -> Option<Option<&'g PackageId>>,
+    rules: &RulesImpl<'g>,
+    path_deps: &PathDependencies<'g>,
+    mut match_cb: impl FnMut(&'g PackageId, MatchSource<'_>),
 ) -> PathMatch {
     let candidate = Candidate::new(path);
 
     // 1. Apply any rules that match the path.
-    for rule in path_rules {
-        if rule.glob_set.is_match_candidate(&candidate) {
-            // This glob matches this rule, so execute it.
-            match &rule.mark_changed {
-                MarkChangedImpl::Packages(packages) => {
-                    for package in packages {
-                        match_cb(package.id());
-                    }
-                }
-                MarkChangedImpl::All => {
-                    // Mark all packages changed.
-                    return PathMatch::RuleMatchedAll;
+    for rule in rules.matching_path_rules(&candidate) {
+        // This glob matches this rule, so execute it.
+        match &rule.mark_changed {
+            MarkChangedImpl::Packages(packages) => {
+                for package in packages {
+                    match_cb(package.id(), MatchSource::Rule(&rule.rule_match));
                 }
             }
+            MarkChangedImpl::All => {
+                // Mark all packages changed.
+                return PathMatch::RuleMatchedAll;
+            }
+        }
 
-            match &rule.post_rule {
-                DeterminatorPostRule::Skip => {
-                    // Skip all further processing for this path but continue reading other
-                    // paths.
-                    return PathMatch::RuleMatched(rule.rule_index);
-                }
-                DeterminatorPostRule::SkipRules => {
-                    // Skip further rule processing but continue to step 2 to match to the
-                    // nearest package.
-                    break;
-                }
-                DeterminatorPostRule::Fallthrough => {
-                    // Continue applying rules.
-                    continue;
-                }
+        match &rule.post_rule {
+            DeterminatorPostRule::Skip => {
+                // Skip all further processing for this path but continue reading other
+                // paths.
+                return PathMatch::RuleMatched(rule.rule_match.rule_index);
+            }
+            DeterminatorPostRule::SkipRules => {
+                // Skip further rule processing but continue to step 2 to match to the
+                // nearest package.
+                break;
+            }
+            DeterminatorPostRule::Fallthrough => {
+                // Continue applying rules.
+                continue;
             }
         }
     }
 
-    // 2. Map the path to its nearest ancestor package.
+    // 2. Map the path to its nearest ancestor package. Path dependencies and excluded directories
+    // may be nested inside workspace packages (e.g. the root package), so check for them at each
+    // level.
     for ancestor in path.ancestors() {
-        if let Ok(package) = workspace.member_by_path(ancestor) {
-            match_cb(package.id());
+        if let Some(package_id) = member_by_path(ancestor) {
+            if let Some(package_id) = package_id {
+                match_cb(package_id, MatchSource::Ancestor);
+            }
             return PathMatch::AncestorMatched;
         }
+        if let Some(dependents) = path_deps.dependents.get(ancestor) {
+            for &id in dependents {
+                match_cb(id, MatchSource::PathDependency);
+            }
+            return PathMatch::PathDependencyMatched;
+        }
+        if rules.workspace_exclude_set.is_match(ancestor) {
+            return PathMatch::WorkspaceExcluded;
+        }
     }
 
     // 3. If a file didn't match anything so far, rebuild everything.
     PathMatch::NoMatches
 }
 
+/// Why `process_path` matched a package.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum MatchSource<'r> {
+    /// A path rule marked the package changed.
+    Rule(&'r RuleMatch),
+    /// The package is the nearest ancestor of the path.
+    Ancestor,
+    /// The path is a path dependency of the package.
+    PathDependency,
+}
+
+/// Returns the build targets of `package` that `path` belongs to, if all of them are targets that
+/// other packages can't depend on.
+///
+/// Returns `None` if the path can't be attributed to any targets, or if it belongs to the library,
+/// the build script, or a binary that another package depends on through an artifact dependency.
+fn attribute_targets_for<'g>(
+    package: PackageMetadata<'g>,
+    path: &Utf8Path,
+) -> Option<BTreeSet<BuildTargetId<'g>>> {
+    let package_dir = package.manifest_path().parent()?;
+    let rel_path = path.strip_prefix(package.source().workspace_path()?).ok()?;
+
+    // The targets found so far, along with how closely they match the path: the number of
+    // components in the directory containing the source root, or usize::MAX for an exact match.
+    let mut best: Option<(usize, BTreeSet<BuildTargetId<'g>>)> = None;
+    for target in package.build_targets() {
+        // If a target is outside the package directory, any file in the package may belong to it.
+        let target_path = target.path().strip_prefix(package_dir).ok()?;
+        let closeness = if target_path == rel_path {
+            usize::MAX
+        } else {
+            match target_path.parent() {
+                // Source roots at the top level of the package, such as build.rs, only match
+                // exactly.
+                Some(dir) if !dir.as_str().is_empty() && rel_path.starts_with(dir) => {
+                    dir.components().count()
+                }
+                _ => continue,
+            }
+        };
+        match &mut best {
+            Some((best_closeness, targets)) if *best_closeness == closeness => {
+                targets.insert(target.id());
+            }
+            Some((best_closeness, _)) if *best_closeness > closeness => {}
+            _ => {
+                best = Some((closeness, iter::once(target.id()).collect()));
+            }
+        }
+    }
+
+    let (_, targets) = best?;
+    let dependable = targets.iter().any(|id| match id {
+        BuildTargetId::Library | BuildTargetId::BuildScript => true,
+        BuildTargetId::Binary(name) => is_artifact_dependency(package, name),
+        _ => false,
+    });
+    if dependable {
+        None
+    } else {
+        Some(targets)
+    }
+}
+
+/// Returns true if another package depends on the binary `name` of `package` through an artifact
+/// dependency, such as `artifact = "bin"`.
+fn is_artifact_dependency(package: PackageMetadata<'_>, name: &str) -> bool {
+    package.reverse_direct_links().any(|link| {
+        [link.normal(), link.build(), link.dev()]
+            .iter()
+            .filter_map(|req| req.artifact())
+            .flat_map(|artifact| artifact.kinds())
+            .any(|kind| match kind {
+                ArtifactKind::AllBinaries => true,
+                ArtifactKind::SelectedBinary(selected) => selected == name,
+                _ => false,
+            })
+    })
+}
+
+/// Path dependencies that aren't in the workspace, such as excluded crates or members of nested
+/// workspaces.
+#[derive(Clone, Debug)]
+struct PathDependencies<'g> {
+    // A map of the path relative to the workspace root to the workspace packages that depend on
+    // this path dependency.
+    dependents: HashMap<&'g Utf8Path, Vec<&'g PackageId>>,
+}
+
+impl<'g> PathDependencies<'g> {
+    fn new(graph: &'g PackageGraph) -> Self {
+        let dependents = graph
+            .packages()
+            .filter_map(|package| match package.source() {
+                PackageSource::Path(path) => {
+                    let dependents = graph
+                        .query_reverse(iter::once(package.id()))
+                        .expect("valid package ID")
+                        .resolve()
+                        .filter(DependencyDirection::Forward, |package| {
+                            package.in_workspace()
+                        })
+                        .package_ids(DependencyDirection::Forward)
+                        .collect();
+                    Some((path, dependents))
+                }
+                _ => None,
+            })
+            .collect();
+        Self { dependents }
+    }
+}
+
 /// Stores a build cache of every package in a workspace.
 #[derive(Debug)]
 struct CargoBuildCache<'g> {
@@ -437,15 +1620,31 @@ impl<'g> CargoBuildCache<'g> {
         let workspace = determinator.new.workspace();
         let default_features_only = determinator.new.feature_graph().resolve_none();
         let features_only = determinator
-            .new_features_only
+            .features_only
             .as_ref()
-            .unwrap_or(&default_features_only);
-
+            .map_or(&default_features_only, |features_only| {
+                &features_only.new_set
+            });
+
+        // Simulations for every workspace package evaluate the same dependency edges, so share
+        // platform evaluations across them.
+        let resolver_cache = determinator.new.new_cargo_resolver_cache();
+        let mut platform_options = determinator.config.platform_options(cargo_options);
+        for options in &mut platform_options {
+            options.set_resolver_cache(&resolver_cache);
+        }
+        let progress = ProgressTracker::new(determinator, ProgressStage::Simulate);
         let result_cache: HashMap<_, _> = workspace
             .par_iter()
             .map(|package| {
                 let id = package.id();
-                let build_result = BuildResult::new(package, cargo_options, features_only);
+                let build_result = BuildResult::new(
+                    package,
+                    &determinator.config,
+                    &platform_options,
+                    features_only,
+                );
+                progress.package_done(package);
                 (id, build_result)
             })
             .collect();
@@ -456,50 +1655,40 @@ impl<'g> CargoBuildCache<'g> {
 
 #[derive(Debug)]
 struct BuildResult<'g> {
-    none: CargoSet<'g>,
-    default: CargoSet<'g>,
-    all: CargoSet<'g>,
-    // TODO: add support for more feature sets?
+    // One for each combination of platform and feature set, along with the index of the platform
+    // options used to simulate it.
+    cargo_sets: Vec<(CargoSet<'g>, usize)>,
 }
 
 impl<'g> BuildResult<'g> {
     fn new(
         package: PackageMetadata<'g>,
-        cargo_options: &CargoOptions<'_>,
+        config: &DeterminatorConfig,
+        platform_options: &[CargoOptions<'_>],
         features_only: &FeatureSet<'g>,
     ) -> Self {
-        let (none, (default, all)) = rayon::join(
-            || {
-                make_cargo_set(
+        let combinations: Vec<_> = (0..platform_options.len())
+            .flat_map(|options_idx| {
+                config
+                    .features
+                    .iter()
+                    .map(move |&features| (options_idx, features))
+            })
+            .collect();
+        let cargo_sets = combinations
+            .into_par_iter()
+            .map(|(options_idx, features)| {
+                let cargo_set = make_cargo_set(
                     &package,
-                    StandardFeatures::None,
-                    cargo_options,
+                    features,
+                    &platform_options[options_idx],
                     features_only,
-                )
-            },
-            || {
-                rayon::join(
-                    || {
-                        make_cargo_set(
-                            &package,
-                            StandardFeatures::Default,
-                            cargo_options,
-                            features_only,
-                        )
-                    },
-                    || {
-                        make_cargo_set(
-                            &package,
-                            StandardFeatures::All,
-                            cargo_options,
-                            features_only,
-                        )
-                    },
-                )
-            },
-        );
+                );
+                (cargo_set, options_idx)
+            })
+            .collect();
 
-        Self { none, default, all }
+        Self { cargo_sets }
     }
 
     /// Returns the unified set of workspace dependencies.
@@ -518,8 +1707,9 @@ impl<'g> BuildResult<'g> {
         target_set.union(&host_set).intersection(workspace_set)
     }
 
-    fn is_changed(&self, other: &BuildResult<'_>, cargo_options: &CargoOptions<'_>) -> bool {
-        for (a, b) in self.all_cargo_sets().zip(other.all_cargo_sets()) {
+    fn is_changed(&self, other: &BuildResult<'_>, platform_options: &[CargoOptions<'_>]) -> bool {
+        for ((a, options_idx), (b, _)) in self.cargo_sets.iter().zip(&other.cargo_sets) {
+            let cargo_options = &platform_options[*options_idx];
             let a_summary = a
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
@@ -534,10 +1724,21 @@ impl<'g> BuildResult<'g> {
         false
     }
 
+    /// Returns fingerprints for each simulated build, for use in a [`BaseSummary`].
+    fn fingerprints(&self, platform_options: &[CargoOptions<'_>]) -> Vec<String> {
+        self.cargo_sets
+            .iter()
+            .map(|(cargo_set, options_idx)| {
+                let summary = cargo_set
+                    .to_summary(&platform_options[*options_idx])
+                    .expect("custom platforms currently unsupported");
+                fingerprint(&summary)
+            })
+            .collect()
+    }
+
     fn all_cargo_sets<'a>(&'a self) -> impl Iterator<Item = &'a CargoSet<'g>> + 'a {
-        std::iter::once(&self.none)
-            .chain(std::iter::once(&self.default))
-            .chain(std::iter::once(&self.all))
+        self.cargo_sets.iter().map(|(cargo_set, _)| cargo_set)
     }
 }
 
@@ -563,8 +1764,9 @@ struct ReverseIndex<'g> {
 /// Edges in the reverse index graph.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ReverseIndexEdge {
-    /// This edge was added as a package rule. This always takes precedence over `CargoBuild`.
-    PackageRule,
+    /// This edge was added as the package rule at this index. This always takes precedence over
+    /// `CargoBuild`.
+    PackageRule(usize),
     /// This edge was added through the Cargo build cache.
     CargoBuild,
 }
@@ -587,7 +1789,7 @@ impl<'g> ReverseIndex<'g> {
 
         // Now, look at all the package rules and add anything in them to the reverse index.
         // IMPORTANT: This comes later so that PackageRule edges overwrite CargoBuild edges.
-        for package_rule in &determinator.rules.package_rules {
+        for (index, package_rule) in determinator.rules.package_rules.iter().enumerate() {
             for on_affected in package_rule
                 .on_affected
                 .package_ids(DependencyDirection::Forward)
@@ -599,7 +1801,7 @@ impl<'g> ReverseIndex<'g> {
                             (
                                 Some(on_affected),
                                 Some(package.id()),
-                                ReverseIndexEdge::PackageRule,
+                                ReverseIndexEdge::PackageRule(index),
                             )
                         }));
                     }
@@ -608,7 +1810,7 @@ impl<'g> ReverseIndex<'g> {
                         reverse_index.add_edge(
                             Some(on_affected),
                             None,
-                            ReverseIndexEdge::PackageRule,
+                            ReverseIndexEdge::PackageRule(index),
                         );
                     }
                 }
@@ -623,16 +1825,13 @@ impl<'g> ReverseIndex<'g> {
         package_graph: &'g PackageGraph,
         path_changed: &HashSet<&'g PackageId>,
         summary_changed: &HashSet<&'g PackageId>,
-    ) -> PackageSet<'g> {
+        changed_targets: &BTreeMap<&'g PackageId, BTreeSet<BuildTargetId<'g>>>,
+    ) -> (PackageSet<'g>, AffectedTrace<'g>) {
         // This is a *really* interesting DFS, in that there's one restriction: you can't follow
         // two CargoBuild edges consecutively. Also, in the initial set, path_changed allows
         // CargoBuild to be followed once while summary_changed doesn't allow it to be followed.
-
-        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-        enum FollowCargoBuild {
-            Allowed,
-            NotAllowed,
-        }
+        // Packages in changed_targets only had targets other packages can't depend on change, so
+        // they're treated like summary_changed.
 
         use FollowCargoBuild::*;
 
@@ -641,13 +1840,24 @@ impl<'g> ReverseIndex<'g> {
         let mut stack: Vec<_> = summary_changed
             .iter()
             .map(|id| (*id, NotAllowed))
-            .chain(path_changed.iter().map(|id| (*id, Allowed)))
+            .chain(path_changed.iter().map(|id| {
+                if changed_targets.contains_key(id) {
+                    (*id, NotAllowed)
+                } else {
+                    (*id, Allowed)
+                }
+            }))
             .collect();
 
         // Do a DFS with two maps, in case there are cycles (can happen with dev deps).
         let mut discovered = HashMap::new();
         let mut finished = HashSet::new();
 
+        // Every node is pushed onto the stack from the first node it was reached from, so the
+        // parents recorded here form a tree rooted at the initial nodes.
+        let mut parents = HashMap::new();
+        let mut pushed: HashSet<_> = stack.iter().copied().collect();
+
         while let Some(&(id, follow)) = stack.last() {
             let push_neighbors = match discovered.entry(id) {
                 Entry::Vacant(entry) => {
@@ -682,27 +1892,41 @@ impl<'g> ReverseIndex<'g> {
                         Some(neighbor) => {
                             let neighbor_follow = match edge {
                                 ReverseIndexEdge::CargoBuild => NotAllowed,
-                                ReverseIndexEdge::PackageRule => Allowed,
+                                ReverseIndexEdge::PackageRule(_) => Allowed,
                             };
 
-                            match (discovered.get(&neighbor), neighbor_follow) {
-                                (None, _) => {
-                                    // Node has not been discovered yet. Add it to the stack to
-                                    // be visited.
-                                    stack.push((neighbor, neighbor_follow))
-                                }
-                                (Some(NotAllowed), Allowed) => {
-                                    // Node was previously discovered with NotAllowed but is
-                                    // now discovered with Allowed. This is an upgrade. Add it to
-                                    // the stack to be visited.
-                                    stack.push((neighbor, neighbor_follow))
+                            let push = match (discovered.get(&neighbor), neighbor_follow) {
+                                // Node has not been discovered yet. Add it to the stack to be
+                                // visited.
+                                (None, _) => true,
+                                // Node was previously discovered with NotAllowed but is now
+                                // discovered with Allowed. This is an upgrade. Add it to the
+                                // stack to be visited.
+                                (Some(NotAllowed), Allowed) => true,
+                                _ => false,
+                            };
+                            if push {
+                                let node = (neighbor, neighbor_follow);
+                                if pushed.insert(node) {
+                                    parents.insert(node, ((id, follow), edge));
                                 }
-                                _ => {}
+                                stack.push(node);
                             }
                         }
                         None => {
                             // Build everything, can just exit here.
-                            return package_graph.resolve_workspace();
+                            let rule_index = match edge {
+                                ReverseIndexEdge::PackageRule(index) => index,
+                                ReverseIndexEdge::CargoBuild => {
+                                    unreachable!("only package rules mark all packages changed")
+                                }
+                            };
+                            let trace = AffectedTrace {
+                                states: discovered,
+                                parents,
+                                all_rule: Some(((id, follow), rule_index)),
+                            };
+                            return (package_graph.resolve_workspace(), trace);
                         }
                     }
                 }
@@ -713,8 +1937,37 @@ impl<'g> ReverseIndex<'g> {
         }
 
         // At the end of this process, finished contains all nodes discovered.
-        package_graph
+        let affected_set = package_graph
             .resolve_ids(finished.iter().copied())
-            .expect("all IDs are valid")
+            .expect("all IDs are valid");
+        let trace = AffectedTrace {
+            states: discovered,
+            parents,
+            all_rule: None,
+        };
+        (affected_set, trace)
     }
 }
+
+/// Whether a `CargoBuild` edge can be followed from a node in the affected closure.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+enum FollowCargoBuild {
+    Allowed,
+    NotAllowed,
+}
+
+/// A node visited while computing the affected closure.
+type TraceNode<'g> = (&'g PackageId, FollowCargoBuild);
+
+/// How the nodes in the affected closure were reached.
+#[derive(Debug)]
+struct AffectedTrace<'g> {
+    // The state each visited package was last discovered with.
+    states: HashMap<&'g PackageId, FollowCargoBuild>,
+    // For each visited node other than the initial ones, the node it was first reached from and
+    // the edge followed.
+    parents: HashMap<TraceNode<'g>, (TraceNode<'g>, ReverseIndexEdge)>,
+    // If a package rule marked all packages changed, the node that triggered it and the index of
+    // the rule.
+    all_rule: Option<(TraceNode<'g>, usize)>,
+}
@@ -4,7 +4,8 @@
 //! Error types returned by the determinator.
 
 use crate::rules::RuleIndex;
-use std::{error, fmt};
+use camino::Utf8PathBuf;
+use std::{error, fmt, io, str::Utf8Error};
 
 /// An error that occurred while resolving a set of determinator rules.
 #[derive(Debug)]
@@ -35,6 +36,20 @@ impl RulesError {
         }
     }
 
+    pub(crate) fn package_metadata(rule_index: RuleIndex, err: guppy::Error) -> Self {
+        Self {
+            rule_index,
+            kind: RulesErrorKind::PackageMetadata(err),
+        }
+    }
+
+    pub(crate) fn conflict(rule_index: RuleIndex, glob: String, other: RuleIndex) -> Self {
+        Self {
+            rule_index,
+            kind: RulesErrorKind::Conflict { glob, other },
+        }
+    }
+
     pub(crate) fn glob_parse(rule_index: RuleIndex, err: globset::Error) -> Self {
         let kind = RulesErrorKind::GlobParse {
             glob: err.glob().map(|s| s.to_owned()),
@@ -58,7 +73,9 @@ impl error::Error for RulesError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match &self.kind {
             RulesErrorKind::ResolveRef(err) => Some(err),
+            RulesErrorKind::PackageMetadata(err) => Some(err),
             RulesErrorKind::GlobParse { err, .. } => Some(&**err),
+            RulesErrorKind::Conflict { .. } => None,
         }
     }
 }
@@ -77,6 +94,17 @@ pub enum RulesErrorKind {
         /// The error that occurred while parsing the glob.
         err: Box<dyn error::Error + Send + Sync>,
     },
+
+    /// The `[package.metadata.determinator]` section of a workspace package couldn't be parsed.
+    PackageMetadata(guppy::Error),
+
+    /// A glob in package metadata is also specified by another rule.
+    Conflict {
+        /// The glob specified by both rules.
+        glob: String,
+        /// The other rule the glob is specified by.
+        other: RuleIndex,
+    },
 }
 
 impl fmt::Display for RulesErrorKind {
@@ -90,6 +118,116 @@ impl fmt::Display for RulesErrorKind {
             RulesErrorKind::GlobParse { glob: None, err } => {
                 write!(f, "while parsing a glob: {}", err)
             }
+            RulesErrorKind::PackageMetadata(err) => write!(f, "{}", err),
+            RulesErrorKind::Conflict { glob, other } => {
+                write!(f, "glob '{}' is also specified by {}", glob, other)
+            }
+        }
+    }
+}
+
+/// An error that occurred while parsing the output of Git's `--name-status -z` option.
+///
+/// Returned by [`PathChange::parse_name_status`](crate::PathChange::parse_name_status).
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum NameStatusParseError {
+    /// An unknown status letter was encountered.
+    UnknownStatus(String),
+
+    /// The output ended before all the paths for a status were read.
+    MissingPath {
+        /// The status that was missing a path.
+        status: String,
+    },
+}
+
+impl fmt::Display for NameStatusParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameStatusParseError::UnknownStatus(status) => {
+                write!(f, "unknown status '{}' in name-status output", status)
+            }
+            NameStatusParseError::MissingPath { status } => {
+                write!(
+                    f,
+                    "missing path for status '{}' in name-status output",
+                    status
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for NameStatusParseError {}
+
+/// An error that occurred while obtaining changes from Git.
+///
+/// Returned by [`GitChanges::between`](crate::GitChanges::between).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GitError {
+    /// The `git` executable couldn't be run.
+    Exec {
+        /// The command that was run.
+        command: String,
+        /// The error that occurred.
+        err: io::Error,
+    },
+
+    /// A `git` command exited with a non-zero status.
+    CommandFailed {
+        /// The command that was run.
+        command: String,
+        /// The directory the command was run in.
+        dir: Utf8PathBuf,
+        /// The standard error output of the command.
+        stderr: String,
+    },
+
+    /// A `git` command produced output that wasn't valid UTF-8.
+    NonUtf8Output {
+        /// The command that was run.
+        command: String,
+        /// The error that occurred.
+        err: Utf8Error,
+    },
+
+    /// The output of `git diff --raw` couldn't be parsed. Contains the entry that failed to parse.
+    InvalidRawOutput(String),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GitError::Exec { command, .. } => write!(f, "failed to execute '{}'", command),
+            GitError::CommandFailed {
+                command,
+                dir,
+                stderr,
+            } => write!(
+                f,
+                "'{}' failed in directory '{}': {}",
+                command,
+                dir,
+                stderr.trim_end()
+            ),
+            GitError::NonUtf8Output { command, .. } => {
+                write!(f, "'{}' produced non-UTF-8 output", command)
+            }
+            GitError::InvalidRawOutput(entry) => {
+                write!(f, "invalid entry '{}' in raw diff output", entry)
+            }
+        }
+    }
+}
+
+impl error::Error for GitError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            GitError::Exec { err, .. } => Some(err),
+            GitError::NonUtf8Output { err, .. } => Some(err),
+            GitError::CommandFailed { .. } | GitError::InvalidRawOutput(_) => None,
         }
     }
 }
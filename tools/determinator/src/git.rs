@@ -0,0 +1,311 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Obtaining file changes between two revisions from Git.
+
+use crate::{errors::GitError, PathChange, Utf8Paths0};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::process::Command;
+
+/// The file changes between two revisions of a Git repository.
+///
+/// This runs the `git` executable found on `PATH`, and is available if the `git` feature is
+/// enabled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use determinator::{Determinator, GitChanges};
+/// use guppy::MetadataCommand;
+///
+/// // Compare the working directory against the merge base of origin/main and HEAD.
+/// let changes = GitChanges::between(".", "origin/main", None).unwrap();
+///
+/// // The package graph for the old revision would typically be obtained by checking it out into
+/// // a separate directory. This example uses the same graph for both revisions.
+/// let graph = MetadataCommand::new().build_graph().unwrap();
+/// let mut determinator = Determinator::new(&graph, &graph);
+/// determinator.add_changes(changes.changes());
+/// let determinator_set = determinator.compute();
+/// ```
+#[derive(Clone, Debug)]
+pub struct GitChanges {
+    merge_base: String,
+    // Changes in the format produced by `git diff -z --name-status`.
+    name_status: Utf8Paths0,
+}
+
+impl GitChanges {
+    /// Computes the file changes between the merge base of `old_rev` and `new_rev`, and
+    /// `new_rev`.
+    ///
+    /// If `new_rev` is `None`, changes are computed between the merge base of `old_rev` and `HEAD`,
+    /// and the working directory. This includes uncommitted changes, but not untracked files.
+    ///
+    /// `repo_root` must be the root of the repository, and is assumed to be the same as the root
+    /// of the Cargo workspace. Renames are detected, and changes within submodules that are
+    /// checked out are reported as changes to the individual files within them. Submodules that
+    /// aren't checked out are reported as a change to the submodule path itself.
+    pub fn between(
+        repo_root: impl AsRef<Utf8Path>,
+        old_rev: &str,
+        new_rev: Option<&str>,
+    ) -> Result<Self, GitError> {
+        let repo_root = repo_root.as_ref();
+        let merge_base = run_git(
+            repo_root,
+            &["merge-base", old_rev, new_rev.unwrap_or("HEAD")],
+        )?
+        .trim()
+        .to_owned();
+
+        let mut name_status = String::new();
+        diff_impl(
+            repo_root,
+            Utf8Path::new(""),
+            &merge_base,
+            new_rev,
+            &mut name_status,
+        )?;
+
+        Ok(Self {
+            merge_base,
+            name_status: Utf8Paths0::new_forward_slashes(name_status),
+        })
+    }
+
+    /// Returns the merge base that changes were computed against, as a full commit hash.
+    pub fn merge_base(&self) -> &str {
+        &self.merge_base
+    }
+
+    /// Returns the list of changes, suitable for passing into
+    /// [`Determinator::add_changes`](crate::Determinator::add_changes).
+    pub fn changes(&self) -> Vec<PathChange<'_>> {
+        PathChange::parse_name_status(&self.name_status)
+            .expect("name-status output generated by GitChanges is valid")
+    }
+
+    /// Returns the paths that changed in either revision, suitable for passing into
+    /// [`Determinator::add_changed_paths`](crate::Determinator::add_changed_paths).
+    ///
+    /// For renames, both the old and the new paths are returned.
+    pub fn changed_paths(&self) -> Vec<&Utf8Path> {
+        let mut paths: Vec<_> = self
+            .changes()
+            .into_iter()
+            .flat_map(|change| change.old_path().into_iter().chain(change.new_path()))
+            .collect();
+        paths.sort_unstable();
+        paths.dedup();
+        paths
+    }
+}
+
+// Git uses this hash for "no object", e.g. the old side of an added file.
+const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+// The mode Git uses for submodules.
+const GITLINK_MODE: &str = "160000";
+
+/// Appends the changes in the repository at `dir` to `out`, with paths prefixed by `prefix`.
+fn diff_impl(
+    dir: &Utf8Path,
+    prefix: &Utf8Path,
+    old_rev: &str,
+    new_rev: Option<&str>,
+    out: &mut String,
+) -> Result<(), GitError> {
+    let mut args = vec![
+        "diff",
+        "-z",
+        "--raw",
+        "--no-abbrev",
+        "--find-renames",
+        "--no-ext-diff",
+        "--ignore-submodules=none",
+        old_rev,
+    ];
+    args.extend(new_rev);
+    args.push("--");
+    let output = run_git(dir, &args)?;
+
+    for entry in parse_raw(&output)? {
+        let is_submodule = entry.old_mode == GITLINK_MODE && entry.new_mode == GITLINK_MODE;
+        if is_submodule {
+            let submodule_dir = dir.join(entry.path);
+            // A submodule that isn't checked out has no .git file or directory.
+            if submodule_dir.join(".git").exists() {
+                // An all-zero hash means the submodule was changed in the working directory.
+                let submodule_new = match new_rev {
+                    Some(_) if entry.new_hash != NULL_HASH => Some(entry.new_hash),
+                    _ => None,
+                };
+                diff_impl(
+                    &submodule_dir,
+                    &prefix.join(entry.path),
+                    entry.old_hash,
+                    submodule_new,
+                    out,
+                )?;
+                continue;
+            }
+        }
+
+        out.push_str(entry.status);
+        out.push('\0');
+        push_path(out, prefix, entry.path);
+        if let Some(new_path) = entry.new_path {
+            push_path(out, prefix, new_path);
+        }
+    }
+
+    Ok(())
+}
+
+fn push_path(out: &mut String, prefix: &Utf8Path, path: &str) {
+    if prefix.as_str().is_empty() {
+        out.push_str(path);
+    } else {
+        // Git always uses forward slashes, so use them here as well.
+        out.push_str(prefix.as_str());
+        out.push('/');
+        out.push_str(path);
+    }
+    out.push('\0');
+}
+
+fn run_git(dir: &Utf8Path, args: &[&str]) -> Result<String, GitError> {
+    let dir: Utf8PathBuf = if dir.as_str().is_empty() {
+        ".".into()
+    } else {
+        dir.to_owned()
+    };
+    let command_str = format!("git {}", args.join(" "));
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(&dir)
+        .args(args)
+        .output()
+        .map_err(|err| GitError::Exec {
+            command: command_str.clone(),
+            err,
+        })?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: command_str,
+            dir,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+    String::from_utf8(output.stdout).map_err(|err| GitError::NonUtf8Output {
+        command: command_str,
+        err: err.utf8_error(),
+    })
+}
+
+/// An entry in the output of `git diff -z --raw`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct RawEntry<'a> {
+    old_mode: &'a str,
+    new_mode: &'a str,
+    old_hash: &'a str,
+    new_hash: &'a str,
+    status: &'a str,
+    path: &'a str,
+    // Present for renames and copies.
+    new_path: Option<&'a str>,
+}
+
+/// Parses the output of `git diff -z --raw`, which looks like:
+///
+/// ```text
+/// :100644 100644 <old hash> <new hash> M\0path\0:100644 100644 <old hash> <new hash> R086\0old\0new\0
+/// ```
+fn parse_raw(output: &str) -> Result<Vec<RawEntry<'_>>, GitError> {
+    let mut fields = output.split('\0').filter(|field| !field.is_empty());
+    let mut entries = vec![];
+    while let Some(header) = fields.next() {
+        let invalid = || GitError::InvalidRawOutput(header.to_owned());
+        let mut parts = header.strip_prefix(':').ok_or_else(invalid)?.split(' ');
+        let mut next_part = || parts.next().ok_or_else(invalid);
+        let old_mode = next_part()?;
+        let new_mode = next_part()?;
+        let old_hash = next_part()?;
+        let new_hash = next_part()?;
+        let status = next_part()?;
+
+        let path = fields.next().ok_or_else(invalid)?;
+        let new_path = match status.as_bytes().first() {
+            Some(b'R') | Some(b'C') => Some(fields.next().ok_or_else(invalid)?),
+            _ => None,
+        };
+        entries.push(RawEntry {
+            old_mode,
+            new_mode,
+            old_hash,
+            new_hash,
+            status,
+            path,
+            new_path,
+        });
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_raw_output() {
+        let hash_a = "1111111111111111111111111111111111111111";
+        let hash_b = "2222222222222222222222222222222222222222";
+        let output = format!(
+            ":100644 100644 {a} {b} M\0a/lib.rs\0\
+             :100644 100644 {a} {a} R100\0b/x.rs\0c/x.rs\0\
+             :160000 160000 {a} {null} M\0sub\0",
+            a = hash_a,
+            b = hash_b,
+            null = NULL_HASH,
+        );
+        let entries = parse_raw(&output).expect("valid output");
+        assert_eq!(
+            entries,
+            vec![
+                RawEntry {
+                    old_mode: "100644",
+                    new_mode: "100644",
+                    old_hash: hash_a,
+                    new_hash: hash_b,
+                    status: "M",
+                    path: "a/lib.rs",
+                    new_path: None,
+                },
+                RawEntry {
+                    old_mode: "100644",
+                    new_mode: "100644",
+                    old_hash: hash_a,
+                    new_hash: hash_a,
+                    status: "R100",
+                    path: "b/x.rs",
+                    new_path: Some("c/x.rs"),
+                },
+                RawEntry {
+                    old_mode: GITLINK_MODE,
+                    new_mode: GITLINK_MODE,
+                    old_hash: hash_a,
+                    new_hash: NULL_HASH,
+                    status: "M",
+                    path: "sub",
+                    new_path: None,
+                },
+            ]
+        );
+
+        assert_eq!(parse_raw("").expect("empty output is valid"), vec![]);
+        assert!(matches!(
+            parse_raw("a/lib.rs\0"),
+            Err(GitError::InvalidRawOutput(header)) if header == "a/lib.rs"
+        ));
+    }
+}
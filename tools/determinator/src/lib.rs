@@ -41,8 +41,41 @@
 //! for package in determinator_set.affected_set.packages(DependencyDirection::Forward) {
 //!     println!("affected: {}", package.name());
 //! }
+//!
+//! // DeterminatorSet::why explains how a package came to be affected, starting from the change.
+//! if let Some(explanation) = determinator_set.why("guppy").unwrap() {
+//!     println!("{}", explanation);
+//! }
+//!
+//! // For output that's stable across runs, such as CI logs, use DeterminatorSet::sorted_names.
+//! // DeterminatorSet::write_summary writes out the affected set as a guppy summary for downstream
+//! // jobs.
+//! println!("affected, sorted: {}", determinator_set.sorted_names().join(", "));
+//! ```
+//!
+//! # Command-line interface
+//!
+//! With the `cli` feature enabled, this crate also provides a `determinator` binary. For example:
+//!
+//! ```sh
+//! cargo install determinator --features cli
+//! git diff -z --name-only $(git merge-base origin/main HEAD) |
+//!     determinator run -z --old old-metadata.json --new new-metadata.json
 //! ```
 //!
+//! The `run` subcommand prints the names of affected packages in sorted order, `explain <package>`
+//! describes why a package was marked affected, and `rules check <file>` validates a rules file.
+//! Pass in `--summary-out <path>` to `run` to also write out the affected set as a
+//! [guppy summary](https://github.com/facebookincubator/cargo-guppy/tree/main/guppy-summaries).
+//!
+//! # Using a summary instead of the old graph
+//!
+//! `cargo metadata` output for the base revision can be tens of megabytes in size. Instead of
+//! storing it, a compact [`BaseSummary`] may be generated at the base revision with
+//! [`Determinator::base_summary`], then passed in through [`Determinator::with_base_summary`].
+//! On the command line, use `determinator summarize` at the base revision and pass its output in
+//! through `--old-summary`.
+//!
 //! # Platform support
 //!
 //! * **Unix platforms**: The determinator works and is supported.
@@ -72,6 +105,21 @@
 //! gnarly edge cases. For more information, see the documentation for
 //! [`Utf8Paths0`](crate::Utf8Paths0).
 //!
+//! If information about renamed and deleted files is available, it can be passed in through
+//! [`add_changes`](crate::Determinator::add_changes). Paths that no longer exist are then
+//! attributed to the package they were in in the old revision, so moving a file from one package
+//! to another marks both packages changed.
+//!
+//! By default, a changed file marks everything that depends on its package affected, even if the
+//! file belongs to a test or benchmark that no other package can depend on. With
+//! [`set_attribute_targets`](crate::Determinator::set_attribute_targets), changed files are instead
+//! attributed to the build targets they belong to, and changes limited to tests, benchmarks,
+//! examples and binaries don't affect dependents (unless the binaries are artifact dependencies
+//! of other packages).
+//!
+//! With the `git` feature enabled, [`GitChanges`](crate::GitChanges) runs Git to obtain these
+//! changes between two revisions, computing the merge base and looking inside submodules.
+//!
 //! These simple rules may need to be customized for particular scenarios (e.g. to ignore certain
 //! files, or mark a package changed if a file outside of it changes). For those situations, the
 //! determinator lets you specify *custom rules*. See the
@@ -91,11 +139,15 @@
 //! * dev-dependencies enabled (by default; this can be customized)
 //! * both the host and target platforms set to the current platform (by default; this can be
 //!   customized)
-//! * three sets of features for each package:
+//! * three sets of features for each package (by default; this can be customized):
 //!   * no features enabled
 //!   * default features
 //!   * all features enabled
 //!
+//! On large workspaces, these simulations can take a while. A
+//! [`DeterminatorConfig`](crate::DeterminatorConfig) controls which feature sets and platforms are
+//! simulated, trading some accuracy for speed.
+//!
 //! If any of these simulated builds indicates that a workspace package has had any dependency
 //! changes, then it is marked changed.
 //!
@@ -200,9 +252,10 @@
 //! without a version bump. `cargo build` can recognize those changes because it compares mtimes of
 //! files on disk, but the determinator cannot do that.
 //!
-//! This is not expected to be a problem for most projects that use workspaces. If
-//! there's future demand, it would be possible to add support for changes to non-workspace path
-//! dependencies if they're in the same repository.
+//! This is not expected to be a problem for most projects that use workspaces. Path dependencies
+//! within the workspace root, such as crates listed in `workspace.exclude` or members of nested
+//! workspaces, are handled by matching changed files to them: the workspace packages that depend on
+//! them are marked changed. Path dependencies outside the workspace root are not tracked.
 //!
 //! # Alternatives and tradeoffs
 //!
@@ -244,9 +297,15 @@
 //! This determinator is inspired by, and shares its name with, the target determinator used in
 //! Facebook's main source repository.
 
+mod base_summary;
+mod changes;
 mod determinator;
 pub mod errors;
+#[cfg(feature = "git")]
+mod git;
 mod paths0;
 pub mod rules;
 
-pub use crate::{determinator::*, paths0::*};
+pub use crate::{base_summary::*, changes::*, determinator::*, paths0::*};
+#[cfg(feature = "git")]
+pub use git::*;
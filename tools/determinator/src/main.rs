@@ -0,0 +1,372 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Command-line interface for the determinator.
+//!
+//! This binary is only built if the `cli` feature is enabled.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{ArgEnum, Parser};
+use color_eyre::eyre::{bail, Result, WrapErr};
+use determinator::{
+    rules::DeterminatorRules, BaseSummary, Determinator, DeterminatorProgress, ProgressStage,
+    Utf8Paths0,
+};
+use guppy::{
+    graph::{DependencyDirection, PackageGraph, PackageMetadata, PackageSet},
+    CargoMetadata,
+};
+use std::{fs, io::Read, sync::Mutex};
+
+/// Figure out which packages in a workspace changed between two revisions.
+///
+/// For more about the determinator, see <https://docs.rs/determinator>.
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Parser)]
+enum Command {
+    /// Compute the set of packages affected by a change
+    ///
+    /// Changed paths, relative to the workspace root, are read from standard input. The names of
+    /// the selected workspace packages are printed to standard output, one per line.
+    Run {
+        #[clap(flatten)]
+        inputs: DeterminatorInputs,
+
+        /// The set of packages to print
+        #[clap(long, arg_enum, default_value = "affected")]
+        set: SetKind,
+
+        /// Print the rules that marked each package changed after its name
+        #[clap(long)]
+        show_rules: bool,
+
+        /// Also write a summary of the affected set to this path, for downstream jobs
+        ///
+        /// The summary is in the guppy-summaries format, with every affected package listed as an
+        /// initial target package.
+        #[clap(long, value_name = "PATH")]
+        summary_out: Option<Utf8PathBuf>,
+    },
+
+    /// Generate a compact summary of a workspace, for use with --old-summary
+    ///
+    /// The summary is printed to standard output. It must be generated with the same rules as
+    /// the ones it's later used with.
+    Summarize {
+        /// Path to `cargo metadata` JSON output for the workspace
+        #[clap(long)]
+        metadata: Utf8PathBuf,
+
+        /// Path to a rules file (default: the built-in default rules)
+        #[clap(long)]
+        rules: Option<Utf8PathBuf>,
+    },
+
+    /// Work with determinator rules
+    #[clap(subcommand)]
+    Rules(RulesCommand),
+
+    /// Explain why a package was marked affected
+    ///
+    /// Changed paths, relative to the workspace root, are read from standard input.
+    Explain {
+        /// The name of the workspace package to explain
+        package: String,
+
+        #[clap(flatten)]
+        inputs: DeterminatorInputs,
+    },
+}
+
+#[derive(Debug, Parser)]
+enum RulesCommand {
+    /// Check that a rules file is valid
+    Check {
+        /// Path to the rules file
+        rules: Utf8PathBuf,
+
+        /// Path to `cargo metadata` JSON output to also resolve the rules against
+        #[clap(long)]
+        metadata: Option<Utf8PathBuf>,
+    },
+}
+
+#[derive(Debug, Parser)]
+struct DeterminatorInputs {
+    /// Path to `cargo metadata` JSON output for the old revision
+    #[clap(long, required_unless_present = "old-summary")]
+    old: Option<Utf8PathBuf>,
+
+    /// Path to a summary of the old revision generated by `determinator summarize`
+    #[clap(long, conflicts_with = "old")]
+    old_summary: Option<Utf8PathBuf>,
+
+    /// Path to `cargo metadata` JSON output for the new revision
+    #[clap(long)]
+    new: Utf8PathBuf,
+
+    /// Path to a rules file (default: the built-in default rules)
+    #[clap(long)]
+    rules: Option<Utf8PathBuf>,
+
+    /// Read null-separated paths from standard input, e.g. from `git diff -z --name-only`
+    ///
+    /// By default, paths are expected to be separated by newlines.
+    #[clap(long, short = 'z')]
+    null: bool,
+
+    /// Don't mark dependents affected by changes to test, benchmark, example or binary targets
+    ///
+    /// Changed paths are attributed to the build targets whose sources they're next to. Packages
+    /// whose changes are limited to targets that other packages can't depend on are still marked
+    /// changed.
+    #[clap(long)]
+    attribute_targets: bool,
+
+    /// Print the progress of build simulations to standard error
+    #[clap(long)]
+    progress: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum SetKind {
+    /// Packages affected directly or indirectly
+    Affected,
+    /// Packages marked changed because a file changed
+    PathChanged,
+    /// Packages marked changed because a simulated build's dependencies changed
+    SummaryChanged,
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+
+    let args = Args::parse();
+    args.command.exec()
+}
+
+impl Command {
+    fn exec(self) -> Result<()> {
+        match self {
+            Command::Run {
+                inputs,
+                set,
+                show_rules,
+                summary_out,
+            } => {
+                let old = inputs.read_old()?;
+                let new = read_graph(&inputs.new)?;
+                let rules = inputs.read_rules()?;
+                let paths = inputs.read_paths()?;
+
+                let mut determinator = old.determinator(&new);
+                determinator
+                    .set_rules(&rules)
+                    .wrap_err("error resolving rules")?;
+                determinator
+                    .set_attribute_targets(inputs.attribute_targets)
+                    .add_changed_paths(&paths);
+                if inputs.progress {
+                    determinator.set_progress_callback(print_progress());
+                }
+                let determinator_set = determinator.compute();
+                if let Some(path) = &summary_out {
+                    determinator_set
+                        .write_summary(path)
+                        .wrap_err_with(|| format!("error writing summary to {}", path))?;
+                }
+
+                let package_set = match set {
+                    SetKind::Affected => &determinator_set.affected_set,
+                    SetKind::PathChanged => &determinator_set.path_changed_set,
+                    SetKind::SummaryChanged => &determinator_set.summary_changed_set,
+                };
+                for package in sorted_packages(package_set) {
+                    let rule_matches = determinator_set.rule_matches.get(package.id());
+                    match rule_matches {
+                        Some(rule_matches) if show_rules => {
+                            let rules: Vec<_> =
+                                rule_matches.iter().map(|rule| rule.to_string()).collect();
+                            println!("{} ({})", package.name(), rules.join(", "));
+                        }
+                        _ => println!("{}", package.name()),
+                    }
+                }
+                Ok(())
+            }
+            Command::Summarize { metadata, rules } => {
+                let graph = read_graph(&metadata)?;
+                let rules = match rules {
+                    Some(path) => read_rules(&path)?,
+                    None => DeterminatorRules::default(),
+                };
+
+                let mut determinator = Determinator::new(&graph, &graph);
+                determinator
+                    .set_rules(&rules)
+                    .wrap_err("error resolving rules")?;
+                let summary = determinator
+                    .base_summary()
+                    .to_string()
+                    .wrap_err("error serializing summary")?;
+                print!("{}", summary);
+                Ok(())
+            }
+            Command::Rules(RulesCommand::Check { rules, metadata }) => {
+                let rules = read_rules(&rules)?;
+                if let Some(metadata) = metadata {
+                    let graph = read_graph(&metadata)?;
+                    Determinator::new(&graph, &graph)
+                        .set_rules(&rules)
+                        .wrap_err("error resolving rules")?;
+                }
+                eprintln!("rules are valid");
+                Ok(())
+            }
+            Command::Explain { package, inputs } => {
+                let old = inputs.read_old()?;
+                let new = read_graph(&inputs.new)?;
+                let rules = inputs.read_rules()?;
+                let paths = inputs.read_paths()?;
+
+                let mut determinator = old.determinator(&new);
+                determinator
+                    .set_rules(&rules)
+                    .wrap_err("error resolving rules")?;
+                determinator
+                    .set_attribute_targets(inputs.attribute_targets)
+                    .add_changed_paths(&paths);
+                if inputs.progress {
+                    determinator.set_progress_callback(print_progress());
+                }
+
+                explain(&determinator, &package)
+            }
+        }
+    }
+}
+
+impl DeterminatorInputs {
+    fn read_old(&self) -> Result<OldWorkspace> {
+        match (&self.old, &self.old_summary) {
+            (Some(path), _) => Ok(OldWorkspace::Graph(read_graph(path)?)),
+            (None, Some(path)) => {
+                let contents =
+                    fs::read_to_string(path).wrap_err_with(|| format!("error reading {}", path))?;
+                let summary = BaseSummary::parse(&contents)
+                    .wrap_err_with(|| format!("error parsing summary from {}", path))?;
+                Ok(OldWorkspace::Summary(summary))
+            }
+            (None, None) => unreachable!("clap ensures one of --old and --old-summary is present"),
+        }
+    }
+
+    fn read_rules(&self) -> Result<DeterminatorRules> {
+        match &self.rules {
+            Some(path) => read_rules(path),
+            None => Ok(DeterminatorRules::default()),
+        }
+    }
+
+    fn read_paths(&self) -> Result<Vec<Utf8PathBuf>> {
+        let mut buf = String::new();
+        std::io::stdin()
+            .read_to_string(&mut buf)
+            .wrap_err("error reading changed paths from standard input")?;
+        let paths = if self.null {
+            Utf8Paths0::new(buf)
+                .iter()
+                .map(|path| path.to_owned())
+                .collect()
+        } else {
+            buf.lines()
+                .filter(|line| !line.is_empty())
+                .map(Utf8PathBuf::from)
+                .collect()
+        };
+        Ok(paths)
+    }
+}
+
+/// The old revision, as either a package graph or a summary.
+enum OldWorkspace {
+    Graph(PackageGraph),
+    Summary(BaseSummary),
+}
+
+impl OldWorkspace {
+    fn determinator<'g, 'a>(&'a self, new: &'g PackageGraph) -> Determinator<'g, 'a> {
+        match self {
+            OldWorkspace::Graph(old) => Determinator::new(old, new),
+            OldWorkspace::Summary(old) => Determinator::with_base_summary(old, new),
+        }
+    }
+}
+
+/// Returns a progress callback that prints a counter to standard error, updated in place.
+fn print_progress() -> impl Fn(DeterminatorProgress<'_>) + Send + Sync {
+    // Progress may be reported out of order from several threads, so only print counts that are
+    // higher than the last one printed.
+    let last_printed = Mutex::new((ProgressStage::Simulate, 0));
+    move |progress| {
+        let mut last_printed = last_printed.lock().expect("lock isn't poisoned");
+        let (stage, completed) = *last_printed;
+        if stage == progress.stage() && completed >= progress.completed() {
+            return;
+        }
+        *last_printed = (progress.stage(), progress.completed());
+
+        let label = match progress.stage() {
+            ProgressStage::Simulate => "simulating builds",
+            ProgressStage::Compare => "comparing against base",
+        };
+        eprint!("\r{}: {}/{}", label, progress.completed(), progress.total());
+        if progress.completed() == progress.total() {
+            eprintln!();
+        }
+    }
+}
+
+fn explain(determinator: &Determinator<'_, '_>, name: &str) -> Result<()> {
+    let determinator_set = determinator.compute();
+    match determinator_set.why(name) {
+        Ok(Some(explanation)) => {
+            println!("{} is affected:", name);
+            for line in explanation.to_string().lines() {
+                println!("  {}", line);
+            }
+        }
+        Ok(None) => println!("{} is not affected", name),
+        Err(_) => bail!("package '{}' not found in the new workspace", name),
+    }
+    Ok(())
+}
+
+fn read_graph(path: &Utf8Path) -> Result<PackageGraph> {
+    let json = fs::read_to_string(path).wrap_err_with(|| format!("error reading {}", path))?;
+    let metadata = CargoMetadata::parse_json(json)
+        .wrap_err_with(|| format!("error parsing metadata from {}", path))?;
+    metadata
+        .build_graph()
+        .wrap_err_with(|| format!("error building package graph from {}", path))
+}
+
+fn read_rules(path: &Utf8Path) -> Result<DeterminatorRules> {
+    let contents = fs::read_to_string(path).wrap_err_with(|| format!("error reading {}", path))?;
+    DeterminatorRules::parse(&contents).wrap_err_with(|| format!("error parsing rules in {}", path))
+}
+
+fn sorted_packages<'g>(package_set: &PackageSet<'g>) -> Vec<PackageMetadata<'g>> {
+    let mut packages: Vec<_> = package_set
+        .packages(DependencyDirection::Forward)
+        .filter(|package| package.in_workspace())
+        .collect();
+    packages.sort_unstable_by_key(|package| package.name());
+    packages
+}
@@ -4,7 +4,9 @@
 //! Custom rules for the target determinator.
 //!
 //! By default, the target determinator follows a simple set of rules:
-//! * Every changed path is matched to its nearest package, and that package is marked changed.
+//! * Every changed path is matched to its nearest package, and that package is marked changed. If
+//!   the nearest package is a path dependency outside the workspace, the workspace packages that
+//!   depend on it are marked changed.
 //! * Cargo builds are simulated against the old and new package graphs, and any packages with
 //!   different results are marked changed.
 //! * The affected set is found through observing simulated Cargo builds and doing a reverse map.
@@ -18,7 +20,8 @@
 //! These custom behaviors can be specified through *determinator rules*.
 //!
 //! There are two sorts of determinator rules:
-//! * **Path rules** match on changed paths, and are applied **in order**, before regular matches.
+//! * **Path rules** match on changed paths, and are applied **in order of priority**, before
+//!   regular matches.
 //! * **Package rules** match based on changed packages, and are applied as required until
 //!   exhausted (i.e. a fixpoint is reached).
 //!
@@ -32,12 +35,42 @@
 //!
 //! The default rules can be [viewed here](DeterminatorRules::DEFAULT_RULES_TOML).
 //!
+//! # Rule priorities
+//!
+//! Every path rule has a `priority`, which is 0 by default. Path rules are applied in order of
+//! decreasing priority. Rules with the same priority are applied in the order they're specified,
+//! with custom rules before default ones.
+//!
+//! All default rules have priority 0, so a custom rule can be applied *after* the default rules by
+//! giving it a negative priority:
+//!
+//! ```toml
+//! [[path-rule]]
+//! globs = ["**/*.md"]
+//! mark-changed = ["docs"]
+//! priority = -1
+//! ```
+//!
 //! To disable default rules entirely, set at the top level:
 //!
 //! ```toml
 //! use-default-rules = false
 //! ```
 //!
+//! # Excluded directories
+//!
+//! Files that aren't inside any workspace package normally cause everything to be rebuilt.
+//! Directories excluded from the workspace, such as the roots of nested workspaces, can be
+//! listed as globs relative to the workspace root:
+//!
+//! ```toml
+//! # Typically the same as workspace.exclude in the root Cargo.toml.
+//! workspace-excludes = ["nested-workspace", "crates/excluded"]
+//! ```
+//!
+//! Changed files within these directories are ignored. Path rules are still applied to them
+//! first, and files inside a path dependency are still matched to that path dependency.
+//!
 //! # Examples for path rules
 //!
 //! To ignore all files named `README.md` and `README.tpl`, and skip all further processing:
@@ -103,13 +136,63 @@
 //! on-affected = ["guppy-benchmarks"]
 //! mark-changed = "all"
 //! ```
+//!
+//! # Marking packages changed by path
+//!
+//! Rather than listing packages by name, both path and package rules can mark every workspace
+//! package whose path matches a glob as changed. The set of packages is computed from the new
+//! workspace, so packages added later are picked up automatically:
+//!
+//! ```toml
+//! [[path-rule]]
+//! name = "service-protos"
+//! globs = ["protos/**"]
+//! mark-changed = []
+//! mark-changed-packages-matching = { workspace-path-glob = "services/*" }
+//! ```
+//!
+//! Packages listed in `mark-changed` and packages matched by `mark-changed-packages-matching` are
+//! both marked changed. If `mark-changed` is `"all"`, `mark-changed-packages-matching` has no
+//! effect.
+//!
+//! # Rule names
+//!
+//! Path and package rules can be given a `name`. For every package marked changed by a rule, the
+//! rules that marked it changed are reported in
+//! [`DeterminatorSet::rule_matches`](crate::DeterminatorSet::rule_matches), identified by name if
+//! they have one.
+//!
+//! # Rules in package metadata
+//!
+//! Rules that only concern a single workspace package can also be specified in that package's
+//! `Cargo.toml`, under `[package.metadata.determinator]`:
+//!
+//! ```toml
+//! [package.metadata.determinator]
+//! # Mark this package changed if any of these files change. Globs are relative to the root of the
+//! # workspace, and matching files are also processed by further rules.
+//! rerun-if-changed = ["migrations/**"]
+//! # Virtual dependencies: mark this package changed if any of these workspace packages are
+//! # affected.
+//! virtual-deps = ["fixtures"]
+//! ```
+//!
+//! These rules are merged with the custom rules, and are applied after them but before the default
+//! rules. A glob in `rerun-if-changed` that's also specified by a custom path rule is reported as
+//! a conflict.
+//!
+//! To ignore rules in package metadata, set at the top level:
+//!
+//! ```toml
+//! use-package-metadata-rules = false
+//! ```
 
 use crate::errors::RulesError;
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use globset::{Candidate, Glob, GlobSet, GlobSetBuilder};
 use guppy::graph::{PackageGraph, PackageMetadata, PackageSet, Workspace};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{cmp::Reverse, fmt};
 
 /// Rules for the target determinator.
 ///
@@ -125,6 +208,13 @@ pub struct DeterminatorRules {
     #[serde(default = "default_true", rename = "use-default-rules")]
     use_default_rules: bool,
 
+    /// Whether to use rules specified in the `[package.metadata.determinator]` section of
+    /// workspace packages, as described by [`PackageMetadataRules`].
+    ///
+    /// This is true by default.
+    #[serde(default = "default_true", rename = "use-package-metadata-rules")]
+    pub use_package_metadata_rules: bool,
+
     /// A list of rules that each changed file path is matched against.
     #[serde(default, rename = "path-rule")]
     pub path_rules: Vec<PathRule>,
@@ -135,6 +225,18 @@ pub struct DeterminatorRules {
     /// packages here act as "virtual dependencies" for the determinator.
     #[serde(default, rename = "package-rule")]
     pub package_rules: Vec<PackageRule>,
+
+    /// Globs for directories that are excluded from the workspace.
+    ///
+    /// This is typically the `workspace.exclude` list in the workspace's `Cargo.toml`, along with
+    /// the roots of any nested workspaces. Changed paths within these directories are ignored,
+    /// unless they're matched by a path rule or belong to a path dependency.
+    #[serde(
+        default,
+        rename = "workspace-excludes",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub workspace_excludes: Vec<String>,
 }
 
 /// The `Default` impl is the set of custom rules used by the determinator if
@@ -145,8 +247,10 @@ impl Default for DeterminatorRules {
     fn default() -> Self {
         Self {
             use_default_rules: true,
+            use_package_metadata_rules: true,
             path_rules: vec![],
             package_rules: vec![],
+            workspace_excludes: vec![],
         }
     }
 }
@@ -215,6 +319,16 @@ The latest version of the default rules is available
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct PathRule {
+    /// The name of this rule, used to report which rules marked packages changed.
+    ///
+    /// # Examples
+    ///
+    /// ```toml
+    /// name = "readmes"
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     /// The globs to match against.
     ///
     /// A changed path matches a rule if it matches any of the globs on this list.
@@ -246,9 +360,39 @@ pub struct PathRule {
     #[serde(with = "mark_changed_impl")]
     pub mark_changed: DeterminatorMarkChanged,
 
+    /// Additional packages to mark as changed, selected by their paths in the workspace.
+    ///
+    /// # Examples
+    ///
+    /// ```toml
+    /// mark-changed-packages-matching = { workspace-path-glob = "services/*" }
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark_changed_packages_matching: Option<PackagesMatching>,
+
     /// The operation to perform after applying the rule. Set to "skip" by default.
     #[serde(default)]
     pub post_rule: DeterminatorPostRule,
+
+    /// The priority of this rule. Set to 0 by default.
+    ///
+    /// Path rules are applied in order of decreasing priority. Rules with the same priority are
+    /// applied in the order they're specified, with custom rules before default ones.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format, specified as an integer:
+    ///
+    /// ```toml
+    /// priority = -10
+    /// ```
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+}
+
+#[inline]
+fn is_zero(priority: &i32) -> bool {
+    *priority == 0
 }
 
 /// The operation to perform after applying the rule.
@@ -316,6 +460,16 @@ impl Default for DeterminatorPostRule {
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct PackageRule {
+    /// The name of this rule, used to report which rules marked packages changed.
+    ///
+    /// # Examples
+    ///
+    /// ```toml
+    /// name = "fixtures"
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
     /// The package names to match against.
     ///
     /// If any of the packages in this list is affected, the given packages will be marked changed.
@@ -346,6 +500,52 @@ pub struct PackageRule {
     /// ```
     #[serde(with = "mark_changed_impl")]
     pub mark_changed: DeterminatorMarkChanged,
+
+    /// Additional packages to mark as changed, selected by their paths in the workspace.
+    ///
+    /// # Examples
+    ///
+    /// ```toml
+    /// mark-changed-packages-matching = { workspace-path-glob = "services/*" }
+    /// ```
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mark_changed_packages_matching: Option<PackagesMatching>,
+}
+
+/// Rules for a single workspace package, read from the `[package.metadata.determinator]` section of
+/// its `Cargo.toml`.
+///
+/// # Examples
+///
+/// ```toml
+/// [package.metadata.determinator]
+/// rerun-if-changed = ["migrations/**"]
+/// virtual-deps = ["fixtures"]
+/// ```
+///
+/// For more, see [the module-level documentation](index.html).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PackageMetadataRules {
+    /// Globs, relative to the workspace root, for files that cause this package to be marked
+    /// changed.
+    ///
+    /// This acts as a path rule with `post-rule = "fallthrough"`, so matching files are also
+    /// processed by further rules and matched to their nearest package.
+    #[serde(default)]
+    pub rerun_if_changed: Vec<String>,
+
+    /// Workspace packages that this package has a virtual dependency on.
+    ///
+    /// If any of these packages is affected, this package is marked changed. This acts as a
+    /// package rule.
+    #[serde(default)]
+    pub virtual_deps: Vec<String>,
+}
+
+impl PackageMetadataRules {
+    /// The key under `package.metadata` that these rules are read from.
+    pub const METADATA_KEY: &'static str = "determinator";
 }
 
 /// The set of packages to mark as changed.
@@ -384,6 +584,42 @@ pub enum DeterminatorMarkChanged {
     All,
 }
 
+/// A set of workspace packages selected by their paths, to mark as changed.
+///
+/// # Examples
+///
+/// ```toml
+/// mark-changed-packages-matching = { workspace-path-glob = "services/*" }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PackagesMatching {
+    /// A [`globset`](https://docs.rs/globset/0.4) glob, relative to the workspace root, that is
+    /// matched against the paths of workspace packages.
+    pub workspace_path_glob: String,
+}
+
+/// A rule that marked a package changed.
+///
+/// Reported in [`DeterminatorSet::rule_matches`](crate::DeterminatorSet::rule_matches).
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RuleMatch {
+    /// The index of the rule.
+    pub rule_index: RuleIndex,
+
+    /// The name of the rule, if it has one.
+    pub name: Option<String>,
+}
+
+impl fmt::Display for RuleMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "rule '{}'", name),
+            None => write!(f, "{}", self.rule_index),
+        }
+    }
+}
+
 /// The result of matching a file path against a determinator.
 ///
 /// Returned by `Determinator::match_path`.
@@ -397,6 +633,14 @@ pub enum PathMatch {
     RuleMatched(RuleIndex),
     /// The path was matched to a package through inspecting the parent directories of each path.
     AncestorMatched,
+    /// The path was matched to a path dependency outside the workspace, such as an excluded crate
+    /// or a member of a nested workspace, through inspecting the parent directories of each path.
+    ///
+    /// The workspace packages that depend on the path dependency are marked changed.
+    PathDependencyMatched,
+    /// The path is within a directory in
+    /// [`workspace_excludes`](DeterminatorRules::workspace_excludes), and was ignored.
+    WorkspaceExcluded,
     /// The path wasn't matched to a rule or a nearby package, causing everything to be rebuilt.
     NoMatches,
 }
@@ -414,6 +658,12 @@ pub enum RuleIndex {
     ///
     /// All package rules are custom: there are no default package rules.
     Package(usize),
+    /// The workspace exclude at this index.
+    WorkspaceExclude(usize),
+    /// The rules in the package metadata of the workspace member at this index, in order of
+    /// workspace path (as returned by
+    /// [`Workspace::iter_by_path`](guppy::graph::Workspace::iter_by_path)).
+    PackageMetadata(usize),
 }
 
 impl fmt::Display for RuleIndex {
@@ -422,6 +672,10 @@ impl fmt::Display for RuleIndex {
             RuleIndex::CustomPath(index) => write!(f, "custom path rule {}", index),
             RuleIndex::DefaultPath(index) => write!(f, "default path rule {}", index),
             RuleIndex::Package(index) => write!(f, "package rule {}", index),
+            RuleIndex::WorkspaceExclude(index) => write!(f, "workspace exclude {}", index),
+            RuleIndex::PackageMetadata(index) => {
+                write!(f, "package metadata rules for workspace member {}", index)
+            }
         }
     }
 }
@@ -433,8 +687,15 @@ impl fmt::Display for RuleIndex {
 /// Internal version of determinator rules.
 #[derive(Clone, Debug)]
 pub(crate) struct RulesImpl<'g> {
+    /// Path rules, sorted in the order they're applied.
     pub(crate) path_rules: Vec<PathRuleImpl<'g>>,
+    /// All the globs across path rules, compiled into a single set.
+    pub(crate) path_glob_set: GlobSet,
+    /// A map from each glob in `path_glob_set` to its index in `path_rules`.
+    pub(crate) glob_to_rule: Vec<usize>,
     pub(crate) package_rules: Vec<PackageRuleImpl<'g>>,
+    /// Directories excluded from the workspace.
+    pub(crate) workspace_exclude_set: GlobSet,
 }
 
 impl<'g> RulesImpl<'g> {
@@ -462,37 +723,119 @@ impl<'g> RulesImpl<'g> {
             .enumerate()
             .map(|(idx, rule)| (RuleIndex::DefaultPath(idx), rule));
 
-        // Default rules come after custom ones.
-        let path_rules = custom_path_rules
+        let mut metadata_path_rules = vec![];
+        let mut metadata_package_rules = vec![];
+        if options.use_package_metadata_rules {
+            for (idx, package) in workspace.iter().enumerate() {
+                let rule_index = RuleIndex::PackageMetadata(idx);
+                let metadata_rules = match package
+                    .metadata_section::<PackageMetadataRules>(PackageMetadataRules::METADATA_KEY)
+                    .map_err(|err| RulesError::package_metadata(rule_index, err))?
+                {
+                    Some(metadata_rules) => metadata_rules,
+                    None => continue,
+                };
+
+                if !metadata_rules.rerun_if_changed.is_empty() {
+                    // A glob that's also in a custom path rule means that the two rules disagree
+                    // over which packages own the same files.
+                    for glob in &metadata_rules.rerun_if_changed {
+                        let conflicting = options
+                            .path_rules
+                            .iter()
+                            .position(|rule| rule.globs.contains(glob));
+                        if let Some(other) = conflicting {
+                            return Err(RulesError::conflict(
+                                rule_index,
+                                glob.clone(),
+                                RuleIndex::CustomPath(other),
+                            ));
+                        }
+                    }
+
+                    metadata_path_rules.push((
+                        rule_index,
+                        PathRule {
+                            name: None,
+                            globs: metadata_rules.rerun_if_changed,
+                            mark_changed: DeterminatorMarkChanged::Packages(vec![package
+                                .name()
+                                .to_owned()]),
+                            mark_changed_packages_matching: None,
+                            post_rule: DeterminatorPostRule::Fallthrough,
+                            priority: 0,
+                        },
+                    ));
+                }
+
+                if !metadata_rules.virtual_deps.is_empty() {
+                    let on_affected = graph
+                        .resolve_workspace_names(&metadata_rules.virtual_deps)
+                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    metadata_package_rules.push(PackageRuleImpl {
+                        rule_match: RuleMatch {
+                            rule_index,
+                            name: None,
+                        },
+                        on_affected,
+                        mark_changed: MarkChangedImpl::Packages(vec![package]),
+                    });
+                }
+            }
+        }
+
+        // Rules in package metadata come after custom ones, and default rules come after both.
+        // This is a stable sort, so that order is preserved for rules with the same priority.
+        let mut sorted_rules: Vec<_> = custom_path_rules
+            .chain(metadata_path_rules.iter().map(|(idx, rule)| (*idx, rule)))
             .chain(default_path_rules)
+            .collect();
+        sorted_rules.sort_by_key(|(_, rule)| Reverse(rule.priority));
+
+        // Compile all the globs into a single globset, since matching paths against a large number
+        // of rules can otherwise dominate runtime.
+        let mut builder = GlobSetBuilder::new();
+        let mut all_globs = vec![];
+        let mut glob_to_rule = vec![];
+        let path_rules = sorted_rules
+            .into_iter()
+            .enumerate()
             .map(
                 |(
-                    rule_index,
-                    PathRule {
-                        globs,
-                        mark_changed,
-                        post_rule,
-                    },
+                    position,
+                    (
+                        rule_index,
+                        PathRule {
+                            name,
+                            globs,
+                            mark_changed,
+                            mark_changed_packages_matching,
+                            post_rule,
+                            priority: _,
+                        },
+                    ),
                 )| {
-                    // Convert the globs to a globset.
-                    let mut builder = GlobSetBuilder::new();
                     for glob in globs {
                         let glob = Glob::new(glob)
                             .map_err(|err| RulesError::glob_parse(rule_index, err))?;
-                        builder.add(glob);
+                        builder.add(glob.clone());
+                        all_globs.push(glob);
+                        glob_to_rule.push(position);
                     }
 
-                    let glob_set = builder
-                        .build()
-                        .map_err(|err| RulesError::glob_parse(rule_index, err))?;
-
                     // Convert workspace paths to packages.
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    let mark_changed = MarkChangedImpl::new(
+                        &workspace,
+                        rule_index,
+                        mark_changed,
+                        mark_changed_packages_matching.as_ref(),
+                    )?;
 
                     Ok(PathRuleImpl {
-                        rule_index,
-                        glob_set,
+                        rule_match: RuleMatch {
+                            rule_index,
+                            name: name.clone(),
+                        },
                         mark_changed,
                         post_rule: *post_rule,
                     })
@@ -500,6 +843,11 @@ impl<'g> RulesImpl<'g> {
             )
             .collect::<Result<Vec<_>, _>>()?;
 
+        let path_glob_set = builder.build().map_err(|err| {
+            let position = glob_to_rule[failing_glob(&all_globs)];
+            RulesError::glob_parse(path_rules[position].rule_match.rule_index, err)
+        })?;
+
         let package_rules = options
             .package_rules
             .iter()
@@ -508,41 +856,115 @@ impl<'g> RulesImpl<'g> {
                 |(
                     rule_index,
                     PackageRule {
+                        name,
                         on_affected,
                         mark_changed,
+                        mark_changed_packages_matching,
                     },
                 )| {
                     let rule_index = RuleIndex::Package(rule_index);
                     let on_affected = graph
                         .resolve_workspace_names(on_affected)
                         .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    let mark_changed = MarkChangedImpl::new(
+                        &workspace,
+                        rule_index,
+                        mark_changed,
+                        mark_changed_packages_matching.as_ref(),
+                    )?;
                     Ok(PackageRuleImpl {
+                        rule_match: RuleMatch {
+                            rule_index,
+                            name: name.clone(),
+                        },
                         on_affected,
                         mark_changed,
                     })
                 },
             )
+            .chain(metadata_package_rules.into_iter().map(Ok))
             .collect::<Result<Vec<_>, _>>()?;
 
+        let mut builder = GlobSetBuilder::new();
+        let mut exclude_globs = vec![];
+        for (idx, glob) in options.workspace_excludes.iter().enumerate() {
+            let glob = Glob::new(glob)
+                .map_err(|err| RulesError::glob_parse(RuleIndex::WorkspaceExclude(idx), err))?;
+            builder.add(glob.clone());
+            exclude_globs.push(glob);
+        }
+        let workspace_exclude_set = builder.build().map_err(|err| {
+            RulesError::glob_parse(
+                RuleIndex::WorkspaceExclude(failing_glob(&exclude_globs)),
+                err,
+            )
+        })?;
+
         Ok(Self {
             path_rules,
+            path_glob_set,
+            glob_to_rule,
             package_rules,
+            workspace_exclude_set,
         })
     }
+
+    /// Returns the path rules matching the given candidate, in the order they should be applied.
+    pub(crate) fn matching_path_rules<'a>(
+        &'a self,
+        candidate: &Candidate<'_>,
+    ) -> impl Iterator<Item = &'a PathRuleImpl<'g>> + 'a {
+        let mut positions: Vec<_> = self
+            .path_glob_set
+            .matches_candidate(candidate)
+            .into_iter()
+            .map(|glob_idx| self.glob_to_rule[glob_idx])
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+            .into_iter()
+            .map(move |position| &self.path_rules[position])
+    }
+}
+
+/// Returns the index of the glob to blame for these globs failing to build into a `GlobSet`.
+///
+/// Errors from building a `GlobSet` don't say which glob they're for, and globs can fail to build
+/// together even if they build individually. Adding globs to a set only makes it larger, so the
+/// glob to blame is the last one in the shortest prefix that fails to build.
+fn failing_glob(globs: &[Glob]) -> usize {
+    let build_prefix = |len: usize| {
+        let mut builder = GlobSetBuilder::new();
+        for glob in &globs[..len] {
+            builder.add(glob.clone());
+        }
+        builder.build()
+    };
+
+    // Invariant: the first `ok_len` globs build, and the first `err_len` globs don't.
+    let (mut ok_len, mut err_len) = (0, globs.len());
+    while err_len - ok_len > 1 {
+        let mid = ok_len + (err_len - ok_len) / 2;
+        if build_prefix(mid).is_ok() {
+            ok_len = mid;
+        } else {
+            err_len = mid;
+        }
+    }
+    err_len - 1
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct PathRuleImpl<'g> {
-    pub(crate) rule_index: RuleIndex,
-    pub(crate) glob_set: GlobSet,
+    pub(crate) rule_match: RuleMatch,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
     pub(crate) post_rule: DeterminatorPostRule,
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct PackageRuleImpl<'g> {
+    pub(crate) rule_match: RuleMatch,
     pub(crate) on_affected: PackageSet<'g>,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
 }
@@ -556,14 +978,33 @@ pub(crate) enum MarkChangedImpl<'g> {
 impl<'g> MarkChangedImpl<'g> {
     fn new(
         workspace: &Workspace<'g>,
+        rule_index: RuleIndex,
         mark_changed: &DeterminatorMarkChanged,
-    ) -> Result<Self, guppy::Error> {
-        match mark_changed {
-            DeterminatorMarkChanged::Packages(names) => Ok(MarkChangedImpl::Packages(
-                workspace.members_by_names(names)?,
-            )),
-            DeterminatorMarkChanged::All => Ok(MarkChangedImpl::All),
+        matching: Option<&PackagesMatching>,
+    ) -> Result<Self, RulesError> {
+        let mut packages: Vec<_> = match mark_changed {
+            DeterminatorMarkChanged::Packages(names) => workspace
+                .members_by_names(names)
+                .map_err(|err| RulesError::resolve_ref(rule_index, err))?,
+            DeterminatorMarkChanged::All => return Ok(MarkChangedImpl::All),
+        };
+
+        if let Some(matching) = matching {
+            let matcher = Glob::new(&matching.workspace_path_glob)
+                .map_err(|err| RulesError::glob_parse(rule_index, err))?
+                .compile_matcher();
+            for (path, package) in workspace.iter_by_path() {
+                if matcher.is_match(path)
+                    && !packages
+                        .iter()
+                        .any(|existing| existing.id() == package.id())
+                {
+                    packages.push(package);
+                }
+            }
         }
+
+        Ok(MarkChangedImpl::Packages(packages))
     }
 }
 
@@ -617,10 +1058,14 @@ mod tests {
 
     #[test]
     fn parse() {
-        let s = r#"[[path-rule]]
+        let s = r#"workspace-excludes = ["nested", "crates/excluded"]
+
+        [[path-rule]]
+        name = "everything"
         globs = ["all/*"]
         mark-changed = "all"
         post-rule = "fallthrough"
+        priority = 10
 
         [[path-rule]]
         globs = ["all/1/2/*"]
@@ -630,8 +1075,10 @@ mod tests {
         [[path-rule]]
         globs = ["none/**/test", "foo/bar"]
         mark-changed = []
+        mark-changed-packages-matching = { workspace-path-glob = "services/*" }
 
         [[package-rule]]
+        name = "wat-rule"
         on-affected = ["foo"]
         mark-changed = ["wat"]
 
@@ -642,33 +1089,50 @@ mod tests {
 
         let expected = DeterminatorRules {
             use_default_rules: true,
+            use_package_metadata_rules: true,
             path_rules: vec![
                 PathRule {
+                    name: Some("everything".to_owned()),
                     globs: vec!["all/*".to_owned()],
                     mark_changed: DeterminatorMarkChanged::All,
+                    mark_changed_packages_matching: None,
                     post_rule: DeterminatorPostRule::Fallthrough,
+                    priority: 10,
                 },
                 PathRule {
+                    name: None,
                     globs: vec!["all/1/2/*".to_owned()],
                     mark_changed: DeterminatorMarkChanged::Packages(vec!["c".to_owned()]),
+                    mark_changed_packages_matching: None,
                     post_rule: DeterminatorPostRule::SkipRules,
+                    priority: 0,
                 },
                 PathRule {
+                    name: None,
                     globs: vec!["none/**/test".to_owned(), "foo/bar".to_owned()],
                     mark_changed: DeterminatorMarkChanged::Packages(vec![]),
+                    mark_changed_packages_matching: Some(PackagesMatching {
+                        workspace_path_glob: "services/*".to_owned(),
+                    }),
                     post_rule: DeterminatorPostRule::Skip,
+                    priority: 0,
                 },
             ],
             package_rules: vec![
                 PackageRule {
+                    name: Some("wat-rule".to_owned()),
                     on_affected: vec!["foo".to_string()],
                     mark_changed: DeterminatorMarkChanged::Packages(vec!["wat".to_string()]),
+                    mark_changed_packages_matching: None,
                 },
                 PackageRule {
+                    name: None,
                     on_affected: vec!["test1".to_string()],
                     mark_changed: DeterminatorMarkChanged::All,
+                    mark_changed_packages_matching: None,
                 },
             ],
+            workspace_excludes: vec!["nested".to_owned(), "crates/excluded".to_owned()],
         };
 
         assert_eq!(
@@ -763,6 +1227,24 @@ mod tests {
             mark-changed = "all"
             post-rule = []
             "#,
+            // priority is not an integer
+            r#"[[path-rule]]
+            globs = ["a/b"]
+            mark-changed = []
+            priority = "high"
+            "#,
+            // mark-changed-packages-matching is missing workspace-path-glob
+            r#"[[path-rule]]
+            globs = ["a/b"]
+            mark-changed = []
+            mark-changed-packages-matching = {}
+            "#,
+            // mark-changed-packages-matching has an unrecognized key
+            r#"[[path-rule]]
+            globs = ["a/b"]
+            mark-changed = []
+            mark-changed-packages-matching = { workspace-path-glob = "a/*", foo = "bar" }
+            "#,
             //
             // *************
             // Package rules
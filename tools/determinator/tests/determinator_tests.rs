@@ -5,11 +5,22 @@
 
 use cfg_if::cfg_if;
 use determinator::{
-    rules::{DeterminatorRules, PathMatch, RuleIndex},
-    Determinator, Utf8Paths0,
+    errors::RulesErrorKind,
+    rules::{DeterminatorRules, PathMatch, RuleIndex, RuleMatch},
+    AffectedEdge, AffectedExplanation, BaseSummary, ChangeCause, Determinator, DeterminatorBase,
+    DeterminatorConfig, DeterminatorSet, PathChange, ProgressStage, Utf8Paths0,
 };
 use fixtures::json::JsonFixture;
-use guppy::{graph::feature::StandardFeatures, CargoMetadata};
+use guppy::{
+    graph::{
+        feature::StandardFeatures,
+        summaries::{PackageStatus, Summary},
+        BuildTargetId, DependencyDirection,
+    },
+    platform::PlatformSpec,
+    CargoMetadata,
+};
+use std::{collections::BTreeSet, sync::Mutex};
 
 #[test]
 fn guppy_no_rules() {
@@ -315,6 +326,310 @@ fn guppy_package_rules_2() {
     );
 }
 
+#[test]
+fn guppy_why() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "package-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+
+    {
+        // This ruleset disables default rules, so Cargo.lock changing causes everything to be
+        // built.
+        let mut determinator = determinator.clone();
+        determinator.add_changed_paths(vec!["Cargo.lock"]);
+        let determinator_set = determinator.compute();
+        let explanation = why(&determinator_set, "guppy").expect("guppy is affected");
+        assert_eq!(
+            explanation.cause,
+            ChangeCause::AllChanged {
+                path: "Cargo.lock".into(),
+                rule_match: None,
+            },
+        );
+        assert!(explanation.steps.is_empty(), "no steps");
+    }
+
+    determinator.add_changed_paths(vec!["fixtures/src/main.rs"]);
+    let determinator_set = determinator.compute();
+
+    let explanation = why(&determinator_set, "fixtures").expect("fixtures is affected");
+    assert_eq!(
+        explanation.cause,
+        ChangeCause::Path("fixtures/src/main.rs".into())
+    );
+    assert!(
+        explanation.steps.is_empty(),
+        "fixtures was changed directly"
+    );
+
+    let explanation = why(&determinator_set, "guppy-cmdlib").expect("guppy-cmdlib is affected");
+    assert_eq!(explanation.changed_package().name(), "fixtures");
+    let steps: Vec<_> = explanation
+        .steps
+        .iter()
+        .map(|step| (step.from.name(), step.to.name(), step.edge.clone()))
+        .collect();
+    assert_eq!(
+        steps,
+        vec![(
+            "fixtures",
+            "guppy-cmdlib",
+            AffectedEdge::PackageRule(RuleMatch {
+                rule_index: RuleIndex::Package(0),
+                name: None,
+            }),
+        )],
+    );
+    assert_eq!(
+        explanation.to_string(),
+        "fixtures was marked changed: fixtures/src/main.rs changed\n\
+         guppy-cmdlib was marked changed by package rule 0 because fixtures is affected",
+    );
+
+    // cargo-compare depends on guppy-cmdlib.
+    let explanation = why(&determinator_set, "cargo-compare").expect("cargo-compare is affected");
+    assert_eq!(explanation.changed_package().name(), "fixtures");
+    assert_chain(&explanation);
+
+    assert!(
+        determinator_set
+            .why("guppy-benchmarks")
+            .expect("guppy-benchmarks is a workspace member")
+            .is_none(),
+        "guppy-benchmarks is not affected"
+    );
+    determinator_set
+        .why("no-such-package")
+        .expect_err("no-such-package is not a workspace member");
+
+    // Changing a "fake-trigger" file means "proptest-ext" changes, which causes "guppy-benchmarks"
+    // to change, which according to a package rule means everything gets rebuilt.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["foo/fake-trigger"]);
+    let determinator_set = determinator.compute();
+    for package in new.graph().workspace().iter() {
+        let explanation = why(&determinator_set, package.name()).expect("all packages affected");
+        assert_eq!(
+            explanation.cause,
+            ChangeCause::PathRule {
+                path: "foo/fake-trigger".into(),
+                rule_match: RuleMatch {
+                    rule_index: RuleIndex::CustomPath(0),
+                    name: None,
+                },
+            },
+            "for {}, cause matches",
+            package.name(),
+        );
+        assert_eq!(explanation.changed_package().name(), "proptest-ext");
+        assert_chain(&explanation);
+    }
+}
+
+#[test]
+fn guppy_sorted_names_and_summary() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changed_paths(vec!["guppy/src/lib.rs"]);
+    let determinator_set = determinator.compute();
+
+    let names = determinator_set.sorted_names();
+    let mut expected: Vec<_> = determinator_set
+        .affected_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.name())
+        .collect();
+    expected.sort_unstable();
+    assert_eq!(names, expected, "names are sorted");
+    assert!(names.contains(&"guppy"), "guppy is affected");
+
+    let summary = determinator_set.to_summary();
+    let summary =
+        Summary::parse(&summary.to_string().expect("summary serialized")).expect("summary parsed");
+    let summary_names: Vec<_> = summary
+        .target_packages
+        .iter()
+        .map(|(summary_id, info)| {
+            assert_eq!(info.status, PackageStatus::Initial);
+            summary_id.name.as_str()
+        })
+        .collect();
+    assert_eq!(
+        summary_names, names,
+        "summary lists every affected package, sorted by name"
+    );
+    assert!(summary.host_packages.is_empty(), "no host packages");
+    assert_eq!(
+        summary.metadata["path-changed"],
+        toml::Value::Array(vec!["guppy".into()]),
+        "path-changed metadata"
+    );
+}
+
+fn why<'g>(determinator_set: &DeterminatorSet<'g>, name: &str) -> Option<AffectedExplanation<'g>> {
+    determinator_set
+        .why(name)
+        .unwrap_or_else(|err| panic!("{} is a workspace member: {}", name, err))
+}
+
+/// Checks that the steps in an explanation form a chain ending at the explained package.
+fn assert_chain(explanation: &AffectedExplanation<'_>) {
+    let mut current = explanation.changed_package();
+    for step in &explanation.steps {
+        assert_eq!(step.from.id(), current.id(), "steps are connected");
+        current = step.to;
+    }
+    assert_eq!(
+        current.id(),
+        explanation.package.id(),
+        "chain ends at the explained package"
+    );
+}
+
+#[test]
+fn packages_matching_rules() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = DeterminatorRules::parse(
+        r#"use-default-rules = false
+
+        [[path-rule]]
+        name = "docs"
+        globs = ["docs/**"]
+        mark-changed = ["guppy"]
+        mark-changed-packages-matching = { workspace-path-glob = "internal-tools/*" }
+
+        [[package-rule]]
+        on-affected = ["guppy-summaries"]
+        mark-changed = []
+        mark-changed-packages-matching = { workspace-path-glob = "target-*" }
+        "#,
+    )
+    .expect("rules parsed");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["docs/index.md", "guppy-summaries/src/lib.rs"]);
+    let determinator_set = determinator.compute();
+
+    let rule_marked = [
+        "guppy",
+        "cargo-compare",
+        "fixture-manager",
+        "guppy-benchmarks",
+        "proptest-ext",
+    ];
+    let expected_path_changed = new
+        .graph()
+        .resolve_workspace_names(rule_marked.iter().chain(&["guppy-summaries"]))
+        .expect("valid workspace names");
+    assert_eq!(
+        determinator_set.path_changed_set, expected_path_changed,
+        "packages matched by the path rule and guppy-summaries changed"
+    );
+    assert!(
+        determinator_set
+            .affected_set
+            .contains(
+                new.graph()
+                    .workspace()
+                    .member_by_name("target-spec")
+                    .unwrap()
+                    .id()
+            )
+            .unwrap(),
+        "target-spec affected through the package rule"
+    );
+
+    let docs_rule = RuleMatch {
+        rule_index: RuleIndex::CustomPath(0),
+        name: Some("docs".to_owned()),
+    };
+    let package_rule = RuleMatch {
+        rule_index: RuleIndex::Package(0),
+        name: None,
+    };
+    let mut expected_rule_matches: Vec<(&str, Vec<RuleMatch>)> = rule_marked
+        .iter()
+        .map(|&name| (name, vec![docs_rule.clone()]))
+        .collect();
+    expected_rule_matches.push(("target-spec", vec![package_rule.clone()]));
+    expected_rule_matches.sort();
+
+    let mut actual_rule_matches: Vec<_> = determinator_set
+        .rule_matches
+        .iter()
+        .map(|(id, rule_matches)| {
+            let name = new.graph().metadata(id).expect("valid package ID").name();
+            (name, rule_matches.iter().cloned().collect::<Vec<_>>())
+        })
+        .collect();
+    actual_rule_matches.sort();
+    assert_eq!(
+        actual_rule_matches, expected_rule_matches,
+        "rule matches are reported for each package"
+    );
+    assert_eq!(docs_rule.to_string(), "rule 'docs'");
+    assert_eq!(package_rule.to_string(), "package rule 0");
+
+    // An invalid glob is reported as an error.
+    let opts = DeterminatorRules::parse(
+        r#"[[path-rule]]
+        globs = ["docs/**"]
+        mark-changed = []
+        mark-changed-packages-matching = { workspace-path-glob = "internal-tools/[" }
+        "#,
+    )
+    .expect("rules parsed");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    let err = determinator
+        .set_rules(&opts)
+        .expect_err("invalid glob is an error");
+    assert_eq!(err.rule_index(), RuleIndex::CustomPath(0));
+    assert!(
+        matches!(err.kind(), RulesErrorKind::GlobParse { .. }),
+        "glob parse error reported"
+    );
+
+    // Globs that build individually but are too large to build together are blamed on the rule
+    // that pushes the set over the limit.
+    let large_glob = |prefix: &str| format!("{}{}", prefix, "?".repeat(80_000));
+    let opts = DeterminatorRules::parse(&format!(
+        r#"[[path-rule]]
+        globs = ["docs/**"]
+        mark-changed = []
+
+        [[path-rule]]
+        globs = ["{}"]
+        mark-changed = []
+
+        [[path-rule]]
+        globs = ["{}"]
+        mark-changed = []
+        "#,
+        large_glob("a"),
+        large_glob("b"),
+    ))
+    .expect("rules parsed");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    let err = determinator
+        .set_rules(&opts)
+        .expect_err("glob set that's too large is an error");
+    assert_eq!(err.rule_index(), RuleIndex::CustomPath(2));
+    assert!(
+        matches!(err.kind(), RulesErrorKind::GlobParse { .. }),
+        "glob parse error reported"
+    );
+}
+
 #[test]
 fn guppy_deps() {
     // new updates the version of toml, which should cause most things to change.
@@ -386,6 +701,239 @@ fn guppy_deps() {
     );
 }
 
+#[test]
+fn guppy_deps_config() {
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let opts = read_options(new, "path-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    let full_set = determinator.compute();
+
+    // Only simulating builds with no features is less accurate: guppy only depends on toml through
+    // its summaries feature, so it isn't marked changed.
+    determinator.set_config(DeterminatorConfig::none_only());
+    let none_only_set = determinator.compute();
+    let guppy_id = new
+        .graph()
+        .workspace()
+        .member_by_name("guppy")
+        .expect("guppy is a workspace member")
+        .id();
+    assert!(
+        !none_only_set
+            .summary_changed_set
+            .contains(guppy_id)
+            .expect("valid package ID"),
+        "guppy isn't marked changed without features"
+    );
+    assert!(
+        none_only_set
+            .affected_set
+            .difference(&full_set.affected_set)
+            .is_empty(),
+        "simulating fewer builds doesn't affect more packages"
+    );
+
+    // Simulating the same builds as the default, but on an explicit platform, produces the same
+    // results.
+    let mut config = DeterminatorConfig::new();
+    config.add_platforms([PlatformSpec::current().expect("current platform is known")]);
+    determinator.set_config(config);
+    let platform_set = determinator.compute();
+    assert_eq!(
+        platform_set.affected_set, full_set.affected_set,
+        "same affected set with the current platform"
+    );
+}
+
+#[test]
+fn guppy_multiple_bases() {
+    let deps_old = JsonFixture::metadata_guppy_78cb7e8();
+    let paths_old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let opts = read_options(new, "path-rules.toml");
+
+    let inputs = [
+        (deps_old, vec!["README.md"]),
+        (paths_old, vec!["tools/determinator/src/lib.rs"]),
+    ];
+    let bases: Vec<_> = inputs
+        .iter()
+        .map(|(old, paths)| {
+            let mut base = DeterminatorBase::new(old.graph());
+            base.add_changed_paths(paths.iter().copied());
+            base
+        })
+        .collect();
+
+    let mut determinator = Determinator::new(paths_old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator
+        .set_features_only(
+            ["fixture-manager"].iter().copied(),
+            StandardFeatures::Default,
+        )
+        .expect("fixture-manager is a valid package name");
+
+    let sets = determinator.compute_bases(&bases);
+    assert_eq!(sets.len(), 2, "one set per base");
+
+    // Each set should be the same as the result of running the determinator on its own.
+    for ((old, paths), set) in inputs.iter().zip(&sets) {
+        let mut single = Determinator::new(old.graph(), new.graph());
+        single.set_rules(&opts).expect("rules set correctly");
+        single
+            .set_features_only(
+                ["fixture-manager"].iter().copied(),
+                StandardFeatures::Default,
+            )
+            .expect("fixture-manager is a valid package name");
+        single.add_changed_paths(paths.iter().copied());
+        let single_set = single.compute();
+        assert_eq!(
+            set.path_changed_set, single_set.path_changed_set,
+            "path changed sets match"
+        );
+        assert_eq!(
+            set.summary_changed_set, single_set.summary_changed_set,
+            "summary changed sets match"
+        );
+        assert_eq!(
+            set.affected_set, single_set.affected_set,
+            "affected sets match"
+        );
+    }
+
+    // The dependency change is only visible relative to the older base.
+    assert!(
+        !sets[0].summary_changed_set.is_empty(),
+        "summary changes relative to the older base"
+    );
+    assert!(
+        sets[1].summary_changed_set.is_empty(),
+        "no summary changes relative to the same graph"
+    );
+}
+
+#[test]
+fn progress_callback() {
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let member_count = new.graph().workspace().member_count();
+
+    let reported = Mutex::new(Vec::new());
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .set_progress_callback(|progress| {
+            assert_eq!(progress.total(), member_count, "total is the member count");
+            assert!(
+                progress.package().in_workspace(),
+                "progress is reported for workspace packages"
+            );
+            reported
+                .lock()
+                .unwrap()
+                .push((progress.stage(), progress.completed()));
+        })
+        .add_changed_paths(vec!["README.md"]);
+    determinator.compute();
+    drop(determinator);
+
+    // Every package is reported once per stage, with each count reported exactly once.
+    let mut reported = reported.into_inner().unwrap();
+    reported
+        .sort_unstable_by_key(|(stage, completed)| (*stage == ProgressStage::Compare, *completed));
+    let expected: Vec<_> = [ProgressStage::Simulate, ProgressStage::Compare]
+        .into_iter()
+        .flat_map(|stage| (1..=member_count).map(move |completed| (stage, completed)))
+        .collect();
+    assert_eq!(reported, expected);
+}
+
+#[test]
+fn guppy_base_summary() {
+    let inputs = [
+        // Dependency changes.
+        (
+            JsonFixture::metadata_guppy_78cb7e8(),
+            JsonFixture::metadata_guppy_869476c(),
+            vec![PathChange::Modified("README.md".into())],
+        ),
+        // A deleted file in a removed package.
+        (
+            JsonFixture::metadata_guppy_44b62fa(),
+            JsonFixture::metadata_guppy_869476c(),
+            vec![PathChange::Deleted("tools/hakari/src/lib.rs".into())],
+        ),
+    ];
+
+    for (old, new, changes) in inputs {
+        let opts = read_options(new, "path-rules.toml");
+        let configure = |determinator: &mut Determinator| {
+            determinator.set_rules(&opts).expect("rules set correctly");
+            determinator
+                .set_features_only(
+                    ["fixture-manager"].iter().copied(),
+                    StandardFeatures::Default,
+                )
+                .expect("fixture-manager is a valid package name");
+        };
+
+        // Generate the summary at the base revision, and round-trip it through TOML.
+        let mut base_determinator = Determinator::new(old.graph(), old.graph());
+        configure(&mut base_determinator);
+        let summary = base_determinator.base_summary();
+        let serialized = summary.to_string().expect("summary serialized");
+        let summary = BaseSummary::parse(&serialized).expect("summary parsed");
+        assert_eq!(
+            summary.packages.len(),
+            old.graph().workspace().iter().len(),
+            "one entry per workspace package"
+        );
+
+        let mut graph_determinator = Determinator::new(old.graph(), new.graph());
+        configure(&mut graph_determinator);
+        graph_determinator.add_changes(changes.iter().copied());
+        let graph_set = graph_determinator.compute();
+
+        let mut summary_determinator = Determinator::with_base_summary(&summary, new.graph());
+        configure(&mut summary_determinator);
+        summary_determinator.add_changes(changes.iter().copied());
+        let summary_set = summary_determinator.compute();
+
+        assert_eq!(
+            summary_set.path_changed_set, graph_set.path_changed_set,
+            "path changed sets match"
+        );
+        assert_eq!(
+            summary_set.summary_changed_set, graph_set.summary_changed_set,
+            "summary changed sets match"
+        );
+        assert_eq!(
+            summary_set.affected_set, graph_set.affected_set,
+            "affected sets match"
+        );
+    }
+
+    // A summary generated with a different config marks every package changed.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let summary = Determinator::new(old.graph(), old.graph()).base_summary();
+    let mut determinator = Determinator::with_base_summary(&summary, new.graph());
+    assert!(
+        determinator.compute().summary_changed_set.is_empty(),
+        "no dependency changes between these fixtures"
+    );
+    determinator.set_config(DeterminatorConfig::none_only());
+    assert_eq!(
+        determinator.compute().summary_changed_set,
+        new.graph().resolve_workspace(),
+        "mismatched config marks everything changed"
+    );
+}
+
 #[test]
 fn guppy_match_paths() {
     let old = JsonFixture::metadata_guppy_869476c();
@@ -439,6 +987,60 @@ fn guppy_match_paths() {
     }
 }
 
+#[test]
+fn guppy_rule_priorities() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    let rules = DeterminatorRules::parse(
+        r#"
+        # Applied after the default rules, so this shouldn't match README.md or Cargo.lock.
+        [[path-rule]]
+        globs = ["README.md", "Cargo.lock", "guppy/src/*"]
+        mark-changed = ["cargo-guppy"]
+        priority = -1
+
+        # Applied first even though it comes after the rule above.
+        [[path-rule]]
+        globs = ["guppy/src/*"]
+        mark-changed = ["guppy"]
+        priority = 1
+        "#,
+    )
+    .expect("rules parsed");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&rules).expect("rules set correctly");
+
+    let expected = vec![
+        (
+            "README.md",
+            PathMatch::RuleMatched(RuleIndex::DefaultPath(4)),
+        ),
+        (
+            "Cargo.lock",
+            PathMatch::RuleMatched(RuleIndex::DefaultPath(3)),
+        ),
+        (
+            "guppy/src/lib.rs",
+            PathMatch::RuleMatched(RuleIndex::CustomPath(1)),
+        ),
+        (
+            "cargo-guppy/README.md",
+            PathMatch::RuleMatched(RuleIndex::DefaultPath(4)),
+        ),
+    ];
+
+    for (path, m) in expected {
+        assert_eq!(
+            determinator.match_path(path, |_| {}),
+            m,
+            "expected rule match for {}",
+            path
+        );
+    }
+}
+
 static GIT_MATCH_PATHS_DIFF: &str =
     include_str!("../../../fixtures/determinator-paths/git-diff.out");
 
@@ -512,6 +1114,470 @@ fn git_match_paths_impl(paths: Utf8Paths0) {
     );
 }
 
+#[test]
+fn workspace_excludes() {
+    // For a description of this layout, see fixtures/determinator-paths/README.md.
+    let json = include_str!("../../../fixtures/determinator-paths/workspace-excludes.json");
+    let package_graph = CargoMetadata::parse_json(json)
+        .expect("metadata json parsed correct")
+        .build_graph()
+        .expect("PackageGraph built");
+
+    let rules = DeterminatorRules::parse(
+        r#"
+        workspace-excludes = ["crates/excluded", "nested"]
+
+        [[path-rule]]
+        globs = ["nested/unused/special.txt"]
+        mark-changed = ["c"]
+        "#,
+    )
+    .expect("rules parsed");
+
+    let mut determinator = Determinator::new(&package_graph, &package_graph);
+    determinator.set_rules(&rules).expect("rules set correctly");
+
+    let expected: Vec<(_, _, &[&str])> = vec![
+        ("src/lib.rs", PathMatch::AncestorMatched, &["root"]),
+        ("crates/a/src/lib.rs", PathMatch::AncestorMatched, &["a"]),
+        // Path dependencies are matched before the workspace excludes, and mark all their
+        // dependents in the workspace changed.
+        (
+            "crates/excluded/src/lib.rs",
+            PathMatch::PathDependencyMatched,
+            &["a", "c"],
+        ),
+        (
+            "nested/inner/src/lib.rs",
+            PathMatch::PathDependencyMatched,
+            &["b"],
+        ),
+        // nested/unused isn't depended on, so it isn't in the graph. It's inside the root package
+        // but is ignored because it's in an excluded directory.
+        (
+            "nested/unused/src/lib.rs",
+            PathMatch::WorkspaceExcluded,
+            &[],
+        ),
+        ("nested/Cargo.toml", PathMatch::WorkspaceExcluded, &[]),
+        // Path rules are applied before workspace excludes.
+        (
+            "nested/unused/special.txt",
+            PathMatch::RuleMatched(RuleIndex::CustomPath(0)),
+            &["c"],
+        ),
+    ];
+
+    for (path, m, names) in expected {
+        let mut matched = vec![];
+        assert_eq!(
+            determinator.match_path(path, |id| {
+                matched.push(package_graph.metadata(id).expect("valid package ID").name())
+            }),
+            m,
+            "expected match for {}",
+            path
+        );
+        matched.sort_unstable();
+        assert_eq!(matched, names, "expected packages matched for {}", path);
+    }
+
+    // Without workspace excludes, paths in the nested workspace match the root package.
+    let default_determinator = Determinator::new(&package_graph, &package_graph);
+    assert_eq!(
+        default_determinator.match_path("nested/unused/src/lib.rs", |_| {}),
+        PathMatch::AncestorMatched,
+    );
+
+    determinator.add_changed_paths(vec![
+        "crates/excluded/src/lib.rs",
+        "nested/unused/src/lib.rs",
+    ]);
+    let determinator_set = determinator.compute();
+    let expected_path_changed = package_graph
+        .resolve_workspace_names(vec!["a", "c"])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_set.path_changed_set, expected_path_changed,
+        "correct path changed set"
+    );
+}
+
+#[test]
+fn package_metadata_rules() {
+    // Add a [package.metadata.determinator] section to b.
+    let json = with_metadata(
+        include_str!("../../../fixtures/determinator-paths/workspace-excludes.json"),
+        "path+file:///workspace-excludes/crates/b#0.1.0",
+        r#"{"determinator":{"rerun-if-changed":["migrations/**"],"virtual-deps":["c"]}}"#,
+    );
+    let package_graph = CargoMetadata::parse_json(&json)
+        .expect("metadata json parsed correct")
+        .build_graph()
+        .expect("PackageGraph built");
+    // Workspace members in path order are root, a, b and c.
+    let rule_index = RuleIndex::PackageMetadata(2);
+
+    let mut determinator = Determinator::new(&package_graph, &package_graph);
+    determinator
+        .set_rules(&DeterminatorRules::default())
+        .expect("rules set correctly");
+
+    // Files matched by rerun-if-changed mark b changed, and then fall through to the root package.
+    let mut matched = vec![];
+    assert_eq!(
+        determinator.match_path("migrations/001.sql", |id| {
+            matched.push(package_graph.metadata(id).expect("valid package ID").name())
+        }),
+        PathMatch::AncestorMatched,
+    );
+    matched.sort_unstable();
+    assert_eq!(matched, ["b", "root"], "expected packages matched");
+
+    // c is a virtual dependency of b, so changing c marks b affected.
+    determinator.add_changed_paths(vec!["crates/c/src/lib.rs"]);
+    let determinator_set = determinator.compute();
+    let expected_affected = package_graph
+        .resolve_workspace_names(vec!["b", "c"])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_set.affected_set, expected_affected,
+        "correct affected set"
+    );
+
+    // Package metadata rules can be turned off.
+    let mut rules = DeterminatorRules::default();
+    rules.use_package_metadata_rules = false;
+    let mut determinator = Determinator::new(&package_graph, &package_graph);
+    determinator.set_rules(&rules).expect("rules set correctly");
+    let mut matched = vec![];
+    determinator.match_path("migrations/001.sql", |id| {
+        matched.push(package_graph.metadata(id).expect("valid package ID").name())
+    });
+    assert_eq!(matched, ["root"], "expected packages matched");
+
+    // The same glob in a custom path rule is a conflict.
+    let rules = DeterminatorRules::parse(
+        r#"
+        [[path-rule]]
+        globs = ["migrations/**"]
+        mark-changed = ["a"]
+        "#,
+    )
+    .expect("rules parsed");
+    let mut determinator = Determinator::new(&package_graph, &package_graph);
+    let err = determinator
+        .set_rules(&rules)
+        .expect_err("conflicting globs are an error");
+    assert_eq!(err.rule_index(), rule_index);
+    assert!(
+        matches!(
+            err.kind(),
+            RulesErrorKind::Conflict { glob, other }
+                if glob == "migrations/**" && *other == RuleIndex::CustomPath(0)
+        ),
+        "unexpected error: {}",
+        err
+    );
+
+    // Invalid sections are reported.
+    let json = with_metadata(
+        include_str!("../../../fixtures/determinator-paths/workspace-excludes.json"),
+        "path+file:///workspace-excludes/crates/b#0.1.0",
+        r#"{"determinator":{"rerun-if":["migrations/**"]}}"#,
+    );
+    let package_graph = CargoMetadata::parse_json(&json)
+        .expect("metadata json parsed correct")
+        .build_graph()
+        .expect("PackageGraph built");
+    let mut determinator = Determinator::new(&package_graph, &package_graph);
+    let err = determinator
+        .set_rules(&DeterminatorRules::default())
+        .expect_err("invalid section is an error");
+    assert_eq!(err.rule_index(), rule_index);
+    assert!(
+        matches!(err.kind(), RulesErrorKind::PackageMetadata(_)),
+        "unexpected error: {}",
+        err
+    );
+}
+
+/// Replaces the metadata table of the package with the given ID in `json`.
+fn with_metadata(json: &str, package_id: &str, metadata: &str) -> String {
+    let id_start = json
+        .find(&format!(r#""id":"{}""#, package_id))
+        .expect("package ID found");
+    let metadata_start = id_start
+        + json[id_start..]
+            .find(r#""metadata":null"#)
+            .expect("metadata table found");
+    format!(
+        r#"{}"metadata":{}{}"#,
+        &json[..metadata_start],
+        metadata,
+        &json[metadata_start + r#""metadata":null"#.len()..]
+    )
+}
+
+#[test]
+fn path_changes() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    // A file moved across package boundaries marks both packages changed.
+    let paths = Utf8Paths0::new("R095\0guppy/src/graph/foo.rs\0target-spec/src/foo.rs\0");
+    let changes = PathChange::parse_name_status(&paths).expect("valid name-status output");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changes(changes);
+    let determinator_set = determinator.compute();
+    let expected = new
+        .graph()
+        .resolve_workspace_names(vec!["guppy", "target-spec"])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_set.path_changed_set, expected,
+        "both sides of the rename marked changed"
+    );
+
+    // The hakari crates were removed between these two fixtures.
+    let old = JsonFixture::metadata_guppy_44b62fa();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let workspace_set = new.graph().resolve_workspace();
+
+    // Without change information, a deleted file in a removed package doesn't match anything, so
+    // everything is marked changed.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changed_paths(vec!["tools/hakari/src/lib.rs"]);
+    assert_eq!(
+        determinator.compute().path_changed_set,
+        workspace_set,
+        "everything changed"
+    );
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changes(vec![PathChange::Deleted("tools/hakari/src/lib.rs".into())]);
+    let determinator_set = determinator.compute();
+    assert!(
+        determinator_set.path_changed_set.is_empty(),
+        "deleted file in removed package doesn't cause changes"
+    );
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changes(vec![PathChange::Renamed {
+        from: "tools/hakari/src/lib.rs".into(),
+        to: "guppy/src/hakari.rs".into(),
+    }]);
+    let determinator_set = determinator.compute();
+    let expected = new
+        .graph()
+        .resolve_workspace_names(vec!["guppy"])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_set.path_changed_set, expected,
+        "new side of the rename marked changed"
+    );
+}
+
+#[test]
+fn attribute_targets() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let guppy_set = new
+        .graph()
+        .resolve_workspace_names(vec!["guppy"])
+        .expect("workspace names resolved");
+    let guppy_id = new
+        .graph()
+        .workspace()
+        .member_by_name("guppy")
+        .expect("guppy is a workspace member")
+        .id();
+
+    // Without target attribution, a change to guppy's tests affects everything that depends on
+    // guppy.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changed_paths(vec!["guppy/tests/graph-tests/main.rs"]);
+    let determinator_set = determinator.compute();
+    assert_eq!(determinator_set.path_changed_set, guppy_set);
+    assert!(
+        determinator_set.affected_set.len() > 1,
+        "dependents of guppy affected"
+    );
+    assert!(determinator_set.changed_targets.is_empty());
+
+    // Changes to tests and examples (including files in subdirectories) only affect guppy.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .set_attribute_targets(true)
+        .add_changed_paths(vec![
+            "guppy/tests/graph-tests/main.rs",
+            "guppy/tests/graph-tests/graph_tests.rs",
+            "guppy/examples/deps.rs",
+        ]);
+    let determinator_set = determinator.compute();
+    assert_eq!(determinator_set.path_changed_set, guppy_set);
+    assert_eq!(
+        determinator_set.affected_set, guppy_set,
+        "only guppy affected"
+    );
+    let expected_targets: BTreeSet<_> = vec![
+        BuildTargetId::Test("graph-tests"),
+        BuildTargetId::Example("deps"),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(
+        determinator_set.changed_targets.get(guppy_id),
+        Some(&expected_targets),
+        "changed targets for guppy"
+    );
+
+    // Changes to the library, the manifest or files outside targets affect dependents as usual.
+    for path in &[
+        "guppy/src/lib.rs",
+        "guppy/src/graph/mod.rs",
+        "guppy/Cargo.toml",
+        "guppy/data/fixture.json",
+    ] {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator
+            .set_attribute_targets(true)
+            .add_changed_paths(vec!["guppy/examples/deps.rs", *path]);
+        let determinator_set = determinator.compute();
+        assert!(
+            determinator_set.affected_set.len() > 1,
+            "{}: dependents of guppy affected",
+            path
+        );
+        assert!(
+            determinator_set.changed_targets.is_empty(),
+            "{}: guppy entirely changed",
+            path
+        );
+    }
+
+    // A binary target at src/main.rs is attributed separately from the library next to it.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator
+        .set_attribute_targets(true)
+        .add_changed_paths(vec!["cargo-guppy/src/main.rs"]);
+    let determinator_set = determinator.compute();
+    let cargo_guppy_id = new
+        .graph()
+        .workspace()
+        .member_by_name("cargo-guppy")
+        .expect("cargo-guppy is a workspace member")
+        .id();
+    let expected_targets: BTreeSet<_> = vec![BuildTargetId::Binary("cargo-guppy")]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        determinator_set.changed_targets.get(cargo_guppy_id),
+        Some(&expected_targets),
+        "changed targets for cargo-guppy"
+    );
+}
+
+#[test]
+fn attribute_targets_artifact_deps() {
+    // None of the fixtures have artifact dependencies, so add a binary to guppy-cmdlib, and
+    // optionally make cargo-guppy depend on it through an artifact dependency.
+    let graph = |artifact: bool| {
+        let mut json: serde_json::Value =
+            serde_json::from_str(JsonFixture::metadata_guppy_c9b4f76().json()).expect("valid JSON");
+        for package in json["packages"]
+            .as_array_mut()
+            .expect("packages is an array")
+        {
+            if package["name"] == "guppy-cmdlib" {
+                let src_path = package["manifest_path"]
+                    .as_str()
+                    .expect("manifest_path is a string")
+                    .replace("Cargo.toml", "src/bin/tool.rs");
+                package["targets"]
+                    .as_array_mut()
+                    .expect("targets is an array")
+                    .push(serde_json::json!({
+                        "kind": ["bin"],
+                        "crate_types": ["bin"],
+                        "name": "tool",
+                        "src_path": src_path,
+                        "edition": "2018",
+                        "doctest": false,
+                        "test": true,
+                    }));
+            } else if package["name"] == "cargo-guppy" && artifact {
+                let dep = package["dependencies"]
+                    .as_array_mut()
+                    .expect("dependencies is an array")
+                    .iter_mut()
+                    .find(|dep| dep["name"] == "guppy-cmdlib")
+                    .expect("guppy-cmdlib found");
+                dep["artifact"] = serde_json::json!({
+                    "kinds": ["bin:tool"],
+                    "lib": true,
+                    "target": null,
+                });
+            }
+        }
+        CargoMetadata::parse_json(json.to_string())
+            .expect("metadata JSON parsed")
+            .build_graph()
+            .expect("package graph built")
+    };
+    let path = "guppy-cmdlib/src/bin/tool.rs";
+
+    // Without an artifact dependency, only guppy-cmdlib is affected.
+    let (old, new) = (graph(false), graph(false));
+    let cmdlib_id = new
+        .workspace()
+        .member_by_name("guppy-cmdlib")
+        .expect("guppy-cmdlib is a workspace member")
+        .id();
+    let mut determinator = Determinator::new(&old, &new);
+    determinator
+        .set_attribute_targets(true)
+        .add_changed_paths(vec![path]);
+    let determinator_set = determinator.compute();
+    let cmdlib_set = new
+        .resolve_workspace_names(vec!["guppy-cmdlib"])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_set.affected_set, cmdlib_set,
+        "only guppy-cmdlib affected"
+    );
+    let expected_targets: BTreeSet<_> = vec![BuildTargetId::Binary("tool")].into_iter().collect();
+    assert_eq!(
+        determinator_set.changed_targets.get(cmdlib_id),
+        Some(&expected_targets),
+        "changed targets for guppy-cmdlib"
+    );
+
+    // With an artifact dependency on the binary, cargo-guppy is affected as well.
+    let (old, new) = (graph(true), graph(true));
+    let mut determinator = Determinator::new(&old, &new);
+    determinator
+        .set_attribute_targets(true)
+        .add_changed_paths(vec![path]);
+    let determinator_set = determinator.compute();
+    assert!(
+        determinator_set.changed_targets.is_empty(),
+        "guppy-cmdlib entirely changed"
+    );
+    let affected: BTreeSet<_> = determinator_set
+        .affected_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.name())
+        .collect();
+    assert!(
+        affected.contains("cargo-guppy"),
+        "cargo-guppy affected: {:?}",
+        affected
+    );
+}
+
 fn read_options(fixture: &JsonFixture, toml_name: &str) -> DeterminatorRules {
     // Path to the determinator.toml file.
     let mut toml_path = fixture.abs_path().to_path_buf();
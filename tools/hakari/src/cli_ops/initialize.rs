@@ -1,11 +1,21 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::cli_ops::workspace_ops::{WorkspaceOp, WorkspaceOps};
-use camino::{Utf8Path, Utf8PathBuf};
+use crate::{
+    cli_ops::workspace_ops::{WorkspaceOp, WorkspaceOps},
+    HakariCargoToml,
+};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use glob::Pattern;
 use guppy::graph::PackageGraph;
 use include_dir::{include_dir, Dir, DirEntry};
-use std::{borrow::Cow, convert::TryInto, error, fmt, io};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    error, fmt, fs, io,
+};
+use toml_edit::{Document, Item};
 
 const CRATE_TEMPLATE_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/templates/crate");
 const CONFIG_TEMPLATE: &str = include_str!("../../templates/hakari.toml-in");
@@ -18,6 +28,10 @@ pub struct HakariInit<'g, 'a> {
     crate_path: &'a Utf8Path,
     config: Option<(&'a Utf8Path, &'a str)>,
     cargo_toml_comment: &'a str,
+    // Files read from a user-provided template directory, keyed by their path within it.
+    template_files: Option<BTreeMap<Utf8PathBuf, Vec<u8>>>,
+    edition: &'g str,
+    add_to_members: bool,
 }
 
 impl<'g, 'a> HakariInit<'g, 'a> {
@@ -73,15 +87,78 @@ impl<'g, 'a> HakariInit<'g, 'a> {
 
         // TODO: check package name validity.
 
+        // If a glob in workspace.members already covers the new crate, it doesn't need to be
+        // added.
+        let root_toml_path = workspace_root.join("Cargo.toml");
+        let root_toml =
+            std::fs::read_to_string(&root_toml_path).map_err(|error| InitError::Io {
+                path: root_toml_path,
+                error,
+            })?;
+        let add_to_members = !members_cover_path(&root_toml, crate_path);
+
         Ok(Self {
             package_graph,
             package_name,
             crate_path,
             config: None,
             cargo_toml_comment: "",
+            template_files: None,
+            edition: infer_edition(package_graph),
+            add_to_members,
         })
     }
 
+    /// Uses the files in the given directory as the template for the workspace-hack crate, instead
+    /// of the built-in template.
+    ///
+    /// The directory must contain a `Cargo.toml-in` file with `### BEGIN HAKARI SECTION` and
+    /// `### END HAKARI SECTION` lines. Files whose names end with `-in`, such as `Cargo.toml-in`
+    /// or `src/lib.rs-in`, are written out without that suffix, and have these placeholders
+    /// replaced:
+    ///
+    /// * `%PACKAGE_NAME%`: the name of the workspace-hack package.
+    /// * `%EDITION%`: the Rust edition used by most workspace members.
+    /// * `%CARGO_TOML_COMMENT%`: the comment set through
+    ///   [`set_cargo_toml_comment`](Self::set_cargo_toml_comment), if on its own line.
+    ///
+    /// Other files are copied as-is. If the generated `Cargo.toml` doesn't specify `edition` or
+    /// `publish` in its `[package]` section, they're set to the inferred edition and `false`
+    /// respectively.
+    pub fn set_template_dir(&mut self, template_dir: &Utf8Path) -> Result<&mut Self, InitError> {
+        let mut template_files = BTreeMap::new();
+        read_dir_recursive(template_dir, Utf8Path::new(""), &mut template_files)?;
+
+        let cargo_toml_path = template_dir.join("Cargo.toml-in");
+        let cargo_toml = match template_files.get(Utf8Path::new("Cargo.toml-in")) {
+            Some(contents) => contents,
+            None => {
+                return Err(InitError::InvalidTemplate {
+                    path: cargo_toml_path,
+                    message: "file not found".to_owned(),
+                })
+            }
+        };
+        let cargo_toml =
+            std::str::from_utf8(cargo_toml).map_err(|_| InitError::InvalidTemplate {
+                path: cargo_toml_path.clone(),
+                message: "file is not valid UTF-8".to_owned(),
+            })?;
+        if !cargo_toml.contains(HakariCargoToml::BEGIN_SECTION)
+            || !cargo_toml.contains(HakariCargoToml::END_SECTION)
+        {
+            return Err(InitError::InvalidTemplate {
+                path: cargo_toml_path,
+                message: "file must contain `### BEGIN HAKARI SECTION` and \
+                          `### END HAKARI SECTION` lines"
+                    .to_owned(),
+            });
+        }
+
+        self.template_files = Some(template_files);
+        Ok(self)
+    }
+
     /// Specifies a path, relative to the workspace root, where a stub configuration file should be
     /// written out. Also accepts a comment (in TOML format) to put at the top of the file.
     ///
@@ -121,36 +198,30 @@ impl<'g, 'a> HakariInit<'g, 'a> {
     // ---
 
     fn make_new_crate_op(&self) -> WorkspaceOp<'g, 'a> {
-        let files = CRATE_TEMPLATE_DIR
-            .find("**/*")
-            .expect("pattern **/* is valid")
-            .flat_map(|entry| {
-                match entry {
+        let files = match &self.template_files {
+            Some(template_files) => template_files
+                .iter()
+                .map(|(path, contents)| {
+                    let (path, contents) = self.render_file(path, contents);
+                    (Cow::Owned(path), Cow::Owned(contents.into_owned()))
+                })
+                .collect(),
+            None => CRATE_TEMPLATE_DIR
+                .find("**/*")
+                .expect("pattern **/* is valid")
+                .flat_map(|entry| match entry {
                     DirEntry::File(file) => {
                         let path: &Utf8Path = file
                             .path()
                             .try_into()
                             .expect("embedded path is valid UTF-8");
-                        // .toml-in files need a bit of processing.
-                        if path.extension() == Some("toml-in") {
-                            let contents = file
-                                .contents_utf8()
-                                .expect("embedded .toml-in is valid UTF-8");
-                            let contents = contents.replace("%PACKAGE_NAME%", self.package_name);
-                            let contents =
-                                contents.replace("%CARGO_TOML_COMMENT%\n", self.cargo_toml_comment);
-                            Some((
-                                Cow::Owned(path.with_extension("toml")),
-                                Cow::Owned(contents.into_bytes()),
-                            ))
-                        } else {
-                            Some((Cow::Borrowed(path), Cow::Borrowed(file.contents())))
-                        }
+                        let (path, contents) = self.render_file(path, file.contents());
+                        Some((Cow::Owned(path), contents))
                     }
                     DirEntry::Dir(_) => None,
-                }
-            })
-            .collect();
+                })
+                .collect(),
+        };
 
         let root_files = self
             .config
@@ -166,8 +237,155 @@ impl<'g, 'a> HakariInit<'g, 'a> {
             crate_path: self.crate_path,
             files,
             root_files,
+            add_to_members: self.add_to_members,
+        }
+    }
+
+    /// Processes a template file. Files ending with `-in` have that suffix removed and their
+    /// placeholders replaced.
+    fn render_file<'b>(&self, path: &Utf8Path, contents: &'b [u8]) -> (Utf8PathBuf, Cow<'b, [u8]>) {
+        let file_name = path.file_name().unwrap_or_default();
+        let (file_name, contents) =
+            match (rendered_file_name(file_name), std::str::from_utf8(contents)) {
+                (Some(file_name), Ok(contents)) => (file_name, contents),
+                _ => return (path.to_owned(), Cow::Borrowed(contents)),
+            };
+        let contents = contents
+            .replace("%PACKAGE_NAME%", self.package_name)
+            .replace("%EDITION%", self.edition)
+            .replace("%CARGO_TOML_COMMENT%\n", self.cargo_toml_comment);
+        let contents = if path.parent() == Some(Utf8Path::new("")) && file_name == "Cargo.toml" {
+            fill_package_fields(contents, self.edition)
+        } else {
+            contents
+        };
+        (
+            path.with_file_name(file_name),
+            Cow::Owned(contents.into_bytes()),
+        )
+    }
+}
+
+/// Returns the name a template file is written out as, if it's a template to be rendered: the
+/// trailing `-in` is removed. Other occurrences of `-in`, such as in `plugin-info-in`, are kept.
+fn rendered_file_name(file_name: &str) -> Option<&str> {
+    file_name
+        .strip_suffix("-in")
+        .filter(|file_name| !file_name.is_empty())
+}
+
+/// Returns the edition used by the most workspace members, preferring newer editions in case of a
+/// tie.
+fn infer_edition(package_graph: &PackageGraph) -> &str {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for package in package_graph.workspace().iter() {
+        *counts.entry(package.edition()).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|&(edition, count)| (count, edition))
+        .map_or("2021", |(edition, _)| edition)
+}
+
+/// Sets `edition` and `publish = false` in the `[package]` section of a generated `Cargo.toml`,
+/// if they aren't already specified.
+fn fill_package_fields(contents: String, edition: &str) -> String {
+    let mut doc = match contents.parse::<Document>() {
+        Ok(doc) => doc,
+        // Leave invalid TOML alone: Cargo will produce a better error message for it.
+        Err(_) => return contents,
+    };
+    let package = match doc.get_mut("package").and_then(Item::as_table_like_mut) {
+        Some(package) => package,
+        None => return contents,
+    };
+    let mut changed = false;
+    if !package.contains_key("edition") {
+        package.insert("edition", toml_edit::value(edition));
+        changed = true;
+    }
+    if !package.contains_key("publish") {
+        package.insert("publish", toml_edit::value(false));
+        changed = true;
+    }
+    if changed {
+        doc.to_string()
+    } else {
+        contents
+    }
+}
+
+/// Returns true if an entry in `workspace.members` of the root `Cargo.toml`, either a literal path
+/// or a glob pattern, matches the path, relative to the workspace root.
+fn members_cover_path(root_toml: &str, crate_path: &Utf8Path) -> bool {
+    let doc = match root_toml.parse::<Document>() {
+        Ok(doc) => doc,
+        Err(_) => return false,
+    };
+    let members = match doc
+        .get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(Item::as_array)
+    {
+        Some(members) => members,
+        None => return false,
+    };
+    let crate_components = normal_components(crate_path);
+    let covered = members
+        .iter()
+        .filter_map(|member| member.as_str())
+        .any(|member| {
+            let member_components = normal_components(Utf8Path::new(member));
+            member_components.len() == crate_components.len()
+                && member_components
+                    .iter()
+                    .zip(&crate_components)
+                    .all(|(member, component)| component_matches(member, component))
+        });
+    covered
+}
+
+fn normal_components(path: &Utf8Path) -> Vec<&str> {
+    path.components()
+        .filter(|component| *component != Utf8Component::CurDir)
+        .map(|component| component.as_str())
+        .collect()
+}
+
+/// Matches a path component against a component of a `workspace.members` entry, which may be a
+/// glob pattern.
+fn component_matches(member: &str, component: &str) -> bool {
+    match Pattern::new(member) {
+        Ok(pattern) => pattern.matches(component),
+        // Cargo rejects invalid patterns, so they can't cover any path.
+        Err(_) => false,
+    }
+}
+
+fn read_dir_recursive(
+    root: &Utf8Path,
+    rel_path: &Utf8Path,
+    files: &mut BTreeMap<Utf8PathBuf, Vec<u8>>,
+) -> Result<(), InitError> {
+    let dir = root.join(rel_path);
+    let io_error = |error| InitError::Io {
+        path: dir.clone(),
+        error,
+    };
+    for entry in dir.read_dir_utf8().map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        let rel_path = rel_path.join(entry.file_name());
+        if entry.file_type().map_err(io_error)?.is_dir() {
+            read_dir_recursive(root, &rel_path, files)?;
+        } else {
+            let contents = fs::read(entry.path()).map_err(|error| InitError::Io {
+                path: entry.path().to_owned(),
+                error,
+            })?;
+            files.insert(rel_path, contents);
         }
     }
+    Ok(())
 }
 
 /// An error that occurred while attempting to initialize `hakari`.
@@ -204,6 +422,15 @@ pub enum InitError {
         abs_path: Utf8PathBuf,
     },
 
+    /// The template directory passed in is invalid.
+    InvalidTemplate {
+        /// The path to the file in the template directory that's invalid.
+        path: Utf8PathBuf,
+
+        /// A message describing the problem.
+        message: String,
+    },
+
     /// An IO error occurred while working with the given path.
     Io {
         /// The path.
@@ -243,6 +470,9 @@ impl fmt::Display for InitError {
             InitError::WorkspacePathExists { abs_path } => {
                 write!(f, "workspace path {} already exists", abs_path)
             }
+            InitError::InvalidTemplate { path, message } => {
+                write!(f, "invalid template at {}: {}", path, message)
+            }
             InitError::Io { path, .. } => {
                 write!(f, "IO error while accessing {}", path)
             }
@@ -257,7 +487,67 @@ impl error::Error for InitError {
             InitError::ConfigPathExists { .. }
             | InitError::PackageNameExists { .. }
             | InitError::WorkspacePathNotInRoot { .. }
-            | InitError::WorkspacePathExists { .. } => None,
+            | InitError::WorkspacePathExists { .. }
+            | InitError::InvalidTemplate { .. } => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_package_fields() {
+        let template = "[package]\nname = \"workspace-hack\"\nversion = \"0.1.0\"\n\n\
+                        ### BEGIN HAKARI SECTION\n### END HAKARI SECTION\n";
+        let filled = fill_package_fields(template.to_owned(), "2021");
+        assert_eq!(
+            filled,
+            "[package]\nname = \"workspace-hack\"\nversion = \"0.1.0\"\n\
+             edition = \"2021\"\npublish = false\n\n\
+             ### BEGIN HAKARI SECTION\n### END HAKARI SECTION\n",
+            "edition and publish added"
+        );
+
+        let template = "[package]\nname = \"workspace-hack\"\nedition = \"2018\"\npublish = true\n";
+        assert_eq!(
+            fill_package_fields(template.to_owned(), "2021"),
+            template,
+            "existing fields left alone"
+        );
+    }
+
+    #[test]
+    fn test_rendered_file_name() {
+        assert_eq!(rendered_file_name("Cargo.toml-in"), Some("Cargo.toml"));
+        assert_eq!(rendered_file_name("plugin-info-in"), Some("plugin-info"));
+        assert_eq!(
+            rendered_file_name("Cargo.toml-in-in"),
+            Some("Cargo.toml-in")
+        );
+        assert_eq!(rendered_file_name("plugin-info.rs"), None);
+        assert_eq!(rendered_file_name("-in.rs"), None);
+        assert_eq!(rendered_file_name("-in"), None, "file name can't be empty");
+    }
+
+    #[test]
+    fn test_members_cover_path() {
+        let root_toml = "[workspace]\nmembers = [\"crates/*\", \"tools/cargo-?\", \"main\"]\n";
+        assert!(members_cover_path(
+            root_toml,
+            "crates/workspace-hack".into()
+        ));
+        assert!(members_cover_path(root_toml, "./tools/cargo-x".into()));
+        assert!(!members_cover_path(root_toml, "tools/cargo-xy".into()));
+        assert!(!members_cover_path(root_toml, "crates/a/b".into()));
+        assert!(members_cover_path(root_toml, "main".into()));
+        assert!(members_cover_path(root_toml, "./main".into()));
+        assert!(!members_cover_path(root_toml, "main/sub".into()));
+        assert!(!members_cover_path(root_toml, "mai".into()));
+        assert!(!members_cover_path(
+            "[package]\n",
+            "crates/workspace-hack".into()
+        ));
+    }
+}
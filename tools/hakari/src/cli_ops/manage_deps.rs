@@ -38,6 +38,9 @@ impl<'g> HakariBuilder<'g> {
                     (Some(link), true) => match self.dep_format_version {
                         DepFormatVersion::V1 => None,
                         DepFormatVersion::V2 => needs_update_v2(hakari_package, link).then(|| true),
+                        DepFormatVersion::V3 => (needs_update_v2(hakari_package, link)
+                            || self.registry_needs_update(link))
+                        .then(|| true),
                     },
                     (None, false) => None,
                 }
@@ -53,6 +56,7 @@ impl<'g> HakariBuilder<'g> {
                     .expect("hakari package is in workspace"),
                 version: hakari_package.version(),
                 dep_format: self.dep_format_version,
+                registry: self.hakari_registry(),
                 add_to,
             });
         }
@@ -87,7 +91,11 @@ impl<'g> HakariBuilder<'g> {
                     .link_to(hakari_package.id())
                     .expect("valid package ID");
                 match link_opt {
-                    Some(link) => needs_update_v2(hakari_package, link),
+                    Some(link) => {
+                        needs_update_v2(hakari_package, link)
+                            || (self.dep_format_version == DepFormatVersion::V3
+                                && self.registry_needs_update(link))
+                    }
                     None => true,
                 }
             })
@@ -102,6 +110,7 @@ impl<'g> HakariBuilder<'g> {
                     .workspace_path()
                     .expect("hakari package is in workspace"),
                 dep_format: self.dep_format_version,
+                registry: self.hakari_registry(),
                 add_to,
             })
         } else {
@@ -144,6 +153,21 @@ impl<'g> HakariBuilder<'g> {
         };
         Some(WorkspaceOps::new(graph, op))
     }
+
+    /// Returns true if the `registry` key of a `workspace-hack = ...` line needs to be updated.
+    ///
+    /// Cargo resolves registry names to index URLs, so the expected registry is looked up in the
+    /// list of alternate registries. If it isn't listed there, the line is only checked for the
+    /// presence of a registry.
+    fn registry_needs_update(&self, link: PackageLink<'_>) -> bool {
+        match self.hakari_registry() {
+            Some(name) => match self.registries.get_by_left(name) {
+                Some(url) => link.registry() != Some(url.as_str()),
+                None => link.registry().is_none(),
+            },
+            None => link.registry().is_some(),
+        }
+    }
 }
 
 #[allow(clippy::if_same_then_else, clippy::needless_bool)]
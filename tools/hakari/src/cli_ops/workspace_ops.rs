@@ -6,16 +6,15 @@ use atomicwrites::{AtomicFile, OverwriteBehavior};
 use camino::{Utf8Path, Utf8PathBuf};
 use guppy::{
     graph::{DependencyDirection, PackageGraph, PackageMetadata, PackageSet},
-    Version,
+    DependencyKind, Version,
 };
+use guppy_edit::{DependencySection, EditError, ManifestEditor};
 use owo_colors::{OwoColorize, Style};
 use std::{
     borrow::Cow, cmp::Ordering, collections::BTreeMap, convert::TryFrom, error, fmt, fs, io,
     io::Write,
 };
-use toml_edit::{
-    Array, Document, Formatted, InlineTable, Item, Table, TableLike, TomlError, Value,
-};
+use toml_edit::{Array, Document, Formatted, InlineTable, Item, TomlError, Value};
 
 /// Represents a set of write operations to the workspace.
 #[derive(Clone, Debug)]
@@ -64,12 +63,14 @@ pub(crate) enum WorkspaceOp<'g, 'a> {
         crate_path: &'a Utf8Path,
         files: BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
         root_files: BTreeMap<Cow<'a, Utf8Path>, Cow<'a, [u8]>>,
+        add_to_members: bool,
     },
     AddDependency {
         name: &'a str,
         crate_path: &'a Utf8Path,
         version: &'a Version,
         dep_format: DepFormatVersion,
+        registry: Option<&'a str>,
         add_to: PackageSet<'g>,
     },
     RemoveDependency {
@@ -85,6 +86,7 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
                 crate_path,
                 files,
                 root_files,
+                add_to_members,
             } => {
                 Self::create_new_crate(workspace_root, crate_path, files)?;
                 // Now that the crate has been created, we can canonicalize it.
@@ -99,18 +101,29 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
                     write_contents(contents, &abs_path)?;
                 }
 
-                Self::add_to_root_toml(workspace_root, &crate_path)
+                if *add_to_members {
+                    Self::add_to_root_toml(workspace_root, &crate_path)?;
+                }
+                Ok(())
             }
             WorkspaceOp::AddDependency {
                 name,
                 crate_path,
                 version,
                 dep_format,
+                registry,
                 add_to,
             } => {
                 let crate_path = canonical_rel_path(crate_path, workspace_root)?;
                 for package in add_to.packages(DependencyDirection::Reverse) {
-                    Self::add_to_cargo_toml(name, version, &crate_path, *dep_format, package)?;
+                    Self::add_to_cargo_toml(
+                        name,
+                        version,
+                        &crate_path,
+                        *dep_format,
+                        *registry,
+                        package,
+                    )?;
                 }
                 Ok(())
             }
@@ -271,11 +284,10 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
         version: &Version,
         crate_path: &Utf8Path,
         dep_format: DepFormatVersion,
+        registry: Option<&str>,
         package: PackageMetadata<'g>,
     ) -> Result<(), ApplyError> {
-        let manifest_path = package.manifest_path();
-        let mut doc = read_toml(manifest_path)?;
-        let dep_table = Self::get_or_insert_dependencies_table(manifest_path, &mut doc)?;
+        let mut editor = ManifestEditor::for_package(package).map_err(ApplyError::edit)?;
 
         let package_path = package
             .source()
@@ -285,24 +297,30 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
         let path = pathdiff::diff_utf8_paths(crate_path, package_path)
             .expect("both new_path and package_path are relative");
 
-        let path_table = Self::inline_table_for_add(version, dep_format, &path);
-
-        dep_table.insert(name, Item::Value(Value::InlineTable(path_table)));
+        let path_table = Self::inline_table_for_add(version, dep_format, &path, registry);
 
-        write_document(&doc, manifest_path)
+        editor
+            .insert_dependency(
+                &DependencySection::new(DependencyKind::Normal),
+                name,
+                path_table,
+            )
+            .map_err(ApplyError::edit)?;
+        editor.write().map_err(ApplyError::edit)
     }
 
     fn inline_table_for_add(
         version: &Version,
         dep_format: DepFormatVersion,
         path: &Utf8Path,
+        registry: Option<&str>,
     ) -> InlineTable {
         let mut itable = InlineTable::new();
 
         // Pass in exact_versions = false because we don't want unnecessary churn in the unlikely
         // event that a published workspace-hack version has a minor bump in it.
-        let version_str = format!("{}", VersionDisplay::new(version, false));
-        if dep_format == DepFormatVersion::V2 {
+        let version_str = hakari_version_req(version, false);
+        if dep_format != DepFormatVersion::V1 {
             itable.insert("version", version_str.into());
         }
 
@@ -315,54 +333,37 @@ impl<'g, 'a> WorkspaceOp<'g, 'a> {
         }
         itable.insert("path", Value::String(path));
 
-        if dep_format == DepFormatVersion::V2 {
+        if dep_format == DepFormatVersion::V3 {
+            if let Some(registry) = registry {
+                itable.insert("registry", registry.into());
+            }
+        }
+
+        if dep_format != DepFormatVersion::V1 {
             itable.fmt();
         }
         itable
     }
 
     fn remove_from_cargo_toml(name: &str, package: PackageMetadata<'g>) -> Result<(), ApplyError> {
-        let manifest_path = package.manifest_path();
-        let mut doc = read_toml(manifest_path)?;
-        let dep_table = Self::get_or_insert_dependencies_table(manifest_path, &mut doc)?;
+        let mut editor = ManifestEditor::for_package(package).map_err(ApplyError::edit)?;
         // TODO: someone might have added the workspace-hack package under a different name.
         // Handle that if someone complains.
-        dep_table.remove(name);
-
-        write_document(&doc, manifest_path)
+        editor
+            .remove_dependency(&DependencySection::new(DependencyKind::Normal), name)
+            .map_err(ApplyError::edit)?;
+        editor.write().map_err(ApplyError::edit)
     }
+}
 
-    fn get_or_insert_dependencies_table<'doc>(
-        manifest_path: &Utf8Path,
-        doc: &'doc mut Document,
-    ) -> Result<&'doc mut dyn TableLike, ApplyError> {
-        let doc_table = doc.as_table_mut();
-
-        if doc_table.contains_key("dependencies") {
-            match doc_table
-                .get_mut("dependencies")
-                .expect("just checked for presence of dependencies")
-                .as_table_like_mut()
-            {
-                Some(table) => Ok(table),
-                None => Err(ApplyError::misc(
-                    "[dependencies] is not a table",
-                    manifest_path,
-                )),
-            }
-        } else {
-            // Add the dependencies table.
-            let mut new_table = Table::new();
-            new_table.set_implicit(true);
-            doc_table.insert("dependencies", Item::Table(new_table));
-            let table = doc_table
-                .get_mut("dependencies")
-                .expect("was just inserted")
-                .as_table_like_mut()
-                .expect("was just inserted");
-            Ok(table)
-        }
-    }
+/// Returns the version requirement written out for dependencies on the workspace-hack package, in
+/// the format `cargo hakari manage-deps` uses.
+///
+/// If `exact_versions` is true or `version` is a pre-release, the version is written out exactly.
+/// Otherwise, this is the shortest requirement that matches `version` and later compatible
+/// versions, e.g. `"0.2"` for `0.2.3`.
+pub fn hakari_version_req(version: &Version, exact_versions: bool) -> String {
+    VersionDisplay::new(version, exact_versions).to_string()
 }
 
 fn decorate(existing: &Value, new: impl Into<Value>) -> Value {
@@ -484,6 +485,14 @@ impl ApplyError {
             kind: ApplyErrorKind::Misc,
         }
     }
+
+    fn edit(err: EditError) -> Self {
+        Self {
+            message: err.message().to_owned(),
+            path: err.path().to_owned(),
+            kind: ApplyErrorKind::Edit { err: Box::new(err) },
+        }
+    }
 }
 
 impl fmt::Display for ApplyError {
@@ -497,6 +506,7 @@ impl error::Error for ApplyError {
         match &self.kind {
             ApplyErrorKind::Io { err } => Some(err),
             ApplyErrorKind::Toml { err } => Some(err),
+            ApplyErrorKind::Edit { err } => err.source(),
             ApplyErrorKind::Misc => None,
         }
     }
@@ -506,6 +516,7 @@ impl error::Error for ApplyError {
 enum ApplyErrorKind {
     Io { err: io::Error },
     Toml { err: TomlError },
+    Edit { err: Box<EditError> },
     Misc,
 }
 
@@ -541,6 +552,7 @@ impl<'g, 'a, 'ops> fmt::Display for WorkspaceOpsDisplay<'g, 'a, 'ops> {
                     crate_path,
                     files,
                     root_files,
+                    add_to_members,
                 } => {
                     write!(
                         f,
@@ -556,13 +568,15 @@ impl<'g, 'a, 'ops> fmt::Display for WorkspaceOpsDisplay<'g, 'a, 'ops> {
                     } else {
                         writeln!(f)?;
                     }
-                    writeln!(
-                        f,
-                        "* {} at {} to {}",
-                        "add crate".style(self.styles.add_bold_style),
-                        crate_path.style(self.styles.add_style),
-                        workspace_root_manifest.style(self.styles.add_to_style),
-                    )?;
+                    if *add_to_members {
+                        writeln!(
+                            f,
+                            "* {} at {} to {}",
+                            "add crate".style(self.styles.add_bold_style),
+                            crate_path.style(self.styles.add_style),
+                            workspace_root_manifest.style(self.styles.add_to_style),
+                        )?;
+                    }
                     if !root_files.is_empty() {
                         writeln!(
                             f,
@@ -578,17 +592,25 @@ impl<'g, 'a, 'ops> fmt::Display for WorkspaceOpsDisplay<'g, 'a, 'ops> {
                     name,
                     version,
                     crate_path,
-                    dep_format: _,
+                    dep_format,
+                    registry,
                     add_to,
                 } => {
-                    writeln!(
+                    write!(
                         f,
-                        "* {} {} v{} (at path {}) to packages:",
+                        "* {} {} v{} (at path {}",
                         "add or update dependency".style(self.styles.add_bold_style),
                         name.style(self.styles.add_style),
                         version.style(self.styles.add_style),
                         crate_path.style(self.styles.add_style),
                     )?;
+                    match registry {
+                        Some(registry) if *dep_format == DepFormatVersion::V3 => {
+                            write!(f, ", registry {}", registry.style(self.styles.add_style))?;
+                        }
+                        _ => {}
+                    }
+                    writeln!(f, ") to packages:")?;
                     for (name, path) in package_names_paths(add_to) {
                         writeln!(
                             f,
@@ -673,20 +695,52 @@ mod tests {
     #[test]
     fn test_inline_table_for_add() {
         let version: Version = "1.2.3".parse().unwrap();
-        let itable =
-            WorkspaceOp::inline_table_for_add(&version, DepFormatVersion::V1, "../../path".into());
+        let itable = WorkspaceOp::inline_table_for_add(
+            &version,
+            DepFormatVersion::V1,
+            "../../path".into(),
+            Some("my-registry"),
+        );
         assert_eq!(
             format!("{}", itable),
             "{ path = \"../../path\"}",
             "dep format v1 matches"
         );
 
-        let itable =
-            WorkspaceOp::inline_table_for_add(&version, DepFormatVersion::V2, "../../path".into());
+        let itable = WorkspaceOp::inline_table_for_add(
+            &version,
+            DepFormatVersion::V2,
+            "../../path".into(),
+            Some("my-registry"),
+        );
         assert_eq!(
             format!("{}", itable),
             "{ version = \"1\", path = \"../../path\" }",
             "dep format v2 matches"
         );
+
+        let itable = WorkspaceOp::inline_table_for_add(
+            &version,
+            DepFormatVersion::V3,
+            "../../path".into(),
+            Some("my-registry"),
+        );
+        assert_eq!(
+            format!("{}", itable),
+            "{ version = \"1\", path = \"../../path\", registry = \"my-registry\" }",
+            "dep format v3 matches"
+        );
+
+        let itable = WorkspaceOp::inline_table_for_add(
+            &version,
+            DepFormatVersion::V3,
+            "../../path".into(),
+            None,
+        );
+        assert_eq!(
+            format!("{}", itable),
+            "{ version = \"1\", path = \"../../path\" }",
+            "dep format v3 without a registry matches"
+        );
     }
 }
@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::explain::HakariExplain;
-use guppy::graph::{feature::StandardFeatures, DependencyDirection};
+use guppy::graph::{
+    feature::{FeatureId, FeatureTrace, StandardFeatures},
+    DependencyDirection, PackageGraph,
+};
 use itertools::{Itertools, Position};
 use owo_colors::{OwoColorize, Style};
 use std::{collections::BTreeSet, fmt};
@@ -143,6 +146,23 @@ impl<'g, 'a, 'explain> fmt::Display for HakariExplainDisplay<'g, 'a, 'explain> {
                         .add_ansi_cell(platform_display);
                     table.add_row(row);
                 }
+
+                if !inner.traces.is_empty() {
+                    table.add_heading(format!(
+                        "\n  {}",
+                        "feature chains:".style(self.styles.header_style)
+                    ));
+                    for trace in &inner.traces {
+                        table.add_heading(format!(
+                            "    {}",
+                            TraceDisplay {
+                                graph: self.explain.graph,
+                                trace,
+                                styles: &self.styles,
+                            }
+                        ));
+                    }
+                }
             }
         }
 
@@ -205,3 +225,36 @@ impl<'g, 'a> fmt::Display for FeatureDisplay<'g, 'a> {
         Ok(())
     }
 }
+
+/// Displays a feature trace as a chain of `package/feature` items.
+struct TraceDisplay<'g, 'explain> {
+    graph: &'g PackageGraph,
+    trace: &'explain FeatureTrace<'g>,
+    styles: &'explain Styles,
+}
+
+impl<'g, 'explain> TraceDisplay<'g, 'explain> {
+    fn fmt_feature(&self, feature_id: FeatureId<'g>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let package = self
+            .graph
+            .metadata(feature_id.package_id())
+            .expect("trace derived from package graph");
+        write!(
+            f,
+            "{}/{}",
+            package.name().style(self.styles.package_name_style),
+            feature_id.label().style(self.styles.feature_style),
+        )
+    }
+}
+
+impl<'g, 'explain> fmt::Display for TraceDisplay<'g, 'explain> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_feature(self.trace.initial(), f)?;
+        for step in self.trace.steps() {
+            write!(f, " -> ")?;
+            self.fmt_feature(step.to(), f)?;
+        }
+        Ok(())
+    }
+}
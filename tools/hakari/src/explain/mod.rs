@@ -15,7 +15,11 @@ pub use display::HakariExplainDisplay;
 
 use crate::{explain::simplify::*, Hakari};
 use guppy::{
-    graph::{cargo::BuildPlatform, feature::StandardFeatures, PackageGraph, PackageMetadata},
+    graph::{
+        cargo::{BuildPlatform, CargoSet},
+        feature::{FeatureId, FeatureTrace, StandardFeatures},
+        DependencyDirection, PackageGraph, PackageMetadata,
+    },
     PackageId,
 };
 use std::{
@@ -46,6 +50,10 @@ struct ExplainInner<'g> {
     workspace_packages: BTreeMap<&'g PackageId, ExplainInnerValue<'g>>,
     #[cfg_attr(not(feature = "cli-support"), allow(dead_code))]
     fixup_platforms: Vec<Simple<Option<usize>>>,
+    // Chains explaining how each feature in the set is enabled, through the workspace package
+    // displayed first.
+    #[cfg_attr(not(feature = "cli-support"), allow(dead_code))]
+    traces: Vec<FeatureTrace<'g>>,
 }
 
 #[derive(Clone, Debug)]
@@ -67,8 +75,19 @@ impl<'g, 'a> HakariExplain<'g, 'a> {
         let metadata = hakari.builder.graph().metadata(dep_id)?;
         let intermediate = ExplainIntermediate::new(hakari, metadata.id())?;
 
-        let target_map = Self::simplify_map(hakari, intermediate.target_map);
-        let host_map = Self::simplify_map(hakari, intermediate.host_map);
+        let mut tracer = FeatureTracer::new(hakari, metadata.id());
+        let target_map = Self::simplify_map(
+            hakari,
+            intermediate.target_map,
+            BuildPlatform::Target,
+            &mut tracer,
+        );
+        let host_map = Self::simplify_map(
+            hakari,
+            intermediate.host_map,
+            BuildPlatform::Host,
+            &mut tracer,
+        );
 
         Ok(Self {
             graph,
@@ -79,7 +98,12 @@ impl<'g, 'a> HakariExplain<'g, 'a> {
         })
     }
 
-    fn simplify_map(hakari: &'a Hakari<'g>, map: IntermediateMap<'g, 'a>) -> ExplainMap<'g, 'a> {
+    fn simplify_map(
+        hakari: &'a Hakari<'g>,
+        map: IntermediateMap<'g, 'a>,
+        build_platform: BuildPlatform,
+        tracer: &mut FeatureTracer<'g, 'a>,
+    ) -> ExplainMap<'g, 'a> {
         const STANDARD_FEATURES_COUNT: usize = 3;
         const INCLUDE_DEV_COUNT: usize = 2;
         // +1 for the None case
@@ -87,6 +111,7 @@ impl<'g, 'a> HakariExplain<'g, 'a> {
 
         map.into_iter()
             .map(|(features, inner)| {
+                let traces = tracer.traces(build_platform, features, &inner);
                 let workspace_packages = inner
                     .workspace_packages
                     .into_iter()
@@ -104,6 +129,7 @@ impl<'g, 'a> HakariExplain<'g, 'a> {
                     ExplainInner {
                         workspace_packages,
                         fixup_platforms,
+                        traces,
                     },
                 )
             })
@@ -211,3 +237,126 @@ impl<'g, 'a> ExplainIntermediate<'g, 'a> {
         })
     }
 }
+
+/// Computes feature traces for explain output.
+#[derive(Debug)]
+struct FeatureTracer<'g, 'a> {
+    hakari: &'a Hakari<'g>,
+    dep_id: &'g PackageId,
+    // Several feature sets are often built by the same package and options, so cache builds.
+    cache: BTreeMap<(&'g PackageId, bool, StandardFeatures, Option<usize>), CargoSet<'g>>,
+}
+
+impl<'g, 'a> FeatureTracer<'g, 'a> {
+    fn new(hakari: &'a Hakari<'g>, dep_id: &'g PackageId) -> Self {
+        Self {
+            hakari,
+            dep_id,
+            cache: BTreeMap::new(),
+        }
+    }
+
+    /// Returns a chain for each feature in the set, through the workspace package that is
+    /// displayed first (the first one in reverse dependency order).
+    fn traces(
+        &mut self,
+        build_platform: BuildPlatform,
+        features: &BTreeSet<&'g str>,
+        inner: &IntermediateInner<'g>,
+    ) -> Vec<FeatureTrace<'g>> {
+        let graph = self.hakari.builder.graph();
+        let package_id = match graph
+            .resolve_ids(inner.workspace_packages.keys().copied())
+            .expect("keys derived from package graph")
+            .package_ids(DependencyDirection::Reverse)
+            .next()
+        {
+            Some(package_id) => package_id,
+            // This feature set is only present because of a post-compute fixup.
+            None => return vec![],
+        };
+        let value = &inner.workspace_packages[package_id];
+        let &(include_dev, standard_features, platform_idx) = value
+            .sets
+            .iter()
+            .next()
+            .expect("at least one set is present");
+
+        let builder = &self.hakari.builder;
+        let cargo_set = self
+            .cache
+            .entry((package_id, include_dev, standard_features, platform_idx))
+            .or_insert_with(|| {
+                builder.make_cargo_set(value.metadata, standard_features, include_dev, platform_idx)
+            });
+        // Host features are often enabled by target packages (e.g. through build dependencies), so
+        // trace through both platforms.
+        let feature_set = match build_platform {
+            BuildPlatform::Target => cargo_set.target_features().clone(),
+            BuildPlatform::Host => cargo_set.host_features().union(cargo_set.target_features()),
+        };
+
+        let feature_ids: Vec<_> = if features.is_empty() {
+            vec![FeatureId::base(self.dep_id)]
+        } else {
+            features
+                .iter()
+                .map(|feature| FeatureId::named(self.dep_id, feature))
+                .collect()
+        };
+        feature_ids
+            .into_iter()
+            .filter_map(|feature_id| {
+                feature_set
+                    .trace(feature_id)
+                    .expect("feature ID is known to the graph")
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+    use guppy::graph::feature::FeatureLabel;
+
+    #[test]
+    fn explain_traces() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let hakari = HakariBuilder::new(graph, None)
+            .expect("HakariBuilder::new succeeded")
+            .compute();
+        let syn = graph
+            .resolve_package_name("syn")
+            .packages(DependencyDirection::Forward)
+            .find(|package| package.version().major == 1)
+            .expect("syn 1 is present");
+        let explain = hakari.explain(syn.id()).expect("syn is unified");
+
+        let mut trace_count = 0;
+        for (_, explain_map) in explain.explain_maps() {
+            for (features, inner) in explain_map {
+                for trace in &inner.traces {
+                    trace_count += 1;
+                    let feature_id = trace.feature();
+                    assert_eq!(feature_id.package_id(), syn.id(), "trace ends at syn");
+                    match feature_id.label() {
+                        FeatureLabel::Named(name) => assert!(features.contains(name)),
+                        FeatureLabel::Base => assert!(features.is_empty()),
+                        FeatureLabel::OptionalDependency(_) => {
+                            panic!("unexpected trace to {}", feature_id)
+                        }
+                    }
+
+                    let initial = graph
+                        .metadata(trace.initial().package_id())
+                        .expect("initial is known");
+                    assert!(initial.in_workspace(), "trace starts in the workspace");
+                }
+            }
+        }
+        assert!(trace_count > 0, "at least one trace is present");
+    }
+}
@@ -14,9 +14,10 @@ use guppy::{
     graph::{
         cargo::{BuildPlatform, CargoOptions, CargoResolverVersion, CargoSet, InitialsPlatform},
         feature::{FeatureId, FeatureLabel, FeatureSet, StandardFeatures},
-        DependencyDirection, PackageGraph, PackageMetadata,
+        DependencyDirection, ExternalSource, PackageGraph, PackageLink, PackageMetadata,
+        PackagePublish,
     },
-    platform::{Platform, PlatformSpec, TargetFeatures},
+    platform::{Platform, PlatformSpec, TargetFeatures, TargetSpec},
     PackageId,
 };
 use rayon::prelude::*;
@@ -38,11 +39,15 @@ pub struct HakariBuilder<'g> {
     resolver: CargoResolverVersion,
     pub(crate) verify_mode: bool,
     pub(crate) traversal_excludes: HashSet<&'g PackageId>,
-    final_excludes: HashSet<&'g PackageId>,
+    pub(crate) final_excludes: HashSet<&'g PackageId>,
+    platform_final_excludes: BTreeMap<String, (TargetSpec, HashSet<&'g PackageId>)>,
+    unify_only: BTreeSet<String>,
     pub(crate) registries: BiHashMap<String, String>,
+    pub(crate) hakari_registry: Option<String>,
     unify_target_host: UnifyTargetHost,
     output_single_feature: bool,
     pub(crate) dep_format_version: DepFormatVersion,
+    patched_deps: PatchedDeps,
 }
 
 impl<'g> HakariBuilder<'g> {
@@ -77,10 +82,14 @@ impl<'g> HakariBuilder<'g> {
             verify_mode: false,
             traversal_excludes: HashSet::new(),
             final_excludes: HashSet::new(),
+            platform_final_excludes: BTreeMap::new(),
+            unify_only: BTreeSet::new(),
             registries: BiHashMap::new(),
+            hakari_registry: None,
             unify_target_host: UnifyTargetHost::default(),
             output_single_feature: false,
             dep_format_version: DepFormatVersion::default(),
+            patched_deps: PatchedDeps::default(),
         })
     }
 
@@ -238,6 +247,54 @@ impl<'g> HakariBuilder<'g> {
         Ok(self.final_excludes.contains(package_id))
     }
 
+    /// Adds packages to be removed from the final output on platforms matching a target spec.
+    ///
+    /// `spec` is a target triple or a `cfg()` expression, as in `[target.'cfg(windows)']`
+    /// sections in `Cargo.toml` files. This is useful for packages that break builds on some
+    /// platforms when their features are unified, such as `openssl-sys` on Windows.
+    ///
+    /// These packages are removed from the sections for [platforms](Self::set_platforms) that
+    /// `spec` matches, or might match because of unknown target features. Since the
+    /// platform-independent sections apply to every platform, these packages are moved out of them
+    /// and into the sections for the remaining platforms. This means that on platforms not
+    /// specified through `set_platforms`, these packages aren't unified at all.
+    ///
+    /// Returns an error if `spec` couldn't be parsed, or if any package IDs specified aren't known
+    /// to the graph.
+    pub fn add_platform_final_excludes<'b>(
+        &mut self,
+        spec: impl Into<String>,
+        excludes: impl IntoIterator<Item = &'b PackageId>,
+    ) -> Result<&mut Self, guppy::Error> {
+        let spec = spec.into();
+        let final_excludes: Vec<&'g PackageId> = excludes
+            .into_iter()
+            .map(|package_id| Ok(self.graph.metadata(package_id)?.id()))
+            .collect::<Result<_, _>>()?;
+        let target_spec = TargetSpec::new(spec.clone()).map_err(|err| {
+            guppy::Error::TargetSpecError(
+                format!("parsing hakari final-excludes spec '{}'", spec),
+                err,
+            )
+        })?;
+        self.platform_final_excludes
+            .entry(spec)
+            .or_insert_with(|| (target_spec, HashSet::new()))
+            .1
+            .extend(final_excludes);
+        Ok(self)
+    }
+
+    /// Returns the target specs and the packages to be removed from the final output on platforms
+    /// matching them.
+    pub fn platform_final_excludes<'b>(
+        &'b self,
+    ) -> impl Iterator<Item = (&'b str, impl Iterator<Item = &'g PackageId> + 'b)> + 'b {
+        self.platform_final_excludes
+            .iter()
+            .map(|(spec, (_, excludes))| (spec.as_str(), excludes.iter().copied()))
+    }
+
     /// Returns true if a package ID is excluded from either the traversal or the final output.
     ///
     /// Also returns true for the Hakari package if specified. This is because the Hakari package is
@@ -249,6 +306,34 @@ impl<'g> HakariBuilder<'g> {
         Ok(self.is_traversal_excluded(package_id)? || self.is_final_excluded(package_id)?)
     }
 
+    /// Restricts the final output to packages with the given names.
+    ///
+    /// Unlike [`final_excludes`](Self::final_excludes), this is an allowlist: packages whose names
+    /// aren't specified here are removed from the final output. All packages are still considered
+    /// during traversals, so feature sets are computed the same way as without this option. This
+    /// is useful for unifying a few heavy dependencies, such as `syn`, without adding every other
+    /// dependency to the workspace-hack package.
+    ///
+    /// All versions of a package with a given name are unified. Names that don't match any
+    /// package are ignored.
+    ///
+    /// This replaces any names previously set. If no names are set (the default), all packages are
+    /// unified.
+    pub fn set_unify_only(
+        &mut self,
+        names: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.unify_only = names.into_iter().map(|name| name.into()).collect();
+        self
+    }
+
+    /// Returns the names of packages the final output is restricted to.
+    ///
+    /// If this is empty, the final output isn't restricted.
+    pub fn unify_only<'b>(&'b self) -> impl ExactSizeIterator<Item = &'b str> + 'b {
+        self.unify_only.iter().map(|name| name.as_str())
+    }
+
     /// Add alternate registries by (name, URL) pairs.
     ///
     /// This is a temporary workaround until [Cargo issue #9052](https://github.com/rust-lang/cargo/issues/9052)
@@ -265,6 +350,35 @@ impl<'g> HakariBuilder<'g> {
         self
     }
 
+    /// Sets the name of the registry the Hakari package is published to.
+    ///
+    /// With [`DepFormatVersion::V3`], this is added as the `registry` key of `workspace-hack = ...`
+    /// lines in other `Cargo.toml` files. The name should be listed in
+    /// [`add_registries`](Self::add_registries) so that existing lines can be checked against it.
+    ///
+    /// If this isn't set, and the Hakari package's `publish` field lists exactly one registry
+    /// other than `crates-io`, that registry is used.
+    pub fn set_hakari_registry(&mut self, registry: Option<String>) -> &mut Self {
+        self.hakari_registry = registry;
+        self
+    }
+
+    /// Returns the name of the registry the Hakari package is published to, if any.
+    ///
+    /// This is either the value passed into [`set_hakari_registry`](Self::set_hakari_registry),
+    /// or inferred from the Hakari package's `publish` field.
+    pub fn hakari_registry(&self) -> Option<&str> {
+        if let Some(registry) = &self.hakari_registry {
+            return Some(registry.as_str());
+        }
+        match self.hakari_package()?.publish() {
+            PackagePublish::Registries([registry]) if registry != PackagePublish::CRATES_IO => {
+                Some(registry.as_str())
+            }
+            _ => None,
+        }
+    }
+
     /// Whether and how to unify feature sets across target and host platforms.
     ///
     /// This is an advanced feature that most users don't need to set. For more information about
@@ -309,6 +423,30 @@ impl<'g> HakariBuilder<'g> {
         self.dep_format_version
     }
 
+    /// How to output dependencies that are replaced through `[patch]` sections.
+    ///
+    /// For more, see the documentation for [`PatchedDeps`](PatchedDeps).
+    pub fn set_patched_deps(&mut self, patched_deps: PatchedDeps) -> &mut Self {
+        self.patched_deps = patched_deps;
+        self
+    }
+
+    /// Returns the current value of `patched_deps`.
+    pub fn patched_deps(&self) -> PatchedDeps {
+        self.patched_deps
+    }
+
+    /// Returns true if this package replaces a dependency through a `[patch]` section.
+    ///
+    /// A package is considered patched if any package depends on it with a declared source that's
+    /// different from the package's own source: for example, a crates.io dependency that's
+    /// resolved to a Git repository or a local path.
+    pub fn is_patched(&self, package: &PackageMetadata<'g>) -> bool {
+        package
+            .reverse_direct_links()
+            .any(|link| is_patched_link(&link))
+    }
+
     /// Computes the `Hakari` for this builder.
     pub fn compute(self) -> Hakari<'g> {
         Hakari::build(self)
@@ -325,6 +463,32 @@ impl<'g> HakariBuilder<'g> {
         self.traversal_excludes.iter().copied()
     }
 
+    /// Simulates a build of this workspace package, with the same options used while computing
+    /// the hakari map.
+    pub(crate) fn make_cargo_set(
+        &self,
+        package: PackageMetadata<'g>,
+        features: StandardFeatures,
+        include_dev: bool,
+        platform_idx: Option<usize>,
+    ) -> CargoSet<'g> {
+        let platform_spec = match platform_idx {
+            Some(idx) => PlatformSpec::Platform(self.platforms[idx].clone()),
+            None => PlatformSpec::Always,
+        };
+        let excludes = self.make_traversal_excludes();
+        let mut cargo_options = CargoOptions::new();
+        cargo_options
+            .set_include_dev(include_dev)
+            .set_resolver(self.resolver)
+            .set_platform(platform_spec)
+            .add_omitted_packages(excludes.iter());
+
+        let initials = package.to_package_set().to_feature_set(features);
+        CargoSet::new(initials, self.make_features_only(), &cargo_options)
+            .expect("cargo resolution should succeed")
+    }
+
     fn make_traversal_excludes<'b>(&'b self) -> TraversalExcludes<'g, 'b> {
         let hakari_package = if self.verify_mode {
             None
@@ -405,6 +569,7 @@ mod summaries {
                 .collect();
             let final_excludes = summary
                 .final_excludes
+                .packages
                 .to_package_set_registry(
                     graph,
                     |name| registries.get_by_left(name).map(|s| s.as_str()),
@@ -412,6 +577,28 @@ mod summaries {
                 )?
                 .package_ids(DependencyDirection::Forward)
                 .collect();
+            let platform_final_excludes = summary
+                .final_excludes
+                .platform
+                .iter()
+                .map(|(spec, excludes)| {
+                    let target_spec = TargetSpec::new(spec.clone()).map_err(|err| {
+                        guppy::Error::TargetSpecError(
+                            format!("parsing hakari final-excludes spec '{}'", spec),
+                            err,
+                        )
+                    })?;
+                    let excludes = excludes
+                        .to_package_set_registry(
+                            graph,
+                            |name| registries.get_by_left(name).map(|s| s.as_str()),
+                            "resolving hakari platform final-excludes",
+                        )?
+                        .package_ids(DependencyDirection::Forward)
+                        .collect();
+                    Ok((spec.clone(), (target_spec, excludes)))
+                })
+                .collect::<Result<_, guppy::Error>>()?;
 
             Ok(Self {
                 graph: DebugIgnore(graph),
@@ -421,10 +608,14 @@ mod summaries {
                 unify_target_host: summary.unify_target_host,
                 output_single_feature: summary.output_single_feature,
                 dep_format_version: summary.dep_format_version,
+                patched_deps: summary.patched_deps,
                 platforms,
                 registries,
+                hakari_registry: summary.hakari_registry.clone(),
                 traversal_excludes,
                 final_excludes,
+                platform_final_excludes,
+                unify_only: summary.unify_only.clone(),
             })
         }
     }
@@ -500,6 +691,82 @@ impl Default for UnifyTargetHost {
     }
 }
 
+/// How to output dependencies that are replaced through `[patch]` sections.
+///
+/// With a `[patch]` section in the workspace `Cargo.toml`, such as:
+///
+/// ```toml
+/// [patch.crates-io]
+/// my-dep = { git = "https://github.com/example/my-dep" }
+/// ```
+///
+/// workspace packages declare a dependency on `my-dep` from crates.io, but Cargo builds it from
+/// the Git repository instead. Hakari unifies the patched package, so its dependency line can't
+/// just be a crates.io version requirement.
+///
+/// Call `HakariBuilder::set_patched_deps` to configure this option.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "proptest1", derive(proptest_derive::Arbitrary))]
+#[cfg_attr(feature = "cli-support", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "cli-support", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum PatchedDeps {
+    /// Output dependency lines that point at the source the dependency is patched to, such as
+    /// `my-dep = { git = "https://github.com/example/my-dep", ... }` or
+    /// `my-dep = { path = "../my-dep", ... }`.
+    ///
+    /// This is the default.
+    Source,
+
+    /// Leave patched dependencies out of the dependency sections, and list them in a comment at
+    /// the end of the output instead.
+    ///
+    /// This is useful if the workspace-hack crate is published, since published crates can't
+    /// have Git or path dependencies.
+    Warn,
+}
+
+impl PatchedDeps {
+    #[cfg(feature = "cli-support")]
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The default for `PatchedDeps`: output dependency lines pointing at the patched source.
+impl Default for PatchedDeps {
+    #[inline]
+    fn default() -> Self {
+        PatchedDeps::Source
+    }
+}
+
+/// Returns true if this link's declared source is different from the source it resolved to.
+fn is_patched_link(link: &PackageLink<'_>) -> bool {
+    // Path dependencies don't have a declared source, and can't be patched.
+    let declared = match link.declared_source().and_then(ExternalSource::new) {
+        Some(declared) => declared,
+        None => return false,
+    };
+    match link.to().source().parse_external() {
+        Some(ExternalSource::Registry(registry)) => declared != ExternalSource::Registry(registry),
+        Some(ExternalSource::Git {
+            repository, req, ..
+        }) => match declared {
+            ExternalSource::Git {
+                repository: declared_repository,
+                req: declared_req,
+                ..
+            } => declared_repository != repository || declared_req != req,
+            _ => true,
+        },
+        // Unrecognized sources can't be compared.
+        Some(_) => false,
+        // The dependency was patched to a local path.
+        None => link.to().source().is_local(),
+    }
+}
+
 /// Version of `workspace-hack = ...` lines in other `Cargo.toml` files to use.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "cli-support", derive(serde::Deserialize, serde::Serialize))]
@@ -515,6 +782,15 @@ pub enum DepFormatVersion {
     /// `cargo hakari 0.9.8`.
     #[cfg_attr(feature = "cli-support", serde(rename = "2"))]
     V2,
+
+    /// `workspace-hack = { version = "0.1", path = ..., registry = ... }`, for workspace-hack
+    /// packages published to an alternate registry.
+    ///
+    /// The registry is specified through
+    /// [`HakariBuilder::set_hakari_registry`](HakariBuilder::set_hakari_registry). If no registry
+    /// is known, this is the same as `V2`.
+    #[cfg_attr(feature = "cli-support", serde(rename = "3"))]
+    V3,
 }
 
 impl Default for DepFormatVersion {
@@ -719,7 +995,7 @@ impl<'g> Hakari<'g> {
         }
 
         let computed_map = computed_map_build.computed_map;
-        let output_map = map_build.finish(&builder.final_excludes);
+        let output_map = map_build.finish(&builder);
 
         Self {
             builder,
@@ -897,6 +1173,9 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
         let features_only = builder.make_features_only();
         let excludes_ref = &excludes;
         let features_only_ref = &features_only;
+        // Every simulation is on the same graph, so share platform evaluations across them.
+        let resolver_cache = builder.graph.new_cargo_resolver_cache();
+        let resolver_cache_ref = &resolver_cache;
 
         let computed_map: ComputedMap<'g> = platforms_features
             .into_par_iter()
@@ -908,7 +1187,8 @@ impl<'g, 'b> ComputedMapBuild<'g, 'b> {
                     .set_include_dev(include_dev)
                     .set_resolver(builder.resolver)
                     .set_platform(platform_spec)
-                    .add_omitted_packages(excludes.iter());
+                    .add_omitted_packages(excludes.iter())
+                    .set_resolver_cache(resolver_cache_ref);
 
                 workspace.par_iter().map(move |workspace_package| {
                     if excludes_ref.is_excluded(workspace_package.id()) {
@@ -1373,7 +1653,48 @@ impl<'g> OutputMapBuild<'g> {
         })
     }
 
-    fn finish(mut self, final_excludes: &HashSet<&'g PackageId>) -> OutputMap<'g> {
+    fn finish(mut self, builder: &HakariBuilder<'g>) -> OutputMap<'g> {
+        // Packages excluded on some platforms can't be in the "always" set, so move them into
+        // the sets for the platforms they aren't excluded on.
+        for (target_spec, excludes) in builder.platform_final_excludes.values() {
+            let included_idxs: Vec<_> = builder
+                .platforms
+                .iter()
+                .enumerate()
+                .filter(|(_, platform)| target_spec.eval(platform) == Some(false))
+                .map(|(idx, _)| idx)
+                .collect();
+            for &build_platform in BuildPlatform::VALUES {
+                let always_key = OutputKey {
+                    platform_idx: None,
+                    build_platform,
+                };
+                for package_id in excludes {
+                    let (package, always_features) = match self
+                        .output_map
+                        .get_mut(&always_key)
+                        .and_then(|always_map| always_map.remove(package_id))
+                    {
+                        Some(v) => v,
+                        None => continue,
+                    };
+                    for &idx in &included_idxs {
+                        let key = OutputKey {
+                            platform_idx: Some(idx),
+                            build_platform,
+                        };
+                        self.output_map
+                            .entry(key)
+                            .or_default()
+                            .entry(package_id)
+                            .or_insert_with(|| (package, BTreeSet::new()))
+                            .1
+                            .extend(always_features.iter().copied());
+                    }
+                }
+            }
+        }
+
         // Remove all features that are already unified in the "always" set.
         for &build_platform in BuildPlatform::VALUES {
             let always_key = OutputKey {
@@ -1420,11 +1741,27 @@ impl<'g> OutputMapBuild<'g> {
             self.output_map.insert(always_key, always_map);
         }
 
-        // Remove final-excludes, and get rid of any maps that are empty.
-        self.output_map.retain(|_, inner_map| {
-            for package_id in final_excludes {
+        // Remove final-excludes, including platform-specific ones, and packages not in unify-only,
+        // and get rid of any maps that are empty.
+        self.output_map.retain(|key, inner_map| {
+            for package_id in &builder.final_excludes {
                 inner_map.remove(package_id);
             }
+            if let Some(idx) = key.platform_idx {
+                let platform = &builder.platforms[idx];
+                for (target_spec, excludes) in builder.platform_final_excludes.values() {
+                    // Packages are excluded if the spec might match the platform.
+                    if target_spec.eval(platform) != Some(false) {
+                        for package_id in excludes {
+                            inner_map.remove(package_id);
+                        }
+                    }
+                }
+            }
+            let unify_only = &builder.unify_only;
+            if !unify_only.is_empty() {
+                inner_map.retain(|_, (package, _)| unify_only.contains(package.name()));
+            }
             !inner_map.is_empty()
         });
 
@@ -1504,4 +1841,99 @@ mod tests {
             "internal build deps => replicate target on host"
         );
     }
+
+    #[test]
+    fn unify_only() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        fn output_names<'g>(hakari: &Hakari<'g>) -> BTreeSet<&'g str> {
+            hakari
+                .output_map
+                .values()
+                .flat_map(|inner_map| inner_map.values().map(|(package, _)| package.name()))
+                .collect()
+        }
+
+        let full = HakariBuilder::new(graph, None)
+            .expect("HakariBuilder::new succeeded")
+            .compute();
+        let full_names = output_names(&full);
+        assert!(full_names.contains("syn"), "syn is unified by default");
+        assert!(full_names.len() > 2, "more than two packages are unified");
+
+        let mut builder = HakariBuilder::new(graph, None).expect("HakariBuilder::new succeeded");
+        builder.set_unify_only(["syn", "serde", "nonexistent"]);
+        let restricted = builder.compute();
+        let restricted_names = output_names(&restricted);
+        assert!(restricted_names.contains("syn"), "syn is still unified");
+        assert!(
+            restricted_names.is_subset(&["syn", "serde"].into_iter().collect()),
+            "only packages in unify-only are unified: {:?}",
+            restricted_names
+        );
+
+        // Feature sets for packages in unify-only are unchanged.
+        for (key, inner_map) in &restricted.output_map {
+            for (package_id, (_, features)) in inner_map {
+                assert_eq!(
+                    Some(features),
+                    full.output_map
+                        .get(key)
+                        .and_then(|full_map| full_map.get(package_id))
+                        .map(|(_, features)| features),
+                    "features for {} match",
+                    package_id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn platform_final_excludes() {
+        let graph = JsonFixture::metadata1().graph();
+        let datatest_id = graph
+            .packages()
+            .find(|package| package.name() == "datatest")
+            .expect("datatest is in the graph")
+            .id();
+        let mut builder = HakariBuilder::new(graph, None).expect("HakariBuilder::new succeeded");
+        builder
+            .set_platforms(["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"])
+            .expect("valid platforms");
+        let full = builder.clone().compute();
+        let always_key = OutputKey {
+            platform_idx: None,
+            build_platform: BuildPlatform::Target,
+        };
+        let always_features = full.output_map[&always_key][datatest_id].1.clone();
+
+        builder
+            .add_platform_final_excludes("cfg(windows)", [datatest_id])
+            .expect("valid spec and package ID");
+        let hakari = builder.compute();
+        let get = |platform_idx| {
+            let key = OutputKey {
+                platform_idx,
+                build_platform: BuildPlatform::Target,
+            };
+            hakari
+                .output_map
+                .get(&key)
+                .and_then(|inner_map| inner_map.get(datatest_id))
+        };
+        assert!(get(None).is_none(), "datatest isn't in the shared section");
+        assert_eq!(
+            get(Some(0)).map(|(_, features)| features),
+            Some(&always_features),
+            "datatest is moved into the Linux section"
+        );
+        assert!(get(Some(1)).is_none(), "datatest is excluded on Windows");
+
+        let mut builder = HakariBuilder::new(graph, None).expect("HakariBuilder::new succeeded");
+        assert!(
+            builder
+                .add_platform_final_excludes("cfg(", [datatest_id])
+                .is_err(),
+            "invalid spec is rejected"
+        );
+    }
 }
@@ -1,6 +1,7 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use crate::VersionFormat;
 use guppy::Version;
 use std::fmt;
 
@@ -9,6 +10,7 @@ use std::fmt;
 pub(crate) struct VersionDisplay<'a> {
     version: &'a Version,
     exact_versions: bool,
+    format: VersionFormat,
 }
 
 impl<'a> VersionDisplay<'a> {
@@ -16,12 +18,31 @@ impl<'a> VersionDisplay<'a> {
         Self {
             version,
             exact_versions,
+            format: VersionFormat::Caret,
         }
     }
+
+    pub(crate) fn with_format(mut self, format: VersionFormat) -> Self {
+        self.format = format;
+        self
+    }
 }
 
 impl<'a> fmt::Display for VersionDisplay<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.format {
+            VersionFormat::Caret => {}
+            VersionFormat::Exact => {
+                let version = self.version;
+                write!(f, "={}.{}.{}", version.major, version.minor, version.patch)?;
+                if !version.pre.is_empty() {
+                    write!(f, "-{}", version.pre)?;
+                }
+                return Ok(());
+            }
+            VersionFormat::ExactWithBuildMetadata => return write!(f, "={}", self.version),
+        }
+
         if self.exact_versions || !self.version.pre.is_empty() {
             // Preserve the version exactly.
             write!(f, "{}", self.version)
@@ -73,6 +94,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn exact_version() {
+        let versions = vec![
+            ("1.4.0", "=1.4.0", "=1.4.0"),
+            ("0.0.7", "=0.0.7", "=0.0.7"),
+            ("1.4.0-b1", "=1.4.0-b1", "=1.4.0-b1"),
+            ("4.2.3+g456", "=4.2.3", "=4.2.3+g456"),
+            ("0.1.0-rc.1+zstd.1.5", "=0.1.0-rc.1", "=0.1.0-rc.1+zstd.1.5"),
+        ];
+
+        for (version_str, exact, exact_with_build) in versions {
+            let version = Version::parse(version_str).expect("valid version");
+            for exact_versions in [false, true] {
+                let display = VersionDisplay::new(&version, exact_versions);
+                assert_eq!(
+                    &format!("{}", display.with_format(VersionFormat::Exact)),
+                    exact
+                );
+                assert_eq!(
+                    &format!(
+                        "{}",
+                        display.with_format(VersionFormat::ExactWithBuildMetadata)
+                    ),
+                    exact_with_build
+                );
+            }
+            let version_req = VersionReq::parse(exact).expect("valid version req");
+            assert!(
+                version_req.matches(&version),
+                "version req {} should match version {}",
+                exact,
+                version
+            );
+        }
+    }
+
     #[test]
     fn min_versions_match() {
         for (&name, fixture) in JsonFixture::all_fixtures() {
@@ -54,6 +54,8 @@
 //! * [the version of the Cargo resolver to use](HakariBuilder::set_resolver)
 //! * [packages to be excluded during computation](HakariBuilder::add_traversal_excludes)
 //! * [packages to be excluded from the final output](HakariBuilder::add_final_excludes)
+//! * [packages to be excluded from the final output on some platforms](HakariBuilder::add_platform_final_excludes)
+//! * [a subset of packages to restrict the final output to](HakariBuilder::set_unify_only)
 //!
 //! With the optional `cli-support` feature, `HakariBuilder` options can be
 //! [read from](HakariBuilder::from_summary) or [written to](HakariBuilder::to_summary)
@@ -116,9 +118,6 @@
 //! `hakari` is still missing a few features:
 //!
 //! * Simulating cross-compilations
-//! * Platform-specific excludes
-//! * Only including a subset of packages in the final result (e.g. unifying core packages like
-//!   `syn` but not any others)
 //!
 //! These features will be added as time permits.
 
@@ -130,6 +129,8 @@ mod hakari;
 mod helpers;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
+pub mod report;
+pub mod stats;
 #[cfg(feature = "cli-support")]
 pub mod summaries;
 mod toml_out;
@@ -137,7 +138,7 @@ pub mod verify;
 
 pub use crate::{
     cargo_toml::*,
-    hakari::{DepFormatVersion, Hakari, HakariBuilder, UnifyTargetHost},
+    hakari::{DepFormatVersion, Hakari, HakariBuilder, PatchedDeps, UnifyTargetHost},
     toml_out::*,
 };
 
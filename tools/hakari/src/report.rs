@@ -0,0 +1,253 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A report of every dependency in the workspace-hack, for audits.
+//!
+//! [`HakariReport`] instances are produced by [`Hakari::report`]. While
+//! [`Hakari::explain`](crate::Hakari::explain) goes into detail about a single dependency, a
+//! report lists out every dependency along with the workspace packages that build it, and an
+//! estimate of how expensive it is to build.
+
+use crate::Hakari;
+use guppy::{graph::PackageMetadata, PackageId};
+use std::collections::{BTreeMap, BTreeSet};
+
+impl<'g> Hakari<'g> {
+    /// Generates a report of every dependency in the workspace-hack.
+    ///
+    /// For more, see the documentation for [`HakariReport`].
+    pub fn report(&self) -> HakariReport<'g> {
+        let graph = self.builder.graph();
+        let mut dependencies: BTreeMap<&'g PackageId, DependencyReport<'g>> = BTreeMap::new();
+
+        for (output_key, deps) in &self.output_map {
+            for (&dep_id, (metadata, features)) in deps {
+                let report = dependencies.entry(dep_id).or_insert_with(|| {
+                    // Dev-dependencies of third-party packages aren't built, so don't count them.
+                    let dependency_count = graph
+                        .query_forward([dep_id])
+                        .expect("valid package ID")
+                        .resolve_with_fn(|_, link| !link.dev_only())
+                        .len()
+                        - 1;
+                    DependencyReport {
+                        metadata: *metadata,
+                        features: BTreeSet::new(),
+                        workspace_packages: BTreeMap::new(),
+                        dependency_count,
+                    }
+                });
+                report.features.extend(features.iter().copied());
+
+                let computed = match self.computed_map.get(&(output_key.platform_idx, dep_id)) {
+                    Some(computed) => computed,
+                    None => continue,
+                };
+                for (_, inner_map) in computed.inner_maps() {
+                    for inner_value in inner_map.values() {
+                        report.workspace_packages.extend(
+                            inner_value
+                                .workspace_packages
+                                .iter()
+                                .map(|(package, _, _)| (package.id(), *package)),
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut dependencies: Vec<_> = dependencies.into_values().collect();
+        dependencies.sort_by(|a, b| {
+            (a.metadata.name(), a.metadata.version())
+                .cmp(&(b.metadata.name(), b.metadata.version()))
+        });
+        HakariReport { dependencies }
+    }
+}
+
+/// A report of every dependency in the workspace-hack.
+///
+/// Produced by [`Hakari::report`].
+#[derive(Clone, Debug)]
+pub struct HakariReport<'g> {
+    dependencies: Vec<DependencyReport<'g>>,
+}
+
+impl<'g> HakariReport<'g> {
+    /// Returns every dependency in the workspace-hack, sorted by name and version.
+    pub fn dependencies(&self) -> &[DependencyReport<'g>] {
+        &self.dependencies
+    }
+
+    /// Groups dependencies by the workspace packages that build them.
+    ///
+    /// A dependency built by several workspace packages is listed once in each of their groups.
+    /// With [`ReportOrder::Name`], groups are sorted by the name of the workspace package, and
+    /// dependencies within a group by name and version. With [`ReportOrder::Cost`], groups and
+    /// dependencies are sorted by their total [estimated
+    /// cost](DependencyReport::estimated_cost), highest first.
+    pub fn by_workspace_package(&self, order: ReportOrder) -> Vec<WorkspacePackageReport<'g, '_>> {
+        let mut groups: BTreeMap<&'g PackageId, WorkspacePackageReport<'g, '_>> = BTreeMap::new();
+        for dep in &self.dependencies {
+            for (&package_id, &package) in &dep.workspace_packages {
+                groups
+                    .entry(package_id)
+                    .or_insert_with(|| WorkspacePackageReport {
+                        package,
+                        dependencies: vec![],
+                    })
+                    .dependencies
+                    .push(dep);
+            }
+        }
+
+        let mut groups: Vec<_> = groups.into_values().collect();
+        match order {
+            ReportOrder::Name => {
+                groups.sort_by_key(|group| group.package.name());
+            }
+            ReportOrder::Cost => {
+                for group in &mut groups {
+                    // This is a stable sort, so ties remain sorted by name and version.
+                    group
+                        .dependencies
+                        .sort_by_key(|dep| std::cmp::Reverse(dep.estimated_cost()));
+                }
+                groups.sort_by(|a, b| {
+                    b.estimated_cost()
+                        .cmp(&a.estimated_cost())
+                        .then_with(|| a.package.name().cmp(b.package.name()))
+                });
+            }
+        }
+        groups
+    }
+}
+
+/// A single dependency in a [`HakariReport`].
+#[derive(Clone, Debug)]
+pub struct DependencyReport<'g> {
+    metadata: PackageMetadata<'g>,
+    features: BTreeSet<&'g str>,
+    workspace_packages: BTreeMap<&'g PackageId, PackageMetadata<'g>>,
+    dependency_count: usize,
+}
+
+impl<'g> DependencyReport<'g> {
+    /// Returns the metadata for this dependency.
+    pub fn metadata(&self) -> PackageMetadata<'g> {
+        self.metadata
+    }
+
+    /// Returns the features enabled for this dependency by the workspace-hack, across all
+    /// platforms.
+    pub fn features(&self) -> impl ExactSizeIterator<Item = &'g str> + '_ {
+        self.features.iter().copied()
+    }
+
+    /// Returns the workspace packages that build this dependency, in no particular order.
+    pub fn workspace_packages(&self) -> impl ExactSizeIterator<Item = PackageMetadata<'g>> + '_ {
+        self.workspace_packages.values().copied()
+    }
+
+    /// Returns the number of packages that this dependency transitively depends on, not counting
+    /// dev-dependencies.
+    pub fn dependency_count(&self) -> usize {
+        self.dependency_count
+    }
+
+    /// Returns a rough estimate of the cost of building this dependency with the features enabled
+    /// by the workspace-hack.
+    ///
+    /// This is the number of features multiplied by the number of packages built, including the
+    /// dependency itself. A dependency with no features is counted as having one.
+    pub fn estimated_cost(&self) -> usize {
+        self.features.len().max(1) * (self.dependency_count + 1)
+    }
+}
+
+/// The dependencies built by a single workspace package, in a [`HakariReport`].
+///
+/// Returned by [`HakariReport::by_workspace_package`].
+#[derive(Clone, Debug)]
+pub struct WorkspacePackageReport<'g, 'report> {
+    /// The workspace package.
+    pub package: PackageMetadata<'g>,
+
+    /// The workspace-hack dependencies built by this package.
+    pub dependencies: Vec<&'report DependencyReport<'g>>,
+}
+
+impl<'g, 'report> WorkspacePackageReport<'g, 'report> {
+    /// Returns the sum of the estimated costs of the dependencies in this group.
+    pub fn estimated_cost(&self) -> usize {
+        self.dependencies
+            .iter()
+            .map(|dep| dep.estimated_cost())
+            .sum()
+    }
+}
+
+/// The order in which [`HakariReport::by_workspace_package`] sorts its results.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReportOrder {
+    /// Sort by name.
+    Name,
+
+    /// Sort by estimated build cost, highest first.
+    Cost,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn report_libra() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let hakari = HakariBuilder::new(graph, None)
+            .expect("HakariBuilder::new succeeded")
+            .compute();
+        let report = hakari.report();
+
+        assert_eq!(
+            report.dependencies().len(),
+            hakari.toml_name_map().len(),
+            "every dependency in the output is reported once"
+        );
+        for dep in report.dependencies() {
+            assert!(
+                dep.workspace_packages().len() > 0,
+                "{} is built by at least one workspace package",
+                dep.metadata().name()
+            );
+            assert!(dep.estimated_cost() > dep.dependency_count());
+        }
+
+        let by_name = report.by_workspace_package(ReportOrder::Name);
+        let names: Vec<_> = by_name.iter().map(|group| group.package.name()).collect();
+        let mut sorted_names = names.clone();
+        sorted_names.sort_unstable();
+        assert_eq!(names, sorted_names, "groups are sorted by name");
+
+        let by_cost = report.by_workspace_package(ReportOrder::Cost);
+        assert_eq!(by_cost.len(), by_name.len(), "same number of groups");
+        for window in by_cost.windows(2) {
+            assert!(
+                window[0].estimated_cost() >= window[1].estimated_cost(),
+                "groups are sorted by cost"
+            );
+        }
+        for group in &by_cost {
+            assert!(group.package.in_workspace());
+            for window in group.dependencies.windows(2) {
+                assert!(
+                    window[0].estimated_cost() >= window[1].estimated_cost(),
+                    "dependencies are sorted by cost"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Metrics that describe the impact of the workspace-hack.
+//!
+//! [`HakariStats`] instances are produced by [`Hakari::stats`]. With the `cli-support` feature,
+//! they can be serialized and deserialized, so that the health of a workspace-hack can be tracked
+//! over time. Two instances can be compared with [`HakariStats::diff`].
+
+use crate::Hakari;
+use guppy::PackageId;
+use std::collections::{BTreeMap, BTreeSet};
+
+impl<'g> Hakari<'g> {
+    /// Computes metrics about the dependencies unified by this workspace-hack.
+    ///
+    /// For more about the metrics, see the documentation for [`HakariStats`].
+    pub fn stats(&self) -> HakariStats {
+        let mut dependencies: BTreeMap<&'g PackageId, DependencyStats> = BTreeMap::new();
+        // The number of distinct builds of each dependency with the workspace-hack, by platform.
+        let mut unified_builds: BTreeMap<(Option<usize>, &'g PackageId), BTreeSet<_>> =
+            BTreeMap::new();
+
+        for (output_key, deps) in &self.output_map {
+            for (&dep_id, (metadata, features)) in deps {
+                let stats = dependencies
+                    .entry(dep_id)
+                    .or_insert_with(|| DependencyStats {
+                        name: metadata.name().to_string(),
+                        version: metadata.version().to_string(),
+                        features_added: BTreeSet::new(),
+                        builds_before: 0,
+                        duplicate_builds_avoided: 0,
+                    });
+                unified_builds
+                    .entry((output_key.platform_idx, dep_id))
+                    .or_default()
+                    .insert(features);
+
+                let computed = match self.computed_map.get(&(output_key.platform_idx, dep_id)) {
+                    Some(computed) => computed,
+                    None => continue,
+                };
+                // The minimal build consists of the features that every build of this dependency
+                // enabled.
+                let mut feature_sets = computed
+                    .inner_maps()
+                    .into_iter()
+                    .flat_map(|(_, inner_map)| inner_map.keys());
+                let minimal = match feature_sets.next() {
+                    Some(first) => feature_sets.fold(first.clone(), |acc, features| {
+                        acc.intersection(features).copied().collect()
+                    }),
+                    None => BTreeSet::new(),
+                };
+                stats.features_added.extend(
+                    features
+                        .difference(&minimal)
+                        .map(|feature| feature.to_string()),
+                );
+            }
+        }
+
+        for ((platform_idx, dep_id), builds_after) in unified_builds {
+            let builds_before =
+                self.computed_map
+                    .get(&(platform_idx, dep_id))
+                    .map_or(0, |computed| {
+                        computed
+                            .inner_maps()
+                            .iter()
+                            .map(|(_, inner_map)| inner_map.len())
+                            .sum()
+                    });
+            let stats = dependencies
+                .get_mut(dep_id)
+                .expect("dependency was inserted above");
+            stats.builds_before += builds_before;
+            stats.duplicate_builds_avoided += builds_before.saturating_sub(builds_after.len());
+        }
+
+        let mut dependencies: Vec<_> = dependencies.into_values().collect();
+        dependencies.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+        HakariStats {
+            unified_count: dependencies.len(),
+            features_added_count: dependencies
+                .iter()
+                .map(|stats| stats.features_added.len())
+                .sum(),
+            duplicate_builds_avoided: dependencies
+                .iter()
+                .map(|stats| stats.duplicate_builds_avoided)
+                .sum(),
+            dependencies,
+        }
+    }
+}
+
+/// Metrics about the dependencies unified by a workspace-hack.
+///
+/// Produced by [`Hakari::stats`].
+///
+/// Builds are counted per platform in the builder's list of platforms (plus once for the
+/// platform-independent computation if no platforms are specified), and separately for the target
+/// and host.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "cli-support", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "cli-support", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub struct HakariStats {
+    /// The number of third-party dependencies unified by the workspace-hack.
+    pub unified_count: usize,
+
+    /// The number of features added to dependencies beyond their minimal builds, summed across
+    /// all dependencies.
+    pub features_added_count: usize,
+
+    /// The estimated number of duplicate dependency builds avoided by the workspace-hack, summed
+    /// across all dependencies.
+    pub duplicate_builds_avoided: usize,
+
+    /// Metrics for each unified dependency, sorted by name and version.
+    pub dependencies: Vec<DependencyStats>,
+}
+
+impl HakariStats {
+    /// Compares these metrics against an older version of them, for example one computed from an
+    /// earlier revision of the workspace.
+    pub fn diff<'a>(&'a self, old: &'a HakariStats) -> HakariStatsDiff<'a> {
+        let old_names: BTreeSet<_> = old.dependencies.iter().map(DependencyStats::key).collect();
+        let new_names: BTreeSet<_> = self.dependencies.iter().map(DependencyStats::key).collect();
+
+        HakariStatsDiff {
+            unified_count: delta(old.unified_count, self.unified_count),
+            features_added_count: delta(old.features_added_count, self.features_added_count),
+            duplicate_builds_avoided: delta(
+                old.duplicate_builds_avoided,
+                self.duplicate_builds_avoided,
+            ),
+            added: new_names.difference(&old_names).copied().collect(),
+            removed: old_names.difference(&new_names).copied().collect(),
+        }
+    }
+}
+
+/// Metrics about a single dependency unified by a workspace-hack.
+///
+/// Part of [`HakariStats`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "cli-support", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "cli-support", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub struct DependencyStats {
+    /// The name of the dependency.
+    pub name: String,
+
+    /// The version of the dependency.
+    pub version: String,
+
+    /// The features enabled by the workspace-hack that weren't enabled by every build of this
+    /// dependency without it.
+    pub features_added: BTreeSet<String>,
+
+    /// The number of distinct builds of this dependency without the workspace-hack.
+    pub builds_before: usize,
+
+    /// The number of builds of this dependency avoided by the workspace-hack.
+    pub duplicate_builds_avoided: usize,
+}
+
+impl DependencyStats {
+    fn key(&self) -> (&str, &str) {
+        (&self.name, &self.version)
+    }
+}
+
+/// The difference between two [`HakariStats`] instances.
+///
+/// Produced by [`HakariStats::diff`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct HakariStatsDiff<'a> {
+    /// The change in the number of unified dependencies.
+    pub unified_count: isize,
+
+    /// The change in the number of features added beyond minimal builds.
+    pub features_added_count: isize,
+
+    /// The change in the estimated number of duplicate builds avoided.
+    pub duplicate_builds_avoided: isize,
+
+    /// Dependencies, as `(name, version)` pairs, that are newly unified.
+    pub added: Vec<(&'a str, &'a str)>,
+
+    /// Dependencies, as `(name, version)` pairs, that are no longer unified.
+    pub removed: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> HakariStatsDiff<'a> {
+    /// Returns true if there are no differences.
+    pub fn is_unchanged(&self) -> bool {
+        self.unified_count == 0
+            && self.features_added_count == 0
+            && self.duplicate_builds_avoided == 0
+            && self.added.is_empty()
+            && self.removed.is_empty()
+    }
+}
+
+fn delta(old: usize, new: usize) -> isize {
+    new as isize - old as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::HakariBuilder;
+    use fixtures::json::JsonFixture;
+
+    #[test]
+    fn stats_libra() {
+        let graph = JsonFixture::metadata_libra_9ffd93b().graph();
+        let hakari = HakariBuilder::new(graph, None)
+            .expect("HakariBuilder::new succeeded")
+            .compute();
+        let stats = hakari.stats();
+
+        assert_eq!(
+            stats.unified_count,
+            hakari.toml_name_map().len(),
+            "every dependency in the output is counted once"
+        );
+        assert_eq!(stats.dependencies.len(), stats.unified_count);
+        assert!(
+            stats.duplicate_builds_avoided > 0,
+            "libra has dependencies built more than one way"
+        );
+
+        let syn = stats
+            .dependencies
+            .iter()
+            .find(|dep| dep.name == "syn" && dep.version.starts_with("1."))
+            .expect("syn 1 is unified");
+        assert!(syn.builds_before >= 2, "syn is built more than one way");
+        assert!(syn.duplicate_builds_avoided >= 1, "syn builds are unified");
+        assert!(!syn.features_added.is_empty(), "syn has features added");
+
+        assert!(stats.diff(&stats).is_unchanged(), "no changes against self");
+
+        let mut builder = HakariBuilder::new(graph, None).expect("HakariBuilder::new succeeded");
+        builder.set_unify_only(["syn"]);
+        let restricted = builder.compute().stats();
+        let diff = restricted.diff(&stats);
+        assert_eq!(
+            diff.unified_count,
+            restricted.unified_count as isize - stats.unified_count as isize,
+        );
+        assert!(
+            diff.added.is_empty(),
+            "restricting unification adds nothing"
+        );
+        assert!(
+            diff.removed.iter().all(|(name, _)| *name != "syn"),
+            "syn is still unified"
+        );
+        assert_eq!(
+            diff.removed.len(),
+            stats.unified_count - restricted.unified_count,
+        );
+    }
+}
@@ -6,14 +6,30 @@
 //! Requires the `cli-support` feature to be enabled.
 
 use crate::{
-    hakari::DepFormatVersion, HakariBuilder, HakariOutputOptions, TomlOutError, UnifyTargetHost,
+    hakari::DepFormatVersion, HakariBuilder, HakariOutputOptions, PatchedDeps, TomlOutError,
+    UnifyTargetHost, VersionFormat,
 };
+use camino::{Utf8Path, Utf8PathBuf};
 use guppy::{
     errors::TargetSpecError,
-    graph::{cargo::CargoResolverVersion, summaries::PackageSetSummary, PackageGraph},
+    graph::{
+        cargo::CargoResolverVersion,
+        summaries::{PackageSetSummary, SummaryId, ThirdPartySummary},
+        PackageGraph,
+    },
+    platform::{Platform, TargetFeatures, Triple},
+    Version,
+};
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error, fmt, fs, io,
+    str::FromStr,
 };
-use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, fmt, str::FromStr};
 use toml::Serializer;
 
 /// The location of the configuration used by `cargo hakari`, relative to the workspace root.
@@ -36,6 +52,14 @@ pub struct HakariConfig {
     /// Output options.
     #[serde(flatten)]
     pub output: OutputOptionsSummary,
+
+    /// Options for managing workspace-hack dependencies.
+    #[serde(default, skip_serializing_if = "ManageDepsSummary::is_empty")]
+    pub manage_deps: ManageDepsSummary,
+
+    /// Options for bumping the version of a published workspace-hack crate.
+    #[serde(default, skip_serializing_if = "BumpSummary::is_default")]
+    pub bump: BumpSummary,
 }
 
 impl FromStr for HakariConfig {
@@ -82,13 +106,26 @@ pub struct HakariBuilderSummary {
     #[serde(default)]
     pub platforms: Vec<String>,
 
+    /// Sources to discover more platforms from.
+    ///
+    /// Discovered platforms are added to `platforms` by
+    /// [`add_discovered_platforms`](Self::add_discovered_platforms).
+    #[serde(default, skip_serializing_if = "DiscoverPlatformsSummary::is_empty")]
+    pub discover_platforms: DiscoverPlatformsSummary,
+
     /// The list of packages excluded during graph traversals.
     #[serde(default)]
     pub traversal_excludes: PackageSetSummary,
 
     /// The list of packages excluded from the final output.
     #[serde(default)]
-    pub final_excludes: PackageSetSummary,
+    pub final_excludes: FinalExcludesSummary,
+
+    /// The names of packages the final output is restricted to.
+    ///
+    /// If empty, the final output isn't restricted.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub unify_only: BTreeSet<String>,
 
     /// The list of alternate registries, as a map of name to URL.
     ///
@@ -100,6 +137,17 @@ pub struct HakariBuilderSummary {
         with = "registries_impl"
     )]
     pub registries: BTreeMap<String, String>,
+
+    /// The name of the registry the Hakari package is published to.
+    ///
+    /// Used with dep format version 3. If not specified, this is inferred from the Hakari
+    /// package's `publish` field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hakari_registry: Option<String>,
+
+    /// How to output dependencies that are replaced through `[patch]` sections.
+    #[serde(default, skip_serializing_if = "PatchedDeps::is_default")]
+    pub patched_deps: PatchedDeps,
 }
 
 impl HakariBuilderSummary {
@@ -118,28 +166,63 @@ impl HakariBuilderSummary {
                 .platforms()
                 .map(|triple_str| triple_str.to_owned())
                 .collect::<Vec<_>>(),
+            discover_platforms: DiscoverPlatformsSummary::default(),
             resolver: builder.resolver(),
             traversal_excludes: PackageSetSummary::from_package_ids(
                 builder.graph(),
                 builder.traversal_excludes_only(),
             )
             .expect("all package IDs are valid"),
-            final_excludes: PackageSetSummary::from_package_ids(
-                builder.graph(),
-                builder.final_excludes(),
-            )
-            .expect("all package IDs are valid"),
+            final_excludes: FinalExcludesSummary {
+                packages: PackageSetSummary::from_package_ids(
+                    builder.graph(),
+                    builder.final_excludes(),
+                )
+                .expect("all package IDs are valid"),
+                platform: builder
+                    .platform_final_excludes()
+                    .map(|(spec, excludes)| {
+                        let excludes =
+                            PackageSetSummary::from_package_ids(builder.graph(), excludes)
+                                .expect("all package IDs are valid");
+                        (spec.to_owned(), excludes)
+                    })
+                    .collect(),
+            },
+            unify_only: builder.unify_only().map(|name| name.to_owned()).collect(),
             registries: builder
                 .registries
                 .iter()
                 .map(|(name, url)| (name.clone(), url.clone()))
                 .collect(),
+            hakari_registry: builder.hakari_registry.clone(),
             unify_target_host: builder.unify_target_host(),
             output_single_feature: builder.output_single_feature(),
             dep_format_version: builder.dep_format_version,
+            patched_deps: builder.patched_deps(),
         })
     }
 
+    /// Discovers platforms from the sources in [`discover_platforms`](Self::discover_platforms),
+    /// and adds the ones that aren't already listed to [`platforms`](Self::platforms).
+    ///
+    /// Paths are relative to `workspace_root`. [`to_hakari_builder`](Self::to_hakari_builder)
+    /// doesn't read from disk, so this must be called before it for discovered platforms to be
+    /// used.
+    ///
+    /// Returns an error if a source couldn't be read or parsed.
+    pub fn add_discovered_platforms(
+        &mut self,
+        workspace_root: &Utf8Path,
+    ) -> Result<(), DiscoverPlatformsError> {
+        for triple_str in self.discover_platforms.discover(workspace_root)? {
+            if !self.platforms.contains(&triple_str) {
+                self.platforms.push(triple_str);
+            }
+        }
+        Ok(())
+    }
+
     /// Creates a `HakariBuilder` from this summary and a `PackageGraph`.
     ///
     /// Returns an error if this summary references a package that's not present, or if there was
@@ -202,6 +285,400 @@ impl<'g> HakariBuilder<'g> {
     }
 }
 
+/// Packages excluded from the final output, in serializable form.
+///
+/// Packages excluded on all platforms are specified directly in `final-excludes`, in the same
+/// format as `traversal-excludes`:
+///
+/// ```toml
+/// [final-excludes]
+/// workspace-members = ["my-package"]
+/// ```
+///
+/// Packages excluded only on some platforms are specified in `platform` tables keyed by target
+/// triples or `cfg()` expressions. In that case, packages excluded on all platforms go in the
+/// `packages` table:
+///
+/// ```toml
+/// [final-excludes.packages]
+/// workspace-members = ["my-package"]
+///
+/// [final-excludes.platform.'cfg(windows)']
+/// third-party = [{ name = "openssl-sys" }]
+/// ```
+///
+/// Either layout is accepted when reading, as long as packages aren't specified in both places.
+/// When writing, the first layout is used if there are no platform-specific excludes, so that
+/// existing configurations round-trip unchanged.
+///
+/// Requires the `cli-support` feature to be enabled.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct FinalExcludesSummary {
+    /// Packages excluded from the final output on all platforms.
+    pub packages: PackageSetSummary,
+
+    /// Packages excluded from the final output on platforms matching a target spec, keyed by the
+    /// spec.
+    ///
+    /// For more, see the documentation for
+    /// [`HakariBuilder::add_platform_final_excludes`](HakariBuilder::add_platform_final_excludes).
+    pub platform: BTreeMap<String, PackageSetSummary>,
+}
+
+impl Serialize for FinalExcludesSummary {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if self.platform.is_empty() {
+            // Use the layout older versions of hakari understand.
+            return self.packages.serialize(serializer);
+        }
+
+        let len = if self.packages.is_empty() { 1 } else { 2 };
+        let mut state = serializer.serialize_struct("FinalExcludesSummary", len)?;
+        if self.packages.is_empty() {
+            state.skip_field("packages")?;
+        } else {
+            state.serialize_field("packages", &self.packages)?;
+        }
+        state.serialize_field("platform", &self.platform)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for FinalExcludesSummary {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // The fields of PackageSetSummary are also accepted at the top level, for older
+        // configurations. They're spelled out rather than flattened, because flattening turns off
+        // deny_unknown_fields.
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+        struct Fields {
+            #[serde(default)]
+            packages: Option<PackageSetSummary>,
+            #[serde(default)]
+            platform: BTreeMap<String, PackageSetSummary>,
+            #[serde(rename = "ids", default)]
+            summary_ids: BTreeSet<SummaryId>,
+            #[serde(default)]
+            workspace_members: BTreeSet<String>,
+            #[serde(default)]
+            third_party: Vec<ThirdPartySummary>,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let legacy = PackageSetSummary {
+            summary_ids: fields.summary_ids,
+            workspace_members: fields.workspace_members,
+            third_party: fields.third_party,
+        };
+        let packages = match fields.packages {
+            Some(packages) => {
+                if !legacy.is_empty() {
+                    return Err(D::Error::custom(
+                        "packages must be specified either in `packages` or at the top level, \
+                         not both",
+                    ));
+                }
+                packages
+            }
+            None => legacy,
+        };
+        Ok(Self {
+            packages,
+            platform: fields.platform,
+        })
+    }
+}
+
+/// Sources to discover platforms from, in serializable form.
+///
+/// Listing platforms by hand means they can go out of sync with the targets a workspace is
+/// actually built for. Discovered platforms are used in addition to the ones in `platforms`:
+///
+/// ```toml
+/// [discover-platforms]
+/// cargo-config = true
+/// rust-toolchain = true
+/// ci-globs = [".github/workflows/*.yml"]
+/// ```
+///
+/// Requires the `cli-support` feature to be enabled.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct DiscoverPlatformsSummary {
+    /// Discover platforms from `build.target` in `.cargo/config.toml` (or `.cargo/config`) at the
+    /// workspace root.
+    ///
+    /// Cargo configuration in parent directories or in `CARGO_HOME` isn't read. Custom targets
+    /// specified as paths to `.json` files are skipped.
+    #[serde(default)]
+    pub cargo_config: bool,
+
+    /// Discover platforms from `toolchain.targets` in `rust-toolchain.toml` (or `rust-toolchain`)
+    /// at the workspace root.
+    #[serde(default)]
+    pub rust_toolchain: bool,
+
+    /// Globs for CI configuration files, relative to the workspace root.
+    ///
+    /// Every target triple built into `rustc` that's mentioned in a matching file is discovered.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ci_globs: Vec<String>,
+}
+
+impl DiscoverPlatformsSummary {
+    /// Returns true if no sources are specified.
+    pub fn is_empty(&self) -> bool {
+        !self.cargo_config && !self.rust_toolchain && self.ci_globs.is_empty()
+    }
+
+    /// Returns the platforms discovered from these sources, relative to `workspace_root`.
+    ///
+    /// Returns an error if a source couldn't be read or parsed, or if Cargo configuration or
+    /// `rust-toolchain.toml` lists an unknown platform.
+    pub fn discover(
+        &self,
+        workspace_root: &Utf8Path,
+    ) -> Result<BTreeSet<String>, DiscoverPlatformsError> {
+        let mut platforms = BTreeSet::new();
+
+        if self.cargo_config {
+            // Cargo uses .cargo/config if both files are present.
+            let dir = workspace_root.join(".cargo");
+            if let Some((path, contents)) = read_first(&dir, &["config", "config.toml"])? {
+                let config: CargoConfig = parse_toml(&path, &contents)?;
+                let targets = match config.build.target {
+                    Some(StringOrVec::String(target)) => vec![target],
+                    Some(StringOrVec::Vec(targets)) => targets,
+                    None => vec![],
+                };
+                // Custom targets specified as paths to JSON files can't be used as platforms.
+                for target in targets
+                    .into_iter()
+                    .filter(|target| !target.ends_with(".json"))
+                {
+                    platforms.insert(check_platform(&path, target)?);
+                }
+            }
+        }
+
+        if self.rust_toolchain {
+            if let Some((path, contents)) =
+                read_first(workspace_root, &["rust-toolchain.toml", "rust-toolchain"])?
+            {
+                let toolchain = match parse_toml::<RustToolchain>(&path, &contents) {
+                    Ok(toolchain) => Some(toolchain.toolchain),
+                    // rust-toolchain may be in the legacy format, which is just a channel name.
+                    Err(_) if path.extension().is_none() && is_legacy_toolchain(&contents) => None,
+                    Err(err) => return Err(err),
+                };
+                for target in toolchain
+                    .into_iter()
+                    .flat_map(|toolchain| toolchain.targets)
+                {
+                    platforms.insert(check_platform(&path, target)?);
+                }
+            }
+        }
+
+        for ci_glob in &self.ci_globs {
+            let pattern = format!(
+                "{}/{}",
+                glob::Pattern::escape(workspace_root.as_str()),
+                ci_glob
+            );
+            let paths =
+                glob::glob(&pattern).map_err(|error| DiscoverPlatformsError::InvalidGlob {
+                    glob: ci_glob.clone(),
+                    error,
+                })?;
+            for path in paths {
+                let path = path.map_err(|error| DiscoverPlatformsError::Io {
+                    path: Utf8PathBuf::from(error.path().to_string_lossy().into_owned()),
+                    error: error.into_error(),
+                })?;
+                // Skip directories and paths that aren't valid UTF-8.
+                let path = match Utf8PathBuf::from_path_buf(path) {
+                    Ok(path) if path.is_file() => path,
+                    _ => continue,
+                };
+                let contents = read_to_string(&path)?;
+                platforms.extend(
+                    contents
+                        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                        .filter(|token| token.contains('-'))
+                        .filter(|token| {
+                            // Plenty of words like "arm-based" can be parsed as triples, so only
+                            // accept builtin ones.
+                            Triple::new(token.to_string())
+                                .map_or(false, |triple| triple.is_builtin())
+                        })
+                        .map(|token| token.to_owned()),
+                );
+            }
+        }
+
+        Ok(platforms)
+    }
+}
+
+fn read_first(
+    dir: &Utf8Path,
+    file_names: &[&str],
+) -> Result<Option<(Utf8PathBuf, String)>, DiscoverPlatformsError> {
+    for file_name in file_names {
+        let path = dir.join(file_name);
+        if path.is_file() {
+            let contents = read_to_string(&path)?;
+            return Ok(Some((path, contents)));
+        }
+    }
+    Ok(None)
+}
+
+fn read_to_string(path: &Utf8Path) -> Result<String, DiscoverPlatformsError> {
+    fs::read_to_string(path).map_err(|error| DiscoverPlatformsError::Io {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+fn parse_toml<T: DeserializeOwned>(
+    path: &Utf8Path,
+    contents: &str,
+) -> Result<T, DiscoverPlatformsError> {
+    toml::from_str(contents).map_err(|error| DiscoverPlatformsError::Toml {
+        path: path.to_owned(),
+        error,
+    })
+}
+
+/// Returns true if the contents of a `rust-toolchain` file look like the legacy format, a single
+/// line with the channel name, rather than TOML.
+fn is_legacy_toolchain(contents: &str) -> bool {
+    let contents = contents.trim();
+    !contents.contains(|c| c == '\n' || c == '=' || c == '[')
+}
+
+fn check_platform(path: &Utf8Path, triple_str: String) -> Result<String, DiscoverPlatformsError> {
+    match Platform::new(triple_str, TargetFeatures::Unknown) {
+        Ok(platform) => Ok(platform.triple_str().to_owned()),
+        Err(error) => Err(DiscoverPlatformsError::UnknownPlatform {
+            path: path.to_owned(),
+            error,
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+struct CargoConfig {
+    #[serde(default)]
+    build: CargoConfigBuild,
+}
+
+#[derive(Default, Deserialize)]
+struct CargoConfigBuild {
+    target: Option<StringOrVec>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrVec {
+    String(String),
+    Vec(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct RustToolchain {
+    toolchain: RustToolchainInner,
+}
+
+#[derive(Deserialize)]
+struct RustToolchainInner {
+    #[serde(default)]
+    targets: Vec<String>,
+}
+
+/// An error that can occur while discovering platforms.
+///
+/// Returned by [`DiscoverPlatformsSummary::discover`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DiscoverPlatformsError {
+    /// A file couldn't be read.
+    Io {
+        /// The path that was attempted to be read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        error: io::Error,
+    },
+
+    /// Cargo configuration or a `rust-toolchain` file couldn't be parsed.
+    Toml {
+        /// The path that was read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        error: toml::de::Error,
+    },
+
+    /// A platform in Cargo configuration or a `rust-toolchain` file is unknown.
+    UnknownPlatform {
+        /// The path that was read.
+        path: Utf8PathBuf,
+
+        /// The error that occurred.
+        error: TargetSpecError,
+    },
+
+    /// A glob in `ci-globs` is invalid.
+    InvalidGlob {
+        /// The glob.
+        glob: String,
+
+        /// The error that occurred.
+        error: glob::PatternError,
+    },
+}
+
+impl fmt::Display for DiscoverPlatformsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiscoverPlatformsError::Io { path, .. } => {
+                write!(f, "error while reading path '{}'", path)
+            }
+            DiscoverPlatformsError::Toml { path, .. } => {
+                write!(f, "error while parsing '{}'", path)
+            }
+            DiscoverPlatformsError::UnknownPlatform { path, .. } => {
+                write!(f, "unknown platform in '{}'", path)
+            }
+            DiscoverPlatformsError::InvalidGlob { glob, .. } => {
+                write!(f, "invalid glob '{}' in ci-globs", glob)
+            }
+        }
+    }
+}
+
+impl error::Error for DiscoverPlatformsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DiscoverPlatformsError::Io { error, .. } => Some(error),
+            DiscoverPlatformsError::Toml { error, .. } => Some(error),
+            DiscoverPlatformsError::UnknownPlatform { error, .. } => Some(error),
+            DiscoverPlatformsError::InvalidGlob { error, .. } => Some(error),
+        }
+    }
+}
+
 /// Options for `hakari` TOML output, in serializable form.
 ///
 /// TODO: add a configuration.md file.
@@ -213,10 +690,22 @@ pub struct OutputOptionsSummary {
     #[serde(default)]
     exact_versions: bool,
 
+    /// The format of version requirements for registry dependencies.
+    #[serde(default)]
+    version_format: VersionFormat,
+
+    /// Annotate registry dependencies with the registry they were resolved from.
+    #[serde(default)]
+    registry_annotations: bool,
+
     /// Output absolute paths for path dependencies.
     #[serde(default)]
     absolute_paths: bool,
 
+    /// Output a separate section for each platform.
+    #[serde(default)]
+    per_platform: bool,
+
     /// Output a [`HakariBuilderSummary`] as comments.
     #[serde(default)]
     builder_summary: bool,
@@ -227,21 +716,138 @@ impl OutputOptionsSummary {
     pub fn new(options: &HakariOutputOptions) -> Self {
         Self {
             exact_versions: options.exact_versions,
+            version_format: options.version_format,
+            registry_annotations: options.registry_annotations,
             absolute_paths: options.absolute_paths,
+            per_platform: options.per_platform,
             builder_summary: options.builder_summary,
         }
     }
 
+    /// Returns true if exact versions are output in package version fields.
+    pub fn exact_versions(&self) -> bool {
+        self.exact_versions
+    }
+
     /// Converts this summary to the options.
     pub fn to_options(&self) -> HakariOutputOptions {
         HakariOutputOptions {
             exact_versions: self.exact_versions,
+            version_format: self.version_format,
+            registry_annotations: self.registry_annotations,
             absolute_paths: self.absolute_paths,
+            per_platform: self.per_platform,
             builder_summary: self.builder_summary,
         }
     }
 }
 
+/// Options for `cargo hakari manage-deps` and `cargo hakari remove-deps`, in serializable form.
+///
+/// Globs are matched against both the names and the workspace paths of packages.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct ManageDepsSummary {
+    /// Globs for workspace packages to operate on.
+    ///
+    /// If empty, all workspace packages are operated on.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Globs for workspace packages to skip. Takes priority over `include`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl ManageDepsSummary {
+    /// Returns true if no globs are specified.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+/// Options for `cargo hakari bump`, in serializable form.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[non_exhaustive]
+pub struct BumpSummary {
+    /// The part of the version to increment if the workspace-hack changed since its last release.
+    #[serde(default)]
+    pub level: BumpLevel,
+
+    /// The format of release tags for the workspace-hack crate.
+    ///
+    /// `{name}` and `{version}` are replaced with the name and the version of the crate.
+    #[serde(default = "BumpSummary::default_tag_format")]
+    pub tag_format: String,
+}
+
+impl BumpSummary {
+    /// The default format of release tags.
+    pub const DEFAULT_TAG_FORMAT: &'static str = "{name}-{version}";
+
+    /// Returns true if all options are set to their defaults.
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Returns the release tag for the given crate name and version.
+    pub fn tag_name(&self, name: &str, version: &Version) -> String {
+        self.tag_format
+            .replace("{name}", name)
+            .replace("{version}", &version.to_string())
+    }
+
+    fn default_tag_format() -> String {
+        Self::DEFAULT_TAG_FORMAT.to_owned()
+    }
+}
+
+impl Default for BumpSummary {
+    fn default() -> Self {
+        Self {
+            level: BumpLevel::default(),
+            tag_format: Self::default_tag_format(),
+        }
+    }
+}
+
+/// The part of a version to increment in `cargo hakari bump`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum BumpLevel {
+    /// Increment the patch version, e.g. 0.1.2 to 0.1.3.
+    Patch,
+
+    /// Increment the minor version, e.g. 0.1.2 to 0.2.0.
+    Minor,
+}
+
+impl BumpLevel {
+    /// Returns the version obtained by bumping this part of the given version.
+    ///
+    /// Pre-release and build metadata are cleared.
+    pub fn bump(self, version: &Version) -> Version {
+        let mut version = Version::new(version.major, version.minor, version.patch);
+        match self {
+            BumpLevel::Patch => version.patch += 1,
+            BumpLevel::Minor => {
+                version.minor += 1;
+                version.patch = 0;
+            }
+        }
+        version
+    }
+}
+
+impl Default for BumpLevel {
+    fn default() -> Self {
+        BumpLevel::Patch
+    }
+}
+
 mod registries_impl {
     use super::*;
     use serde::{Deserializer, Serializer};
@@ -296,7 +902,7 @@ mod registries_impl {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use fixtures::json::*;
+    use fixtures::{json::*, temp_workspace::TempWorkspace};
 
     #[test]
     fn parse_registries() {
@@ -348,4 +954,341 @@ mod tests {
             "summary => serialized => summary roundtrip"
         );
     }
+
+    #[test]
+    fn parse_unify_only() {
+        static PARSE_UNIFY_ONLY_INPUT: &str = r#"
+        resolver = "2"
+        unify-only = ["syn", "quote"]
+        "#;
+
+        let summary: HakariBuilderSummary =
+            toml::from_str(PARSE_UNIFY_ONLY_INPUT).expect("failed to parse toml");
+        let builder = summary
+            .to_hakari_builder(JsonFixture::metadata_libra_9ffd93b().graph())
+            .expect("summary => builder conversion");
+        assert_eq!(
+            builder.unify_only().collect::<Vec<_>>(),
+            vec!["quote", "syn"],
+            "unify-only is correct"
+        );
+
+        let summary2 = builder.to_summary().expect("builder => summary conversion");
+        assert_eq!(summary, summary2, "summary => builder => summary roundtrip");
+    }
+
+    #[test]
+    fn parse_platform_final_excludes() {
+        static PARSE_PLATFORM_FINAL_EXCLUDES_INPUT: &str = r#"
+        resolver = "2"
+        platforms = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"]
+
+        [final-excludes.packages]
+        third-party = [{ name = "winapi" }]
+
+        [final-excludes.platform.'cfg(windows)']
+        third-party = [{ name = "datatest" }]
+        "#;
+
+        let summary: HakariBuilderSummary =
+            toml::from_str(PARSE_PLATFORM_FINAL_EXCLUDES_INPUT).expect("failed to parse toml");
+        let builder = summary
+            .to_hakari_builder(JsonFixture::metadata1().graph())
+            .expect("summary => builder conversion");
+        assert_eq!(builder.final_excludes().count(), 1, "one global exclude");
+        let platform_excludes: Vec<_> = builder
+            .platform_final_excludes()
+            .map(|(spec, excludes)| (spec, excludes.count()))
+            .collect();
+        assert_eq!(
+            platform_excludes,
+            vec![("cfg(windows)", 1)],
+            "one exclude for cfg(windows)"
+        );
+
+        let summary2 = builder.to_summary().expect("builder => summary conversion");
+        let serialized = toml::to_string(&summary2).expect("serialized to TOML correctly");
+        let summary3: HakariBuilderSummary =
+            toml::from_str(&serialized).expect("deserialized from TOML correctly");
+        assert_eq!(
+            summary2, summary3,
+            "summary => serialized => summary roundtrip"
+        );
+        assert_eq!(
+            summary3.final_excludes.platform.len(),
+            1,
+            "platform excludes are serialized"
+        );
+
+        static INVALID_SPEC_INPUT: &str = r#"
+        resolver = "2"
+
+        [final-excludes.platform.'cfg(']
+        third-party = [{ name = "datatest" }]
+        "#;
+        let summary: HakariBuilderSummary =
+            toml::from_str(INVALID_SPEC_INPUT).expect("failed to parse toml");
+        summary
+            .to_hakari_builder(JsonFixture::metadata1().graph())
+            .expect_err("invalid spec is rejected");
+
+        assert!(
+            serialized.contains("[[final-excludes.packages.ids]]"),
+            "packages are serialized in their own table: {}",
+            serialized
+        );
+    }
+
+    #[test]
+    fn parse_final_excludes_layouts() {
+        // The older layout, with packages specified directly in final-excludes.
+        static LEGACY_INPUT: &str = r#"
+        resolver = "2"
+
+        [final-excludes]
+        workspace-members = ["testcrate"]
+        third-party = [{ name = "winapi" }]
+        "#;
+        static CURRENT_INPUT: &str = r#"
+        resolver = "2"
+
+        [final-excludes.packages]
+        workspace-members = ["testcrate"]
+        third-party = [{ name = "winapi" }]
+        "#;
+        let legacy: HakariBuilderSummary =
+            toml::from_str(LEGACY_INPUT).expect("legacy layout parsed");
+        let current: HakariBuilderSummary =
+            toml::from_str(CURRENT_INPUT).expect("current layout parsed");
+        assert_eq!(legacy, current, "both layouts parse to the same summary");
+        assert_eq!(
+            current.final_excludes.packages.workspace_members.len(),
+            1,
+            "workspace member parsed"
+        );
+
+        // Without platform excludes, the older layout is written out so that existing configs
+        // round-trip unchanged.
+        let serialized = toml::to_string(&current).expect("serialized to TOML correctly");
+        assert!(
+            serialized.contains("[final-excludes]\n") && !serialized.contains("packages"),
+            "packages are serialized directly in final-excludes: {}",
+            serialized
+        );
+        let roundtrip: HakariBuilderSummary =
+            toml::from_str(&serialized).expect("deserialized from TOML correctly");
+        assert_eq!(
+            current, roundtrip,
+            "summary => serialized => summary roundtrip"
+        );
+
+        static UNKNOWN_KEY_INPUT: &str = r#"
+        resolver = "2"
+
+        [final-excludes]
+        workspace-members = ["testcrate"]
+        third-parties = [{ name = "winapi" }]
+        "#;
+        let err = toml::from_str::<HakariBuilderSummary>(UNKNOWN_KEY_INPUT)
+            .expect_err("unknown key is rejected");
+        assert!(
+            err.to_string().contains("unknown field `third-parties`"),
+            "error mentions the unknown key: {}",
+            err
+        );
+
+        static UNKNOWN_PACKAGES_KEY_INPUT: &str = r#"
+        resolver = "2"
+
+        [final-excludes.packages]
+        third-parties = [{ name = "winapi" }]
+        "#;
+        toml::from_str::<HakariBuilderSummary>(UNKNOWN_PACKAGES_KEY_INPUT)
+            .expect_err("unknown key in packages is rejected");
+
+        static MIXED_INPUT: &str = r#"
+        resolver = "2"
+
+        [final-excludes]
+        workspace-members = ["testcrate"]
+
+        [final-excludes.packages]
+        third-party = [{ name = "winapi" }]
+        "#;
+        let err = toml::from_str::<HakariBuilderSummary>(MIXED_INPUT)
+            .expect_err("mixing layouts is rejected");
+        assert!(
+            err.to_string().contains("not both"),
+            "error explains the conflict: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_manage_deps() {
+        static PARSE_MANAGE_DEPS_INPUT: &str = r#"
+        hakari-package = "workspace-hack"
+        resolver = "2"
+
+        [manage-deps]
+        include = ["crates/*"]
+        exclude = ["*-bench", "fuzz/**"]
+        "#;
+
+        let config: HakariConfig = PARSE_MANAGE_DEPS_INPUT
+            .parse()
+            .expect("failed to parse toml");
+        assert_eq!(config.manage_deps.include, vec!["crates/*"]);
+        assert_eq!(config.manage_deps.exclude, vec!["*-bench", "fuzz/**"]);
+
+        let config: HakariConfig = "resolver = \"2\"".parse().expect("failed to parse toml");
+        assert!(
+            config.manage_deps.is_empty(),
+            "manage-deps defaults to empty"
+        );
+    }
+
+    #[test]
+    fn parse_bump() {
+        static PARSE_BUMP_INPUT: &str = r#"
+        hakari-package = "workspace-hack"
+        resolver = "2"
+
+        [bump]
+        level = "minor"
+        tag-format = "hack-v{version}"
+        "#;
+
+        let config: HakariConfig = PARSE_BUMP_INPUT.parse().expect("failed to parse toml");
+        assert_eq!(config.bump.level, BumpLevel::Minor);
+        let version = Version::new(0, 1, 2);
+        assert_eq!(
+            config.bump.tag_name("workspace-hack", &version),
+            "hack-v0.1.2"
+        );
+        assert_eq!(config.bump.level.bump(&version), Version::new(0, 2, 0));
+        assert_eq!(BumpLevel::Patch.bump(&version), Version::new(0, 1, 3));
+
+        let config: HakariConfig = "resolver = \"2\"".parse().expect("failed to parse toml");
+        assert!(config.bump.is_default(), "bump defaults to default options");
+        assert_eq!(
+            config.bump.tag_name("workspace-hack", &version),
+            "workspace-hack-0.1.2"
+        );
+    }
+
+    #[test]
+    fn discover_platforms() {
+        static DISCOVER_PLATFORMS_INPUT: &str = r#"
+        resolver = "2"
+        platforms = ["x86_64-unknown-linux-gnu"]
+
+        [discover-platforms]
+        cargo-config = true
+        rust-toolchain = true
+        ci-globs = ["ci/*.yml"]
+        "#;
+
+        let workspace = TempWorkspace::inside_outside();
+        fs::create_dir_all(workspace.root().join(".cargo")).expect(".cargo created");
+        fs::create_dir_all(workspace.root().join("ci")).expect("ci created");
+        workspace.write(
+            ".cargo/config.toml",
+            "[build]\ntarget = \"aarch64-apple-darwin\"\n",
+        );
+        workspace.write(
+            "rust-toolchain.toml",
+            "[toolchain]\nchannel = \"stable\"\ntargets = [\"wasm32-wasi\", \"x86_64-unknown-linux-gnu\"]\n",
+        );
+        workspace.write(
+            "ci/build.yml",
+            "runs-on: ubuntu-latest\nsteps:\n  - run: cargo build --target=i686-pc-windows-msvc\n  - run: echo arm-based\n",
+        );
+
+        let mut config: HakariConfig = DISCOVER_PLATFORMS_INPUT
+            .parse()
+            .expect("failed to parse toml");
+        let serialized = config.builder.to_string().expect("summary serialized");
+        assert_eq!(
+            toml::from_str::<HakariBuilderSummary>(&serialized).expect("failed to parse toml"),
+            config.builder,
+            "discover-platforms round-trips"
+        );
+        config
+            .builder
+            .add_discovered_platforms(workspace.root())
+            .expect("platforms discovered");
+        assert_eq!(
+            config.builder.platforms,
+            vec![
+                "x86_64-unknown-linux-gnu",
+                "aarch64-apple-darwin",
+                "i686-pc-windows-msvc",
+                "wasm32-wasi",
+            ],
+            "discovered platforms are added after listed ones, without duplicates"
+        );
+
+        // A legacy rust-toolchain file with just a channel name has no targets.
+        fs::remove_file(workspace.root().join("rust-toolchain.toml"))
+            .expect("rust-toolchain.toml removed");
+        workspace.write("rust-toolchain", "stable\n");
+        // Cargo prefers .cargo/config over .cargo/config.toml.
+        workspace.write(
+            ".cargo/config",
+            "[build]\ntarget = [\"my-custom-target\"]\n",
+        );
+        let discover = &config.builder.discover_platforms;
+        match discover.discover(workspace.root()) {
+            Err(DiscoverPlatformsError::UnknownPlatform { path, .. }) => {
+                assert_eq!(path, workspace.root().join(".cargo/config"));
+            }
+            other => panic!("expected unknown platform error, found {:?}", other),
+        }
+
+        // Custom targets specified as JSON files are skipped.
+        workspace.write(
+            ".cargo/config",
+            "[build]\ntarget = [\"aarch64-apple-darwin\", \"targets/custom.json\"]\n",
+        );
+        assert_eq!(
+            discover
+                .discover(workspace.root())
+                .expect("platforms discovered"),
+            ["aarch64-apple-darwin", "i686-pc-windows-msvc"]
+                .iter()
+                .map(|triple| triple.to_string())
+                .collect(),
+        );
+
+        // A rust-toolchain file in TOML format with a syntax error isn't treated as a legacy file.
+        workspace.write(
+            "rust-toolchain",
+            "[toolchain]\ntargets = [\"wasm32-wasi\"\n",
+        );
+        match discover.discover(workspace.root()) {
+            Err(DiscoverPlatformsError::Toml { path, .. }) => {
+                assert_eq!(path, workspace.root().join("rust-toolchain"));
+            }
+            other => panic!("expected TOML error, found {:?}", other),
+        }
+        workspace.write("rust-toolchain", "nightly-2021-09-01\n");
+
+        fs::remove_file(workspace.root().join(".cargo/config")).expect(".cargo/config removed");
+        assert_eq!(
+            discover
+                .discover(workspace.root())
+                .expect("platforms discovered"),
+            ["aarch64-apple-darwin", "i686-pc-windows-msvc"]
+                .iter()
+                .map(|triple| triple.to_string())
+                .collect(),
+        );
+
+        let config: HakariConfig = "resolver = \"2\"".parse().expect("failed to parse toml");
+        assert!(
+            config.builder.discover_platforms.is_empty(),
+            "discover-platforms defaults to empty"
+        );
+    }
 }
@@ -6,7 +6,7 @@
 #[cfg(feature = "cli-support")]
 use crate::summaries::HakariBuilderSummary;
 use crate::{
-    hakari::{HakariBuilder, OutputMap},
+    hakari::{HakariBuilder, OutputKey, OutputMap, PatchedDeps},
     helpers::VersionDisplay,
 };
 use camino::Utf8PathBuf;
@@ -18,7 +18,7 @@ use guppy::{
 };
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     error, fmt,
     hash::{Hash, Hasher},
 };
@@ -29,7 +29,10 @@ use twox_hash::XxHash64;
 #[derive(Clone, Debug)]
 pub struct HakariOutputOptions {
     pub(crate) exact_versions: bool,
+    pub(crate) version_format: VersionFormat,
+    pub(crate) registry_annotations: bool,
     pub(crate) absolute_paths: bool,
+    pub(crate) per_platform: bool,
     #[cfg(feature = "cli-support")]
     pub(crate) builder_summary: bool,
 }
@@ -39,11 +42,17 @@ impl HakariOutputOptions {
     ///
     /// The default settings are:
     /// * do not output exact versions
+    /// * output caret version requirements
+    /// * do not annotate registry dependencies with their registry
+    /// * output platform-independent dependencies in a shared section
     /// * do not output a summary of builder options
     pub fn new() -> Self {
         Self {
             exact_versions: false,
+            version_format: VersionFormat::Caret,
+            registry_annotations: false,
             absolute_paths: false,
+            per_platform: false,
             #[cfg(feature = "cli-support")]
             builder_summary: false,
         }
@@ -69,6 +78,40 @@ impl HakariOutputOptions {
         self
     }
 
+    /// Sets the format of version requirements for registry dependencies.
+    ///
+    /// By default, Hakari outputs caret requirements, which Cargo treats as semver-compatible
+    /// ranges. With [`VersionFormat::Exact`] or [`VersionFormat::ExactWithBuildMetadata`], Hakari
+    /// instead pins each dependency to the version currently selected in `Cargo.lock`, for example:
+    ///
+    /// ```toml
+    /// serde = { version = "=1.0.130", features = ["derive"] }
+    /// ```
+    ///
+    /// Exact formats always output the full version, regardless of
+    /// [`set_exact_versions`](Self::set_exact_versions). The same caveats apply: if the
+    /// `Cargo.lock` file isn't checked in, Hakari's output will vary based on the repository it
+    /// is run in.
+    pub fn set_version_format(&mut self, version_format: VersionFormat) -> &mut Self {
+        self.version_format = version_format;
+        self
+    }
+
+    /// If set to true, annotates each registry dependency with a comment naming the registry its
+    /// version was resolved from, for example:
+    ///
+    /// ```toml
+    /// serde = { version = "1", features = ["derive"] } # crates-io
+    /// my-crate = { version = "0.2", registry = "my-registry" } # my-registry
+    /// ```
+    ///
+    /// This makes it easier to tell where pinned versions come from in workspaces that use
+    /// several registries.
+    pub fn set_registry_annotations(&mut self, registry_annotations: bool) -> &mut Self {
+        self.registry_annotations = registry_annotations;
+        self
+    }
+
     /// If set to true, outputs absolute paths for path dependencies.
     ///
     /// By default, `hakari` outputs relative paths, for example:
@@ -95,6 +138,35 @@ impl HakariOutputOptions {
         self
     }
 
+    /// If set to true, outputs a separate section for each platform set through
+    /// [`HakariBuilder::set_platforms`](HakariBuilder::set_platforms).
+    ///
+    /// By default, dependencies that are unified on every platform are output in a shared
+    /// `[dependencies]` section, and only the remainder is output in platform-specific
+    /// `[target.'triple'.dependencies]` sections. Since Cargo applies the shared section to every
+    /// platform, including ones not set through `set_platforms`, builds on those other platforms
+    /// pull in the same dependencies.
+    ///
+    /// If set to true, `hakari` instead outputs every dependency in a section keyed by each
+    /// platform it is unified on, for example:
+    ///
+    /// ```toml
+    /// [target.x86_64-unknown-linux-gnu.dependencies]
+    /// libc = { version = "0.2", features = ["std"] }
+    /// serde = { version = "1", features = ["derive"] }
+    ///
+    /// [target.x86_64-pc-windows-msvc.dependencies]
+    /// serde = { version = "1", features = ["derive"] }
+    /// ```
+    ///
+    /// Builds on platforms not set through `set_platforms` then don't unify any dependencies.
+    ///
+    /// This has no effect if no platforms are set.
+    pub fn set_per_platform(&mut self, per_platform: bool) -> &mut Self {
+        self.per_platform = per_platform;
+        self
+    }
+
     /// If set to true, outputs a summary of the builder options used to generate the `Hakari`, as
     /// TOML comments.
     ///
@@ -120,6 +192,40 @@ impl Default for HakariOutputOptions {
     }
 }
 
+/// The format of version requirements for registry dependencies in Hakari output.
+///
+/// Set through [`HakariOutputOptions::set_version_format`].
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "cli-support", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "cli-support", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum VersionFormat {
+    /// A caret requirement such as `1` or `0.4`, matching semver-compatible versions.
+    ///
+    /// The full version is output if [`HakariOutputOptions::set_exact_versions`] is set to true,
+    /// or if the version has a pre-release component.
+    ///
+    /// This is the default.
+    Caret,
+
+    /// An exact requirement such as `=1.0.130` or `=0.4.0-beta.1`, matching only the version in
+    /// use. Build metadata isn't output, since Cargo ignores it while matching versions.
+    Exact,
+
+    /// An exact requirement including build metadata, such as `=0.4.0+zstd.1.5.2`.
+    ///
+    /// Cargo ignores build metadata while matching versions, so this is useful to record which
+    /// build of a package is in use.
+    ExactWithBuildMetadata,
+}
+
+impl Default for VersionFormat {
+    #[inline]
+    fn default() -> Self {
+        VersionFormat::Caret
+    }
+}
+
 /// An error that occurred while writing out TOML.
 #[derive(Debug)]
 #[non_exhaustive]
@@ -287,6 +393,14 @@ pub(crate) fn write_toml(
         }
     }
 
+    let per_platform_map;
+    let output_map = if options.per_platform && !builder.platforms.is_empty() {
+        per_platform_map = make_per_platform_map(output_map, builder.platforms.len());
+        &per_platform_map
+    } else {
+        output_map
+    };
+
     let mut packages_by_name: HashMap<&str, HashSet<_>> = HashMap::new();
     for vals in output_map.values() {
         for (&package_id, (package, _)) in vals {
@@ -306,6 +420,10 @@ pub(crate) fn write_toml(
 
     let mut document = Document::new();
 
+    // Dependencies replaced through [patch] sections that are left out of the output, sorted by
+    // name and version.
+    let mut patched = BTreeMap::new();
+
     // Remove the leading newline from the first visual table to match what older versions of
     // hakari did.
     let mut first_element = true;
@@ -330,6 +448,11 @@ pub(crate) fn write_toml(
         }
 
         for (dep, all_features) in vals.values() {
+            if builder.patched_deps() == PatchedDeps::Warn && builder.is_patched(dep) {
+                patched.insert((dep.name(), dep.version(), dep.id()), dep.source());
+                continue;
+            }
+
             let mut itable = InlineTable::new();
 
             let name: Cow<str> = if packages_by_name[dep.name()].len() > 1 {
@@ -339,16 +462,15 @@ pub(crate) fn write_toml(
                 dep.name().into()
             };
 
+            let version_display = VersionDisplay::new(dep.version(), options.exact_versions)
+                .with_format(options.version_format);
+            // The registry this dependency was resolved from, if any.
+            let mut registry_annotation = None;
+
             let source = dep.source();
             if source.is_crates_io() {
-                itable.insert(
-                    "version",
-                    format!(
-                        "{}",
-                        VersionDisplay::new(dep.version(), options.exact_versions)
-                    )
-                    .into(),
-                );
+                itable.insert("version", format!("{}", version_display).into());
+                registry_annotation = Some("crates-io");
             } else {
                 match source {
                     PackageSource::Workspace(path) | PackageSource::Path(path) => {
@@ -412,15 +534,9 @@ pub(crate) fn write_toml(
                                     package_id: dep.id().clone(),
                                     registry_url: registry_url.to_owned(),
                                 })?;
-                            itable.insert(
-                                "version",
-                                format!(
-                                    "{}",
-                                    VersionDisplay::new(dep.version(), options.exact_versions)
-                                )
-                                .into(),
-                            );
+                            itable.insert("version", format!("{}", version_display).into());
                             itable.insert("registry", registry_name.into());
+                            registry_annotation = Some(registry_name);
                         }
                         _ => {
                             return Err(TomlOutError::UnrecognizedExternal {
@@ -452,7 +568,11 @@ pub(crate) fn write_toml(
 
             itable.fmt();
 
-            dep_table.insert(name.as_ref(), Item::Value(Value::InlineTable(itable)));
+            let mut value = Value::InlineTable(itable);
+            if let Some(registry) = registry_annotation.filter(|_| options.registry_annotations) {
+                value.decor_mut().set_suffix(format!(" # {}", registry));
+            }
+            dep_table.insert(name.as_ref(), Item::Value(value));
         }
     }
 
@@ -463,9 +583,49 @@ pub(crate) fn write_toml(
         writeln!(out)?;
     }
 
+    if !patched.is_empty() {
+        if !document.is_empty() {
+            writeln!(out)?;
+        }
+        writeln!(
+            out,
+            "# These dependencies are replaced through [patch] sections, and aren't unified:"
+        )?;
+        for ((name, version, _), source) in patched {
+            writeln!(out, "# {} {} ({})", name, version, source)?;
+        }
+    }
+
     Ok(())
 }
 
+/// Copies dependencies in the platform-independent sections into every platform-specific section,
+/// so that each platform's section lists exactly what's unified on it.
+fn make_per_platform_map<'g>(output_map: &OutputMap<'g>, platform_count: usize) -> OutputMap<'g> {
+    let mut per_platform_map = OutputMap::new();
+    for (key, vals) in output_map {
+        let platform_idxs = match key.platform_idx {
+            Some(idx) => idx..(idx + 1),
+            None => 0..platform_count,
+        };
+        for platform_idx in platform_idxs {
+            let new_key = OutputKey {
+                platform_idx: Some(platform_idx),
+                build_platform: key.build_platform,
+            };
+            let inner_map = per_platform_map.entry(new_key).or_default();
+            for (&package_id, (package, features)) in vals {
+                inner_map
+                    .entry(package_id)
+                    .or_insert_with(|| (*package, BTreeSet::new()))
+                    .1
+                    .extend(features.iter().copied());
+            }
+        }
+    }
+    per_platform_map
+}
+
 /// Generate a unique, stable package name from the metadata.
 fn make_hashed_name(dep: &PackageMetadata<'_>) -> String {
     // Use a fixed seed to ensure stable hashes.
@@ -522,6 +682,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn per_platform() {
+        let fixture = JsonFixture::metadata1();
+        let mut builder =
+            HakariBuilder::new(fixture.graph(), None).expect("builder initialization succeeded");
+        builder
+            .set_platforms(["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"])
+            .expect("valid platforms");
+        let hakari = builder.compute();
+
+        let mut output_options = HakariOutputOptions::new();
+        output_options.set_absolute_paths(true);
+        let output = hakari
+            .to_toml_string(&output_options)
+            .expect("shared output succeeded");
+        output_options.set_per_platform(true);
+        let per_platform_output = hakari
+            .to_toml_string(&output_options)
+            .expect("per-platform output succeeded");
+        assert!(
+            output.starts_with("[dependencies]\ndatatest = "),
+            "datatest is in the shared section, actual output:\n{}",
+            output
+        );
+        assert!(
+            !per_platform_output.contains("[dependencies]"),
+            "no shared section with per-platform output, actual output:\n{}",
+            per_platform_output
+        );
+        for triple in ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"] {
+            let section = format!("[target.{}.dependencies]\ndatatest = ", triple);
+            assert!(
+                per_platform_output.contains(&section),
+                "datatest is in the section for {}, actual output:\n{}",
+                triple,
+                per_platform_output
+            );
+        }
+        assert_eq!(
+            per_platform_output.matches("winapi = ").count(),
+            1,
+            "winapi is only unified on Windows, actual output:\n{}",
+            per_platform_output
+        );
+
+        // Without any platforms, there's nothing to split the shared section into.
+        let hakari = HakariBuilder::new(fixture.graph(), None)
+            .expect("builder initialization succeeded")
+            .compute();
+        let per_platform_output = hakari
+            .to_toml_string(&output_options)
+            .expect("per-platform output succeeded");
+        output_options.set_per_platform(false);
+        let output = hakari
+            .to_toml_string(&output_options)
+            .expect("shared output succeeded");
+        assert_eq!(
+            per_platform_output, output,
+            "per-platform has no effect without platforms"
+        );
+    }
+
+    #[test]
+    fn patched_deps() {
+        // In metadata2, quote is replaced with a local path through [patch].
+        let fixture = JsonFixture::metadata2();
+        let graph = fixture.graph();
+        let quote = graph
+            .metadata(&fixtures::package_id(METADATA2_QUOTE))
+            .expect("quote is present");
+        let mut builder =
+            HakariBuilder::new(graph, None).expect("builder initialization succeeded");
+        builder.set_output_single_feature(true);
+        assert!(builder.is_patched(&quote), "quote is patched");
+        assert!(
+            graph
+                .packages()
+                .filter(|package| builder.is_patched(package))
+                .all(|package| package.name() == "quote"),
+            "only quote is patched"
+        );
+
+        let mut output_options = HakariOutputOptions::new();
+        output_options.set_absolute_paths(true);
+        let output = builder
+            .clone()
+            .compute()
+            .to_toml_string(&output_options)
+            .expect("patched source output succeeded");
+        assert!(
+            output.contains("\nquote = { path = "),
+            "quote is output with its patched path, actual output:\n{}",
+            output
+        );
+
+        builder.set_patched_deps(PatchedDeps::Warn);
+        let output = builder
+            .compute()
+            .to_toml_string(&output_options)
+            .expect("patched warning output succeeded");
+        assert!(
+            !output.contains("\nquote = "),
+            "quote isn't output, actual output:\n{}",
+            output
+        );
+        assert!(
+            output.ends_with(
+                "# These dependencies are replaced through [patch] sections, and aren't unified:\n\
+                 # quote 1.0.2 (../quote)\n"
+            ),
+            "quote is listed as patched, actual output:\n{}",
+            output
+        );
+    }
+
     #[test]
     fn alternate_registries() {
         let fixture = JsonFixture::metadata_alternate_registries();
@@ -564,5 +839,28 @@ mod tests {
                 output
             );
         }
+
+        // Exact versions with registry annotations.
+        let mut output_options = HakariOutputOptions::new();
+        output_options
+            .set_version_format(VersionFormat::Exact)
+            .set_registry_annotations(true);
+        let output = hakari
+            .to_toml_string(&output_options)
+            .expect("alternate registry specified => success");
+
+        static EXACT_MATCH_STRINGS: &[&str] = &[
+            r#"serde_derive = { version = "=1.0.130", registry = "alt-registry" } # alt-registry"#,
+            r#"itoa = { version = "=0.4.8", default-features = false } # crates-io"#,
+        ];
+
+        for &needle in EXACT_MATCH_STRINGS {
+            assert!(
+                output.contains(needle),
+                "output did not contain string '{}', actual output follows:\n***\n{}\n",
+                needle,
+                output
+            );
+        }
     }
 }
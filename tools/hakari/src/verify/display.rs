@@ -51,6 +51,18 @@ impl<'g, 'verify> fmt::Display for VerifyErrorsDisplay<'g, 'verify> {
             write!(indented(f).with_str("  "), "{}", display)?;
         }
 
+        let fixes = self.verify.fixes();
+        if !fixes.is_empty() {
+            writeln!(
+                f,
+                "{}:",
+                "suggested fixes".style(self.styles.fixes_heading_style)
+            )?;
+            for fix in fixes {
+                writeln!(f, "  * {}", fix)?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -58,10 +70,12 @@ impl<'g, 'verify> fmt::Display for VerifyErrorsDisplay<'g, 'verify> {
 #[derive(Clone, Debug, Default)]
 struct Styles {
     dependency_id_style: Style,
+    fixes_heading_style: Style,
 }
 
 impl Styles {
     fn colorize(&mut self) {
         self.dependency_id_style = Style::new().bright_magenta();
+        self.fixes_heading_style = Style::new().bold();
     }
 }
@@ -22,8 +22,24 @@ mod display;
 pub use display::VerifyErrorsDisplay;
 
 use crate::{explain::HakariExplain, Hakari, HakariBuilder};
-use guppy::PackageId;
-use std::collections::BTreeSet;
+use guppy::{
+    graph::{ExternalSource, PackageMetadata},
+    PackageId,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// Platforms tried out by [`VerifyErrors::fixes`] when looking for platforms to add.
+///
+/// These are the Tier 1 platforms builds are most commonly performed on.
+pub const CANDIDATE_PLATFORMS: &[&str] = &[
+    "x86_64-unknown-linux-gnu",
+    "aarch64-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "x86_64-pc-windows-msvc",
+];
 
 impl<'g> HakariBuilder<'g> {
     /// Verify that `hakari` worked properly.
@@ -85,6 +101,94 @@ impl<'g> VerifyErrors<'g> {
             .map(move |id| HakariExplain::new(hakari, id).expect("package ID is from this graph"))
     }
 
+    /// Returns a list of suggested fixes for these errors.
+    ///
+    /// Fixes are found by re-running the builder in generate mode, both as configured and with
+    /// candidate changes to the configuration. A dependency is considered fixed by a change if
+    /// the workspace-hack would include it after that change. Dependencies for which no fix was
+    /// found are not mentioned.
+    ///
+    /// Changes are tried out in this order, and only the first one that applies to a dependency
+    /// is suggested:
+    ///
+    /// 1. naming the alternate registry the dependency is from
+    /// 2. regenerating the workspace-hack with the current configuration
+    /// 3. removing the dependency from the final excludes
+    /// 4. adding one of the [`CANDIDATE_PLATFORMS`] that aren't already specified
+    pub fn fixes(&self) -> Vec<VerifyFix<'g>> {
+        let builder = &self.hakari.builder;
+        let graph = builder.graph();
+        let mut remaining: BTreeSet<_> = self.dependency_ids.clone();
+        let mut fixes = vec![];
+
+        // Dependencies from unnamed alternate registries can't be written out at all.
+        let mut by_registry: BTreeMap<&'g str, Vec<PackageMetadata<'g>>> = BTreeMap::new();
+        remaining.retain(|&package_id| {
+            let package = graph.metadata(package_id).expect("valid package ID");
+            match unnamed_registry(builder, package) {
+                Some(registry_url) => {
+                    by_registry.entry(registry_url).or_default().push(package);
+                    false
+                }
+                None => true,
+            }
+        });
+        fixes.extend(by_registry.into_iter().map(|(registry_url, dependencies)| {
+            VerifyFix::AddRegistry {
+                registry_url,
+                dependencies,
+            }
+        }));
+
+        let regenerated = generate_with(builder, |_| {});
+        let dependencies = take_covered(&mut remaining, &regenerated);
+        if !dependencies.is_empty() {
+            fixes.push(VerifyFix::Regenerate {
+                dependencies: to_metadatas(builder, dependencies),
+            });
+        }
+
+        for package_id in remaining.clone() {
+            if !builder.final_excludes.contains(package_id) {
+                continue;
+            }
+            let without_exclude = generate_with(builder, |builder| {
+                builder.final_excludes.remove(package_id);
+            });
+            if is_covered(package_id, &without_exclude) {
+                remaining.remove(package_id);
+                fixes.push(VerifyFix::RemoveFinalExclude {
+                    package: graph.metadata(package_id).expect("valid package ID"),
+                });
+            }
+        }
+
+        for &triple in CANDIDATE_PLATFORMS {
+            if remaining.is_empty() {
+                break;
+            }
+            if builder.platforms().any(|platform| platform == triple) {
+                continue;
+            }
+            let mut platforms: Vec<String> = builder.platforms().map(|p| p.to_owned()).collect();
+            platforms.push(triple.to_owned());
+            let with_platform = generate_with(builder, |builder| {
+                builder
+                    .set_platforms(platforms)
+                    .expect("candidate platforms are known");
+            });
+            let dependencies = take_covered(&mut remaining, &with_platform);
+            if !dependencies.is_empty() {
+                fixes.push(VerifyFix::AddPlatform {
+                    triple,
+                    dependencies: to_metadatas(builder, dependencies),
+                });
+            }
+        }
+
+        fixes
+    }
+
     /// Returns a displayer for this instance.
     #[cfg(feature = "cli-support")]
     pub fn display<'verify>(&'verify self) -> VerifyErrorsDisplay<'g, 'verify> {
@@ -92,6 +196,161 @@ impl<'g> VerifyErrors<'g> {
     }
 }
 
+/// A change that fixes some of the errors returned by [`HakariBuilder::verify`].
+///
+/// Returned by [`VerifyErrors::fixes`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum VerifyFix<'g> {
+    /// The dependencies are from an alternate registry that isn't named in the configuration, so
+    /// they can't be written out to the workspace-hack.
+    AddRegistry {
+        /// The URL of the registry.
+        registry_url: &'g str,
+        /// The dependencies from this registry.
+        dependencies: Vec<PackageMetadata<'g>>,
+    },
+
+    /// Regenerating the workspace-hack would unify these dependencies: it is out of date.
+    Regenerate {
+        /// The dependencies that would be unified.
+        dependencies: Vec<PackageMetadata<'g>>,
+    },
+
+    /// The dependency is excluded from the final output. Removing it from the final excludes
+    /// would unify it.
+    RemoveFinalExclude {
+        /// The dependency that is excluded.
+        package: PackageMetadata<'g>,
+    },
+
+    /// Adding this platform would unify these dependencies.
+    AddPlatform {
+        /// The target triple for the platform.
+        triple: &'static str,
+        /// The dependencies that would be unified.
+        dependencies: Vec<PackageMetadata<'g>>,
+    },
+}
+
+impl<'g> fmt::Display for VerifyFix<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyFix::AddRegistry {
+                registry_url,
+                dependencies,
+            } => write!(
+                f,
+                "name the registry at {} in the registries section ({})",
+                registry_url,
+                DisplayDependencies(dependencies),
+            ),
+            VerifyFix::Regenerate { dependencies } => write!(
+                f,
+                "regenerate the workspace-hack ({})",
+                DisplayDependencies(dependencies),
+            ),
+            VerifyFix::RemoveFinalExclude { package } => write!(
+                f,
+                "remove {} {} from final-excludes",
+                package.name(),
+                package.version(),
+            ),
+            VerifyFix::AddPlatform {
+                triple,
+                dependencies,
+            } => write!(
+                f,
+                "add platform {} ({})",
+                triple,
+                DisplayDependencies(dependencies),
+            ),
+        }
+    }
+}
+
+struct DisplayDependencies<'a, 'g>(&'a [PackageMetadata<'g>]);
+
+impl<'a, 'g> fmt::Display for DisplayDependencies<'a, 'g> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, package) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", package.name(), package.version())?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes a `Hakari` in generate mode, after applying `tweak_fn` to a copy of `builder`.
+fn generate_with<'g>(
+    builder: &HakariBuilder<'g>,
+    tweak_fn: impl FnOnce(&mut HakariBuilder<'g>),
+) -> Hakari<'g> {
+    let mut builder = builder.clone();
+    builder.verify_mode = false;
+    tweak_fn(&mut builder);
+    builder.compute()
+}
+
+fn is_covered(package_id: &PackageId, hakari: &Hakari<'_>) -> bool {
+    hakari
+        .output_map
+        .values()
+        .any(|inner_map| inner_map.contains_key(package_id))
+}
+
+/// Removes and returns the package IDs in `remaining` that the workspace-hack for `hakari` would
+/// include.
+fn take_covered<'g>(
+    remaining: &mut BTreeSet<&'g PackageId>,
+    hakari: &Hakari<'g>,
+) -> Vec<&'g PackageId> {
+    let covered: Vec<_> = remaining
+        .iter()
+        .copied()
+        .filter(|package_id| is_covered(package_id, hakari))
+        .collect();
+    for package_id in &covered {
+        remaining.remove(package_id);
+    }
+    covered
+}
+
+fn to_metadatas<'g>(
+    builder: &HakariBuilder<'g>,
+    package_ids: Vec<&'g PackageId>,
+) -> Vec<PackageMetadata<'g>> {
+    package_ids
+        .into_iter()
+        .map(|package_id| {
+            builder
+                .graph()
+                .metadata(package_id)
+                .expect("valid package ID")
+        })
+        .collect()
+}
+
+fn unnamed_registry<'g>(
+    builder: &HakariBuilder<'g>,
+    package: PackageMetadata<'g>,
+) -> Option<&'g str> {
+    let source = package.source();
+    if source.is_crates_io() {
+        return None;
+    }
+    match source.parse_external() {
+        Some(ExternalSource::Registry(registry_url))
+            if !builder.registries.contains_right(registry_url) =>
+        {
+            Some(registry_url)
+        }
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 #[cfg(feature = "cli-support")]
 mod cli_support_tests {
@@ -121,3 +380,60 @@ mod cli_support_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::*;
+    use guppy::graph::DependencyDirection;
+
+    #[test]
+    fn fixes() {
+        let graph = JsonFixture::metadata1().graph();
+        let builder = HakariBuilder::new(graph, None).expect("builder initialization succeeded");
+        let errs = builder
+            .verify()
+            .expect_err("no hakari package => verify fails");
+        let fixes = errs.fixes();
+        assert_eq!(fixes.len(), 1, "one fix suggested");
+        match &fixes[0] {
+            VerifyFix::Regenerate { dependencies } => {
+                let names: Vec<_> = dependencies.iter().map(|package| package.name()).collect();
+                assert_eq!(names, vec!["datatest"], "regenerating unifies datatest");
+            }
+            other => panic!("expected regenerate, got {:?}", other),
+        }
+
+        // Excluding a package that verification fails on should suggest removing the exclude.
+        let graph = JsonFixture::metadata_libra().graph();
+        let mut builder =
+            HakariBuilder::new(graph, None).expect("builder initialization succeeded");
+        builder
+            .add_final_excludes(
+                graph
+                    .resolve_package_name("backtrace")
+                    .package_ids(DependencyDirection::Forward),
+            )
+            .expect("backtrace is a known package");
+        let errs = builder
+            .verify()
+            .expect_err("no hakari package => verify fails");
+        let fixes = errs.fixes();
+        let mut remove_excludes = vec![];
+        for fix in &fixes {
+            match fix {
+                VerifyFix::Regenerate { dependencies } => {
+                    assert!(
+                        dependencies
+                            .iter()
+                            .all(|package| package.name() != "backtrace"),
+                        "regenerating doesn't unify backtrace"
+                    );
+                }
+                VerifyFix::RemoveFinalExclude { package } => remove_excludes.push(package.name()),
+                other => panic!("unexpected fix {:?}", other),
+            }
+        }
+        assert_eq!(remove_excludes, vec!["backtrace"], "excluded backtrace");
+    }
+}